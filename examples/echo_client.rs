@@ -26,6 +26,42 @@ fn main() {
 
                     println!("received \"{}\"", packet_data_utf8);
                 }
+                uflow::client::Event::CadenceChange(cadence) => {
+                    println!("server cadence: {:?}", cadence);
+                }
+                uflow::client::Event::SendFailed(_) => {
+                    println!("packet not sent");
+                }
+                uflow::client::Event::WindowLimited(usage) => {
+                    println!("window limited: {:?}", usage);
+                }
+                uflow::client::Event::StateChange(state) => {
+                    println!("connection state: {:?}", state);
+                }
+                uflow::client::Event::QualityChange(score) => {
+                    println!("connection quality: {}", score);
+                }
+                uflow::client::Event::CustomFrame(type_id, frame_data) => {
+                    println!("custom frame {}: {:?}", type_id, frame_data);
+                }
+                uflow::client::Event::Backpressure(channel_id, signal) => {
+                    println!("channel {} backpressure: {:?}", channel_id, signal);
+                }
+                uflow::client::Event::Heartbeat(data) => {
+                    println!("heartbeat: {:?}", data);
+                }
+                uflow::client::Event::Summary(summary) => {
+                    println!("session summary: {:?}", summary);
+                }
+                uflow::client::Event::Trace(trace_id, outcome) => {
+                    println!("traced packet {}: {:?}", trace_id, outcome);
+                }
+                uflow::client::Event::Delivered(channel_id, tag) => {
+                    println!("packet {} delivered on channel {}", tag, channel_id);
+                }
+                uflow::client::Event::Reset(drop_count) => {
+                    println!("connection reset ({} packets dropped)", drop_count);
+                }
             }
         }
 
@@ -9,22 +9,28 @@ fn main() {
         // Process inbound UDP frames and handle events
         for event in server.step() {
             match event {
-                uflow::server::Event::Connect(client_address) => {
-                    println!("[{:?}] connected", client_address);
+                uflow::server::Event::Connect(client_id) => {
+                    println!("[{:?}] connected", client_id);
                 }
-                uflow::server::Event::Disconnect(client_address) => {
-                    println!("[{:?}] disconnected", client_address);
+                uflow::server::Event::Disconnect(client_id) => {
+                    println!("[{:?}] disconnected", client_id);
                 }
-                uflow::server::Event::Error(client_address, err) => {
-                    println!("[{:?}] error: {:?}", client_address, err);
+                uflow::server::Event::Error(client_id, err) => {
+                    println!("[{:?}] error: {:?}", client_id, err);
                 }
-                uflow::server::Event::Receive(client_address, packet_data) => {
+                uflow::server::Event::HandshakeError(client_address, err) => {
+                    println!("[{:?}] handshake error: {:?}", client_address, err);
+                }
+                uflow::server::Event::ConnectionRefused(client_address, reason) => {
+                    println!("[{:?}] connection refused: {:?}", client_address, reason);
+                }
+                uflow::server::Event::Receive(client_id, packet_data) => {
                     let packet_data_utf8 = std::str::from_utf8(&packet_data).unwrap();
                     let reversed_string: std::string::String = packet_data_utf8.chars().rev().collect();
 
-                    println!("[{:?}] received \"{}\"", client_address, packet_data_utf8);
+                    println!("[{:?}] received \"{}\"", client_id, packet_data_utf8);
 
-                    let mut client = server.client(&client_address).unwrap().borrow_mut();
+                    let mut client = server.client(client_id).unwrap().borrow_mut();
 
                     // Echo the packet reliably on channel 0
                     client.send(packet_data, 0, uflow::SendMode::Reliable);
@@ -32,6 +38,42 @@ fn main() {
                     // Echo the reverse of the packet unreliably on channel 1
                     client.send(reversed_string.as_bytes().into(), 1, uflow::SendMode::Unreliable);
                 }
+                uflow::server::Event::Migrate(client_id, old_address, new_address) => {
+                    println!("[{:?}] migrated from {:?} to {:?}", client_id, old_address, new_address);
+                }
+                uflow::server::Event::CadenceChange(client_id, cadence) => {
+                    println!("[{:?}] cadence: {:?}", client_id, cadence);
+                }
+                uflow::server::Event::WindowLimited(client_id, usage) => {
+                    println!("[{:?}] window limited: {:?}", client_id, usage);
+                }
+                uflow::server::Event::StateChange(client_id, state) => {
+                    println!("[{:?}] connection state: {:?}", client_id, state);
+                }
+                uflow::server::Event::QualityChange(client_id, score) => {
+                    println!("[{:?}] connection quality: {}", client_id, score);
+                }
+                uflow::server::Event::CustomFrame(client_id, type_id, frame_data) => {
+                    println!("[{:?}] custom frame {}: {:?}", client_id, type_id, frame_data);
+                }
+                uflow::server::Event::Backpressure(client_id, channel_id, signal) => {
+                    println!("[{:?}] channel {} backpressure: {:?}", client_id, channel_id, signal);
+                }
+                uflow::server::Event::Heartbeat(client_id, data) => {
+                    println!("[{:?}] heartbeat: {:?}", client_id, data);
+                }
+                uflow::server::Event::Summary(client_id, summary) => {
+                    println!("[{:?}] session summary: {:?}", client_id, summary);
+                }
+                uflow::server::Event::Trace(client_id, trace_id, outcome) => {
+                    println!("[{:?}] traced packet {}: {:?}", client_id, trace_id, outcome);
+                }
+                uflow::server::Event::Delivered(client_id, channel_id, tag) => {
+                    println!("[{:?}] packet {} delivered on channel {}", client_id, tag, channel_id);
+                }
+                uflow::server::Event::Reset(client_id, drop_count) => {
+                    println!("[{:?}] connection reset ({} packets dropped)", client_id, drop_count);
+                }
             }
         }
 
@@ -0,0 +1,28 @@
+#!/bin/sh
+# Checks that the library builds under every combination of its optional features.
+
+set -e
+
+cd "$(dirname "$0")/.."
+
+FEATURES="server stats encryption parallel cpu-accounting ffi"
+
+combinations() {
+    if [ $# -eq 0 ]; then
+        echo ""
+        return
+    fi
+
+    first=$1
+    shift
+
+    combinations "$@" | while read -r rest; do
+        echo "$rest"
+        echo "$first${rest:+,$rest}"
+    done
+}
+
+combinations $FEATURES | while read -r features; do
+    echo "Checking features: [${features}]"
+    cargo check --quiet --lib --no-default-features --features "$features"
+done
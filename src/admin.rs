@@ -0,0 +1,248 @@
+
+use crate::server::Server;
+use crate::Transport;
+use crate::LinkStats;
+
+use std::fmt::Write as _;
+use std::io::Read;
+use std::io::Write;
+use std::net;
+use std::time;
+
+// Time allowed for an admin client to send its request and receive its reply
+const REQUEST_TIMEOUT_MS: u64 = 1000;
+
+// The maximum number of connections accepted by each call to serve()
+const MAX_ACCEPTS_PER_SERVE: usize = 4;
+
+// The maximum number of connections whose requests are outstanding at once
+const MAX_CONNECTIONS: usize = 16;
+
+// Only the request line is of interest
+const MAX_REQUEST_SIZE: usize = 1024;
+
+enum ConnectionState {
+    // Buffering the request
+    Reading(Vec<u8>),
+    // Sending the reply, of which the given number of bytes have been written
+    Writing(Box<[u8]>, usize),
+}
+
+struct Connection {
+    stream: net::TcpStream,
+    state: ConnectionState,
+    timeout_time: time::Instant,
+}
+
+/// Serves a read-only snapshot of a [`Server`]'s connections over HTTP, so that the transport
+/// layer of a live server may be inspected without adding debug code to the application.
+///
+/// The endpoint does not spawn any threads. Instead, the application calls
+/// [`serve()`](Self::serve) periodically (e.g. once per server update), which makes progress on
+/// any pending requests. Every `GET` request is answered with the JSON document produced by
+/// [`snapshot_json()`].
+///
+/// *Note*: Requests are never waited upon: partial requests are buffered until the next call to
+/// `serve()`, only a few connections are accepted per call, and connections which have not
+/// completed within one second are closed. The endpoint should nonetheless be bound to a local or
+/// otherwise trusted address.
+///
+/// ```
+/// let mut server = uflow::server::Server::bind("127.0.0.1:0", Default::default()).unwrap();
+/// let mut admin = uflow::admin::AdminEndpoint::bind("127.0.0.1:0").unwrap();
+///
+/// loop {
+///     for event in server.step() {
+///         // ...
+///     }
+///
+///     // Reply to any pending admin requests
+///     admin.serve(&server);
+///
+///     server.flush();
+/// #   break;
+/// }
+/// ```
+pub struct AdminEndpoint {
+    listener: net::TcpListener,
+    connections: Vec<Connection>,
+}
+
+impl AdminEndpoint {
+    /// Opens a non-blocking TCP listener bound to the given address.
+    pub fn bind<A: net::ToSocketAddrs>(addr: A) -> Result<Self, std::io::Error> {
+        let listener = net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self { listener, connections: Vec::new() })
+    }
+
+    /// Returns the local address of the internal TCP listener.
+    pub fn local_address(&self) -> net::SocketAddr {
+        self.listener.local_addr().unwrap()
+    }
+
+    /// Accepts pending connections, and reads requests from and writes replies to each as far as
+    /// possible without blocking. Replies contain a snapshot of the given server's connections at
+    /// the time the request was completed.
+    pub fn serve<T: Transport>(&mut self, server: &Server<T>) {
+        let now = time::Instant::now();
+
+        for _ in 0 .. MAX_ACCEPTS_PER_SERVE {
+            if self.connections.len() >= MAX_CONNECTIONS {
+                break;
+            }
+
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    if stream.set_nonblocking(true).is_ok() {
+                        self.connections.push(Connection {
+                            stream,
+                            state: ConnectionState::Reading(Vec::new()),
+                            timeout_time: now + time::Duration::from_millis(REQUEST_TIMEOUT_MS),
+                        });
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+
+        self.connections.retain_mut(|connection| {
+            now < connection.timeout_time && Self::advance(connection, server).unwrap_or(false)
+        });
+    }
+
+    // Makes as much progress on a connection as possible, returning true if it remains open
+    fn advance<T: Transport>(connection: &mut Connection, server: &Server<T>) -> Result<bool, std::io::Error> {
+        if let ConnectionState::Reading(ref mut request) = connection.state {
+            let mut buf = [0; MAX_REQUEST_SIZE];
+
+            while request.len() < MAX_REQUEST_SIZE && !request.contains(&b'\n') {
+                match connection.stream.read(&mut buf[.. MAX_REQUEST_SIZE - request.len()]) {
+                    Ok(0) => break,
+                    Ok(n) => request.extend_from_slice(&buf[.. n]),
+                    Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => return Ok(true),
+                    Err(err) => return Err(err),
+                }
+            }
+
+            connection.state = ConnectionState::Writing(Self::response(request, server), 0);
+        }
+
+        if let ConnectionState::Writing(ref response, ref mut written) = connection.state {
+            while *written < response.len() {
+                match connection.stream.write(&response[*written ..]) {
+                    Ok(0) => return Ok(false),
+                    Ok(n) => *written += n,
+                    Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => return Ok(true),
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn response<T: Transport>(request: &[u8], server: &Server<T>) -> Box<[u8]> {
+        let response = if request.starts_with(b"GET ") {
+            let body = snapshot_json(server);
+
+            format!("HTTP/1.0 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(), body)
+        } else {
+            "HTTP/1.0 405 Method Not Allowed\r\nContent-Length: 0\r\n\r\n".to_string()
+        };
+
+        response.into_bytes().into_boxed_slice()
+    }
+}
+
+fn write_f64(out: &mut String, value: Option<f64>) {
+    match value {
+        Some(value) if value.is_finite() => { let _ = write!(out, "{}", value); }
+        _ => out.push_str("null"),
+    }
+}
+
+fn write_link_stats(out: &mut String, stats: &LinkStats) {
+    out.push_str("{\"rtt_s\":");
+    write_f64(out, stats.rtt_s);
+    let _ = write!(out,
+        ",\"send_buffer_size\":{},\"frames_in_flight\":{},\"stale_drop_count\":{},\"stale_drop_bytes\":{},\
+         \"duplicate_frames\":{},\"duplicate_datagrams\":{},\"duplicate_ack_groups\":{},\"expired_frames\":{},\
+         \"send_blocked_count\":{},\"window_limited_count\":{},\"max_ack_delay_ms\":{}}}",
+        stats.send_buffer_size,
+        stats.frames_in_flight,
+        stats.stale_drop_count,
+        stats.stale_drop_bytes,
+        stats.duplicate_frames,
+        stats.duplicate_datagrams,
+        stats.duplicate_ack_groups,
+        stats.expired_frames,
+        stats.send_blocked_count,
+        stats.window_limited_count,
+        stats.max_ack_delay_ms);
+}
+
+/// Returns a JSON document describing the given server and each of its connections, ordered by
+/// address. Connections which are not active report default statistics.
+///
+/// The document has the following form:
+///
+/// ```text
+/// {
+///   "address": "0.0.0.0:8888",
+///   "unknown_address_frames": 0,
+///   "half_open_evictions": 0,
+///   "connections": [
+///     {
+///       "address": "127.0.0.1:50000",
+///       "state": "active",
+///       "stats": { "rtt_s": 0.05, "send_buffer_size": 0, "frames_in_flight": 0, ... }
+///     }
+///   ]
+/// }
+/// ```
+pub fn snapshot_json<T: Transport>(server: &Server<T>) -> String {
+    let mut clients = server.clients().map(|client_rc| client_rc.borrow()).collect::<Vec<_>>();
+    clients.sort_by_key(|client| client.address());
+
+    let mut out = String::new();
+
+    let _ = write!(out, "{{\"address\":\"{}\",\"unknown_address_frames\":{},\"half_open_evictions\":{},\"connections\":[",
+                   server.address(),
+                   server.unknown_address_stats().frames,
+                   server.half_open_eviction_count());
+
+    for (idx, client) in clients.iter().enumerate() {
+        if idx > 0 {
+            out.push(',');
+        }
+
+        let _ = write!(out, "{{\"address\":\"{}\",\"state\":\"{}\",\"stats\":", client.address(), client.state_name());
+        write_link_stats(&mut out, &client.link_stats());
+        out.push('}');
+    }
+
+    out.push_str("]}");
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_finite_values() {
+        let mut out = String::new();
+
+        write_f64(&mut out, Some(0.5));
+        out.push(',');
+        write_f64(&mut out, Some(f64::NAN));
+        out.push(',');
+        write_f64(&mut out, None);
+
+        assert_eq!(out, "0.5,null,null");
+    }
+}
@@ -1,43 +1,187 @@
 use std::net;
+use std::sync::Arc;
 use std::time;
 
+use crate::ArrivalCadence;
+use crate::Backpressure;
 use crate::CHANNEL_COUNT;
+use crate::MAX_CHANNEL_COUNT;
+use crate::ChannelStats;
+use crate::Clock;
+use crate::SystemClock;
+use crate::CloseReason;
+use crate::ConnectionState;
+use crate::CUSTOM_FRAME_TYPE_COUNT;
+use crate::DatagramTransform;
 use crate::EndpointConfig;
+use crate::FrameAction;
+use crate::FrameHook;
+use crate::HeartbeatProvider;
+use crate::LatencyClass;
 use crate::frame::serial::Serialize;
 use crate::frame;
 use crate::half_connection;
+use crate::logger;
+use crate::LogLevel;
+use crate::Logger;
+use crate::LinkStats;
+use crate::MAX_CONNECT_TOKEN_SIZE;
+use crate::MAX_CUSTOM_FRAME_SIZE;
 use crate::MAX_FRAME_SIZE;
-use crate::MAX_FRAME_WINDOW_SIZE;
-use crate::MAX_PACKET_WINDOW_SIZE;
+use crate::MAX_TRANSFORM_OVERHEAD;
+use crate::Pacer;
+use crate::MAX_GROUP_NAME_SIZE;
+use crate::MAX_GROUP_SUBSCRIPTIONS;
+use crate::MAX_PATHS;
+use crate::PathStats;
 use crate::PROTOCOL_VERSION;
+use crate::QueueAction;
+use crate::QueuedPacket;
+use crate::ReorderLimits;
+use crate::SendCongested;
+use crate::SendHandle;
 use crate::SendMode;
-use crate::udp_frame_sink::UdpFrameSink;
-
-static HANDSHAKE_RESEND_INTERVAL_MS: u64 = 2000;
-static HANDSHAKE_RESEND_COUNT: u8 = 10;
+use crate::SendOptions;
+use crate::SessionSummary;
+use crate::TraceOutcome;
+use crate::Watermarks;
+#[cfg(feature = "stats")]
+use crate::StatsHistory;
+#[cfg(feature = "encryption")]
+use crate::key_exchange;
+use crate::seal;
+use crate::seal::Sealed;
+use crate::Transport;
+use crate::WindowUsage;
+
+mod multiplexer;
+mod paths;
+
+pub use multiplexer::Multiplexer;
+pub use multiplexer::MultiplexedClient;
 
 static DISCONNECT_RESEND_INTERVAL_MS: u64 = 2000;
 static DISCONNECT_RESEND_COUNT: u8 = 10;
 
 static CLOSED_TIMEOUT_MS: u64 = 20000;
 
+static GROUP_RESEND_INTERVAL_MS: u64 = 250;
+
+// Longest interval between steps while blocked in flush_and_close(), so that acknowledgements are
+// processed promptly
+static FLUSH_POLL_INTERVAL: time::Duration = time::Duration::from_millis(5);
+
 /// Stores configuration parameters for a [`Client`](Client) object.
 pub struct Config {
     /// Endpoint configuration to use for outbound server connections.
     pub endpoint_config: EndpointConfig,
+    /// The time to wait before first resending a connection request, in milliseconds. The interval
+    /// doubles after each subsequent resend, up to `handshake_max_resend_interval_ms`.
+    pub handshake_resend_interval_ms: u64,
+    /// The maximum time to wait between connection requests, in milliseconds.
+    pub handshake_max_resend_interval_ms: u64,
+    /// The number of times a connection request will be resent before the connection attempt is
+    /// abandoned.
+    pub handshake_resend_count: u8,
+    /// Opaque application data sent with each connection request, if set, e.g. a matchmaking token
+    /// to be validated by the server (see [`ConnectValidator`](crate::ConnectValidator)). May be
+    /// no larger than [`MAX_CONNECT_TOKEN_SIZE`](crate::MAX_CONNECT_TOKEN_SIZE).
+    ///
+    /// *Note*: Connection requests are not encrypted, so the token may be observed by anyone on
+    /// the path to the server.
+    pub connect_token: Option<Box<[u8]>>,
+    /// Receives warnings and diagnostic messages concerning the connection, if set (see
+    /// [`Logger`]).
+    pub logger: Option<Box<dyn Logger>>,
+    /// Observes raw frames received from the server before they are processed, if set (see
+    /// [`FrameHook`]).
+    pub frame_hook: Option<FrameHook>,
+    /// Supplies payloads for keepalive frames sent to the server, if set (see
+    /// [`HeartbeatProvider`]).
+    pub heartbeat_provider: Option<HeartbeatProvider>,
+    /// Limits the data sent by each flush, if set (see [`Pacer`]).
+    pub pacer: Option<Box<dyn Pacer>>,
+    /// Transforms the payload of each datagram exchanged with the server, if set (see
+    /// [`DatagramTransform`]). The server must supply a matching transform for this client via
+    /// [`server::Config::transform_selector`](crate::server::Config::transform_selector).
+    pub transform: Option<Box<dyn DatagramTransform>>,
+    /// The source of time for all connection timers (see [`Clock`]).
+    pub clock: Arc<dyn Clock>,
+    /// Whether session keys should be agreed upon with the server during the handshake. If
+    /// enabled, all frames exchanged after the server's reply are sealed using keys known only to
+    /// this client and the server, and the connection fails with [`ErrorType::KeyExchange`] if the
+    /// server does not complete the exchange (see
+    /// [`server::Config::static_key`](crate::server::Config::static_key)).
+    ///
+    /// Any [`session_keys`](EndpointConfig#structfield.session_keys) continue to seal the
+    /// connection request and the server's reply.
+    #[cfg(feature = "encryption")]
+    pub key_exchange: bool,
+    /// The public key expected of the server during a key exchange, if known. Unless set, the
+    /// client accepts any server key, so that the exchange protects against eavesdroppers but not
+    /// against an attacker who impersonates the server. The key presented by the server may be
+    /// queried via [`Client::server_public_key()`].
+    ///
+    /// Requires `key_exchange` to be enabled.
+    #[cfg(feature = "encryption")]
+    pub server_public_key: Option<[u8; 32]>,
 }
 
 impl Config {
     /// Returns true if the given configuration is valid.
     pub fn is_valid(&self) -> bool {
-        self.endpoint_config.is_valid()
+        self.handshake_resend_interval_ms > 0
+            && self.handshake_max_resend_interval_ms >= self.handshake_resend_interval_ms
+            && self.connect_token.as_ref().map_or(true, |token| token.len() <= MAX_CONNECT_TOKEN_SIZE)
+            && self.transform.as_ref().map_or(true, |transform| transform.overhead() <= MAX_TRANSFORM_OVERHEAD)
+            && self.key_exchange_valid()
+            && self.endpoint_config.is_valid()
+    }
+
+    #[cfg(feature = "encryption")]
+    fn key_exchange_valid(&self) -> bool {
+        self.key_exchange || self.server_public_key.is_none()
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn key_exchange_valid(&self) -> bool {
+        true
     }
 }
 
 impl Default for Config {
+    /// Creates a client configuration with the following parameters:
+    ///   * Handshake resend interval: 1s
+    ///   * Maximum handshake resend interval: 4s
+    ///   * Handshake resend count: 5
+    ///   * Connect token: None
+    ///   * Logger: None
+    ///   * Frame hook: None
+    ///   * Heartbeat provider: None
+    ///   * Pacer: None
+    ///   * Transform: None
+    ///   * Clock: [`SystemClock`]
+    ///   * Key exchange: Disabled
+    ///   * Server public key: None
+    ///
+    /// With these values, a connection attempt which receives no reply is abandoned after 19s.
     fn default() -> Self {
         Self {
             endpoint_config: Default::default(),
+            handshake_resend_interval_ms: 1000,
+            handshake_max_resend_interval_ms: 4000,
+            handshake_resend_count: 5,
+            connect_token: None,
+            logger: None,
+            frame_hook: None,
+            heartbeat_provider: None,
+            pacer: None,
+            transform: None,
+            clock: Arc::new(SystemClock::new()),
+            #[cfg(feature = "encryption")]
+            key_exchange: false,
+            #[cfg(feature = "encryption")]
+            server_public_key: None,
         }
     }
 }
@@ -45,8 +189,14 @@ impl Default for Config {
 /// Represents a connection error.
 #[derive(Debug,PartialEq)]
 pub enum ErrorType {
-    /// Indicates that an active connection or connection handshake has timed out.
+    /// Indicates that an active connection has timed out.
     Timeout,
+    /// Indicates that the server did not respond to any connection request.
+    ConnectTimeout,
+    /// Indicates that the server's host refused the final connection request, e.g. because no
+    /// socket was bound to the server address. Refusals of earlier requests are not considered
+    /// final, as the server may not yet have been listening.
+    ConnectRefused,
     /// Indicates that a connection could not be established due to a protocol version mismatch.
     Version,
     /// Indicates that a connection could not be established due to an endpoint configuration
@@ -55,6 +205,13 @@ pub enum ErrorType {
     /// Indicates that a connection could not be established because the maximum number of clients
     /// are already connected to the server.
     ServerFull,
+    /// Indicates that the server declined the connection, e.g. because the connect token was not
+    /// valid (see [`Config::connect_token`]).
+    Rejected,
+    /// Indicates that a connection could not be established because the server did not complete a
+    /// key exchange, or presented a key other than [`Config::server_public_key`].
+    #[cfg(feature = "encryption")]
+    KeyExchange,
 }
 
 /// Used to signal connection events and deliver received packets.
@@ -69,6 +226,46 @@ pub enum Event {
     Receive(Box<[u8]>),
     /// Indicates that a connection has been terminated due to an unrecoverable error.
     Error(ErrorType),
+    /// Indicates that the estimated arrival cadence of data sent by the server has changed
+    /// significantly. See [`Client::link_stats()`].
+    CadenceChange(ArrivalCadence),
+    /// Returns a packet which was enqueued before the connection was established, but which could
+    /// not be sent because the connection attempt failed. These events follow the corresponding
+    /// [`Error`](Event::Error) event, in the order the packets were enqueued.
+    SendFailed(Box<[u8]>),
+    /// Indicates that sending has paused because the frame transfer window is full, i.e. more
+    /// frames were sent than the server could acknowledge within one round trip. This is distinct
+    /// from a reduced send rate due to congestion. See [`WindowUsage`].
+    WindowLimited(WindowUsage),
+    /// Indicates that the state of the connection has changed. Only generated if
+    /// [`EndpointConfig::state_events`] is set. See [`Client::connection_state()`].
+    StateChange(ConnectionState),
+    /// Indicates that the quality score of the connection has changed significantly. Only
+    /// generated if [`EndpointConfig::quality_events`] is set. See [`LinkStats::quality`].
+    QualityChange(u8),
+    /// Indicates that the send queue of the given channel has crossed one of the channel's
+    /// watermarks. Only generated for channels with watermarks (see [`Client::set_watermarks()`]).
+    Backpressure(usize, Backpressure),
+    /// Signals a custom frame received from the server, along with its type ID. Only generated for
+    /// types registered via [`EndpointConfig::custom_frame_types`].
+    CustomFrame(usize, Box<[u8]>),
+    /// Signals a heartbeat payload carried by a keepalive frame from the server. Only generated if
+    /// [`EndpointConfig::heartbeat_events`] is set.
+    Heartbeat(Box<[u8]>),
+    /// Describes the entire lifetime of an established connection which has ended. Generated
+    /// immediately before the corresponding [`Disconnect`](Event::Disconnect) or
+    /// [`Error`](Event::Error) event, and only if [`EndpointConfig::session_summaries`] is set.
+    Summary(Box<SessionSummary>),
+    /// Reports the outcome of a packet sent via [`Client::send_traced()`], along with its trace
+    /// ID.
+    Trace(u64, TraceOutcome),
+    /// Confirms that a packet sent via [`Client::send_confirmed()`] has been received by the
+    /// server, along with its channel ID and tag.
+    Delivered(usize, u64),
+    /// Indicates that a soft reset of the connection has completed, whether requested via
+    /// [`Client::reset()`] or by the server, along with the number of packets which this client
+    /// dropped from its send queue and transfer window as a result.
+    Reset(u64),
 }
 
 struct PacketReceiveSink<'a> {
@@ -93,6 +290,8 @@ struct SendEntry {
     data: Box<[u8]>,
     channel_id: u8,
     mode: SendMode,
+    options: SendOptions,
+    send_id: u64,
 }
 
 pub (super) enum DisconnectMode {
@@ -103,18 +302,43 @@ pub (super) enum DisconnectMode {
 struct PendingState {
     local_nonce: u32,
 
+    // Kept so that a handshake cookie may be echoed in subsequent requests
+    request: frame::HandshakeSynFrame,
     request_bytes: Box<[u8]>,
     resend_time_ms: u64,
+    resend_interval_ms: u64,
     resend_count: u8,
+    refused: bool,
 
     initial_sends: Vec<SendEntry>,
+    initial_stale_bounds: Vec<(u8, Option<u64>)>,
+    initial_reorder_limits: Vec<(u8, Option<ReorderLimits>)>,
+    initial_receive_pauses: Vec<(u8, bool)>,
+    initial_watermarks: Vec<(u8, Option<Watermarks>)>,
+
+    // Set if session keys are to be agreed upon with the server
+    #[cfg(feature = "encryption")]
+    key_exchange: Option<key_exchange::ClientHandshake>,
 }
 
-struct ActiveState {
+struct ActiveState<const CHANNELS: usize> {
     local_nonce: u32,
-    half_connection: half_connection::HalfConnection,
+    remote_nonce: u32,
+    half_connection: half_connection::HalfConnection<CHANNELS>,
+    // Time at which the half connection was created, relative to the client's time base
+    time_base_ms: u64,
     timeout_time_ms: u64,
     disconnect_signal: Option<DisconnectMode>,
+    // Set if the server accepts group subscriptions
+    groups_enabled: bool,
+    // Set if the server accepts custom frames
+    custom_frames_enabled: bool,
+    // Set if the server accepts heartbeat payloads
+    heartbeats_enabled: bool,
+    // Set if the server may challenge the client to continue the connection from a new address
+    migration_enabled: bool,
+    // Set if the server accepts frames over additional paths
+    multipath_enabled: bool,
 }
 
 struct ClosingState {
@@ -127,25 +351,89 @@ struct ClosedState {
     timeout_time_ms: u64,
 }
 
-enum State {
+// Group subscriptions are sent to the server in full whenever they change, and resent until
+// acknowledged
+struct GroupState {
+    names: Vec<String>,
+    version: u32,
+    acked: bool,
+    resend_time_ms: u64,
+}
+
+impl GroupState {
+    fn new() -> Self {
+        Self {
+            names: Vec::new(),
+            version: 0,
+            acked: true,
+            resend_time_ms: 0,
+        }
+    }
+
+    fn mark_changed(&mut self) {
+        self.version = self.version.wrapping_add(1);
+        self.acked = false;
+        self.resend_time_ms = 0;
+    }
+}
+
+enum State<const CHANNELS: usize> {
     Pending(PendingState),
-    Active(ActiveState),
+    Active(ActiveState<CHANNELS>),
     Closing(ClosingState),
     Closed(ClosedState),
     Fin,
 }
 
 /// Manages a single outbound `uflow` connection.
-pub struct Client {
-    socket: net::UdpSocket,
+///
+/// A client communicates over a non-blocking UDP socket by default, but may be created with any
+/// other [`Transport`] via [`Client::with_transport()`].
+///
+/// A client may use up to [`EndpointConfig::channel_count`](crate::EndpointConfig::channel_count)
+/// channels, which defaults to [`CHANNEL_COUNT`]. The client proposes its channel count to the
+/// server while connecting, and fails to connect if the server does not have as many channels.
+///
+/// An application which only ever uses a few channels may instead create a client with a fixed
+/// channel count of `CHANNELS` via [`Client::with_fixed_channels()`], whose per-channel state is
+/// then held in arrays of exactly that size. A `CHANNELS` of 0, the default, leaves the channel
+/// count to be configured at runtime.
+pub struct Client<T: Transport = net::UdpSocket, const CHANNELS: usize = 0> {
+    socket: Sealed<paths::Paths<T>>,
     config: Config,
 
     local_addr: net::SocketAddr,
     remote_addr: net::SocketAddr,
 
-    time_base: time::Instant,
+    time_base: time::Duration,
+
+    state: State<CHANNELS>,
+
+    groups: GroupState,
+
+    // Number of consecutive frames received which could not be decoded
+    decode_failure_count: u32,
+
+    // Reason reported once the connection has been closed
+    close_reason: CloseReason,
+    // Most recent state signaled via Event::StateChange
+    signaled_state: ConnectionState,
+    // Final statistics of the connection, recorded once it is no longer active, and signaled once
+    // it has ended
+    session_summary: Option<SessionSummary>,
+
+    // Public key presented by the server during a completed key exchange
+    #[cfg(feature = "encryption")]
+    server_public_key: Option<[u8; 32]>,
+
+    // Assigned by the server during the handshake, if it supports connection IDs
+    connection_id: Option<u32>,
+
+    // Identifies the next packet passed to send()
+    next_send_id: u64,
 
-    state: State,
+    // Reported to the server by acknowledgements, if negotiated
+    app_latency_ms: u64,
 
     events_out: Vec<Event>,
 }
@@ -181,14 +469,82 @@ impl Client {
         socket.set_nonblocking(true)?;
         socket.connect(dst_socket_addr)?;
 
-        let local_addr = socket.local_addr()?;
         let remote_addr = socket.peer_addr()?;
 
+        Self::new(Arc::new(socket), remote_addr, config)
+    }
+}
+
+impl<T: Transport> Client<T> {
+    /// Returns a [`Client`](Self) object which communicates over the provided transport, which
+    /// must be non-blocking (see [`Transport`]). A connection to the server at the provided
+    /// address is initiated immediately. Datagrams received from any other address are ignored.
+    ///
+    /// # Error Handling
+    ///
+    /// Any errors resulting from querying the transport's local address are forwarded to the
+    /// caller. This function will panic if the provided client configuration is not valid.
+    pub fn with_transport(transport: T, remote_addr: net::SocketAddr, config: Config) -> Result<Self, std::io::Error> {
+        assert!(config.is_valid(), "invalid client config");
+
+        Self::new(Arc::new(transport), remote_addr, config)
+    }
+}
+
+impl<T: Transport, const CHANNELS: usize> Client<T, CHANNELS> {
+    /// Returns a [`Client`](Self) object which communicates over the provided transport, and which
+    /// uses exactly `CHANNELS` channels, in place of the [channel
+    /// count](crate::EndpointConfig::channel_count) of the provided configuration. Otherwise
+    /// identical to [`with_transport()`](Client::with_transport).
+    ///
+    /// ```
+    /// # let (transport, server_transport) = uflow::transport::Memory::pair();
+    /// # let server_address = server_transport.address();
+    /// let client = uflow::client::Client::<_, 4>::with_fixed_channels(transport, server_address, Default::default()).unwrap();
+    /// ```
+    ///
+    /// # Error Handling
+    ///
+    /// Any errors resulting from querying the transport's local address are forwarded to the
+    /// caller. This function will panic if `CHANNELS` is zero or exceeds [`MAX_CHANNEL_COUNT`], or
+    /// if the provided client configuration is not valid.
+    pub fn with_fixed_channels(transport: T, remote_addr: net::SocketAddr, mut config: Config) -> Result<Self, std::io::Error> {
+        assert!(CHANNELS > 0 && CHANNELS <= MAX_CHANNEL_COUNT, "invalid channel count {}", CHANNELS);
+
+        config.endpoint_config.channel_count = CHANNELS;
+
+        assert!(config.is_valid(), "invalid client config");
+
+        Self::new(Arc::new(transport), remote_addr, config)
+    }
+
+    // Initiates a connection to the given remote address using an existing non-blocking socket
+    fn new(
+        socket: Arc<T>,
+        remote_addr: net::SocketAddr,
+        config: Config
+    ) -> Result<Self, std::io::Error> {
+        let local_addr = socket.local_addr()?;
+
+        let socket = paths::Paths::new(socket);
+
+        #[cfg(feature = "encryption")]
+        let socket = Sealed::new(socket, &config.endpoint_config, seal::Role::Client, config.key_exchange);
+        #[cfg(not(feature = "encryption"))]
+        let socket = Sealed::new(socket, &config.endpoint_config, seal::Role::Client, false);
+
+        #[cfg(feature = "encryption")]
+        let key_exchange = if config.key_exchange {
+            Some(key_exchange::ClientHandshake::new())
+        } else {
+            None
+        };
+
         // Send initial connection request
 
         let nonce = rand::random::<u32>();
 
-        let request = frame::Frame::HandshakeSynFrame(frame::HandshakeSynFrame {
+        let request = frame::HandshakeSynFrame {
             version: PROTOCOL_VERSION,
             nonce,
             max_receive_rate: config
@@ -203,23 +559,67 @@ impl Client {
                 .endpoint_config
                 .max_receive_alloc
                 .min(u32::MAX as usize) as u32,
-        });
+            features: frame::serial::FEATURE_GROUPS | frame::serial::FEATURE_CONTROL_CHANNELS | frame::serial::FEATURE_MIGRATION |
+                      frame::serial::FEATURE_DROP_RANGES | if config.endpoint_config.fragment_acks {
+                frame::serial::FEATURE_FRAGMENT_ACKS
+            } else {
+                0
+            } | if config.endpoint_config.receiver_health {
+                frame::serial::FEATURE_RECEIVER_HEALTH
+            } else {
+                0
+            } | if config.endpoint_config.custom_frame_types != 0 {
+                frame::serial::FEATURE_CUSTOM_FRAMES
+            } else {
+                0
+            } | if config.endpoint_config.heartbeat_events {
+                frame::serial::FEATURE_HEARTBEATS
+            } else {
+                0
+            },
+            max_receive_window: config.endpoint_config.max_receive_window,
+            cookie: None,
+            connect_token: config.connect_token.clone().unwrap_or_default(),
+            #[cfg(feature = "encryption")]
+            key_share: key_exchange.as_ref().map(|handshake| handshake.key_share()),
+            #[cfg(not(feature = "encryption"))]
+            key_share: None,
+            ext_features: frame::serial::EXT_FEATURE_CONNECTION_IDS | frame::serial::EXT_FEATURE_MULTIPATH |
+                          frame::serial::EXT_FEATURE_CHANNEL_COUNT | if config.endpoint_config.app_latency {
+                frame::serial::EXT_FEATURE_APP_LATENCY
+            } else {
+                0
+            },
+            channel_count: config.endpoint_config.channel_count as u16,
+        };
 
-        let request_bytes = request.write();
-        let _ = socket.send_to(&request_bytes, dst_addr);
+        let request_bytes = frame::Frame::HandshakeSynFrame(request.clone()).write();
+        let _ = socket.send_to(&request_bytes, remote_addr);
 
         // Initialize state object
 
         let state = State::Pending(PendingState {
             local_nonce: nonce,
 
+            request,
             request_bytes,
-            resend_time_ms: HANDSHAKE_RESEND_INTERVAL_MS,
-            resend_count: HANDSHAKE_RESEND_COUNT,
+            resend_time_ms: config.handshake_resend_interval_ms,
+            resend_interval_ms: config.handshake_resend_interval_ms,
+            resend_count: config.handshake_resend_count,
+            refused: false,
 
             initial_sends: Vec::new(),
+            initial_stale_bounds: Vec::new(),
+            initial_reorder_limits: Vec::new(),
+            initial_receive_pauses: Vec::new(),
+            initial_watermarks: Vec::new(),
+
+            #[cfg(feature = "encryption")]
+            key_exchange,
         });
 
+        let time_base = config.clock.now();
+
         Ok(Self {
             socket,
             config,
@@ -227,10 +627,27 @@ impl Client {
             local_addr,
             remote_addr,
 
-            time_base: time::Instant::now(),
+            time_base,
 
             state,
 
+            groups: GroupState::new(),
+
+            decode_failure_count: 0,
+
+            close_reason: CloseReason::Disconnect,
+            signaled_state: ConnectionState::Connecting,
+            session_summary: None,
+
+            #[cfg(feature = "encryption")]
+            server_public_key: None,
+
+            connection_id: None,
+
+            next_send_id: 0,
+
+            app_latency_ms: 0,
+
             events_out: Vec::new(),
         })
     }
@@ -275,44 +692,544 @@ impl Client {
         }
     }
 
+    /// Returns the current state of the connection. If
+    /// [`EndpointConfig::state_events`] is set, each change in state is also signaled by a
+    /// [`StateChange`](Event::StateChange) event.
+    pub fn connection_state(&self) -> ConnectionState {
+        match self.state {
+            State::Pending(_) => ConnectionState::Connecting,
+            State::Active(ref state) => {
+                if state.disconnect_signal.is_some() {
+                    ConnectionState::Draining
+                } else {
+                    ConnectionState::Connected
+                }
+            }
+            State::Closing(_) => ConnectionState::Closing,
+            State::Closed(_) | State::Fin => ConnectionState::Closed { reason: self.close_reason },
+        }
+    }
+
+    /// Returns the number of channels which may be used with this client (see
+    /// [`EndpointConfig::channel_count`](crate::EndpointConfig::channel_count)).
+    pub fn channel_count(&self) -> usize {
+        self.config.endpoint_config.channel_count
+    }
+
+    /// Returns the maximum size of a packet which may be sent to the server (see
+    /// [`EndpointConfig::max_packet_size`](crate::EndpointConfig#structfield.max_packet_size)).
+    pub fn max_packet_size(&self) -> usize {
+        self.config.endpoint_config.max_packet_size
+    }
+
     /// Enqueues a packet for delivery to the server. The packet will be sent on the given channel
     /// according to the specified mode.
     ///
     /// If a connection has not yet been established, the packet will remain enqueued until the
-    /// connection succeeds. Otherwise, if the connection is not active, the packet will be
-    /// silently discarded.
+    /// connection succeeds, at which point enqueued packets are sent in order. If the connection
+    /// attempt fails, the packet is returned by a [`SendFailed`](Event::SendFailed) event.
+    /// Otherwise, if the connection is not active, the packet will be silently discarded.
+    ///
+    /// The returned handle may be passed to [`cancel()`](Self::cancel) to withdraw the packet
+    /// before it is sent.
     ///
     /// # Error Handling
     ///
     /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
-    /// CHANNEL_COUNT`), or if `data.len()` exceeds the [maximum packet
+    /// self.channel_count()`), or if `data.len()` exceeds the [maximum packet
     /// size](crate::EndpointConfig#structfield.max_packet_size).
-    pub fn send(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode) {
+    pub fn send(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions::default())
+    }
+
+    /// Enqueues a packet for delivery to the server with the given options (see [`SendOptions`]).
+    /// Otherwise identical to [`send()`](Self::send).
+    ///
+    /// Options may be combined, e.g. to send a critical packet which is both keyed and traced. The
+    /// remaining `send_*()` functions are shorthand for this function with a single option set.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `options.ttl` is set and `mode` is not
+    /// [`TimeSensitive`](SendMode::TimeSensitive) or [`Unreliable`](SendMode::Unreliable), if
+    /// `options.confirm_tag` is set and `mode` is not [`Reliable`](SendMode::Reliable) or
+    /// [`ReliableUnordered`](SendMode::ReliableUnordered), if `channel_id` does not refer to a valid
+    /// channel (`channel_id >= self.channel_count()`), or if `data.len()` exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_with(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, options: SendOptions) -> SendHandle {
+        self.check_send(&data, channel_id, mode, &options);
+
+        let send_id = self.next_send_id;
+        self.next_send_id += 1;
+
+        match self.state {
+            State::Pending(ref mut state) => {
+                state.initial_sends.push(SendEntry { data, channel_id: channel_id as u8, mode, options, send_id });
+            }
+            State::Active(ref mut state) => {
+                state.half_connection.send_with(data, channel_id as u8, mode, options, send_id);
+            }
+            State::Closing(_) => {
+                // Disconnecting, nothing to do
+            }
+            State::Closed(_) => {
+                // Remote host has closed this connection, nothing to do
+            }
+            State::Fin => {
+                // Connection is dead, nothing to do
+            }
+        }
+
+        SendHandle(send_id)
+    }
+
+    // Panics if a packet with the given parameters may not be sent
+    fn check_send(&self, data: &[u8], channel_id: usize, mode: SendMode, options: &SendOptions) {
+        assert!(options.ttl.is_none() || mode == SendMode::TimeSensitive || mode == SendMode::Unreliable,
+                "send failed: send mode {:?} does not support a TTL",
+                mode);
+
+        assert!(options.confirm_tag.is_none() || mode == SendMode::Reliable || mode == SendMode::ReliableUnordered,
+                "send failed: send mode {:?} does not support delivery confirmation",
+                mode);
+
         assert!(data.len() <= self.config.endpoint_config.max_packet_size,
                 "send failed: packet of size {} exceeds configured maximum of {}",
                 data.len(),
                 self.config.endpoint_config.max_packet_size);
 
-        assert!(channel_id < CHANNEL_COUNT,
+        assert!(channel_id < self.config.endpoint_config.channel_count,
                 "send failed: channel ID {} is invalid",
                 channel_id);
+    }
+
+    /// Cancels a packet previously enqueued by [`send()`](Self::send) or one of its variants, e.g.
+    /// when a newer snapshot of the same state supersedes one which has yet to be sent. Returns
+    /// `true` if the packet was canceled, or `false` if it has already been sent (in part or in
+    /// full) or discarded.
+    ///
+    /// A packet can be canceled while it remains in the send queue, or while the connection is
+    /// pending. A packet which will not be resent, i.e. one sent with
+    /// [`TimeSensitive`](SendMode::TimeSensitive) or [`Unreliable`](SendMode::Unreliable), may also
+    /// be canceled after leaving the send queue, provided none of its fragments have been sent; the
+    /// server then treats it as lost. Canceled packets are counted by
+    /// [`ChannelStats::sender_dropped`](crate::ChannelStats#structfield.sender_dropped) once the
+    /// connection is active, and canceled packets which were traced are reported as
+    /// [`Dropped`](TraceOutcome::Dropped).
+    pub fn cancel(&mut self, handle: SendHandle) -> bool {
+        match self.state {
+            State::Pending(ref mut state) => {
+                match state.initial_sends.iter().position(|entry| entry.send_id == handle.0) {
+                    Some(index) => {
+                        let initial_send = state.initial_sends.remove(index);
+
+                        if let Some(trace_id) = initial_send.options.trace_id {
+                            self.events_out.push(Event::Trace(trace_id, TraceOutcome::Dropped));
+                        }
+
+                        true
+                    }
+                    None => false,
+                }
+            }
+            State::Active(ref mut state) => state.half_connection.cancel(handle.0),
+            _ => false,
+        }
+    }
+
+    /// Enqueues a packet for delivery to the server, unless the connection is congested. Otherwise
+    /// identical to [`send()`](Self::send).
+    ///
+    /// The connection is considered congested if the packet data already queued or awaiting
+    /// acknowledgement would take longer than
+    /// [`max_send_delay_ms`](crate::EndpointConfig#structfield.max_send_delay_ms) to drain at the
+    /// current send rate. In that case the packet is not enqueued, and the estimated delay is
+    /// returned instead, so that a real-time application may send less data rather than queueing
+    /// data which would be stale on arrival. Packets are always accepted while the connection is
+    /// pending.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`), or if `data.len()` exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn try_send(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode) -> Result<SendHandle, SendCongested> {
+        self.check_send(&data, channel_id, mode, &SendOptions::default());
+
+        if let State::Active(ref state) = self.state {
+            let estimated_delay_ms = state.half_connection.send_delay_ms();
+
+            if estimated_delay_ms > self.config.endpoint_config.max_send_delay_ms {
+                return Err(SendCongested { estimated_delay_ms });
+            }
+        }
+
+        Ok(self.send(data, channel_id, mode))
+    }
+
+    /// Enqueues a packet for delivery to the server with the given latency class (see
+    /// [`LatencyClass`]). Otherwise identical to [`send()`](Self::send).
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`), or if `data.len()` exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_with_class(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, latency_class: LatencyClass) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions { latency_class, .. Default::default() })
+    }
+
+    /// Enqueues a packet for delivery to the server which is ordered only with respect to other
+    /// packets sent on the same channel with the same key. Otherwise identical to
+    /// [`send()`](Self::send).
+    ///
+    /// A keyed packet will not be received until every previous [`Reliable`](SendMode::Reliable)
+    /// packet sent on its channel with the same key has been received, but it may be received
+    /// ahead of packets with other keys, and ahead of packets sent without a key. This allows, for
+    /// example, updates concerning many independent entities to share a single channel without
+    /// one entity's lost packets stalling the others.
+    ///
+    /// Keyed packets are not subject to the channel's [reorder
+    /// limits](Self::set_reorder_limits).
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`), or if `data.len()` exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_keyed(&mut self, data: Box<[u8]>, channel_id: usize, key: u16, mode: SendMode) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions { key: Some(key), .. Default::default() })
+    }
+
+    /// Enqueues a packet for delivery to the server whose outcome will be reported by a
+    /// [`Trace`](Event::Trace) event carrying the given trace ID. Otherwise identical to
+    /// [`send()`](Self::send).
+    ///
+    /// The trace ID is opaque to `uflow`, and is never sent to the server. A packet is reported as
+    /// delivered once every fragment has been acknowledged, as dropped if it is discarded before
+    /// being sent, and as lost if the server moves past it before it is acknowledged in full (see
+    /// [`TraceOutcome`]). If the connection attempt fails, a dropped outcome follows the packet's
+    /// [`SendFailed`](Event::SendFailed) event. No outcome is reported for packets which are
+    /// unresolved when the connection ends.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`), or if `data.len()` exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_traced(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, trace_id: u64) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions { trace_id: Some(trace_id), .. Default::default() })
+    }
+
+    /// Enqueues a reliable packet for delivery to the server whose receipt will be confirmed by a
+    /// [`Delivered`](Event::Delivered) event carrying its channel ID and the given tag. Otherwise
+    /// identical to [`send()`](Self::send).
+    ///
+    /// The tag is opaque to `uflow`, and is never sent to the server. A packet is confirmed as soon
+    /// as every fragment has been acknowledged, or once the server has moved past it, which it
+    /// cannot do without receiving it. Confirmation means the packet has reached the server's
+    /// receive buffer, not that it has been delivered to the server application. No event is
+    /// generated for packets which are unconfirmed when the connection ends, or which are
+    /// discarded by a [reset](Self::reset).
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `mode` is not [`Reliable`](SendMode::Reliable) or
+    /// [`ReliableUnordered`](SendMode::ReliableUnordered), if `channel_id` does not refer to a
+    /// valid channel (`channel_id >= self.channel_count()`), or if `data.len()` exceeds the
+    /// [maximum packet size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_confirmed(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, tag: u64) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions { confirm_tag: Some(tag), .. Default::default() })
+    }
+
+    /// Enqueues an unreliable packet for delivery to the server which is dropped, rather than
+    /// sent, if it remains in the send queue for longer than `ttl` (rounded down to the nearest
+    /// millisecond). Otherwise identical to [`send()`](Self::send).
+    ///
+    /// This bounds the age of data which may be sent while the connection is congested. Expired
+    /// packets are counted by [`ChannelStats::sender_dropped`](crate::ChannelStats#structfield.sender_dropped).
+    /// Packets enqueued while the connection is pending are timed from when the connection is
+    /// established.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `mode` is not [`TimeSensitive`](SendMode::TimeSensitive) or
+    /// [`Unreliable`](SendMode::Unreliable), if `channel_id` does not refer to a valid channel
+    /// (`channel_id >= self.channel_count()`), or if `data.len()` exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_with_ttl(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, ttl: time::Duration) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions { ttl: Some(ttl), .. Default::default() })
+    }
+
+    /// Subscribes to the server-side group with the given name, so that packets sent to the group
+    /// via [`Server::send_group()`](crate::server::Server::send_group) are delivered to this
+    /// client. Has no effect if already subscribed.
+    ///
+    /// Subscriptions may be changed at any time, and are sent to the server once the connection
+    /// is active. Changes are applied by the server in the order they were made, but packets sent
+    /// to a group before the server has received the change will not reflect it.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `group` is empty, if `group.len()` exceeds
+    /// [`MAX_GROUP_NAME_SIZE`], or if the client is already subscribed to
+    /// [`MAX_GROUP_SUBSCRIPTIONS`] groups.
+    pub fn join_group(&mut self, group: &str) {
+        assert!(!group.is_empty() && group.len() <= MAX_GROUP_NAME_SIZE,
+                "join_group failed: group name of size {} is invalid",
+                group.len());
+
+        if self.groups.names.iter().any(|name| name == group) {
+            return;
+        }
+
+        assert!(self.groups.names.len() < MAX_GROUP_SUBSCRIPTIONS,
+                "join_group failed: already subscribed to {} groups",
+                MAX_GROUP_SUBSCRIPTIONS);
+
+        self.groups.names.push(group.to_owned());
+        self.groups.mark_changed();
+    }
+
+    /// Unsubscribes from the server-side group with the given name. Has no effect if not
+    /// subscribed.
+    pub fn leave_group(&mut self, group: &str) {
+        if let Some(idx) = self.groups.names.iter().position(|name| name == group) {
+            self.groups.names.remove(idx);
+            self.groups.mark_changed();
+        }
+    }
+
+    /// Returns the names of the groups to which this client has subscribed.
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.groups.names.iter().map(|name| name.as_str())
+    }
+
+    /// Enqueues a custom frame of the given type, to be sent to the server during the next call to
+    /// [`Client::step()`] or [`Client::flush()`]. Custom frames are sent unreliably, and are
+    /// silently discarded if the connection is not active, or if the server has not registered any
+    /// custom frame types.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `type_id` does not refer to a valid custom frame type (`type_id
+    /// >= CUSTOM_FRAME_TYPE_COUNT`), or if the size of `data` exceeds [`MAX_CUSTOM_FRAME_SIZE`].
+    pub fn send_custom_frame(&mut self, type_id: usize, data: Box<[u8]>) {
+        assert!(type_id < CUSTOM_FRAME_TYPE_COUNT,
+                "send_custom_frame failed: type ID {} is invalid",
+                type_id);
+
+        assert!(data.len() <= MAX_CUSTOM_FRAME_SIZE,
+                "send_custom_frame failed: frame of size {} exceeds maximum of {}",
+                data.len(),
+                MAX_CUSTOM_FRAME_SIZE);
+
+        if let State::Active(ref mut state) = self.state {
+            if state.custom_frames_enabled {
+                state.half_connection.send_custom_frame(type_id as u8, data);
+            }
+        }
+    }
+
+    /// Returns a description of each packet which has been enqueued for sending to the server, but
+    /// which has not yet been sent, in the order the packets will be sent (or, if
+    /// [`channel_weights`](EndpointConfig#structfield.channel_weights) are set, the order the
+    /// packets were enqueued). Packets enqueued before the connection was established are not
+    /// included.
+    pub fn queued_packets(&self) -> impl Iterator<Item = QueuedPacket<'_>> {
+        match self.state {
+            State::Active(ref state) => Some(state.half_connection.queued_packets()),
+            _ => None,
+        }.into_iter().flatten()
+    }
+
+    /// Reorders or drops packets which have been enqueued for sending to the server, but which
+    /// have not yet been sent, according to the given function (see [`QueueAction`]). The function
+    /// is called once for each queued packet, in the order the packets would otherwise be sent.
+    ///
+    /// Packets are assigned sequence numbers when they are sent, so a promoted packet will be
+    /// delivered ahead of any packets which were enqueued before it on the same channel, and a
+    /// demoted packet will be delivered behind any packets which were enqueued after it. Packets
+    /// retain their relative order otherwise.
+    pub fn reshape_send_queue(&mut self, f: impl FnMut(&QueuedPacket) -> QueueAction) {
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.reshape_send_queue(f);
+        }
+    }
+
+    /// Sets the staleness bound of the given channel, in milliseconds.
+    ///
+    /// If the connection is congested such that the send queue cannot be drained within one RTT,
+    /// any [`Unreliable`](SendMode::Unreliable) or [`TimeSensitive`](SendMode::TimeSensitive)
+    /// packets on this channel which have waited in the send queue for longer than the staleness
+    /// bound will be dropped by the client. Dropped packets are counted by `link_stats()`. A bound
+    /// of `None` (the default) disables this behavior.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn set_stale_bound(&mut self, channel_id: usize, bound_ms: Option<u64>) {
+        assert!(channel_id < self.config.endpoint_config.channel_count,
+                "set_stale_bound failed: channel ID {} is invalid",
+                channel_id);
+
+        match self.state {
+            State::Pending(ref mut state) => {
+                state.initial_stale_bounds.push((channel_id as u8, bound_ms));
+            }
+            State::Active(ref mut state) => {
+                state.half_connection.set_stale_bound(channel_id as u8, bound_ms);
+            }
+            _ => (),
+        }
+    }
+
+    /// Sets the watermarks of the given channel, which bound the number of bytes which may wait in
+    /// the channel's send queue before a [`Backpressure`](Event::Backpressure) event is generated
+    /// (see [`Watermarks`]). Watermarks are evaluated during each call to [`Client::step()`], so an
+    /// application may pause producing data for a channel once signaled, rather than polling the
+    /// size of its send queue. Watermarks of `None` (the default) disable this behavior, and
+    /// resume the channel if it was paused.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`), or if `watermarks.low` is not less than `watermarks.high`.
+    pub fn set_watermarks(&mut self, channel_id: usize, watermarks: Option<Watermarks>) {
+        assert!(channel_id < self.config.endpoint_config.channel_count,
+                "set_watermarks failed: channel ID {} is invalid",
+                channel_id);
+
+        if let Some(watermarks) = watermarks {
+            assert!(watermarks.low < watermarks.high,
+                    "set_watermarks failed: low watermark {} is not less than high watermark {}",
+                    watermarks.low,
+                    watermarks.high);
+        }
+
+        match self.state {
+            State::Pending(ref mut state) => {
+                state.initial_watermarks.push((channel_id as u8, watermarks));
+            }
+            State::Active(ref mut state) => {
+                state.half_connection.set_watermarks(channel_id as u8, watermarks);
+            }
+            _ => (),
+        }
+    }
+
+    /// Sets the reorder limits of the given channel, which bound the number of packets received
+    /// from the server that may wait for a missing predecessor, and the time for which they may
+    /// wait (see [`ReorderLimits`]). Limits of `None` (the default) allow packets to wait
+    /// indefinitely.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn set_reorder_limits(&mut self, channel_id: usize, limits: Option<ReorderLimits>) {
+        assert!(channel_id < self.config.endpoint_config.channel_count,
+                "set_reorder_limits failed: channel ID {} is invalid",
+                channel_id);
+
+        match self.state {
+            State::Pending(ref mut state) => {
+                state.initial_reorder_limits.push((channel_id as u8, limits));
+            }
+            State::Active(ref mut state) => {
+                state.half_connection.set_reorder_limits(channel_id as u8, limits);
+            }
+            _ => (),
+        }
+    }
+
+    /// Withholds packets received from the server on the given channel until
+    /// [`resume_receive()`](Self::resume_receive) is called, e.g. while a loading screen is shown.
+    /// Packets on other channels continue to be delivered.
+    ///
+    /// Withheld packets occupy the receive window, so the server's send rate on all channels is
+    /// naturally limited once the window fills, and the connection stalls if the channel remains
+    /// paused for too long. If
+    /// [`receiver_health`](crate::EndpointConfig#structfield.receiver_health) is enabled, the
+    /// server also learns that the client is backlogged.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn pause_receive(&mut self, channel_id: usize) {
+        assert!(channel_id < self.config.endpoint_config.channel_count,
+                "pause_receive failed: channel ID {} is invalid",
+                channel_id);
+
+        self.set_receive_paused(channel_id as u8, true);
+    }
+
+    /// Resumes delivery of packets received on the given channel. Withheld packets are delivered
+    /// in order by the next call to [`Client::step()`].
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn resume_receive(&mut self, channel_id: usize) {
+        assert!(channel_id < self.config.endpoint_config.channel_count,
+                "resume_receive failed: channel ID {} is invalid",
+                channel_id);
+
+        self.set_receive_paused(channel_id as u8, false);
+    }
+
+    fn set_receive_paused(&mut self, channel_id: u8, paused: bool) {
+        match self.state {
+            State::Pending(ref mut state) => {
+                state.initial_receive_pauses.push((channel_id, paused));
+            }
+            State::Active(ref mut state) => {
+                state.half_connection.set_receive_paused(channel_id, paused);
+            }
+            _ => (),
+        }
+    }
+
+    /// Discards all packets received from the server on the given channel which are waiting for a
+    /// missing predecessor, except for the newest, which will be delivered by the next call to
+    /// [`Client::step()`]. Missing predecessors are no longer awaited, and are dropped if they
+    /// arrive later. Returns the number of packets discarded.
+    ///
+    /// This is intended for applications which resume after a long pause, and which would rather
+    /// skip a backlog of stale data than receive it in order. Packets sent with an ordering key are
+    /// unaffected.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn skip_to_latest(&mut self, channel_id: usize) -> usize {
+        assert!(channel_id < self.config.endpoint_config.channel_count,
+                "skip_to_latest failed: channel ID {} is invalid",
+                channel_id);
+
+        match self.state {
+            State::Active(ref mut state) => state.half_connection.skip_to_latest(channel_id as u8),
+            _ => 0,
+        }
+    }
 
-        match self.state {
-            State::Pending(ref mut state) => {
-                state.initial_sends.push(SendEntry { data, channel_id: channel_id as u8, mode });
-            }
-            State::Active(ref mut state) => {
-                state.half_connection.send(data, channel_id as u8, mode);
-            }
-            State::Closing(_) => {
-                // Disconnecting, nothing to do
-            }
-            State::Closed(_) => {
-                // Remote host has closed this connection, nothing to do
-            }
-            State::Fin => {
-                // Connection is dead, nothing to do
-            }
+    /// Requests a soft reset of this connection, which returns both endpoints to a clean slate
+    /// without a reconnect round trip. All packets awaiting transmission or acknowledgement are
+    /// dropped (traced packets are reported via [`Event::Trace`] as usual), and the server is asked
+    /// to drop its own, after which both transfer windows restart from the next sequence IDs.
+    /// Packets received but not yet delivered are discarded by both endpoints.
+    ///
+    /// Packets received from the server are ignored, and no packets are sent, until the server
+    /// acknowledges the reset, at which point an [`Event::Reset`] is generated. Packets enqueued in
+    /// the meantime are sent afterward. Has no effect if a reset is already pending, or if the
+    /// connection is not active.
+    pub fn reset(&mut self) {
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.reset();
         }
     }
 
@@ -326,6 +1243,7 @@ impl Client {
         match self.state {
             State::Pending(_) => {
                 // No point in assuming the server will reply, so enter fin immediately
+                self.close_reason = CloseReason::Disconnect;
                 self.state = State::Fin;
             }
             State::Active(ref mut state) => {
@@ -344,6 +1262,7 @@ impl Client {
         match self.state {
             State::Pending(_) => {
                 // No point in assuming the server will reply, so enter fin immediately
+                self.close_reason = CloseReason::Disconnect;
                 self.state = State::Fin;
             }
             State::Active(ref mut state) => {
@@ -353,7 +1272,67 @@ impl Client {
         }
     }
 
-    /// Returns the local address of the internal UDP socket.
+    /// Sends all pending packets, waits for every outstanding packet to be acknowledged, and then
+    /// disconnects, blocking the calling thread until the disconnect completes or the given
+    /// timeout elapses, whichever comes first. This allows a short-lived process (e.g. a command
+    /// line tool) to ensure its final packets are delivered before exiting, without running its
+    /// own service loop. Returns `true` if every packet was acknowledged, or `false` if the
+    /// timeout elapsed or the connection failed first. In the latter case, the connection is
+    /// terminated as with [`disconnect_now()`](Self::disconnect_now).
+    ///
+    /// If a connection has not yet been established, the client first waits for the handshake to
+    /// complete. The client's [clock](Config::clock) is used to measure the timeout, and to wait
+    /// between steps (see [`Clock::sleep()`]). Events which would be returned by
+    /// [`step()`](Self::step) in the meantime, including any packets received from the server,
+    /// are discarded.
+    pub fn flush_and_close(&mut self, timeout: time::Duration) -> bool {
+        let clock = Arc::clone(&self.config.clock);
+        let deadline = clock.now() + timeout;
+
+        // Wait for all packets to be acknowledged
+        let acked = loop {
+            self.step().for_each(drop);
+            self.flush();
+
+            let acked = match self.state {
+                State::Pending(_) => false,
+                State::Active(ref state) => !state.half_connection.is_send_pending() && state.half_connection.send_buffer_size() == 0,
+                _ => break false,
+            };
+
+            let now = clock.now();
+
+            if acked || now >= deadline {
+                break acked;
+            }
+
+            let interval = self.next_timeout().unwrap_or(FLUSH_POLL_INTERVAL).min(FLUSH_POLL_INTERVAL);
+            clock.sleep(interval.min(deadline - now));
+        };
+
+        if !acked {
+            self.disconnect_now();
+            self.step().for_each(drop);
+            return false;
+        }
+
+        // Wait for the server to acknowledge the disconnect
+        self.disconnect();
+
+        loop {
+            self.step().for_each(drop);
+
+            let now = clock.now();
+
+            if !matches!(self.state, State::Active(_) | State::Closing(_)) || now >= deadline {
+                return true;
+            }
+
+            clock.sleep(FLUSH_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Returns the local address of the internal socket.
     pub fn local_address(&self) -> net::SocketAddr {
         self.local_addr
     }
@@ -363,6 +1342,99 @@ impl Client {
         self.remote_addr
     }
 
+    /// Returns the public key presented by the server once a key exchange has been completed (see
+    /// [`Config::key_exchange`]), so that it may be compared with a known key, or remembered for
+    /// future connections.
+    ///
+    /// If no key exchange has been completed, `None` is returned instead.
+    #[cfg(feature = "encryption")]
+    pub fn server_public_key(&self) -> Option<[u8; 32]> {
+        self.server_public_key
+    }
+
+    /// Returns the connection ID assigned by the server during the handshake (see
+    /// [`server::Config::enable_connection_ids`](crate::server::Config::enable_connection_ids)).
+    /// All frames sent to the server after the connection request are tagged with this ID.
+    ///
+    /// If the server has not assigned one, `None` is returned instead.
+    pub fn connection_id(&self) -> Option<u32> {
+        self.connection_id
+    }
+
+    /// Adds a transport bound to another local address, e.g. that of a second network interface,
+    /// as an additional path to the server. Returns the index of the new path; the transport the
+    /// client was created with is path 0. The transport must be non-blocking (see [`Transport`]).
+    ///
+    /// If the server supports it (see
+    /// [`server::Config::enable_multipath`](crate::server::Config::enable_multipath)), the client
+    /// probes each new path until the server has validated it, and then spreads data frames over
+    /// all working paths according to the round-trip time and loss measured on each. Other frames
+    /// are sent over the path with the least round-trip time. A path whose frames go
+    /// unacknowledged for several round trips is abandoned, and its data moves to the remaining
+    /// paths, until a frame is once again received over it (see [`path_stats()`](Self::path_stats)).
+    /// The server replies via the path over which it most recently received a frame.
+    ///
+    /// If the server does not support multiple paths, additional paths are never used.
+    ///
+    /// # Error Handling
+    ///
+    /// If the client already has [`MAX_PATHS`](crate::MAX_PATHS) paths, an error of kind
+    /// [`InvalidInput`](std::io::ErrorKind::InvalidInput) is returned.
+    pub fn add_path(&mut self, transport: T) -> Result<usize, std::io::Error> {
+        if self.socket.inner().len() >= MAX_PATHS {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "too many paths"));
+        }
+
+        let path = self.socket.inner_mut().add(transport);
+
+        if let State::Active(ref mut state) = self.state {
+            if state.multipath_enabled {
+                state.half_connection.add_path();
+            }
+        }
+
+        Ok(path)
+    }
+
+    /// Returns the number of paths over which the client may send, including the transport it was
+    /// created with (see [`add_path()`](Self::add_path)).
+    pub fn path_count(&self) -> usize {
+        self.socket.inner().len()
+    }
+
+    /// Returns measurements of the given path (see [`add_path()`](Self::add_path)). Paths are
+    /// only measured while the client sends over more than one.
+    ///
+    /// If the connection is not active, or if the server does not support multiple paths, default
+    /// values are returned instead.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `path` does not refer to a path of this client (`path >=
+    /// path_count()`).
+    pub fn path_stats(&self, path: usize) -> PathStats {
+        assert!(path < self.path_count(),
+                "path_stats failed: path {} is invalid",
+                path);
+
+        match self.state {
+            State::Active(ref state) if path < state.half_connection.path_count() => state.half_connection.path_stats(path),
+            _ => PathStats::default(),
+        }
+    }
+
+    /// Sets the application latency reported to the server by subsequent acknowledgements, in
+    /// milliseconds, e.g. the time taken to process the most recently received packet (see
+    /// [`EndpointConfig::app_latency`]). Values are reported with millisecond precision, up to
+    /// 65535ms.
+    pub fn set_app_latency_ms(&mut self, latency_ms: u64) {
+        self.app_latency_ms = latency_ms;
+
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.set_app_latency_ms(latency_ms);
+        }
+    }
+
     /// Returns the current estimate of the round-trip time (RTT), in seconds.
     ///
     /// If the RTT has not yet been computed, `None` is returned instead.
@@ -386,12 +1458,96 @@ impl Client {
         }
     }
 
+    /// Returns `true` if the most recent flush was interrupted because the socket's send buffer
+    /// was full. Frames which could not be sent are retained, and are sent first on the next flush.
+    ///
+    /// If the connection is not active, `false` is returned instead.
+    pub fn is_send_blocked(&self) -> bool {
+        match self.state {
+            State::Active(ref state) => state.half_connection.is_send_blocked(),
+            _ => false,
+        }
+    }
+
+    /// Returns the amount of time after which [`step()`](Self::step) and [`flush()`](Self::flush)
+    /// should next be called, assuming no frames are received in the meantime. Returns `None` if
+    /// the connection has been closed and forgotten.
+    ///
+    /// This allows the client to be driven by an event loop which waits for its socket to become
+    /// readable (see [`AsRawFd`](std::os::unix::io::AsRawFd)) or for this timeout to elapse,
+    /// whichever comes first, rather than calling [`step()`](Self::step) at a fixed interval. If
+    /// [`is_send_blocked()`](Self::is_send_blocked) returns `true`, the event loop should also
+    /// wait for the socket to become writable.
+    pub fn next_timeout(&self) -> Option<time::Duration> {
+        let deadline_ms = match self.state {
+            State::Pending(ref state) => Some(state.resend_time_ms),
+            State::Active(ref state) => {
+                if state.disconnect_signal.is_some() {
+                    Some(0)
+                } else {
+                    let service_ms = state.half_connection.next_timeout_ms().map(|service_ms| state.time_base_ms + service_ms);
+                    let group_ms = if state.groups_enabled && !self.groups.acked { Some(self.groups.resend_time_ms) } else { None };
+
+                    [ Some(state.timeout_time_ms), service_ms, group_ms ].iter().flatten().min().copied()
+                }
+            }
+            State::Closing(ref state) => Some(state.resend_time_ms),
+            State::Closed(ref state) => Some(state.timeout_time_ms),
+            State::Fin => None,
+        };
+
+        deadline_ms.map(|deadline_ms| time::Duration::from_millis(deadline_ms.saturating_sub(self.now_ms())))
+    }
+
+    /// Returns a snapshot of statistics describing the current connection.
+    ///
+    /// If the connection is not active, default values are returned instead.
+    pub fn link_stats(&self) -> LinkStats {
+        match self.state {
+            State::Active(ref state) => state.half_connection.link_stats(),
+            _ => LinkStats::default(),
+        }
+    }
+
+    /// Returns packet counters for the given channel.
+    ///
+    /// If the connection is not active, default values are returned instead.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn channel_stats(&self, channel_id: usize) -> ChannelStats {
+        assert!(channel_id < self.config.endpoint_config.channel_count,
+                "channel_stats failed: channel ID {} is invalid",
+                channel_id);
+
+        match self.state {
+            State::Active(ref state) => state.half_connection.channel_stats(channel_id as u8),
+            _ => ChannelStats::default(),
+        }
+    }
+
+    /// Returns the history of sampled connection metrics, if enabled (see
+    /// [`EndpointConfig::stats_sample_interval_ms`](crate::EndpointConfig#structfield.stats_sample_interval_ms)).
+    ///
+    /// If sampling is disabled, or if the connection is not active, `None` is returned instead.
+    #[cfg(feature = "stats")]
+    pub fn stats_history(&self) -> Option<&StatsHistory> {
+        match self.state {
+            State::Active(ref state) => state.half_connection.stats_history(),
+            _ => None,
+        }
+    }
+
     fn now_ms(&self) -> u64 {
-        let now = time::Instant::now();
+        let now = self.config.clock.now();
         (now - self.time_base).as_millis() as u64
     }
 
     fn handle_handshake_syn_ack(&mut self, frame: frame::HandshakeSynAckFrame) {
+        let now_ms = self.now_ms();
+
         match self.state {
             State::Pending(ref mut state) => {
                 // If the server responds to our SYN with a matching SYN+ACK, it has already
@@ -404,22 +1560,67 @@ impl Client {
                 // handshake must have been received. Ignore that.
 
                 if frame.nonce_ack == state.local_nonce {
+                    // A server unaware of our channel count would only receive on the default
+                    // number of channels
+                    if frame.ext_features & frame::serial::EXT_FEATURE_CHANNEL_COUNT == 0 &&
+                        self.config.endpoint_config.channel_count > CHANNEL_COUNT {
+                        self.events_out.push(Event::Error(ErrorType::Config));
+                        Self::fail_initial_sends(state, &mut self.events_out);
+                        self.close_reason = CloseReason::Rejected;
+                        self.state = State::Fin;
+                        return;
+                    }
+
+                    #[cfg(feature = "encryption")]
+                    if let Some(ref handshake) = state.key_exchange {
+                        // The server must complete the exchange using the expected static key, if
+                        // one was given
+                        let pinned_key = self.config.server_public_key;
+
+                        let session_keys = frame.key_share.as_ref()
+                            .filter(|key_share| pinned_key.map_or(true, |key| key == key_share.static_key))
+                            .and_then(|key_share| handshake.complete(key_share));
+
+                        match session_keys {
+                            Some(session_keys) => {
+                                // Our ACK, and all frames which follow, are sealed using the new keys
+                                self.socket.set_peer_keys(self.remote_addr, &session_keys);
+                                self.server_public_key = frame.key_share.map(|key_share| key_share.static_key);
+                            }
+                            None => {
+                                self.events_out.push(Event::Error(ErrorType::KeyExchange));
+                                Self::fail_initial_sends(state, &mut self.events_out);
+                                self.close_reason = CloseReason::Rejected;
+                                self.state = State::Fin;
+                                return;
+                            }
+                        }
+                    }
+
+                    // Our ACK, and all frames which follow, are tagged with the assigned ID
+                    if frame.ext_features & frame::serial::EXT_FEATURE_CONNECTION_IDS != 0 {
+                        self.socket.set_peer_connection_id(self.remote_addr, frame.connection_id);
+                        self.connection_id = Some(frame.connection_id);
+                    }
+
                     let reply = frame::Frame::HandshakeAckFrame(frame::HandshakeAckFrame {
                         nonce_ack: frame.nonce,
                     });
-                    let _ = self.socket.send(&reply.write());
+                    let _ = self.socket.send_to(&reply.write(), self.remote_addr);
 
                     use crate::packet_id;
 
+                    let (tx_window_size, rx_window_size) = self.config.endpoint_config.transfer_windows(frame.max_receive_window);
+
                     let config = half_connection::Config {
-                        tx_frame_window_size: MAX_FRAME_WINDOW_SIZE,
-                        rx_frame_window_size: MAX_FRAME_WINDOW_SIZE,
+                        tx_frame_window_size: tx_window_size,
+                        rx_frame_window_size: rx_window_size,
 
                         tx_frame_base_id: state.local_nonce,
                         rx_frame_base_id: frame.nonce,
 
-                        tx_packet_window_size: MAX_PACKET_WINDOW_SIZE,
-                        rx_packet_window_size: MAX_PACKET_WINDOW_SIZE,
+                        tx_packet_window_size: tx_window_size,
+                        rx_packet_window_size: rx_window_size,
 
                         tx_packet_base_id: state.local_nonce & packet_id::MASK,
                         rx_packet_base_id: frame.nonce & packet_id::MASK,
@@ -429,18 +1630,90 @@ impl Client {
                         tx_alloc_limit: frame.max_receive_alloc as usize,
                         rx_alloc_limit: self.config.endpoint_config.max_receive_alloc as usize,
 
+                        rx_max_packet_size: frame.max_packet_size as usize,
+
                         keepalive_interval_ms: if self.config.endpoint_config.keepalive {
                             Some(self.config.endpoint_config.keepalive_interval_ms)
                         } else {
                             None
                         },
+
+                        #[cfg(feature = "stats")]
+                        stats_sample_interval_ms: self.config.endpoint_config.stats_sample_interval_ms,
+                        #[cfg(feature = "stats")]
+                        stats_history_len: self.config.endpoint_config.stats_history_len,
+
+                        reorder_tolerance_frames: self.config.endpoint_config.reorder_tolerance_frames,
+                        reorder_tolerance_ms: self.config.endpoint_config.reorder_tolerance_ms,
+
+                        early_retransmit: self.config.endpoint_config.early_retransmit,
+                        min_rto_ms: self.config.endpoint_config.min_rto_ms,
+
+                        max_frame_age_ms: self.config.endpoint_config.max_frame_age_ms,
+
+                        fragment_acks: self.config.endpoint_config.fragment_acks &&
+                            frame.features & frame::serial::FEATURE_FRAGMENT_ACKS != 0,
+
+                        receiver_health: self.config.endpoint_config.receiver_health &&
+                            frame.features & frame::serial::FEATURE_RECEIVER_HEALTH != 0,
+
+                        app_latency: self.config.endpoint_config.app_latency &&
+                            frame.ext_features & frame::serial::EXT_FEATURE_APP_LATENCY != 0,
+
+                        control_channels: frame.features & frame::serial::FEATURE_CONTROL_CHANNELS != 0,
+
+                        drop_ranges: frame.features & frame::serial::FEATURE_DROP_RANGES != 0,
+
+                        adaptive_frame_size: self.config.endpoint_config.adaptive_frame_size,
+
+                        deliver_empty_packets: self.config.endpoint_config.deliver_empty_packets,
+                        receive_order: self.config.endpoint_config.receive_order,
+
+                        channel_count: self.config.endpoint_config.channel_count,
+                        channel_weights: self.config.endpoint_config.channel_weights.clone(),
+                        probe_rate: self.config.endpoint_config.probe_rate,
+
+                        frame_overhead: self.socket.frame_overhead(self.remote_addr),
+
+                        clock: Arc::clone(&self.config.clock),
                     };
 
                     let mut half_connection = half_connection::HalfConnection::new(config);
 
+                    if let Some(transform) = self.config.transform.take() {
+                        half_connection.set_transform(transform);
+                    }
+
                     let initial_sends = std::mem::take(&mut state.initial_sends);
                     for initial_send in initial_sends.into_iter() {
-                        half_connection.send(initial_send.data, initial_send.channel_id, initial_send.mode);
+                        half_connection.send_with(initial_send.data, initial_send.channel_id, initial_send.mode,
+                                                  initial_send.options, initial_send.send_id);
+                    }
+
+                    for &(channel_id, bound_ms) in state.initial_stale_bounds.iter() {
+                        half_connection.set_stale_bound(channel_id, bound_ms);
+                    }
+
+                    for &(channel_id, limits) in state.initial_reorder_limits.iter() {
+                        half_connection.set_reorder_limits(channel_id, limits);
+                    }
+
+                    for &(channel_id, paused) in state.initial_receive_pauses.iter() {
+                        half_connection.set_receive_paused(channel_id, paused);
+                    }
+
+                    half_connection.set_app_latency_ms(self.app_latency_ms);
+
+                    for &(channel_id, watermarks) in state.initial_watermarks.iter() {
+                        half_connection.set_watermarks(channel_id, watermarks);
+                    }
+
+                    let multipath_enabled = frame.ext_features & frame::serial::EXT_FEATURE_MULTIPATH != 0;
+
+                    if multipath_enabled {
+                        for _ in 1 .. self.socket.inner().len() {
+                            half_connection.add_path();
+                        }
                     }
 
                     // Initialize connection and signal connect
@@ -448,9 +1721,16 @@ impl Client {
 
                     self.state = State::Active(ActiveState {
                         local_nonce: state.local_nonce,
+                        remote_nonce: frame.nonce,
                         half_connection,
+                        time_base_ms: now_ms,
                         timeout_time_ms: self.config.endpoint_config.active_timeout_ms,
                         disconnect_signal: None,
+                        groups_enabled: frame.features & frame::serial::FEATURE_GROUPS != 0,
+                        custom_frames_enabled: frame.features & frame::serial::FEATURE_CUSTOM_FRAMES != 0,
+                        heartbeats_enabled: frame.features & frame::serial::FEATURE_HEARTBEATS != 0,
+                        migration_enabled: frame.features & frame::serial::FEATURE_MIGRATION != 0,
+                        multipath_enabled,
                     });
                 }
             }
@@ -464,7 +1744,7 @@ impl Client {
                     let reply = frame::Frame::HandshakeAckFrame(frame::HandshakeAckFrame {
                         nonce_ack: frame.nonce,
                     });
-                    let _ = self.socket.send(&reply.write());
+                    let _ = self.socket.send_to(&reply.write(), self.remote_addr);
                 }
             }
             _ => (),
@@ -477,16 +1757,19 @@ impl Client {
         // error and forget the connection.
 
         match self.state {
-            State::Pending(ref state) => {
+            State::Pending(ref mut state) => {
                 if frame.nonce_ack == state.local_nonce {
                     let error_type = match frame.error {
                         frame::HandshakeErrorType::Version => ErrorType::Version,
                         frame::HandshakeErrorType::Config => ErrorType::Config,
                         frame::HandshakeErrorType::ServerFull => ErrorType::ServerFull,
+                        frame::HandshakeErrorType::Rejected => ErrorType::Rejected,
                     };
 
                     // Forget connection and signal appropriate handshake error
                     self.events_out.push(Event::Error(error_type));
+                    Self::fail_initial_sends(state, &mut self.events_out);
+                    self.close_reason = CloseReason::Rejected;
                     self.state = State::Fin;
                 }
             }
@@ -494,6 +1777,17 @@ impl Client {
         }
     }
 
+    // Returns packets which were enqueued prior to a failed connection attempt
+    fn fail_initial_sends(state: &mut PendingState, events_out: &mut Vec<Event>) {
+        for initial_send in std::mem::take(&mut state.initial_sends).into_iter() {
+            events_out.push(Event::SendFailed(initial_send.data));
+
+            if let Some(trace_id) = initial_send.options.trace_id {
+                events_out.push(Event::Trace(trace_id, TraceOutcome::Dropped));
+            }
+        }
+    }
+
     fn handle_disconnect(&mut self, now_ms: u64) {
         // Receiving a disconnect while active or closing terminates the connection immediately.
         // The original and subsequent requests are acknowledged for a finite amount of time.
@@ -508,15 +1802,18 @@ impl Client {
             },
             State::Active(ref mut state) => {
                 let reply = frame::Frame::DisconnectAckFrame(frame::DisconnectAckFrame {});
-                let _ = self.socket.send(&reply.write());
+                let _ = self.socket.send_to(&reply.write(), self.remote_addr);
 
                 // Signal any remaining received packets prior to connection destruction
                 state.half_connection.receive(&mut PacketReceiveSink::new(&mut self.events_out));
 
                 // Signal disconnect
+                self.record_summary(CloseReason::Disconnect);
+                self.signal_summary();
                 self.events_out.push(Event::Disconnect);
 
                 // Close now, but forget after a timeout
+                self.close_reason = CloseReason::Disconnect;
                 self.state = State::Closed(ClosedState {
                     timeout_time_ms: now_ms + CLOSED_TIMEOUT_MS,
                 });
@@ -524,12 +1821,14 @@ impl Client {
             State::Closing(_) => {
                 // This may as well be an acknowledgement
                 let reply = frame::Frame::DisconnectAckFrame(frame::DisconnectAckFrame {});
-                let _ = self.socket.send(&reply.write());
+                let _ = self.socket.send_to(&reply.write(), self.remote_addr);
 
                 // Signal disconnect
+                self.signal_summary();
                 self.events_out.push(Event::Disconnect);
 
                 // Close now, but forget after a timeout
+                self.close_reason = CloseReason::Disconnect;
                 self.state = State::Closed(ClosedState {
                     timeout_time_ms: now_ms + CLOSED_TIMEOUT_MS,
                 });
@@ -537,7 +1836,7 @@ impl Client {
             State::Closed(_) => {
                 // Acknowledge subsequent disconnection requests
                 let reply = frame::Frame::DisconnectAckFrame(frame::DisconnectAckFrame {});
-                let _ = self.socket.send(&reply.write());
+                let _ = self.socket.send_to(&reply.write(), self.remote_addr);
             },
             State::Fin => (),
         }
@@ -547,7 +1846,9 @@ impl Client {
         match self.state {
             State::Closing(_) => {
                 // Signal disconnect and forget connection
+                self.signal_summary();
                 self.events_out.push(Event::Disconnect);
+                self.close_reason = CloseReason::Disconnect;
                 self.state = State::Fin;
             }
             _ => (),
@@ -558,15 +1859,22 @@ impl Client {
         match self.state {
             State::Active(ref mut state) => {
                 state.half_connection.handle_data_frame(frame);
+                state.half_connection.flush_urgent_acks(&mut paths::PathFrameSink::new(&self.socket, self.remote_addr));
                 state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
             }
             _ => (),
         }
     }
 
-    fn handle_sync(&mut self, now_ms: u64, frame: frame::SyncFrame) {
+    fn handle_sync(&mut self, now_ms: u64, mut frame: frame::SyncFrame) {
         match self.state {
             State::Active(ref mut state) => {
+                if let Some(data) = frame.heartbeat.take() {
+                    if self.config.endpoint_config.heartbeat_events {
+                        self.events_out.push(Event::Heartbeat(data));
+                    }
+                }
+
                 state.half_connection.handle_sync_frame(frame);
                 state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
             }
@@ -574,26 +1882,107 @@ impl Client {
         }
     }
 
-    fn handle_ack(&mut self, now_ms: u64, frame: frame::AckFrame) {
+    fn handle_acks(&mut self, frames: Vec<frame::AckFrame>, now_ms: u64) {
+        if frames.is_empty() {
+            return;
+        }
+
         match self.state {
             State::Active(ref mut state) => {
-                state.half_connection.handle_ack_frame(frame);
+                state.half_connection.handle_ack_frames(frames);
                 state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
             }
             _ => (),
         }
     }
 
+    fn handle_fragment_ack(&mut self, now_ms: u64, frame: frame::FragmentAckFrame) {
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.handle_fragment_ack_frame(frame);
+            state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+        }
+    }
+
+    fn handle_group_ack(&mut self, now_ms: u64, frame: frame::GroupAckFrame) {
+        if let State::Active(ref mut state) = self.state {
+            if frame.version == self.groups.version {
+                self.groups.acked = true;
+            }
+            state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+        }
+    }
+
+    fn handle_custom(&mut self, now_ms: u64, frame: frame::CustomFrame) {
+        if let State::Active(ref mut state) = self.state {
+            if self.config.endpoint_config.custom_frame_types & (1 << frame.type_id) != 0 {
+                self.events_out.push(Event::CustomFrame(frame.type_id as usize, frame.data));
+            }
+            state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+        }
+    }
+
+    fn handle_reset(&mut self, now_ms: u64, frame: frame::ResetFrame) {
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.handle_reset_frame(frame);
+            state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+        }
+    }
+
+    fn handle_reset_ack(&mut self, now_ms: u64, frame: frame::ResetAckFrame) {
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.handle_reset_ack_frame(frame);
+            state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+        }
+    }
+
+    fn handle_handshake_cookie(&mut self, frame: frame::HandshakeCookieFrame) {
+        // The server will not consider our request until we prove that we can receive frames at
+        // this address, so repeat the request immediately with the cookie it sent
+        if let State::Pending(ref mut state) = self.state {
+            if frame.nonce_ack == state.local_nonce {
+                state.request.cookie = Some(frame.cookie);
+                state.request_bytes = frame::Frame::HandshakeSynFrame(state.request.clone()).write();
+
+                let _ = self.socket.send_to(&state.request_bytes, self.remote_addr);
+            }
+        }
+    }
+
+    fn handle_path_challenge(&mut self, frame: frame::PathChallengeFrame) {
+        if let State::Active(ref state) = self.state {
+            // The server has received frames from this client via an address it does not
+            // recognize, so prove ownership of the connection from the address it sees now. Each
+            // path is challenged separately, so the reply is sent over the path which received the
+            // challenge.
+            if state.migration_enabled || state.multipath_enabled {
+                let reply = frame::Frame::PathResponseFrame(frame::PathResponseFrame {
+                    challenge_ack: frame.challenge,
+                    client_nonce: state.local_nonce,
+                    server_nonce: state.remote_nonce,
+                });
+                let paths = self.socket.inner();
+                paths.select(paths.recv_path());
+                let _ = self.socket.send_to(&reply.write(), self.remote_addr);
+            }
+        }
+    }
+
     fn handle_frame(&mut self, frame: frame::Frame, now_ms: u64) {
         match frame {
-            frame::Frame::HandshakeSynFrame(_) => (),
-            frame::Frame::HandshakeAckFrame(_) => (),
+            frame::Frame::HandshakeSynFrame(_) |
+            frame::Frame::HandshakeAckFrame(_) => {
+                logger::log(&mut self.config.logger, LogLevel::Warn, self.remote_addr,
+                            format_args!("received handshake request from server"));
+            }
             frame::Frame::HandshakeSynAckFrame(frame) => {
                 self.handle_handshake_syn_ack(frame);
             },
             frame::Frame::HandshakeErrorFrame(frame) => {
                 self.handle_handshake_error(frame);
             }
+            frame::Frame::HandshakeCookieFrame(frame) => {
+                self.handle_handshake_cookie(frame);
+            }
             frame::Frame::DisconnectFrame(_frame) => {
                 self.handle_disconnect(now_ms);
             }
@@ -607,19 +1996,92 @@ impl Client {
                 self.handle_sync(now_ms, frame);
             }
             frame::Frame::AckFrame(frame) => {
-                self.handle_ack(now_ms, frame);
+                self.handle_acks(vec![ frame ], now_ms);
+            }
+            frame::Frame::FragmentAckFrame(frame) => {
+                self.handle_fragment_ack(now_ms, frame);
+            }
+            frame::Frame::GroupFrame(_) => {
+                logger::log(&mut self.config.logger, LogLevel::Warn, self.remote_addr,
+                            format_args!("received group subscription from server"));
+            }
+            frame::Frame::GroupAckFrame(frame) => {
+                self.handle_group_ack(now_ms, frame);
+            }
+            frame::Frame::CustomFrame(frame) => {
+                self.handle_custom(now_ms, frame);
+            }
+            frame::Frame::PathChallengeFrame(frame) => {
+                self.handle_path_challenge(frame);
+            }
+            frame::Frame::PathResponseFrame(_) => {
+                logger::log(&mut self.config.logger, LogLevel::Warn, self.remote_addr,
+                            format_args!("received path response from server"));
+            }
+            frame::Frame::ResetFrame(frame) => {
+                self.handle_reset(now_ms, frame);
+            }
+            frame::Frame::ResetAckFrame(frame) => {
+                self.handle_reset_ack(now_ms, frame);
             }
         }
     }
 
-    fn handle_frames(&mut self, now_ms: u64) {
-        let mut frame_data_buf = [0; MAX_FRAME_SIZE];
+    fn handle_frame_data(&mut self, frame_data: &[u8], pending_acks: &mut Vec<frame::AckFrame>, now_ms: u64) {
+        if let Some(ref mut hook) = self.config.frame_hook {
+            if frame::serial::checksum_valid(frame_data) && hook(self.remote_addr, frame_data) == FrameAction::Consume {
+                return;
+            }
+        }
 
-        while let Ok(frame_size) = self.socket.recv(&mut frame_data_buf) {
-            if let Some(frame) = frame::Frame::read(&frame_data_buf[..frame_size]) {
-                self.handle_frame(frame, now_ms);
+        if let Some(frame) = frame::Frame::read(frame_data) {
+            self.decode_failure_count = 0;
+
+            if let frame::Frame::AckFrame(frame) = frame {
+                // Deferred, so that consecutive ack frames may be processed as a batch
+                pending_acks.push(frame);
+                return;
+            }
+
+            self.handle_acks(std::mem::take(pending_acks), now_ms);
+            self.handle_frame(frame, now_ms);
+            self.signal_state_change();
+        } else {
+            self.decode_failure_count += 1;
+
+            if self.decode_failure_count == logger::DECODE_FAILURE_WARN_COUNT {
+                logger::log(&mut self.config.logger, LogLevel::Warn, self.remote_addr,
+                            format_args!("{} consecutive frames could not be decoded", self.decode_failure_count));
+            }
+        }
+    }
+
+    fn handle_frames(&mut self, now_ms: u64) {
+        let mut frame_data_buf = [0; MAX_FRAME_SIZE + MAX_TRANSFORM_OVERHEAD];
+        let mut pending_acks = Vec::new();
+
+        loop {
+            match self.socket.recv_from(&mut frame_data_buf) {
+                Ok((frame_size, address)) => {
+                    if address == self.remote_addr {
+                        self.handle_frame_data(&frame_data_buf[..frame_size], &mut pending_acks, now_ms);
+
+                        if let State::Active(ref mut state) = self.state {
+                            state.half_connection.notify_path_received(self.socket.inner().recv_path());
+                        }
+                    }
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    // The remote host has signaled that a previous frame could not be delivered
+                    if let State::Pending(ref mut state) = self.state {
+                        state.refused = true;
+                    }
+                }
+                Err(_) => break,
             }
         }
+
+        self.handle_acks(pending_acks, now_ms);
     }
 
     fn handle_events(&mut self, now_ms: u64) {
@@ -627,13 +2089,23 @@ impl Client {
             State::Pending(ref mut state) => {
                 if now_ms >= state.resend_time_ms {
                     if state.resend_count > 0 {
-                        // Resend SYN
-                        let _ = self.socket.send(&state.request_bytes);
-                        state.resend_time_ms = now_ms + HANDSHAKE_RESEND_INTERVAL_MS;
+                        // Resend SYN, and back off
+                        let _ = self.socket.send_to(&state.request_bytes, self.remote_addr);
+                        state.resend_interval_ms = (state.resend_interval_ms * 2).min(self.config.handshake_max_resend_interval_ms);
+                        state.resend_time_ms = now_ms + state.resend_interval_ms;
                         state.resend_count -= 1;
+                        state.refused = false;
                     } else {
-                        // Signal timeout and forget connection
-                        self.events_out.push(Event::Error(ErrorType::Timeout));
+                        // Signal timeout or refusal and forget connection
+                        let (error_type, close_reason) = if state.refused {
+                            (ErrorType::ConnectRefused, CloseReason::Rejected)
+                        } else {
+                            (ErrorType::ConnectTimeout, CloseReason::Timeout)
+                        };
+
+                        self.events_out.push(Event::Error(error_type));
+                        Self::fail_initial_sends(state, &mut self.events_out);
+                        self.close_reason = close_reason;
                         self.state = State::Fin;
                     }
                 }
@@ -641,7 +2113,10 @@ impl Client {
             State::Active(ref state) => {
                 if now_ms >= state.timeout_time_ms {
                     // Signal timeout and forget connection
+                    self.record_summary(CloseReason::Timeout);
+                    self.signal_summary();
                     self.events_out.push(Event::Error(ErrorType::Timeout));
+                    self.close_reason = CloseReason::Timeout;
                     self.state = State::Fin;
                 }
             }
@@ -649,12 +2124,17 @@ impl Client {
                 if now_ms >= state.resend_time_ms {
                     if state.resend_count > 0 {
                         // Resend disconnect request
-                        let _ = self.socket.send(&state.request_bytes);
+                        let _ = self.socket.send_to(&state.request_bytes, self.remote_addr);
                         state.resend_time_ms = now_ms + DISCONNECT_RESEND_INTERVAL_MS;
                         state.resend_count -= 1;
                     } else {
                         // Signal timeout and forget connection
+                        if let Some(ref mut summary) = self.session_summary {
+                            summary.reason = CloseReason::Timeout;
+                        }
+                        self.signal_summary();
                         self.events_out.push(Event::Error(ErrorType::Timeout));
+                        self.close_reason = CloseReason::Timeout;
                         self.state = State::Fin;
                     }
                 }
@@ -667,6 +2147,37 @@ impl Client {
             }
             State::Fin => (),
         }
+
+        self.signal_state_change();
+    }
+
+    // Signals the current connection state if it differs from the last state signaled
+    fn signal_state_change(&mut self) {
+        let state = self.connection_state();
+
+        if state != self.signaled_state {
+            self.signaled_state = state;
+
+            if self.config.endpoint_config.state_events {
+                self.events_out.push(Event::StateChange(state));
+            }
+        }
+    }
+
+    // Records the final statistics of an active connection which is about to end
+    fn record_summary(&mut self, reason: CloseReason) {
+        if let State::Active(ref state) = self.state {
+            if self.config.endpoint_config.session_summaries {
+                self.session_summary = Some(state.half_connection.session_summary(reason));
+            }
+        }
+    }
+
+    // Signals the recorded statistics of a connection which has ended
+    fn signal_summary(&mut self) {
+        if let Some(summary) = self.session_summary.take() {
+            self.events_out.push(Event::Summary(Box::new(summary)));
+        }
     }
 
     fn step_if_active(&mut self, now_ms: u64) {
@@ -684,17 +2195,60 @@ impl Client {
 
                     // Attempt to close the connection
                     let request_bytes = frame::Frame::DisconnectFrame(frame::DisconnectFrame {}).write();
-                    let _ = self.socket.send(&request_bytes);
+                    let _ = self.socket.send_to(&request_bytes, self.remote_addr);
+
+                    self.record_summary(CloseReason::Disconnect);
 
                     self.state = State::Closing(ClosingState {
                         request_bytes,
                         resend_time_ms: now_ms + DISCONNECT_RESEND_INTERVAL_MS,
                         resend_count: DISCONNECT_RESEND_COUNT,
                     });
+
+                    self.signal_state_change();
                 } else {
                     // Process and signal received packets
                     state.half_connection.step();
                     state.half_connection.receive(&mut PacketReceiveSink::new(&mut self.events_out));
+
+                    if self.config.endpoint_config.cadence_events {
+                        if let Some(cadence) = state.half_connection.poll_cadence_change() {
+                            self.events_out.push(Event::CadenceChange(cadence));
+                        }
+                    }
+
+                    if self.config.endpoint_config.quality_events {
+                        if let Some(score) = state.half_connection.poll_quality_change() {
+                            self.events_out.push(Event::QualityChange(score));
+                        }
+                    }
+
+                    if let Some(usage) = state.half_connection.poll_window_limited() {
+                        logger::log(&mut self.config.logger, LogLevel::Warn, self.remote_addr,
+                                    format_args!("frame window stalled ({} of {} frames in flight)",
+                                                 usage.frames_in_flight, usage.frame_window_size));
+                        self.events_out.push(Event::WindowLimited(usage));
+                    }
+
+                    for (channel_id, signal) in state.half_connection.poll_backpressure() {
+                        self.events_out.push(Event::Backpressure(channel_id as usize, signal));
+                    }
+
+                    for (trace_id, outcome) in state.half_connection.poll_trace_outcomes() {
+                        logger::log(&mut self.config.logger, LogLevel::Debug, self.remote_addr,
+                                    format_args!("traced packet {:#018x}: {:?}", trace_id, outcome));
+                        self.events_out.push(Event::Trace(trace_id, outcome));
+                    }
+
+                    for (channel_id, tag) in state.half_connection.poll_confirmations() {
+                        self.events_out.push(Event::Delivered(channel_id as usize, tag));
+                    }
+
+                    if let Some(drop_count) = state.half_connection.poll_reset() {
+                        logger::log(&mut self.config.logger, LogLevel::Info, self.remote_addr,
+                                    format_args!("connection reset ({} packets dropped)", drop_count));
+                        self.events_out.push(Event::Reset(drop_count));
+                    }
                 }
             }
             _ => (),
@@ -702,12 +2256,53 @@ impl Client {
     }
 
     fn flush_if_active(&mut self) {
+        let now_ms = self.now_ms();
+
         match self.state {
             State::Active(ref mut state) => {
-                let ref mut data_sink = UdpFrameSink::new(&self.socket, self.remote_addr);
-                state.half_connection.flush(data_sink);
+                if state.groups_enabled && !self.groups.acked && now_ms >= self.groups.resend_time_ms {
+                    let request = frame::Frame::GroupFrame(frame::GroupFrame {
+                        version: self.groups.version,
+                        groups: self.groups.names.clone(),
+                    });
+                    let _ = self.socket.send_to(&request.write(), self.remote_addr);
+
+                    self.groups.resend_time_ms = now_ms + GROUP_RESEND_INTERVAL_MS;
+                }
+
+                let ref mut data_sink = paths::PathFrameSink::new(&self.socket, self.remote_addr);
+
+                let remote_addr = self.remote_addr;
+                let heartbeats_enabled = state.heartbeats_enabled;
+                let provider = &mut self.config.heartbeat_provider;
+                let heartbeat = || if heartbeats_enabled { provider.as_mut().and_then(|p| p(remote_addr)) } else { None };
+
+                if let Some(ref mut pacer) = self.config.pacer {
+                    state.half_connection.flush_paced(data_sink, heartbeat, pacer.as_mut(), remote_addr);
+                } else {
+                    state.half_connection.flush_with_heartbeat(data_sink, heartbeat);
+                }
             }
             _ => (),
         }
     }
 }
+
+#[cfg(unix)]
+impl<const CHANNELS: usize> std::os::unix::io::AsRawFd for Client<net::UdpSocket, CHANNELS> {
+    /// Returns the file descriptor of the client's UDP socket, which may be registered with an
+    /// external event loop (e.g. via `mio::unix::SourceFd`). The socket must not be read from or
+    /// written to directly.
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.socket.inner().primary().as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl<const CHANNELS: usize> std::os::windows::io::AsRawSocket for Client<net::UdpSocket, CHANNELS> {
+    /// Returns the handle of the client's UDP socket, which may be registered with an external
+    /// event loop. The socket must not be read from or written to directly.
+    fn as_raw_socket(&self) -> std::os::windows::io::RawSocket {
+        self.socket.inner().primary().as_raw_socket()
+    }
+}
@@ -0,0 +1,249 @@
+
+use std::collections::HashMap;
+use std::net;
+use std::sync::Arc;
+
+use crate::seal;
+use crate::frame;
+
+use crate::ChannelStats;
+use crate::LinkStats;
+use crate::SendHandle;
+use crate::SendMode;
+use crate::SendOptions;
+
+use super::Client;
+use super::Config;
+use super::Event;
+use super::State;
+
+/// Manages several outbound `uflow` connections which share a single UDP socket.
+///
+/// Each connection is represented by a [`Client`] object, and is identified by the address of its
+/// server. Inbound frames are read from the shared socket and dispatched to the client whose server
+/// address matches the source address of each frame; frames from any other address are ignored.
+/// This allows a client behind a NAT to communicate with multiple servers using a single port.
+pub struct Multiplexer {
+    socket: Arc<net::UdpSocket>,
+    local_addr: net::SocketAddr,
+
+    clients: HashMap<net::SocketAddr, Client>,
+
+    events_out: Vec<(net::SocketAddr, Event)>,
+}
+
+impl Multiplexer {
+    /// Opens a non-blocking UDP socket bound to the provided address, and returns a corresponding
+    /// [`Multiplexer`](Self) object with no connections.
+    ///
+    /// # Error Handling
+    ///
+    /// Any errors resulting from socket initialization are forwarded to the caller.
+    pub fn bind<A: net::ToSocketAddrs>(addr: A) -> Result<Self, std::io::Error> {
+        let socket = net::UdpSocket::bind(addr)?;
+
+        socket.set_nonblocking(true)?;
+
+        let local_addr = socket.local_addr()?;
+
+        Ok(Self {
+            socket: Arc::new(socket),
+            local_addr,
+
+            clients: HashMap::new(),
+
+            events_out: Vec::new(),
+        })
+    }
+
+    /// Equivalent to calling [`Multiplexer::bind()`](Self::bind) with address
+    /// `(std::net::Ipv4Addr::UNSPECIFIED, 0)`.
+    pub fn bind_any_ipv4() -> Result<Self, std::io::Error> {
+        Self::bind((net::Ipv4Addr::UNSPECIFIED, 0))
+    }
+
+    /// Equivalent to calling [`Multiplexer::bind()`](Self::bind) with address
+    /// `(std::net::Ipv6Addr::UNSPECIFIED, 0)`.
+    pub fn bind_any_ipv6() -> Result<Self, std::io::Error> {
+        Self::bind((net::Ipv6Addr::UNSPECIFIED, 0))
+    }
+
+    /// Initiates a connection to the server at the provided destination address using the shared
+    /// socket. Returns the resolved server address, which identifies the new connection.
+    ///
+    /// # Error Handling
+    ///
+    /// Any errors resulting from address resolution are forwarded to the caller. If a connection to
+    /// the same server address already exists and has not yet been forgotten, an error of kind
+    /// [`AlreadyExists`](std::io::ErrorKind::AlreadyExists) is returned. This function will panic
+    /// if the provided client configuration is not valid.
+    pub fn connect<A: net::ToSocketAddrs>(
+        &mut self,
+        dst_addr: A,
+        config: Config
+    ) -> Result<net::SocketAddr, std::io::Error> {
+        assert!(config.is_valid(), "invalid client config");
+
+        let remote_addr = dst_addr.to_socket_addrs()?.next().expect("expected at least one socket addresses");
+
+        if self.clients.contains_key(&remote_addr) {
+            return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "connection to server already exists"));
+        }
+
+        let client = Client::new(Arc::clone(&self.socket), remote_addr, config)?;
+
+        self.clients.insert(remote_addr, client);
+
+        Ok(remote_addr)
+    }
+
+    /// Flushes outbound frames for each connection, then processes as many inbound frames as
+    /// possible from the shared socket. Returns an iterator of [`Event`] objects, each paired with
+    /// the address of the server it concerns.
+    ///
+    /// Once a connection has terminated and all of its events have been returned, it is forgotten,
+    /// and its server address may be used in a subsequent call to
+    /// [`Multiplexer::connect()`](Self::connect).
+    ///
+    /// *Note*: All events are considered delivered, even if the iterator is not consumed until the
+    /// end.
+    pub fn step(&mut self) -> impl Iterator<Item = (net::SocketAddr, Event)> {
+        for client in self.clients.values_mut() {
+            client.flush_if_active();
+        }
+
+        self.handle_frames();
+
+        for (&remote_addr, client) in self.clients.iter_mut() {
+            let now_ms = client.now_ms();
+
+            client.handle_events(now_ms);
+            client.step_if_active(now_ms);
+
+            self.events_out.extend(client.events_out.drain(..).map(|event| (remote_addr, event)));
+        }
+
+        self.clients.retain(|_, client| !matches!(client.state, State::Fin));
+
+        std::mem::take(&mut self.events_out).into_iter()
+    }
+
+    /// Sends as many outbound frames as possible for each connection.
+    pub fn flush(&mut self) {
+        for client in self.clients.values_mut() {
+            client.flush_if_active();
+        }
+    }
+
+    /// Returns the amount of time after which [`step()`](Self::step) and [`flush()`](Self::flush)
+    /// should next be called, assuming no frames are received in the meantime (see
+    /// [`Client::next_timeout()`]). Returns `None` if there are no connections.
+    pub fn next_timeout(&self) -> Option<std::time::Duration> {
+        self.clients.values().filter_map(|client| client.next_timeout()).min()
+    }
+
+    /// Returns the local address of the shared UDP socket.
+    pub fn local_address(&self) -> net::SocketAddr {
+        self.local_addr
+    }
+
+    /// Returns the number of connections which have not yet been forgotten.
+    pub fn client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Returns a reference to the connection with the given server address, if one exists.
+    pub fn client(&self, remote_addr: &net::SocketAddr) -> Option<&Client> {
+        self.clients.get(remote_addr)
+    }
+
+    /// Returns a [`MultiplexedClient`] handle to the connection with the given server address, if
+    /// one exists. The handle may be used to send packets or disconnect, but not to step the
+    /// connection, which is done by [`Multiplexer::step()`](Self::step).
+    pub fn client_mut(&mut self, remote_addr: &net::SocketAddr) -> Option<MultiplexedClient<'_>> {
+        self.clients.get_mut(remote_addr).map(|client| MultiplexedClient { client })
+    }
+
+    fn handle_frames(&mut self) {
+        let mut datagram_buf = [0; seal::MAX_DATAGRAM_SIZE];
+        let mut pending_acks: HashMap<net::SocketAddr, Vec<frame::AckFrame>> = HashMap::new();
+
+        while let Ok((datagram_size, src_addr)) = self.socket.recv_from(&mut datagram_buf) {
+            if let Some(client) = self.clients.get_mut(&src_addr) {
+                // Each client opens frames sealed with its own session keys
+                if let Some(frame_data) = client.socket.open(&mut datagram_buf[..datagram_size], src_addr) {
+                    let now_ms = client.now_ms();
+                    client.handle_frame_data(frame_data, pending_acks.entry(src_addr).or_default(), now_ms);
+                }
+            }
+        }
+
+        for (src_addr, frames) in pending_acks.into_iter() {
+            if let Some(client) = self.clients.get_mut(&src_addr) {
+                let now_ms = client.now_ms();
+                client.handle_acks(frames, now_ms);
+            }
+        }
+    }
+}
+
+/// A mutable handle to a connection which belongs to a [`Multiplexer`]. Only those [`Client`]
+/// operations which do not read from or write to the shared socket directly are available.
+pub struct MultiplexedClient<'a> {
+    client: &'a mut Client,
+}
+
+impl MultiplexedClient<'_> {
+    /// Enqueues a packet for delivery to the server. See [`Client::send()`].
+    pub fn send(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode) -> SendHandle {
+        self.client.send(data, channel_id, mode)
+    }
+
+    /// Enqueues a packet for delivery to the server with the given options. See
+    /// [`Client::send_with()`].
+    pub fn send_with(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, options: SendOptions) -> SendHandle {
+        self.client.send_with(data, channel_id, mode, options)
+    }
+
+    /// Gracefully terminates this connection once all packets have been sent. See
+    /// [`Client::disconnect()`].
+    pub fn disconnect(&mut self) {
+        self.client.disconnect();
+    }
+
+    /// Gracefully terminates this connection as soon as possible. See
+    /// [`Client::disconnect_now()`].
+    pub fn disconnect_now(&mut self) {
+        self.client.disconnect_now();
+    }
+
+    /// Returns a snapshot of statistics describing the current connection. See
+    /// [`Client::link_stats()`].
+    pub fn link_stats(&self) -> LinkStats {
+        self.client.link_stats()
+    }
+
+    /// Returns packet counters for the given channel. See [`Client::channel_stats()`].
+    pub fn channel_stats(&self, channel_id: usize) -> ChannelStats {
+        self.client.channel_stats(channel_id)
+    }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for Multiplexer {
+    /// Returns the file descriptor of the shared UDP socket, which may be registered with an
+    /// external event loop (e.g. via `mio::unix::SourceFd`). The socket must not be read from or
+    /// written to directly.
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.socket.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawSocket for Multiplexer {
+    /// Returns the handle of the shared UDP socket, which may be registered with an external event
+    /// loop. The socket must not be read from or written to directly.
+    fn as_raw_socket(&self) -> std::os::windows::io::RawSocket {
+        self.socket.as_raw_socket()
+    }
+}
@@ -0,0 +1,116 @@
+use std::cell::Cell;
+use std::io;
+use std::net;
+use std::sync::Arc;
+
+use crate::half_connection;
+use crate::seal::Sealed;
+use crate::Transport;
+
+// The transports over which a client communicates with its server, each bound to a different
+// local address. The first is the transport the client was created with, which may be shared with
+// other clients by a Multiplexer. Datagrams are sent over the selected path, and received from any.
+pub struct Paths<T> {
+    primary: Arc<T>,
+    additional: Vec<T>,
+    send_path: Cell<usize>,
+    // Path over which the most recent datagram was received
+    recv_path: Cell<usize>,
+}
+
+impl<T: Transport> Paths<T> {
+    pub fn new(primary: Arc<T>) -> Self {
+        Self {
+            primary,
+            additional: Vec::new(),
+            send_path: Cell::new(0),
+            recv_path: Cell::new(0),
+        }
+    }
+
+    pub fn primary(&self) -> &T {
+        &self.primary
+    }
+
+    pub fn len(&self) -> usize {
+        1 + self.additional.len()
+    }
+
+    pub fn add(&mut self, transport: T) -> usize {
+        self.additional.push(transport);
+        self.additional.len()
+    }
+
+    pub fn select(&self, path: usize) {
+        debug_assert!(path < self.len());
+        self.send_path.set(path);
+    }
+
+    pub fn recv_path(&self) -> usize {
+        self.recv_path.get()
+    }
+
+    fn get(&self, path: usize) -> &T {
+        if path == 0 {
+            &self.primary
+        } else {
+            &self.additional[path - 1]
+        }
+    }
+}
+
+impl<T: Transport> Transport for Paths<T> {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        self.get(self.send_path.get()).send_to(buf, addr)
+    }
+
+    // Errors from additional paths are treated as though they had nothing to receive, so that a
+    // failed interface does not interrupt the others
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        match self.primary.recv_from(buf) {
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => (),
+            result => {
+                self.recv_path.set(0);
+                return result;
+            }
+        }
+
+        for (index, transport) in self.additional.iter().enumerate() {
+            if let Ok(result) = transport.recv_from(buf) {
+                self.recv_path.set(index + 1);
+                return Ok(result);
+            }
+        }
+
+        Err(io::ErrorKind::WouldBlock.into())
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.primary.local_addr()
+    }
+}
+
+// Sends frames to the server over whichever path the half connection selects
+pub struct PathFrameSink<'a, T: Transport> {
+    socket: &'a Sealed<Paths<T>>,
+    address: net::SocketAddr,
+}
+
+impl<'a, T: Transport> PathFrameSink<'a, T> {
+    pub fn new(socket: &'a Sealed<Paths<T>>, address: net::SocketAddr) -> Self {
+        Self {
+            socket,
+            address,
+        }
+    }
+}
+
+impl<'a, T: Transport> half_connection::FrameSink for PathFrameSink<'a, T> {
+    fn send(&mut self, frame_data: &[u8]) -> io::Result<()> {
+        self.socket.send_to(frame_data, self.address).map(|_| ())
+    }
+
+    fn select_path(&mut self, path: usize) {
+        self.socket.inner().select(path);
+    }
+}
@@ -0,0 +1,104 @@
+
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time;
+
+/// A source of monotonic time, from which every endpoint timer (handshake resends, keepalives,
+/// timeouts, send rate allocation, etc.) is derived. See [`client::Config`](crate::client::Config)
+/// and [`server::Config`](crate::server::Config).
+pub trait Clock: Send + Sync {
+    /// Returns the time elapsed since an arbitrary, fixed point in time. Successive calls must
+    /// never return a smaller value.
+    fn now(&self) -> time::Duration;
+
+    /// Blocks the calling thread until the given duration has elapsed according to this clock.
+    /// Used by blocking functions such as
+    /// [`Client::flush_and_close()`](crate::client::Client::flush_and_close). By default, the
+    /// calling thread sleeps for the given duration.
+    fn sleep(&self, duration: time::Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+/// A [`Clock`] which follows the system's monotonic clock (see [`std::time::Instant`]). This is
+/// the default for all endpoints.
+#[derive(Clone,Copy,Debug)]
+pub struct SystemClock {
+    origin: time::Instant,
+}
+
+impl SystemClock {
+    /// Creates a clock which measures time from the present moment.
+    pub fn new() -> Self {
+        Self {
+            origin: time::Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> time::Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// A [`Clock`] which only advances when instructed to, allowing tests and simulations to skip
+/// arbitrary amounts of time instantly.
+///
+/// Clones of a virtual clock share the same time, so a single clock may drive several endpoints.
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let clock = uflow::VirtualClock::new();
+///
+/// let config = uflow::client::Config {
+///     clock: Arc::new(clock.clone()),
+///     .. Default::default()
+/// };
+///
+/// let mut client = uflow::client::Client::connect("127.0.0.1:8888", config).unwrap();
+///
+/// // Skip the entire connection handshake
+/// clock.advance(Duration::from_secs(60));
+///
+/// for event in client.step() {
+///     // Connection attempt has timed out
+/// #   let _ = event;
+/// }
+/// ```
+#[derive(Clone,Debug,Default)]
+pub struct VirtualClock {
+    now_ns: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    /// Creates a virtual clock which reads zero.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Advances the clock by the given duration.
+    pub fn advance(&self, duration: time::Duration) {
+        self.now_ns.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> time::Duration {
+        time::Duration::from_nanos(self.now_ns.load(Ordering::Relaxed))
+    }
+
+    // Blocking functions advance virtual time, rather than waiting for it to be advanced
+    fn sleep(&self, duration: time::Duration) {
+        self.advance(duration);
+    }
+}
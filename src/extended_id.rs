@@ -0,0 +1,72 @@
+
+// Frame IDs are truncated to 32 bits on the wire, but are tracked internally as 64-bit extended
+// IDs, which do not wrap in practice. An extended ID is reconstructed from a truncated ID by
+// selecting the value nearest to a reference ID (typically the base of a window) whose low 32 bits
+// match the truncated ID, as in appendix A.3 of RFC 9000.
+//
+// Packet IDs are not extended: they are only ever compared within a window of at most
+// MAX_PACKET_WINDOW_SIZE packets, which is far smaller than half of the 20-bit ID space, so the
+// modular arithmetic of packet_id orders them correctly across wraparound.
+
+pub type Type = u64;
+
+const SPAN: Type = 1 << 32;
+const HALF_SPAN: Type = SPAN / 2;
+const MASK: Type = SPAN - 1;
+
+pub fn extend(reference: Type, id: u32) -> Type {
+    let candidate = (reference & !MASK) | id as Type;
+
+    if candidate.saturating_add(HALF_SPAN) <= reference && candidate <= u64::MAX - SPAN {
+        candidate + SPAN
+    } else if candidate > reference.saturating_add(HALF_SPAN) && candidate >= SPAN {
+        candidate - SPAN
+    } else {
+        candidate
+    }
+}
+
+pub fn truncate(id: Type) -> u32 {
+    id as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic() {
+        assert_eq!(extend(0, 0), 0);
+        assert_eq!(extend(0, 5), 5);
+        assert_eq!(extend(100, 90), 90);
+        assert_eq!(extend(100, 110), 110);
+
+        // Values near zero cannot be extended backwards
+        assert_eq!(extend(5, u32::MAX), u32::MAX as u64);
+    }
+
+    #[test]
+    fn wraparound() {
+        let reference = (3u64 << 32) + u32::MAX as u64 - 2;
+
+        // Later IDs which have wrapped
+        assert_eq!(extend(reference, 0), 4u64 << 32);
+        assert_eq!(extend(reference, 10), (4u64 << 32) + 10);
+
+        // Earlier IDs which have not
+        assert_eq!(extend(reference, u32::MAX - 10), (3u64 << 32) + u32::MAX as u64 - 10);
+
+        // Earlier IDs relative to a reference which has wrapped
+        assert_eq!(extend(4u64 << 32, u32::MAX), (4u64 << 32) - 1);
+    }
+
+    #[test]
+    fn round_trip() {
+        for &reference in [ 0u64, 1 << 31, u32::MAX as u64, 7 << 32, (7 << 32) + 12345 ].iter() {
+            for delta in -1000i64 .. 1000 {
+                let id = (reference as i64 + delta).max(0) as u64;
+                assert_eq!(extend(reference, truncate(id)), id);
+            }
+        }
+    }
+}
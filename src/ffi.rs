@@ -0,0 +1,651 @@
+//! A C interface for embedding `uflow` in applications not written in Rust.
+//!
+//! Endpoints are created by [`uflow_server_bind()`] and [`uflow_client_connect()`], and are
+//! released by the corresponding `_free()` function. After each call to `_step()`, pending events
+//! are retrieved one at a time by calling `_poll_event()` until it returns `false`. Any data
+//! referenced by an event remains valid until the next call to `_poll_event()`, `_step()`, or
+//! `_free()` on the same endpoint.
+//!
+//! Endpoints use the system clock by default, but may instead be driven by a virtual clock which
+//! is advanced by the application; see [`uflow_virtual_clock_new()`].
+//!
+//! A C header may be generated from this module with a tool such as `cbindgen`. To produce a
+//! linkable library, build this crate with `--features ffi --crate-type staticlib` (or `cdylib`).
+//!
+//! All functions must be called from a single thread per endpoint. No function panics on invalid
+//! arguments which can be detected (e.g. null pointers, invalid channel IDs, or oversized packets):
+//! functions which return a value report them by returning `false` or null, and the remaining
+//! functions do nothing.
+
+use crate::client;
+use crate::server;
+use crate::SendMode;
+use crate::VirtualClock;
+
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::net;
+use std::os::raw::c_char;
+use std::sync::Arc;
+use std::time;
+
+/// The size of the address buffer of a [`UflowEvent`], including the terminating NUL.
+pub const UFLOW_ADDRESS_SIZE: usize = 64;
+
+/// A virtual clock which may be shared by several endpoints.
+pub struct UflowClock {
+    clock: VirtualClock,
+}
+
+/// A server, together with its pending events.
+pub struct UflowServer {
+    server: server::Server,
+    events: VecDeque<server::Event>,
+    current_data: Option<Box<[u8]>>,
+}
+
+/// A client, together with its pending events.
+pub struct UflowClient {
+    client: client::Client,
+    events: VecDeque<client::Event>,
+    current_data: Option<Box<[u8]>>,
+}
+
+/// Mirrors [`SendMode`].
+#[repr(C)]
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum UflowSendMode {
+    /// See [`SendMode::TimeSensitive`].
+    TimeSensitive,
+    /// See [`SendMode::Unreliable`].
+    Unreliable,
+    /// See [`SendMode::Persistent`].
+    Persistent,
+    /// See [`SendMode::Reliable`].
+    Reliable,
+    /// See [`SendMode::ReliableUnordered`].
+    ReliableUnordered,
+}
+
+impl From<UflowSendMode> for SendMode {
+    fn from(mode: UflowSendMode) -> Self {
+        match mode {
+            UflowSendMode::TimeSensitive => SendMode::TimeSensitive,
+            UflowSendMode::Unreliable => SendMode::Unreliable,
+            UflowSendMode::Persistent => SendMode::Persistent,
+            UflowSendMode::Reliable => SendMode::Reliable,
+            UflowSendMode::ReliableUnordered => SendMode::ReliableUnordered,
+        }
+    }
+}
+
+/// The kind of a [`UflowEvent`].
+#[repr(C)]
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum UflowEventKind {
+    /// A connection has been established.
+    Connect,
+    /// A connection has been closed normally.
+    Disconnect,
+    /// A packet has been received; its contents are given by `data` and `data_size`.
+    Receive,
+    /// A connection has been closed, or could not be established, due to an error.
+    Error,
+    /// An event which is not otherwise represented by this interface.
+    Other,
+}
+
+/// An event retrieved from a client or server.
+#[repr(C)]
+pub struct UflowEvent {
+    /// The kind of event.
+    pub kind: UflowEventKind,
+    /// The NUL-terminated address of the remote endpoint concerned, or an empty string if the
+    /// server no longer knows the client concerned.
+    pub address: [c_char; UFLOW_ADDRESS_SIZE],
+    /// The ID of the client concerned (see [`ClientId`](server::ClientId)), or zero if the event
+    /// concerns no client, or was retrieved from a client.
+    pub client_id: u32,
+    /// The event's data, or null if it has none.
+    pub data: *const u8,
+    /// The size of the event's data, in bytes.
+    pub data_size: usize,
+}
+
+impl UflowEvent {
+    fn new(kind: UflowEventKind, address: Option<net::SocketAddr>, client_id: u32, data: Option<&[u8]>) -> Self {
+        let mut event = Self {
+            kind,
+            address: [0; UFLOW_ADDRESS_SIZE],
+            client_id,
+            data: data.map_or(std::ptr::null(), |data| data.as_ptr()),
+            data_size: data.map_or(0, |data| data.len()),
+        };
+
+        let address = address.map_or(String::new(), |address| address.to_string());
+        let size = address.len().min(UFLOW_ADDRESS_SIZE - 1);
+
+        for (dst, &src) in event.address.iter_mut().zip(address.as_bytes()[.. size].iter()) {
+            *dst = src as c_char;
+        }
+
+        event
+    }
+}
+
+unsafe fn parse_address(address: *const c_char) -> Option<net::SocketAddr> {
+    if address.is_null() {
+        return None;
+    }
+    CStr::from_ptr(address).to_str().ok()?.parse().ok()
+}
+
+unsafe fn packet_data(data: *const u8, data_size: usize) -> Option<Box<[u8]>> {
+    if data_size == 0 {
+        return Some(Box::new([]));
+    }
+    if data.is_null() {
+        return None;
+    }
+    Some(std::slice::from_raw_parts(data, data_size).into())
+}
+
+/// Creates a new virtual clock whose time begins at zero.
+#[no_mangle]
+pub extern "C" fn uflow_virtual_clock_new() -> *mut UflowClock {
+    Box::into_raw(Box::new(UflowClock { clock: VirtualClock::new() }))
+}
+
+/// Advances a virtual clock by the given number of milliseconds.
+///
+/// # Safety
+///
+/// `clock` must have been returned by [`uflow_virtual_clock_new()`], and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_virtual_clock_advance(clock: *const UflowClock, ms: u64) {
+    if clock.is_null() {
+        return;
+    }
+    (*clock).clock.advance(time::Duration::from_millis(ms));
+}
+
+/// Frees a virtual clock. Endpoints which use the clock retain their own reference to it.
+///
+/// # Safety
+///
+/// `clock` must be null, or have been returned by [`uflow_virtual_clock_new()`] and not yet
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_virtual_clock_free(clock: *mut UflowClock) {
+    if !clock.is_null() {
+        drop(Box::from_raw(clock));
+    }
+}
+
+/// Creates a server bound to the given address, e.g. `"0.0.0.0:8888"`, with the default
+/// configuration. If `clock` is not null, the server is driven by that virtual clock.
+///
+/// Returns null if the address is invalid, or if the socket could not be bound.
+///
+/// # Safety
+///
+/// `address` must be a valid NUL-terminated string. `clock` must be null, or a valid clock.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_server_bind(address: *const c_char, clock: *const UflowClock) -> *mut UflowServer {
+    let address = match parse_address(address) {
+        Some(address) => address,
+        None => return std::ptr::null_mut(),
+    };
+
+    let mut config = server::Config::default();
+
+    if !clock.is_null() {
+        config.clock = Arc::new((*clock).clock.clone());
+    }
+
+    match server::Server::bind(address, config) {
+        Ok(server) => Box::into_raw(Box::new(UflowServer {
+            server,
+            events: VecDeque::new(),
+            current_data: None,
+        })),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Frees a server, closing its socket without notifying its clients.
+///
+/// # Safety
+///
+/// `server` must be null, or have been returned by [`uflow_server_bind()`] and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_server_free(server: *mut UflowServer) {
+    if !server.is_null() {
+        drop(Box::from_raw(server));
+    }
+}
+
+/// Processes received frames and timeouts, queueing any resulting events. See
+/// [`Server::step()`](server::Server::step).
+///
+/// # Safety
+///
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_server_step(server: *mut UflowServer) {
+    if server.is_null() {
+        return;
+    }
+    let server = &mut *server;
+    server.current_data = None;
+    let events = server.server.step().collect::<Vec<_>>();
+    server.events.extend(events);
+}
+
+/// Sends pending outbound frames. See [`Server::flush()`](server::Server::flush).
+///
+/// # Safety
+///
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_server_flush(server: *mut UflowServer) {
+    if server.is_null() {
+        return;
+    }
+    (*server).server.flush();
+}
+
+/// Removes the oldest pending event and writes it to `event`. Returns `false`, leaving `event`
+/// unmodified, if there are no pending events.
+///
+/// # Safety
+///
+/// `server` must be a valid server, and `event` must point to writable memory for a
+/// [`UflowEvent`].
+#[no_mangle]
+pub unsafe extern "C" fn uflow_server_poll_event(server: *mut UflowServer, event: *mut UflowEvent) -> bool {
+    if server.is_null() || event.is_null() {
+        return false;
+    }
+
+    let server = &mut *server;
+
+    let (kind, client_id, data) = match server.events.pop_front() {
+        Some(server::Event::Connect(client_id)) => (UflowEventKind::Connect, client_id, None),
+        Some(server::Event::Disconnect(client_id)) => (UflowEventKind::Disconnect, client_id, None),
+        Some(server::Event::Receive(client_id, data)) => (UflowEventKind::Receive, client_id, Some(data)),
+        Some(server::Event::Error(client_id, _)) => (UflowEventKind::Error, client_id, None),
+        Some(server::Event::HandshakeError(address, _)) => {
+            event.write(UflowEvent::new(UflowEventKind::Error, Some(address), 0, None));
+            return true;
+        }
+        Some(server::Event::ConnectionRefused(address, _)) => {
+            event.write(UflowEvent::new(UflowEventKind::Other, Some(address), 0, None));
+            return true;
+        }
+        Some(server::Event::Migrate(client_id, ..)) |
+        Some(server::Event::CadenceChange(client_id, _)) |
+        Some(server::Event::WindowLimited(client_id, _)) |
+        Some(server::Event::StateChange(client_id, _)) |
+        Some(server::Event::QualityChange(client_id, _)) |
+        Some(server::Event::Backpressure(client_id, ..)) |
+        Some(server::Event::CustomFrame(client_id, ..)) |
+        Some(server::Event::Heartbeat(client_id, _)) |
+        Some(server::Event::Summary(client_id, _)) |
+        Some(server::Event::Trace(client_id, ..)) |
+        Some(server::Event::Delivered(client_id, ..)) |
+        Some(server::Event::Reset(client_id, _)) => (UflowEventKind::Other, client_id, None),
+        None => return false,
+    };
+
+    let address = server.server.client(client_id).map(|client| client.borrow().address());
+
+    server.current_data = data;
+    event.write(UflowEvent::new(kind, address, client_id.0, server.current_data.as_deref()));
+
+    true
+}
+
+/// Enqueues a packet for sending to the client with the given ID. Returns `false` if no such
+/// client exists, if `channel_id` does not refer to a valid channel, or if `data_size` exceeds the
+/// maximum packet size. See [`RemoteClient::send()`](server::RemoteClient::send).
+///
+/// # Safety
+///
+/// `server` must be a valid server, and `data` must point to `data_size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_server_send(server: *mut UflowServer, client_id: u32,
+                                           data: *const u8, data_size: usize,
+                                           channel_id: usize, mode: UflowSendMode) -> bool {
+    if server.is_null() {
+        return false;
+    }
+
+    let client = (*server).server.client(server::ClientId(client_id));
+
+    match (client, packet_data(data, data_size)) {
+        (Some(client), Some(data)) => {
+            let mut client = client.borrow_mut();
+
+            if channel_id >= client.channel_count() || data.len() > client.max_packet_size() {
+                return false;
+            }
+
+            client.send(data, channel_id, mode.into());
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Disconnects the client with the given ID once its pending packets have been sent. Returns
+/// `false` if no such client exists. See
+/// [`RemoteClient::disconnect()`](server::RemoteClient::disconnect).
+///
+/// # Safety
+///
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_server_disconnect(server: *mut UflowServer, client_id: u32) -> bool {
+    if server.is_null() {
+        return false;
+    }
+
+    match (*server).server.client(server::ClientId(client_id)) {
+        Some(client) => {
+            client.borrow_mut().disconnect();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Creates a client which connects to the server at the given address, e.g. `"127.0.0.1:8888"`,
+/// with the default configuration. If `clock` is not null, the client is driven by that virtual
+/// clock.
+///
+/// Returns null if the address is invalid, or if a socket could not be bound.
+///
+/// # Safety
+///
+/// `address` must be a valid NUL-terminated string. `clock` must be null, or a valid clock.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_client_connect(address: *const c_char, clock: *const UflowClock) -> *mut UflowClient {
+    let address = match parse_address(address) {
+        Some(address) => address,
+        None => return std::ptr::null_mut(),
+    };
+
+    let mut config = client::Config::default();
+
+    if !clock.is_null() {
+        config.clock = Arc::new((*clock).clock.clone());
+    }
+
+    match client::Client::connect(address, config) {
+        Ok(client) => Box::into_raw(Box::new(UflowClient {
+            client,
+            events: VecDeque::new(),
+            current_data: None,
+        })),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Frees a client, closing its socket without notifying the server.
+///
+/// # Safety
+///
+/// `client` must be null, or have been returned by [`uflow_client_connect()`] and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_client_free(client: *mut UflowClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+/// Processes received frames and timeouts, queueing any resulting events. See
+/// [`Client::step()`](client::Client::step).
+///
+/// # Safety
+///
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_client_step(client: *mut UflowClient) {
+    if client.is_null() {
+        return;
+    }
+    let client = &mut *client;
+    client.current_data = None;
+    let events = client.client.step().collect::<Vec<_>>();
+    client.events.extend(events);
+}
+
+/// Sends pending outbound frames. See [`Client::flush()`](client::Client::flush).
+///
+/// # Safety
+///
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_client_flush(client: *mut UflowClient) {
+    if client.is_null() {
+        return;
+    }
+    (*client).client.flush();
+}
+
+/// Removes the oldest pending event and writes it to `event`. Returns `false`, leaving `event`
+/// unmodified, if there are no pending events. The event's address is always that of the server.
+///
+/// # Safety
+///
+/// `client` must be a valid client, and `event` must point to writable memory for a
+/// [`UflowEvent`].
+#[no_mangle]
+pub unsafe extern "C" fn uflow_client_poll_event(client: *mut UflowClient, event: *mut UflowEvent) -> bool {
+    if client.is_null() || event.is_null() {
+        return false;
+    }
+
+    let client = &mut *client;
+
+    let (kind, data) = match client.events.pop_front() {
+        Some(client::Event::Connect) => (UflowEventKind::Connect, None),
+        Some(client::Event::Disconnect) => (UflowEventKind::Disconnect, None),
+        Some(client::Event::Receive(data)) => (UflowEventKind::Receive, Some(data)),
+        Some(client::Event::Error(_)) => (UflowEventKind::Error, None),
+        Some(_) => (UflowEventKind::Other, None),
+        None => return false,
+    };
+
+    client.current_data = data;
+    event.write(UflowEvent::new(kind, Some(client.client.remote_address()), 0, client.current_data.as_deref()));
+
+    true
+}
+
+/// Enqueues a packet for sending to the server. Returns `false` if `channel_id` does not refer to
+/// a valid channel, or if `data_size` exceeds the maximum packet size. See
+/// [`Client::send()`](client::Client::send).
+///
+/// # Safety
+///
+/// `client` must be a valid client, and `data` must point to `data_size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_client_send(client: *mut UflowClient, data: *const u8, data_size: usize,
+                                           channel_id: usize, mode: UflowSendMode) -> bool {
+    if client.is_null() {
+        return false;
+    }
+
+    let client = &mut (*client).client;
+
+    match packet_data(data, data_size) {
+        Some(data) if channel_id < client.channel_count() && data.len() <= client.max_packet_size() => {
+            client.send(data, channel_id, mode.into());
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Disconnects from the server once all pending packets have been sent. See
+/// [`Client::disconnect()`](client::Client::disconnect).
+///
+/// # Safety
+///
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_client_disconnect(client: *mut UflowClient) {
+    if client.is_null() {
+        return;
+    }
+    (*client).client.disconnect();
+}
+
+/// Blocks until all pending packets have been acknowledged and the client has disconnected, or
+/// until `timeout_ms` milliseconds have elapsed. Returns `true` if all packets were acknowledged.
+/// See [`Client::flush_and_close()`](client::Client::flush_and_close).
+///
+/// # Safety
+///
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_client_flush_and_close(client: *mut UflowClient, timeout_ms: u64) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    (*client).client.flush_and_close(time::Duration::from_millis(timeout_ms))
+}
+
+/// Returns `true` if the client is currently connected. See
+/// [`Client::is_active()`](client::Client::is_active).
+///
+/// # Safety
+///
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn uflow_client_is_active(client: *const UflowClient) -> bool {
+    if client.is_null() {
+        return false;
+    }
+    (*client).client.is_active()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+
+    unsafe fn poll_server(server: *mut UflowServer) -> Vec<(UflowEventKind, u32, Vec<u8>)> {
+        let mut events = Vec::new();
+        let mut event = MaybeUninit::<UflowEvent>::uninit();
+
+        while uflow_server_poll_event(server, event.as_mut_ptr()) {
+            let event = event.assume_init_ref();
+            let data = if event.data.is_null() { Vec::new() } else { std::slice::from_raw_parts(event.data, event.data_size).to_vec() };
+            events.push((event.kind, event.client_id, data));
+        }
+
+        events
+    }
+
+    unsafe fn poll_client(client: *mut UflowClient) -> Vec<(UflowEventKind, Vec<u8>)> {
+        let mut events = Vec::new();
+        let mut event = MaybeUninit::<UflowEvent>::uninit();
+
+        while uflow_client_poll_event(client, event.as_mut_ptr()) {
+            let event = event.assume_init_ref();
+            let data = if event.data.is_null() { Vec::new() } else { std::slice::from_raw_parts(event.data, event.data_size).to_vec() };
+            events.push((event.kind, data));
+        }
+
+        events
+    }
+
+    #[test]
+    fn null_arguments() {
+        unsafe {
+            let mut event = MaybeUninit::<UflowEvent>::uninit();
+
+            assert!(uflow_server_bind(std::ptr::null(), std::ptr::null()).is_null());
+            assert!(uflow_client_connect(std::ptr::null(), std::ptr::null()).is_null());
+
+            uflow_virtual_clock_advance(std::ptr::null(), 10);
+            uflow_server_step(std::ptr::null_mut());
+            uflow_server_flush(std::ptr::null_mut());
+            uflow_client_step(std::ptr::null_mut());
+            uflow_client_flush(std::ptr::null_mut());
+            uflow_client_disconnect(std::ptr::null_mut());
+
+            assert!(!uflow_server_poll_event(std::ptr::null_mut(), event.as_mut_ptr()));
+            assert!(!uflow_server_send(std::ptr::null_mut(), 1, std::ptr::null(), 0, 0, UflowSendMode::Reliable));
+            assert!(!uflow_server_disconnect(std::ptr::null_mut(), 1));
+            assert!(!uflow_client_poll_event(std::ptr::null_mut(), event.as_mut_ptr()));
+            assert!(!uflow_client_send(std::ptr::null_mut(), std::ptr::null(), 0, 0, UflowSendMode::Reliable));
+            assert!(!uflow_client_flush_and_close(std::ptr::null_mut(), 0));
+            assert!(!uflow_client_is_active(std::ptr::null()));
+        }
+    }
+
+    #[test]
+    fn connect_and_exchange() {
+        unsafe {
+            let address = CString::new("127.0.0.1:5741").unwrap();
+
+            assert!(uflow_server_bind(CString::new("not an address").unwrap().as_ptr(), std::ptr::null()).is_null());
+
+            let clock = uflow_virtual_clock_new();
+            let server = uflow_server_bind(address.as_ptr(), clock);
+            let client = uflow_client_connect(address.as_ptr(), clock);
+
+            assert!(!server.is_null());
+            assert!(!client.is_null());
+
+            let mut server_events = Vec::new();
+            let mut client_events = Vec::new();
+
+            for _ in 0 .. 100 {
+                uflow_server_step(server);
+
+                for (kind, client_id, data) in poll_server(server) {
+                    if kind == UflowEventKind::Connect {
+                        assert_ne!(client_id, 0);
+                        assert!(!uflow_server_send(server, client_id, [ 0, 1, 2 ].as_ptr(), 3, 64, UflowSendMode::Reliable));
+                        assert!(uflow_server_send(server, client_id, [ 0, 1, 2 ].as_ptr(), 3, 0, UflowSendMode::Reliable));
+                    }
+                    server_events.push((kind, data));
+                }
+
+                uflow_client_step(client);
+
+                for (kind, data) in poll_client(client) {
+                    if kind == UflowEventKind::Connect {
+                        assert!(!uflow_client_send(client, [ 3, 4, 5 ].as_ptr(), 3, 64, UflowSendMode::Reliable));
+                        assert!(!uflow_client_send(client, std::ptr::null(), 3, 0, UflowSendMode::Reliable));
+                        assert!(uflow_client_send(client, [ 3, 4, 5 ].as_ptr(), 3, 0, UflowSendMode::Reliable));
+                    }
+                    client_events.push((kind, data));
+                }
+
+                uflow_server_flush(server);
+                uflow_client_flush(client);
+
+                uflow_virtual_clock_advance(clock, 10);
+
+                std::thread::sleep(time::Duration::from_millis(1));
+            }
+
+            assert!(uflow_client_is_active(client));
+
+            assert_eq!(server_events, vec![ (UflowEventKind::Connect, vec![]), (UflowEventKind::Receive, vec![ 3, 4, 5 ]) ]);
+            assert_eq!(client_events, vec![ (UflowEventKind::Connect, vec![]), (UflowEventKind::Receive, vec![ 0, 1, 2 ]) ]);
+
+            uflow_client_free(client);
+            uflow_server_free(server);
+            uflow_virtual_clock_free(clock);
+        }
+    }
+}
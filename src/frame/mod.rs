@@ -8,6 +8,30 @@ pub struct HandshakeSynFrame {
     pub max_receive_rate: u32,
     pub max_packet_size: u32,
     pub max_receive_alloc: u32,
+    pub features: u8,
+    // Zero if the sender does not advertise a receive window
+    pub max_receive_window: u32,
+    // Echoed from a handshake cookie frame, if the server has sent one
+    pub cookie: Option<HandshakeCookie>,
+    // Opaque application data, empty if none was given
+    pub connect_token: Box<[u8]>,
+    // Ephemeral public key, if the client requests a key exchange
+    pub key_share: Option<[u8; 32]>,
+    pub ext_features: u8,
+    // Zero unless EXT_FEATURE_CHANNEL_COUNT is advertised
+    pub channel_count: u16,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct HandshakeCookie {
+    pub timestamp: u64,
+    pub mac: u64,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct HandshakeCookieFrame {
+    pub nonce_ack: u32,
+    pub cookie: HandshakeCookie,
 }
 
 #[derive(Clone,Debug,PartialEq)]
@@ -17,6 +41,20 @@ pub struct HandshakeSynAckFrame {
     pub max_receive_rate: u32,
     pub max_packet_size: u32,
     pub max_receive_alloc: u32,
+    pub features: u8,
+    // Zero if the sender does not advertise a receive window
+    pub max_receive_window: u32,
+    // Present if the server accepted a key exchange
+    pub key_share: Option<ServerKeyShare>,
+    pub ext_features: u8,
+    // Assigned by the server if connection IDs were negotiated, zero otherwise
+    pub connection_id: u32,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct ServerKeyShare {
+    pub ephemeral_key: [u8; 32],
+    pub static_key: [u8; 32],
 }
 
 #[derive(Clone,Debug,PartialEq)]
@@ -29,6 +67,7 @@ pub enum HandshakeErrorType {
     Version,
     Config,
     ServerFull,
+    Rejected,
 }
 
 #[derive(Clone,Debug,PartialEq)]
@@ -63,6 +102,7 @@ pub struct Datagram {
     pub channel_id: u8,
     pub window_parent_lead: u16,
     pub channel_parent_lead: u16,
+    pub keyed: bool,
     pub fragment_id: u16,
     pub fragment_id_last: u16,
     pub data: Box<[u8]>,
@@ -74,6 +114,7 @@ pub struct DatagramRef<'a> {
     pub channel_id: u8,
     pub window_parent_lead: u16,
     pub channel_parent_lead: u16,
+    pub keyed: bool,
     pub fragment_id: u16,
     pub fragment_id_last: u16,
     pub data: &'a [u8],
@@ -86,6 +127,7 @@ impl<'a> From<&'a Datagram> for DatagramRef<'a> {
             channel_id: obj.channel_id,
             window_parent_lead: obj.window_parent_lead,
             channel_parent_lead: obj.channel_parent_lead,
+            keyed: obj.keyed,
             fragment_id: obj.fragment_id,
             fragment_id_last: obj.fragment_id_last,
             data: &obj.data,
@@ -100,6 +142,7 @@ impl<'a> From<DatagramRef<'a>> for Datagram {
             channel_id: obj.channel_id,
             window_parent_lead: obj.window_parent_lead,
             channel_parent_lead: obj.channel_parent_lead,
+            keyed: obj.keyed,
             fragment_id: obj.fragment_id,
             fragment_id_last: obj.fragment_id_last,
             data: obj.data.into(),
@@ -114,10 +157,22 @@ pub struct DataFrame {
     pub datagrams: Vec<Datagram>,
 }
 
+// A run of consecutive packets which the sender has abandoned, and which the receiver need not
+// wait for. Each packet's window parent lead is `window_parent_lead` plus its offset in the run, or
+// zero if `window_parent_lead` is zero.
+#[derive(Clone,Debug,PartialEq)]
+pub struct DropRange {
+    pub base_id: u32,
+    pub count: u16,
+    pub window_parent_lead: u16,
+}
+
 #[derive(Clone,Debug,PartialEq)]
 pub struct SyncFrame {
     pub next_frame_id: Option<u32>,
     pub next_packet_id: Option<u32>,
+    pub heartbeat: Option<Box<[u8]>>,
+    pub drop_ranges: Vec<DropRange>,
 }
 
 #[derive(Clone,Debug,PartialEq)]
@@ -132,6 +187,72 @@ pub struct AckFrame {
     pub frame_window_base_id: u32,
     pub packet_window_base_id: u32,
     pub frame_acks: Vec<AckGroup>,
+    pub health: Option<ReceiverHealth>,
+    pub app_latency_ms: Option<u16>,
+}
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct ReceiverHealth {
+    pub buffered_bytes: u32,
+    pub backlogged: bool,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct FragmentAckGroup {
+    pub sequence_id: u32,
+    pub base_id: u16,
+    pub bitfield: u32,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct FragmentAckFrame {
+    pub fragment_acks: Vec<FragmentAckGroup>,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct GroupFrame {
+    pub version: u32,
+    pub groups: Vec<String>,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct GroupAckFrame {
+    pub version: u32,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct CustomFrame {
+    pub type_id: u8,
+    pub data: Box<[u8]>,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct PathChallengeFrame {
+    pub challenge: u32,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct PathResponseFrame {
+    pub challenge_ack: u32,
+    // Handshake nonces of the connection being migrated, as sent by the client and the server
+    pub client_nonce: u32,
+    pub server_nonce: u32,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct ResetFrame {
+    pub nonce: u32,
+    // First frame and packet sequence IDs the sender will send after the reset
+    pub frame_base_id: u32,
+    pub packet_base_id: u32,
+}
+
+#[derive(Clone,Debug,PartialEq)]
+pub struct ResetAckFrame {
+    pub nonce_ack: u32,
+    // First frame and packet sequence IDs the sender will send after the reset
+    pub frame_base_id: u32,
+    pub packet_base_id: u32,
 }
 
 #[derive(Clone,Debug,PartialEq)]
@@ -140,10 +261,19 @@ pub enum Frame {
     HandshakeSynAckFrame(HandshakeSynAckFrame),
     HandshakeAckFrame(HandshakeAckFrame),
     HandshakeErrorFrame(HandshakeErrorFrame),
+    HandshakeCookieFrame(HandshakeCookieFrame),
     DisconnectFrame(DisconnectFrame),
     DisconnectAckFrame(DisconnectAckFrame),
     DataFrame(DataFrame),
     SyncFrame(SyncFrame),
     AckFrame(AckFrame),
+    FragmentAckFrame(FragmentAckFrame),
+    GroupFrame(GroupFrame),
+    GroupAckFrame(GroupAckFrame),
+    CustomFrame(CustomFrame),
+    PathChallengeFrame(PathChallengeFrame),
+    PathResponseFrame(PathResponseFrame),
+    ResetFrame(ResetFrame),
+    ResetAckFrame(ResetAckFrame),
 }
 
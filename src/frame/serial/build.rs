@@ -1,6 +1,7 @@
 
 use super::DatagramRef;
 use super::AckGroup;
+use super::ReceiverHealth;
 
 use super::DATA_FRAME_ID;
 use super::DATA_FRAME_MAX_DATAGRAM_COUNT;
@@ -10,9 +11,13 @@ use super::DATAGRAM_HEADER_SIZE_LARGE;
 
 use super::ACK_FRAME_ID;
 use super::ACK_GROUP_SIZE;
+use super::ACK_FRAME_HEALTH_SIZE;
+use super::ACK_FRAME_HEALTH_FLAG;
+use super::ACK_FRAME_APP_LATENCY_SIZE;
+use super::ACK_FRAME_APP_LATENCY_FLAG;
 
 use super::FRAME_CRC_SIZE;
-use super::MAX_CHANNELS;
+use super::channel_to_wire;
 
 use super::crc;
 
@@ -22,27 +27,29 @@ use crate::packet_id;
 // are 16384 frames in the receive window, and a 20-bit sequence ID is sufficient to ensure no
 // packets are ambiguous within it
 
-// C: Channel ID          [0, 2^6)
+// C: Channel wire ID     [0, 2^6)
+// X: Wide channel ID     [0, 2^2), the high bits of the channel wire ID
 // S: Sequence ID         [0, 2^20)
 // D: Payload length      [0, 2^16)
 // W: Window parent lead  [0, 2^16)
 // H: Channel parent lead [0, 2^16)
+// K: Keyed flag          [0, 2)
 // F: Fragment ID         [0, 2^16)
 // L: Last fragment ID    [0, 2^16)
 
 // L == 0 => F == 0
 
-// If L == 0 && D < 64 && W < 128 && H < 256:
+// If L == 0 && K == 0 && D < 64 && W < 128 && H < 256 && X == 0:
 //   Micro header (6 bytes)
 //   0CDDDDDD  SSSSCCCC  SSSSSSSS  SSSSSSSS  CWWWWWWW  HHHHHHHH
 
 // Else if L == 0 && D < 256 && L == 0:
 //   Small header (9 bytes)
-//   10CCCCCC  DDDDDDDD  0000SSSS  SSSSSSSS  SSSSSSSS  WWWWWWWW  WWWWWWWW  HHHHHHHH  HHHHHHHH
+//   10CCCCCC  DDDDDDDD  KXX0SSSS  SSSSSSSS  SSSSSSSS  WWWWWWWW  WWWWWWWW  HHHHHHHH  HHHHHHHH
 
 // Else:
 //   Large header (14 bytes)
-//   11CCCCCC  DDDDDDDD  DDDDDDDD  0000SSSS  SSSSSSSS  SSSSSSSS  WWWWWWWW  WWWWWWWW  HHHHHHHH  HHHHHHHH  FFFFFFFF  FFFFFFFF  LLLLLLLL  LLLLLLLL
+//   11CCCCCC  DDDDDDDD  DDDDDDDD  KXX0SSSS  SSSSSSSS  SSSSSSSS  WWWWWWWW  WWWWWWWW  HHHHHHHH  HHHHHHHH  FFFFFFFF  FFFFFFFF  LLLLLLLL  LLLLLLLL
 
 pub struct DataFrameBuilder {
     buffer: Vec<u8>,
@@ -71,38 +78,44 @@ impl DataFrameBuilder {
     }
 
     pub fn add(&mut self, datagram: &DatagramRef) {
-        debug_assert!((datagram.channel_id as usize) < MAX_CHANNELS);
+        self.add_with(datagram, datagram.data.len(), |out| out.copy_from_slice(datagram.data));
+    }
+
+    // Adds a datagram whose payload of length `data_len`, in place of the datagram's own data, is
+    // written directly into the frame by `write_data`
+    pub fn add_with(&mut self, datagram: &DatagramRef, data_len: usize, write_data: impl FnOnce(&mut [u8])) {
         debug_assert!(packet_id::is_valid(datagram.sequence_id));
-        debug_assert!(datagram.data.len() <= u16::MAX as usize);
+        debug_assert!(data_len <= u16::MAX as usize);
         debug_assert!(self.count < DATA_FRAME_MAX_DATAGRAM_COUNT);
 
-        let data_len_u16 = datagram.data.len() as u16;
+        let data_len_u16 = data_len as u16;
+
+        let wire_id = channel_to_wire(datagram.channel_id);
 
         if datagram.fragment_id_last == 0 {
             debug_assert!(datagram.fragment_id == 0);
 
-            if data_len_u16 < 64 && datagram.window_parent_lead < 128 && datagram.channel_parent_lead < 256 {
+            if !datagram.keyed && data_len_u16 < 64 && datagram.window_parent_lead < 128 && datagram.channel_parent_lead < 256 && wire_id < 64 {
                 // Micro
                 let header = [
-                    data_len_u16 as u8 | (datagram.channel_id & 0x10) << 2,
-                    (datagram.sequence_id >> 12) as u8 & 0xF0 | datagram.channel_id & 0x0F,
+                    data_len_u16 as u8 | (wire_id & 0x10) << 2,
+                    (datagram.sequence_id >> 12) as u8 & 0xF0 | wire_id & 0x0F,
                     (datagram.sequence_id >>  8) as u8,
                     (datagram.sequence_id      ) as u8,
-                    datagram.window_parent_lead as u8 | (datagram.channel_id & 0x20) << 2,
+                    datagram.window_parent_lead as u8 | (wire_id & 0x20) << 2,
                     datagram.channel_parent_lead as u8,
                 ];
 
                 self.buffer.extend_from_slice(&header);
-                self.buffer.extend_from_slice(&datagram.data);
-                self.count += 1;
+                self.write_data(data_len, write_data);
 
                 return;
             } else if data_len_u16 < 256 {
                 // Small
                 let header = [
-                    datagram.channel_id | 0x80,
+                    wire_id & 0x3F | 0x80,
                     data_len_u16 as u8,
-                    (datagram.sequence_id >> 16) as u8,
+                    (datagram.sequence_id >> 16) as u8 | (datagram.keyed as u8) << 7 | (wire_id & 0xC0) >> 1,
                     (datagram.sequence_id >>  8) as u8,
                     (datagram.sequence_id      ) as u8,
                     (datagram.window_parent_lead >> 8) as u8,
@@ -112,8 +125,7 @@ impl DataFrameBuilder {
                 ];
 
                 self.buffer.extend_from_slice(&header);
-                self.buffer.extend_from_slice(&datagram.data);
-                self.count += 1;
+                self.write_data(data_len, write_data);
 
                 return;
             }
@@ -121,10 +133,10 @@ impl DataFrameBuilder {
 
         // Large
         let header = [
-            datagram.channel_id | 0xC0,
+            wire_id & 0x3F | 0xC0,
             (data_len_u16 >> 8) as u8,
             (data_len_u16     ) as u8,
-            (datagram.sequence_id >> 16) as u8,
+            (datagram.sequence_id >> 16) as u8 | (datagram.keyed as u8) << 7 | (wire_id & 0xC0) >> 1,
             (datagram.sequence_id >>  8) as u8,
             (datagram.sequence_id      ) as u8,
             (datagram.window_parent_lead >> 8) as u8,
@@ -138,7 +150,13 @@ impl DataFrameBuilder {
         ];
 
         self.buffer.extend_from_slice(&header);
-        self.buffer.extend_from_slice(&datagram.data);
+        self.write_data(data_len, write_data);
+    }
+
+    fn write_data(&mut self, data_len: usize, write_data: impl FnOnce(&mut [u8])) {
+        let offset = self.buffer.len();
+        self.buffer.resize(offset + data_len, 0);
+        write_data(&mut self.buffer[offset ..]);
         self.count += 1;
     }
 
@@ -170,10 +188,15 @@ impl DataFrameBuilder {
     }
 
     pub fn encoded_size(datagram: &DatagramRef) -> usize {
-        let data_len = datagram.data.len();
+        Self::encoded_size_with(datagram, datagram.data.len())
+    }
+
+    // As `encoded_size()`, but for a payload of length `data_len` (see `add_with()`)
+    pub fn encoded_size_with(datagram: &DatagramRef, data_len: usize) -> usize {
 
         if datagram.fragment_id_last == 0 {
-            if data_len < 64 && datagram.window_parent_lead < 128 && datagram.channel_parent_lead < 256 {
+            if !datagram.keyed && data_len < 64 && datagram.window_parent_lead < 128 && datagram.channel_parent_lead < 256 &&
+               channel_to_wire(datagram.channel_id) < 64 {
                 return DATAGRAM_HEADER_SIZE_MICRO + data_len;
             } else if data_len < 256 {
                 return DATAGRAM_HEADER_SIZE_SMALL + data_len;
@@ -186,10 +209,13 @@ impl DataFrameBuilder {
 pub struct AckFrameBuilder {
     buffer: Vec<u8>,
     count: u16,
+    health: Option<ReceiverHealth>,
+    app_latency_ms: Option<u16>,
 }
 
 impl AckFrameBuilder {
-    pub fn new(frame_window_base_id: u32, packet_window_base_id: u32) -> Self {
+    pub fn new(frame_window_base_id: u32, packet_window_base_id: u32, health: Option<ReceiverHealth>,
+               app_latency_ms: Option<u16>) -> Self {
         let header = vec![
             ACK_FRAME_ID,
             (frame_window_base_id >> 24) as u8,
@@ -207,6 +233,8 @@ impl AckFrameBuilder {
         Self {
             buffer: header,
             count: 0,
+            health,
+            app_latency_ms,
         }
     }
 
@@ -228,10 +256,35 @@ impl AckFrameBuilder {
     }
 
     pub fn build(mut self) -> Box<[u8]> {
+        let count = if let Some(health) = self.health {
+            self.buffer.extend_from_slice(&[
+                (health.buffered_bytes >> 24) as u8,
+                (health.buffered_bytes >> 16) as u8,
+                (health.buffered_bytes >>  8) as u8,
+                (health.buffered_bytes      ) as u8,
+                health.backlogged as u8,
+            ]);
+
+            self.count | ACK_FRAME_HEALTH_FLAG
+        } else {
+            self.count
+        };
+
+        let count = if let Some(app_latency_ms) = self.app_latency_ms {
+            self.buffer.extend_from_slice(&[
+                (app_latency_ms >> 8) as u8,
+                (app_latency_ms     ) as u8,
+            ]);
+
+            count | ACK_FRAME_APP_LATENCY_FLAG
+        } else {
+            count
+        };
+
         let count_offset_0 = 9;
         let count_offset_1 = 10;
-        self.buffer[count_offset_0] = (self.count >> 8) as u8;
-        self.buffer[count_offset_1] = (self.count     ) as u8;
+        self.buffer[count_offset_0] = (count >> 8) as u8;
+        self.buffer[count_offset_1] = (count     ) as u8;
 
         let data_bytes = self.buffer.as_slice();
         let crc = crc::compute(&data_bytes);
@@ -247,7 +300,10 @@ impl AckFrameBuilder {
     }
 
     pub fn size(&self) -> usize {
-        self.buffer.len() + FRAME_CRC_SIZE
+        self.buffer.len() +
+            if self.health.is_some() { ACK_FRAME_HEALTH_SIZE } else { 0 } +
+            if self.app_latency_ms.is_some() { ACK_FRAME_APP_LATENCY_SIZE } else { 0 } +
+            FRAME_CRC_SIZE
     }
 
     pub fn encoded_size(_frame_ack: &AckGroup) -> usize {
@@ -100,7 +100,86 @@ pub fn extend(initial_crc: u32, data: &[u8]) -> u32 {
 }
 
 pub fn compute(data: &[u8]) -> u32 {
-    extend(INITIAL_CRC, data)
+    match backend() {
+        #[cfg(target_arch = "x86_64")]
+        crate::ChecksumBackend::Pclmul => unsafe { pclmul::extend(INITIAL_CRC, data) },
+        _ => extend(INITIAL_CRC, data),
+    }
+}
+
+// Selects the fastest implementation supported by the current CPU, once
+pub fn backend() -> crate::ChecksumBackend {
+    static BACKEND: std::sync::OnceLock<crate::ChecksumBackend> = std::sync::OnceLock::new();
+
+    *BACKEND.get_or_init(|| {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("pclmulqdq") && is_x86_feature_detected!("sse2") {
+                return crate::ChecksumBackend::Pclmul;
+            }
+        }
+
+        crate::ChecksumBackend::Table
+    })
+}
+
+// The CRC register is a polynomial over GF(2), with bits in reflected order. Because the CRC of a
+// message depends only on the remainder of the message polynomial modulo the CRC polynomial, a
+// message may be folded 16 bytes at a time into a 128-bit polynomial with the same remainder, using
+// carry-less multiplication by constants of the form x^n mod P. The folded polynomial is then
+// reduced bytewise using the table.
+#[cfg(target_arch = "x86_64")]
+mod pclmul {
+    use std::arch::x86_64::*;
+
+    const POLYNOMIAL: u64 = 0x132c00699;
+
+    // Computes x^exp mod P, in reflected order with the x^0 coefficient at bit 63
+    const fn x_pow_mod(exp: u32) -> u64 {
+        let mut rem: u64 = 1;
+        let mut i = 0;
+        while i < exp {
+            rem <<= 1;
+            if rem & (1 << 32) != 0 {
+                rem ^= POLYNOMIAL;
+            }
+            i += 1;
+        }
+        rem.reverse_bits()
+    }
+
+    // A product of two reflected 64-bit operands is a reflected 128-bit value multiplied by x, so
+    // each exponent is one less than the fold distance
+    const K_HI: u64 = x_pow_mod(128 + 64 - 1);
+    const K_LO: u64 = x_pow_mod(128 - 1);
+
+    #[target_feature(enable = "pclmulqdq,sse2")]
+    pub unsafe fn extend(initial_crc: u32, data: &[u8]) -> u32 {
+        if data.len() < 32 {
+            return super::extend(initial_crc, data);
+        }
+
+        let mut blocks = data.chunks_exact(16);
+        let tail = blocks.remainder();
+
+        let k = _mm_set_epi64x(K_LO as i64, K_HI as i64);
+
+        // The initial register value is equivalent to XORing the first four bytes of the message
+        let mut acc = _mm_loadu_si128(blocks.next().unwrap().as_ptr() as *const __m128i);
+        acc = _mm_xor_si128(acc, _mm_cvtsi32_si128(!initial_crc as i32));
+
+        for block in blocks {
+            let block = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+            let hi = _mm_clmulepi64_si128(acc, k, 0x00);
+            let lo = _mm_clmulepi64_si128(acc, k, 0x11);
+            acc = _mm_xor_si128(_mm_xor_si128(hi, lo), block);
+        }
+
+        let mut acc_bytes = [0u8; 16];
+        _mm_storeu_si128(acc_bytes.as_mut_ptr() as *mut __m128i, acc);
+
+        super::extend(super::extend(!0, &acc_bytes), tail)
+    }
 }
 
 #[cfg(test)]
@@ -145,5 +224,24 @@ mod tests {
             assert_eq!(extend_slow(initial_crc, &data), extend(initial_crc, &data));
         }
     }
+
+    #[test]
+    fn backend_consistency() {
+        for len in 0 .. 600 {
+            let data = (0 .. len).map(|_| rand::random::<u8>()).collect::<Vec<_>>().into_boxed_slice();
+            assert_eq!(compute(&data), extend(INITIAL_CRC, &data));
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            if backend() == crate::ChecksumBackend::Pclmul {
+                for _ in 0 .. 100 {
+                    let data = (0 .. 1024).map(|_| rand::random::<u8>()).collect::<Vec<_>>().into_boxed_slice();
+                    let initial_crc = rand::random::<u32>();
+                    assert_eq!(unsafe { pclmul::extend(initial_crc, &data) }, extend(initial_crc, &data));
+                }
+            }
+        }
+    }
 }
 
@@ -1,31 +1,71 @@
 
+use crate::seal;
 use crate::MAX_FRAME_SIZE;
+use crate::MAX_FRAME_WINDOW_SIZE;
+use crate::MAX_PACKET_WINDOW_SIZE;
 use super::*;
 
+use std::convert::TryInto;
+
 mod build;
 mod crc;
 
 pub use build::DataFrameBuilder;
 pub use build::AckFrameBuilder;
 
+pub fn checksum_backend() -> crate::ChecksumBackend {
+    crc::backend()
+}
+
 const FRAME_HEADER_SIZE: usize = 1;
 const FRAME_CRC_SIZE: usize = 4;
 const FRAME_OVERHEAD: usize = FRAME_HEADER_SIZE + FRAME_CRC_SIZE;
 
+// Set in a frame's type byte if a connection ID follows it
+const CONNECTION_ID_FLAG: u8 = 0x40;
+pub const CONNECTION_ID_SIZE: usize = 4;
+
 const HANDSHAKE_SYN_FRAME_ID: u8 = 0;
 const HANDSHAKE_SYN_ACK_FRAME_ID: u8 = 1;
 const HANDSHAKE_ACK_FRAME_ID: u8 = 2;
 const HANDSHAKE_ERROR_FRAME_ID: u8 = 3;
 const DISCONNECT_FRAME_ID: u8 = 4;
 const DISCONNECT_ACK_FRAME_ID: u8 = 5;
+const HANDSHAKE_COOKIE_FRAME_ID: u8 = 6;
 const DATA_FRAME_ID: u8 = 10;
 const SYNC_FRAME_ID: u8 = 11;
 const ACK_FRAME_ID: u8 = 12;
-
-const HANDSHAKE_SYN_FRAME_PAYLOAD_SIZE: usize = MAX_FRAME_SIZE - FRAME_OVERHEAD; // Padded to internet MTU
+const FRAGMENT_ACK_FRAME_ID: u8 = 13;
+const GROUP_FRAME_ID: u8 = 14;
+const GROUP_ACK_FRAME_ID: u8 = 15;
+const PATH_CHALLENGE_FRAME_ID: u8 = 16;
+const PATH_RESPONSE_FRAME_ID: u8 = 17;
+const RESET_FRAME_ID: u8 = 18;
+const RESET_ACK_FRAME_ID: u8 = 19;
+// Custom frame types occupy a contiguous range of frame IDs
+const CUSTOM_FRAME_ID_BASE: u8 = 0x80;
+
+// Frames which fill a datagram are limited to this size, so that they fit within the internet MTU
+// even when sealed
+pub const MAX_UNSEALED_FRAME_SIZE: usize = MAX_FRAME_SIZE - seal::MAX_SEAL_OVERHEAD;
+// Likewise for frames which may also be tagged with a connection ID, i.e. all but connection
+// requests
+pub const MAX_UNTAGGED_FRAME_SIZE: usize = MAX_UNSEALED_FRAME_SIZE - CONNECTION_ID_SIZE;
+
+const HANDSHAKE_SYN_FRAME_PAYLOAD_SIZE: usize = MAX_UNSEALED_FRAME_SIZE - FRAME_OVERHEAD; // Padded to internet MTU
+const HANDSHAKE_SYN_FRAME_COOKIE_OFFSET: usize = 22;
+const HANDSHAKE_SYN_FRAME_TOKEN_OFFSET: usize = 39;
+const HANDSHAKE_SYN_FRAME_KEY_SHARE_OFFSET: usize = HANDSHAKE_SYN_FRAME_PAYLOAD_SIZE - 33;
+const HANDSHAKE_SYN_FRAME_EXT_FEATURES_OFFSET: usize = HANDSHAKE_SYN_FRAME_KEY_SHARE_OFFSET - 1;
+const HANDSHAKE_SYN_FRAME_CHANNEL_COUNT_OFFSET: usize = HANDSHAKE_SYN_FRAME_EXT_FEATURES_OFFSET - 2;
 const HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE: usize = 20;
+const HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE_FEATURES: usize = 21;
+const HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE_WINDOW: usize = 25;
+const HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE_KEY_SHARE: usize = 89;
+const HANDSHAKE_SYN_ACK_FRAME_EXT_SIZE: usize = 5;
 const HANDSHAKE_ACK_FRAME_PAYLOAD_SIZE: usize = 4;
 const HANDSHAKE_ERROR_FRAME_PAYLOAD_SIZE: usize = 5;
+const HANDSHAKE_COOKIE_FRAME_PAYLOAD_SIZE: usize = 20;
 const DISCONNECT_FRAME_PAYLOAD_SIZE: usize = 0;
 const DISCONNECT_ACK_FRAME_PAYLOAD_SIZE: usize = 0;
 
@@ -42,15 +82,110 @@ pub const DATA_FRAME_OVERHEAD: usize = FRAME_OVERHEAD + DATA_FRAME_PAYLOAD_HEADE
 pub const DATA_FRAME_MAX_DATAGRAM_COUNT: usize = 127;
 
 const SYNC_FRAME_PAYLOAD_SIZE: usize = 9;
+pub const MAX_HEARTBEAT_SIZE: usize = 32;
+
+// A connect token occupies the remainder of a SYN frame up to the channel count, after a two-byte
+// length
+pub const MAX_CONNECT_TOKEN_SIZE: usize = HANDSHAKE_SYN_FRAME_CHANNEL_COUNT_OFFSET - HANDSHAKE_SYN_FRAME_TOKEN_OFFSET - 2;
+const DROP_RANGE_SIZE: usize = 8;
+pub const MAX_DROP_RANGES: usize = 16;
 
 pub const ACK_GROUP_SIZE: usize = 9;
 const ACK_FRAME_PAYLOAD_HEADER_SIZE: usize = 10;
+pub const ACK_FRAME_HEALTH_SIZE: usize = 5;
+// Set in an ack frame's group count if a receiver health trailer follows the ack groups
+const ACK_FRAME_HEALTH_FLAG: u16 = 0x8000;
+pub const ACK_FRAME_APP_LATENCY_SIZE: usize = 2;
+// Set in an ack frame's group count if an application latency trailer follows any health trailer
+const ACK_FRAME_APP_LATENCY_FLAG: u16 = 0x4000;
 #[cfg(test)]
 pub const ACK_FRAME_OVERHEAD: usize = FRAME_OVERHEAD + ACK_FRAME_PAYLOAD_HEADER_SIZE;
 
-pub const MAX_CHANNELS: usize = 64;
+const FRAGMENT_ACK_GROUP_SIZE: usize = 10;
+const FRAGMENT_ACK_FRAME_PAYLOAD_HEADER_SIZE: usize = 2;
+pub const MAX_FRAGMENT_ACK_GROUPS: usize = (MAX_UNTAGGED_FRAME_SIZE - FRAME_OVERHEAD - FRAGMENT_ACK_FRAME_PAYLOAD_HEADER_SIZE) / FRAGMENT_ACK_GROUP_SIZE;
+
+const GROUP_FRAME_PAYLOAD_HEADER_SIZE: usize = 5;
+const GROUP_ACK_FRAME_PAYLOAD_SIZE: usize = 4;
+pub const MAX_GROUP_NAME_SIZE: usize = 64;
+pub const MAX_GROUPS: usize = 16;
+
+const PATH_CHALLENGE_FRAME_PAYLOAD_SIZE: usize = 4;
+const PATH_RESPONSE_FRAME_PAYLOAD_SIZE: usize = 12;
+
+const RESET_FRAME_PAYLOAD_SIZE: usize = 12;
+const RESET_ACK_FRAME_PAYLOAD_SIZE: usize = 12;
+
+pub const MAX_CUSTOM_FRAME_TYPES: usize = 16;
+pub const MAX_CUSTOM_FRAME_PAYLOAD_SIZE: usize = MAX_UNTAGGED_FRAME_SIZE - FRAME_OVERHEAD;
+
+// Channel IDs are sent as 8-bit wire IDs, of which narrow IDs occupy the low six bits alone, and
+// may be sent to any peer. Narrow wire ID 63 is the control channel, and the two high bits of wide
+// wire IDs are placed in spare bits of the small and large datagram headers.
+pub const MAX_CHANNELS: usize = 256;
+// Channels at or beyond this index are reserved for control messages, if negotiated
+pub const MAX_APPLICATION_CHANNELS: usize = 255;
+pub const CONTROL_CHANNEL_ID: u8 = 255;
+// Number of application channels with narrow wire IDs
+pub const NARROW_CHANNELS: usize = 63;
+const NARROW_CONTROL_CHANNEL_ID: u8 = 63;
 pub const MAX_FRAGMENTS: usize = 1 << 16;
 
+// Optional protocol features, advertised by a client's SYN and accepted by the server's SYN+ACK
+pub const FEATURE_FRAGMENT_ACKS: u8 = 0x01;
+pub const FEATURE_GROUPS: u8 = 0x02;
+// Advertised independently by each endpoint if it accepts any custom frame types
+pub const FEATURE_CUSTOM_FRAMES: u8 = 0x04;
+// Advertised independently by each endpoint if it accepts heartbeat payloads on sync frames
+pub const FEATURE_HEARTBEATS: u8 = 0x08;
+// Negotiated like FEATURE_FRAGMENT_ACKS; ack frames carry a receiver health trailer
+pub const FEATURE_RECEIVER_HEALTH: u8 = 0x10;
+// Negotiated; packets on the control channel (CONTROL_CHANNEL_ID) carry control messages
+pub const FEATURE_CONTROL_CHANNELS: u8 = 0x20;
+// Negotiated; the server may move the connection to a new client address after a path challenge
+pub const FEATURE_MIGRATION: u8 = 0x40;
+// Negotiated; sync frames may list packets which the sender has abandoned
+pub const FEATURE_DROP_RANGES: u8 = 0x80;
+
+// Optional protocol features beyond those of the features byte, negotiated likewise
+// Negotiated; frames sent by the client are tagged with a connection ID assigned by the server
+pub const EXT_FEATURE_CONNECTION_IDS: u8 = 0x01;
+// Negotiated; ack frames carry an application latency trailer
+pub const EXT_FEATURE_APP_LATENCY: u8 = 0x02;
+// Negotiated; the server accepts frames from additional client addresses after a path challenge,
+// and replies via the address from which it most recently heard
+pub const EXT_FEATURE_MULTIPATH: u8 = 0x04;
+// Negotiated; the client's SYN carries the number of channels it uses, which may require wide
+// channel IDs
+pub const EXT_FEATURE_CHANNEL_COUNT: u8 = 0x08;
+
+// Returns the wire ID of the given channel. Application channels beyond the narrow range follow the
+// narrow control channel ID.
+pub fn channel_to_wire(channel_id: u8) -> u8 {
+    if channel_id == CONTROL_CHANNEL_ID {
+        NARROW_CONTROL_CHANNEL_ID
+    } else if (channel_id as usize) < NARROW_CHANNELS {
+        channel_id
+    } else {
+        channel_id + 1
+    }
+}
+
+pub fn channel_from_wire(wire_id: u8) -> u8 {
+    if wire_id == NARROW_CONTROL_CHANNEL_ID {
+        CONTROL_CHANNEL_ID
+    } else if (wire_id as usize) < NARROW_CHANNELS {
+        wire_id
+    } else {
+        wire_id - 1
+    }
+}
+
+// A nonzero receive window must be usable as a packet window
+fn receive_window_valid(window: u32) -> bool {
+    window == 0 || (window.is_power_of_two() && window <= MAX_FRAME_WINDOW_SIZE.min(MAX_PACKET_WINDOW_SIZE))
+}
+
 fn read_handshake_syn_payload(data: &[u8]) -> Option<Frame> {
     if data.len() != HANDSHAKE_SYN_FRAME_PAYLOAD_SIZE {
         return None;
@@ -78,19 +213,126 @@ fn read_handshake_syn_payload(data: &[u8]) -> Option<Frame> {
                             ((data[15] as u32) <<  8) |
                             ((data[16] as u32)      );
 
+    // Older clients leave these bytes as padding
+    let features = data[17];
+
+    let max_receive_window = ((data[18] as u32) << 24) |
+                             ((data[19] as u32) << 16) |
+                             ((data[20] as u32) <<  8) |
+                             (data[21] as u32);
+
+    if !receive_window_valid(max_receive_window) {
+        return None;
+    }
+
+    // Likewise, the cookie flag is zero unless a cookie follows
+    let cookie = match data[HANDSHAKE_SYN_FRAME_COOKIE_OFFSET] {
+        0 => None,
+        1 => Some(read_handshake_cookie(&data[HANDSHAKE_SYN_FRAME_COOKIE_OFFSET + 1 .. HANDSHAKE_SYN_FRAME_TOKEN_OFFSET])),
+        _ => return None,
+    };
+
+    // ...and the token length is zero unless a connect token follows
+    let connect_token_size = ((data[HANDSHAKE_SYN_FRAME_TOKEN_OFFSET] as usize) << 8) |
+                             (data[HANDSHAKE_SYN_FRAME_TOKEN_OFFSET + 1] as usize);
+
+    if connect_token_size > MAX_CONNECT_TOKEN_SIZE {
+        return None;
+    }
+
+    let connect_token_begin = HANDSHAKE_SYN_FRAME_TOKEN_OFFSET + 2;
+    let connect_token = data[connect_token_begin .. connect_token_begin + connect_token_size].into();
+
+    // ...and the extended features byte is zero unless any are advertised
+    let ext_features = data[HANDSHAKE_SYN_FRAME_EXT_FEATURES_OFFSET];
+
+    // ...and the channel count is zero unless advertised
+    let channel_count = ((data[HANDSHAKE_SYN_FRAME_CHANNEL_COUNT_OFFSET] as u16) << 8) |
+                        (data[HANDSHAKE_SYN_FRAME_CHANNEL_COUNT_OFFSET + 1] as u16);
+
+    if (ext_features & EXT_FEATURE_CHANNEL_COUNT != 0) != (channel_count != 0) ||
+       channel_count as usize > MAX_APPLICATION_CHANNELS {
+        return None;
+    }
+
+    // ...and the key share flag is zero unless a key share follows
+    let key_share = match data[HANDSHAKE_SYN_FRAME_KEY_SHARE_OFFSET] {
+        0 => None,
+        1 => Some(data[HANDSHAKE_SYN_FRAME_KEY_SHARE_OFFSET + 1 ..].try_into().unwrap()),
+        _ => return None,
+    };
+
     Some(Frame::HandshakeSynFrame(HandshakeSynFrame {
         version,
         nonce,
         max_receive_rate,
         max_packet_size,
         max_receive_alloc,
+        features,
+        max_receive_window,
+        cookie,
+        connect_token,
+        key_share,
+        ext_features,
+        channel_count,
     }))
 }
 
 fn read_handshake_syn_ack_payload(data: &[u8]) -> Option<Frame> {
-    if data.len() != HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE {
-        return None;
-    }
+    // Extended features are only sent to clients which advertised any, and are appended, along
+    // with the connection ID, to the reply as it would otherwise be sent
+    let (data, ext_features, connection_id) = match data.len() {
+        len if len == HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE_WINDOW + HANDSHAKE_SYN_ACK_FRAME_EXT_SIZE ||
+               len == HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE_KEY_SHARE + HANDSHAKE_SYN_ACK_FRAME_EXT_SIZE => {
+            let (data, ext) = data.split_at(len - HANDSHAKE_SYN_ACK_FRAME_EXT_SIZE);
+
+            if ext[0] == 0 {
+                return None;
+            }
+
+            (data, ext[0], u32::from_be_bytes(ext[1 ..].try_into().unwrap()))
+        }
+        _ => (data, 0, 0),
+    };
+
+    // The features byte is only present if nonzero, and the receive window only if the client
+    // advertised one, so that older clients may parse the reply. Key shares are only sent to
+    // clients which requested a key exchange, and are preceded by both.
+    let (features, max_receive_window, key_share) = match data.len() {
+        HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE => (0, 0, None),
+        HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE_FEATURES if data[20] != 0 => (data[20], 0, None),
+        HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE_WINDOW => {
+            let max_receive_window = ((data[21] as u32) << 24) |
+                                     ((data[22] as u32) << 16) |
+                                     ((data[23] as u32) <<  8) |
+                                     (data[24] as u32);
+
+            // A zero window may accompany extended features
+            if (max_receive_window == 0 && ext_features == 0) || !receive_window_valid(max_receive_window) {
+                return None;
+            }
+
+            (data[20], max_receive_window, None)
+        }
+        HANDSHAKE_SYN_ACK_FRAME_PAYLOAD_SIZE_KEY_SHARE => {
+            let max_receive_window = ((data[21] as u32) << 24) |
+                                     ((data[22] as u32) << 16) |
+                                     ((data[23] as u32) <<  8) |
+                                     (data[24] as u32);
+
+            if max_receive_window != 0 && !receive_window_valid(max_receive_window) {
+                return None;
+            }
+
+            let key_share = ServerKeyShare {
+                ephemeral_key: data[25 .. 57].try_into().unwrap(),
+                static_key: data[57 .. 89].try_into().unwrap(),
+            };
+
+            (data[20], max_receive_window, Some(key_share))
+        }
+        _ => return None,
+    };
 
     let nonce_ack = ((data[0] as u32) << 24) |
                     ((data[1] as u32) << 16) |
@@ -123,6 +365,11 @@ fn read_handshake_syn_ack_payload(data: &[u8]) -> Option<Frame> {
         max_receive_rate,
         max_packet_size,
         max_receive_alloc,
+        features,
+        max_receive_window,
+        key_share,
+        ext_features,
+        connection_id,
     }))
 }
 
@@ -155,6 +402,7 @@ fn read_handshake_error_payload(data: &[u8]) -> Option<Frame> {
         0 => HandshakeErrorType::Version,
         1 => HandshakeErrorType::Config,
         2 => HandshakeErrorType::ServerFull,
+        3 => HandshakeErrorType::Rejected,
         _ => return None,
     };
 
@@ -164,6 +412,46 @@ fn read_handshake_error_payload(data: &[u8]) -> Option<Frame> {
     }))
 }
 
+fn read_handshake_cookie(data: &[u8]) -> HandshakeCookie {
+    let timestamp = ((data[0] as u64) << 56) |
+                    ((data[1] as u64) << 48) |
+                    ((data[2] as u64) << 40) |
+                    ((data[3] as u64) << 32) |
+                    ((data[4] as u64) << 24) |
+                    ((data[5] as u64) << 16) |
+                    ((data[6] as u64) <<  8) |
+                    ((data[7] as u64)      );
+
+    let mac = ((data[8] as u64) << 56) |
+              ((data[9] as u64) << 48) |
+              ((data[10] as u64) << 40) |
+              ((data[11] as u64) << 32) |
+              ((data[12] as u64) << 24) |
+              ((data[13] as u64) << 16) |
+              ((data[14] as u64) <<  8) |
+              ((data[15] as u64)      );
+
+    HandshakeCookie { timestamp, mac }
+}
+
+fn read_handshake_cookie_payload(data: &[u8]) -> Option<Frame> {
+    if data.len() != HANDSHAKE_COOKIE_FRAME_PAYLOAD_SIZE {
+        return None;
+    }
+
+    let nonce_ack = ((data[0] as u32) << 24) |
+                    ((data[1] as u32) << 16) |
+                    ((data[2] as u32) <<  8) |
+                    ((data[3] as u32)      );
+
+    let cookie = read_handshake_cookie(&data[4 .. 20]);
+
+    Some(Frame::HandshakeCookieFrame(HandshakeCookieFrame {
+        nonce_ack,
+        cookie,
+    }))
+}
+
 fn read_disconnect_payload(data: &[u8]) -> Option<Frame> {
     if data.len() != DISCONNECT_FRAME_PAYLOAD_SIZE {
         return None;
@@ -197,9 +485,9 @@ fn read_datagram(data: &[u8]) -> Option<(Datagram, usize)> {
         }
 
         // Micro
-        let channel_id = ((data[4] >> 2) & 0x20) |
-                         ((data[0] >> 2) & 0x10) |
-                         ((data[1]     ) & 0x0F);
+        let channel_id = channel_from_wire(((data[4] >> 2) & 0x20) |
+                                           ((data[0] >> 2) & 0x10) |
+                                           ((data[1]     ) & 0x0F));
 
         let sequence_id = (((data[1] & 0xF0) as u32) << 12) |
                           (((data[2]       ) as u32) <<  8) |
@@ -209,6 +497,8 @@ fn read_datagram(data: &[u8]) -> Option<(Datagram, usize)> {
 
         let channel_parent_lead = data[5] as u16;
 
+        let keyed = false;
+
         let fragment_id = 0;
 
         let fragment_id_last = 0;
@@ -220,6 +510,7 @@ fn read_datagram(data: &[u8]) -> Option<(Datagram, usize)> {
             sequence_id,
             window_parent_lead,
             channel_parent_lead,
+            keyed,
             fragment_id,
             fragment_id_last,
             data,
@@ -236,7 +527,7 @@ fn read_datagram(data: &[u8]) -> Option<(Datagram, usize)> {
             return None;
         }
 
-        let channel_id = data[0] & 0x3F;
+        let channel_id = channel_from_wire(((data[2] & 0x60) << 1) | (data[0] & 0x3F));
 
         let sequence_id = (((data[2] & 0x0F) as u32) << 16) |
                           (((data[3]       ) as u32) <<  8) |
@@ -248,6 +539,8 @@ fn read_datagram(data: &[u8]) -> Option<(Datagram, usize)> {
         let channel_parent_lead = ((data[7] as u16) << 8) |
                                   ((data[8] as u16)     );
 
+        let keyed = data[2] & 0x80 != 0;
+
         let fragment_id = 0;
 
         let fragment_id_last = 0;
@@ -259,6 +552,7 @@ fn read_datagram(data: &[u8]) -> Option<(Datagram, usize)> {
             sequence_id,
             window_parent_lead,
             channel_parent_lead,
+            keyed,
             fragment_id,
             fragment_id_last,
             data,
@@ -276,7 +570,7 @@ fn read_datagram(data: &[u8]) -> Option<(Datagram, usize)> {
             return None;
         }
 
-        let channel_id = data[0] & 0x3F;
+        let channel_id = channel_from_wire(((data[3] & 0x60) << 1) | (data[0] & 0x3F));
 
         let sequence_id = (((data[3] & 0x0F) as u32) << 16) |
                           (((data[4]       ) as u32) <<  8) |
@@ -288,6 +582,8 @@ fn read_datagram(data: &[u8]) -> Option<(Datagram, usize)> {
         let channel_parent_lead = ((data[8] as u16) << 8) |
                                   ((data[9] as u16)     );
 
+        let keyed = data[3] & 0x80 != 0;
+
         let fragment_id = ((data[10] as u16) << 8) |
                           ((data[11] as u16)     );
 
@@ -301,6 +597,7 @@ fn read_datagram(data: &[u8]) -> Option<(Datagram, usize)> {
             sequence_id,
             window_parent_lead,
             channel_parent_lead,
+            keyed,
             fragment_id,
             fragment_id_last,
             data,
@@ -344,7 +641,7 @@ fn read_data_payload(data: &[u8]) -> Option<Frame> {
 }
 
 fn read_sync_payload(data: &[u8]) -> Option<Frame> {
-    if data.len() != SYNC_FRAME_PAYLOAD_SIZE {
+    if data.len() < SYNC_FRAME_PAYLOAD_SIZE {
         return None;
     }
 
@@ -368,7 +665,65 @@ fn read_sync_payload(data: &[u8]) -> Option<Frame> {
         None
     };
 
-    Some(Frame::SyncFrame(SyncFrame { next_frame_id, next_packet_id }))
+    let mut data_slice = &data[SYNC_FRAME_PAYLOAD_SIZE ..];
+
+    let heartbeat = if mode & 0x04 != 0x00 {
+        if data_slice.is_empty() {
+            return None;
+        }
+
+        let heartbeat_len = data_slice[0] as usize;
+
+        if heartbeat_len > MAX_HEARTBEAT_SIZE || data_slice.len() < 1 + heartbeat_len {
+            return None;
+        }
+
+        let heartbeat = data_slice[1 .. 1 + heartbeat_len].into();
+        data_slice = &data_slice[1 + heartbeat_len ..];
+
+        Some(heartbeat)
+    } else {
+        None
+    };
+
+    let mut drop_ranges = Vec::new();
+
+    if mode & 0x08 != 0x00 {
+        if data_slice.is_empty() {
+            return None;
+        }
+
+        let range_count = data_slice[0] as usize;
+
+        if range_count > MAX_DROP_RANGES || data_slice.len() < 1 + range_count * DROP_RANGE_SIZE {
+            return None;
+        }
+
+        data_slice = &data_slice[1 ..];
+
+        for _ in 0 .. range_count {
+            let base_id = ((data_slice[0] as u32) << 24) |
+                          ((data_slice[1] as u32) << 16) |
+                          ((data_slice[2] as u32) <<  8) |
+                          ((data_slice[3] as u32)      );
+
+            let count = ((data_slice[4] as u16) << 8) |
+                        ((data_slice[5] as u16)     );
+
+            let window_parent_lead = ((data_slice[6] as u16) << 8) |
+                                     ((data_slice[7] as u16)     );
+
+            drop_ranges.push(DropRange { base_id, count, window_parent_lead });
+
+            data_slice = &data_slice[DROP_RANGE_SIZE ..];
+        }
+    }
+
+    if !data_slice.is_empty() {
+        return None;
+    }
+
+    Some(Frame::SyncFrame(SyncFrame { next_frame_id, next_packet_id, heartbeat, drop_ranges }))
 }
 
 fn read_frame_ack(data: &[u8]) -> Option<(AckGroup, usize)> {
@@ -414,6 +769,10 @@ fn read_ack_payload(data: &[u8]) -> Option<Frame> {
     let frame_ack_num = ((data[8] as u16) << 8) |
                         ((data[9] as u16)     );
 
+    let has_health = frame_ack_num & ACK_FRAME_HEALTH_FLAG != 0;
+    let has_app_latency = frame_ack_num & ACK_FRAME_APP_LATENCY_FLAG != 0;
+    let frame_ack_num = frame_ack_num & !(ACK_FRAME_HEALTH_FLAG | ACK_FRAME_APP_LATENCY_FLAG);
+
     let mut data_slice = &data[ACK_FRAME_PAYLOAD_HEADER_SIZE ..];
     let mut frame_acks = Vec::new();
 
@@ -426,16 +785,229 @@ fn read_ack_payload(data: &[u8]) -> Option<Frame> {
         }
     }
 
+    let health = if has_health {
+        if data_slice.len() < ACK_FRAME_HEALTH_SIZE {
+            return None;
+        }
+
+        let buffered_bytes = ((data_slice[0] as u32) << 24) |
+                             ((data_slice[1] as u32) << 16) |
+                             ((data_slice[2] as u32) <<  8) |
+                              (data_slice[3] as u32);
+
+        let backlogged = match data_slice[4] {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+
+        data_slice = &data_slice[ACK_FRAME_HEALTH_SIZE ..];
+
+        Some(ReceiverHealth { buffered_bytes, backlogged })
+    } else {
+        None
+    };
+
+    let app_latency_ms = if has_app_latency {
+        if data_slice.len() < ACK_FRAME_APP_LATENCY_SIZE {
+            return None;
+        }
+
+        let app_latency_ms = ((data_slice[0] as u16) << 8) |
+                              (data_slice[1] as u16);
+
+        data_slice = &data_slice[ACK_FRAME_APP_LATENCY_SIZE ..];
+
+        Some(app_latency_ms)
+    } else {
+        None
+    };
+
     if data_slice.len() != 0 {
         return None;
     }
 
-    Some(Frame::AckFrame(AckFrame { frame_window_base_id, packet_window_base_id, frame_acks }))
+    Some(Frame::AckFrame(AckFrame { frame_window_base_id, packet_window_base_id, frame_acks, health, app_latency_ms }))
+}
+
+fn read_fragment_ack_payload(data: &[u8]) -> Option<Frame> {
+    if data.len() < FRAGMENT_ACK_FRAME_PAYLOAD_HEADER_SIZE {
+        return None;
+    }
+
+    let fragment_ack_num = ((data[0] as usize) << 8) |
+                            (data[1] as usize);
+
+    let data_slice = &data[FRAGMENT_ACK_FRAME_PAYLOAD_HEADER_SIZE ..];
+
+    if data_slice.len() != fragment_ack_num * FRAGMENT_ACK_GROUP_SIZE {
+        return None;
+    }
+
+    let fragment_acks = data_slice.chunks_exact(FRAGMENT_ACK_GROUP_SIZE).map(|group| {
+        let sequence_id = ((group[0] as u32) << 24) |
+                          ((group[1] as u32) << 16) |
+                          ((group[2] as u32) <<  8) |
+                           (group[3] as u32);
+
+        let base_id = ((group[4] as u16) << 8) |
+                       (group[5] as u16);
+
+        let bitfield = ((group[6] as u32) << 24) |
+                       ((group[7] as u32) << 16) |
+                       ((group[8] as u32) <<  8) |
+                        (group[9] as u32);
+
+        FragmentAckGroup { sequence_id, base_id, bitfield }
+    }).collect();
+
+    Some(Frame::FragmentAckFrame(FragmentAckFrame { fragment_acks }))
+}
+
+fn read_group_payload(data: &[u8]) -> Option<Frame> {
+    if data.len() < GROUP_FRAME_PAYLOAD_HEADER_SIZE {
+        return None;
+    }
+
+    let version = ((data[0] as u32) << 24) |
+                  ((data[1] as u32) << 16) |
+                  ((data[2] as u32) <<  8) |
+                   (data[3] as u32);
+
+    let group_num = data[4] as usize;
+
+    if group_num > MAX_GROUPS {
+        return None;
+    }
+
+    let mut groups = Vec::with_capacity(group_num);
+    let mut data_slice = &data[GROUP_FRAME_PAYLOAD_HEADER_SIZE ..];
+
+    for _ in 0 .. group_num {
+        if data_slice.is_empty() {
+            return None;
+        }
+
+        let name_len = data_slice[0] as usize;
+
+        if name_len == 0 || name_len > MAX_GROUP_NAME_SIZE || data_slice.len() < 1 + name_len {
+            return None;
+        }
+
+        let name = std::str::from_utf8(&data_slice[1 .. 1 + name_len]).ok()?;
+
+        groups.push(name.to_owned());
+        data_slice = &data_slice[1 + name_len ..];
+    }
+
+    if !data_slice.is_empty() {
+        return None;
+    }
+
+    Some(Frame::GroupFrame(GroupFrame { version, groups }))
+}
+
+fn read_group_ack_payload(data: &[u8]) -> Option<Frame> {
+    if data.len() != GROUP_ACK_FRAME_PAYLOAD_SIZE {
+        return None;
+    }
+
+    let version = ((data[0] as u32) << 24) |
+                  ((data[1] as u32) << 16) |
+                  ((data[2] as u32) <<  8) |
+                   (data[3] as u32);
+
+    Some(Frame::GroupAckFrame(GroupAckFrame { version }))
+}
+
+fn read_path_challenge_payload(data: &[u8]) -> Option<Frame> {
+    if data.len() != PATH_CHALLENGE_FRAME_PAYLOAD_SIZE {
+        return None;
+    }
+
+    let challenge = ((data[0] as u32) << 24) |
+                    ((data[1] as u32) << 16) |
+                    ((data[2] as u32) <<  8) |
+                     (data[3] as u32);
+
+    Some(Frame::PathChallengeFrame(PathChallengeFrame { challenge }))
+}
+
+fn read_path_response_payload(data: &[u8]) -> Option<Frame> {
+    if data.len() != PATH_RESPONSE_FRAME_PAYLOAD_SIZE {
+        return None;
+    }
+
+    let challenge_ack = ((data[0] as u32) << 24) |
+                        ((data[1] as u32) << 16) |
+                        ((data[2] as u32) <<  8) |
+                         (data[3] as u32);
+
+    let client_nonce = ((data[4] as u32) << 24) |
+                       ((data[5] as u32) << 16) |
+                       ((data[6] as u32) <<  8) |
+                        (data[7] as u32);
+
+    let server_nonce = ((data[8] as u32) << 24) |
+                       ((data[9] as u32) << 16) |
+                       ((data[10] as u32) <<  8) |
+                        (data[11] as u32);
+
+    Some(Frame::PathResponseFrame(PathResponseFrame { challenge_ack, client_nonce, server_nonce }))
+}
+
+fn read_reset_payload(data: &[u8]) -> Option<Frame> {
+    if data.len() != RESET_FRAME_PAYLOAD_SIZE {
+        return None;
+    }
+
+    let nonce = ((data[0] as u32) << 24) |
+                ((data[1] as u32) << 16) |
+                ((data[2] as u32) <<  8) |
+                 (data[3] as u32);
+
+    let frame_base_id = ((data[4] as u32) << 24) |
+                        ((data[5] as u32) << 16) |
+                        ((data[6] as u32) <<  8) |
+                         (data[7] as u32);
+
+    let packet_base_id = ((data[8] as u32) << 24) |
+                         ((data[9] as u32) << 16) |
+                         ((data[10] as u32) <<  8) |
+                          (data[11] as u32);
+
+    Some(Frame::ResetFrame(ResetFrame { nonce, frame_base_id, packet_base_id }))
+}
+
+fn read_reset_ack_payload(data: &[u8]) -> Option<Frame> {
+    if data.len() != RESET_ACK_FRAME_PAYLOAD_SIZE {
+        return None;
+    }
+
+    let nonce_ack = ((data[0] as u32) << 24) |
+                    ((data[1] as u32) << 16) |
+                    ((data[2] as u32) <<  8) |
+                     (data[3] as u32);
+
+    let frame_base_id = ((data[4] as u32) << 24) |
+                        ((data[5] as u32) << 16) |
+                        ((data[6] as u32) <<  8) |
+                         (data[7] as u32);
+
+    let packet_base_id = ((data[8] as u32) << 24) |
+                         ((data[9] as u32) << 16) |
+                         ((data[10] as u32) <<  8) |
+                          (data[11] as u32);
+
+    Some(Frame::ResetAckFrame(ResetAckFrame { nonce_ack, frame_base_id, packet_base_id }))
 }
 
+fn read_custom_payload(type_id: u8, data: &[u8]) -> Option<Frame> {
+    Some(Frame::CustomFrame(CustomFrame { type_id, data: data.into() }))
+}
 
 fn write_handshake_syn(frame: &HandshakeSynFrame) -> Box<[u8]> {
-    let mut frame_bytes = Box::new([0; MAX_FRAME_SIZE]);
+    let mut frame_bytes = Box::new([0; MAX_UNSEALED_FRAME_SIZE]);
 
     let non_padding_bytes = [
         HANDSHAKE_SYN_FRAME_ID,
@@ -456,10 +1028,39 @@ fn write_handshake_syn(frame: &HandshakeSynFrame) -> Box<[u8]> {
         (frame.max_receive_alloc >> 16) as u8,
         (frame.max_receive_alloc >>  8) as u8,
         (frame.max_receive_alloc      ) as u8,
+        frame.features,
+        (frame.max_receive_window >> 24) as u8,
+        (frame.max_receive_window >> 16) as u8,
+        (frame.max_receive_window >>  8) as u8,
+        frame.max_receive_window as u8,
     ];
 
     frame_bytes[.. non_padding_bytes.len()].clone_from_slice(&non_padding_bytes);
 
+    if let Some(cookie) = frame.cookie {
+        let cookie_offset = FRAME_HEADER_SIZE + HANDSHAKE_SYN_FRAME_COOKIE_OFFSET;
+        frame_bytes[cookie_offset] = 1;
+        frame_bytes[cookie_offset + 1 .. cookie_offset + 9].clone_from_slice(&cookie.timestamp.to_be_bytes());
+        frame_bytes[cookie_offset + 9 .. cookie_offset + 17].clone_from_slice(&cookie.mac.to_be_bytes());
+    }
+
+    debug_assert!(frame.connect_token.len() <= MAX_CONNECT_TOKEN_SIZE);
+
+    let token_offset = FRAME_HEADER_SIZE + HANDSHAKE_SYN_FRAME_TOKEN_OFFSET;
+    frame_bytes[token_offset .. token_offset + 2].clone_from_slice(&(frame.connect_token.len() as u16).to_be_bytes());
+    frame_bytes[token_offset + 2 .. token_offset + 2 + frame.connect_token.len()].clone_from_slice(&frame.connect_token);
+
+    let channel_count_offset = FRAME_HEADER_SIZE + HANDSHAKE_SYN_FRAME_CHANNEL_COUNT_OFFSET;
+    frame_bytes[channel_count_offset .. channel_count_offset + 2].clone_from_slice(&frame.channel_count.to_be_bytes());
+
+    frame_bytes[FRAME_HEADER_SIZE + HANDSHAKE_SYN_FRAME_EXT_FEATURES_OFFSET] = frame.ext_features;
+
+    if let Some(ref key_share) = frame.key_share {
+        let key_share_offset = FRAME_HEADER_SIZE + HANDSHAKE_SYN_FRAME_KEY_SHARE_OFFSET;
+        frame_bytes[key_share_offset] = 1;
+        frame_bytes[key_share_offset + 1 .. key_share_offset + 33].clone_from_slice(key_share);
+    }
+
     let frame_len = frame_bytes.len();
     let data_bytes = &frame_bytes[0 .. frame_len - 4];
 
@@ -473,7 +1074,7 @@ fn write_handshake_syn(frame: &HandshakeSynFrame) -> Box<[u8]> {
 }
 
 fn write_handshake_syn_ack(frame: &HandshakeSynAckFrame) -> Box<[u8]> {
-    let mut frame_bytes = Box::new([
+    let mut frame_bytes = vec![
         HANDSHAKE_SYN_ACK_FRAME_ID,
         (frame.nonce_ack >> 24) as u8,
         (frame.nonce_ack >> 16) as u8,
@@ -495,11 +1096,26 @@ fn write_handshake_syn_ack(frame: &HandshakeSynAckFrame) -> Box<[u8]> {
         (frame.max_receive_alloc >> 16) as u8,
         (frame.max_receive_alloc >>  8) as u8,
         (frame.max_receive_alloc      ) as u8,
-        0,
-        0,
-        0,
-        0,
-    ]);
+    ];
+
+    if let Some(ref key_share) = frame.key_share {
+        frame_bytes.push(frame.features);
+        frame_bytes.extend_from_slice(&frame.max_receive_window.to_be_bytes());
+        frame_bytes.extend_from_slice(&key_share.ephemeral_key);
+        frame_bytes.extend_from_slice(&key_share.static_key);
+    } else if frame.max_receive_window != 0 || frame.ext_features != 0 {
+        frame_bytes.push(frame.features);
+        frame_bytes.extend_from_slice(&frame.max_receive_window.to_be_bytes());
+    } else if frame.features != 0 {
+        frame_bytes.push(frame.features);
+    }
+
+    if frame.ext_features != 0 {
+        frame_bytes.push(frame.ext_features);
+        frame_bytes.extend_from_slice(&frame.connection_id.to_be_bytes());
+    }
+
+    frame_bytes.extend_from_slice(&[0; FRAME_CRC_SIZE]);
 
     let frame_len = frame_bytes.len();
     let data_bytes = &frame_bytes[0 .. frame_len - 4];
@@ -510,7 +1126,7 @@ fn write_handshake_syn_ack(frame: &HandshakeSynAckFrame) -> Box<[u8]> {
     frame_bytes[frame_len - 2] = (crc >>  8) as u8;
     frame_bytes[frame_len - 1] = (crc      ) as u8;
 
-    return frame_bytes;
+    return frame_bytes.into_boxed_slice();
 }
 
 fn write_handshake_ack(frame: &HandshakeAckFrame) -> Box<[u8]> {
@@ -549,6 +1165,7 @@ fn write_handshake_error(frame: &HandshakeErrorFrame) -> Box<[u8]> {
             HandshakeErrorType::Version => 0,
             HandshakeErrorType::Config => 1,
             HandshakeErrorType::ServerFull => 2,
+            HandshakeErrorType::Rejected => 3,
         },
         0,
         0,
@@ -568,6 +1185,31 @@ fn write_handshake_error(frame: &HandshakeErrorFrame) -> Box<[u8]> {
     return frame_bytes;
 }
 
+fn write_handshake_cookie(frame: &HandshakeCookieFrame) -> Box<[u8]> {
+    let mut frame_bytes = vec![
+        HANDSHAKE_COOKIE_FRAME_ID,
+        (frame.nonce_ack >> 24) as u8,
+        (frame.nonce_ack >> 16) as u8,
+        (frame.nonce_ack >>  8) as u8,
+        (frame.nonce_ack      ) as u8,
+    ];
+
+    frame_bytes.extend_from_slice(&frame.cookie.timestamp.to_be_bytes());
+    frame_bytes.extend_from_slice(&frame.cookie.mac.to_be_bytes());
+    frame_bytes.extend_from_slice(&[0; FRAME_CRC_SIZE]);
+
+    let frame_len = frame_bytes.len();
+    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+
+    let crc = crc::compute(data_bytes);
+    frame_bytes[frame_len - 4] = (crc >> 24) as u8;
+    frame_bytes[frame_len - 3] = (crc >> 16) as u8;
+    frame_bytes[frame_len - 2] = (crc >>  8) as u8;
+    frame_bytes[frame_len - 1] = (crc      ) as u8;
+
+    frame_bytes.into_boxed_slice()
+}
+
 fn write_disconnect(_frame: &DisconnectFrame) -> Box<[u8]> {
     let mut frame_bytes = Box::new([
         DISCONNECT_FRAME_ID,
@@ -622,12 +1264,14 @@ fn write_data(frame: &DataFrame) -> Box<[u8]> {
 
 fn write_sync(frame: &SyncFrame) -> Box<[u8]> {
     let mode = ((frame.next_frame_id.is_some() as u8) << 0) |
-               ((frame.next_packet_id.is_some() as u8) << 1);
+               ((frame.next_packet_id.is_some() as u8) << 1) |
+               ((frame.heartbeat.is_some() as u8) << 2) |
+               ((!frame.drop_ranges.is_empty() as u8) << 3);
 
     let next_frame_id = frame.next_frame_id.unwrap_or(0);
     let next_packet_id = frame.next_packet_id.unwrap_or(0);
 
-    let mut frame_bytes = Box::new([
+    let mut frame_bytes = vec![
         SYNC_FRAME_ID,
         mode,
         (next_frame_id >> 24) as u8,
@@ -638,26 +1282,43 @@ fn write_sync(frame: &SyncFrame) -> Box<[u8]> {
         (next_packet_id >> 16) as u8,
         (next_packet_id >>  8) as u8,
         (next_packet_id      ) as u8,
-        0,
-        0,
-        0,
-        0,
-    ]);
+    ];
 
-    let frame_len = frame_bytes.len();
-    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+    if let Some(ref heartbeat) = frame.heartbeat {
+        debug_assert!(heartbeat.len() <= MAX_HEARTBEAT_SIZE);
+        frame_bytes.push(heartbeat.len() as u8);
+        frame_bytes.extend_from_slice(heartbeat);
+    }
 
-    let crc = crc::compute(&data_bytes);
-    frame_bytes[frame_len - 4] = (crc >> 24) as u8;
-    frame_bytes[frame_len - 3] = (crc >> 16) as u8;
-    frame_bytes[frame_len - 2] = (crc >>  8) as u8;
-    frame_bytes[frame_len - 1] = (crc      ) as u8;
+    if !frame.drop_ranges.is_empty() {
+        debug_assert!(frame.drop_ranges.len() <= MAX_DROP_RANGES);
+        frame_bytes.push(frame.drop_ranges.len() as u8);
+
+        for range in frame.drop_ranges.iter() {
+            frame_bytes.extend_from_slice(&[
+                (range.base_id >> 24) as u8,
+                (range.base_id >> 16) as u8,
+                (range.base_id >>  8) as u8,
+                (range.base_id      ) as u8,
+                (range.count >> 8) as u8,
+                (range.count     ) as u8,
+                (range.window_parent_lead >> 8) as u8,
+                (range.window_parent_lead     ) as u8,
+            ]);
+        }
+    }
 
-    return frame_bytes;
+    let crc = crc::compute(&frame_bytes);
+    frame_bytes.push((crc >> 24) as u8);
+    frame_bytes.push((crc >> 16) as u8);
+    frame_bytes.push((crc >>  8) as u8);
+    frame_bytes.push((crc      ) as u8);
+
+    return frame_bytes.into_boxed_slice();
 }
 
 fn write_ack(frame: &AckFrame) -> Box<[u8]> {
-    let mut builder = build::AckFrameBuilder::new(frame.frame_window_base_id, frame.packet_window_base_id);
+    let mut builder = build::AckFrameBuilder::new(frame.frame_window_base_id, frame.packet_window_base_id, frame.health, frame.app_latency_ms);
 
     for frame_ack in frame.frame_acks.iter() {
         builder.add(frame_ack);
@@ -666,41 +1327,370 @@ fn write_ack(frame: &AckFrame) -> Box<[u8]> {
     builder.build()
 }
 
+fn write_fragment_ack(frame: &FragmentAckFrame) -> Box<[u8]> {
+    debug_assert!(frame.fragment_acks.len() <= MAX_FRAGMENT_ACK_GROUPS);
+
+    let fragment_ack_num = frame.fragment_acks.len();
+
+    let mut frame_bytes = Vec::with_capacity(FRAME_OVERHEAD + FRAGMENT_ACK_FRAME_PAYLOAD_HEADER_SIZE +
+                                             fragment_ack_num * FRAGMENT_ACK_GROUP_SIZE);
+
+    frame_bytes.extend_from_slice(&[
+        FRAGMENT_ACK_FRAME_ID,
+        (fragment_ack_num >> 8) as u8,
+        (fragment_ack_num     ) as u8,
+    ]);
+
+    for group in frame.fragment_acks.iter() {
+        frame_bytes.extend_from_slice(&[
+            (group.sequence_id >> 24) as u8,
+            (group.sequence_id >> 16) as u8,
+            (group.sequence_id >>  8) as u8,
+            (group.sequence_id      ) as u8,
+            (group.base_id >> 8) as u8,
+            (group.base_id     ) as u8,
+            (group.bitfield >> 24) as u8,
+            (group.bitfield >> 16) as u8,
+            (group.bitfield >>  8) as u8,
+            (group.bitfield      ) as u8,
+        ]);
+    }
+
+    frame_bytes.extend_from_slice(&[0; FRAME_CRC_SIZE]);
+
+    let frame_len = frame_bytes.len();
+    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+
+    let crc = crc::compute(data_bytes);
+    frame_bytes[frame_len - 4] = (crc >> 24) as u8;
+    frame_bytes[frame_len - 3] = (crc >> 16) as u8;
+    frame_bytes[frame_len - 2] = (crc >>  8) as u8;
+    frame_bytes[frame_len - 1] = (crc      ) as u8;
+
+    frame_bytes.into_boxed_slice()
+}
+
+fn write_group(frame: &GroupFrame) -> Box<[u8]> {
+    debug_assert!(frame.groups.len() <= MAX_GROUPS);
+
+    let mut frame_bytes = Vec::with_capacity(FRAME_OVERHEAD + GROUP_FRAME_PAYLOAD_HEADER_SIZE +
+                                             frame.groups.iter().map(|name| 1 + name.len()).sum::<usize>());
+
+    frame_bytes.extend_from_slice(&[
+        GROUP_FRAME_ID,
+        (frame.version >> 24) as u8,
+        (frame.version >> 16) as u8,
+        (frame.version >>  8) as u8,
+        (frame.version      ) as u8,
+        frame.groups.len() as u8,
+    ]);
+
+    for name in frame.groups.iter() {
+        debug_assert!(!name.is_empty() && name.len() <= MAX_GROUP_NAME_SIZE);
+
+        frame_bytes.push(name.len() as u8);
+        frame_bytes.extend_from_slice(name.as_bytes());
+    }
+
+    frame_bytes.extend_from_slice(&[0; FRAME_CRC_SIZE]);
+
+    let frame_len = frame_bytes.len();
+    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+
+    let crc = crc::compute(data_bytes);
+    frame_bytes[frame_len - 4] = (crc >> 24) as u8;
+    frame_bytes[frame_len - 3] = (crc >> 16) as u8;
+    frame_bytes[frame_len - 2] = (crc >>  8) as u8;
+    frame_bytes[frame_len - 1] = (crc      ) as u8;
+
+    frame_bytes.into_boxed_slice()
+}
+
+fn write_group_ack(frame: &GroupAckFrame) -> Box<[u8]> {
+    let mut frame_bytes = Box::new([
+        GROUP_ACK_FRAME_ID,
+        (frame.version >> 24) as u8,
+        (frame.version >> 16) as u8,
+        (frame.version >>  8) as u8,
+        (frame.version      ) as u8,
+        0,
+        0,
+        0,
+        0,
+    ]);
+
+    let frame_len = frame_bytes.len();
+    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+
+    let crc = crc::compute(data_bytes);
+    frame_bytes[frame_len - 4] = (crc >> 24) as u8;
+    frame_bytes[frame_len - 3] = (crc >> 16) as u8;
+    frame_bytes[frame_len - 2] = (crc >>  8) as u8;
+    frame_bytes[frame_len - 1] = (crc      ) as u8;
+
+    frame_bytes
+}
+
+fn write_path_challenge(frame: &PathChallengeFrame) -> Box<[u8]> {
+    let mut frame_bytes = Box::new([
+        PATH_CHALLENGE_FRAME_ID,
+        (frame.challenge >> 24) as u8,
+        (frame.challenge >> 16) as u8,
+        (frame.challenge >>  8) as u8,
+        (frame.challenge      ) as u8,
+        0,
+        0,
+        0,
+        0,
+    ]);
+
+    let frame_len = frame_bytes.len();
+    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+
+    let crc = crc::compute(data_bytes);
+    frame_bytes[frame_len - 4] = (crc >> 24) as u8;
+    frame_bytes[frame_len - 3] = (crc >> 16) as u8;
+    frame_bytes[frame_len - 2] = (crc >>  8) as u8;
+    frame_bytes[frame_len - 1] = (crc      ) as u8;
+
+    frame_bytes
+}
+
+fn write_path_response(frame: &PathResponseFrame) -> Box<[u8]> {
+    let mut frame_bytes = Box::new([
+        PATH_RESPONSE_FRAME_ID,
+        (frame.challenge_ack >> 24) as u8,
+        (frame.challenge_ack >> 16) as u8,
+        (frame.challenge_ack >>  8) as u8,
+        (frame.challenge_ack      ) as u8,
+        (frame.client_nonce >> 24) as u8,
+        (frame.client_nonce >> 16) as u8,
+        (frame.client_nonce >>  8) as u8,
+        (frame.client_nonce      ) as u8,
+        (frame.server_nonce >> 24) as u8,
+        (frame.server_nonce >> 16) as u8,
+        (frame.server_nonce >>  8) as u8,
+        (frame.server_nonce      ) as u8,
+        0,
+        0,
+        0,
+        0,
+    ]);
+
+    let frame_len = frame_bytes.len();
+    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+
+    let crc = crc::compute(data_bytes);
+    frame_bytes[frame_len - 4] = (crc >> 24) as u8;
+    frame_bytes[frame_len - 3] = (crc >> 16) as u8;
+    frame_bytes[frame_len - 2] = (crc >>  8) as u8;
+    frame_bytes[frame_len - 1] = (crc      ) as u8;
+
+    frame_bytes
+}
+
+fn write_reset(frame: &ResetFrame) -> Box<[u8]> {
+    let mut frame_bytes = Box::new([
+        RESET_FRAME_ID,
+        (frame.nonce >> 24) as u8,
+        (frame.nonce >> 16) as u8,
+        (frame.nonce >>  8) as u8,
+        (frame.nonce      ) as u8,
+        (frame.frame_base_id >> 24) as u8,
+        (frame.frame_base_id >> 16) as u8,
+        (frame.frame_base_id >>  8) as u8,
+        (frame.frame_base_id      ) as u8,
+        (frame.packet_base_id >> 24) as u8,
+        (frame.packet_base_id >> 16) as u8,
+        (frame.packet_base_id >>  8) as u8,
+        (frame.packet_base_id      ) as u8,
+        0,
+        0,
+        0,
+        0,
+    ]);
+
+    let frame_len = frame_bytes.len();
+    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+
+    let crc = crc::compute(data_bytes);
+    frame_bytes[frame_len - 4] = (crc >> 24) as u8;
+    frame_bytes[frame_len - 3] = (crc >> 16) as u8;
+    frame_bytes[frame_len - 2] = (crc >>  8) as u8;
+    frame_bytes[frame_len - 1] = (crc      ) as u8;
+
+    frame_bytes
+}
+
+fn write_reset_ack(frame: &ResetAckFrame) -> Box<[u8]> {
+    let mut frame_bytes = Box::new([
+        RESET_ACK_FRAME_ID,
+        (frame.nonce_ack >> 24) as u8,
+        (frame.nonce_ack >> 16) as u8,
+        (frame.nonce_ack >>  8) as u8,
+        (frame.nonce_ack      ) as u8,
+        (frame.frame_base_id >> 24) as u8,
+        (frame.frame_base_id >> 16) as u8,
+        (frame.frame_base_id >>  8) as u8,
+        (frame.frame_base_id      ) as u8,
+        (frame.packet_base_id >> 24) as u8,
+        (frame.packet_base_id >> 16) as u8,
+        (frame.packet_base_id >>  8) as u8,
+        (frame.packet_base_id      ) as u8,
+        0,
+        0,
+        0,
+        0,
+    ]);
+
+    let frame_len = frame_bytes.len();
+    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+
+    let crc = crc::compute(data_bytes);
+    frame_bytes[frame_len - 4] = (crc >> 24) as u8;
+    frame_bytes[frame_len - 3] = (crc >> 16) as u8;
+    frame_bytes[frame_len - 2] = (crc >>  8) as u8;
+    frame_bytes[frame_len - 1] = (crc      ) as u8;
+
+    frame_bytes
+}
+
+fn write_custom(frame: &CustomFrame) -> Box<[u8]> {
+    debug_assert!((frame.type_id as usize) < MAX_CUSTOM_FRAME_TYPES);
+    debug_assert!(frame.data.len() <= MAX_CUSTOM_FRAME_PAYLOAD_SIZE);
+
+    let mut frame_bytes = Vec::with_capacity(FRAME_OVERHEAD + frame.data.len());
+
+    frame_bytes.push(CUSTOM_FRAME_ID_BASE + frame.type_id);
+    frame_bytes.extend_from_slice(&frame.data);
+    frame_bytes.extend_from_slice(&[0; FRAME_CRC_SIZE]);
+
+    let frame_len = frame_bytes.len();
+    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+
+    let crc = crc::compute(data_bytes);
+    frame_bytes[frame_len - 4] = (crc >> 24) as u8;
+    frame_bytes[frame_len - 3] = (crc >> 16) as u8;
+    frame_bytes[frame_len - 2] = (crc >>  8) as u8;
+    frame_bytes[frame_len - 1] = (crc      ) as u8;
+
+    frame_bytes.into_boxed_slice()
+}
+
 pub trait Serialize {
     fn read(data: &[u8]) -> Option<Self> where Self: Sized;
     fn write(&self) -> Box<[u8]>;
 }
 
+// Returns true if the given frame is of a type which may be exchanged before session keys have been
+// agreed upon during the handshake, i.e. a connection request, or a reply to one. The frame is not
+// otherwise validated.
+#[cfg(feature = "encryption")]
+pub fn precedes_key_exchange(frame_bytes: &[u8]) -> bool {
+    match frame_bytes.first() {
+        Some(&HANDSHAKE_SYN_FRAME_ID) |
+        Some(&HANDSHAKE_SYN_ACK_FRAME_ID) |
+        Some(&HANDSHAKE_ERROR_FRAME_ID) |
+        Some(&HANDSHAKE_COOKIE_FRAME_ID) => true,
+        _ => false,
+    }
+}
+
+// Returns true if the given frame is of a type which is never tagged with a connection ID, i.e. a
+// connection request. The frame is not otherwise validated.
+pub fn is_handshake_syn(frame_bytes: &[u8]) -> bool {
+    frame_bytes.first() == Some(&HANDSHAKE_SYN_FRAME_ID)
+}
+
+// Returns a copy of the given frame, tagged with the given connection ID
+pub fn tag_connection_id(frame_bytes: &[u8], connection_id: u32) -> Box<[u8]> {
+    debug_assert!(frame_bytes.len() >= FRAME_OVERHEAD);
+    debug_assert!(frame_bytes[0] & CONNECTION_ID_FLAG == 0);
+
+    let payload_bytes = &frame_bytes[FRAME_HEADER_SIZE .. frame_bytes.len() - FRAME_CRC_SIZE];
+
+    let mut tagged_bytes = Vec::with_capacity(frame_bytes.len() + CONNECTION_ID_SIZE);
+    tagged_bytes.push(frame_bytes[0] | CONNECTION_ID_FLAG);
+    tagged_bytes.extend_from_slice(&connection_id.to_be_bytes());
+    tagged_bytes.extend_from_slice(payload_bytes);
+
+    let crc = crc::compute(&tagged_bytes);
+    tagged_bytes.extend_from_slice(&crc.to_be_bytes());
+
+    tagged_bytes.into_boxed_slice()
+}
+
+// Returns the connection ID with which the given frame is tagged, if any. The frame is not
+// otherwise validated.
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+pub fn connection_id(frame_bytes: &[u8]) -> Option<u32> {
+    if frame_bytes.len() >= FRAME_OVERHEAD + CONNECTION_ID_SIZE && frame_bytes[0] & CONNECTION_ID_FLAG != 0 {
+        Some(u32::from_be_bytes(frame_bytes[1 .. 5].try_into().unwrap()))
+    } else {
+        None
+    }
+}
+
+// Returns true if the given frame is long enough to contain a header and checksum, and its checksum
+// is valid. The frame may nonetheless be of an unknown type, or malformed.
+pub fn checksum_valid(frame_bytes: &[u8]) -> bool {
+    if frame_bytes.len() < FRAME_OVERHEAD {
+        return false;
+    }
+
+    let frame_len = frame_bytes.len();
+    let data_bytes = &frame_bytes[0 .. frame_len - 4];
+
+    let crc = ((frame_bytes[frame_len - 4] as u32) << 24) |
+              ((frame_bytes[frame_len - 3] as u32) << 16) |
+              ((frame_bytes[frame_len - 2] as u32) <<  8) |
+               (frame_bytes[frame_len - 1] as u32);
+
+    crc::compute(data_bytes) == crc
+}
+
 impl Serialize for Frame {
     fn read(frame_bytes: &[u8]) -> Option<Self> {
-        if frame_bytes.len() < 5 {
+        if !checksum_valid(frame_bytes) {
             return None;
         }
 
         let frame_len = frame_bytes.len();
-        let data_bytes = &frame_bytes[0 .. frame_len - 4];
 
-        let crc = ((frame_bytes[frame_len - 4] as u32) << 24) |
-                  ((frame_bytes[frame_len - 3] as u32) << 16) |
-                  ((frame_bytes[frame_len - 2] as u32) <<  8) |
-                  ((frame_bytes[frame_len - 1] as u32)      );
+        // A connection ID, if any, is of no concern to the frame itself
+        let (frame_type, payload_begin) = if frame_bytes[0] & CONNECTION_ID_FLAG != 0 {
+            if frame_len < FRAME_OVERHEAD + CONNECTION_ID_SIZE {
+                return None;
+            }
 
-        if crc::compute(&data_bytes) != crc {
-            return None;
-        }
+            (frame_bytes[0] & !CONNECTION_ID_FLAG, FRAME_HEADER_SIZE + CONNECTION_ID_SIZE)
+        } else {
+            (frame_bytes[0], FRAME_HEADER_SIZE)
+        };
 
-        let payload_bytes = &frame_bytes[1 .. frame_len - 4];
+        let payload_bytes = &frame_bytes[payload_begin .. frame_len - 4];
 
-        match frame_bytes[0] {
+        match frame_type {
             HANDSHAKE_SYN_FRAME_ID => read_handshake_syn_payload(payload_bytes),
             HANDSHAKE_SYN_ACK_FRAME_ID => read_handshake_syn_ack_payload(payload_bytes),
             HANDSHAKE_ACK_FRAME_ID => read_handshake_ack_payload(payload_bytes),
             HANDSHAKE_ERROR_FRAME_ID => read_handshake_error_payload(payload_bytes),
+            HANDSHAKE_COOKIE_FRAME_ID => read_handshake_cookie_payload(payload_bytes),
             DISCONNECT_FRAME_ID => read_disconnect_payload(payload_bytes),
             DISCONNECT_ACK_FRAME_ID => read_disconnect_ack_payload(payload_bytes),
             DATA_FRAME_ID => read_data_payload(payload_bytes),
             SYNC_FRAME_ID => read_sync_payload(payload_bytes),
             ACK_FRAME_ID => read_ack_payload(payload_bytes),
+            FRAGMENT_ACK_FRAME_ID => read_fragment_ack_payload(payload_bytes),
+            GROUP_FRAME_ID => read_group_payload(payload_bytes),
+            GROUP_ACK_FRAME_ID => read_group_ack_payload(payload_bytes),
+            PATH_CHALLENGE_FRAME_ID => read_path_challenge_payload(payload_bytes),
+            PATH_RESPONSE_FRAME_ID => read_path_response_payload(payload_bytes),
+            RESET_FRAME_ID => read_reset_payload(payload_bytes),
+            RESET_ACK_FRAME_ID => read_reset_ack_payload(payload_bytes),
+            id if id.wrapping_sub(CUSTOM_FRAME_ID_BASE) < MAX_CUSTOM_FRAME_TYPES as u8 => {
+                read_custom_payload(id - CUSTOM_FRAME_ID_BASE, payload_bytes)
+            }
             _ => None,
         }
     }
@@ -711,11 +1701,20 @@ impl Serialize for Frame {
             Frame::HandshakeSynAckFrame(frame) => write_handshake_syn_ack(frame),
             Frame::HandshakeAckFrame(frame) => write_handshake_ack(frame),
             Frame::HandshakeErrorFrame(frame) => write_handshake_error(frame),
+            Frame::HandshakeCookieFrame(frame) => write_handshake_cookie(frame),
             Frame::DisconnectFrame(frame) => write_disconnect(frame),
             Frame::DisconnectAckFrame(frame) => write_disconnect_ack(frame),
             Frame::DataFrame(frame) => write_data(frame),
             Frame::SyncFrame(frame) => write_sync(frame),
             Frame::AckFrame(frame) => write_ack(frame),
+            Frame::FragmentAckFrame(frame) => write_fragment_ack(frame),
+            Frame::GroupFrame(frame) => write_group(frame),
+            Frame::GroupAckFrame(frame) => write_group_ack(frame),
+            Frame::CustomFrame(frame) => write_custom(frame),
+            Frame::PathChallengeFrame(frame) => write_path_challenge(frame),
+            Frame::PathResponseFrame(frame) => write_path_response(frame),
+            Frame::ResetFrame(frame) => write_reset(frame),
+            Frame::ResetAckFrame(frame) => write_reset_ack(frame),
         }
     }
 }
@@ -765,6 +1764,53 @@ mod tests {
             max_receive_rate: 0x98765432,
             max_packet_size: 0x01234567,
             max_receive_alloc: 0xABCDEF01,
+            features: 0,
+            max_receive_window: 0,
+            cookie: None,
+            connect_token: Box::new([]),
+            key_share: None,
+            ext_features: 0,
+            channel_count: 0,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn handshake_syn_features() {
+        let f = Frame::HandshakeSynFrame(HandshakeSynFrame {
+            version: 0x7F,
+            nonce: 0x18273645,
+            max_receive_rate: 0x98765432,
+            max_packet_size: 0x01234567,
+            max_receive_alloc: 0xABCDEF01,
+            features: FEATURE_FRAGMENT_ACKS,
+            max_receive_window: 0,
+            cookie: None,
+            connect_token: Box::new([]),
+            key_share: None,
+            ext_features: 0,
+            channel_count: 0,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn handshake_syn_ack_basic() {
+        let f = Frame::HandshakeSynAckFrame(HandshakeSynAckFrame {
+            nonce_ack: 0x03246387,
+            nonce: 0x18273645,
+            max_receive_rate: 0x98765432,
+            max_packet_size: 0x01234567,
+            max_receive_alloc: 0xABCDEF01,
+            features: 0,
+            max_receive_window: 0,
+            key_share: None,
+            ext_features: 0,
+            connection_id: 0,
         });
         verify_consistent(&f);
         verify_extra_bytes_fail(&f);
@@ -772,38 +1818,338 @@ mod tests {
     }
 
     #[test]
-    fn handshake_syn_ack_basic() {
-        let f = Frame::HandshakeSynAckFrame(HandshakeSynAckFrame {
-            nonce_ack: 0x03246387,
+    fn handshake_syn_ack_features() {
+        let f = Frame::HandshakeSynAckFrame(HandshakeSynAckFrame {
+            nonce_ack: 0x03246387,
+            nonce: 0x18273645,
+            max_receive_rate: 0x98765432,
+            max_packet_size: 0x01234567,
+            max_receive_alloc: 0xABCDEF01,
+            features: FEATURE_FRAGMENT_ACKS,
+            max_receive_window: 0,
+            key_share: None,
+            ext_features: 0,
+            connection_id: 0,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+
+        // The features byte is omitted when zero
+        let no_features = Frame::HandshakeSynAckFrame(HandshakeSynAckFrame {
+            nonce_ack: 0x03246387,
+            nonce: 0x18273645,
+            max_receive_rate: 0x98765432,
+            max_packet_size: 0x01234567,
+            max_receive_alloc: 0xABCDEF01,
+            features: 0,
+            max_receive_window: 0,
+            key_share: None,
+            ext_features: 0,
+            connection_id: 0,
+        });
+        assert_eq!(no_features.write().len(), f.write().len() - 1);
+    }
+
+    #[test]
+    fn handshake_receive_window() {
+        let syn = Frame::HandshakeSynFrame(HandshakeSynFrame {
+            version: 0x7F,
+            nonce: 0x18273645,
+            max_receive_rate: 0x98765432,
+            max_packet_size: 0x01234567,
+            max_receive_alloc: 0xABCDEF01,
+            features: FEATURE_FRAGMENT_ACKS,
+            max_receive_window: 256,
+            cookie: None,
+            connect_token: Box::new([]),
+            key_share: None,
+            ext_features: 0,
+            channel_count: 0,
+        });
+        verify_consistent(&syn);
+        verify_extra_bytes_fail(&syn);
+        verify_truncation_fails(&syn);
+
+        // The features byte is retained even if zero when a window is present
+        for &features in [ 0, FEATURE_FRAGMENT_ACKS ].iter() {
+            let syn_ack = Frame::HandshakeSynAckFrame(HandshakeSynAckFrame {
+                nonce_ack: 0x03246387,
+                nonce: 0x18273645,
+                max_receive_rate: 0x98765432,
+                max_packet_size: 0x01234567,
+                max_receive_alloc: 0xABCDEF01,
+                features,
+                max_receive_window: MAX_FRAME_WINDOW_SIZE,
+                key_share: None,
+                ext_features: 0,
+                connection_id: 0,
+            });
+            verify_consistent(&syn_ack);
+            verify_extra_bytes_fail(&syn_ack);
+            verify_truncation_fails(&syn_ack);
+        }
+
+        // Windows which are not powers of two, or which exceed the maximum, are rejected
+        for &window in [ 3, MAX_FRAME_WINDOW_SIZE * 2 ].iter() {
+            let syn = Frame::HandshakeSynFrame(HandshakeSynFrame {
+                version: 0x7F,
+                nonce: 0x18273645,
+                max_receive_rate: 0x98765432,
+                max_packet_size: 0x01234567,
+                max_receive_alloc: 0xABCDEF01,
+                features: 0,
+                max_receive_window: window,
+                cookie: None,
+                connect_token: Box::new([]),
+                key_share: None,
+                ext_features: 0,
+                channel_count: 0,
+            });
+            assert_eq!(Frame::read(&syn.write()), None);
+        }
+    }
+
+    #[test]
+    fn handshake_ack_basic() {
+        let f = Frame::HandshakeAckFrame(HandshakeAckFrame {
+            nonce_ack: 0x03246387,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn handshake_error_basic() {
+        let f = Frame::HandshakeErrorFrame(HandshakeErrorFrame {
+            nonce_ack: 0x03246387,
+            error: HandshakeErrorType::ServerFull,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+
+        let f = Frame::HandshakeErrorFrame(HandshakeErrorFrame {
+            nonce_ack: 0x03246387,
+            error: HandshakeErrorType::Rejected,
+        });
+        verify_consistent(&f);
+    }
+
+    #[test]
+    fn handshake_syn_connect_token() {
+        for &size in [ 1, MAX_CONNECT_TOKEN_SIZE ].iter() {
+            let f = Frame::HandshakeSynFrame(HandshakeSynFrame {
+                version: 0x7F,
+                nonce: 0x18273645,
+                max_receive_rate: 0x98765432,
+                max_packet_size: 0x01234567,
+                max_receive_alloc: 0xABCDEF01,
+                features: FEATURE_FRAGMENT_ACKS,
+                max_receive_window: 256,
+                cookie: Some(HandshakeCookie { timestamp: 0x0123456789ABCDEF, mac: 0xFEDCBA9876543210 }),
+                connect_token: (0 .. size).map(|i| i as u8).collect(),
+                key_share: None,
+                ext_features: 0,
+                channel_count: 0,
+            });
+            verify_consistent(&f);
+            verify_extra_bytes_fail(&f);
+            verify_truncation_fails(&f);
+        }
+
+        // Tokens which would overrun the frame are rejected
+        let mut bytes = Frame::HandshakeSynFrame(HandshakeSynFrame {
+            version: 0x7F,
+            nonce: 0x18273645,
+            max_receive_rate: 0x98765432,
+            max_packet_size: 0x01234567,
+            max_receive_alloc: 0xABCDEF01,
+            features: 0,
+            max_receive_window: 0,
+            cookie: None,
+            connect_token: Box::new([]),
+            key_share: None,
+            ext_features: 0,
+            channel_count: 0,
+        }).write();
+
+        let token_offset = FRAME_HEADER_SIZE + HANDSHAKE_SYN_FRAME_TOKEN_OFFSET;
+        bytes[token_offset .. token_offset + 2].clone_from_slice(&(MAX_CONNECT_TOKEN_SIZE as u16 + 1).to_be_bytes());
+
+        let frame_len = bytes.len();
+        let crc = crc::compute(&bytes[.. frame_len - 4]);
+        bytes[frame_len - 4 ..].clone_from_slice(&crc.to_be_bytes());
+
+        assert_eq!(Frame::read(&bytes), None);
+    }
+
+    #[test]
+    fn handshake_cookie_basic() {
+        let f = Frame::HandshakeCookieFrame(HandshakeCookieFrame {
+            nonce_ack: 0x03246387,
+            cookie: HandshakeCookie { timestamp: 0x0123456789ABCDEF, mac: 0xFEDCBA9876543210 },
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+
+        // An echoed cookie occupies what was previously padding
+        let syn = Frame::HandshakeSynFrame(HandshakeSynFrame {
+            version: 0x7F,
+            nonce: 0x18273645,
+            max_receive_rate: 0x98765432,
+            max_packet_size: 0x01234567,
+            max_receive_alloc: 0xABCDEF01,
+            features: FEATURE_FRAGMENT_ACKS,
+            max_receive_window: 256,
+            cookie: Some(HandshakeCookie { timestamp: 0x0123456789ABCDEF, mac: 0xFEDCBA9876543210 }),
+            connect_token: Box::new([]),
+            key_share: None,
+            ext_features: 0,
+            channel_count: 0,
+        });
+        verify_consistent(&syn);
+        verify_extra_bytes_fail(&syn);
+        verify_truncation_fails(&syn);
+    }
+
+    #[test]
+    fn handshake_key_share() {
+        // A key share occupies the end of a SYN frame, whatever the size of the connect token
+        for &size in [ 0, MAX_CONNECT_TOKEN_SIZE ].iter() {
+            let syn = Frame::HandshakeSynFrame(HandshakeSynFrame {
+                version: 0x7F,
+                nonce: 0x18273645,
+                max_receive_rate: 0x98765432,
+                max_packet_size: 0x01234567,
+                max_receive_alloc: 0xABCDEF01,
+                features: 0,
+                max_receive_window: 0,
+                cookie: Some(HandshakeCookie { timestamp: 0x0123456789ABCDEF, mac: 0xFEDCBA9876543210 }),
+                connect_token: (0 .. size).map(|i| i as u8).collect(),
+                key_share: Some([0xA5; 32]),
+                ext_features: 0,
+                channel_count: 0,
+            });
+            verify_consistent(&syn);
+            verify_extra_bytes_fail(&syn);
+            verify_truncation_fails(&syn);
+        }
+
+        // The receive window may be zero when followed by a key share
+        for &max_receive_window in [ 0, MAX_FRAME_WINDOW_SIZE ].iter() {
+            let syn_ack = Frame::HandshakeSynAckFrame(HandshakeSynAckFrame {
+                nonce_ack: 0x03246387,
+                nonce: 0x18273645,
+                max_receive_rate: 0x98765432,
+                max_packet_size: 0x01234567,
+                max_receive_alloc: 0xABCDEF01,
+                features: 0,
+                max_receive_window,
+                key_share: Some(ServerKeyShare { ephemeral_key: [0x5A; 32], static_key: [0x3C; 32] }),
+                ext_features: 0,
+                connection_id: 0,
+            });
+            verify_consistent(&syn_ack);
+            verify_extra_bytes_fail(&syn_ack);
+            verify_truncation_fails(&syn_ack);
+        }
+    }
+
+    #[test]
+    fn handshake_ext_features() {
+        let syn = Frame::HandshakeSynFrame(HandshakeSynFrame {
+            version: 0x7F,
+            nonce: 0x18273645,
+            max_receive_rate: 0x98765432,
+            max_packet_size: 0x01234567,
+            max_receive_alloc: 0xABCDEF01,
+            features: 0,
+            max_receive_window: 0,
+            cookie: None,
+            connect_token: (0 .. MAX_CONNECT_TOKEN_SIZE).map(|i| i as u8).collect(),
+            key_share: Some([0xA5; 32]),
+            ext_features: EXT_FEATURE_CONNECTION_IDS,
+            channel_count: 0,
+        });
+        verify_consistent(&syn);
+        verify_extra_bytes_fail(&syn);
+        verify_truncation_fails(&syn);
+
+        // Extended features and a connection ID follow a reply as it would otherwise be sent, whose
+        // receive window may then be zero
+        for key_share in [ None, Some(ServerKeyShare { ephemeral_key: [0x5A; 32], static_key: [0x3C; 32] }) ].iter() {
+            for &max_receive_window in [ 0, MAX_FRAME_WINDOW_SIZE ].iter() {
+                let syn_ack = Frame::HandshakeSynAckFrame(HandshakeSynAckFrame {
+                    nonce_ack: 0x03246387,
+                    nonce: 0x18273645,
+                    max_receive_rate: 0x98765432,
+                    max_packet_size: 0x01234567,
+                    max_receive_alloc: 0xABCDEF01,
+                    features: 0,
+                    max_receive_window,
+                    key_share: key_share.clone(),
+                    ext_features: EXT_FEATURE_CONNECTION_IDS,
+                    connection_id: 0x0A1B2C3D,
+                });
+                verify_consistent(&syn_ack);
+                verify_extra_bytes_fail(&syn_ack);
+                verify_truncation_fails(&syn_ack);
+            }
+        }
+    }
+
+    #[test]
+    fn handshake_syn_channel_count() {
+        let syn = HandshakeSynFrame {
+            version: 0x7F,
             nonce: 0x18273645,
             max_receive_rate: 0x98765432,
             max_packet_size: 0x01234567,
             max_receive_alloc: 0xABCDEF01,
-        });
+            features: 0,
+            max_receive_window: 0,
+            cookie: None,
+            connect_token: (0 .. MAX_CONNECT_TOKEN_SIZE).map(|i| i as u8).collect(),
+            key_share: None,
+            ext_features: EXT_FEATURE_CHANNEL_COUNT,
+            channel_count: MAX_APPLICATION_CHANNELS as u16,
+        };
+
+        let f = Frame::HandshakeSynFrame(syn.clone());
         verify_consistent(&f);
         verify_extra_bytes_fail(&f);
         verify_truncation_fails(&f);
+
+        // The channel count must be given if and only if it is advertised, and may not exceed the
+        // number of application channels
+        for &(ext_features, channel_count) in [ (EXT_FEATURE_CHANNEL_COUNT, 0),
+                                                (0, 1),
+                                                (EXT_FEATURE_CHANNEL_COUNT, MAX_APPLICATION_CHANNELS as u16 + 1) ].iter() {
+            let f = Frame::HandshakeSynFrame(HandshakeSynFrame { ext_features, channel_count, .. syn.clone() });
+            assert_eq!(Frame::read(&f.write()), None);
+        }
     }
 
     #[test]
-    fn handshake_ack_basic() {
+    fn connection_id_tag() {
         let f = Frame::HandshakeAckFrame(HandshakeAckFrame {
             nonce_ack: 0x03246387,
         });
-        verify_consistent(&f);
-        verify_extra_bytes_fail(&f);
-        verify_truncation_fails(&f);
-    }
 
-    #[test]
-    fn handshake_error_basic() {
-        let f = Frame::HandshakeErrorFrame(HandshakeErrorFrame {
-            nonce_ack: 0x03246387,
-            error: HandshakeErrorType::ServerFull,
-        });
-        verify_consistent(&f);
-        verify_extra_bytes_fail(&f);
-        verify_truncation_fails(&f);
+        let bytes = f.write();
+        assert_eq!(connection_id(&bytes), None);
+
+        let tagged_bytes = tag_connection_id(&bytes, 0x0A1B2C3D);
+        assert_eq!(tagged_bytes.len(), bytes.len() + CONNECTION_ID_SIZE);
+        assert_eq!(connection_id(&tagged_bytes), Some(0x0A1B2C3D));
+        assert_eq!(Frame::read(&tagged_bytes), Some(f));
+
+        for i in 1 .. tagged_bytes.len() {
+            assert_eq!(Frame::read(&tagged_bytes[0 .. i]), None);
+        }
     }
 
     #[test]
@@ -835,6 +2181,7 @@ mod tests {
                     channel_id: 63,
                     window_parent_lead: 0x34A8,
                     channel_parent_lead: 0x8A43,
+                    keyed: false,
                     fragment_id: 0x4789,
                     fragment_id_last: 0x478A,
                     data: vec![ 0x00, 0x01, 0x02 ].into_boxed_slice(),
@@ -844,6 +2191,7 @@ mod tests {
                     channel_id: 63,
                     window_parent_lead: 0x34A8,
                     channel_parent_lead: 0x8A43,
+                    keyed: false,
                     fragment_id: 0,
                     fragment_id_last: 0,
                     data: small_data,
@@ -853,6 +2201,51 @@ mod tests {
                     channel_id: 63,
                     window_parent_lead: 0x34A8,
                     channel_parent_lead: 0x8A43,
+                    keyed: false,
+                    fragment_id: 0,
+                    fragment_id_last: 0,
+                    data: vec![ 0x00, 0x01, 0x02 ].into_boxed_slice(),
+                },
+            ],
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn data_keyed() {
+        let f = Frame::DataFrame(DataFrame {
+            sequence_id: 0x010203,
+            nonce: false,
+            datagrams: vec![
+                Datagram {
+                    sequence_id: 0xFFFFF,
+                    channel_id: 63,
+                    window_parent_lead: 0x34A8,
+                    channel_parent_lead: 0x8A43,
+                    keyed: true,
+                    fragment_id: 0x4789,
+                    fragment_id_last: 0x478A,
+                    data: vec![ 0x00, 0x01, 0x02 ].into_boxed_slice(),
+                },
+                Datagram {
+                    sequence_id: 0xFFFFF,
+                    channel_id: 63,
+                    window_parent_lead: 0x34A8,
+                    channel_parent_lead: 0x8A43,
+                    keyed: true,
+                    fragment_id: 0,
+                    fragment_id_last: 0,
+                    data: vec![ 0x00; 200 ].into_boxed_slice(),
+                },
+                // Would otherwise fit in a micro header
+                Datagram {
+                    sequence_id: 0xFFFFF,
+                    channel_id: 63,
+                    window_parent_lead: 1,
+                    channel_parent_lead: 1,
+                    keyed: true,
                     fragment_id: 0,
                     fragment_id_last: 0,
                     data: vec![ 0x00, 0x01, 0x02 ].into_boxed_slice(),
@@ -864,18 +2257,135 @@ mod tests {
         verify_truncation_fails(&f);
     }
 
+    #[test]
+    fn data_wide_channels() {
+        let channel_ids = [ 0, NARROW_CHANNELS as u8 - 1, NARROW_CHANNELS as u8, 200, MAX_APPLICATION_CHANNELS as u8 - 1,
+                            CONTROL_CHANNEL_ID ];
+
+        for &channel_id in channel_ids.iter() {
+            // Micro, small, and large headers, respectively
+            let f = Frame::DataFrame(DataFrame {
+                sequence_id: 0x010203,
+                nonce: false,
+                datagrams: vec![
+                    Datagram {
+                        sequence_id: 0x12345,
+                        channel_id,
+                        window_parent_lead: 1,
+                        channel_parent_lead: 1,
+                        keyed: false,
+                        fragment_id: 0,
+                        fragment_id_last: 0,
+                        data: vec![ 0x00, 0x01, 0x02 ].into_boxed_slice(),
+                    },
+                    Datagram {
+                        sequence_id: 0x12345,
+                        channel_id,
+                        window_parent_lead: 0x34A8,
+                        channel_parent_lead: 0x8A43,
+                        keyed: true,
+                        fragment_id: 0,
+                        fragment_id_last: 0,
+                        data: vec![ 0x00; 200 ].into_boxed_slice(),
+                    },
+                    Datagram {
+                        sequence_id: 0x12345,
+                        channel_id,
+                        window_parent_lead: 0x34A8,
+                        channel_parent_lead: 0x8A43,
+                        keyed: false,
+                        fragment_id: 0x4789,
+                        fragment_id_last: 0x478A,
+                        data: vec![ 0x00, 0x01, 0x02 ].into_boxed_slice(),
+                    },
+                ],
+            });
+            verify_consistent(&f);
+            verify_extra_bytes_fail(&f);
+            verify_truncation_fails(&f);
+        }
+
+        // Narrow channel IDs are unchanged on the wire
+        for channel_id in 0 .. NARROW_CHANNELS as u8 {
+            assert_eq!(channel_to_wire(channel_id), channel_id);
+        }
+
+        assert_eq!(channel_to_wire(CONTROL_CHANNEL_ID), NARROW_CONTROL_CHANNEL_ID);
+
+        for channel_id in 0 ..= u8::MAX {
+            assert_eq!(channel_from_wire(channel_to_wire(channel_id)), channel_id);
+        }
+    }
+
     #[test]
     fn sync_basic() {
         let f = Frame::SyncFrame(SyncFrame {
             next_frame_id: Some(0x01020304),
             next_packet_id: None,
+            heartbeat: None,
+            drop_ranges: Vec::new(),
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+        let f = Frame::SyncFrame(SyncFrame {
+            next_frame_id: None,
+            next_packet_id: Some(0x05060708),
+            heartbeat: None,
+            drop_ranges: Vec::new(),
         });
         verify_consistent(&f);
         verify_extra_bytes_fail(&f);
         verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn sync_heartbeat() {
         let f = Frame::SyncFrame(SyncFrame {
             next_frame_id: None,
+            next_packet_id: None,
+            heartbeat: Some(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice()),
+            drop_ranges: Vec::new(),
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+        let f = Frame::SyncFrame(SyncFrame {
+            next_frame_id: Some(0x01020304),
             next_packet_id: Some(0x05060708),
+            heartbeat: Some(vec![ 0xFF; MAX_HEARTBEAT_SIZE ].into_boxed_slice()),
+            drop_ranges: Vec::new(),
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+        let f = Frame::SyncFrame(SyncFrame {
+            next_frame_id: None,
+            next_packet_id: None,
+            heartbeat: Some(Box::new([])),
+            drop_ranges: Vec::new(),
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn sync_drop_ranges() {
+        let f = Frame::SyncFrame(SyncFrame {
+            next_frame_id: None,
+            next_packet_id: None,
+            heartbeat: None,
+            drop_ranges: vec![ DropRange { base_id: 0x01020304, count: 0x0506, window_parent_lead: 0x0708 } ],
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+        let f = Frame::SyncFrame(SyncFrame {
+            next_frame_id: Some(0x01020304),
+            next_packet_id: None,
+            heartbeat: Some(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice()),
+            drop_ranges: vec![ DropRange { base_id: 0xFFFFFFFF, count: 0xFFFF, window_parent_lead: 0 }; MAX_DROP_RANGES ],
         });
         verify_consistent(&f);
         verify_extra_bytes_fail(&f);
@@ -894,12 +2404,235 @@ mod tests {
                     nonce: true,
                 },
             ],
+            health: None,
+            app_latency_ms: None,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn ack_health() {
+        let f = Frame::AckFrame(AckFrame {
+            frame_window_base_id: 0x010203,
+            packet_window_base_id: 0x040506,
+            frame_acks: vec![
+                AckGroup {
+                    base_id: 0x28475809,
+                    bitfield: 0b01000100111101110110100110101u32,
+                    nonce: false,
+                },
+            ],
+            health: Some(ReceiverHealth { buffered_bytes: 0x0A0B0C0D, backlogged: true }),
+            app_latency_ms: None,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+        let f = Frame::AckFrame(AckFrame {
+            frame_window_base_id: 0x010203,
+            packet_window_base_id: 0x040506,
+            frame_acks: Vec::new(),
+            health: Some(ReceiverHealth { buffered_bytes: 0, backlogged: false }),
+            app_latency_ms: None,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn ack_app_latency() {
+        let f = Frame::AckFrame(AckFrame {
+            frame_window_base_id: 0x010203,
+            packet_window_base_id: 0x040506,
+            frame_acks: vec![
+                AckGroup {
+                    base_id: 0x28475809,
+                    bitfield: 0b01000100111101110110100110101u32,
+                    nonce: true,
+                },
+            ],
+            health: None,
+            app_latency_ms: Some(0x0A0B),
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+        let f = Frame::AckFrame(AckFrame {
+            frame_window_base_id: 0x010203,
+            packet_window_base_id: 0x040506,
+            frame_acks: Vec::new(),
+            health: Some(ReceiverHealth { buffered_bytes: 0x0A0B0C0D, backlogged: true }),
+            app_latency_ms: Some(0),
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn fragment_ack_basic() {
+        let f = Frame::FragmentAckFrame(FragmentAckFrame {
+            fragment_acks: vec![
+                FragmentAckGroup {
+                    sequence_id: 0x010203,
+                    base_id: 0x0020,
+                    bitfield: 0b01000100111101110110100110101u32,
+                },
+                FragmentAckGroup {
+                    sequence_id: 0x040506,
+                    base_id: 0xFFE0,
+                    bitfield: 0xFFFFFFFF,
+                },
+            ],
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn fragment_ack_max_size() {
+        let f = Frame::FragmentAckFrame(FragmentAckFrame {
+            fragment_acks: (0 .. MAX_FRAGMENT_ACK_GROUPS).map(|i| FragmentAckGroup {
+                sequence_id: i as u32,
+                base_id: 0,
+                bitfield: 0x1,
+            }).collect(),
+        });
+        assert!(f.write().len() <= MAX_FRAME_SIZE);
+        verify_consistent(&f);
+    }
+
+    #[test]
+    fn group_basic() {
+        let f = Frame::GroupFrame(GroupFrame {
+            version: 0x01020304,
+            groups: vec![ "lobby".to_owned(), "zone/4".to_owned(), "ünïcödé".to_owned() ],
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+
+        let f = Frame::GroupFrame(GroupFrame {
+            version: 0,
+            groups: Vec::new(),
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn group_max_size() {
+        let f = Frame::GroupFrame(GroupFrame {
+            version: 0xFFFFFFFF,
+            groups: (0 .. MAX_GROUPS).map(|i| format!("{:0>1$}", i, MAX_GROUP_NAME_SIZE)).collect(),
+        });
+        assert!(f.write().len() <= MAX_FRAME_SIZE);
+        verify_consistent(&f);
+    }
+
+    #[test]
+    fn group_ack_basic() {
+        let f = Frame::GroupAckFrame(GroupAckFrame {
+            version: 0x01020304,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn path_challenge_basic() {
+        let f = Frame::PathChallengeFrame(PathChallengeFrame {
+            challenge: 0x01020304,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn path_response_basic() {
+        let f = Frame::PathResponseFrame(PathResponseFrame {
+            challenge_ack: 0x01020304,
+            client_nonce: 0x05060708,
+            server_nonce: 0x090A0B0C,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn reset_basic() {
+        let f = Frame::ResetFrame(ResetFrame {
+            nonce: 0x01020304,
+            frame_base_id: 0x05060708,
+            packet_base_id: 0x090A0B0C,
+        });
+        verify_consistent(&f);
+        verify_extra_bytes_fail(&f);
+        verify_truncation_fails(&f);
+    }
+
+    #[test]
+    fn reset_ack_basic() {
+        let f = Frame::ResetAckFrame(ResetAckFrame {
+            nonce_ack: 0x01020304,
+            frame_base_id: 0x05060708,
+            packet_base_id: 0x090A0B0C,
         });
         verify_consistent(&f);
         verify_extra_bytes_fail(&f);
         verify_truncation_fails(&f);
     }
 
+    #[test]
+    fn checksum() {
+        let mut bytes = Frame::GroupAckFrame(GroupAckFrame { version: 0x01020304 }).write().to_vec();
+        assert!(checksum_valid(&bytes));
+
+        // Frames of unknown type are accepted so long as the checksum is valid
+        bytes[0] = 0xFF;
+        assert!(!checksum_valid(&bytes));
+        let frame_len = bytes.len();
+        let crc = crc::compute(&bytes[0 .. frame_len - 4]);
+        bytes[frame_len - 4 ..].copy_from_slice(&crc.to_be_bytes());
+        assert!(checksum_valid(&bytes));
+        assert_eq!(Frame::read(&bytes), None);
+
+        assert!(!checksum_valid(&bytes[0 .. FRAME_OVERHEAD - 1]));
+    }
+
+    #[test]
+    fn custom_basic() {
+        for type_id in 0 .. MAX_CUSTOM_FRAME_TYPES as u8 {
+            let f = Frame::CustomFrame(CustomFrame {
+                type_id,
+                data: vec![ type_id; 10 ].into(),
+            });
+            verify_consistent(&f);
+            verify_truncation_fails(&f);
+        }
+
+        let f = Frame::CustomFrame(CustomFrame {
+            type_id: 0,
+            data: Box::new([]),
+        });
+        verify_consistent(&f);
+
+        let f = Frame::CustomFrame(CustomFrame {
+            type_id: MAX_CUSTOM_FRAME_TYPES as u8 - 1,
+            data: vec![ 0xAB; MAX_CUSTOM_FRAME_PAYLOAD_SIZE ].into(),
+        });
+        assert_eq!(f.write().len(), MAX_UNTAGGED_FRAME_SIZE);
+        verify_consistent(&f);
+    }
+
     #[test]
     fn data_empty() {
         let f = Frame::DataFrame(DataFrame {
@@ -918,6 +2651,8 @@ mod tests {
             frame_window_base_id: 0x010203,
             packet_window_base_id: 0x040506,
             frame_acks: Vec::new(),
+            health: None,
+            app_latency_ms: None,
         });
         verify_consistent(&f);
         verify_extra_bytes_fail(&f);
@@ -945,6 +2680,7 @@ mod tests {
                     channel_id: (rand::random::<usize>() % MAX_CHANNELS) as u8,
                     window_parent_lead: rand::random::<u16>() % 128,
                     channel_parent_lead: rand::random::<u16>() % 256,
+                    keyed: false,
                     fragment_id: 0,
                     fragment_id_last: 0,
                     data: random_data(0, 64),
@@ -955,6 +2691,7 @@ mod tests {
                     channel_id: (rand::random::<usize>() % MAX_CHANNELS) as u8,
                     window_parent_lead: rand::random::<u16>(),
                     channel_parent_lead: rand::random::<u16>(),
+                    keyed: false,
                     fragment_id: 0,
                     fragment_id_last: 0,
                     data: random_data(64, MAX_DATA_SIZE),
@@ -973,6 +2710,7 @@ mod tests {
                         channel_id: (rand::random::<usize>() % MAX_CHANNELS) as u8,
                         window_parent_lead: rand::random::<u16>(),
                         channel_parent_lead: rand::random::<u16>(),
+                        keyed: false,
                         fragment_id,
                         fragment_id_last,
                         data: random_data(0, MAX_DATA_SIZE),
@@ -1014,6 +2752,16 @@ mod tests {
             let f = Frame::SyncFrame(SyncFrame {
                 next_frame_id: if rand::random::<u32>() % 5 != 0 { Some(rand::random::<u32>()) } else { None },
                 next_packet_id: if rand::random::<u32>() % 5 != 0 { Some(rand::random::<u32>()) } else { None },
+                heartbeat: if rand::random::<u32>() % 2 != 0 {
+                    Some((0 .. rand::random::<usize>() % (MAX_HEARTBEAT_SIZE + 1)).map(|_| rand::random::<u8>()).collect())
+                } else {
+                    None
+                },
+                drop_ranges: (0 .. rand::random::<usize>() % (MAX_DROP_RANGES + 1)).map(|_| DropRange {
+                    base_id: rand::random::<u32>(),
+                    count: rand::random::<u16>(),
+                    window_parent_lead: rand::random::<u16>(),
+                }).collect(),
             });
             verify_consistent(&f);
             verify_extra_bytes_fail(&f);
@@ -1043,6 +2791,16 @@ mod tests {
                 frame_window_base_id: rand::random::<u32>(),
                 packet_window_base_id: rand::random::<u32>(),
                 frame_acks: frame_acks,
+                health: if rand::random::<bool>() {
+                    Some(ReceiverHealth { buffered_bytes: rand::random::<u32>(), backlogged: rand::random::<bool>() })
+                } else {
+                    None
+                },
+                app_latency_ms: if rand::random::<bool>() {
+                    Some(rand::random::<u16>())
+                } else {
+                    None
+                },
             });
 
             verify_consistent(&f);
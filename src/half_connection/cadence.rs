@@ -0,0 +1,157 @@
+
+// Estimates the interval at which the remote host sends data, and the variation in that interval
+// (jitter), according to the arrival times of data frames. Because a sender places all frames for
+// a given flush on the network at once, frames which arrive within BURST_INTERVAL_MS of one another
+// are considered to be part of the same burst. The interval estimate is smoothed as the SRTT of
+// RFC 6298, and the jitter estimate is smoothed as the interarrival jitter of RFC 3550.
+
+use crate::ArrivalCadence;
+
+const BURST_INTERVAL_MS: f64 = 1.0;
+
+// Number of intervals which must be measured before an estimate is reported
+const MIN_SAMPLE_COUNT: u32 = 8;
+
+// Minimum change (relative to the reported interval) which causes a new estimate to be reported
+const CHANGE_THRESHOLD: f64 = 0.1;
+const MIN_CHANGE_MS: f64 = 1.0;
+
+pub struct CadenceEstimator {
+    last_arrival_ms: Option<f64>,
+    interval_ms: f64,
+    jitter_ms: f64,
+    sample_count: u32,
+
+    reported: Option<ArrivalCadence>,
+}
+
+impl CadenceEstimator {
+    pub fn new() -> Self {
+        Self {
+            last_arrival_ms: None,
+            interval_ms: 0.0,
+            jitter_ms: 0.0,
+            sample_count: 0,
+
+            reported: None,
+        }
+    }
+
+    pub fn notify_arrival(&mut self, now_ms: f64) {
+        if let Some(last_arrival_ms) = self.last_arrival_ms {
+            let sample_ms = now_ms - last_arrival_ms;
+
+            if sample_ms < BURST_INTERVAL_MS {
+                // Same burst
+                return;
+            }
+
+            if self.sample_count == 0 {
+                self.interval_ms = sample_ms;
+                self.jitter_ms = sample_ms / 2.0;
+            } else {
+                let delta_ms = sample_ms - self.interval_ms;
+                self.interval_ms += delta_ms / 8.0;
+                self.jitter_ms += (delta_ms.abs() - self.jitter_ms) / 16.0;
+            }
+
+            self.sample_count = self.sample_count.saturating_add(1);
+        }
+
+        self.last_arrival_ms = Some(now_ms);
+    }
+
+    pub fn cadence(&self) -> Option<ArrivalCadence> {
+        if self.sample_count >= MIN_SAMPLE_COUNT {
+            Some(ArrivalCadence { interval_ms: self.interval_ms, jitter_ms: self.jitter_ms })
+        } else {
+            None
+        }
+    }
+
+    // Returns the current estimate if it differs significantly from the last estimate returned
+    pub fn poll_change(&mut self) -> Option<ArrivalCadence> {
+        let cadence = self.cadence()?;
+
+        if let Some(ref reported) = self.reported {
+            let threshold_ms = (reported.interval_ms * CHANGE_THRESHOLD).max(MIN_CHANGE_MS);
+
+            if (cadence.interval_ms - reported.interval_ms).abs() <= threshold_ms &&
+               (cadence.jitter_ms - reported.jitter_ms).abs() <= threshold_ms {
+                return None;
+            }
+        }
+
+        self.reported = Some(cadence);
+
+        Some(cadence)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steady_cadence() {
+        let mut ce = CadenceEstimator::new();
+
+        for i in 0 .. MIN_SAMPLE_COUNT {
+            assert_eq!(ce.cadence(), None);
+            assert_eq!(ce.poll_change(), None);
+
+            // Bursts of three frames every 50ms
+            ce.notify_arrival(i as f64 * 50.0);
+            ce.notify_arrival(i as f64 * 50.0 + 0.1);
+            ce.notify_arrival(i as f64 * 50.0 + 0.2);
+        }
+
+        ce.notify_arrival(MIN_SAMPLE_COUNT as f64 * 50.0);
+
+        let cadence = ce.cadence().unwrap();
+        assert_eq!(cadence.interval_ms, 50.0);
+        assert!(cadence.jitter_ms < 25.0);
+
+        assert_eq!(ce.poll_change(), Some(cadence));
+        assert_eq!(ce.poll_change(), None);
+    }
+
+    #[test]
+    fn cadence_change() {
+        let mut ce = CadenceEstimator::new();
+        let mut now_ms = 0.0;
+
+        for _ in 0 ..= MIN_SAMPLE_COUNT {
+            ce.notify_arrival(now_ms);
+            now_ms += 50.0;
+        }
+
+        assert!(ce.poll_change().is_some());
+
+        // Sender slows to 100ms intervals, estimate should converge
+        for _ in 0 .. 100 {
+            ce.notify_arrival(now_ms);
+            now_ms += 100.0;
+        }
+
+        let cadence = ce.poll_change().unwrap();
+        assert!((cadence.interval_ms - 100.0).abs() < 1.0);
+        assert!(cadence.jitter_ms < 5.0);
+    }
+
+    #[test]
+    fn jitter_estimate() {
+        let mut ce = CadenceEstimator::new();
+        let mut now_ms = 0.0;
+
+        // Alternating 40ms / 60ms intervals
+        for i in 0 .. 200 {
+            ce.notify_arrival(now_ms);
+            now_ms += if i % 2 == 0 { 40.0 } else { 60.0 };
+        }
+
+        let cadence = ce.cadence().unwrap();
+        assert!((cadence.interval_ms - 50.0).abs() < 2.0);
+        assert!((cadence.jitter_ms - 10.0).abs() < 2.0);
+    }
+}
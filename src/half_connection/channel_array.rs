@@ -0,0 +1,79 @@
+// Per-channel state of each application channel. If N is nonzero, the state of up to N channels is
+// held inline in a fixed-size array; otherwise, the channel count is only known at runtime, and the
+// state is allocated separately.
+
+pub struct ChannelArray<C, const N: usize> {
+    fixed: [C; N],
+    fixed_len: usize,
+    allocated: Box<[C]>,
+}
+
+impl<C, const N: usize> ChannelArray<C, N> {
+    pub fn new(channel_count: usize, mut new_channel: impl FnMut() -> C) -> Self {
+        let fixed_len = if N > 0 {
+            assert!(channel_count <= N, "channel count {} exceeds fixed channel count {}", channel_count, N);
+            channel_count
+        } else {
+            0
+        };
+
+        Self {
+            fixed: std::array::from_fn(|_| new_channel()),
+            fixed_len,
+            allocated: (fixed_len .. channel_count).map(|_| new_channel()).collect(),
+        }
+    }
+}
+
+impl<C, const N: usize> std::ops::Deref for ChannelArray<C, N> {
+    type Target = [C];
+
+    fn deref(&self) -> &[C] {
+        if N > 0 {
+            &self.fixed[.. self.fixed_len]
+        } else {
+            &self.allocated
+        }
+    }
+}
+
+impl<C, const N: usize> std::ops::DerefMut for ChannelArray<C, N> {
+    fn deref_mut(&mut self) -> &mut [C] {
+        if N > 0 {
+            &mut self.fixed[.. self.fixed_len]
+        } else {
+            &mut self.allocated
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed() {
+        let mut channels = ChannelArray::<u32, 4>::new(3, || 7);
+
+        assert_eq!(&channels[..], &[ 7, 7, 7 ]);
+
+        channels[2] = 9;
+        assert_eq!(&channels[..], &[ 7, 7, 9 ]);
+    }
+
+    #[test]
+    fn allocated() {
+        let mut channels = ChannelArray::<u32, 0>::new(5, || 7);
+
+        assert_eq!(&channels[..], &[ 7, 7, 7, 7, 7 ]);
+
+        channels[4] = 9;
+        assert_eq!(&channels[..], &[ 7, 7, 7, 7, 9 ]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn fixed_overflow() {
+        ChannelArray::<u32, 4>::new(5, || 7);
+    }
+}
@@ -0,0 +1,112 @@
+// A set of channel IDs, including that of the control channel, stored as a fixed-size bitmask.
+
+use crate::frame;
+
+const WORDS: usize = frame::serial::MAX_CHANNELS / 64;
+
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct ChannelMask([u64; WORDS]);
+
+impl ChannelMask {
+    pub fn new() -> Self {
+        Self([0; WORDS])
+    }
+
+    pub fn insert(&mut self, channel_id: u8) {
+        self.0[channel_id as usize / 64] |= 1 << (channel_id % 64);
+    }
+
+    pub fn remove(&mut self, channel_id: u8) {
+        self.0[channel_id as usize / 64] &= !(1 << (channel_id % 64));
+    }
+
+    pub fn contains(&self, channel_id: u8) -> bool {
+        self.0[channel_id as usize / 64] & (1 << (channel_id % 64)) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|&word| word == 0)
+    }
+
+    // Returns each channel ID in the set, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        self.0.iter().enumerate().flat_map(|(word_idx, &word)| {
+            let mut bits = word;
+
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+
+                let bit = bits.trailing_zeros();
+                bits &= bits - 1;
+
+                Some((word_idx * 64) as u8 + bit as u8)
+            })
+        })
+    }
+}
+
+impl std::ops::BitAnd for ChannelMask {
+    type Output = Self;
+
+    fn bitand(mut self, other: Self) -> Self {
+        self &= other;
+        self
+    }
+}
+
+impl std::ops::BitAndAssign for ChannelMask {
+    fn bitand_assign(&mut self, other: Self) {
+        self.0.iter_mut().zip(other.0.iter()).for_each(|(word, &other)| *word &= other);
+    }
+}
+
+impl std::ops::BitOrAssign for ChannelMask {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0.iter_mut().zip(other.0.iter()).for_each(|(word, &other)| *word |= other);
+    }
+}
+
+impl std::ops::Not for ChannelMask {
+    type Output = Self;
+
+    fn not(mut self) -> Self {
+        self.0.iter_mut().for_each(|word| *word = !*word);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic() {
+        let mut mask = ChannelMask::new();
+        assert!(mask.is_empty());
+
+        for &channel_id in [ 0, 63, 64, 200, frame::serial::CONTROL_CHANNEL_ID ].iter() {
+            mask.insert(channel_id);
+        }
+
+        assert!(mask.contains(64));
+        assert!(!mask.contains(65));
+        assert_eq!(mask.iter().collect::<Vec<_>>(), vec![ 0, 63, 64, 200, 255 ]);
+
+        mask.remove(63);
+        assert_eq!(mask.iter().collect::<Vec<_>>(), vec![ 0, 64, 200, 255 ]);
+
+        let mut other = ChannelMask::new();
+        other.insert(64);
+        other.insert(1);
+
+        assert_eq!((mask & other).iter().collect::<Vec<_>>(), vec![ 64 ]);
+
+        mask &= !other;
+        assert_eq!(mask.iter().collect::<Vec<_>>(), vec![ 0, 200, 255 ]);
+
+        mask |= other;
+        assert_eq!(mask.iter().collect::<Vec<_>>(), vec![ 0, 1, 64, 200, 255 ]);
+    }
+}
@@ -2,22 +2,75 @@
 use crate::frame;
 use crate::frame::serial::AckFrameBuilder;
 use crate::frame::serial::DataFrameBuilder;
+use crate::DatagramTransform;
 use crate::MAX_FRAME_SIZE;
 use crate::MAX_FRAME_WINDOW_SIZE;
 use crate::packet_id;
 
 use super::pending_packet;
+use super::pending_queue;
 use super::frame_queue;
+use super::resend_queue;
+
+// Loss rate at or below which data frames are filled to the maximum size
+const FRAME_SIZE_LOSS_RATE_THRESHOLD: f64 = 0.02;
+// Smallest size to which data frames will be reduced under loss
+const MIN_TARGET_FRAME_SIZE: usize = MAX_FRAME_SIZE / 4;
+
+// Selects the size to which data frames are filled according to the measured loss rate. Beyond a
+// threshold, the target size shrinks in proportion to the loss rate so that each lost frame
+// damages fewer datagrams, and grows back as the loss rate falls.
+pub struct FrameSizePolicy {
+    target_size: usize,
+}
+
+impl FrameSizePolicy {
+    pub fn new() -> Self {
+        Self {
+            target_size: MAX_FRAME_SIZE,
+        }
+    }
+
+    pub fn update(&mut self, loss_rate: f64) {
+        self.target_size = if loss_rate > FRAME_SIZE_LOSS_RATE_THRESHOLD {
+            let target_size = MAX_FRAME_SIZE as f64 * FRAME_SIZE_LOSS_RATE_THRESHOLD / loss_rate;
+            (target_size as usize).max(MIN_TARGET_FRAME_SIZE)
+        } else {
+            MAX_FRAME_SIZE
+        };
+    }
+
+    pub fn target_size(&self) -> usize {
+        self.target_size
+    }
+}
 
 #[derive(Debug,PartialEq)]
 pub enum DataPushError {
     SizeLimited,
     WindowLimited,
+    SendFailed,
+}
+
+// Determines when a reliable fragment will next be resent, once the frame containing it has been
+// sent successfully
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct ResendSchedule {
+    pub resend_time: u64,
+    // The (saturating) number of times the fragment will have been sent
+    pub send_count: u8,
+}
+
+impl ResendSchedule {
+    fn is_retransmit(&self) -> bool {
+        self.send_count > 1
+    }
 }
 
 struct InProgressDataFrame {
     fbuilder: frame::serial::DataFrameBuilder,
     resend_refs: Vec<pending_packet::FragmentRef>,
+    fragments: Vec<(pending_packet::FragmentRef, Option<ResendSchedule>)>,
     nonce: bool,
 }
 
@@ -26,32 +79,83 @@ pub struct DataFrameEmitter<'a, F> {
     frame_queue: &'a mut frame_queue::FrameQueue,
 
     in_progress_frame: Option<InProgressDataFrame>,
+    max_frame_size: usize,
+    // Bytes added to each frame after it is emitted, which count against the flush allocation
+    frame_overhead: usize,
     flush_alloc: isize,
     emit_cb: F,
+    // Applied to the payload of each datagram as it is added to a frame, if set
+    transform: Option<&'a mut dyn DatagramTransform>,
+
+    // Fragments to be resent, which were contained by frames sent successfully
+    resend_entries: Vec<resend_queue::Entry>,
+    // Fragments contained by a frame which could not be sent
+    unsent_entries: Vec<pending_queue::Entry>,
 }
 
-impl<'a, F> DataFrameEmitter<'a, F> where F: FnMut(Box<[u8]>) {
-    pub fn new(now_ms: u64, frame_queue: &'a mut frame_queue::FrameQueue, flush_alloc: isize, emit_cb: F) -> Self {
+// The emit callback returns the index of the path over which each frame was sent
+impl<'a, F> DataFrameEmitter<'a, F> where F: FnMut(Box<[u8]>) -> Result<u8, ()> {
+    // Frames are filled with datagrams up to max_frame_size, unless a single datagram would exceed it
+    pub fn new(now_ms: u64, frame_queue: &'a mut frame_queue::FrameQueue, max_frame_size: usize, flush_alloc: isize, emit_cb: F) -> Self {
+        debug_assert!(max_frame_size <= MAX_FRAME_SIZE);
+
         Self {
             now_ms,
             frame_queue,
 
             in_progress_frame: None,
+            max_frame_size,
+            frame_overhead: 0,
             flush_alloc,
             emit_cb,
+            transform: None,
+
+            resend_entries: Vec::new(),
+            unsent_entries: Vec::new(),
+        }
+    }
+
+    pub fn set_transform(&mut self, transform: &'a mut dyn DatagramTransform) {
+        self.transform = Some(transform);
+    }
+
+    pub fn set_frame_overhead(&mut self, frame_overhead: usize) {
+        self.frame_overhead = frame_overhead;
+    }
+
+    fn encoded_size(&self, datagram: &frame::DatagramRef) -> usize {
+        match self.transform {
+            Some(ref transform) => DataFrameBuilder::encoded_size_with(datagram, datagram.data.len() + transform.overhead()),
+            None => DataFrameBuilder::encoded_size(datagram),
+        }
+    }
+
+    fn add_datagram(fbuilder: &mut DataFrameBuilder, datagram: &frame::DatagramRef, transform: &mut Option<&'a mut dyn DatagramTransform>) {
+        match transform {
+            Some(transform) => {
+                let data_len = datagram.data.len() + transform.overhead();
+                fbuilder.add_with(datagram, data_len, |out| transform.encode(datagram.data, out));
+            }
+            None => fbuilder.add(datagram),
         }
     }
 
     // Returns Ok(()) if the datagram was added successfully
     // Returns Err(DataPushError) if the datagram could not be added
-    pub fn push(&mut self, packet_rc: &pending_packet::PendingPacketRc, fragment_id: u16, resend: bool) -> Result<(), DataPushError> {
+    pub fn push(&mut self, packet_rc: &pending_packet::PendingPacketRc, fragment_id: u16, resend: Option<ResendSchedule>) -> Result<(), DataPushError> {
+        if !self.unsent_entries.is_empty() {
+            // A previous frame could not be sent
+            return Err(DataPushError::SendFailed);
+        }
+
         let packet_ref = packet_rc.borrow();
         let datagram = packet_ref.datagram(fragment_id);
+        let encoded_size = self.encoded_size(&datagram);
 
         if let Some(ref mut next_frame) = self.in_progress_frame {
             // Try to add to existing frame
             let frame_size = next_frame.fbuilder.size();
-            let potential_frame_size = frame_size + DataFrameBuilder::encoded_size(&datagram);
+            let potential_frame_size = frame_size + encoded_size;
 
             // Restrict the number of datagrams per frame to ensure that packet IDs are unique over
             // the receiver's frame window, which has size MAX_FRAME_WINDOW_SIZE * 2. I.e.:
@@ -66,15 +170,22 @@ impl<'a, F> DataFrameEmitter<'a, F> where F: FnMut(Box<[u8]>) {
                 self.finalize();
                 self.frame_queue.mark_rate_limited();
                 return Err(DataPushError::SizeLimited);
-            } else if potential_frame_size > MAX_FRAME_SIZE || next_frame.fbuilder.count() >= max_packet_count {
+            } else if potential_frame_size > self.max_frame_size || next_frame.fbuilder.count() >= max_packet_count {
                 // Would exceed maximum
                 self.finalize();
+
+                if !self.unsent_entries.is_empty() {
+                    return Err(DataPushError::SendFailed);
+                }
             } else {
-                next_frame.fbuilder.add(&datagram);
+                Self::add_datagram(&mut next_frame.fbuilder, &datagram, &mut self.transform);
                 debug_assert!(next_frame.fbuilder.size() == potential_frame_size);
-                if resend {
-                    next_frame.resend_refs.push(pending_packet::FragmentRef::new(packet_rc, fragment_id));
+                // Traced fragments are acknowledged via the frame log even if they will not be resent
+                let fragment_ref = pending_packet::FragmentRef::new(packet_rc, fragment_id);
+                if resend.is_some() || packet_ref.traced() {
+                    next_frame.resend_refs.push(fragment_ref.clone());
                 }
+                next_frame.fragments.push((fragment_ref, resend));
                 return Ok(());
             }
         }
@@ -97,13 +208,16 @@ impl<'a, F> DataFrameEmitter<'a, F> where F: FnMut(Box<[u8]>) {
         let mut next_frame = InProgressDataFrame {
             fbuilder: DataFrameBuilder::new(frame_id, nonce),
             resend_refs: Vec::new(),
+            fragments: Vec::new(),
             nonce,
         };
 
-        next_frame.fbuilder.add(&datagram);
-        if resend {
-            next_frame.resend_refs.push(pending_packet::FragmentRef::new(packet_rc, fragment_id));
+        Self::add_datagram(&mut next_frame.fbuilder, &datagram, &mut self.transform);
+        let fragment_ref = pending_packet::FragmentRef::new(packet_rc, fragment_id);
+        if resend.is_some() || packet_ref.traced() {
+            next_frame.resend_refs.push(fragment_ref.clone());
         }
+        next_frame.fragments.push((fragment_ref, resend));
 
         debug_assert!(self.in_progress_frame.is_none());
         self.in_progress_frame = Some(next_frame);
@@ -114,39 +228,100 @@ impl<'a, F> DataFrameEmitter<'a, F> where F: FnMut(Box<[u8]>) {
     pub fn finalize(&mut self) {
         if let Some(next_frame) = self.in_progress_frame.take() {
             let frame_bytes = next_frame.fbuilder.build();
-            let resend_refs = next_frame.resend_refs.into_boxed_slice();
+            let frame_size = frame_bytes.len() + self.frame_overhead;
+
+            if let Ok(path) = (self.emit_cb)(frame_bytes) {
+                let resend_refs = next_frame.resend_refs.into_boxed_slice();
+
+                let retransmit = next_frame.fragments.iter()
+                    .any(|(_, resend)| resend.is_some_and(|schedule| schedule.is_retransmit()));
+
+                if retransmit {
+                    self.frame_queue.mark_retransmit();
+                }
 
-            debug_assert!(self.frame_queue.can_push());
-            self.frame_queue.push(frame_bytes.len(), self.now_ms, resend_refs, next_frame.nonce);
+                self.frame_queue.mark_path(path);
 
-            self.flush_alloc -= frame_bytes.len() as isize;
-            (self.emit_cb)(frame_bytes);
+                debug_assert!(self.frame_queue.can_push());
+                self.frame_queue.push(frame_size, self.now_ms, resend_refs, next_frame.nonce);
+
+                self.flush_alloc -= frame_size as isize;
+
+                for (fragment_ref, resend) in next_frame.fragments.into_iter() {
+                    if let Some(schedule) = resend {
+                        self.resend_entries.push(resend_queue::Entry::new(fragment_ref, schedule.resend_time, schedule.send_count));
+                    }
+                }
+            } else {
+                // The frame was never sent, so its frame ID is reused and its fragments are
+                // returned to the caller
+                for (fragment_ref, resend) in next_frame.fragments.into_iter() {
+                    self.unsent_entries.push(pending_queue::Entry::new(fragment_ref, resend.is_some()));
+                }
+            }
         }
     }
+
+    // Finalizes any frame in progress, and returns the fragments to be resent from frames which
+    // were sent, and the fragments contained by a frame which could not be sent, in order
+    pub fn finish(mut self) -> (Vec<resend_queue::Entry>, Vec<pending_queue::Entry>) {
+        self.finalize();
+        (self.resend_entries, self.unsent_entries)
+    }
+}
+
+struct InProgressAckFrame {
+    fbuilder: frame::serial::AckFrameBuilder,
+    ack_groups: Vec<frame::AckGroup>,
 }
 
 pub struct AckFrameEmitter<F> {
     frame_window_base_id: u32,
     packet_window_base_id: u32,
-
-    in_progress_frame: Option<frame::serial::AckFrameBuilder>,
+    // Receiver health and application latency reported by each frame, if enabled
+    health: Option<frame::ReceiverHealth>,
+    app_latency_ms: Option<u16>,
+
+    in_progress_frame: Option<InProgressAckFrame>,
+    // Bytes added to each frame after it is emitted, which count against the flush allocation and
+    // the maximum frame size
+    frame_overhead: usize,
     flush_alloc: isize,
     emit_cb: F,
+
+    // Ack groups contained by a frame which could not be sent
+    unsent_groups: Vec<frame::AckGroup>,
+    send_failed: bool,
 }
 
-impl<F> AckFrameEmitter<F> where F: FnMut(Box<[u8]>) {
-    pub fn new(frame_window_base_id: u32, packet_window_base_id: u32, flush_alloc: isize, emit_cb: F) -> Self {
+impl<F> AckFrameEmitter<F> where F: FnMut(Box<[u8]>) -> Result<(), ()> {
+    pub fn new(frame_window_base_id: u32, packet_window_base_id: u32, health: Option<frame::ReceiverHealth>,
+               app_latency_ms: Option<u16>, flush_alloc: isize, emit_cb: F) -> Self {
         Self {
             frame_window_base_id,
             packet_window_base_id,
+            health,
+            app_latency_ms,
 
             in_progress_frame: None,
+            frame_overhead: 0,
             flush_alloc,
             emit_cb,
+
+            unsent_groups: Vec::new(),
+            send_failed: false,
         }
     }
 
+    pub fn set_frame_overhead(&mut self, frame_overhead: usize) {
+        self.frame_overhead = frame_overhead;
+    }
+
     pub fn push_dud(&mut self) -> Result<(), ()> {
+        if self.send_failed {
+            return Err(());
+        }
+
         if self.in_progress_frame.is_some() {
             return Ok(());
         }
@@ -157,10 +332,10 @@ impl<F> AckFrameEmitter<F> where F: FnMut(Box<[u8]>) {
             return Err(());
         }
 
-        let fbuilder = AckFrameBuilder::new(self.frame_window_base_id, self.packet_window_base_id);
+        let fbuilder = AckFrameBuilder::new(self.frame_window_base_id, self.packet_window_base_id, self.health, self.app_latency_ms);
 
         debug_assert!(self.in_progress_frame.is_none());
-        self.in_progress_frame = Some(fbuilder);
+        self.in_progress_frame = Some(InProgressAckFrame { fbuilder, ack_groups: Vec::new() });
 
         return Ok(());
     }
@@ -168,21 +343,30 @@ impl<F> AckFrameEmitter<F> where F: FnMut(Box<[u8]>) {
     // Returns Ok(()) if the ack group was added successfully
     // Returns Err(()) if the ack group could not be added
     pub fn push(&mut self, ack_group: &frame::AckGroup) -> Result<(), ()> {
+        if self.send_failed {
+            return Err(());
+        }
+
         if let Some(ref mut next_frame) = self.in_progress_frame {
             // Try to add to existing frame
-            let frame_size = next_frame.size();
+            let frame_size = next_frame.fbuilder.size();
             let potential_frame_size = frame_size + AckFrameBuilder::encoded_size(ack_group);
 
             if (self.flush_alloc - frame_size as isize) < 0 {
                 // Out of bandwidth
                 self.finalize();
                 return Err(());
-            } else if potential_frame_size > MAX_FRAME_SIZE {
+            } else if potential_frame_size + self.frame_overhead > MAX_FRAME_SIZE {
                 // Would exceed maximum
                 self.finalize();
+
+                if self.send_failed {
+                    return Err(());
+                }
             } else {
-                next_frame.add(ack_group);
-                debug_assert!(next_frame.size() == potential_frame_size);
+                next_frame.fbuilder.add(ack_group);
+                next_frame.ack_groups.push(ack_group.clone());
+                debug_assert!(next_frame.fbuilder.size() == potential_frame_size);
                 return Ok(());
             }
         }
@@ -193,20 +377,38 @@ impl<F> AckFrameEmitter<F> where F: FnMut(Box<[u8]>) {
             return Err(());
         }
 
-        let mut fbuilder = AckFrameBuilder::new(self.frame_window_base_id, self.packet_window_base_id);
+        let mut fbuilder = AckFrameBuilder::new(self.frame_window_base_id, self.packet_window_base_id, self.health, self.app_latency_ms);
         fbuilder.add(ack_group);
 
         debug_assert!(self.in_progress_frame.is_none());
-        self.in_progress_frame = Some(fbuilder);
+        self.in_progress_frame = Some(InProgressAckFrame { fbuilder, ack_groups: vec![ ack_group.clone() ] });
 
         return Ok(());
     }
 
     pub fn finalize(&mut self) {
         if let Some(next_frame) = self.in_progress_frame.take() {
-            let frame_bytes = next_frame.build();
-            self.flush_alloc -= frame_bytes.len() as isize;
-            (self.emit_cb)(frame_bytes);
+            let frame_bytes = next_frame.fbuilder.build();
+            let frame_size = frame_bytes.len() + self.frame_overhead;
+
+            if (self.emit_cb)(frame_bytes).is_ok() {
+                self.flush_alloc -= frame_size as isize;
+            } else {
+                self.unsent_groups.extend(next_frame.ack_groups);
+                self.send_failed = true;
+            }
+        }
+    }
+
+    // Finalizes any frame in progress, and returns the ack groups contained by a frame which could
+    // not be sent, in order
+    pub fn finish(mut self) -> Result<(), Vec<frame::AckGroup>> {
+        self.finalize();
+
+        if self.send_failed {
+            Err(self.unsent_groups)
+        } else {
+            Ok(())
         }
     }
 }
@@ -225,22 +427,23 @@ mod tests {
     fn max_datagram_test(flush_alloc: isize, window_size: u32, push_count: usize, final_result: Result<(),DataPushError>) -> Vec<Box<[u8]>> {
         let now_ms = 0;
 
-        let mut fq = frame_queue::FrameQueue::new(window_size, window_size, 0);
+        let mut fq = frame_queue::FrameQueue::new(window_size, window_size, 0, 3);
 
         let mut frames = Vec::new();
         let emit_cb = |frame_bytes: Box<[u8]>| {
             frames.push(frame_bytes);
+            Ok(0)
         };
 
-        let mut dfe = DataFrameEmitter::new(now_ms, &mut fq, flush_alloc, emit_cb);
+        let mut dfe = DataFrameEmitter::new(now_ms, &mut fq, MAX_FRAME_SIZE, flush_alloc, emit_cb);
 
         let packet_bytes = (0 .. 2*MAX_FRAGMENT_SIZE).map(|i| i as u8).collect::<Vec<u8>>().into_boxed_slice();
-        let packet_rc = Rc::new(RefCell::new(PendingPacket::new(packet_bytes, 0, 0, 0, 0)));
+        let packet_rc = Rc::new(RefCell::new(PendingPacket::new(packet_bytes.into(), 0, 0, 0, 0, false)));
 
         for _ in 0 .. push_count - 1 {
-            assert_eq!(dfe.push(&packet_rc, 0, false), Ok(()));
+            assert_eq!(dfe.push(&packet_rc, 0, None), Ok(()));
         }
-        assert_eq!(dfe.push(&packet_rc, 0, false), final_result);
+        assert_eq!(dfe.push(&packet_rc, 0, None), final_result);
         dfe.finalize();
 
         return frames;
@@ -249,22 +452,23 @@ mod tests {
     fn datagram_test(flush_alloc: isize, payload_size: usize, push_count: usize, final_result: Result<(),DataPushError>) -> Vec<frame::DataFrame> {
         let now_ms = 0;
 
-        let mut fq = frame_queue::FrameQueue::new(MAX_FRAME_WINDOW_SIZE, MAX_FRAME_WINDOW_SIZE, 0);
+        let mut fq = frame_queue::FrameQueue::new(MAX_FRAME_WINDOW_SIZE, MAX_FRAME_WINDOW_SIZE, 0, 3);
 
         let mut frames = Vec::new();
         let emit_cb = |frame_bytes: Box<[u8]>| {
             frames.push(frame_bytes);
+            Ok(0)
         };
 
-        let mut dfe = DataFrameEmitter::new(now_ms, &mut fq, flush_alloc, emit_cb);
+        let mut dfe = DataFrameEmitter::new(now_ms, &mut fq, MAX_FRAME_SIZE, flush_alloc, emit_cb);
 
         let packet_bytes = (0 .. payload_size).map(|i| i as u8).collect::<Vec<u8>>().into_boxed_slice();
-        let packet_rc = Rc::new(RefCell::new(PendingPacket::new(packet_bytes, 0, 0, 0, 0)));
+        let packet_rc = Rc::new(RefCell::new(PendingPacket::new(packet_bytes.into(), 0, 0, 0, 0, false)));
 
         for _ in 0 .. push_count - 1 {
-            assert_eq!(dfe.push(&packet_rc, 0, false), Ok(()));
+            assert_eq!(dfe.push(&packet_rc, 0, None), Ok(()));
         }
-        assert_eq!(dfe.push(&packet_rc, 0, false), final_result);
+        assert_eq!(dfe.push(&packet_rc, 0, None), final_result);
         dfe.finalize();
 
         use frame::serial::Serialize;
@@ -281,9 +485,10 @@ mod tests {
         let mut frames = Vec::new();
         let emit_cb = |frame_bytes: Box<[u8]>| {
             frames.push(frame_bytes);
+            Ok(())
         };
 
-        let mut afe = AckFrameEmitter::new(0, 0, flush_alloc, emit_cb);
+        let mut afe = AckFrameEmitter::new(0, 0, None, None, flush_alloc, emit_cb);
 
         let ack_group = frame::AckGroup { base_id: 0, bitfield: 0, nonce: false };
 
@@ -307,8 +512,69 @@ mod tests {
     fn data_max_frame_size() {
         let frames = max_datagram_test(2 * MAX_FRAME_SIZE as isize, MAX_FRAME_WINDOW_SIZE, 2, Ok(()));
         assert_eq!(frames.len(), 2);
-        assert_eq!(frames[0].len(), MAX_FRAME_SIZE);
-        assert_eq!(frames[1].len(), MAX_FRAME_SIZE);
+        assert_eq!(frames[0].len(), frame::serial::MAX_UNTAGGED_FRAME_SIZE);
+        assert_eq!(frames[1].len(), frame::serial::MAX_UNTAGGED_FRAME_SIZE);
+    }
+
+    // Prefixes each payload with its length
+    struct LengthPrefix;
+
+    impl DatagramTransform for LengthPrefix {
+        fn overhead(&self) -> usize {
+            2
+        }
+
+        fn encode(&mut self, data: &[u8], out: &mut [u8]) {
+            out[.. 2].copy_from_slice(&(data.len() as u16).to_be_bytes());
+            out[2 ..].copy_from_slice(data);
+        }
+
+        fn decode(&mut self, data: &[u8]) -> Option<Box<[u8]>> {
+            Some(data[2 ..].into())
+        }
+    }
+
+    // Transformed payloads should be written into each frame, and their transformed size should be
+    // accounted for when frames are filled
+    #[test]
+    fn data_transform() {
+        let payload_len = 5;
+        let max_frame_size = frame::serial::DATA_FRAME_OVERHEAD + 2 * (frame::serial::MIN_DATAGRAM_OVERHEAD + payload_len);
+
+        let mut fq = frame_queue::FrameQueue::new(MAX_FRAME_WINDOW_SIZE, MAX_FRAME_WINDOW_SIZE, 0, 3);
+
+        let mut frames = Vec::new();
+        let emit_cb = |frame_bytes: Box<[u8]>| {
+            frames.push(frame_bytes);
+            Ok(0)
+        };
+
+        let mut transform = LengthPrefix;
+
+        let mut dfe = DataFrameEmitter::new(0, &mut fq, max_frame_size, 10 * MAX_FRAME_SIZE as isize, emit_cb);
+        dfe.set_transform(&mut transform);
+
+        let packet_rc = Rc::new(RefCell::new(PendingPacket::new(vec![ 7; payload_len ].into_boxed_slice().into(), 0, 0, 0, 0, false)));
+
+        assert_eq!(dfe.push(&packet_rc, 0, None), Ok(()));
+        assert_eq!(dfe.push(&packet_rc, 0, None), Ok(()));
+        dfe.finalize();
+
+        // Untransformed, both datagrams would have fit in one frame
+        assert_eq!(frames.len(), 2);
+
+        use frame::serial::Serialize;
+
+        for frame_bytes in frames.iter() {
+            match frame::Frame::read(&frame_bytes) {
+                Some(frame::Frame::DataFrame(data_frame)) => {
+                    assert_eq!(data_frame.datagrams.len(), 1);
+                    assert_eq!(&data_frame.datagrams[0].data[..], &[ 0, 5, 7, 7, 7, 7, 7 ]);
+                    assert_eq!(LengthPrefix.decode(&data_frame.datagrams[0].data), Some(vec![ 7; payload_len ].into()));
+                }
+                _ => panic!(),
+            }
+        }
     }
 
     #[test]
@@ -354,6 +620,59 @@ mod tests {
         }
     }
 
+    #[test]
+    fn data_reduced_frame_size() {
+        let max_frame_size = MAX_FRAME_SIZE / 4;
+
+        let mut fq = frame_queue::FrameQueue::new(MAX_FRAME_WINDOW_SIZE, MAX_FRAME_WINDOW_SIZE, 0, 3);
+
+        let mut frames = Vec::new();
+        let emit_cb = |frame_bytes: Box<[u8]>| {
+            frames.push(frame_bytes);
+            Ok(0)
+        };
+
+        let mut dfe = DataFrameEmitter::new(0, &mut fq, max_frame_size, 10 * MAX_FRAME_SIZE as isize, emit_cb);
+
+        let small_packet_rc = Rc::new(RefCell::new(PendingPacket::new(vec![0; 100].into_boxed_slice().into(), 0, 0, 0, 0, false)));
+        let large_packet_rc = Rc::new(RefCell::new(PendingPacket::new(vec![0; MAX_FRAGMENT_SIZE].into_boxed_slice().into(), 0, 0, 0, 0, false)));
+
+        for _ in 0 .. 6 {
+            assert_eq!(dfe.push(&small_packet_rc, 0, None), Ok(()));
+        }
+
+        // Datagrams which exceed the target size are sent alone
+        assert_eq!(dfe.push(&large_packet_rc, 0, None), Ok(()));
+        assert_eq!(dfe.push(&small_packet_rc, 0, None), Ok(()));
+
+        dfe.finalize();
+
+        let frame_sizes = frames.iter().map(|frame_bytes| frame_bytes.len()).collect::<Vec<_>>();
+
+        assert_eq!(frame_sizes.len(), 4);
+        assert!(frame_sizes[0] <= max_frame_size && frame_sizes[1] <= max_frame_size);
+        assert_eq!(frame_sizes[2], frame::serial::MAX_UNTAGGED_FRAME_SIZE);
+        assert!(frame_sizes[3] <= max_frame_size);
+    }
+
+    #[test]
+    fn frame_size_policy() {
+        let mut policy = FrameSizePolicy::new();
+        assert_eq!(policy.target_size(), MAX_FRAME_SIZE);
+
+        policy.update(FRAME_SIZE_LOSS_RATE_THRESHOLD);
+        assert_eq!(policy.target_size(), MAX_FRAME_SIZE);
+
+        policy.update(FRAME_SIZE_LOSS_RATE_THRESHOLD * 2.0);
+        assert_eq!(policy.target_size(), MAX_FRAME_SIZE / 2);
+
+        policy.update(0.5);
+        assert_eq!(policy.target_size(), MIN_TARGET_FRAME_SIZE);
+
+        policy.update(0.0);
+        assert_eq!(policy.target_size(), MAX_FRAME_SIZE);
+    }
+
     #[test]
     fn data_window_limited() {
         let frames = max_datagram_test(6 * MAX_FRAME_SIZE as isize, 5, 6, Err(DataPushError::WindowLimited));
@@ -418,13 +737,63 @@ mod tests {
         let mut frames = Vec::new();
         let emit_cb = |frame_bytes: Box<[u8]>| {
             frames.push(frame_bytes);
+            Ok(())
         };
 
-        let mut afe = AckFrameEmitter::new(0, 0, MAX_FRAME_SIZE as isize, emit_cb);
+        let mut afe = AckFrameEmitter::new(0, 0, None, None, MAX_FRAME_SIZE as isize, emit_cb);
         assert_eq!(afe.push_dud(), Ok(()));
         afe.finalize();
 
         assert_eq!(frames.len(), 1);
     }
-}
 
+    // A frame which could not be sent should not be counted, and its fragments should be returned
+    #[test]
+    fn data_send_failed() {
+        let mut fq = frame_queue::FrameQueue::new(MAX_FRAME_WINDOW_SIZE, MAX_FRAME_WINDOW_SIZE, 0, 3);
+
+        let mut send_count = 0;
+        let emit_cb = |_: Box<[u8]>| {
+            send_count += 1;
+            if send_count == 1 { Ok(0) } else { Err(()) }
+        };
+
+        let mut dfe = DataFrameEmitter::new(0, &mut fq, MAX_FRAME_SIZE, 10 * MAX_FRAME_SIZE as isize, emit_cb);
+
+        let packet_bytes = (0 .. 3*MAX_FRAGMENT_SIZE).map(|i| i as u8).collect::<Vec<u8>>().into_boxed_slice();
+        let packet_rc = Rc::new(RefCell::new(PendingPacket::new(packet_bytes.into(), 0, 0, 0, 0, false)));
+
+        let schedule = ResendSchedule { resend_time: 100, send_count: 1 };
+
+        assert_eq!(dfe.push(&packet_rc, 0, Some(schedule)), Ok(()));
+        assert_eq!(dfe.push(&packet_rc, 1, None), Ok(()));
+        assert_eq!(dfe.push(&packet_rc, 2, Some(schedule)), Err(DataPushError::SendFailed));
+
+        let (resend_entries, unsent_entries) = dfe.finish();
+
+        assert_eq!(resend_entries.len(), 1);
+        assert_eq!(resend_entries[0].fragment_ref.fragment_id, 0);
+
+        assert_eq!(unsent_entries.len(), 1);
+        assert_eq!(unsent_entries[0].fragment_ref.fragment_id, 1);
+        assert!(!unsent_entries[0].resend);
+
+        // Only the first frame was sent
+        assert_eq!(fq.next_id(), 1);
+    }
+
+    #[test]
+    fn ack_send_failed() {
+        let emit_cb = |_: Box<[u8]>| Err(());
+
+        let mut afe = AckFrameEmitter::new(0, 0, None, None, MAX_FRAME_SIZE as isize, emit_cb);
+
+        let ack_group_a = frame::AckGroup { base_id: 0, bitfield: 1, nonce: false };
+        let ack_group_b = frame::AckGroup { base_id: 40, bitfield: 1, nonce: true };
+
+        assert_eq!(afe.push(&ack_group_a), Ok(()));
+        assert_eq!(afe.push(&ack_group_b), Ok(()));
+
+        assert_eq!(afe.finish(), Err(vec![ ack_group_a, ack_group_b ]));
+    }
+}
@@ -1,23 +1,34 @@
 
+use crate::extended_id;
 use crate::frame;
 
 struct ReceiveWindow {
-    base_id: u32,
+    base_id: extended_id::Type,
     size: u32,
+    // Bit i is set if frame (base_id - 1 - i) was seen
+    seen_history: u64,
 }
 
 impl ReceiveWindow {
     pub fn new(base_id: u32, size: u32) -> Self {
-        Self { base_id, size }
+        // Extended IDs begin at one span past zero, so that earlier IDs may always be reconstructed
+        Self { base_id: (1 << 32) | base_id as extended_id::Type, size, seen_history: 0 }
+    }
+
+    fn extend(&self, frame_id: u32) -> extended_id::Type {
+        extended_id::extend(self.base_id, frame_id)
     }
 
     pub fn contains(&self, frame_id: u32) -> bool {
-        frame_id.wrapping_sub(self.base_id) < self.size
+        let frame_id = self.extend(frame_id);
+        frame_id >= self.base_id && frame_id - self.base_id < self.size as extended_id::Type
     }
 
     pub fn advance(&mut self, new_base_id: u32) -> bool {
-        let delta = new_base_id.wrapping_sub(self.base_id);
-        if delta > 0 && delta <= self.size {
+        let new_base_id = self.extend(new_base_id);
+        if new_base_id > self.base_id && new_base_id - self.base_id <= self.size as extended_id::Type {
+            let delta = (new_base_id - self.base_id) as u32;
+            self.seen_history = self.seen_history.checked_shl(delta).unwrap_or(0);
             self.base_id = new_base_id;
             true
         } else {
@@ -25,8 +36,25 @@ impl ReceiveWindow {
         }
     }
 
+    pub fn mark_seen(&mut self, frame_id: u32) {
+        if self.advance(frame_id.wrapping_add(1)) {
+            self.seen_history |= 1;
+        }
+    }
+
+    // Returns true if the given frame precedes the window, and is known to have been seen
+    pub fn was_seen(&self, frame_id: u32) -> bool {
+        let frame_id = self.extend(frame_id);
+        if frame_id < self.base_id {
+            let bit = self.base_id - 1 - frame_id;
+            bit < 64 && self.seen_history & (1 << bit) != 0
+        } else {
+            false
+        }
+    }
+
     pub fn base_id(&self) -> u32 {
-        self.base_id
+        extended_id::truncate(self.base_id)
     }
 }
 
@@ -43,6 +71,10 @@ impl FrameAckQueue {
         }
     }
 
+    pub fn window_size(&self) -> u32 {
+        self.receive_window.size
+    }
+
     pub fn base_id(&self) -> u32 {
         self.receive_window.base_id()
     }
@@ -55,9 +87,20 @@ impl FrameAckQueue {
         self.receive_window.contains(frame_id)
     }
 
-    pub fn mark_seen(&mut self, frame_id: u32, nonce: bool) {
+    // Returns true if the given frame has already been marked as seen, and is no longer contained
+    // by the receive window
+    pub fn is_duplicate(&self, frame_id: u32) -> bool {
+        self.receive_window.was_seen(frame_id)
+    }
+
+    // Returns true if one or more preceding frames were skipped, i.e. a gap was detected
+    pub fn mark_seen(&mut self, frame_id: u32, nonce: bool) -> bool {
+        let mut gap = false;
+
         if self.receive_window.contains(frame_id) {
-            self.receive_window.advance(frame_id.wrapping_add(1));
+            gap = frame_id != self.receive_window.base_id();
+
+            self.receive_window.mark_seen(frame_id);
 
             if let Some(last_entry) = self.entries.back_mut() {
                 let bit = frame_id.wrapping_sub(last_entry.base_id);
@@ -81,6 +124,8 @@ impl FrameAckQueue {
                 });
             }
         }
+
+        gap
     }
 
     pub fn pop(&mut self) -> Option<frame::AckGroup> {
@@ -93,6 +138,13 @@ impl FrameAckQueue {
         return None;
     }
 
+    // Returns previously popped ack groups to the front of the queue, in order
+    pub fn requeue(&mut self, ack_groups: Vec<frame::AckGroup>) {
+        for ack_group in ack_groups.into_iter().rev() {
+            self.entries.push_front(ack_group);
+        }
+    }
+
     pub fn peek(&self) -> Option<&frame::AckGroup> {
         self.entries.front()
     }
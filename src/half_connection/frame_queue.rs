@@ -1,4 +1,5 @@
 
+use crate::extended_id;
 use crate::frame;
 
 use super::reorder_buffer;
@@ -6,6 +7,8 @@ use super::loss_rate;
 
 use super::send_rate;
 
+use super::path_set::PathFeedback;
+
 use super::pending_packet::FragmentRef;
 
 use std::collections::VecDeque;
@@ -17,39 +20,67 @@ pub struct Entry {
     pub fragment_refs: Box<[FragmentRef]>,
     pub nonce: bool,
     pub rate_limited: bool,
+    pub retransmit: bool,
     pub acked: bool,
+    // Index of the path over which the frame was sent
+    pub path: u8,
+}
+
+// Frame IDs are extended relative to the base of the frame log, or of the transfer window
+fn extend_frame_id(reference: extended_id::Type, frame_id: u32) -> extended_id::Type {
+    extended_id::extend(reference, frame_id)
+}
+
+// Extended IDs begin at one span past zero, so that earlier IDs may always be reconstructed
+fn initial_frame_id(base_id: u32) -> extended_id::Type {
+    (1 << 32) | base_id as extended_id::Type
 }
 
 #[derive(Debug)]
 pub struct FrameLog {
-    next_id: u32,
-    base_id: u32,
+    next_id: extended_id::Type,
+    base_id: extended_id::Type,
     frames: VecDeque<Entry>,
+    // Send time of the most recently drained frame
+    drained_send_time_ms: u64,
 }
 
 impl FrameLog {
     pub fn new(base_id: u32) -> Self {
         Self {
-            next_id: base_id,
-            base_id: base_id,
+            next_id: initial_frame_id(base_id),
+            base_id: initial_frame_id(base_id),
             frames: VecDeque::new(),
+            drained_send_time_ms: 0,
         }
     }
 
     pub fn next_id(&self) -> u32 {
-        self.next_id
+        extended_id::truncate(self.next_id)
     }
 
     pub fn base_id(&self) -> u32 {
+        extended_id::truncate(self.base_id)
+    }
+
+    pub fn next_id_ext(&self) -> extended_id::Type {
+        self.next_id
+    }
+
+    pub fn base_id_ext(&self) -> extended_id::Type {
         self.base_id
     }
 
+    fn index(&self, frame_id: u32) -> Option<usize> {
+        extend_frame_id(self.base_id, frame_id).checked_sub(self.base_id).map(|delta| delta as usize)
+    }
+
     pub fn get_frame(&self, frame_id: u32) -> Option<&Entry> {
-        self.frames.get(frame_id.wrapping_sub(self.base_id) as usize)
+        self.index(frame_id).and_then(|idx| self.frames.get(idx))
     }
 
     pub fn get_frame_mut(&mut self, frame_id: u32) -> Option<&mut Entry> {
-        self.frames.get_mut(frame_id.wrapping_sub(self.base_id) as usize)
+        self.index(frame_id).and_then(move |idx| self.frames.get_mut(idx))
     }
 
     pub fn len(&self) -> u32 {
@@ -61,26 +92,27 @@ impl FrameLog {
         debug_assert!(self.frames.len() < u32::MAX as usize);
 
         self.frames.push_back(entry);
-        self.next_id = self.next_id.wrapping_add(1);
+        self.next_id += 1;
     }
 
-    pub fn find_expiration_cutoff(&self, thresh_ms: u64) -> u32 {
-        let mut expiry_point = self.base_id;
-        for frame in self.frames.iter() {
-            if frame.send_time_ms < thresh_ms {
-                expiry_point = expiry_point.wrapping_add(1);
-            } else {
-                break;
-            }
-        }
-        return expiry_point;
+    pub fn find_expiration_cutoff(&self, thresh_ms: u64) -> extended_id::Type {
+        let expired_count = self.frames.iter().take_while(|frame| frame.send_time_ms < thresh_ms).count();
+        self.base_id + expired_count as extended_id::Type
     }
 
-    pub fn drain(&mut self, frame_id: u32) {
-        let drain_idx = frame_id.wrapping_sub(self.base_id) as usize;
-        self.frames.drain(.. drain_idx);
+    pub fn drain(&mut self, frame_id: extended_id::Type) {
+        debug_assert!(frame_id >= self.base_id && frame_id <= self.next_id);
+
+        let drain_idx = (frame_id - self.base_id) as usize;
+        if let Some(last) = self.frames.drain(.. drain_idx).next_back() {
+            self.drained_send_time_ms = last.send_time_ms;
+        }
         self.base_id = frame_id;
     }
+
+    pub fn drained_send_time_ms(&self) -> u64 {
+        self.drained_send_time_ms
+    }
 }
 
 fn ms_to_s(v_s: u64) -> f64 {
@@ -88,11 +120,20 @@ fn ms_to_s(v_s: u64) -> f64 {
 }
 
 struct AckData {
-    last_send_time_ms: u64,
+    // Send time of the latest acknowledged frame which contained no retransmitted data
+    last_send_time_ms: Option<u64>,
     total_ack_size: usize,
     rate_limited: bool,
 }
 
+impl AckData {
+    fn merge(&mut self, other: AckData) {
+        self.last_send_time_ms = self.last_send_time_ms.max(other.last_send_time_ms);
+        self.total_ack_size += other.total_ack_size;
+        self.rate_limited |= other.rate_limited;
+    }
+}
+
 pub struct FeedbackGen {
     // Last time feedback was handled
     last_feedback_ms: Option<u64>,
@@ -100,22 +141,26 @@ pub struct FeedbackGen {
     // Aggregated feedback data from latest ack frames
     ack_data: Option<AckData>,
 
-    // Determines when frames are considered dropped (NDUPACK = 3)
+    // Determines when frames are considered dropped (NDUPACK = reorder tolerance)
     reorder_buffer: reorder_buffer::ReorderBuffer,
 
     // Used to compute the receiver loss rate
     loss_intervals: loss_rate::LossIntervalQueue,
+
+    // IDs of frames which have been declared lost since last taken
+    nacked_ids: Vec<u32>,
 }
 
 impl FeedbackGen {
     const INITIAL_RTT_MS: u64 = 100;
 
-    fn new(base_id: u32, max_span: u32) -> Self {
+    fn new(base_id: u32, max_span: u32, reorder_tolerance: u32) -> Self {
         Self {
             last_feedback_ms: None,
             ack_data: None,
-            reorder_buffer: reorder_buffer::ReorderBuffer::new(base_id, max_span),
+            reorder_buffer: reorder_buffer::ReorderBuffer::new(base_id, max_span, reorder_tolerance),
             loss_intervals: loss_rate::LossIntervalQueue::new(),
+            nacked_ids: Vec::new(),
         }
     }
 
@@ -123,9 +168,15 @@ impl FeedbackGen {
         self.loss_intervals.reset(new_loss_rate);
     }
 
+    // Forgets any frames awaiting acknowledgement, but retains the loss history of the path
+    fn reset(&mut self, base_id: u32) {
+        self.reorder_buffer.reset(base_id);
+        self.nacked_ids.clear();
+    }
+
     fn get_feedback(&mut self, now_ms: u64) -> Option<send_rate::FeedbackData> {
         if let Some(ack_data) = self.ack_data.take() {
-            let rtt_ms = now_ms - ack_data.last_send_time_ms;
+            let rtt_ms = ack_data.last_send_time_ms.map(|last_send_time_ms| now_ms - last_send_time_ms);
 
             let receive_rate = if let Some(last_feedback_ms) = self.last_feedback_ms {
                 let delta_time_s = ms_to_s(now_ms - last_feedback_ms);
@@ -148,9 +199,7 @@ impl FeedbackGen {
 
     fn put_ack_data(&mut self, ack_data: AckData) {
         if let Some(ref mut feedback_data) = self.ack_data {
-            feedback_data.last_send_time_ms = feedback_data.last_send_time_ms.max(ack_data.last_send_time_ms);
-            feedback_data.total_ack_size += ack_data.total_ack_size;
-            feedback_data.rate_limited |= ack_data.rate_limited;
+            feedback_data.merge(ack_data);
         } else {
             self.ack_data = Some(ack_data);
         }
@@ -158,6 +207,7 @@ impl FeedbackGen {
 
     fn notify_ack(&mut self, frame_id: u32, frame_log: &FrameLog, rtt_ms: Option<u64>) {
         let ref mut loss_intervals = self.loss_intervals;
+        let nacked_ids = &mut self.nacked_ids;
 
         if self.reorder_buffer.can_put(frame_id) {
             // New frame, cycle reorder buffer
@@ -168,6 +218,7 @@ impl FeedbackGen {
                     loss_intervals.push_ack();
                 } else {
                     loss_intervals.push_nack(sent_frame.send_time_ms, rtt_ms.unwrap_or(Self::INITIAL_RTT_MS));
+                    nacked_ids.push(frame_id);
                 }
             });
         } else {
@@ -178,6 +229,7 @@ impl FeedbackGen {
 
     fn notify_advancement(&mut self, new_base_id: u32, frame_log: &FrameLog, rtt_ms: Option<u64>) {
         let ref mut loss_intervals = self.loss_intervals;
+        let nacked_ids = &mut self.nacked_ids;
 
         if self.reorder_buffer.can_advance(new_base_id) {
             // This new base ID necessitates ack/nack advancement
@@ -188,6 +240,7 @@ impl FeedbackGen {
                     loss_intervals.push_ack();
                 } else {
                     loss_intervals.push_nack(sent_frame.send_time_ms, rtt_ms.unwrap_or(Self::INITIAL_RTT_MS));
+                    nacked_ids.push(frame_id);
                 }
             });
         }
@@ -195,14 +248,14 @@ impl FeedbackGen {
 }
 
 struct TransferWindow {
-    base_id: u32,
+    base_id: extended_id::Type,
     size: u32,
     tail_size: u32,
 }
 
 impl TransferWindow {
     fn new(base_id: u32, size: u32, tail_size: u32) -> Self {
-        Self { base_id, size, tail_size }
+        Self { base_id: initial_frame_id(base_id), size, tail_size }
     }
 }
 
@@ -212,35 +265,130 @@ pub struct FrameQueue {
     window: TransferWindow,
 
     rate_limited: bool,
+    retransmit: bool,
+    path: u8,
+
+    duplicate_ack_count: u64,
+
+    // Sequence IDs of packets with fragments in frames which have been declared lost
+    lost_packet_ids: Vec<u32>,
+
+    // Acknowledgements and losses of frames, by path, since last taken, if tracked
+    path_feedback: Option<Vec<PathFeedback>>,
 }
 
 impl FrameQueue {
-    pub fn new(size: u32, tail_size: u32, base_id: u32) -> Self {
+    pub fn new(size: u32, tail_size: u32, base_id: u32, reorder_tolerance: u32) -> Self {
         Self {
             frame_log: FrameLog::new(base_id),
-            feedback_gen: FeedbackGen::new(base_id, size + tail_size),
+            feedback_gen: FeedbackGen::new(base_id, size + tail_size, reorder_tolerance),
             window: TransferWindow::new(base_id, size, tail_size),
 
             rate_limited: false,
+            retransmit: false,
+            path: 0,
+
+            duplicate_ack_count: 0,
+
+            lost_packet_ids: Vec::new(),
+
+            path_feedback: None,
         }
     }
 
+    // Returns the number of valid ack groups received which acknowledged no new frames
+    pub fn duplicate_ack_count(&self) -> u64 {
+        self.duplicate_ack_count
+    }
+
     pub fn can_push(&self) -> bool {
-        return self.next_id().wrapping_sub(self.window.base_id) < self.window.size;
+        self.frame_log.next_id_ext() - self.window.base_id < self.window.size as extended_id::Type
     }
 
     pub fn next_id(&self) -> u32 {
         self.frame_log.next_id()
     }
 
+    pub fn in_flight_count(&self) -> u32 {
+        (self.frame_log.next_id_ext() - self.window.base_id) as u32
+    }
+
+    // Returns the combined size of all frames in the transfer window which have not been
+    // acknowledged, in bytes. Frames which have been forgotten are presumed lost.
+    pub fn in_flight_size(&self) -> usize {
+        let log_base_id = self.frame_log.base_id_ext();
+        let skip_count = self.window.base_id.saturating_sub(log_base_id) as usize;
+
+        self.frame_log.frames.iter()
+            .skip(skip_count)
+            .filter(|sent_frame| !sent_frame.acked)
+            .map(|sent_frame| sent_frame.size as usize)
+            .sum()
+    }
+
+    pub fn window_size(&self) -> u32 {
+        self.window.size
+    }
+
     pub fn base_id(&self) -> u32 {
-        self.window.base_id
+        extended_id::truncate(self.window.base_id)
+    }
+
+    // Returns the sequence IDs of any packets which lost a fragment since this was last called
+    pub fn take_lost_packets(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.lost_packet_ids)
+    }
+
+    // Begins recording the acknowledgement or loss of each frame against the path over which it
+    // was sent
+    pub fn track_paths(&mut self) {
+        self.path_feedback.get_or_insert_with(Vec::new);
+    }
+
+    // Returns the per-path feedback recorded since this was last called
+    pub fn take_path_feedback(&mut self) -> Vec<PathFeedback> {
+        self.path_feedback.as_mut().map_or_else(Vec::new, std::mem::take)
+    }
+
+    fn collect_lost_packets(&mut self) {
+        for frame_id in std::mem::take(&mut self.feedback_gen.nacked_ids).into_iter() {
+            if let Some(sent_frame) = self.frame_log.get_frame(frame_id) {
+                if sent_frame.acked {
+                    continue;
+                }
+
+                if let Some(ref mut path_feedback) = self.path_feedback {
+                    path_feedback.push(PathFeedback::Loss { path: sent_frame.path });
+                }
+
+                for fragment_ref in sent_frame.fragment_refs.iter() {
+                    if let Some(packet_rc) = fragment_ref.packet.upgrade() {
+                        let sequence_id = packet_rc.borrow().sequence_id();
+
+                        if !self.lost_packet_ids.contains(&sequence_id) {
+                            self.lost_packet_ids.push(sequence_id);
+                        }
+                    }
+                }
+            }
+        }
     }
 
     pub fn mark_rate_limited(&mut self) {
         self.rate_limited = true;
     }
 
+    // Marks the next frame pushed as containing retransmitted data, which precludes it from being
+    // used as an RTT sample (Karn's algorithm)
+    pub fn mark_retransmit(&mut self) {
+        self.retransmit = true;
+    }
+
+    // Marks the next frame pushed as having been sent over the given path
+    pub fn mark_path(&mut self, path: u8) {
+        self.path = path;
+    }
+
     pub fn push(&mut self, size: usize, now_ms: u64, fragment_refs: Box<[FragmentRef]>, nonce: bool) {
         debug_assert!(size <= u32::MAX as usize);
 
@@ -251,23 +399,60 @@ impl FrameQueue {
                 fragment_refs,
                 nonce,
                 rate_limited: self.rate_limited,
+                retransmit: self.retransmit,
                 acked: false,
+                path: self.path,
             });
 
             self.rate_limited = false;
+            self.retransmit = false;
+            self.path = 0;
         }
     }
 
     pub fn forget_frames(&mut self, thresh_ms: u64, rtt_ms: Option<u64>) {
         let max_base_id = self.frame_log.find_expiration_cutoff(thresh_ms);
 
-        let delta = max_base_id.wrapping_sub(self.frame_log.base_id());
-
-        if delta != 0 {
+        if max_base_id != self.frame_log.base_id_ext() {
             self.cull_log_entries(max_base_id, rtt_ms);
         }
     }
 
+    // Declares any frames in the transfer window which were sent before the given time lost, and
+    // forcibly advances the transfer window past them. The fragments of each unacknowledged frame
+    // are passed to the given callback. Returns the number of frames the window was advanced by.
+    pub fn expire_frames<F>(&mut self, thresh_ms: u64, rtt_ms: Option<u64>, mut expired_cb: F) -> u32
+        where F: FnMut(FragmentRef) {
+        let window_base_id = self.window.base_id;
+        let log_base_id = self.frame_log.base_id_ext();
+
+        if window_base_id < log_base_id && self.frame_log.drained_send_time_ms() >= thresh_ms {
+            // Some forgotten frames in the window have not yet expired
+            return 0;
+        }
+
+        let cutoff_id = self.frame_log.find_expiration_cutoff(thresh_ms);
+
+        if cutoff_id <= window_base_id {
+            return 0;
+        }
+
+        for frame_id in window_base_id.max(log_base_id) .. cutoff_id {
+            let sent_frame = self.frame_log.get_frame_mut(extended_id::truncate(frame_id)).unwrap();
+
+            if !sent_frame.acked {
+                for fragment_ref in std::mem::take(&mut sent_frame.fragment_refs).into_vec().into_iter() {
+                    expired_cb(fragment_ref);
+                }
+            }
+        }
+
+        self.window.base_id = cutoff_id;
+        self.cull_log_entries(cutoff_id, rtt_ms);
+
+        (cutoff_id - window_base_id) as u32
+    }
+
     pub fn get_feedback(&mut self, now_ms: u64) -> Option<send_rate::FeedbackData> {
         self.feedback_gen.get_feedback(now_ms)
     }
@@ -276,12 +461,63 @@ impl FrameQueue {
         self.feedback_gen.reset_loss_rate(new_loss_rate);
     }
 
+    // Forgets every frame sent so far, and restarts the transfer window at the next sequence ID.
+    // Acknowledgements of earlier frames will be ignored.
+    pub fn reset(&mut self) {
+        let base_id = self.frame_log.next_id();
+
+        self.frame_log = FrameLog::new(base_id);
+        self.feedback_gen.reset(base_id);
+        self.window = TransferWindow::new(base_id, self.window.size, self.window.tail_size);
+
+        self.lost_packet_ids.clear();
+    }
+
+    #[cfg(test)]
     pub fn acknowledge_group(&mut self, ack: frame::AckGroup, rtt_ms: Option<u64>) {
-        let mut true_nonce = false;
+        self.acknowledge_groups(vec![ ack ], rtt_ms);
+    }
 
-        let mut last_send_time_ms = 0;
-        let mut total_ack_size = 0;
-        let mut rate_limited = false;
+    // Acknowledges a batch of ack groups, such as those of several ack frames received at once.
+    // Equivalent to acknowledging each group in turn, except that the groups are visited in order of
+    // frame ID, so that acks which were reordered in transit do not register as losses, and lost
+    // packets and feedback data are collected once for the entire batch.
+    pub fn acknowledge_groups(&mut self, mut acks: Vec<frame::AckGroup>, rtt_ms: Option<u64>) {
+        let log_base_id = self.frame_log.base_id();
+
+        acks.sort_by_key(|ack| (ack.base_id.wrapping_sub(log_base_id), ack.bitfield));
+
+        // Identical groups are common when acks are retransmitted
+        let group_count = acks.len();
+        acks.dedup();
+        self.duplicate_ack_count += (group_count - acks.len()) as u64;
+
+        let mut batch_ack_data: Option<AckData> = None;
+
+        for ack in acks.iter() {
+            let bitfield_size = match self.validate_group(ack) {
+                Some(bitfield_size) => bitfield_size,
+                None => continue,
+            };
+
+            match (self.mark_group(ack, bitfield_size, rtt_ms), batch_ack_data.as_mut()) {
+                (Some(ack_data), Some(batch_ack_data)) => batch_ack_data.merge(ack_data),
+                (Some(ack_data), None) => batch_ack_data = Some(ack_data),
+                (None, _) => self.duplicate_ack_count += 1,
+            }
+        }
+
+        self.collect_lost_packets();
+
+        if let Some(ack_data) = batch_ack_data {
+            self.feedback_gen.put_ack_data(ack_data);
+        }
+    }
+
+    // Returns the number of frames spanned by the given ack group, or None if the group
+    // acknowledges nothing, refers to frames which are not in the frame log, or has a bad nonce
+    fn validate_group(&self, ack: &frame::AckGroup) -> Option<u32> {
+        let mut true_nonce = false;
 
         let mut bitfield_size = 0;
         for i in (0 .. 32).rev() {
@@ -293,7 +529,7 @@ impl FrameQueue {
 
         if bitfield_size == 0 {
             // Dud
-            return;
+            return None;
         }
 
         for i in 0 .. bitfield_size {
@@ -306,15 +542,26 @@ impl FrameQueue {
                 }
             } else {
                 // Packet forgotten or ack group exceeds span of transfer queue
-                return;
+                return None;
             }
         }
 
         if ack.nonce != true_nonce {
             // Penalize bad nonce
-            return;
+            return None;
         }
 
+        Some(bitfield_size)
+    }
+
+    // Marks the frames of a valid ack group acknowledged, returning feedback data if any were not
+    // already acknowledged
+    fn mark_group(&mut self, ack: &frame::AckGroup, bitfield_size: u32, rtt_ms: Option<u64>) -> Option<AckData> {
+        let mut last_send_time_ms = None;
+        let mut total_ack_size = 0;
+        let mut rate_limited = false;
+        let mut new_ack = false;
+
         for i in 0 .. bitfield_size {
             let frame_id = ack.base_id.wrapping_add(i);
 
@@ -326,6 +573,7 @@ impl FrameQueue {
                 // Receiver has received this packet
                 if sent_frame.acked == false {
                     sent_frame.acked = true;
+                    new_ack = true;
 
                     // Mark each fragment acknowledged and clear the list
                     let fragment_refs = std::mem::take(&mut sent_frame.fragment_refs);
@@ -338,8 +586,16 @@ impl FrameQueue {
                         }
                     }
 
-                    // Mark send time of latest included packet
-                    last_send_time_ms = last_send_time_ms.max(sent_frame.send_time_ms);
+                    // Mark send time of latest included packet, unless its acknowledgement could
+                    // correspond to an earlier transmission
+                    if !sent_frame.retransmit {
+                        last_send_time_ms = last_send_time_ms.max(Some(sent_frame.send_time_ms));
+                    }
+
+                    if let Some(ref mut path_feedback) = self.path_feedback {
+                        let send_time_ms = if sent_frame.retransmit { None } else { Some(sent_frame.send_time_ms) };
+                        path_feedback.push(PathFeedback::Ack { path: sent_frame.path, send_time_ms });
+                    }
 
                     // Add to total ack size
                     total_ack_size += sent_frame.size as usize;
@@ -350,39 +606,39 @@ impl FrameQueue {
             }
         }
 
-        // Add to pending feedback data
-        self.feedback_gen.put_ack_data(AckData { last_send_time_ms, total_ack_size, rate_limited });
+        if new_ack {
+            Some(AckData { last_send_time_ms, total_ack_size, rate_limited })
+        } else {
+            None
+        }
     }
 
     pub fn can_advance_transfer_window(&mut self, new_base_id: u32) -> bool {
-        let log_next_id = self.frame_log.next_id();
         let window_base_id = self.window.base_id;
+        let new_base_id = extend_frame_id(window_base_id, new_base_id);
 
         // Ensure transfer window never backtracks and never advances beyond frame log's next_id
-        let next_delta = log_next_id.wrapping_sub(window_base_id);
-        let delta = new_base_id.wrapping_sub(window_base_id);
-
-        delta != 0 && delta <= next_delta
+        new_base_id > window_base_id && new_base_id <= self.frame_log.next_id_ext()
     }
 
     pub fn advance_transfer_window(&mut self, new_base_id: u32, rtt_ms: Option<u64>) {
         if self.can_advance_transfer_window(new_base_id) {
-            self.window.base_id = new_base_id;
-
-            let max_base_id = self.window.base_id.wrapping_sub(self.window.tail_size);
+            self.window.base_id = extend_frame_id(self.window.base_id, new_base_id);
 
-            let delta = max_base_id.wrapping_sub(self.frame_log.base_id());
+            let max_base_id = self.window.base_id - self.window.tail_size as extended_id::Type;
+            let log_base_id = self.frame_log.base_id_ext();
 
-            if delta != 0 && delta <= self.frame_log.len() {
+            if max_base_id > log_base_id && max_base_id - log_base_id <= self.frame_log.len() as extended_id::Type {
                 self.cull_log_entries(max_base_id, rtt_ms);
             }
         }
     }
 
-    fn cull_log_entries(&mut self, new_log_base_id: u32, rtt_ms: Option<u64>) {
-        debug_assert!(new_log_base_id.wrapping_sub(self.frame_log.base_id()) <= self.frame_log.len());
+    fn cull_log_entries(&mut self, new_log_base_id: extended_id::Type, rtt_ms: Option<u64>) {
+        debug_assert!(new_log_base_id - self.frame_log.base_id_ext() <= self.frame_log.len() as extended_id::Type);
 
-        self.feedback_gen.notify_advancement(new_log_base_id, &self.frame_log, rtt_ms);
+        self.feedback_gen.notify_advancement(extended_id::truncate(new_log_base_id), &self.frame_log, rtt_ms);
+        self.collect_lost_packets();
         self.frame_log.drain(new_log_base_id);
     }
 }
@@ -409,10 +665,10 @@ mod tests {
 
     #[test]
     fn feedback_generation() {
-        let mut fq = FrameQueue::new(MAX_FRAME_WINDOW_SIZE, MAX_FRAME_WINDOW_SIZE, 0);
+        let mut fq = FrameQueue::new(MAX_FRAME_WINDOW_SIZE, MAX_FRAME_WINDOW_SIZE, 0, 3);
 
         let packet_rc = Rc::new(RefCell::new(
-            PendingPacket::new(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice(), 0, 0, 0, 0)
+            PendingPacket::new(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice().into(), 0, 0, 0, 0, false)
         ));
 
         let n0 = rand::random();
@@ -439,7 +695,7 @@ mod tests {
             loss_rate: 0.0,
             receive_rate: 0, // First receive_rate is always zero
             rate_limited: false,
-            rtt_ms: 1000,
+            rtt_ms: Some(1000),
         }));
 
         fq.acknowledge_group(frame::AckGroup { base_id: 2, bitfield: 0b11, nonce: n2 ^ n3 }, None);
@@ -448,7 +704,7 @@ mod tests {
             loss_rate: 0.0,
             receive_rate: 8,
             rate_limited: false,
-            rtt_ms: 2000,
+            rtt_ms: Some(2000),
         }));
 
         fq.acknowledge_group(frame::AckGroup { base_id: 4, bitfield: 0b1, nonce: n4 }, None);
@@ -458,7 +714,7 @@ mod tests {
             loss_rate: 0.2, // Frame 2 was dropped, current loss interval is 5 sequence IDs long
             receive_rate: 48,
             rate_limited: true, // Frame 4 was marked rate limited
-            rtt_ms: 3000,
+            rtt_ms: Some(3000),
         }));
 
         // No feedback until an ack frame has been received
@@ -467,10 +723,10 @@ mod tests {
 
     #[test]
     fn window_advancement() {
-        let mut fq = FrameQueue::new(5, 3, 0);
+        let mut fq = FrameQueue::new(5, 3, 0, 3);
 
         let packet_rc = Rc::new(RefCell::new(
-            PendingPacket::new(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice(), 0, 0, 0, 0)
+            PendingPacket::new(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice().into(), 0, 0, 0, 0, false)
         ));
 
         let n0 = rand::random();
@@ -508,19 +764,19 @@ mod tests {
             loss_rate: 0.2, // Frames 0-1 were dropped, current loss interval is 5 sequence IDs long
             receive_rate: 0, // First receive_rate is always zero
             rate_limited: false,
-            rtt_ms: 1000,
+            rtt_ms: Some(1000),
         }));
     }
 
     fn new_full_queue(size: u32) -> (FrameQueue, Vec<bool>) {
-        let mut fq = FrameQueue::new(size, size, 0);
+        let mut fq = FrameQueue::new(size, size, 0, 3);
 
         let mut nonces = Vec::new();
 
         for _ in 0 .. size {
             let nonce = rand::random();
             let packet_rc = Rc::new(RefCell::new(
-                PendingPacket::new(vec![].into_boxed_slice(), 0, 0, 0, 0)
+                PendingPacket::new(vec![].into_boxed_slice().into(), 0, 0, 0, 0, false)
             ));
 
             fq.push(32, 0, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), nonce);
@@ -532,7 +788,7 @@ mod tests {
         for _ in 0 .. size {
             let nonce = rand::random();
             let packet_rc = Rc::new(RefCell::new(
-                PendingPacket::new(vec![].into_boxed_slice(), 0, 0, 0, 0)
+                PendingPacket::new(vec![].into_boxed_slice().into(), 0, 0, 0, 0, false)
             ));
 
             fq.push(32, 0, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), nonce);
@@ -563,7 +819,7 @@ mod tests {
             loss_rate: 1.0/((2*size) as f64),
             receive_rate: 0,
             rate_limited: false,
-            rtt_ms: 1000,
+            rtt_ms: Some(1000),
         }));
     }
 
@@ -588,7 +844,7 @@ mod tests {
             loss_rate: 1.0/(size as f64),
             receive_rate: 0,
             rate_limited: false,
-            rtt_ms: 1000,
+            rtt_ms: Some(1000),
         }));
     }
 
@@ -613,8 +869,233 @@ mod tests {
             loss_rate: 1.0/((2*size) as f64),
             receive_rate: 0,
             rate_limited: false,
-            rtt_ms: 1000,
+            rtt_ms: Some(1000),
+        }));
+    }
+
+    #[test]
+    fn frame_expiry() {
+        let mut fq = FrameQueue::new(4, 4, 0, 3);
+
+        let packet_rc = Rc::new(RefCell::new(
+            PendingPacket::new(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice().into(), 0, 0, 0, 0, false)
+        ));
+
+        let n0 = rand::random();
+        let n1 = rand::random();
+
+        fq.push(1,  0, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), n0);
+        fq.push(1, 10, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), n1);
+        fq.push(1, 20, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), false);
+        fq.push(1, 30, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), false);
+
+        assert_eq!(fq.can_push(), false);
+
+        fq.acknowledge_group(frame::AckGroup { base_id: 1, bitfield: 0b1, nonce: n1 }, None);
+
+        // No frames are old enough
+        assert_eq!(fq.expire_frames(0, None, |_| panic!()), 0);
+
+        // Frames 0 through 2 have expired, but frame 1 was acknowledged
+        let mut expired_count = 0;
+        assert_eq!(fq.expire_frames(25, None, |_| expired_count += 1), 3);
+        assert_eq!(expired_count, 2);
+
+        assert_eq!(fq.base_id(), 3);
+        assert_eq!(fq.frame_log.base_id(), 3);
+        assert_eq!(fq.can_push(), true);
+    }
+
+    #[test]
+    fn forgotten_frame_expiry() {
+        let mut fq = FrameQueue::new(4, 4, 0, 3);
+
+        let packet_rc = Rc::new(RefCell::new(
+            PendingPacket::new(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice().into(), 0, 0, 0, 0, false)
+        ));
+
+        fq.push(1,  0, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), false);
+        fq.push(1, 10, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), false);
+        fq.push(1, 20, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), false);
+
+        // Frames 0 and 1 are forgotten, but remain in the transfer window
+        fq.forget_frames(15, None);
+        assert_eq!(fq.frame_log.base_id(), 2);
+        assert_eq!(fq.base_id(), 0);
+
+        // Frame 1 has not yet expired
+        assert_eq!(fq.expire_frames(5, None, |_| panic!()), 0);
+        assert_eq!(fq.base_id(), 0);
+
+        // Forgotten frames no longer have fragments to report
+        assert_eq!(fq.expire_frames(15, None, |_| panic!()), 2);
+        assert_eq!(fq.base_id(), 2);
+    }
+
+    #[test]
+    fn duplicate_acknowledgement() {
+        let mut fq = FrameQueue::new(MAX_FRAME_WINDOW_SIZE, MAX_FRAME_WINDOW_SIZE, 0, 3);
+
+        let packet_rc = Rc::new(RefCell::new(
+            PendingPacket::new(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice().into(), 0, 0, 0, 0, false)
+        ));
+
+        let n0 = rand::random();
+        let n1 = rand::random();
+
+        fq.push(4, 100, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), n0);
+        fq.push(8, 100, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), n1);
+
+        fq.acknowledge_group(frame::AckGroup { base_id: 0, bitfield: 0b1, nonce: n0 }, None);
+        assert_eq!(fq.get_feedback(1000).unwrap().rtt_ms, Some(900));
+
+        // A retransmitted ack group produces no feedback
+        fq.acknowledge_group(frame::AckGroup { base_id: 0, bitfield: 0b1, nonce: n0 }, None);
+        assert_eq!(fq.get_feedback(2000), None);
+        assert_eq!(fq.duplicate_ack_count(), 1);
+
+        // Nor does it skew feedback when combined with a new acknowledgement
+        fq.acknowledge_group(frame::AckGroup { base_id: 0, bitfield: 0b1, nonce: n0 }, None);
+        fq.acknowledge_group(frame::AckGroup { base_id: 0, bitfield: 0b11, nonce: n0 ^ n1 }, None);
+        assert_eq!(fq.get_feedback(3000), Some(send_rate::FeedbackData {
+            loss_rate: 0.0,
+            receive_rate: 4,
+            rate_limited: false,
+            rtt_ms: Some(2900),
+        }));
+        assert_eq!(fq.duplicate_ack_count(), 2);
+    }
+
+    // Ack groups received together should be processed in order of frame ID, so that acks which
+    // were reordered in transit are not mistaken for losses.
+    #[test]
+    fn batch_acknowledgement() {
+        let new_queue = || {
+            let mut fq = FrameQueue::new(MAX_FRAME_WINDOW_SIZE, MAX_FRAME_WINDOW_SIZE, 0, 3);
+            let mut nonces = Vec::new();
+
+            let packet_rc = Rc::new(RefCell::new(
+                PendingPacket::new(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice().into(), 0, 0, 0, 0, false)
+            ));
+
+            for _ in 0 .. 6 {
+                let nonce = rand::random();
+                fq.push(4, 100, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), nonce);
+                nonces.push(nonce);
+            }
+
+            (fq, nonces, packet_rc)
+        };
+
+        let groups = |nonces: &Vec<bool>| vec![
+            frame::AckGroup { base_id: 3, bitfield: 0b111, nonce: nonces[3] ^ nonces[4] ^ nonces[5] },
+            frame::AckGroup { base_id: 0, bitfield: 0b111, nonce: nonces[0] ^ nonces[1] ^ nonces[2] },
+            frame::AckGroup { base_id: 3, bitfield: 0b111, nonce: nonces[3] ^ nonces[4] ^ nonces[5] },
+        ];
+
+        // Acknowledged one at a time, the first three frames are declared lost
+        let (mut fq, nonces, _packet_rc) = new_queue();
+
+        for group in groups(&nonces).into_iter() {
+            fq.acknowledge_group(group, None);
+        }
+
+        assert_eq!(fq.feedback_gen.loss_intervals.compute_loss_rate() > 0.0, true);
+        assert_eq!(fq.take_lost_packets(), vec![ 0 ]);
+        assert_eq!(fq.duplicate_ack_count(), 1);
+
+        // Acknowledged as a batch, no frames are lost
+        let (mut fq, nonces, _packet_rc) = new_queue();
+
+        fq.acknowledge_groups(groups(&nonces), None);
+
+        assert!((0 .. 6).all(|frame_id| fq.frame_log.get_frame(frame_id).unwrap().acked));
+        assert_eq!(fq.get_feedback(1000), Some(send_rate::FeedbackData {
+            loss_rate: 0.0,
+            receive_rate: 0,
+            rate_limited: false,
+            rtt_ms: Some(900),
         }));
+        assert_eq!(fq.take_lost_packets(), Vec::<u32>::new());
+        assert_eq!(fq.duplicate_ack_count(), 1);
+    }
+
+    #[test]
+    fn in_flight_size() {
+        let mut fq = FrameQueue::new(MAX_FRAME_WINDOW_SIZE, MAX_FRAME_WINDOW_SIZE, 0, 3);
+
+        let packet_rc = Rc::new(RefCell::new(
+            PendingPacket::new(vec![ 0x00, 0x01, 0x02 ].into_boxed_slice().into(), 0, 0, 0, 0, false)
+        ));
+
+        let n0 = rand::random();
+        let n1 = rand::random();
+        let n2 = rand::random();
+
+        fq.push(4, 100, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), n0);
+        fq.push(8, 100, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), n1);
+        fq.push(16, 100, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), n2);
+        assert_eq!(fq.in_flight_size(), 28);
+
+        fq.acknowledge_group(frame::AckGroup { base_id: 1, bitfield: 0b1, nonce: n1 }, None);
+        assert_eq!(fq.in_flight_size(), 20);
+
+        // Frames behind the transfer window are no longer in flight
+        fq.advance_transfer_window(1, None);
+        assert_eq!(fq.in_flight_size(), 16);
+
+        fq.advance_transfer_window(3, None);
+        assert_eq!(fq.in_flight_size(), 0);
+    }
+
+    #[test]
+    fn id_wraparound() {
+        let size = MAX_FRAME_WINDOW_SIZE;
+        let base_id = u32::MAX - 2;
+
+        let mut fq = FrameQueue::new(size, size, base_id, 3);
+        let mut nonces = Vec::new();
+
+        let packet_rc = Rc::new(RefCell::new(
+            PendingPacket::new(vec![].into_boxed_slice().into(), 0, 0, 0, 0, false)
+        ));
+
+        // Push frames on both sides of the wrap
+        for _ in 0 .. 6 {
+            let nonce = rand::random();
+            fq.push(32, 0, vec![ FragmentRef::new(&packet_rc, 0) ].into_boxed_slice(), nonce);
+            nonces.push(nonce);
+        }
+
+        assert_eq!(fq.next_id(), 3);
+        assert_eq!(fq.frame_log.len(), 6);
+
+        // Frames after the wrap may be found, frames before the base may not
+        assert!(fq.frame_log.get_frame(2).is_some());
+        assert!(fq.frame_log.get_frame(base_id - 1).is_none());
+
+        // Acknowledge a group which spans the wrap
+        fq.acknowledge_group(frame::AckGroup {
+            base_id,
+            bitfield: 0b111111,
+            nonce: nonces.iter().fold(false, |acc, &n| acc ^ n),
+        }, None);
+
+        assert!(fq.frame_log.get_frame(1).unwrap().acked);
+
+        // The window cannot backtrack, or advance beyond the next ID
+        assert_eq!(fq.can_advance_transfer_window(base_id - 1), false);
+        assert_eq!(fq.can_advance_transfer_window(4), false);
+        assert_eq!(fq.can_advance_transfer_window(3), true);
+
+        fq.advance_transfer_window(3, None);
+        assert_eq!(fq.base_id(), 3);
+
+        // Forget all frames across the wrap
+        fq.forget_frames(500, None);
+
+        assert_eq!(fq.frame_log.base_id(), 3);
+        assert_eq!(fq.frame_log.len(), 0);
     }
 
     // TODO: Test max acknowledgement, etc.
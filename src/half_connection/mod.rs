@@ -1,17 +1,53 @@
 
+use crate::ArrivalCadence;
+use crate::Backpressure;
+use crate::Clock;
+use crate::DatagramTransform;
+use crate::ChannelStats;
+use crate::CloseReason;
+use crate::LinkStats;
+use crate::MAX_FRAGMENT_SIZE;
+use crate::MAX_FRAME_SIZE;
+use crate::MAX_TRANSFORM_OVERHEAD;
+use crate::Pacer;
+use crate::PathStats;
+use crate::QueueAction;
+use crate::QueuedPacket;
+use crate::ReceiveOrder;
+use crate::ReceiverHealth;
+use crate::ReorderLimits;
+use crate::RttHistogram;
 use crate::SendMode;
+use crate::SendOptions;
+use crate::SessionSummary;
+#[cfg(feature = "stats")]
+use crate::StatsHistory;
+#[cfg(feature = "stats")]
+use crate::StatsSample;
+use crate::TraceOutcome;
+use crate::Watermarks;
+use crate::WindowUsage;
 use crate::frame;
 
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::net;
+use std::sync::Arc;
 use std::time;
 
+mod cadence;
+mod channel_array;
+mod channel_mask;
 mod emit;
 mod frame_ack_queue;
 mod frame_queue;
 mod loss_rate;
 mod packet_receiver;
 mod packet_sender;
+mod path_set;
 mod pending_packet;
 mod pending_queue;
+mod quality;
 mod recv_rate_set;
 mod reorder_buffer;
 mod resend_queue;
@@ -23,9 +59,62 @@ mod packet_tests;
 const INITIAL_RTT_ESTIMATE_MS: u64 = 150;
 const INITIAL_RTO_ESTIMATE_MS: u64 = 4*INITIAL_RTT_ESTIMATE_MS;
 const MIN_SYNC_TIMEOUT_MS: u64 = 2000;
+const MAX_INTERLEAVED_PACKETS: usize = 4;
+
+// Size of each probe packet, and the most probe data which may accrue while probes cannot be sent
+const PROBE_SIZE: usize = MAX_FRAGMENT_SIZE;
+const MAX_PROBE_CREDIT: f64 = (2*PROBE_SIZE) as f64;
+
+// Messages sent on the control channel are identified by their first byte
+const CONTROL_PROBE: u8 = 0;
+
+// Soft reset requested by this endpoint, which awaits acknowledgement
+struct LocalReset {
+    frame: frame::ResetFrame,
+    // Number of packets dropped from the send queue and transfer window
+    drop_count: u64,
+    resend_time_ms: u64,
+}
 
 pub trait FrameSink {
-    fn send(&mut self, frame_data: &[u8]);
+    // Returns an error if the frame could not be sent, in which case the frame is not considered
+    // sent, and its contents are retried on a subsequent flush
+    fn send(&mut self, frame_data: &[u8]) -> std::io::Result<()>;
+
+    // Directs subsequent frames over the given path, if the sink sends over several
+    fn select_path(&mut self, _path: usize) {}
+}
+
+// Forwards frames to an underlying sink, noting whether any send failed because the sink would
+// block
+struct BlockDetectSink<'a, S> {
+    sink: &'a mut S,
+    would_block: bool,
+}
+
+impl<'a, S> BlockDetectSink<'a, S> {
+    fn new(sink: &'a mut S) -> Self {
+        Self {
+            sink,
+            would_block: false,
+        }
+    }
+}
+
+impl<'a, S: FrameSink> FrameSink for BlockDetectSink<'a, S> {
+    fn send(&mut self, frame_data: &[u8]) -> std::io::Result<()> {
+        let result = self.sink.send(frame_data);
+        if let Err(ref err) = result {
+            if err.kind() == std::io::ErrorKind::WouldBlock {
+                self.would_block = true;
+            }
+        }
+        result
+    }
+
+    fn select_path(&mut self, path: usize) {
+        self.sink.select_path(path);
+    }
 }
 
 pub trait PacketSink {
@@ -51,26 +140,130 @@ pub struct Config {
     pub tx_alloc_limit: usize,
     pub rx_alloc_limit: usize,
 
+    pub rx_max_packet_size: usize,
+
     pub keepalive_interval_ms: Option<u64>,
+
+    #[cfg(feature = "stats")]
+    pub stats_sample_interval_ms: Option<u64>,
+    #[cfg(feature = "stats")]
+    pub stats_history_len: usize,
+
+    pub reorder_tolerance_frames: u32,
+    pub reorder_tolerance_ms: u64,
+
+    pub early_retransmit: bool,
+    pub min_rto_ms: u64,
+
+    pub max_frame_age_ms: u64,
+
+    pub fragment_acks: bool,
+
+    pub receiver_health: bool,
+
+    pub app_latency: bool,
+
+    pub control_channels: bool,
+
+    pub drop_ranges: bool,
+
+    pub adaptive_frame_size: bool,
+
+    pub deliver_empty_packets: bool,
+    pub receive_order: ReceiveOrder,
+
+    pub channel_count: usize,
+    pub channel_weights: Vec<u32>,
+
+    pub probe_rate: u32,
+
+    // Bytes added to each frame by the transport, e.g. by sealing
+    pub frame_overhead: usize,
+
+    pub clock: Arc<dyn Clock>,
 }
 
-pub struct HalfConnection {
-    packet_sender: packet_sender::PacketSender,
+pub struct HalfConnection<const CHANNELS: usize = 0> {
+    packet_sender: packet_sender::PacketSender<CHANNELS>,
     pending_queue: pending_queue::PendingQueue,
+    // Fragments of critical packets, which are sent ahead of all other data
+    critical_queue: pending_queue::PendingQueue,
+    // Encoded custom frames awaiting the next flush
+    custom_frame_queue: VecDeque<Box<[u8]>>,
     resend_queue: resend_queue::ResendQueue,
     frame_queue: frame_queue::FrameQueue,
 
-    packet_receiver: packet_receiver::PacketReceiver,
+    packet_receiver: packet_receiver::PacketReceiver<CHANNELS>,
     frame_ack_queue: frame_ack_queue::FrameAckQueue,
 
     send_rate_comp: send_rate::SendRateComp,
+    cadence_estimator: cadence::CadenceEstimator,
+    quality_estimator: quality::QualityEstimator,
+    frame_size_policy: Option<emit::FrameSizePolicy>,
+    // Paths over which frames may be sent, of which there is only one unless more are added
+    paths: path_set::PathSet,
+    // Transforms the payloads of datagrams sent and received, if set
+    transform: Option<Box<dyn DatagramTransform>>,
+    // Bytes added to each frame by the transport, which count against the flush allocation and the
+    // maximum frame size
+    frame_overhead: usize,
+
+    // Whether ack frames report the health of the receive buffer, and the most recent report from
+    // the remote endpoint
+    receiver_health: bool,
+    remote_health: Option<frame::ReceiverHealth>,
+
+    // Whether ack frames report the latency set by the application, the latency to report, and the
+    // most recent report from the remote endpoint
+    app_latency: bool,
+    app_latency_ms: u64,
+    remote_app_latency_ms: Option<u64>,
+
+    // Whether sync frames list packets which were abandoned by the sender
+    drop_ranges: bool,
+
+    // Rate at which probes are sent while idle (0 if disabled), the probe data accrued since the
+    // connection last became idle, the time at which it was last accrued, and the total size of
+    // probes sent
+    probe_rate: u32,
+    probe_credit: f64,
+    probe_time_ms: u64,
+    probe_bytes: u64,
+
+    stale_drop_count: u64,
+    stale_drop_bytes: u64,
+
+    duplicate_frame_count: u64,
+    expired_frame_count: u64,
+
+    #[cfg(feature = "stats")]
+    stats_history: Option<StatsHistory>,
+    #[cfg(feature = "stats")]
+    stats_sample_interval_ms: u64,
+    #[cfg(feature = "stats")]
+    stats_next_sample_ms: u64,
+    #[cfg(feature = "stats")]
+    stats_delivered_size: u64,
+
+    // Totals reported by session summaries
+    sent_packet_count: u64,
+    sent_packet_bytes: u64,
+    rtt_histogram: RttHistogram,
+
+    #[cfg(feature = "cpu-accounting")]
+    processing_time: time::Duration,
 
     now_ms: u64,
     rtt_ms: u64,
     rto_ms: u64,
 
-    time_base: time::Instant,
-    time_last_flushed: Option<time::Instant>,
+    reorder_tolerance_ms: u64,
+    min_rto_ms: u64,
+    max_frame_age_ms: u64,
+
+    clock: Arc<dyn Clock>,
+    time_base: time::Duration,
+    time_last_flushed: Option<time::Duration>,
     sync_timeout_base_ms: u64,
 
     flush_alloc: isize,
@@ -78,26 +271,130 @@ pub struct HalfConnection {
 
     sync_reply: bool,
     sync_keepalive_interval_ms: Option<u64>,
+
+    ack_urgent: bool,
+    // Time at which the oldest frame yet to be acknowledged was received, and the delays between
+    // receipt and acknowledgement observed so far
+    ack_pending_since_ms: Option<u64>,
+    ack_delay_ms: Option<u64>,
+    max_ack_delay_ms: u64,
+
+    send_blocked: bool,
+    send_blocked_count: u64,
+
+    window_limited: bool,
+    window_limited_count: u64,
+    window_limited_event: Option<WindowUsage>,
+
+    // Soft reset requested by this endpoint, and the acknowledgement of the most recent soft reset
+    // requested by the remote endpoint, which is resent if the request is repeated
+    local_reset: Option<LocalReset>,
+    remote_reset_ack: Option<frame::ResetAckFrame>,
+    remote_reset_ack_pending: bool,
+    // Number of packets dropped by a soft reset which has completed since last polled
+    reset_event: Option<u64>,
 }
 
-impl HalfConnection {
+impl<const CHANNELS: usize> HalfConnection<CHANNELS> {
     pub fn new(config: Config) -> Self {
+        // Without early retransmission, no number of later acknowledgements is enough to declare a
+        // frame lost, which happens only once the transfer window passes it
+        let reorder_tolerance_frames = if config.early_retransmit {
+            config.reorder_tolerance_frames
+        } else {
+            config.tx_frame_window_size * 2
+        };
+
+        let mut packet_receiver = packet_receiver::PacketReceiver::new(config.rx_packet_window_size, config.rx_packet_base_id, config.rx_alloc_limit, config.channel_count);
+
+        if config.fragment_acks {
+            packet_receiver.enable_fragment_acks();
+        }
+
+        packet_receiver.set_max_packet_size(config.rx_max_packet_size);
+
+        if !config.deliver_empty_packets {
+            packet_receiver.discard_empty_packets();
+        }
+
+        packet_receiver.set_receive_order(config.receive_order);
+
+        if config.control_channels {
+            packet_receiver.enable_control_channels();
+        }
+
+        let mut packet_sender = packet_sender::PacketSender::new(config.tx_packet_window_size, config.tx_packet_base_id, config.tx_alloc_limit, config.channel_count);
+
+        if !config.channel_weights.is_empty() {
+            packet_sender.set_channel_weights(&config.channel_weights);
+        }
+
         Self {
-            packet_sender: packet_sender::PacketSender::new(config.tx_packet_window_size, config.tx_packet_base_id, config.tx_alloc_limit),
+            packet_sender,
             pending_queue: pending_queue::PendingQueue::new(),
+            critical_queue: pending_queue::PendingQueue::new(),
+            custom_frame_queue: VecDeque::new(),
             resend_queue: resend_queue::ResendQueue::new(),
-            frame_queue: frame_queue::FrameQueue::new(config.tx_frame_window_size, config.tx_frame_window_size, config.tx_frame_base_id),
+            frame_queue: frame_queue::FrameQueue::new(config.tx_frame_window_size, config.tx_frame_window_size, config.tx_frame_base_id, reorder_tolerance_frames),
 
-            packet_receiver: packet_receiver::PacketReceiver::new(config.rx_packet_window_size, config.rx_packet_base_id, config.rx_alloc_limit),
+            packet_receiver,
             frame_ack_queue: frame_ack_queue::FrameAckQueue::new(config.rx_frame_window_size, config.rx_frame_base_id),
 
             send_rate_comp: send_rate::SendRateComp::new(config.tx_bandwidth_limit),
+            cadence_estimator: cadence::CadenceEstimator::new(),
+            quality_estimator: quality::QualityEstimator::new(),
+            frame_size_policy: if config.adaptive_frame_size { Some(emit::FrameSizePolicy::new()) } else { None },
+            paths: path_set::PathSet::new(),
+            transform: None,
+            frame_overhead: config.frame_overhead,
+
+            receiver_health: config.receiver_health,
+            remote_health: None,
+
+            app_latency: config.app_latency,
+            app_latency_ms: 0,
+            remote_app_latency_ms: None,
+
+            drop_ranges: config.drop_ranges,
+
+            // Probes are sent on the control channel, which the remote endpoint may not support
+            probe_rate: if config.control_channels { config.probe_rate } else { 0 },
+            probe_credit: 0.0,
+            probe_time_ms: 0,
+            probe_bytes: 0,
+
+            stale_drop_count: 0,
+            stale_drop_bytes: 0,
+
+            duplicate_frame_count: 0,
+            expired_frame_count: 0,
+
+            #[cfg(feature = "stats")]
+            stats_history: config.stats_sample_interval_ms.map(|_| StatsHistory::new(config.stats_history_len)),
+            #[cfg(feature = "stats")]
+            stats_sample_interval_ms: config.stats_sample_interval_ms.unwrap_or(0),
+            #[cfg(feature = "stats")]
+            stats_next_sample_ms: 0,
+            #[cfg(feature = "stats")]
+            stats_delivered_size: 0,
+
+            sent_packet_count: 0,
+            sent_packet_bytes: 0,
+            rtt_histogram: RttHistogram::default(),
+
+            #[cfg(feature = "cpu-accounting")]
+            processing_time: time::Duration::ZERO,
 
             now_ms: 0,
             rtt_ms: 0,
             rto_ms: 0,
 
-            time_base: time::Instant::now(),
+            reorder_tolerance_ms: config.reorder_tolerance_ms,
+            min_rto_ms: config.min_rto_ms,
+            max_frame_age_ms: config.max_frame_age_ms,
+
+            time_base: config.clock.now(),
+            clock: config.clock,
             time_last_flushed: None,
             sync_timeout_base_ms: 0,
 
@@ -106,6 +403,23 @@ impl HalfConnection {
 
             sync_reply: false,
             sync_keepalive_interval_ms: config.keepalive_interval_ms,
+
+            ack_urgent: false,
+            ack_pending_since_ms: None,
+            ack_delay_ms: None,
+            max_ack_delay_ms: 0,
+
+            send_blocked: false,
+            send_blocked_count: 0,
+
+            window_limited: false,
+            window_limited_count: 0,
+            window_limited_event: None,
+
+            local_reset: None,
+            remote_reset_ack: None,
+            remote_reset_ack_pending: false,
+            reset_event: None,
         }
     }
 
@@ -117,337 +431,1382 @@ impl HalfConnection {
         self.packet_sender.total_size()
     }
 
-    pub fn is_send_pending(&self) -> bool {
-        self.packet_sender.pending_count() != 0 || self.pending_queue.len() != 0 || self.resend_queue.len() != 0
+    // Returns the time in milliseconds needed to drain all queued and unacknowledged packet data
+    // at the current send rate
+    pub fn send_delay_ms(&self) -> u64 {
+        (self.packet_sender.total_size() as f64 * 1000.0 / self.send_rate_comp.send_rate()).ceil() as u64
     }
 
-    pub fn send(&mut self, data: Box<[u8]>, channel_id: u8, mode: SendMode) {
-        self.packet_sender.enqueue_packet(data, channel_id, mode, self.flush_id);
+    pub fn link_stats(&self) -> LinkStats {
+        LinkStats {
+            rtt_s: self.rtt_s(),
+            send_buffer_size: self.send_buffer_size(),
+            arrival_cadence: self.cadence_estimator.cadence(),
+            quality: self.quality_estimator.score(),
+            stale_drop_count: self.stale_drop_count,
+            stale_drop_bytes: self.stale_drop_bytes,
+            probe_bytes: self.probe_bytes,
+            duplicate_frames: self.duplicate_frame_count,
+            duplicate_ack_groups: self.frame_queue.duplicate_ack_count(),
+            expired_frames: self.expired_frame_count,
+            duplicate_datagrams: self.packet_receiver.duplicate_datagram_count(),
+            oversized_packets: self.packet_receiver.oversized_packet_count(),
+            checksum_backend: crate::checksum_backend(),
+            send_blocked_count: self.send_blocked_count,
+            frames_in_flight: self.frame_queue.in_flight_count(),
+            bytes_in_flight: self.frame_queue.in_flight_size(),
+            window_limited_count: self.window_limited_count,
+            ack_delay_ms: self.ack_delay_ms,
+            max_ack_delay_ms: self.max_ack_delay_ms,
+            remote_health: self.remote_health.map(|health| ReceiverHealth {
+                buffered_bytes: health.buffered_bytes as usize,
+                backlogged: health.backlogged,
+            }),
+            remote_app_latency_ms: self.remote_app_latency_ms,
+            // Set by the server for connections with a selected variant
+            variant: None,
+            send_window: self.frame_queue.window_size(),
+            receive_window: self.frame_ack_queue.window_size(),
+            #[cfg(feature = "cpu-accounting")]
+            processing_time: Some(self.processing_time),
+            #[cfg(not(feature = "cpu-accounting"))]
+            processing_time: None,
+        }
     }
 
-    pub fn receive(&mut self, sink: &mut impl PacketSink) {
-        self.packet_receiver.receive(sink);
+    // Returns the totals accumulated over the lifetime of this connection, along with its final
+    // link statistics
+    pub fn session_summary(&self, reason: CloseReason) -> SessionSummary {
+        SessionSummary {
+            reason,
+            duration: self.clock.now().saturating_sub(self.time_base),
+            packets_sent: self.sent_packet_count,
+            bytes_sent: self.sent_packet_bytes,
+            packets_received: self.packet_receiver.delivered_count(),
+            bytes_received: self.packet_receiver.delivered_size(),
+            rtt_histogram: self.rtt_histogram,
+            link_stats: self.link_stats(),
+        }
     }
 
-    pub fn handle_data_frame(&mut self, frame: frame::DataFrame) {
-        if self.frame_ack_queue.window_contains(frame.sequence_id) {
-            self.frame_ack_queue.mark_seen(frame.sequence_id, frame.nonce);
-
-            for datagram in frame.datagrams.into_iter() {
-                self.packet_receiver.handle_datagram(datagram);
-            }
-        }
+    #[cfg(feature = "stats")]
+    pub fn stats_history(&self) -> Option<&StatsHistory> {
+        self.stats_history.as_ref()
     }
 
-    pub fn handle_sync_frame(&mut self, frame: frame::SyncFrame) {
-        if let Some(next_frame_id) = frame.next_frame_id {
-            self.frame_ack_queue.resynchronize(next_frame_id);
+    pub fn channel_stats(&self, channel_id: u8) -> ChannelStats {
+        let (delivered, out_of_order) = self.packet_receiver.channel_counts(channel_id);
+        let (reorder_stalls, reorder_skips) = self.packet_receiver.reorder_counts(channel_id);
+        let (sender_dropped, lost) = self.packet_sender.channel_counts(channel_id);
+
+        ChannelStats {
+            delivered,
+            out_of_order,
+            reorder_stalls,
+            reorder_skips,
+            sender_dropped,
+            lost,
+            queued_bytes: self.packet_sender.queued_size(channel_id),
         }
+    }
 
-        if let Some(next_packet_id) = frame.next_packet_id {
-            self.packet_receiver.resynchronize(next_packet_id);
-        }
+    // Adds a path over which frames may be sent, and returns its index. Once a connection has more
+    // than one path, each data frame is sent over the path selected by the scheduler.
+    pub fn add_path(&mut self) -> usize {
+        self.frame_queue.track_paths();
+        self.paths.add()
+    }
 
-        self.sync_reply = true;
+    pub fn path_count(&self) -> usize {
+        self.paths.len()
     }
 
-    pub fn handle_ack_frame(&mut self, frame: frame::AckFrame) {
-        let rtt_ms = self.send_rate_comp.rtt_ms();
+    pub fn path_stats(&self, path: usize) -> PathStats {
+        self.paths.stats(path)
+    }
 
-        for frame_ack in frame.frame_acks.into_iter() {
-            self.frame_queue.acknowledge_group(frame_ack.clone(), rtt_ms);
-        }
+    // Notes that a frame was received over the given path, which shows the path to be usable
+    pub fn notify_path_received(&mut self, path: usize) {
+        self.paths.notify_received(path);
+    }
 
-        self.frame_queue.advance_transfer_window(frame.frame_window_base_id, rtt_ms);
-        self.packet_sender.acknowledge(frame.packet_window_base_id);
+    // Sets the transform applied to the payload of each datagram sent, and reversed for each
+    // datagram received
+    pub fn set_transform(&mut self, transform: Box<dyn DatagramTransform>) {
+        assert!(transform.overhead() <= MAX_TRANSFORM_OVERHEAD, "transform overhead exceeds MAX_TRANSFORM_OVERHEAD");
+        self.transform = Some(transform);
     }
 
-    pub fn step(&mut self) {
-        let now = time::Instant::now();
+    // Returns a new arrival cadence estimate if it has changed significantly since last polled
+    pub fn poll_cadence_change(&mut self) -> Option<ArrivalCadence> {
+        self.cadence_estimator.poll_change()
+    }
 
-        let now_ms = (now - self.time_base).as_millis() as u64;
-        let rtt_ms = self.send_rate_comp.rtt_ms().unwrap_or(INITIAL_RTT_ESTIMATE_MS);
-        let rto_ms = self.send_rate_comp.rto_ms().unwrap_or(INITIAL_RTO_ESTIMATE_MS);
+    // Returns a new quality score if it has changed significantly since last polled
+    pub fn poll_quality_change(&mut self) -> Option<u8> {
+        self.quality_estimator.poll_change()
+    }
 
-        // Store these values for subsequent flush()
-        self.now_ms = now_ms;
-        self.rtt_ms = rtt_ms;
-        self.rto_ms = rto_ms;
+    // Returns a snapshot of the frame window if sending has become window-limited since last polled
+    pub fn poll_window_limited(&mut self) -> Option<WindowUsage> {
+        self.window_limited_event.take()
+    }
 
-        // Forget old frame data
-        self.frame_queue.forget_frames(now_ms.saturating_sub(rtt_ms*4), self.send_rate_comp.rtt_ms());
+    // Returns the number of packets dropped by this endpoint if a soft reset has completed since
+    // last polled
+    pub fn poll_reset(&mut self) -> Option<u64> {
+        self.reset_event.take()
+    }
 
-        // Fill flush allocation
-        self.fill_flush_alloc(now);
+    // Returns a signal for each channel whose send queue has crossed one of its watermarks since
+    // last polled
+    pub fn poll_backpressure(&mut self) -> Vec<(u8, Backpressure)> {
+        self.packet_sender.poll_backpressure()
+    }
 
-        // Ignore previous TimeSensitive packets
-        self.flush_id = self.flush_id.wrapping_add(1);
+    // Returns the outcome of each traced packet which has been resolved since last polled
+    pub fn poll_trace_outcomes(&mut self) -> Vec<(u64, TraceOutcome)> {
+        self.packet_sender.poll_trace_outcomes()
+    }
 
-        // Update send rate value
-        let ref mut frame_queue = self.frame_queue;
-        self.send_rate_comp.step(now_ms, frame_queue.get_feedback(now_ms),
-            |new_loss_rate: f64| {
-                frame_queue.reset_loss_rate(new_loss_rate);
-            }
-        );
+    // Returns the channel ID and tag of each packet whose delivery has been confirmed since last
+    // polled
+    pub fn poll_confirmations(&mut self) -> Vec<(u8, u64)> {
+        self.packet_sender.poll_confirmations()
     }
 
-    pub fn flush(&mut self, sink: &mut impl FrameSink) {
-        // Send as many frames as possible
-        self.emit_frames(self.now_ms, self.rtt_ms, self.rto_ms, self.flush_id, sink);
+    pub fn is_send_pending(&self) -> bool {
+        self.packet_sender.pending_count() != 0 || self.pending_queue.len() != 0 || !self.critical_queue.is_empty() ||
+            self.resend_queue.len() != 0 || !self.custom_frame_queue.is_empty()
     }
 
-    fn fill_flush_alloc(&mut self, now: time::Instant) {
-        if let Some(time_last_flushed) = self.time_last_flushed {
-            let send_rate = self.send_rate_comp.send_rate();
-            let rtt_s = self.send_rate_comp.rtt_s();
+    // Returns the time (relative to this connection's time base) by which step() and flush()
+    // should next be called, or None if neither would send a frame until a packet is enqueued or a
+    // frame is received. This considers the send rate, the resend schedule, and the frame window,
+    // so that a caller need not poll while data is outstanding.
+    // A flush which is window-limited or blocked by the socket resumes once a frame is received
+    // or the socket becomes writable, respectively.
+    pub fn next_timeout_ms(&self) -> Option<u64> {
+        if let Some(reset_time_ms) = self.next_reset_ms() {
+            return Some(reset_time_ms);
+        }
 
-            let delta_time = (now - time_last_flushed).as_secs_f64();
-            let new_bytes = (send_rate * delta_time).round() as isize;
-            let alloc_max = (send_rate * rtt_s.unwrap_or(0.0)).round() as isize;
+        let now_ms = self.now_ms;
+        let sync_timeout_ms = self.rto_ms.max(MIN_SYNC_TIMEOUT_MS);
 
-            self.flush_alloc = self.flush_alloc.saturating_add(new_bytes).min(alloc_max);
+        let data_ready = !self.window_limited && !self.send_blocked &&
+            (self.packet_sender.pending_count() != 0 || !self.pending_queue.is_empty() || !self.critical_queue.is_empty());
 
-            //println!("dt: {}s, rtt: {:?}s, rate: {}B/s, new: {}B, max: {}B, val: {}B",
-            //       delta_time, rtt_s, send_rate, new_bytes, alloc_max, self.flush_alloc);
-        }
-        self.time_last_flushed = Some(now);
-    }
+        let mut timeout_ms =
+            if data_ready || self.frame_ack_queue.peek().is_some() || self.sync_reply || !self.custom_frame_queue.is_empty() {
+                Some(now_ms)
+            } else if self.frame_queue.next_id() != self.frame_queue.base_id() ||
+                      self.packet_sender.next_id() != self.packet_sender.base_id() {
+                Some(self.sync_timeout_base_ms + sync_timeout_ms)
+            } else {
+                self.sync_keepalive_interval_ms.map(|keepalive_interval_ms| {
+                    self.sync_timeout_base_ms + sync_timeout_ms.max(keepalive_interval_ms)
+                })
+            };
 
-    fn emit_frames(&mut self, now_ms: u64, rtt_ms: u64, rto_ms: u64, flush_id: u32, sink: &mut impl FrameSink) {
-        match self.emit_ack_frames(sink) {
-            Err(_) => return,
-            Ok(_) => (),
+        if let Some(entry) = self.resend_queue.peek() {
+            timeout_ms = Some(timeout_ms.map_or(entry.resend_time, |timeout_ms| timeout_ms.min(entry.resend_time)));
         }
 
-        match self.emit_data_frames(now_ms, rtt_ms, flush_id, sink) {
-            Err(_) => return,
-            Ok(_) => (),
+        if let Some(probe_ms) = self.next_probe_ms() {
+            timeout_ms = Some(timeout_ms.map_or(probe_ms, |timeout_ms| timeout_ms.min(probe_ms)));
         }
 
-        match self.emit_sync_frame(now_ms, rto_ms, sink) {
-            Err(_) => return,
-            Ok(_) => (),
+        // Nothing will be sent until the flush allocation has been replenished
+        if self.flush_alloc < 0 {
+            let send_rate = self.send_rate_comp.send_rate().max(1.0);
+            let refill_ms = ((-self.flush_alloc) as f64 * 1000.0 / send_rate).ceil() as u64;
+
+            timeout_ms = timeout_ms.map(|timeout_ms| timeout_ms.max(now_ms + refill_ms.max(1)));
         }
+
+        timeout_ms
     }
 
-    fn emit_sync_frame(&mut self, now_ms: u64, rto_ms: u64, sink: &mut impl FrameSink) -> Result<(),()> {
-        let elapsed_ms = now_ms - self.sync_timeout_base_ms;
-        let sync_timeout_ms = rto_ms.max(MIN_SYNC_TIMEOUT_MS);
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub fn send(&mut self, data: Box<[u8]>, channel_id: u8, mode: SendMode) {
+        self.count_sent_packet(data.len());
+        self.packet_sender.enqueue_packet(data.into(), channel_id, mode, self.flush_id, self.now_ms);
+    }
 
-        if elapsed_ms >= sync_timeout_ms {
-            // A sync frame contains a frame ID any time the frame queue contains unacknowledged
-            // frames. This indicates to the receiver that sufficient time has passed since a frame
-            // was sent, and that the frame receive window should be advanced. This effects a call
-            // to `FrameAckQueue::resynchronize()` on the receiver, preventing the send/receive
-            // windows from desynchronizing in the event that many frames are dropped.
-            let next_frame_id =
-                if self.frame_queue.next_id() != self.frame_queue.base_id() {
-                    Some(self.frame_queue.next_id())
-                } else {
-                    None
-                };
+    // Sends a packet with the given options, which may be canceled using the given send ID until
+    // it has been sent
+    pub fn send_with(&mut self, data: Box<[u8]>, channel_id: u8, mode: SendMode, options: SendOptions, send_id: u64) {
+        self.count_sent_packet(data.len());
+        self.packet_sender.enqueue_packet_with(data.into(), channel_id, mode, options, Some(send_id), self.flush_id, self.now_ms);
+    }
 
-            // A sync frame contains a packet ID any time the send queue contains unacknowledged
-            // packets and no packet fragments would be resent. This indicates to the receiver that
-            // sufficient time has passed since a packet was sent, and that the packet receive
-            // window should be advanced. This effects a call to `PacketReceiver::resynchronize()`
-            // on the receiver, preventing the send/receive windows from desynchronizing in the
-            // event that many unreliable packets are dropped.
-            let next_packet_id =
-                if self.packet_sender.next_id() != self.packet_sender.base_id() &&
-                   self.resend_queue.len() == 0 && self.pending_queue.len() == 0 {
-                    Some(self.packet_sender.next_id())
-                } else {
-                    None
-                };
+    // Cancels the packet sent with the given send ID, if none of its fragments have been sent.
+    // Returns true if the packet was canceled.
+    pub fn cancel(&mut self, send_id: u64) -> bool {
+        if self.packet_sender.cancel_queued(send_id) {
+            return true;
+        }
 
-            // If neither a frame ID nor a packet ID would be sent, send a sync frame anyway to
-            // generate an ack and keep the connection alive. The user-specified keeaplive interval
-            // is considered in this case, but the rate at which keepalive frames are sent will
-            // still be restricted by TFRC's RTO computation and by MIN_SYNC_TIMEOUT_MS.
-            if next_frame_id.is_none() && next_packet_id.is_none() {
-                if let Some(keepalive_interval_ms) = self.sync_keepalive_interval_ms {
-                    if elapsed_ms < keepalive_interval_ms {
-                        return Ok(());
-                    }
-                } else {
-                    return Ok(());
-                }
-            }
+        // An unreliable packet may have been assigned a sequence ID, but still have all of its
+        // fragments awaiting a future flush
+        if let Some(packet_rc) = self.packet_sender.emitted_packet(send_id) {
+            let fragment_count = packet_rc.borrow().last_fragment_id() as usize + 1;
 
-            if self.flush_alloc < 0 {
-                return Err(());
+            let pending_count = self.pending_queue.iter().chain(self.critical_queue.iter())
+                .filter(|entry| entry.fragment_ref.belongs_to(&packet_rc))
+                .count();
+
+            if pending_count == fragment_count {
+                self.pending_queue.retain(|entry| !entry.fragment_ref.belongs_to(&packet_rc));
+                self.critical_queue.retain(|entry| !entry.fragment_ref.belongs_to(&packet_rc));
+                self.packet_sender.cancel_emitted(send_id);
+                return true;
             }
+        }
 
-            let frame = frame::Frame::SyncFrame(frame::SyncFrame { next_frame_id, next_packet_id });
+        false
+    }
 
-            use frame::serial::Serialize;
-            let frame_bytes = frame.write();
+    fn count_sent_packet(&mut self, size: usize) {
+        self.sent_packet_count += 1;
+        self.sent_packet_bytes += size as u64;
+    }
 
-            sink.send(&frame_bytes);
-            self.flush_alloc -= frame_bytes.len() as isize;
-            self.sync_timeout_base_ms = now_ms;
-        }
+    // Enqueues a custom frame, which will be sent on the next flush ahead of any data frames
+    pub fn send_custom_frame(&mut self, type_id: u8, data: Box<[u8]>) {
+        use frame::serial::Serialize;
+        let frame = frame::Frame::CustomFrame(frame::CustomFrame { type_id, data });
+        self.custom_frame_queue.push_back(frame.write());
+    }
 
-        return Ok(());
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub fn send_shared(&mut self, data: Arc<[u8]>, channel_id: u8, mode: SendMode) {
+        self.count_sent_packet(data.len());
+        self.packet_sender.enqueue_packet(data.into(), channel_id, mode, self.flush_id, self.now_ms);
     }
 
-    fn emit_ack_frames(&mut self, sink: &mut impl FrameSink) -> Result<(),()> {
-        let flush_alloc_init = self.flush_alloc;
-        let sync_reply_init = self.sync_reply;
+    pub fn queued_packets(&self) -> impl Iterator<Item = QueuedPacket<'_>> {
+        let now_ms = (self.clock.now() - self.time_base).as_millis() as u64;
+        self.packet_sender.queued_packets(now_ms)
+    }
 
-        let frame_window_base_id = self.frame_ack_queue.base_id();
-        let packet_window_base_id = self.packet_receiver.base_id();
+    pub fn reshape_send_queue(&mut self, f: impl FnMut(&QueuedPacket) -> QueueAction) {
+        let now_ms = (self.clock.now() - self.time_base).as_millis() as u64;
+        self.packet_sender.reshape(now_ms, f);
+    }
 
-        let ref mut flush_alloc = self.flush_alloc;
-        let ref mut sync_reply = self.sync_reply;
+    pub fn set_stale_bound(&mut self, channel_id: u8, bound_ms: Option<u64>) {
+        self.packet_sender.set_stale_bound(channel_id, bound_ms);
+    }
 
-        let emit_cb = |frame_bytes: Box<[u8]>| {
-            sink.send(&frame_bytes);
-            *flush_alloc -= frame_bytes.len() as isize;
-            *sync_reply = false;
-        };
+    pub fn set_watermarks(&mut self, channel_id: u8, watermarks: Option<Watermarks>) {
+        self.packet_sender.set_watermarks(channel_id, watermarks);
+    }
 
-        let mut afe = emit::AckFrameEmitter::new(frame_window_base_id, packet_window_base_id, flush_alloc_init, emit_cb);
+    pub fn set_reorder_limits(&mut self, channel_id: u8, limits: Option<ReorderLimits>) {
+        self.packet_receiver.set_reorder_limits(channel_id, limits);
+    }
 
-        if sync_reply_init {
-            match afe.push_dud() {
-                Err(_) => return Err(()),
-                Ok(_) => (),
-            }
-        }
+    pub fn set_app_latency_ms(&mut self, latency_ms: u64) {
+        self.app_latency_ms = latency_ms;
+    }
 
-        while let Some(ack_group) = self.frame_ack_queue.peek() {
-            match afe.push(ack_group) {
-                Err(_) => return Err(()),
-                Ok(_) => (),
-            }
+    pub fn set_receive_paused(&mut self, channel_id: u8, paused: bool) {
+        self.packet_receiver.set_paused(channel_id, paused);
+    }
+
+    pub fn skip_to_latest(&mut self, channel_id: u8) -> usize {
+        self.packet_receiver.skip_to_latest(channel_id) as usize
+    }
 
-            self.frame_ack_queue.pop();
+    // Returns the time at which a soft reset frame is next due, if one is pending. Nothing else is
+    // sent while a soft reset requested by this endpoint awaits acknowledgement.
+    fn next_reset_ms(&self) -> Option<u64> {
+        if self.remote_reset_ack_pending {
+            Some(self.now_ms)
+        } else {
+            self.local_reset.as_ref().map(|reset| reset.resend_time_ms.max(self.now_ms))
         }
+    }
 
-        afe.finalize();
+    // Begins a soft reset of the connection: all queued and unacknowledged packets are dropped, and
+    // the remote endpoint is asked to do the same, so that both transfer windows restart from the
+    // next sequence IDs. Data received from the remote endpoint is ignored until it acknowledges
+    // the reset. Has no effect if a soft reset requested by this endpoint is already pending.
+    pub fn reset(&mut self) {
+        if self.local_reset.is_some() {
+            return;
+        }
 
-        return Ok(());
+        let drop_count = self.reset_send_state();
+
+        self.local_reset = Some(LocalReset {
+            frame: frame::ResetFrame {
+                nonce: rand::random::<u32>(),
+                frame_base_id: self.frame_queue.next_id(),
+                packet_base_id: self.packet_sender.next_id(),
+            },
+            drop_count,
+            resend_time_ms: self.now_ms,
+        });
     }
 
-    fn emit_data_frames(&mut self, now_ms: u64, rtt_ms: u64, flush_id: u32, sink: &mut impl FrameSink) -> Result<(),()> {
-        let flush_alloc_init = self.flush_alloc;
+    // Drops all outbound data, returning the number of packets dropped
+    fn reset_send_state(&mut self) -> u64 {
+        let drop_count = self.packet_sender.reset();
 
-        let ref mut send_rate_comp = self.send_rate_comp;
-        let ref mut flush_alloc = self.flush_alloc;
-        let ref mut sync_timeout_base_ms = self.sync_timeout_base_ms;
+        self.pending_queue.clear();
+        self.critical_queue.clear();
+        self.resend_queue.clear();
+        self.frame_queue.reset();
 
-        let emit_cb = |frame_bytes: Box<[u8]>| {
-            sink.send(&frame_bytes);
-            send_rate_comp.notify_frame_sent(now_ms);
-            *flush_alloc -= frame_bytes.len() as isize;
-            *sync_timeout_base_ms = now_ms;
-        };
+        self.window_limited = false;
 
-        let mut dfe = emit::DataFrameEmitter::new(now_ms, &mut self.frame_queue, flush_alloc_init, emit_cb);
+        drop_count
+    }
 
-        while let Some(entry) = self.resend_queue.peek() {
-            if let Some(packet_rc) = entry.fragment_ref.packet.upgrade() {
-                let packet_ref = packet_rc.borrow();
+    // Discards all inbound data, and expects the remote endpoint to send from the given sequence IDs
+    fn reset_receive_state(&mut self, frame_base_id: u32, packet_base_id: u32) {
+        self.frame_ack_queue = frame_ack_queue::FrameAckQueue::new(self.frame_ack_queue.window_size(), frame_base_id);
+        self.packet_receiver.reset(packet_base_id);
 
-                if packet_ref.fragment_acknowledged(entry.fragment_ref.fragment_id) {
-                    self.resend_queue.pop();
-                    continue;
-                }
+        self.ack_urgent = false;
+        self.ack_pending_since_ms = None;
+        self.sync_reply = false;
+    }
 
-                if entry.resend_time > now_ms {
-                    break;
-                }
+    pub fn handle_reset_frame(&mut self, frame: frame::ResetFrame) {
+        if self.remote_reset_ack.as_ref().is_some_and(|ack| ack.nonce_ack == frame.nonce) {
+            // Our acknowledgement was lost, so repeat it without resetting again
+            self.remote_reset_ack_pending = true;
+            return;
+        }
 
-                match dfe.push(&packet_rc, entry.fragment_ref.fragment_id, true) {
-                    // Being window-limited does not preclude further sends
-                    Err(emit::DataPushError::WindowLimited) => return Ok(()),
-                    Err(emit::DataPushError::SizeLimited) => return Err(()),
-                    Ok(_) => (),
-                }
+        self.reset_receive_state(frame.frame_base_id, frame.packet_base_id);
 
-                let entry = self.resend_queue.pop().unwrap();
+        // If both endpoints requested a reset at once, outbound data has already been dropped, and
+        // our own request will complete once the remote endpoint acknowledges it
+        if self.local_reset.is_none() {
+            let drop_count = self.reset_send_state();
+            self.reset_event = Some(drop_count);
+        }
 
-                const MAX_SEND_COUNT: u8 = 2;
+        self.remote_reset_ack = Some(frame::ResetAckFrame {
+            nonce_ack: frame.nonce,
+            frame_base_id: self.frame_queue.next_id(),
+            packet_base_id: self.packet_sender.next_id(),
+        });
+        self.remote_reset_ack_pending = true;
+    }
 
-                let new_resend_time = now_ms + rtt_ms*(1 << entry.send_count);
-                let new_send_count = (entry.send_count + 1).min(MAX_SEND_COUNT);
+    pub fn handle_reset_ack_frame(&mut self, frame: frame::ResetAckFrame) {
+        if self.local_reset.as_ref().is_some_and(|reset| reset.frame.nonce == frame.nonce_ack) {
+            let reset = self.local_reset.take().unwrap();
 
-                self.resend_queue.push(resend_queue::Entry::new(entry.fragment_ref, new_resend_time, new_send_count));
-            } else {
-                self.resend_queue.pop();
-                continue;
-            }
+            self.reset_receive_state(frame.frame_base_id, frame.packet_base_id);
+            self.reset_event = Some(reset.drop_count);
         }
+    }
 
-        loop {
-            if self.pending_queue.is_empty() {
-                if let Some((packet_rc, resend)) = self.packet_sender.emit_packet(flush_id) {
-                    let pending_packet_ref = packet_rc.borrow();
+    pub fn receive(&mut self, sink: &mut impl PacketSink) {
+        let now_ms = (self.clock.now() - self.time_base).as_millis() as u64;
+        self.accounted(|hc| hc.packet_receiver.receive(now_ms, sink));
 
-                    let last_fragment_id = pending_packet_ref.last_fragment_id();
-                    for i in 0 ..= last_fragment_id {
-                        let fragment_ref = pending_packet::FragmentRef::new(&packet_rc, i);
-                        let entry = pending_queue::Entry::new(fragment_ref, resend);
-                        self.pending_queue.push_back(entry);
-                    }
-                } else {
-                    break;
+        // Probes carry no information, and no other control messages are defined by this version,
+        // so any sent by a newer peer are ignored
+        self.packet_receiver.take_control_packets();
+    }
+
+    pub fn handle_data_frame(&mut self, frame: frame::DataFrame) {
+        self.accounted(|hc| hc.handle_data_frame_inner(frame));
+    }
+
+    fn handle_data_frame_inner(&mut self, mut frame: frame::DataFrame) {
+        if self.local_reset.is_some() {
+            // Sent before the remote endpoint reset, or with sequence IDs we have yet to learn
+            return;
+        }
+
+        if let Some(ref mut transform) = self.transform {
+            // A frame containing any datagram which cannot be decoded is treated as lost, and is
+            // not acknowledged
+            for datagram in frame.datagrams.iter_mut() {
+                match transform.decode(&datagram.data) {
+                    Some(data) => datagram.data = data,
+                    None => return,
                 }
             }
+        }
 
-            while let Some(entry) = self.pending_queue.front() {
-                if let Some(packet_rc) = entry.fragment_ref.packet.upgrade() {
-                    let packet_ref = packet_rc.borrow();
+        if self.frame_ack_queue.window_contains(frame.sequence_id) {
+            if self.frame_ack_queue.mark_seen(frame.sequence_id, frame.nonce) {
+                // Inform the sender of the gap as soon as possible
+                self.ack_urgent = true;
+            }
+
+            let arrival_ms = (self.clock.now() - self.time_base).as_secs_f64() * 1000.0;
+            self.cadence_estimator.notify_arrival(arrival_ms);
+
+            if self.ack_pending_since_ms.is_none() {
+                self.ack_pending_since_ms = Some(arrival_ms as u64);
+            }
+
+            for datagram in frame.datagrams.into_iter() {
+                self.packet_receiver.handle_datagram(datagram, arrival_ms as u64);
+            }
+        } else if self.frame_ack_queue.is_duplicate(frame.sequence_id) {
+            // Already acknowledged, and its datagrams already handled
+            self.duplicate_frame_count += 1;
+        }
+    }
+
+    pub fn handle_sync_frame(&mut self, frame: frame::SyncFrame) {
+        self.accounted(|hc| hc.handle_sync_frame_inner(frame));
+    }
+
+    fn handle_sync_frame_inner(&mut self, frame: frame::SyncFrame) {
+        if self.local_reset.is_some() {
+            return;
+        }
+
+        if let Some(next_frame_id) = frame.next_frame_id {
+            self.frame_ack_queue.resynchronize(next_frame_id);
+        }
+
+        if let Some(next_packet_id) = frame.next_packet_id {
+            self.packet_receiver.resynchronize(next_packet_id);
+        }
+
+        for range in frame.drop_ranges.iter() {
+            self.packet_receiver.handle_drop_range(range);
+        }
+
+        self.sync_reply = true;
+    }
+
+    // Handles several ack frames received at once, in the order they were received. The ack
+    // groups of all frames are processed as a single batch.
+    pub fn handle_ack_frames(&mut self, frames: Vec<frame::AckFrame>) {
+        self.accounted(|hc| hc.handle_ack_frames_inner(frames));
+    }
+
+    fn handle_ack_frames_inner(&mut self, frames: Vec<frame::AckFrame>) {
+        let rtt_ms = self.send_rate_comp.rtt_ms();
+
+        let frame_acks = frames.iter().flat_map(|frame| frame.frame_acks.iter().cloned()).collect();
+        self.frame_queue.acknowledge_groups(frame_acks, rtt_ms);
+
+        self.packet_sender.confirm_acknowledged();
+
+        // Window base IDs which have since been surpassed are ignored
+        for frame in frames.iter() {
+            self.frame_queue.advance_transfer_window(frame.frame_window_base_id, rtt_ms);
+            self.packet_sender.acknowledge(frame.packet_window_base_id);
+        }
+
+        self.abandon_lost_packets();
+
+        if let Some(app_latency_ms) = frames.iter().rev().find_map(|frame| frame.app_latency_ms) {
+            self.remote_app_latency_ms = Some(app_latency_ms as u64);
+        }
+
+        if let Some(health) = frames.into_iter().rev().find_map(|frame| frame.health) {
+            self.handle_remote_health(health);
+        }
+    }
+
+    fn handle_remote_health(&mut self, health: frame::ReceiverHealth) {
+        // A backlogged receiver's buffered bytes are reserved from the allocation limit until it
+        // reports that it has recovered
+        self.packet_sender.set_remote_backlog(if health.backlogged { health.buffered_bytes as usize } else { 0 });
+        self.remote_health = Some(health);
+    }
+
+    pub fn handle_fragment_ack_frame(&mut self, frame: frame::FragmentAckFrame) {
+        self.accounted(|hc| hc.handle_fragment_ack_frame_inner(frame));
+    }
+
+    fn handle_fragment_ack_frame_inner(&mut self, frame: frame::FragmentAckFrame) {
+        for group in frame.fragment_acks.into_iter() {
+            self.packet_sender.acknowledge_fragments(group.sequence_id, group.base_id, group.bitfield);
+        }
+
+        self.packet_sender.confirm_acknowledged();
+    }
+
+    pub fn step(&mut self) {
+        self.accounted(|hc| hc.step_inner());
+    }
+
+    fn step_inner(&mut self) {
+        let now = self.clock.now();
+
+        let now_ms = (now - self.time_base).as_millis() as u64;
+        let rtt_ms = self.send_rate_comp.rtt_ms().unwrap_or(INITIAL_RTT_ESTIMATE_MS);
+        let rto_ms = self.send_rate_comp.rto_ms().unwrap_or(INITIAL_RTO_ESTIMATE_MS);
+
+        // Store these values for subsequent flush()
+        self.now_ms = now_ms;
+        self.rtt_ms = rtt_ms;
+        self.rto_ms = rto_ms;
+
+        // Abandon frames which have been in flight for too long
+        self.expire_frames(now_ms);
+
+        // Forget old frame data
+        let forget_age_ms = (rtt_ms*4 + self.reorder_tolerance_ms).max(self.min_rto_ms);
+        self.frame_queue.forget_frames(now_ms.saturating_sub(forget_age_ms), self.send_rate_comp.rtt_ms());
+
+        // Packets which lost fragments will not be assembled unless they are resent
+        self.abandon_lost_packets();
+
+        // Drop stale unreliable packets if the send queue cannot be drained within one RTT
+        let max_backlog = (self.send_rate_comp.send_rate() * rtt_ms as f64 / 1000.0) as usize;
+        let (drop_count, drop_bytes) = self.packet_sender.drop_stale(now_ms, max_backlog);
+        self.stale_drop_count += drop_count;
+        self.stale_drop_bytes += drop_bytes;
+
+        // Fill flush allocation
+        self.fill_flush_alloc(now);
+
+        // Ignore previous TimeSensitive packets
+        self.flush_id = self.flush_id.wrapping_add(1);
+
+        // Keep the link busy with probes if there is nothing else to send
+        self.enqueue_probes(now_ms);
+
+        // Update send rate value
+        let ref mut frame_queue = self.frame_queue;
+        let feedback = frame_queue.get_feedback(now_ms);
+
+        if let Some(rtt_ms) = feedback.as_ref().and_then(|feedback| feedback.rtt_ms) {
+            self.rtt_histogram.record(rtt_ms);
+        }
+
+        self.send_rate_comp.step(now_ms, feedback,
+            |new_loss_rate: f64| {
+                frame_queue.reset_loss_rate(new_loss_rate);
+            }
+        );
+
+        // Update path measurements, and fail over from any path which has stopped working
+        if self.paths.len() > 1 {
+            for feedback in self.frame_queue.take_path_feedback().into_iter() {
+                self.paths.handle_feedback(feedback, now_ms);
+            }
+
+            self.paths.step(now_ms);
+        }
+
+        // Update the quality score
+        let jitter_ms = self.cadence_estimator.cadence().map(|cadence| cadence.jitter_ms);
+        let stall_count = self.packet_receiver.total_stall_count() + self.window_limited_count;
+        self.quality_estimator.sample(now_ms, self.send_rate_comp.loss_rate(), self.send_rate_comp.rtt_ms(),
+                                      jitter_ms, stall_count);
+
+        // Adapt data frame size to the new loss rate
+        if let Some(ref mut frame_size_policy) = self.frame_size_policy {
+            frame_size_policy.update(self.send_rate_comp.loss_rate());
+        }
+
+        // Sample connection metrics
+        #[cfg(feature = "stats")]
+        self.sample_stats(now_ms);
+    }
+
+    // Enqueues padded probe packets on the control channel while there is nothing else to send, so
+    // that RTT and send rate estimates remain fresh. Probe data accrues at the probe rate only while
+    // the connection is idle, and probes are otherwise sent like unreliable packets.
+    fn enqueue_probes(&mut self, now_ms: u64) {
+        let elapsed_ms = now_ms.saturating_sub(self.probe_time_ms);
+        self.probe_time_ms = now_ms;
+
+        if self.probe_rate == 0 {
+            return;
+        }
+
+        if self.is_send_pending() || self.local_reset.is_some() {
+            self.probe_credit = 0.0;
+            return;
+        }
+
+        self.probe_credit = (self.probe_credit + self.probe_rate as f64 * elapsed_ms as f64 / 1000.0).min(MAX_PROBE_CREDIT);
+
+        while self.probe_credit >= PROBE_SIZE as f64 {
+            let mut data = vec![0; PROBE_SIZE];
+            data[0] = CONTROL_PROBE;
+
+            self.packet_sender.enqueue_packet(data.into_boxed_slice().into(), frame::serial::CONTROL_CHANNEL_ID,
+                                              SendMode::Unreliable, self.flush_id, now_ms);
+
+            self.probe_credit -= PROBE_SIZE as f64;
+            self.probe_bytes += PROBE_SIZE as u64;
+        }
+    }
+
+    // Returns the time at which enough probe data will have accrued to send a probe, if probes are
+    // enabled
+    fn next_probe_ms(&self) -> Option<u64> {
+        if self.probe_rate == 0 {
+            return None;
+        }
+
+        let remaining = (PROBE_SIZE as f64 - self.probe_credit).max(0.0);
+
+        Some(self.probe_time_ms + (remaining * 1000.0 / self.probe_rate as f64).ceil() as u64)
+    }
+
+    #[cfg(test)]
+    pub fn flush(&mut self, sink: &mut impl FrameSink) {
+        self.flush_with_heartbeat(sink, || None);
+    }
+
+    // Like `flush()`, but attaches the payload returned by `heartbeat` to any sync frame which is
+    // sent. The callback is only invoked if a sync frame is about to be sent.
+    pub fn flush_with_heartbeat(&mut self, sink: &mut impl FrameSink, mut heartbeat: impl FnMut() -> Option<Box<[u8]>>) {
+        let mut sink = BlockDetectSink::new(sink);
+
+        // Send as many frames as possible, stopping early if the sink would block
+        self.accounted(|hc| hc.emit_frames(hc.now_ms, hc.rtt_ms, hc.rto_ms, hc.flush_id, &mut sink, &mut heartbeat));
+
+        self.update_send_blocked(sink.would_block);
+    }
+
+    // Like `flush_with_heartbeat()`, but consults the given pacer for the number of bytes which may
+    // be sent, in place of the connection's own budget if it is smaller
+    pub fn flush_paced(&mut self, sink: &mut impl FrameSink, heartbeat: impl FnMut() -> Option<Box<[u8]>>,
+                       pacer: &mut dyn Pacer, address: net::SocketAddr) {
+        // A frame may be started so long as the allocation is nonnegative, so an allocation of
+        // zero permits one frame
+        let flush_alloc = self.flush_alloc;
+        let budget = flush_alloc.saturating_add(1).max(0) as usize;
+
+        let granted = pacer.grant(address, budget, self.frame_queue.in_flight_size());
+        let paced_alloc = flush_alloc.min(granted.min(isize::MAX as usize) as isize - 1);
+
+        self.flush_alloc = paced_alloc;
+        self.flush_with_heartbeat(sink, heartbeat);
+
+        let sent = paced_alloc - self.flush_alloc;
+        self.flush_alloc = flush_alloc - sent;
+
+        pacer.sent(address, sent as usize);
+    }
+
+    // Sends pending acknowledgements if a gap in the received frame sequence has been detected since
+    // acknowledgements were last sent
+    pub fn flush_urgent_acks(&mut self, sink: &mut impl FrameSink) {
+        if self.ack_urgent {
+            let mut sink = BlockDetectSink::new(sink);
+            sink.select_path(self.paths.preferred());
+
+            self.accounted(|hc| {
+                let _ = hc.emit_ack_frames(&mut sink);
+            });
+
+            self.update_send_blocked(sink.would_block);
+        }
+    }
+
+    // Sends pending acknowledgements without sending any other frames. A server calls this for
+    // every client before sending data to any, so that no client's acknowledgements wait behind
+    // another client's data when the send buffer fills.
+    //
+    // One acknowledgement frame may be sent even if the flush allocation has been exhausted by
+    // data, so that the remote endpoint is never starved of feedback. Acknowledgements are only
+    // charged against what remains of the allocation, since a connection which has yet to send
+    // data (and so to raise its send rate) could otherwise accrue a debt it would never repay.
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub fn flush_acks(&mut self, sink: &mut impl FrameSink) {
+        if self.local_reset.is_some() || self.remote_reset_ack_pending {
+            return;
+        }
+
+        let flush_alloc = self.flush_alloc;
+        let ack_alloc = flush_alloc.max(0);
+
+        let mut sink = BlockDetectSink::new(sink);
+        sink.select_path(self.paths.preferred());
+
+        self.flush_alloc = ack_alloc;
+
+        self.accounted(|hc| {
+            let _ = hc.emit_ack_frames(&mut sink);
+        });
+
+        let sent = ack_alloc - self.flush_alloc;
+        self.flush_alloc = flush_alloc - sent.min(ack_alloc);
+
+        self.update_send_blocked(sink.would_block);
+    }
+
+    // Returns true if the most recent flush was interrupted because the sink would block. Any
+    // frames which could not be sent will be sent first on the next flush.
+    pub fn is_send_blocked(&self) -> bool {
+        self.send_blocked
+    }
+
+    fn update_send_blocked(&mut self, would_block: bool) {
+        if would_block {
+            self.send_blocked_count += 1;
+        }
+        self.send_blocked = would_block;
+    }
+
+    // Calls the given function, adding the time elapsed to the total processing time
+    #[cfg(feature = "cpu-accounting")]
+    fn accounted<F>(&mut self, f: F) where F: FnOnce(&mut Self) {
+        let begin = time::Instant::now();
+        f(self);
+        self.processing_time += begin.elapsed();
+    }
+
+    #[cfg(not(feature = "cpu-accounting"))]
+    fn accounted<F>(&mut self, f: F) where F: FnOnce(&mut Self) {
+        f(self);
+    }
+
+    #[cfg(feature = "stats")]
+    fn sample_stats(&mut self, now_ms: u64) {
+        if let Some(ref mut stats_history) = self.stats_history {
+            if now_ms < self.stats_next_sample_ms {
+                return;
+            }
+
+            let delivered_size = self.packet_receiver.delivered_size();
+
+            let elapsed_ms = match stats_history.latest() {
+                Some(sample) => now_ms - sample.time_ms,
+                None => now_ms,
+            };
+
+            let goodput = if elapsed_ms > 0 {
+                (delivered_size - self.stats_delivered_size) as f64 * 1000.0 / elapsed_ms as f64
+            } else {
+                0.0
+            };
+
+            stats_history.push(StatsSample {
+                time_ms: now_ms,
+                rtt_s: self.send_rate_comp.rtt_s(),
+                loss_rate: self.send_rate_comp.loss_rate(),
+                send_rate: self.send_rate_comp.send_rate(),
+                send_buffer_size: self.packet_sender.total_size(),
+                goodput,
+            });
+
+            self.stats_delivered_size = delivered_size;
+
+            // Skip any missed samples rather than sampling repeatedly
+            self.stats_next_sample_ms += self.stats_sample_interval_ms;
+            if self.stats_next_sample_ms <= now_ms {
+                self.stats_next_sample_ms = now_ms + self.stats_sample_interval_ms;
+            }
+        }
+    }
+
+    fn expire_frames(&mut self, now_ms: u64) {
+        let mut expired_refs = Vec::new();
+
+        let expired_count = self.frame_queue.expire_frames(now_ms.saturating_sub(self.max_frame_age_ms),
+                                                           self.send_rate_comp.rtt_ms(),
+                                                           |fragment_ref| expired_refs.push(fragment_ref));
+
+        self.expired_frame_count += expired_count as u64;
+
+        if !expired_refs.is_empty() {
+            // Resend any persistent fragments of the expired frames immediately
+            let expired_keys = expired_refs.iter()
+                .map(|fragment_ref| (fragment_ref.packet.as_ptr(), fragment_ref.fragment_id))
+                .collect::<HashSet<_>>();
+
+            let entries = std::mem::take(&mut self.resend_queue).into_vec();
+
+            self.resend_queue = entries.into_iter().map(|mut entry| {
+                if expired_keys.contains(&(entry.fragment_ref.packet.as_ptr(), entry.fragment_ref.fragment_id)) {
+                    entry.resend_time = entry.resend_time.min(now_ms);
+                }
+                entry
+            }).collect();
+        }
+    }
+
+    fn abandon_lost_packets(&mut self) {
+        for sequence_id in self.frame_queue.take_lost_packets().into_iter() {
+            self.packet_sender.abandon_packet(sequence_id);
+        }
+    }
+
+    fn fill_flush_alloc(&mut self, now: time::Duration) {
+        if let Some(time_last_flushed) = self.time_last_flushed {
+            let send_rate = self.send_rate_comp.send_rate();
+            let rtt_s = self.send_rate_comp.rtt_s();
+
+            let delta_time = (now - time_last_flushed).as_secs_f64();
+            let new_bytes = (send_rate * delta_time).round() as isize;
+            let alloc_max = (send_rate * rtt_s.unwrap_or(0.0)).round() as isize;
+
+            self.flush_alloc = self.flush_alloc.saturating_add(new_bytes).min(alloc_max);
+
+            //println!("dt: {}s, rtt: {:?}s, rate: {}B/s, new: {}B, max: {}B, val: {}B",
+            //       delta_time, rtt_s, send_rate, new_bytes, alloc_max, self.flush_alloc);
+        }
+        self.time_last_flushed = Some(now);
+    }
+
+    fn emit_frames(&mut self, now_ms: u64, rtt_ms: u64, rto_ms: u64, flush_id: u32, sink: &mut impl FrameSink,
+                   heartbeat: &mut impl FnMut() -> Option<Box<[u8]>>) {
+        // Frames other than data frames are sent over the best path
+        sink.select_path(self.paths.preferred());
+
+        if self.emit_reset_frames(now_ms, rto_ms, sink).is_err() || self.local_reset.is_some() {
+            return;
+        }
+
+        match self.emit_ack_frames(sink) {
+            Err(_) => return,
+            Ok(_) => (),
+        }
+
+        if self.emit_fragment_ack_frames(sink).is_err() {
+            return;
+        }
+
+        if self.emit_custom_frames(sink).is_err() {
+            return;
+        }
+
+        match self.emit_data_frames(now_ms, rtt_ms, flush_id, sink) {
+            Err(_) => return,
+            Ok(_) => (),
+        }
+
+        sink.select_path(self.paths.preferred());
+
+        match self.emit_sync_frame(now_ms, rto_ms, sink, heartbeat) {
+            Err(_) => return,
+            Ok(_) => (),
+        }
+
+        self.emit_path_probes(now_ms, sink);
+    }
+
+    // Sends a keepalive sync frame over each path which is due to be probed. The remote endpoint
+    // acknowledges the probe over the path from which it was received, if the path works.
+    fn emit_path_probes(&mut self, now_ms: u64, sink: &mut impl FrameSink) {
+        if self.paths.len() > 1 {
+            while let Some(path) = self.paths.take_probe(now_ms) {
+                let frame = frame::Frame::SyncFrame(frame::SyncFrame {
+                    next_frame_id: None,
+                    next_packet_id: None,
+                    heartbeat: None,
+                    drop_ranges: Vec::new(),
+                });
+
+                use frame::serial::Serialize;
+                let frame_bytes = frame.write();
+
+                sink.select_path(path);
+
+                if sink.send(&frame_bytes).is_err() {
+                    break;
+                }
+
+                self.flush_alloc -= (frame_bytes.len() + self.frame_overhead) as isize;
+            }
+
+            sink.select_path(self.paths.preferred());
+        }
+    }
+
+    fn emit_sync_frame(&mut self, now_ms: u64, rto_ms: u64, sink: &mut impl FrameSink,
+                       heartbeat: &mut impl FnMut() -> Option<Box<[u8]>>) -> Result<(),()> {
+        let elapsed_ms = now_ms - self.sync_timeout_base_ms;
+        let sync_timeout_ms = rto_ms.max(MIN_SYNC_TIMEOUT_MS);
+
+        if elapsed_ms >= sync_timeout_ms {
+            // A sync frame contains a frame ID any time the frame queue contains unacknowledged
+            // frames. This indicates to the receiver that sufficient time has passed since a frame
+            // was sent, and that the frame receive window should be advanced. This effects a call
+            // to `FrameAckQueue::resynchronize()` on the receiver, preventing the send/receive
+            // windows from desynchronizing in the event that many frames are dropped.
+            let next_frame_id =
+                if self.frame_queue.next_id() != self.frame_queue.base_id() {
+                    Some(self.frame_queue.next_id())
+                } else {
+                    None
+                };
+
+            // A sync frame contains a packet ID any time the send queue contains unacknowledged
+            // packets and no packet fragments would be resent. This indicates to the receiver that
+            // sufficient time has passed since a packet was sent, and that the packet receive
+            // window should be advanced. This effects a call to `PacketReceiver::resynchronize()`
+            // on the receiver, preventing the send/receive windows from desynchronizing in the
+            // event that many unreliable packets are dropped.
+            let next_packet_id =
+                if self.packet_sender.next_id() != self.packet_sender.base_id() &&
+                   self.resend_queue.len() == 0 && self.pending_queue.len() == 0 && self.critical_queue.is_empty() {
+                    Some(self.packet_sender.next_id())
+                } else {
+                    None
+                };
+
+            // A sync frame lists abandoned packets whenever the remote endpoint understands drop
+            // ranges and a packet ID would not be sent. This allows the receiver to advance past
+            // lost unreliable packets while reliable packets are still being resent. Ranges are
+            // repeated until the receiver's window has passed them.
+            let drop_ranges =
+                if self.drop_ranges && next_packet_id.is_none() {
+                    self.packet_sender.drop_ranges(frame::serial::MAX_DROP_RANGES)
+                } else {
+                    Vec::new()
+                };
+
+            // If neither a frame ID nor a packet ID would be sent, send a sync frame anyway to
+            // generate an ack and keep the connection alive. The user-specified keeaplive interval
+            // is considered in this case, but the rate at which keepalive frames are sent will
+            // still be restricted by TFRC's RTO computation and by MIN_SYNC_TIMEOUT_MS.
+            if next_frame_id.is_none() && next_packet_id.is_none() && drop_ranges.is_empty() {
+                if let Some(keepalive_interval_ms) = self.sync_keepalive_interval_ms {
+                    if elapsed_ms < keepalive_interval_ms {
+                        return Ok(());
+                    }
+                } else {
+                    return Ok(());
+                }
+            }
+
+            if self.flush_alloc < 0 {
+                return Err(());
+            }
+
+            // Oversized heartbeat payloads are dropped rather than truncated
+            let heartbeat = heartbeat().filter(|data| data.len() <= frame::serial::MAX_HEARTBEAT_SIZE);
+
+            let frame = frame::Frame::SyncFrame(frame::SyncFrame { next_frame_id, next_packet_id, heartbeat, drop_ranges });
+
+            use frame::serial::Serialize;
+            let frame_bytes = frame.write();
+
+            if sink.send(&frame_bytes).is_err() {
+                return Err(());
+            }
+
+            self.flush_alloc -= (frame_bytes.len() + self.frame_overhead) as isize;
+            self.sync_timeout_base_ms = now_ms;
+        }
+
+        return Ok(());
+    }
+
+    fn emit_reset_frames(&mut self, now_ms: u64, rto_ms: u64, sink: &mut impl FrameSink) -> Result<(),()> {
+        use frame::serial::Serialize;
+
+        if self.remote_reset_ack_pending {
+            let frame = frame::Frame::ResetAckFrame(self.remote_reset_ack.clone().unwrap());
+
+            if sink.send(&frame.write()).is_err() {
+                return Err(());
+            }
+
+            self.remote_reset_ack_pending = false;
+        }
+
+        if let Some(ref mut reset) = self.local_reset {
+            if now_ms >= reset.resend_time_ms {
+                let frame = frame::Frame::ResetFrame(reset.frame.clone());
+
+                if sink.send(&frame.write()).is_err() {
+                    return Err(());
+                }
+
+                reset.resend_time_ms = now_ms + rto_ms;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit_ack_frames(&mut self, sink: &mut impl FrameSink) -> Result<(),()> {
+        self.ack_urgent = false;
+
+        let flush_alloc_init = self.flush_alloc;
+        let sync_reply_init = self.sync_reply;
+
+        let frame_window_base_id = self.frame_ack_queue.base_id();
+        let packet_window_base_id = self.packet_receiver.base_id();
+        let health = if self.receiver_health { Some(self.packet_receiver.health()) } else { None };
+        let app_latency_ms = if self.app_latency { Some(self.app_latency_ms.min(u16::MAX as u64) as u16) } else { None };
+        let frame_overhead = self.frame_overhead;
+
+        let ref mut flush_alloc = self.flush_alloc;
+        let ref mut sync_reply = self.sync_reply;
+
+        let emit_cb = |frame_bytes: Box<[u8]>| {
+            sink.send(&frame_bytes).map_err(|_| ())?;
+            *flush_alloc -= (frame_bytes.len() + frame_overhead) as isize;
+            *sync_reply = false;
+            Ok(())
+        };
+
+        let mut afe = emit::AckFrameEmitter::new(frame_window_base_id, packet_window_base_id, health, app_latency_ms, flush_alloc_init, emit_cb);
+        afe.set_frame_overhead(frame_overhead);
+
+        let result = 'emit: {
+            if sync_reply_init {
+                match afe.push_dud() {
+                    Err(_) => break 'emit Err(()),
+                    Ok(_) => (),
+                }
+            }
+
+            while let Some(ack_group) = self.frame_ack_queue.peek() {
+                match afe.push(ack_group) {
+                    Err(_) => break 'emit Err(()),
+                    Ok(_) => (),
+                }
+
+                self.frame_ack_queue.pop();
+            }
+
+            Ok(())
+        };
+
+        if let Err(unsent_groups) = afe.finish() {
+            // Retry the ack groups of the failed frame on the next flush
+            self.frame_ack_queue.requeue(unsent_groups);
+
+            return Err(());
+        }
+
+        if result.is_ok() {
+            if let Some(since_ms) = self.ack_pending_since_ms.take() {
+                let now_ms = (self.clock.now() - self.time_base).as_millis() as u64;
+                let delay_ms = now_ms.saturating_sub(since_ms);
+
+                self.ack_delay_ms = Some(delay_ms);
+                self.max_ack_delay_ms = self.max_ack_delay_ms.max(delay_ms);
+            }
+        }
+
+        return result;
+    }
+
+    fn emit_fragment_ack_frames(&mut self, sink: &mut impl FrameSink) -> Result<(),()> {
+        let fragment_acks = self.packet_receiver.take_fragment_acks();
+
+        // Any groups which cannot be sent are forgotten; because groups are cumulative, the next
+        // fragment received for the same packet will cause them to be reported again
+        for chunk in fragment_acks.chunks(frame::serial::MAX_FRAGMENT_ACK_GROUPS) {
+            if self.flush_alloc < 0 {
+                return Err(());
+            }
+
+            let frame = frame::Frame::FragmentAckFrame(frame::FragmentAckFrame { fragment_acks: chunk.to_vec() });
+
+            use frame::serial::Serialize;
+            let frame_bytes = frame.write();
+
+            if sink.send(&frame_bytes).is_err() {
+                return Err(());
+            }
+
+            self.flush_alloc -= (frame_bytes.len() + self.frame_overhead) as isize;
+        }
+
+        Ok(())
+    }
+
+    fn emit_custom_frames(&mut self, sink: &mut impl FrameSink) -> Result<(),()> {
+        while let Some(frame_bytes) = self.custom_frame_queue.front() {
+            if self.flush_alloc < 0 {
+                return Err(());
+            }
+
+            if sink.send(frame_bytes).is_err() {
+                return Err(());
+            }
+
+            self.flush_alloc -= (frame_bytes.len() + self.frame_overhead) as isize;
+            self.custom_frame_queue.pop_front();
+        }
+
+        Ok(())
+    }
+
+    fn update_window_limited(&mut self, window_limited: bool) {
+        if window_limited && !self.window_limited {
+            self.window_limited_count += 1;
+            self.window_limited_event = Some(WindowUsage {
+                frames_in_flight: self.frame_queue.in_flight_count(),
+                frame_window_size: self.frame_queue.window_size(),
+                send_buffer_size: self.packet_sender.total_size(),
+            });
+        }
+
+        self.window_limited |= window_limited;
+    }
+
+    fn emit_data_frames(&mut self, now_ms: u64, rtt_ms: u64, flush_id: u32, sink: &mut impl FrameSink) -> Result<(),()> {
+        let flush_alloc_init = self.flush_alloc;
+        let reorder_tolerance_ms = self.reorder_tolerance_ms;
+        let min_rto_ms = self.min_rto_ms;
+
+        // Resends back off exponentially, but never precede the minimum RTO
+        let resend_delay_ms = |backoff: u8| (rtt_ms*(1 << backoff) + reorder_tolerance_ms).max(min_rto_ms);
+
+        // Remain window-limited until the window has room, regardless of other limits
+        if self.frame_queue.can_push() {
+            self.window_limited = false;
+        }
+
+        let frame_overhead = self.frame_overhead;
+
+        let ref mut send_rate_comp = self.send_rate_comp;
+        let ref mut flush_alloc = self.flush_alloc;
+        let ref mut sync_timeout_base_ms = self.sync_timeout_base_ms;
+        let ref mut paths = self.paths;
+
+        let emit_cb = |frame_bytes: Box<[u8]>| {
+            let path = if paths.len() > 1 {
+                let path = paths.schedule();
+                sink.select_path(path);
+                path
+            } else {
+                0
+            };
+
+            sink.send(&frame_bytes).map_err(|_| ())?;
+            send_rate_comp.notify_frame_sent(now_ms);
+            *flush_alloc -= (frame_bytes.len() + frame_overhead) as isize;
+            *sync_timeout_base_ms = now_ms;
+
+            if paths.len() > 1 {
+                paths.notify_sent(path, now_ms);
+            }
+
+            Ok(path as u8)
+        };
+
+        let max_frame_size = self.frame_size_policy.as_ref().map_or(MAX_FRAME_SIZE, |policy| policy.target_size())
+            .min(MAX_FRAME_SIZE - frame_overhead);
+
+        let mut dfe = emit::DataFrameEmitter::new(now_ms, &mut self.frame_queue, max_frame_size, flush_alloc_init, emit_cb);
+        dfe.set_frame_overhead(frame_overhead);
+
+        if let Some(ref mut transform) = self.transform {
+            dfe.set_transform(transform.as_mut());
+        }
+
+        let mut window_limited = false;
+
+        let result = 'emit: {
+            // Pull queued packets up to and including the last critical packet, so that critical
+            // fragments occupy the earliest frames of this flush. Packet IDs are assigned in
+            // order, so any normal packets enqueued before a critical packet are pulled too, but
+            // these are sent behind resends as usual.
+            while self.packet_sender.has_critical_packets() {
+                let critical = self.packet_sender.next_packet_critical();
+
+                if let Some((packet_rc, resend)) = self.packet_sender.emit_packet(flush_id, now_ms) {
+                    let last_fragment_id = packet_rc.borrow().last_fragment_id();
+
+                    for i in 0 ..= last_fragment_id {
+                        let fragment_ref = pending_packet::FragmentRef::new(&packet_rc, i);
+                        let entry = pending_queue::Entry::new(fragment_ref, resend);
+
+                        if critical {
+                            self.critical_queue.push_back(entry);
+                        } else {
+                            self.pending_queue.push_back(entry);
+                        }
+                    }
+                } else {
+                    break;
+                }
+            }
+
+            while let Some(entry) = self.critical_queue.front() {
+                if let Some(packet_rc) = entry.fragment_ref.packet.upgrade() {
+                    let packet_ref = packet_rc.borrow();
+
+                    if packet_ref.fragment_acknowledged(entry.fragment_ref.fragment_id) {
+                        self.critical_queue.pop_front();
+                        continue;
+                    }
+
+                    let schedule = if entry.resend {
+                        Some(emit::ResendSchedule { resend_time: now_ms + resend_delay_ms(0), send_count: 1 })
+                    } else {
+                        None
+                    };
+
+                    match dfe.push(&packet_rc, entry.fragment_ref.fragment_id, schedule) {
+                        // Being window-limited does not preclude further sends
+                        Err(emit::DataPushError::WindowLimited) => {
+                            window_limited = true;
+                            break 'emit Ok(());
+                        }
+                        Err(_) => break 'emit Err(()),
+                        Ok(_) => (),
+                    }
+
+                    self.critical_queue.pop_front();
+                } else {
+                    self.critical_queue.pop_front();
+                    continue;
+                }
+            }
+
+            while let Some(entry) = self.resend_queue.peek() {
+                if let Some(packet_rc) = entry.fragment_ref.packet.upgrade() {
+                    let packet_ref = packet_rc.borrow();
 
                     if packet_ref.fragment_acknowledged(entry.fragment_ref.fragment_id) {
                         self.resend_queue.pop();
                         continue;
                     }
 
-                    match dfe.push(&packet_rc, entry.fragment_ref.fragment_id, entry.resend) {
-                        // Being window-limited does not preclude further sends
-                        Err(emit::DataPushError::WindowLimited) => return Ok(()),
-                        Err(emit::DataPushError::SizeLimited) => return Err(()),
-                        Ok(_) => (),
+                    if entry.resend_time > now_ms {
+                        break;
+                    }
+
+                    const MAX_SEND_COUNT: u8 = 2;
+
+                    let schedule = emit::ResendSchedule {
+                        resend_time: now_ms + resend_delay_ms(entry.send_count),
+                        send_count: (entry.send_count + 1).min(MAX_SEND_COUNT),
+                    };
+
+                    match dfe.push(&packet_rc, entry.fragment_ref.fragment_id, Some(schedule)) {
+                        // Being window-limited does not preclude further sends
+                        Err(emit::DataPushError::WindowLimited) => {
+                            window_limited = true;
+                            break 'emit Ok(());
+                        }
+                        Err(_) => break 'emit Err(()),
+                        Ok(_) => (),
+                    }
+
+                    self.resend_queue.pop();
+                } else {
+                    self.resend_queue.pop();
+                    continue;
+                }
+            }
+
+            loop {
+                if self.pending_queue.is_empty() {
+                    if let Some(first_packet) = self.packet_sender.emit_packet(flush_id, now_ms) {
+                        let mut packets = vec![ first_packet ];
+
+                        // Interleave the fragments of consecutive multi-fragment packets, so that
+                        // a lost frame delays the completion of only one packet
+                        if packets[0].0.borrow().last_fragment_id() > 0 {
+                            while packets.len() < MAX_INTERLEAVED_PACKETS && self.packet_sender.next_packet_fragmented() {
+                                match self.packet_sender.emit_packet(flush_id, now_ms) {
+                                    Some(packet) => packets.push(packet),
+                                    None => break,
+                                }
+                            }
+                        }
+
+                        let max_last_fragment_id = packets.iter().map(|(packet_rc, _)| packet_rc.borrow().last_fragment_id()).max().unwrap();
+
+                        for i in 0 ..= max_last_fragment_id {
+                            for (packet_rc, resend) in packets.iter() {
+                                if i <= packet_rc.borrow().last_fragment_id() {
+                                    let fragment_ref = pending_packet::FragmentRef::new(packet_rc, i);
+                                    let entry = pending_queue::Entry::new(fragment_ref, *resend);
+                                    self.pending_queue.push_back(entry);
+                                }
+                            }
+                        }
+                    } else {
+                        break;
                     }
+                }
 
-                    let entry = self.pending_queue.pop_front().unwrap();
-
-                    if entry.resend {
-                        self.resend_queue.push(resend_queue::Entry::new(entry.fragment_ref, now_ms + rtt_ms, 1));
+                while let Some(entry) = self.pending_queue.front() {
+                    if let Some(packet_rc) = entry.fragment_ref.packet.upgrade() {
+                        let packet_ref = packet_rc.borrow();
+
+                        if packet_ref.fragment_acknowledged(entry.fragment_ref.fragment_id) {
+                            self.pending_queue.pop_front();
+                            continue;
+                        }
+
+                        let schedule = if entry.resend {
+                            Some(emit::ResendSchedule { resend_time: now_ms + resend_delay_ms(0), send_count: 1 })
+                        } else {
+                            None
+                        };
+
+                        match dfe.push(&packet_rc, entry.fragment_ref.fragment_id, schedule) {
+                            // Being window-limited does not preclude further sends
+                            Err(emit::DataPushError::WindowLimited) => {
+                                window_limited = true;
+                                break 'emit Ok(());
+                            }
+                            Err(_) => break 'emit Err(()),
+                            Ok(_) => (),
+                        }
+
+                        self.pending_queue.pop_front();
+                    } else {
+                        self.pending_queue.pop_front();
+                        continue;
                     }
-                } else {
-                    self.resend_queue.pop();
-                    continue;
                 }
             }
+
+            Ok(())
+        };
+
+        let (resend_entries, unsent_entries) = dfe.finish();
+
+        self.update_window_limited(window_limited);
+
+        for entry in resend_entries.into_iter() {
+            self.resend_queue.push(entry);
         }
 
-        dfe.finalize();
+        if !unsent_entries.is_empty() {
+            // Retry the fragments of the failed frame first on the next flush
+            for entry in unsent_entries.into_iter().rev() {
+                self.pending_queue.push_front(entry);
+            }
 
-        return Ok(());
+            return Err(());
+        }
+
+        return result;
     }
 }
 
-// Internal Rc objects are unique to this object
-unsafe impl Send for HalfConnection {}
+// Internal Rc objects are unique to this object, and the datagram transform is itself Send
+unsafe impl<const CHANNELS: usize> Send for HalfConnection<CHANNELS> {}
 
-// Internal RefCell objects cannot be accessed through a &HalfConnection
-unsafe impl Sync for HalfConnection {}
+// Internal RefCell objects, and the datagram transform, cannot be accessed through a
+// &HalfConnection
+unsafe impl<const CHANNELS: usize> Sync for HalfConnection<CHANNELS> {}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::CHANNEL_COUNT;
+    use crate::LatencyClass;
     use crate::SendMode;
+    use crate::SystemClock;
+    use crate::VirtualClock;
     use crate::frame::Datagram;
 
     use crate::MAX_FRAGMENT_SIZE;
     use crate::MAX_FRAME_SIZE;
     use crate::MAX_FRAME_WINDOW_SIZE;
+    use crate::MAX_PACKET_SIZE;
     use crate::MAX_PACKET_WINDOW_SIZE;
 
     struct TestSink {
@@ -463,8 +1822,9 @@ mod tests {
     }
 
     impl FrameSink for TestSink {
-        fn send(&mut self, frame_bytes: &[u8]) {
+        fn send(&mut self, frame_bytes: &[u8]) -> std::io::Result<()> {
             self.emitted.push(frame_bytes.into());
+            Ok(())
         }
     }
 
@@ -493,7 +1853,11 @@ mod tests {
 
     impl TestApparatus {
         fn new() -> Self {
-            let config = Config {
+            Self::new_config(Self::default_config())
+        }
+
+        fn default_config() -> Config {
+            Config {
                 tx_frame_window_size: MAX_FRAME_WINDOW_SIZE,
                 rx_frame_window_size: MAX_FRAME_WINDOW_SIZE,
 
@@ -511,10 +1875,41 @@ mod tests {
                 tx_alloc_limit: MAX_FRAGMENT_SIZE * MAX_PACKET_WINDOW_SIZE as usize,
                 rx_alloc_limit: MAX_FRAGMENT_SIZE * MAX_PACKET_WINDOW_SIZE as usize,
 
+                rx_max_packet_size: MAX_PACKET_SIZE,
+
                 keepalive_interval_ms: Some(5000),
-            };
 
-            Self::new_config(config)
+                #[cfg(feature = "stats")]
+                stats_sample_interval_ms: None,
+                #[cfg(feature = "stats")]
+                stats_history_len: 1,
+
+                reorder_tolerance_frames: 3,
+                reorder_tolerance_ms: 0,
+
+                early_retransmit: true,
+                min_rto_ms: 0,
+
+                max_frame_age_ms: 10000,
+
+                fragment_acks: false,
+
+                receiver_health: false,
+            app_latency: false,
+                control_channels: false,
+                drop_ranges: false,
+
+                adaptive_frame_size: false,
+
+                deliver_empty_packets: true,
+                receive_order: ReceiveOrder::Sent,
+                channel_weights: Vec::new(),
+                probe_rate: 0,
+                channel_count: CHANNEL_COUNT,
+                frame_overhead: 0,
+
+                clock: Arc::new(SystemClock::new()),
+            }
         }
 
         fn new_config(config: Config) -> Self {
@@ -541,7 +1936,7 @@ mod tests {
         }
 
         fn receive_ack(&mut self, frame: frame::AckFrame) {
-            self.hc.handle_ack_frame(frame);
+            self.hc.handle_ack_frames(vec![ frame ]);
         }
 
         fn enqueue_packet(&mut self, data: Box<[u8]>, channel_id: u8, mode: SendMode) {
@@ -567,7 +1962,7 @@ mod tests {
 
             self.hc.flush_alloc = flush_alloc;
 
-            self.hc.emit_frames(now_ms, rtt_ms, 4*rtt_ms, self.flush_id, &mut test_sink);
+            self.hc.emit_frames(now_ms, rtt_ms, 4*rtt_ms, self.flush_id, &mut test_sink, &mut || None);
 
             return test_sink.emitted;
         }
@@ -647,6 +2042,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 0,
             data: vec![ 0, 0, 0 ].into_boxed_slice(),
@@ -673,6 +2069,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 1,
             data: packet_data[ .. MAX_FRAGMENT_SIZE].into(),
@@ -683,6 +2080,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 1,
             fragment_id_last: 1,
             data: packet_data[MAX_FRAGMENT_SIZE .. ].into(),
@@ -691,8 +2089,8 @@ mod tests {
         test_data_frame(&frames[0], 0, vec![ dg0 ]);
         test_data_frame(&frames[1], 1, vec![ dg1 ]);
 
-        assert_eq!(frames[0].len(), MAX_FRAME_SIZE);
-        assert_eq!(frames[1].len(), MAX_FRAME_SIZE);
+        assert_eq!(frames[0].len(), frame::serial::MAX_UNTAGGED_FRAME_SIZE);
+        assert_eq!(frames[1].len(), frame::serial::MAX_UNTAGGED_FRAME_SIZE);
     }
 
     // Time sensitive packet IDs should not be resent if the flush ID does not match.
@@ -716,6 +2114,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 0,
             data: vec![ 1, 1, 1 ].into_boxed_slice(),
@@ -758,6 +2157,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 0,
             data: p4,
@@ -804,6 +2204,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 0,
             data: p1,
@@ -812,6 +2213,142 @@ mod tests {
         test_data_frame(&frames[0], 5, vec![ dg1 ]);
     }
 
+    // If frame acknowledgements are lost, fragment acknowledgements should prevent the received
+    // fragments of a multi-fragment packet from being resent.
+    #[test]
+    fn no_resend_after_fragment_ack() {
+        use crate::frame::serial::Serialize;
+
+        let now_ms = 0;
+        let rtt_ms = 100;
+
+        let mut sender = TestApparatus::new();
+        let mut receiver = TestApparatus::new_config(Config { fragment_acks: true, .. TestApparatus::default_config() });
+
+        let p0 = (0 .. MAX_FRAGMENT_SIZE*5).map(|i| i as u8).collect::<Vec<u8>>().into_boxed_slice();
+        sender.enqueue_packet(p0, 0, SendMode::Persistent);
+
+        let frames = sender.emit_frames(now_ms, rtt_ms, 10000);
+        assert_eq!(frames.len(), 5);
+
+        // Fragments 1 and 4 are lost
+        for &i in [ 0, 2, 3 ].iter() {
+            match frame::Frame::read(&frames[i]) {
+                Some(frame::Frame::DataFrame(data_frame)) => receiver.receive_data(data_frame),
+                _ => panic!("Expected DataFrame"),
+            }
+        }
+
+        // The frame acknowledgement is lost, but the fragment acknowledgement is not
+        let mut fragment_acks = Vec::new();
+
+        for frame_bytes in receiver.emit_frames(now_ms, rtt_ms, 10000).iter() {
+            if let Some(frame::Frame::FragmentAckFrame(frame)) = frame::Frame::read(frame_bytes) {
+                fragment_acks.push(frame);
+            }
+        }
+
+        assert_eq!(fragment_acks, vec![
+            frame::FragmentAckFrame {
+                fragment_acks: vec![
+                    frame::FragmentAckGroup { sequence_id: 0, base_id: 0, bitfield: 0b01101 },
+                ],
+            },
+        ]);
+
+        for frame in fragment_acks.into_iter() {
+            sender.hc.handle_fragment_ack_frame(frame);
+        }
+
+        let frames = sender.emit_frames(now_ms + rtt_ms, rtt_ms, 10000);
+
+        let mut resent_fragment_ids = frames.iter().flat_map(|frame_bytes| {
+            match frame::Frame::read(frame_bytes) {
+                Some(frame::Frame::DataFrame(data_frame)) => data_frame.datagrams,
+                _ => panic!("Expected DataFrame"),
+            }
+        }).map(|datagram| datagram.fragment_id).collect::<Vec<_>>();
+
+        resent_fragment_ids.sort();
+        assert_eq!(resent_fragment_ids, vec![ 1, 4 ]);
+    }
+
+    // The fragments of consecutive multi-fragment packets should be interleaved, but those of
+    // single-fragment packets should not.
+    #[test]
+    fn fragment_interleaving() {
+        use crate::frame::serial::Serialize;
+
+        let now_ms = 0;
+        let rtt_ms = 100;
+
+        let mut ta = TestApparatus::new();
+
+        ta.enqueue_packet(vec![ 0; MAX_FRAGMENT_SIZE*2 + 1 ].into_boxed_slice(), 0, SendMode::Reliable);
+        ta.enqueue_packet(vec![ 1; MAX_FRAGMENT_SIZE*3     ].into_boxed_slice(), 0, SendMode::Reliable);
+        ta.enqueue_packet(vec![ 2; 1                       ].into_boxed_slice(), 0, SendMode::Reliable);
+        ta.enqueue_packet(vec![ 3; MAX_FRAGMENT_SIZE + 1   ].into_boxed_slice(), 0, SendMode::Reliable);
+
+        let frames = ta.emit_frames(now_ms, rtt_ms, 100000);
+
+        let datagram_ids = frames.iter().flat_map(|frame_bytes| {
+            match frame::Frame::read(frame_bytes) {
+                Some(frame::Frame::DataFrame(data_frame)) => data_frame.datagrams,
+                _ => panic!("Expected DataFrame"),
+            }
+        }).map(|datagram| (datagram.sequence_id, datagram.fragment_id)).collect::<Vec<_>>();
+
+        assert_eq!(datagram_ids, vec![
+            (0, 0), (1, 0),
+            (0, 1), (1, 1),
+            (0, 2), (1, 2),
+            (2, 0),
+            (3, 0),
+            (3, 1),
+        ]);
+    }
+
+    // Critical packets should be packed into the earliest frames of a flush, ahead of other
+    // pending data, without reordering packet IDs.
+    #[test]
+    fn critical_packing() {
+        use crate::frame::serial::Serialize;
+
+        let now_ms = 0;
+        let rtt_ms = 100;
+
+        let mut ta = TestApparatus::new();
+
+        // Sent in the previous flush, and still pending
+        ta.enqueue_packet(vec![ 0; MAX_FRAGMENT_SIZE*3 ].into_boxed_slice(), 0, SendMode::Reliable);
+        let frames = ta.emit_frames(now_ms, rtt_ms, 1);
+        assert_eq!(frames.len(), 1);
+
+        let critical = SendOptions { latency_class: LatencyClass::Critical, .. Default::default() };
+
+        ta.enqueue_packet(vec![ 1; MAX_FRAGMENT_SIZE ].into_boxed_slice(), 0, SendMode::Reliable);
+        ta.hc.send_with(vec![ 2; 10 ].into_boxed_slice(), 1, SendMode::Reliable, critical, 0);
+        ta.enqueue_packet(vec![ 3; 10 ].into_boxed_slice(), 1, SendMode::Reliable);
+        ta.hc.send_with(vec![ 4; 10 ].into_boxed_slice(), 2, SendMode::Unreliable, critical, 1);
+
+        let frames = ta.emit_frames(now_ms, rtt_ms, 100000);
+
+        let datagram_ids = frames.iter().map(|frame_bytes| {
+            match frame::Frame::read(frame_bytes) {
+                Some(frame::Frame::DataFrame(data_frame)) => {
+                    data_frame.datagrams.iter().map(|datagram| (datagram.sequence_id, datagram.fragment_id)).collect::<Vec<_>>()
+                }
+                _ => panic!("Expected DataFrame"),
+            }
+        }).collect::<Vec<_>>();
+
+        assert_eq!(datagram_ids, vec![
+            vec![ (2, 0), (4, 0), (0, 1) ],
+            vec![ (0, 2) ],
+            vec![ (1, 0), (3, 0) ],
+        ]);
+    }
+
     // Simple sync case for which both the frame and packet windows are resynchronized.
     #[test]
     fn sync_frame_and_packet_window() {
@@ -832,7 +2369,7 @@ mod tests {
         let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
         assert_eq!(frames.len(), 1);
 
-        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: Some(5), next_packet_id: Some(5) });
+        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: Some(5), next_packet_id: Some(5), heartbeat: None, drop_ranges: Vec::new() });
     }
 
     // Sync case for which packets exist in the resend/pending queues, and the frame transfer
@@ -856,7 +2393,7 @@ mod tests {
         let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
         assert_eq!(frames.len(), 1);
 
-        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: Some(MAX_FRAME_WINDOW_SIZE), next_packet_id: None });
+        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: Some(MAX_FRAME_WINDOW_SIZE), next_packet_id: None, heartbeat: None, drop_ranges: Vec::new() });
     }
 
     // Sync case for which no the receiver has not yet called receive(), and only the packet window
@@ -875,14 +2412,14 @@ mod tests {
         let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
         assert_eq!(frames.len(), 5);
 
-        ta.receive_ack(frame::AckFrame { frame_acks: Vec::new(), frame_window_base_id: 5, packet_window_base_id: 0 });
+        ta.receive_ack(frame::AckFrame { frame_acks: Vec::new(), frame_window_base_id: 5, packet_window_base_id: 0, health: None, app_latency_ms: None });
 
         now_ms += MIN_SYNC_TIMEOUT_MS;
 
         let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
         assert_eq!(frames.len(), 1);
 
-        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: None, next_packet_id: Some(5) });
+        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: None, next_packet_id: Some(5), heartbeat: None, drop_ranges: Vec::new() });
     }
 
     // An ack frame should always be sent in response to a sync frame
@@ -891,19 +2428,292 @@ mod tests {
         let now_ms = 0;
         let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
 
-        let mut ta = TestApparatus::new();
+        let mut ta = TestApparatus::new();
+
+        ta.receive_sync(frame::SyncFrame { next_frame_id: Some(5), next_packet_id: Some(5), heartbeat: None, drop_ranges: Vec::new() });
+
+        let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
+        assert_eq!(frames.len(), 1);
+
+        test_ack_frame(&frames[0], frame::AckFrame { frame_acks: Vec::new(), frame_window_base_id: 5, packet_window_base_id: 5, health: None, app_latency_ms: None });
+    }
+
+    // A reset should drop pending data, be resent every RTO until acknowledged, and signal its
+    // completion once acknowledged.
+    #[test]
+    fn reset_exchange() {
+        use crate::frame::serial::Serialize;
+
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+
+        let mut ta = TestApparatus::new();
+        let mut now_ms = 0;
+
+        for _ in 0 .. 5 {
+            ta.enqueue_packet(vec![ 0; MAX_FRAGMENT_SIZE ].into_boxed_slice(), 0, SendMode::Reliable);
+        }
+
+        let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
+        assert_eq!(frames.len(), 5);
+
+        ta.enqueue_packet(vec![ 0 ].into_boxed_slice(), 0, SendMode::Reliable);
+
+        ta.hc.reset();
+
+        let reset_frame = match frame::Frame::read(&ta.emit_frames(now_ms, rtt_ms, 10000)[0]).unwrap() {
+            frame::Frame::ResetFrame(reset_frame) => reset_frame,
+            _ => panic!("Expected ResetFrame"),
+        };
+
+        assert_eq!(reset_frame.frame_base_id, 5);
+        assert_eq!(reset_frame.packet_base_id, 5);
+
+        // Nothing else is sent until the reset has been acknowledged
+        ta.enqueue_packet(vec![ 0 ].into_boxed_slice(), 0, SendMode::Reliable);
+        assert_eq!(ta.emit_frames(now_ms, rtt_ms, 10000).len(), 0);
+
+        now_ms += 4*rtt_ms;
+
+        let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frame::Frame::read(&frames[0]).unwrap(), frame::Frame::ResetFrame(reset_frame.clone()));
+
+        // Acknowledgements of other resets are ignored
+        ta.hc.handle_reset_ack_frame(frame::ResetAckFrame { nonce_ack: reset_frame.nonce.wrapping_add(1), frame_base_id: 0, packet_base_id: 0 });
+        assert_eq!(ta.hc.poll_reset(), None);
+
+        ta.hc.handle_reset_ack_frame(frame::ResetAckFrame { nonce_ack: reset_frame.nonce, frame_base_id: 0, packet_base_id: 0 });
+        assert_eq!(ta.hc.poll_reset(), Some(6));
+        assert_eq!(ta.hc.poll_reset(), None);
+
+        let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
+        assert_eq!(frames.len(), 1);
+        test_data_frame(&frames[0], 5, vec![ Datagram {
+            sequence_id: 5,
+            channel_id: 0,
+            window_parent_lead: 0,
+            channel_parent_lead: 0,
+            keyed: false,
+            fragment_id: 0,
+            fragment_id_last: 0,
+            data: vec![ 0 ].into_boxed_slice(),
+        } ]);
+    }
+
+    // A reset from the remote endpoint should be acknowledged with our own sequence IDs, and a
+    // repeated reset acknowledged without resetting again.
+    #[test]
+    fn reset_response() {
+        use crate::frame::serial::Serialize;
+
+        let now_ms = 0;
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+
+        let mut ta = TestApparatus::new();
+
+        ta.enqueue_packet(vec![ 0 ].into_boxed_slice(), 0, SendMode::Reliable);
+
+        let reset_frame = frame::ResetFrame { nonce: 7, frame_base_id: 5, packet_base_id: 5 };
+        let reset_ack_frame = frame::ResetAckFrame { nonce_ack: 7, frame_base_id: 0, packet_base_id: 0 };
+
+        ta.hc.handle_reset_frame(reset_frame.clone());
+        assert_eq!(ta.hc.poll_reset(), Some(1));
+
+        let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frame::Frame::read(&frames[0]).unwrap(), frame::Frame::ResetAckFrame(reset_ack_frame.clone()));
+
+        ta.hc.handle_reset_frame(reset_frame);
+        assert_eq!(ta.hc.poll_reset(), None);
+
+        let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frame::Frame::read(&frames[0]).unwrap(), frame::Frame::ResetAckFrame(reset_ack_frame));
+
+        // The receive window now begins at the remote endpoint's base IDs
+        ta.receive_sync(frame::SyncFrame { next_frame_id: None, next_packet_id: None, heartbeat: None, drop_ranges: Vec::new() });
+
+        let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
+        assert_eq!(frames.len(), 1);
+        test_ack_frame(&frames[0], frame::AckFrame { frame_acks: Vec::new(), frame_window_base_id: 5, packet_window_base_id: 5, health: None, app_latency_ms: None });
+    }
+
+    // Packets should be resent [1, 2, 4, 4, ... 4] RTTs after the previous send.
+    #[test]
+    fn resend_timing() {
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+
+        let mut ta = TestApparatus::new();
+
+        let p0 = (0 .. 400).map(|i| i as u8).collect::<Vec<u8>>().into_boxed_slice();
+        ta.enqueue_packet(p0.clone(), 0, SendMode::Persistent);
+
+        let frames = ta.emit_frames(0, rtt_ms, MAX_FRAME_SIZE as isize);
+        assert_eq!(frames.len(), 1);
+
+        let frames = ta.emit_frames(1, rtt_ms, MAX_FRAME_SIZE as isize);
+        assert_eq!(frames.len(), 0);
+
+        let resend_times = [ rtt_ms, 3*rtt_ms, 7*rtt_ms, 11*rtt_ms, 15*rtt_ms, 19*rtt_ms, 23*rtt_ms ];
+
+        for &now_ms in resend_times.iter() {
+            let frames = ta.emit_frames(now_ms - 1, rtt_ms, MAX_FRAME_SIZE as isize);
+            assert_eq!(frames.len(), 0);
+
+            let frames = ta.emit_frames(now_ms    , rtt_ms, MAX_FRAME_SIZE as isize);
+            assert_eq!(frames.len(), 1);
+
+            let frames = ta.emit_frames(now_ms + 1, rtt_ms, MAX_FRAME_SIZE as isize);
+            assert_eq!(frames.len(), 0);
+        }
+    }
+
+    // The reorder tolerance should delay each resend by a fixed amount.
+    #[test]
+    fn resend_timing_reorder_tolerance() {
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+        let tolerance_ms = 50;
+
+        let mut ta = TestApparatus::new_config(Config { reorder_tolerance_ms: tolerance_ms, .. TestApparatus::default_config() });
+
+        let p0 = (0 .. 400).map(|i| i as u8).collect::<Vec<u8>>().into_boxed_slice();
+        ta.enqueue_packet(p0.clone(), 0, SendMode::Persistent);
+
+        let frames = ta.emit_frames(0, rtt_ms, MAX_FRAME_SIZE as isize);
+        assert_eq!(frames.len(), 1);
+
+        let first_resend_ms = rtt_ms + tolerance_ms;
+        let second_resend_ms = first_resend_ms + 2*rtt_ms + tolerance_ms;
+
+        for &now_ms in [ first_resend_ms, second_resend_ms ].iter() {
+            let frames = ta.emit_frames(now_ms - 1, rtt_ms, MAX_FRAME_SIZE as isize);
+            assert_eq!(frames.len(), 0);
+
+            let frames = ta.emit_frames(now_ms    , rtt_ms, MAX_FRAME_SIZE as isize);
+            assert_eq!(frames.len(), 1);
+        }
+    }
+
+    // The minimum RTO should delay resends which would otherwise precede it.
+    #[test]
+    fn resend_timing_min_rto() {
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+        let min_rto_ms = 3*rtt_ms;
+
+        let mut ta = TestApparatus::new_config(Config { min_rto_ms, .. TestApparatus::default_config() });
+
+        let p0 = (0 .. 400).map(|i| i as u8).collect::<Vec<u8>>().into_boxed_slice();
+        ta.enqueue_packet(p0.clone(), 0, SendMode::Persistent);
+
+        let frames = ta.emit_frames(0, rtt_ms, MAX_FRAME_SIZE as isize);
+        assert_eq!(frames.len(), 1);
+
+        let first_resend_ms = min_rto_ms;
+        let second_resend_ms = first_resend_ms + min_rto_ms;
+        let third_resend_ms = second_resend_ms + 4*rtt_ms;
+
+        for &now_ms in [ first_resend_ms, second_resend_ms, third_resend_ms ].iter() {
+            let frames = ta.emit_frames(now_ms - 1, rtt_ms, MAX_FRAME_SIZE as isize);
+            assert_eq!(frames.len(), 0);
+
+            let frames = ta.emit_frames(now_ms    , rtt_ms, MAX_FRAME_SIZE as isize);
+            assert_eq!(frames.len(), 1);
+        }
+    }
+
+    // Unless early retransmission is disabled, a frame should be declared lost once enough later
+    // frames have been acknowledged.
+    #[test]
+    fn early_retransmit() {
+        let rtt_ms = 100;
+
+        for &early_retransmit in [ true, false ].iter() {
+            let mut ta = TestApparatus::new_config(Config { early_retransmit, .. TestApparatus::default_config() });
+
+            for i in 0 .. 4 {
+                ta.enqueue_packet(vec![ i; MAX_FRAGMENT_SIZE ].into_boxed_slice(), 0, SendMode::Persistent);
+            }
+
+            let frames = ta.emit_frames(0, rtt_ms, 10000);
+            assert_eq!(frames.len(), 4);
+
+            let nonce = frames[1 ..].iter().fold(false, |nonce, frame| nonce ^ data_frame_nonce(frame));
+            ta.acknowledge_frame_group(frame::AckGroup { base_id: 1, bitfield: 0b111, nonce }, Some(rtt_ms));
+
+            let expected_lost = if early_retransmit { vec![ 0 ] } else { Vec::new() };
+            assert_eq!(ta.hc.frame_queue.take_lost_packets(), expected_lost);
+        }
+    }
+
+    // Frames which exceed the maximum frame age should be abandoned, and their persistent
+    // fragments resent immediately.
+    #[test]
+    fn frame_expiry() {
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+        let max_age_ms = 50;
+
+        let mut ta = TestApparatus::new_config(Config { max_frame_age_ms: max_age_ms, .. TestApparatus::default_config() });
+
+        let p0 = (0 .. 400).map(|i| i as u8).collect::<Vec<u8>>().into_boxed_slice();
+        ta.enqueue_packet(p0.clone(), 0, SendMode::Persistent);
+
+        let frames = ta.emit_frames(0, rtt_ms, MAX_FRAME_SIZE as isize);
+        assert_eq!(frames.len(), 1);
+
+        ta.hc.expire_frames(max_age_ms);
+        assert_eq!(ta.hc.link_stats().expired_frames, 0);
+
+        let now_ms = max_age_ms + 1;
+        ta.hc.expire_frames(now_ms);
+        assert_eq!(ta.hc.link_stats().expired_frames, 1);
+        assert_eq!(ta.hc.frame_queue.base_id(), 1);
+
+        // The resend is no longer scheduled one RTT after the original send
+        let frames = ta.emit_frames(now_ms, rtt_ms, MAX_FRAME_SIZE as isize);
+        assert_eq!(frames.len(), 1);
+    }
+
+    // A full frame window should be signaled once each time it occurs.
+    #[test]
+    fn window_limited() {
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+
+        let mut ta = TestApparatus::new_config(Config { tx_frame_window_size: 2, .. TestApparatus::default_config() });
+
+        for _ in 0 .. 6 {
+            ta.enqueue_packet(vec![ 0; MAX_FRAGMENT_SIZE ].into_boxed_slice(), 0, SendMode::Unreliable);
+        }
+
+        let frames = ta.emit_frames(0, rtt_ms, 100000);
+        assert_eq!(frames.len(), 2);
+
+        assert_eq!(ta.hc.poll_window_limited(), Some(WindowUsage {
+            frames_in_flight: 2,
+            frame_window_size: 2,
+            send_buffer_size: 6*MAX_FRAGMENT_SIZE,
+        }));
+        assert_eq!(ta.hc.poll_window_limited(), None);
 
-        ta.receive_sync(frame::SyncFrame { next_frame_id: Some(5), next_packet_id: Some(5) });
+        // Still limited
+        let frames = ta.emit_frames(1, rtt_ms, 100000);
+        assert_eq!(frames.len(), 0);
+        assert_eq!(ta.hc.poll_window_limited(), None);
+        assert_eq!(ta.hc.link_stats().window_limited_count, 1);
 
-        let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
-        assert_eq!(frames.len(), 1);
+        ta.receive_ack(frame::AckFrame { frame_acks: Vec::new(), frame_window_base_id: 2, packet_window_base_id: 2, health: None, app_latency_ms: None });
+        assert_eq!(ta.hc.link_stats().frames_in_flight, 0);
 
-        test_ack_frame(&frames[0], frame::AckFrame { frame_acks: Vec::new(), frame_window_base_id: 5, packet_window_base_id: 5 });
+        let frames = ta.emit_frames(2, rtt_ms, 100000);
+        assert_eq!(frames.len(), 2);
+        assert!(ta.hc.poll_window_limited().is_some());
+        assert_eq!(ta.hc.link_stats().window_limited_count, 2);
     }
 
-    // Packets should be resent [1, 2, 4, 4, ... 4] RTTs after the previous send.
+    // Acknowledgements of frames containing retransmitted data should not produce RTT samples.
     #[test]
-    fn resend_timing() {
+    fn retransmit_rtt_sampling() {
+        use frame::serial::Serialize;
+
         let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
 
         let mut ta = TestApparatus::new();
@@ -911,24 +2721,38 @@ mod tests {
         let p0 = (0 .. 400).map(|i| i as u8).collect::<Vec<u8>>().into_boxed_slice();
         ta.enqueue_packet(p0.clone(), 0, SendMode::Persistent);
 
-        let frames = ta.emit_frames(0, rtt_ms, MAX_FRAME_SIZE as isize);
-        assert_eq!(frames.len(), 1);
-
-        let frames = ta.emit_frames(1, rtt_ms, MAX_FRAME_SIZE as isize);
-        assert_eq!(frames.len(), 0);
-
-        let resend_times = [ rtt_ms, 3*rtt_ms, 7*rtt_ms, 11*rtt_ms, 15*rtt_ms, 19*rtt_ms, 23*rtt_ms ];
-
-        for &now_ms in resend_times.iter() {
-            let frames = ta.emit_frames(now_ms - 1, rtt_ms, MAX_FRAME_SIZE as isize);
-            assert_eq!(frames.len(), 0);
+        let mut nonces = Vec::new();
 
-            let frames = ta.emit_frames(now_ms    , rtt_ms, MAX_FRAME_SIZE as isize);
+        for &now_ms in [ 0, rtt_ms ].iter() {
+            let frames = ta.emit_frames(now_ms, rtt_ms, MAX_FRAME_SIZE as isize);
             assert_eq!(frames.len(), 1);
 
-            let frames = ta.emit_frames(now_ms + 1, rtt_ms, MAX_FRAME_SIZE as isize);
-            assert_eq!(frames.len(), 0);
+            match frame::Frame::read(&frames[0]) {
+                Some(frame::Frame::DataFrame(data_frame)) => nonces.push(data_frame.nonce),
+                _ => panic!(),
+            }
         }
+
+        // The resend is acknowledged, but may correspond to the original
+        ta.receive_ack(frame::AckFrame {
+            frame_acks: vec![ frame::AckGroup { base_id: 1, bitfield: 0b1, nonce: nonces[1] } ],
+            frame_window_base_id: 0,
+            packet_window_base_id: 0,
+            health: None,
+            app_latency_ms: None,
+        });
+
+        assert_eq!(ta.hc.frame_queue.get_feedback(2*rtt_ms).unwrap().rtt_ms, None);
+
+        ta.receive_ack(frame::AckFrame {
+            frame_acks: vec![ frame::AckGroup { base_id: 0, bitfield: 0b1, nonce: nonces[0] } ],
+            frame_window_base_id: 0,
+            packet_window_base_id: 0,
+            health: None,
+            app_latency_ms: None,
+        });
+
+        assert_eq!(ta.hc.frame_queue.get_feedback(3*rtt_ms).unwrap().rtt_ms, Some(3*rtt_ms));
     }
 
     // Keepalive syncs should be sent periodically after no data has been sent
@@ -951,7 +2775,7 @@ mod tests {
 
         let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
         assert_eq!(frames.len(), 1);
-        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: None, next_packet_id: None });
+        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: None, next_packet_id: None, heartbeat: None, drop_ranges: Vec::new() });
 
         let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
         assert_eq!(frames.len(), 0);
@@ -960,7 +2784,7 @@ mod tests {
 
         let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
         assert_eq!(frames.len(), 1);
-        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: None, next_packet_id: None });
+        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: None, next_packet_id: None, heartbeat: None, drop_ranges: Vec::new() });
 
         // Disrupt the normal timing
         now_ms += ta.hc.sync_keepalive_interval_ms.unwrap()/2;
@@ -973,15 +2797,130 @@ mod tests {
 
         let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
         assert_eq!(frames.len(), 1);
-        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: Some(1), next_packet_id: Some(1) });
+        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: Some(1), next_packet_id: Some(1), heartbeat: None, drop_ranges: Vec::new() });
 
-        ta.receive_ack(frame::AckFrame { frame_acks: Vec::new(), frame_window_base_id: 1, packet_window_base_id: 1 });
+        ta.receive_ack(frame::AckFrame { frame_acks: Vec::new(), frame_window_base_id: 1, packet_window_base_id: 1, health: None, app_latency_ms: None });
 
         now_ms += ta.hc.sync_keepalive_interval_ms.unwrap();
 
         let frames = ta.emit_frames(now_ms, rtt_ms, 10000);
         assert_eq!(frames.len(), 1);
-        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: None, next_packet_id: None });
+        test_sync_frame(&frames[0], frame::SyncFrame { next_frame_id: None, next_packet_id: None, heartbeat: None, drop_ranges: Vec::new() });
+    }
+
+    // Heartbeat payloads are attached to sync frames, and are only requested when a sync frame is
+    // actually sent
+    #[test]
+    fn keepalive_heartbeat() {
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+
+        let mut ta = TestApparatus::new();
+        let mut requests = 0;
+
+        let mut heartbeat = || {
+            requests += 1;
+            Some(vec![ 1, 2, 3 ].into_boxed_slice())
+        };
+
+        let mut test_sink = TestSink::new();
+        ta.hc.flush_alloc = 10000;
+        ta.hc.emit_frames(0, rtt_ms, 4*rtt_ms, ta.flush_id, &mut test_sink, &mut heartbeat);
+        assert_eq!(test_sink.emitted.len(), 0);
+
+        let now_ms = ta.hc.sync_keepalive_interval_ms.unwrap();
+
+        let mut test_sink = TestSink::new();
+        ta.hc.flush_alloc = 10000;
+        ta.hc.emit_frames(now_ms, rtt_ms, 4*rtt_ms, ta.flush_id, &mut test_sink, &mut heartbeat);
+        assert_eq!(test_sink.emitted.len(), 1);
+        test_sync_frame(&test_sink.emitted[0], frame::SyncFrame {
+            next_frame_id: None,
+            next_packet_id: None,
+            heartbeat: Some(vec![ 1, 2, 3 ].into_boxed_slice()),
+            drop_ranges: Vec::new(),
+        });
+
+        assert_eq!(requests, 1);
+
+        // Oversized payloads are dropped
+        let now_ms = now_ms + ta.hc.sync_keepalive_interval_ms.unwrap();
+
+        let mut test_sink = TestSink::new();
+        ta.hc.flush_alloc = 10000;
+        ta.hc.emit_frames(now_ms, rtt_ms, 4*rtt_ms, ta.flush_id, &mut test_sink,
+                          &mut || Some(vec![ 0; frame::serial::MAX_HEARTBEAT_SIZE + 1 ].into_boxed_slice()));
+        assert_eq!(test_sink.emitted.len(), 1);
+        test_sync_frame(&test_sink.emitted[0], frame::SyncFrame { next_frame_id: None, next_packet_id: None, heartbeat: None, drop_ranges: Vec::new() });
+    }
+
+    // Probes accrue at the probe rate while idle, are sent on the control channel, and are only
+    // sent if control channels were negotiated
+    #[test]
+    fn probe_accrual() {
+        let clock = VirtualClock::new();
+        let probe_rate = (PROBE_SIZE * 10) as u32;
+
+        let mut ta = TestApparatus::new_config(Config {
+            control_channels: true,
+            probe_rate,
+            clock: Arc::new(clock.clone()),
+            .. TestApparatus::default_config()
+        });
+
+        ta.step();
+        assert!(!ta.is_send_pending());
+        assert_eq!(ta.hc.next_timeout_ms(), Some(100));
+
+        clock.advance(std::time::Duration::from_millis(99));
+        ta.step();
+        assert!(!ta.is_send_pending());
+
+        clock.advance(std::time::Duration::from_millis(1));
+        ta.step();
+        assert!(ta.is_send_pending());
+        assert_eq!(ta.hc.link_stats().probe_bytes, PROBE_SIZE as u64);
+
+        let frames = ta.flush();
+        assert_eq!(frames.len(), 1);
+
+        let mut probe_data = vec![ 0; PROBE_SIZE ];
+        probe_data[0] = CONTROL_PROBE;
+
+        let dg0 = Datagram {
+            sequence_id: 0,
+            channel_id: frame::serial::CONTROL_CHANNEL_ID,
+            window_parent_lead: 0,
+            channel_parent_lead: 0,
+            keyed: false,
+            fragment_id: 0,
+            fragment_id_last: 0,
+            data: probe_data.into_boxed_slice(),
+        };
+
+        test_data_frame(&frames[0], 0, vec![ dg0 ]);
+
+        // Probe data does not accrue while application data is waiting to be sent
+        ta.enqueue_packet(vec![ 0; MAX_FRAGMENT_SIZE ].into_boxed_slice(), 0, SendMode::Unreliable);
+
+        clock.advance(std::time::Duration::from_millis(100));
+        ta.step();
+        assert_eq!(ta.hc.link_stats().probe_bytes, PROBE_SIZE as u64);
+        assert_eq!(ta.hc.probe_credit, 0.0);
+
+        // Probes are not sent if the remote endpoint does not support control channels
+        let mut ta = TestApparatus::new_config(Config {
+            probe_rate,
+            clock: Arc::new(clock.clone()),
+            .. TestApparatus::default_config()
+        });
+
+        for _ in 0 .. 10 {
+            clock.advance(std::time::Duration::from_millis(100));
+            ta.step();
+        }
+
+        assert!(!ta.is_send_pending());
+        assert_eq!(ta.hc.link_stats().probe_bytes, 0);
     }
 
     // No two packets in a frame receive window may have the same ID
@@ -1030,6 +2969,8 @@ mod tests {
                 frame_acks: Vec::new(),
                 frame_window_base_id: data_frame.sequence_id,
                 packet_window_base_id: max_id as u32,
+                health: None,
+                app_latency_ms: None,
             });
         }
 
@@ -1037,6 +2978,406 @@ mod tests {
         println!("packet_id::SPAN: {}", packet_id::SPAN);
     }
 
+    // Metrics should be sampled once per sample interval
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_sampling() {
+        let clock = VirtualClock::new();
+
+        let config = Config {
+            tx_frame_window_size: MAX_FRAME_WINDOW_SIZE,
+            rx_frame_window_size: MAX_FRAME_WINDOW_SIZE,
+
+            tx_frame_base_id: 0,
+            rx_frame_base_id: 0,
+
+            tx_packet_window_size: MAX_PACKET_WINDOW_SIZE,
+            rx_packet_window_size: MAX_PACKET_WINDOW_SIZE,
+
+            tx_packet_base_id: 0,
+            rx_packet_base_id: 0,
+
+            tx_bandwidth_limit: 100_000,
+
+            tx_alloc_limit: MAX_FRAGMENT_SIZE * MAX_PACKET_WINDOW_SIZE as usize,
+            rx_alloc_limit: MAX_FRAGMENT_SIZE * MAX_PACKET_WINDOW_SIZE as usize,
+
+            rx_max_packet_size: MAX_PACKET_SIZE,
+
+            keepalive_interval_ms: None,
+
+            stats_sample_interval_ms: Some(50),
+            stats_history_len: 2,
+
+            reorder_tolerance_frames: 3,
+            reorder_tolerance_ms: 0,
+
+            early_retransmit: true,
+            min_rto_ms: 0,
+
+            max_frame_age_ms: 10000,
+
+            fragment_acks: false,
+
+
+            receiver_health: false,
+            app_latency: false,
+            control_channels: false,
+            drop_ranges: false,
+
+            adaptive_frame_size: false,
+
+            deliver_empty_packets: true,
+            receive_order: ReceiveOrder::Sent,
+            channel_weights: Vec::new(),
+            probe_rate: 0,
+            channel_count: CHANNEL_COUNT,
+            frame_overhead: 0,
+
+            clock: Arc::new(clock.clone()),
+        };
+
+        let mut ta = TestApparatus::new_config(config);
+
+        ta.step();
+        ta.step();
+        assert_eq!(ta.hc.stats_history().unwrap().len(), 1);
+
+        for _ in 0 .. 2 {
+            clock.advance(std::time::Duration::from_millis(50));
+            ta.step();
+        }
+
+        let stats_history = ta.hc.stats_history().unwrap();
+        assert_eq!(stats_history.len(), 2);
+        assert_eq!(stats_history.latest().unwrap().time_ms, 100);
+    }
+
+    // Transfers must proceed normally as frame and packet IDs wrap
+    #[test]
+    fn id_wraparound_transfer() {
+        use frame::serial::Serialize;
+
+        use crate::packet_id;
+
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+
+        let config = Config {
+            tx_frame_base_id: u32::MAX - 3,
+            rx_frame_base_id: u32::MAX - 3,
+
+            tx_packet_base_id: packet_id::MASK - 3,
+            rx_packet_base_id: packet_id::MASK - 3,
+
+            .. TestApparatus::default_config()
+        };
+
+        let mut sender = TestApparatus::new_config(config.clone());
+        let mut receiver = TestApparatus::new_config(config);
+
+        let mut sent_packets = Vec::new();
+        let mut now_ms = 0;
+
+        for _ in 0 .. 5 {
+            for _ in 0 .. 10 {
+                let packet_data = (0 .. 1000).map(|_| rand::random::<u8>()).collect::<Vec<u8>>().into_boxed_slice();
+                sender.enqueue_packet(packet_data.clone(), 0, SendMode::Reliable);
+                sent_packets.push(packet_data);
+            }
+
+            for frame_bytes in sender.emit_frames(now_ms, rtt_ms, 100000).iter() {
+                match frame::Frame::read(frame_bytes) {
+                    Some(frame::Frame::DataFrame(data_frame)) => receiver.receive_data(data_frame),
+                    _ => panic!(),
+                }
+            }
+
+            for frame_bytes in receiver.emit_frames(now_ms, rtt_ms, 100000).iter() {
+                match frame::Frame::read(frame_bytes) {
+                    Some(frame::Frame::AckFrame(ack_frame)) => sender.receive_ack(ack_frame),
+                    _ => panic!(),
+                }
+            }
+
+            now_ms += 10;
+        }
+
+        assert_eq!(receiver.receive_packets(), sent_packets);
+
+        // All frames have been acknowledged
+        assert!(sender.hc.frame_queue.next_id() < 100);
+        assert_eq!(sender.hc.frame_queue.base_id(), sender.hc.frame_queue.next_id());
+    }
+
+    // Duplicate frames and datagrams should be counted, and should neither be acknowledged twice nor
+    // delivered twice. Late frames which were never received are not duplicates.
+    #[test]
+    fn duplicate_frames() {
+        use frame::serial::Serialize;
+
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+
+        let mut sender = TestApparatus::new();
+        let mut receiver = TestApparatus::new();
+
+        let read_data_frame = |frame_bytes: &Box<[u8]>| {
+            match frame::Frame::read(frame_bytes) {
+                Some(frame::Frame::DataFrame(data_frame)) => data_frame,
+                _ => panic!(),
+            }
+        };
+
+        let p0: Box<[u8]> = vec![ 0; 100 ].into();
+        let p1: Box<[u8]> = vec![ 1; 100 ].into();
+
+        sender.enqueue_packet(p0.clone(), 0, SendMode::Persistent);
+        let frame_a = read_data_frame(&sender.emit_frames(0, rtt_ms, 100000)[0]);
+
+        sender.enqueue_packet(p1.clone(), 0, SendMode::Reliable);
+        let frame_b = read_data_frame(&sender.emit_frames(1, rtt_ms, 100000)[0]);
+
+        // Frame A is resent in a new frame
+        let frame_a_resend = read_data_frame(&sender.emit_frames(rtt_ms, rtt_ms, 100000)[0]);
+
+        receiver.receive_data(frame_a.clone());
+        receiver.receive_data(frame_a);
+        receiver.receive_data(frame_b);
+        receiver.receive_data(frame_a_resend);
+
+        assert_eq!(receiver.receive_packets(), vec![ p0, p1 ]);
+
+        let stats = receiver.hc.link_stats();
+        assert_eq!(stats.duplicate_frames, 1);
+        assert_eq!(stats.duplicate_datagrams, 1);
+
+        // Three distinct frames are acknowledged once each
+        let frames = receiver.emit_frames(rtt_ms, rtt_ms, 100000);
+        assert_eq!(frames.len(), 1);
+
+        match frame::Frame::read(&frames[0]) {
+            Some(frame::Frame::AckFrame(ack_frame)) => {
+                let ack_count: u32 = ack_frame.frame_acks.iter().map(|group| group.bitfield.count_ones()).sum();
+                assert_eq!(ack_count, 3);
+            }
+            _ => panic!(),
+        }
+
+        // Frame D arrives before frame C, so that C is dropped but not considered a duplicate
+        let p2: Box<[u8]> = vec![ 2; 100 ].into();
+        let p3: Box<[u8]> = vec![ 3; 100 ].into();
+
+        sender.enqueue_packet(p2, 0, SendMode::Unreliable);
+        let frame_c = read_data_frame(&sender.emit_frames(rtt_ms + 1, rtt_ms, 100000)[0]);
+
+        sender.enqueue_packet(p3, 0, SendMode::Unreliable);
+        let frame_d = read_data_frame(&sender.emit_frames(rtt_ms + 2, rtt_ms, 100000)[0]);
+
+        receiver.receive_data(frame_d);
+        receiver.receive_data(frame_c);
+
+        let stats = receiver.hc.link_stats();
+        assert_eq!(stats.duplicate_frames, 1);
+        assert_eq!(stats.duplicate_datagrams, 1);
+    }
+
+    // An acknowledgement should be sent immediately once a gap in the received frame sequence is
+    // detected, but not for frames received in order.
+    #[test]
+    fn urgent_ack_on_gap() {
+        use frame::serial::Serialize;
+
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+
+        let mut sender = TestApparatus::new();
+        let mut receiver = TestApparatus::new();
+
+        let mut data_frames = Vec::new();
+
+        for i in 0 .. 3 {
+            sender.enqueue_packet(vec![ i; 100 ].into(), 0, SendMode::Unreliable);
+
+            match frame::Frame::read(&sender.emit_frames(i as u64, rtt_ms, 100000)[0]) {
+                Some(frame::Frame::DataFrame(data_frame)) => data_frames.push(data_frame),
+                _ => panic!(),
+            }
+        }
+
+        let mut sink = TestSink::new();
+
+        receiver.receive_data(data_frames[0].clone());
+        receiver.hc.flush_urgent_acks(&mut sink);
+        assert_eq!(sink.emitted.len(), 0);
+
+        // Frame 1 is missing
+        receiver.receive_data(data_frames[2].clone());
+        receiver.hc.flush_urgent_acks(&mut sink);
+        assert_eq!(sink.emitted.len(), 1);
+
+        match frame::Frame::read(&sink.emitted[0]) {
+            Some(frame::Frame::AckFrame(ack_frame)) => {
+                assert_eq!(ack_frame.frame_acks, vec![ frame::AckGroup { base_id: 0, bitfield: 0b101, nonce: data_frames[0].nonce ^ data_frames[2].nonce } ]);
+            }
+            _ => panic!(),
+        }
+
+        // Only one acknowledgement is sent per gap
+        receiver.hc.flush_urgent_acks(&mut sink);
+        assert_eq!(sink.emitted.len(), 1);
+    }
+
+    // Frames which could not be sent should not count toward the frame window, and their contents
+    // should be sent in full once sending succeeds.
+    #[test]
+    fn send_failure_retry() {
+        use frame::serial::Serialize;
+
+        struct FailingSink;
+
+        impl FrameSink for FailingSink {
+            fn send(&mut self, _: &[u8]) -> std::io::Result<()> {
+                Err(std::io::ErrorKind::WouldBlock.into())
+            }
+        }
+
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+
+        let mut sender = TestApparatus::new();
+        let mut receiver = TestApparatus::new();
+
+        let packets = (0 .. 4).map(|i| vec![ i; 2000 ].into_boxed_slice()).collect::<Vec<_>>();
+
+        for packet in packets.iter() {
+            sender.enqueue_packet(packet.clone(), 0, SendMode::Reliable);
+        }
+
+        sender.hc.flush_alloc = 100000;
+        sender.hc.emit_frames(0, rtt_ms, 4*rtt_ms, 0, &mut FailingSink, &mut || None);
+
+        assert_eq!(sender.hc.frame_queue.next_id(), 0);
+        assert_eq!(sender.hc.flush_alloc, 100000);
+
+        for frame_bytes in sender.emit_frames(1, rtt_ms, 100000).iter() {
+            match frame::Frame::read(frame_bytes) {
+                Some(frame::Frame::DataFrame(data_frame)) => receiver.receive_data(data_frame),
+                _ => panic!(),
+            }
+        }
+
+        assert_eq!(receiver.receive_packets(), packets);
+
+        // Acknowledgements are retried as well
+        receiver.hc.flush_alloc = 100000;
+        receiver.hc.emit_frames(1, rtt_ms, 4*rtt_ms, 0, &mut FailingSink, &mut || None);
+
+        let frames = receiver.emit_frames(2, rtt_ms, 100000);
+        assert_eq!(frames.len(), 1);
+
+        match frame::Frame::read(&frames[0]) {
+            Some(frame::Frame::AckFrame(ack_frame)) => sender.receive_ack(ack_frame),
+            _ => panic!(),
+        }
+
+        assert_eq!(sender.hc.frame_queue.base_id(), sender.hc.frame_queue.next_id());
+    }
+
+    // Flushing acknowledgements alone should send a single acknowledgement even if the allocation
+    // has been exhausted, without adding to the deficit, and report its delay.
+    #[test]
+    fn flush_acks() {
+        use frame::serial::Serialize;
+
+        let rtt_ms = INITIAL_RTT_ESTIMATE_MS;
+
+        let mut sender = TestApparatus::new();
+        let mut receiver = TestApparatus::new();
+
+        sender.enqueue_packet(vec![ 0; 100 ].into_boxed_slice(), 0, SendMode::Reliable);
+
+        for frame_bytes in sender.emit_frames(0, rtt_ms, 100000).iter() {
+            match frame::Frame::read(frame_bytes) {
+                Some(frame::Frame::DataFrame(data_frame)) => receiver.receive_data(data_frame),
+                _ => panic!(),
+            }
+        }
+
+        assert_eq!(receiver.hc.link_stats().ack_delay_ms, None);
+
+        receiver.enqueue_packet(vec![ 0; 100 ].into_boxed_slice(), 0, SendMode::Reliable);
+        receiver.hc.flush_alloc = -500;
+
+        let mut sink = TestSink::new();
+        receiver.hc.flush_acks(&mut sink);
+
+        assert_eq!(sink.emitted.len(), 1);
+        assert_eq!(receiver.hc.flush_alloc, -500);
+
+        match frame::Frame::read(&sink.emitted[0]) {
+            Some(frame::Frame::AckFrame(ack_frame)) => sender.receive_ack(ack_frame),
+            _ => panic!(),
+        }
+
+        assert_eq!(sender.hc.frame_queue.base_id(), sender.hc.frame_queue.next_id());
+        assert_eq!(receiver.hc.link_stats().ack_delay_ms, Some(0));
+        assert_eq!(receiver.hc.link_stats().max_ack_delay_ms, 0);
+
+        // Nothing further is sent while nothing remains to be acknowledged
+        let mut sink = TestSink::new();
+        receiver.hc.flush_acks(&mut sink);
+        assert!(sink.emitted.is_empty());
+    }
+
+    // A flush which fails because the sink would block should be reported, but other send errors
+    // should not.
+    #[test]
+    fn send_blocked() {
+        struct ErrorSink(std::io::ErrorKind);
+
+        impl FrameSink for ErrorSink {
+            fn send(&mut self, _: &[u8]) -> std::io::Result<()> {
+                Err(self.0.into())
+            }
+        }
+
+        let mut ta = TestApparatus::new();
+
+        ta.enqueue_packet(vec![ 0; 100 ].into_boxed_slice(), 0, SendMode::Reliable);
+        ta.hc.flush_alloc = 100000;
+
+        ta.hc.flush(&mut ErrorSink(std::io::ErrorKind::WouldBlock));
+        assert!(ta.hc.is_send_blocked());
+        assert_eq!(ta.hc.link_stats().send_blocked_count, 1);
+
+        ta.hc.flush(&mut ErrorSink(std::io::ErrorKind::ConnectionRefused));
+        assert!(!ta.hc.is_send_blocked());
+        assert_eq!(ta.hc.link_stats().send_blocked_count, 1);
+
+        ta.hc.flush(&mut ErrorSink(std::io::ErrorKind::WouldBlock));
+        assert!(ta.hc.is_send_blocked());
+        assert_eq!(ta.hc.link_stats().send_blocked_count, 2);
+
+        let mut sink = TestSink::new();
+        ta.hc.flush(&mut sink);
+        assert!(!ta.hc.is_send_blocked());
+        assert_eq!(sink.emitted.len(), 1);
+    }
+
+    // Processing time is only reported if the corresponding feature is enabled
+    #[test]
+    fn processing_time() {
+        let mut ta = TestApparatus::new();
+
+        ta.step();
+        ta.flush();
+
+        let processing_time = ta.hc.link_stats().processing_time;
+
+        if cfg!(feature = "cpu-accounting") {
+            assert!(processing_time.unwrap() > std::time::Duration::ZERO);
+        } else {
+            assert_eq!(processing_time, None);
+        }
+    }
+
     fn bandwidth_trial(send_rate: u32) {
         use frame::serial::Serialize;
 
@@ -1064,7 +3405,41 @@ mod tests {
             tx_alloc_limit: packet_size,
             rx_alloc_limit: packet_size,
 
+            rx_max_packet_size: MAX_PACKET_SIZE,
+
             keepalive_interval_ms: None,
+
+            #[cfg(feature = "stats")]
+            stats_sample_interval_ms: None,
+            #[cfg(feature = "stats")]
+            stats_history_len: 1,
+
+            reorder_tolerance_frames: 3,
+            reorder_tolerance_ms: 0,
+
+            early_retransmit: true,
+            min_rto_ms: 0,
+
+            max_frame_age_ms: 10000,
+
+            fragment_acks: false,
+
+
+            receiver_health: false,
+            app_latency: false,
+            control_channels: false,
+            drop_ranges: false,
+
+            adaptive_frame_size: false,
+
+            deliver_empty_packets: true,
+            receive_order: ReceiveOrder::Sent,
+            channel_weights: Vec::new(),
+            probe_rate: 0,
+            channel_count: CHANNEL_COUNT,
+            frame_overhead: 0,
+
+            clock: Arc::new(SystemClock::new()),
         };
 
         let mut sender = TestApparatus::new_config(config.clone());
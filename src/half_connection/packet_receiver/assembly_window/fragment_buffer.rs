@@ -44,6 +44,21 @@ impl FragmentBuffer {
         }
     }
 
+    pub fn contains(&self, idx: usize) -> bool {
+        debug_assert!(idx < self.num_fragments);
+
+        self.fragment_bitfields[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    // Returns a bitfield of the received fragments starting at the given index, which must be a
+    // multiple of 32
+    pub fn bitfield(&self, base_idx: usize) -> u32 {
+        debug_assert!(base_idx < self.num_fragments);
+        debug_assert!(base_idx & 31 == 0);
+
+        (self.fragment_bitfields[base_idx / 64] >> (base_idx % 64)) as u32
+    }
+
     pub fn finalize(mut self) -> Box<[u8]> {
         debug_assert!(self.total_size <= self.buffer.len());
         let ptr = self.buffer.as_mut_ptr();
@@ -1,5 +1,6 @@
 
 use super::MAX_FRAGMENT_SIZE;
+use super::MAX_PACKET_SIZE;
 use super::MAX_PACKET_WINDOW_SIZE;
 
 use crate::frame;
@@ -14,6 +15,7 @@ struct ActiveEntry {
     channel_id: u8,
     window_parent_lead: u16,
     channel_parent_lead: u16,
+    keyed: bool,
     last_fragment_id: u16,
 
     // Assembles a multi-fragment packet
@@ -21,13 +23,14 @@ struct ActiveEntry {
 }
 
 impl ActiveEntry {
-    fn new(alloc_size: usize, channel_id: u8, window_parent_lead: u16, channel_parent_lead: u16, last_fragment_id: u16, num_fragments: usize) -> Self {
+    fn new(alloc_size: usize, channel_id: u8, window_parent_lead: u16, channel_parent_lead: u16, keyed: bool, last_fragment_id: u16, num_fragments: usize) -> Self {
         Self {
             alloc_size,
 
             channel_id,
             window_parent_lead,
             channel_parent_lead,
+            keyed,
             last_fragment_id,
 
             asm_buffer: fragment_buffer::FragmentBuffer::new(num_fragments),
@@ -47,6 +50,7 @@ pub struct Packet {
     pub sequence_id: u32,
     pub window_parent_lead: u16,
     pub channel_parent_lead: u16,
+    pub keyed: bool,
     pub data: Option<Box<[u8]>>,
 }
 
@@ -59,11 +63,37 @@ fn packet_alloc_size(datagram: &frame::Datagram) -> usize {
     }
 }
 
+// The smallest size of the packet to which the given datagram belongs. Only the final fragment may
+// be smaller than the maximum fragment size, and it may not be empty.
+fn min_packet_size(datagram: &frame::Datagram) -> usize {
+    let last_size = if datagram.fragment_id == datagram.fragment_id_last {
+        datagram.data.len()
+    } else {
+        1
+    };
+    datagram.fragment_id_last as usize * MAX_FRAGMENT_SIZE + last_size
+}
+
+fn dud_packet(datagram: &frame::Datagram) -> Packet {
+    Packet {
+        channel_id: datagram.channel_id,
+        sequence_id: datagram.sequence_id,
+        window_parent_lead: datagram.window_parent_lead,
+        channel_parent_lead: datagram.channel_parent_lead,
+        keyed: datagram.keyed,
+        data: None
+    }
+}
+
 pub struct AssemblyWindow {
     window: Box<[WindowEntry]>,
 
     alloc: usize,
     max_alloc: usize,
+
+    // Largest packet the sender may send, and the number of datagrams which implied a larger one
+    max_packet_size: usize,
+    oversize_count: u64,
 }
 
 impl AssemblyWindow {
@@ -77,18 +107,45 @@ impl AssemblyWindow {
 
             alloc: 0,
             max_alloc: max_alloc_ceil,
+
+            max_packet_size: MAX_PACKET_SIZE,
+            oversize_count: 0,
         }
     }
 
+    // Sets the size of the largest packet which will be assembled. Larger packets are rejected as
+    // soon as any of their datagrams implies their size.
+    pub fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.max_packet_size = max_packet_size;
+    }
+
+    // Returns the number of packets which were rejected for exceeding the maximum packet size
+    pub fn oversize_count(&self) -> u64 {
+        self.oversize_count
+    }
+
+    // Returns the number of bytes allocated for packets which have not yet been delivered, and the
+    // maximum allocation
+    pub fn alloc(&self) -> (usize, usize) {
+        (self.alloc, self.max_alloc)
+    }
+
     pub fn try_add(&mut self, idx: usize, datagram: frame::Datagram) -> Option<Packet> {
         match self.window[idx] {
             WindowEntry::Open => {
                 // New packet
 
-                // If this packet would exceed the memory limit, enqueue a closed entry with no
-                // allocation value and pass on a dud packet (as if the packet was received in
-                // full). Otherwise, pass the datagram on directly or allocate an assembly
-                // buffer as appropriate.
+                // If this packet is larger than the sender could have sent, or would exceed the
+                // memory limit, enqueue a closed entry with no allocation value and pass on a dud
+                // packet (as if the packet was received in full). Otherwise, pass the datagram on
+                // directly or allocate an assembly buffer as appropriate.
+
+                if min_packet_size(&datagram) > self.max_packet_size {
+                    self.oversize_count += 1;
+                    self.window[idx] = WindowEntry::Closed(0);
+
+                    return Some(dud_packet(&datagram));
+                }
 
                 let alloc_size = packet_alloc_size(&datagram);
 
@@ -96,13 +153,7 @@ impl AssemblyWindow {
                     // Never should have come here!
                     self.window[idx] = WindowEntry::Closed(0);
 
-                    return Some(Packet {
-                        channel_id: datagram.channel_id,
-                        sequence_id: datagram.sequence_id,
-                        window_parent_lead: datagram.window_parent_lead,
-                        channel_parent_lead: datagram.channel_parent_lead,
-                        data: None
-                    });
+                    return Some(dud_packet(&datagram));
                 } else {
                     self.alloc += alloc_size;
 
@@ -116,6 +167,7 @@ impl AssemblyWindow {
                             sequence_id: datagram.sequence_id,
                             window_parent_lead: datagram.window_parent_lead,
                             channel_parent_lead: datagram.channel_parent_lead,
+                            keyed: datagram.keyed,
                             data: Some(datagram.data),
                         });
                     } else {
@@ -125,6 +177,7 @@ impl AssemblyWindow {
                                                              datagram.channel_id,
                                                              datagram.window_parent_lead,
                                                              datagram.channel_parent_lead,
+                                                             datagram.keyed,
                                                              datagram.fragment_id_last,
                                                              num_fragments);
 
@@ -153,10 +206,22 @@ impl AssemblyWindow {
                 if datagram.channel_parent_lead != entry.channel_parent_lead {
                     return None;
                 }
+                if datagram.keyed != entry.keyed {
+                    return None;
+                }
                 if datagram.fragment_id_last != entry.last_fragment_id {
                     return None;
                 }
 
+                // The final fragment determines the exact size of the packet
+                if min_packet_size(&datagram) > self.max_packet_size {
+                    self.oversize_count += 1;
+                    self.alloc -= entry.alloc_size;
+                    self.window[idx] = WindowEntry::Closed(0);
+
+                    return Some(dud_packet(&datagram));
+                }
+
                 entry.asm_buffer.write(datagram.fragment_id as usize, datagram.data);
 
                 if entry.asm_buffer.is_finished() {
@@ -170,6 +235,7 @@ impl AssemblyWindow {
                                 sequence_id: datagram.sequence_id,
                                 window_parent_lead: datagram.window_parent_lead,
                                 channel_parent_lead: datagram.channel_parent_lead,
+                                keyed: datagram.keyed,
                                 data: Some(entry.asm_buffer.finalize()),
                             });
                         }
@@ -182,6 +248,39 @@ impl AssemblyWindow {
         }
     }
 
+    // Returns true if the given datagram has already been received, or its packet has already been
+    // produced
+    pub fn contains(&self, idx: usize, datagram: &frame::Datagram) -> bool {
+        match self.window[idx] {
+            WindowEntry::Open => false,
+            WindowEntry::Closed(_) => true,
+            WindowEntry::Active(ref entry) => {
+                datagram.fragment_id_last == entry.last_fragment_id &&
+                entry.asm_buffer.contains(datagram.fragment_id as usize)
+            }
+        }
+    }
+
+    // Returns a bitfield of the received fragments of the packet at the given index, starting at
+    // the given fragment ID, or None if no such packet is being received. A packet which has been
+    // produced is reported as received in full.
+    pub fn fragment_bitfield(&self, idx: usize, base_fragment_id: u16, last_fragment_id: u16) -> Option<u32> {
+        match self.window[idx] {
+            WindowEntry::Open => None,
+            WindowEntry::Closed(_) => {
+                let count = (last_fragment_id - base_fragment_id) as u32 + 1;
+                Some(if count >= 32 { u32::MAX } else { (1 << count) - 1 })
+            }
+            WindowEntry::Active(ref entry) => {
+                if entry.last_fragment_id == last_fragment_id {
+                    Some(entry.asm_buffer.bitfield(base_fragment_id as usize))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
     pub fn clear(&mut self, idx: usize) {
         match self.window[idx] {
             WindowEntry::Open => {
@@ -217,6 +316,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 0,
             data: packet_data.clone(),
@@ -227,6 +327,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             data: Some(packet_data),
         });
     }
@@ -245,6 +346,7 @@ mod tests {
                 channel_id: 0,
                 window_parent_lead: 0,
                 channel_parent_lead: 0,
+                keyed: false,
                 fragment_id: i as u16,
                 fragment_id_last: 4,
                 data: fragment_data,
@@ -256,6 +358,7 @@ mod tests {
                     channel_id: 0,
                     window_parent_lead: 0,
                     channel_parent_lead: 0,
+                    keyed: false,
                     data: Some(packet_data),
                 });
 
@@ -266,6 +369,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn fragment_bitfield() {
+        let mut window = AssemblyWindow::new(100000);
+
+        let fragment_datagram = |fragment_id: u16| Datagram {
+            sequence_id: 0,
+            channel_id: 0,
+            window_parent_lead: 0,
+            channel_parent_lead: 0,
+            keyed: false,
+            fragment_id,
+            fragment_id_last: 39,
+            data: vec![0; MAX_FRAGMENT_SIZE].into_boxed_slice(),
+        };
+
+        assert_eq!(window.fragment_bitfield(0, 0, 39), None);
+
+        for &i in [ 0, 2, 31, 32, 35 ].iter() {
+            assert_eq!(window.try_add(0, fragment_datagram(i)), None);
+        }
+
+        assert_eq!(window.fragment_bitfield(0, 0, 39), Some(0x80000005));
+        assert_eq!(window.fragment_bitfield(0, 32, 39), Some(0x00000009));
+
+        // Mismatched packet
+        assert_eq!(window.fragment_bitfield(0, 0, 40), None);
+
+        for i in 0 .. 40 {
+            let _ = window.try_add(0, fragment_datagram(i));
+        }
+
+        // Completed packets are reported in full
+        assert_eq!(window.fragment_bitfield(0, 0, 39), Some(0xFFFFFFFF));
+        assert_eq!(window.fragment_bitfield(0, 32, 39), Some(0x000000FF));
+    }
+
     #[test]
     fn alloc_exceeded() {
         let packet_0_size = 100;
@@ -281,6 +420,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 0,
             data: packet_0_data.clone(),
@@ -291,6 +431,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             data: Some(packet_0_data.clone()),
         });
 
@@ -300,6 +441,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 0,
             data: vec![].into_boxed_slice(),
@@ -310,6 +452,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             data: Some(vec![].into_boxed_slice()),
         });
 
@@ -320,6 +463,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 1,
             data: packet_2_data[ .. MAX_FRAGMENT_SIZE].into(),
@@ -330,6 +474,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             data: None,
         });
 
@@ -338,6 +483,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 1,
             fragment_id_last: 1,
             data: packet_2_data[MAX_FRAGMENT_SIZE .. ].into(),
@@ -355,6 +501,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 0,
             data: packet_0_data.clone(),
@@ -365,10 +512,62 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             data: Some(packet_0_data.clone()),
         });
     }
 
+    #[test]
+    fn oversized_packets() {
+        let mut window = AssemblyWindow::new(10*MAX_FRAGMENT_SIZE);
+        window.set_max_packet_size(2*MAX_FRAGMENT_SIZE + 10);
+
+        let fragment = |fragment_id: u16, fragment_id_last: u16, size: usize| Datagram {
+            sequence_id: 0,
+            channel_id: 0,
+            window_parent_lead: 0,
+            channel_parent_lead: 0,
+            keyed: false,
+            fragment_id,
+            fragment_id_last,
+            data: vec![0; size].into_boxed_slice(),
+        };
+
+        let dud = Packet {
+            sequence_id: 0,
+            channel_id: 0,
+            window_parent_lead: 0,
+            channel_parent_lead: 0,
+            keyed: false,
+            data: None,
+        };
+
+        // Too many fragments, as implied by the first fragment received
+        assert_eq!(window.try_add(0, fragment(0, 3, MAX_FRAGMENT_SIZE)), Some(dud));
+        assert_eq!(window.oversize_count(), 1);
+        assert_eq!(window.alloc, 0);
+
+        // Further fragments of the rejected packet are ignored
+        assert_eq!(window.try_add(0, fragment(1, 3, MAX_FRAGMENT_SIZE)), None);
+        assert_eq!(window.oversize_count(), 1);
+
+        // An oversized final fragment is only detected once it arrives
+        assert_eq!(window.try_add(1, fragment(0, 2, MAX_FRAGMENT_SIZE)), None);
+        assert_eq!(window.try_add(1, fragment(1, 2, MAX_FRAGMENT_SIZE)), None);
+        assert_eq!(window.alloc, 3*MAX_FRAGMENT_SIZE);
+
+        assert_eq!(window.try_add(1, fragment(2, 2, 11)).unwrap().data, None);
+        assert_eq!(window.oversize_count(), 2);
+        assert_eq!(window.alloc, 0);
+
+        // A packet of exactly the maximum size is assembled
+        assert_eq!(window.try_add(2, fragment(2, 2, 10)), None);
+        assert_eq!(window.try_add(2, fragment(0, 2, MAX_FRAGMENT_SIZE)), None);
+        let packet = window.try_add(2, fragment(1, 2, MAX_FRAGMENT_SIZE)).unwrap();
+        assert_eq!(packet.data.unwrap().len(), 2*MAX_FRAGMENT_SIZE + 10);
+        assert_eq!(window.oversize_count(), 2);
+    }
+
     #[test]
     fn alloc_counter() {
         use super::packet_alloc_size;
@@ -397,6 +596,7 @@ mod tests {
                 channel_id: 0,
                 window_parent_lead: 0,
                 channel_parent_lead: 0,
+                keyed: false,
                 fragment_id: 0,
                 fragment_id_last: (num_fragments - 1) as u16,
                 data: fragment_0_data,
@@ -569,6 +769,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 1,
             data: packet_data[ .. MAX_FRAGMENT_SIZE].into(),
@@ -582,6 +783,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 1,
             data: packet_data[ .. MAX_FRAGMENT_SIZE].into(),
@@ -595,6 +797,7 @@ mod tests {
             channel_id: 1,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 1,
             fragment_id_last: 1,
             data: packet_data[MAX_FRAGMENT_SIZE .. ].into(),
@@ -608,6 +811,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 1,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 1,
             fragment_id_last: 1,
             data: packet_data[MAX_FRAGMENT_SIZE .. ].into(),
@@ -621,6 +825,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 1,
+            keyed: false,
             fragment_id: 1,
             fragment_id_last: 1,
             data: packet_data[MAX_FRAGMENT_SIZE .. ].into(),
@@ -634,6 +839,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 1,
             fragment_id_last: 2,
             data: packet_data[MAX_FRAGMENT_SIZE .. ].into(),
@@ -647,6 +853,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             fragment_id: 1,
             fragment_id_last: 1,
             data: packet_data[MAX_FRAGMENT_SIZE .. ].into(),
@@ -657,6 +864,7 @@ mod tests {
             channel_id: 0,
             window_parent_lead: 0,
             channel_parent_lead: 0,
+            keyed: false,
             data: Some(packet_data),
         });
     }
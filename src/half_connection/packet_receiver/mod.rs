@@ -1,16 +1,25 @@
 
 use super::PacketSink;
+use super::channel_array::ChannelArray;
+use super::channel_mask::ChannelMask;
 
 use crate::frame;
-use crate::CHANNEL_COUNT;
 use crate::MAX_FRAGMENT_SIZE;
+use crate::MAX_PACKET_SIZE;
 use crate::MAX_PACKET_WINDOW_SIZE;
 use crate::packet_id;
+use crate::ReceiveOrder;
+use crate::ReorderLimits;
+use crate::ReorderMode;
+
+use std::collections::HashMap;
 
 mod assembly_window;
 
-pub fn datagram_is_valid(dg: &frame::Datagram) -> bool {
-    if dg.channel_id as usize >= CHANNEL_COUNT {
+// Returns true if the given datagram is well-formed, and belongs to one of the given number of
+// application channels or to the control channel
+pub fn datagram_is_valid(dg: &frame::Datagram, channel_count: usize) -> bool {
+    if dg.channel_id as usize >= channel_count && dg.channel_id != frame::serial::CONTROL_CHANNEL_ID {
         return false;
     }
     if dg.channel_parent_lead != 0 {
@@ -24,6 +33,10 @@ pub fn datagram_is_valid(dg: &frame::Datagram) -> bool {
     if dg.fragment_id < dg.fragment_id_last && dg.data.len() != MAX_FRAGMENT_SIZE {
         return false;
     }
+    if dg.fragment_id_last > 0 && dg.data.is_empty() {
+        // Only a zero-length packet may be sent as an empty datagram
+        return false;
+    }
     if dg.data.len() > MAX_FRAGMENT_SIZE {
         return false;
     }
@@ -33,6 +46,8 @@ pub fn datagram_is_valid(dg: &frame::Datagram) -> bool {
 struct ChannelAdvEntry {
     channel_id: u8,
     channel_parent_lead: u16,
+    // Whether this packet is ordered by key rather than by channel
+    keyed: bool,
 }
 
 struct WindowAdvEntry {
@@ -41,6 +56,17 @@ struct WindowAdvEntry {
 
 struct DataEntry {
     data: Option<Box<[u8]>>,
+    // Time at which the packet was completed
+    arrival_ms: u64,
+    // Number of packets completed before this one
+    arrival_index: u64,
+}
+
+// A packet delivered by a single call to receive(), before being passed to the packet sink
+struct Delivery {
+    channel_id: u8,
+    arrival_index: u64,
+    data: Box<[u8]>,
 }
 
 struct Channel {
@@ -50,6 +76,20 @@ struct Channel {
     // channel's base ID is the same as that of the receive window.
     base_id: Option<u32>,
     packet_count: u32,
+    // Sequence ID of the newest packet received on this channel
+    newest_id: Option<u32>,
+    // Number of packets delivered from this channel
+    delivered_count: u64,
+    // Number of packets which were received after a newer packet on this channel
+    out_of_order_count: u64,
+    // Limits applied to packets awaiting their predecessors, if any
+    reorder_limits: Option<ReorderLimits>,
+    // Sequence ID of the packet for which a stall was most recently counted
+    stall_id: Option<u32>,
+    // Number of times the reorder limits were exceeded in strict mode
+    stall_count: u64,
+    // Number of times missing packets were skipped in lossy mode
+    skip_count: u64,
 }
 
 impl Channel {
@@ -57,6 +97,99 @@ impl Channel {
         Self {
             base_id: None,
             packet_count: 0,
+            newest_id: None,
+            delivered_count: 0,
+            out_of_order_count: 0,
+            reorder_limits: None,
+            stall_id: None,
+            stall_count: 0,
+            skip_count: 0,
+        }
+    }
+
+    fn is_lossy(&self) -> bool {
+        self.reorder_limits.is_some_and(|limits| limits.mode == ReorderMode::Lossy)
+    }
+
+    // Returns true if a packet which arrived at the given time and awaits a predecessor has
+    // exceeded this channel's reorder limits
+    fn limits_exceeded(&self, arrival_ms: u64, now_ms: u64) -> bool {
+        match self.reorder_limits {
+            Some(limits) => {
+                limits.max_packets.is_some_and(|max_packets| self.packet_count > max_packets) ||
+                    limits.max_hold_ms.is_some_and(|max_hold_ms| now_ms.saturating_sub(arrival_ms) >= max_hold_ms)
+            }
+            None => false,
+        }
+    }
+}
+
+// Per-channel state of each application channel, followed by that of the control channel, indexed
+// by channel ID
+struct Channels<const N: usize> {
+    application: ChannelArray<Channel, N>,
+    control: Channel,
+}
+
+impl<const N: usize> Channels<N> {
+    fn new(channel_count: usize) -> Self {
+        Self {
+            application: ChannelArray::new(channel_count, Channel::new),
+            control: Channel::new(),
+        }
+    }
+
+    // Returns the number of channels, including the control channel
+    fn len(&self) -> usize {
+        self.application.len() + 1
+    }
+
+    // Returns the position of the given channel in the order above
+    fn slot(&self, channel_id: u8) -> usize {
+        if channel_id == frame::serial::CONTROL_CHANNEL_ID {
+            self.application.len()
+        } else {
+            channel_id as usize
+        }
+    }
+
+    // Returns the ID of the channel at the given position in the order above
+    fn slot_id(&self, slot: usize) -> u8 {
+        if slot == self.application.len() {
+            frame::serial::CONTROL_CHANNEL_ID
+        } else {
+            slot as u8
+        }
+    }
+
+    // Returns each channel along with its ID
+    fn enumerate(&self) -> impl Iterator<Item = (usize, &Channel)> {
+        self.application.iter().enumerate().chain(std::iter::once((frame::serial::CONTROL_CHANNEL_ID as usize, &self.control)))
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Channel> {
+        self.application.iter_mut().chain(std::iter::once(&mut self.control))
+    }
+}
+
+impl<const N: usize> std::ops::Index<usize> for Channels<N> {
+    type Output = Channel;
+
+    fn index(&self, channel_idx: usize) -> &Channel {
+        if channel_idx == frame::serial::CONTROL_CHANNEL_ID as usize {
+            &self.control
+        } else {
+            &self.application[channel_idx]
+        }
+    }
+}
+
+impl<const N: usize> std::ops::IndexMut<usize> for Channels<N> {
+    fn index_mut(&mut self, channel_idx: usize) -> &mut Channel {
+        if channel_idx == frame::serial::CONTROL_CHANNEL_ID as usize {
+            &mut self.control
+        } else {
+            &mut self.application[channel_idx]
         }
     }
 }
@@ -67,7 +200,7 @@ macro_rules! window_index {
     };
 }
 
-pub struct PacketReceiver {
+pub struct PacketReceiver<const CHANNELS: usize = 0> {
     base_id: u32,
     end_id: u32,
 
@@ -83,15 +216,44 @@ pub struct PacketReceiver {
     entry_flags: Box<[u64]>,
     data_flags: Box<[u64]>,
 
-    channels: Box<[Channel]>,
-    channel_base_markers: Box<[Option<u8>]>,
+    channels: Channels<CHANNELS>,
+    // Bitmask of channels whose base ID is each sequence ID, indexed by channel slot, with
+    // base_marker_words words per sequence ID
+    channel_base_markers: Box<[u64]>,
+    base_marker_words: usize,
 
-    channel_ready_flags: u64,
+    channel_ready_flags: ChannelMask,
+    // Channels whose packets are withheld from the packet sink
+    channel_paused_flags: ChannelMask,
     window_ready_flag: bool,
+    // Set if a keyed packet has arrived since packets were last received
+    keyed_ready_flag: bool,
+
+    delivered_size: u64,
+    delivered_count: u64,
+    duplicate_datagram_count: u64,
+
+    // Whether zero-length packets are passed to the packet sink
+    deliver_empty_packets: bool,
+    // Order in which the packets delivered by each call to receive() are passed to the packet sink
+    receive_order: ReceiveOrder,
+    // Number of packets completed so far
+    arrival_count: u64,
+
+    // Whether fragment acknowledgements are recorded
+    fragment_acks: bool,
+    // Sequence ID, base fragment ID, and last fragment ID of each group of multi-fragment packet
+    // fragments which has been received since fragment acknowledgements were last taken
+    pending_fragment_acks: Vec<(u32, u16, u16)>,
+
+    // Whether packets on control channels are withheld from the packet sink
+    control_channels: bool,
+    // Control packets which have been received since last taken
+    control_packets: Vec<(u8, Box<[u8]>)>,
 }
 
-impl PacketReceiver {
-    pub fn new(window_size: u32, base_id: u32, max_alloc: usize) -> Self {
+impl<const CHANNELS: usize> PacketReceiver<CHANNELS> {
+    pub fn new(window_size: u32, base_id: u32, max_alloc: usize, channel_count: usize) -> Self {
         debug_assert!(window_size > 0);
         debug_assert!(window_size <= MAX_PACKET_WINDOW_SIZE);
         debug_assert!(window_size & (window_size - 1) == 0);
@@ -99,17 +261,19 @@ impl PacketReceiver {
         debug_assert!(packet_id::is_valid(base_id));
 
         let channel_entries: Vec<ChannelAdvEntry> =
-            (0 .. window_size).map(|_| ChannelAdvEntry { channel_id: 0, channel_parent_lead: 0 }).collect();
+            (0 .. window_size).map(|_| ChannelAdvEntry { channel_id: 0, channel_parent_lead: 0, keyed: false }).collect();
         let window_entries: Vec<WindowAdvEntry> =
             (0 .. window_size).map(|_| WindowAdvEntry { window_parent_lead: 0 }).collect();
         let data_entries: Vec<DataEntry> =
-            (0 .. window_size).map(|_| DataEntry { data: None }).collect();
+            (0 .. window_size).map(|_| DataEntry { data: None, arrival_ms: 0, arrival_index: 0 }).collect();
 
         let entry_flags = vec![0u64; (window_size as usize + 63)/64].into_boxed_slice();
         let data_flags = vec![0u64; (window_size as usize + 63)/64].into_boxed_slice();
 
-        let channels: Vec<Channel> = (0 .. CHANNEL_COUNT).map(|_| Channel::new()).collect();
-        let channel_base_markers: Vec<Option<u8>> = (0 .. window_size).map(|_| None).collect();
+        let channels = Channels::new(channel_count);
+
+        let base_marker_words = channels.len().div_ceil(64);
+        let channel_base_markers = vec![0u64; window_size as usize * base_marker_words].into_boxed_slice();
 
         Self {
             base_id: base_id,
@@ -127,11 +291,28 @@ impl PacketReceiver {
             entry_flags,
             data_flags,
 
-            channels: channels.into_boxed_slice(),
-            channel_base_markers: channel_base_markers.into_boxed_slice(),
+            channels,
+            channel_base_markers,
+            base_marker_words,
 
-            channel_ready_flags: 0,
+            channel_ready_flags: ChannelMask::new(),
+            channel_paused_flags: ChannelMask::new(),
             window_ready_flag: false,
+            keyed_ready_flag: false,
+
+            delivered_size: 0,
+            delivered_count: 0,
+            duplicate_datagram_count: 0,
+
+            deliver_empty_packets: true,
+            receive_order: ReceiveOrder::Sent,
+            arrival_count: 0,
+
+            fragment_acks: false,
+            pending_fragment_acks: Vec::new(),
+
+            control_channels: false,
+            control_packets: Vec::new(),
         }
     }
 
@@ -139,12 +320,147 @@ impl PacketReceiver {
         self.base_id
     }
 
-    pub fn handle_datagram(&mut self, datagram: frame::Datagram) {
+    // Returns the total size of all packets delivered, in bytes
+    pub fn delivered_size(&self) -> u64 {
+        self.delivered_size
+    }
+
+    // Returns the total number of packets delivered
+    pub fn delivered_count(&self) -> u64 {
+        self.delivered_count
+    }
+
+    // Returns the number of datagrams received which had already been received or delivered
+    pub fn duplicate_datagram_count(&self) -> u64 {
+        self.duplicate_datagram_count
+    }
+
+    // Returns a snapshot of the receive buffer's occupancy. The receiver is considered backlogged if
+    // more than half of its allocation is held by packets which have not been delivered.
+    pub fn health(&self) -> frame::ReceiverHealth {
+        let (alloc, max_alloc) = self.assembly_window.alloc();
+
+        frame::ReceiverHealth {
+            buffered_bytes: alloc.min(u32::MAX as usize) as u32,
+            backlogged: alloc > max_alloc / 2,
+        }
+    }
+
+    // Returns the number of packets rejected for exceeding the maximum packet size
+    pub fn oversized_packet_count(&self) -> u64 {
+        self.assembly_window.oversize_count()
+    }
+
+    // Sets the size of the largest packet which will be accepted. Larger packets are skipped, as
+    // though they had been received but not delivered.
+    pub fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        self.assembly_window.set_max_packet_size(max_packet_size);
+    }
+
+    // Returns the number of packets delivered, and the number of packets received out of order,
+    // respectively, on the given channel.
+    pub fn channel_counts(&self, channel_id: u8) -> (u64, u64) {
+        let channel = &self.channels[channel_id as usize];
+        (channel.delivered_count, channel.out_of_order_count)
+    }
+
+    // Returns the number of times the given channel's reorder limits were exceeded in strict mode,
+    // and the number of times missing packets were skipped in lossy mode, respectively.
+    pub fn reorder_counts(&self, channel_id: u8) -> (u64, u64) {
+        let channel = &self.channels[channel_id as usize];
+        (channel.stall_count, channel.skip_count)
+    }
+
+    // Returns the total number of times any channel's reorder limits were exceeded, whether the
+    // channel stalled or skipped missing packets as a result.
+    pub fn total_stall_count(&self) -> u64 {
+        self.channels.enumerate().map(|(_, channel)| channel.stall_count + channel.skip_count).sum()
+    }
+
+    // Withholds packets on the given channel from the packet sink until resumed. Withheld packets
+    // remain in the receive window, which cannot advance past them.
+    pub fn set_paused(&mut self, channel_id: u8, paused: bool) {
+        if paused {
+            self.channel_paused_flags.insert(channel_id);
+        } else if self.channel_paused_flags.contains(channel_id) {
+            self.channel_paused_flags.remove(channel_id);
+
+            // Reconsider any packets which were withheld
+            self.channel_ready_flags.insert(channel_id);
+            self.keyed_ready_flag = true;
+            self.window_ready_flag = true;
+        }
+    }
+
+    pub fn set_reorder_limits(&mut self, channel_id: u8, limits: Option<ReorderLimits>) {
+        self.channels[channel_id as usize].reorder_limits = limits;
+    }
+
+    // Causes packets received on control channels to be withheld from the packet sink, to be
+    // taken via take_control_packets() instead
+    pub fn enable_control_channels(&mut self) {
+        self.control_channels = true;
+    }
+
+    // Returns the channel ID and data of each control packet delivered since last taken
+    pub fn take_control_packets(&mut self) -> Vec<(u8, Box<[u8]>)> {
+        std::mem::take(&mut self.control_packets)
+    }
+
+    // Causes zero-length packets to be discarded rather than passed to the packet sink. They are
+    // otherwise ordered and counted like any other packet.
+    pub fn set_receive_order(&mut self, order: ReceiveOrder) {
+        self.receive_order = order;
+    }
+
+    pub fn discard_empty_packets(&mut self) {
+        self.deliver_empty_packets = false;
+    }
+
+    // Causes the receipt of each fragment of a multi-fragment packet to be recorded for
+    // acknowledgement (see `take_fragment_acks()`)
+    pub fn enable_fragment_acks(&mut self) {
+        self.fragment_acks = true;
+    }
+
+    // Returns acknowledgements for the fragments received since this function was last called.
+    // Each group reports every fragment received so far within its range, so that a lost
+    // acknowledgement is repaired by any subsequent one.
+    pub fn take_fragment_acks(&mut self) -> Vec<frame::FragmentAckGroup> {
+        let mut groups = Vec::new();
+
+        for (sequence_id, base_id, last_id) in std::mem::take(&mut self.pending_fragment_acks).into_iter() {
+            if packet_id::sub(sequence_id, self.base_id) >= self.receive_window_size {
+                // Packet has since been delivered or skipped
+                continue;
+            }
+
+            let window_idx = window_index!(self, sequence_id);
+
+            if let Some(bitfield) = self.assembly_window.fragment_bitfield(window_idx, base_id, last_id) {
+                groups.push(frame::FragmentAckGroup { sequence_id, base_id, bitfield });
+            }
+        }
+
+        groups
+    }
+
+    fn note_fragment(&mut self, sequence_id: u32, fragment_id: u16, fragment_id_last: u16) {
+        if self.fragment_acks && fragment_id_last > 0 {
+            let key = (sequence_id, fragment_id & !31, fragment_id_last);
+
+            if !self.pending_fragment_acks.contains(&key) {
+                self.pending_fragment_acks.push(key);
+            }
+        }
+    }
+
+    pub fn handle_datagram(&mut self, datagram: frame::Datagram, now_ms: u64) {
         let base_id = self.base_id;
         let channel_idx = datagram.channel_id as usize;
         let sequence_id = datagram.sequence_id;
 
-        if !datagram_is_valid(&datagram) {
+        if !datagram_is_valid(&datagram, self.channels.application.len()) {
             // Datagram has invalid contents
             return;
         }
@@ -161,22 +477,76 @@ impl PacketReceiver {
 
         if packet_lead >= self.receive_window_size {
             // Packet not contained by transfer window
+            if packet_id::sub(base_id, sequence_id) <= self.receive_window_size {
+                // Packet precedes the window, and has therefore been delivered or skipped
+                self.duplicate_datagram_count += 1;
+            }
             return;
         }
 
-        if packet_lead < channel_lead {
-            // Packet already surpassed by this channel
+        let window_idx = window_index!(self, sequence_id);
+
+        // Set if this packet was skipped by its channel, but has not yet been received
+        let mut skipped = false;
+
+        // Keyed packets are not ordered with respect to the channel's base ID
+        if packet_lead < channel_lead && !datagram.keyed {
+            let entry_present = self.entry_flags[window_idx / 64] & (1 << (window_idx % 64)) != 0;
+
+            // A channel only surpasses packets it has not received if it is lossy, or if it was
+            // skipped forward by skip_to_latest()
+            if entry_present {
+                // Packet already surpassed by this channel
+                self.duplicate_datagram_count += 1;
+                return;
+            }
+
+            // Although it will not be delivered, this packet may be needed to advance the window
+            skipped = true;
+        }
+
+        // A resent fragment implies that the sender has not learned of its receipt, nor perhaps of
+        // its neighbors'
+        self.note_fragment(sequence_id, datagram.fragment_id, datagram.fragment_id_last);
+
+        if self.assembly_window.contains(window_idx, &datagram) {
+            // Datagram already received
+            self.duplicate_datagram_count += 1;
             return;
         }
 
-        let window_idx = window_index!(self, sequence_id);
+        if !skipped && self.entry_flags[window_idx / 64] & (1 << (window_idx % 64)) != 0 {
+            // Packet already assembled, or dropped by the sender
+            self.duplicate_datagram_count += 1;
+            return;
+        }
+
+        let channel = &mut self.channels[channel_idx];
 
         // Add this datagram to the assembly window
         if let Some(packet) = self.assembly_window.try_add(window_idx, datagram) {
+            if skipped {
+                // Record the packet as though it had already been delivered
+                self.window_entries[window_idx] = WindowAdvEntry {
+                    window_parent_lead: packet.window_parent_lead,
+                };
+
+                self.entry_flags[window_idx / 64] |= 1 << (window_idx % 64);
+
+                let window_parent_lead = packet.window_parent_lead as u32;
+
+                if window_parent_lead == 0 || window_parent_lead > packet_lead {
+                    self.window_ready_flag = true;
+                }
+
+                return;
+            }
+
             // Assembly window has produced a packet, add to receive window
             self.channel_entries[window_idx] = ChannelAdvEntry {
                 channel_id: packet.channel_id,
                 channel_parent_lead: packet.channel_parent_lead,
+                keyed: packet.keyed,
             };
 
             self.window_entries[window_idx] = WindowAdvEntry {
@@ -185,8 +555,12 @@ impl PacketReceiver {
 
             self.data_entries[window_idx] = DataEntry {
                 data: packet.data,
+                arrival_ms: now_ms,
+                arrival_index: self.arrival_count,
             };
 
+            self.arrival_count += 1;
+
             // Set corresponding bits in entry_flags and data_flags to
             // indicate an entry with data is present at the given sequence ID
             self.entry_flags[window_idx / 64] |= 1 << (window_idx % 64);
@@ -197,14 +571,27 @@ impl PacketReceiver {
                 self.end_id = packet_id::add(sequence_id, 1);
             }
 
-            channel.packet_count += 1;
+            match channel.newest_id {
+                Some(newest_id) if packet_id::sub(newest_id, sequence_id).wrapping_sub(1) < self.receive_window_size => {
+                    channel.out_of_order_count += 1;
+                }
+                _ => channel.newest_id = Some(sequence_id),
+            }
+
+            if packet.keyed {
+                // Whether this packet may be received depends on its parent, which is checked
+                // during the next call to receive()
+                self.keyed_ready_flag = true;
+            } else {
+                channel.packet_count += 1;
 
-            // Set corresponding bit in channel_ready_flags if this packet may be received
-            let channel_parent_lead = packet.channel_parent_lead as u32;
-            let channel_delta = packet_id::sub(sequence_id, channel_base_id);
+                // Set corresponding bit in channel_ready_flags if this packet may be received
+                let channel_parent_lead = packet.channel_parent_lead as u32;
+                let channel_delta = packet_id::sub(sequence_id, channel_base_id);
 
-            if channel_parent_lead == 0 || channel_parent_lead > channel_delta {
-                self.channel_ready_flags |= 1 << channel_idx;
+                if channel_parent_lead == 0 || channel_parent_lead > channel_delta {
+                    self.channel_ready_flags.insert(channel_idx as u8);
+                }
             }
 
             // Set window ready flag if this packet will cause the window to advance
@@ -217,27 +604,86 @@ impl PacketReceiver {
         }
     }
 
+    // Responds to a note from the sender that the given packets were abandoned. Each packet which
+    // has not yet been received is recorded as though it had been delivered, so that the window
+    // may advance past it without waiting for resynchronization.
+    pub fn handle_drop_range(&mut self, range: &frame::DropRange) {
+        if !packet_id::is_valid(range.base_id) {
+            return;
+        }
+
+        for offset in 0 .. range.count as u32 {
+            let sequence_id = packet_id::add(range.base_id, offset);
+
+            let packet_lead = packet_id::sub(sequence_id, self.base_id);
+
+            if packet_lead >= self.receive_window_size {
+                // Packet not contained by transfer window
+                continue;
+            }
+
+            let window_idx = window_index!(self, sequence_id);
+
+            if self.entry_flags[window_idx / 64] & (1 << (window_idx % 64)) != 0 {
+                // Packet already received
+                continue;
+            }
+
+            let window_parent_lead = if range.window_parent_lead == 0 {
+                0
+            } else {
+                range.window_parent_lead as u32 + offset
+            };
+
+            // Discard any fragments received so far
+            self.assembly_window.clear(window_idx);
+
+            self.window_entries[window_idx] = WindowAdvEntry {
+                window_parent_lead: window_parent_lead.min(u16::MAX as u32) as u16,
+            };
+
+            self.entry_flags[window_idx / 64] |= 1 << (window_idx % 64);
+
+            if packet_id::sub(sequence_id, self.end_id) < self.receive_window_size {
+                self.end_id = packet_id::add(sequence_id, 1);
+            }
+
+            if window_parent_lead == 0 || window_parent_lead > packet_lead {
+                self.window_ready_flag = true;
+            }
+        }
+    }
+
     fn set_channel_base_id(&mut self, channel_id: u8, new_id: u32) {
+        let slot = self.channels.slot(channel_id);
+        let marker_offset = slot / 64;
+        let channel_bit = 1u64 << (slot % 64);
+
         let ref mut channel = self.channels[channel_id as usize];
 
         if let Some(base_id) = channel.base_id {
-            self.channel_base_markers[window_index!(self, base_id)] = None;
+            self.channel_base_markers[window_index!(self, base_id) * self.base_marker_words + marker_offset] &= !channel_bit;
         }
 
-        let ref mut base_marker = self.channel_base_markers[window_index!(self, new_id)];
+        let base_markers = &mut self.channel_base_markers[window_index!(self, new_id) * self.base_marker_words + marker_offset];
 
-        debug_assert!(base_marker.is_none());
-        *base_marker = Some(channel_id);
+        debug_assert!(*base_markers & channel_bit == 0);
+        *base_markers |= channel_bit;
 
         channel.base_id = Some(new_id);
     }
 
     fn try_unset_channel_base_id(&mut self, sequence_id: u32) {
-        let window_idx = window_index!(self, sequence_id);
+        let markers_idx = window_index!(self, sequence_id) * self.base_marker_words;
 
-        let ref mut base_marker = self.channel_base_markers[window_idx];
-        if let Some(channel_id) = base_marker.take() {
-            self.channels[channel_id as usize].base_id = None;
+        for word_idx in 0 .. self.base_marker_words {
+            let mut base_markers = std::mem::take(&mut self.channel_base_markers[markers_idx + word_idx]);
+
+            while base_markers != 0 {
+                let channel_id = self.channels.slot_id(word_idx * 64 + base_markers.trailing_zeros() as usize);
+                self.channels[channel_id as usize].base_id = None;
+                base_markers &= base_markers - 1;
+            }
         }
     }
 
@@ -281,25 +727,122 @@ impl PacketReceiver {
         self.base_id = new_base_id;
     }
 
+    // Discards every undelivered packet on the given channel except the newest, and advances the
+    // channel's base ID to the newest packet so that it may be delivered. Predecessors which arrive
+    // later are skipped, as they would be by a lossy channel. Keyed packets are unaffected. Returns
+    // the number of packets discarded.
+    pub fn skip_to_latest(&mut self, channel_id: u8) -> u32 {
+        let channel_idx = channel_id as usize;
+        let channel_base_id = self.channels[channel_idx].base_id.unwrap_or(self.base_id);
+
+        let is_buffered = |rx: &Self, sequence_id: u32| {
+            let window_idx = window_index!(rx, sequence_id);
+            let ref channel_entry = rx.channel_entries[window_idx];
+
+            rx.data_flags[window_idx / 64] & (1 << (window_idx % 64)) != 0 &&
+                channel_entry.channel_id == channel_id && !channel_entry.keyed
+        };
+
+        let mut newest_id = None;
+        let mut sequence_id = channel_base_id;
+
+        while sequence_id != self.end_id {
+            if is_buffered(self, sequence_id) {
+                newest_id = Some(sequence_id);
+            }
+
+            sequence_id = packet_id::add(sequence_id, 1);
+        }
+
+        let newest_id = match newest_id {
+            Some(newest_id) => newest_id,
+            None => return 0,
+        };
+
+        let mut discard_count = 0;
+        let mut sequence_id = channel_base_id;
+
+        while sequence_id != newest_id {
+            if is_buffered(self, sequence_id) {
+                let window_idx = window_index!(self, sequence_id);
+
+                // The entry remains, so that the window may advance past it
+                self.data_entries[window_idx].data = None;
+                self.data_flags[window_idx / 64] &= !(1 << (window_idx % 64));
+
+                discard_count += 1;
+            }
+
+            sequence_id = packet_id::add(sequence_id, 1);
+        }
+
+        self.channels[channel_idx].packet_count -= discard_count;
+
+        if newest_id != channel_base_id {
+            self.set_channel_base_id(channel_id, newest_id);
+        }
+
+        self.channel_ready_flags.insert(channel_id);
+        self.window_ready_flag = true;
+
+        discard_count
+    }
+
+    // Returns true if the keyed packet with the given sequence ID and parent lead may be delivered,
+    // that is, if its parent has been delivered, or has passed beyond the receive window.
+    fn keyed_parent_delivered(&self, sequence_id: u32, channel_parent_lead: u16) -> bool {
+        let parent_lead = channel_parent_lead as u32;
+
+        if parent_lead == 0 || parent_lead > packet_id::sub(sequence_id, self.base_id) {
+            return true;
+        }
+
+        let parent_idx = window_index!(self, packet_id::sub(sequence_id, parent_lead));
+
+        let flag_bit = 1 << (parent_idx % 64);
+        let flags_index = parent_idx / 64;
+
+        self.entry_flags[flags_index] & flag_bit != 0 && self.data_flags[flags_index] & flag_bit == 0
+    }
+
     // Delivers as many received packets as possible, and advances channel base IDs accordingly.
     // Advances the transfer window if no reliable packets would be skipped.
-    pub fn receive(&mut self, sink: &mut impl PacketSink) {
+    pub fn receive(&mut self, now_ms: u64, sink: &mut impl PacketSink) {
         let base_id = self.base_id;
         let end_id = self.end_id;
 
+        // Reconsider any channel with buffered packets whose reorder limits may have been exceeded
+        for (channel_idx, channel) in self.channels.enumerate() {
+            if channel.reorder_limits.is_some() && channel.packet_count > 0 {
+                self.channel_ready_flags.insert(channel_idx as u8);
+            }
+        }
+
+        // Packets on paused channels are not considered until their channels are resumed
+        let paused_ready_flags = self.channel_ready_flags & self.channel_paused_flags;
+        self.channel_ready_flags &= !self.channel_paused_flags;
+
         debug_assert!(packet_id::sub(end_id, base_id) <= self.receive_window_size);
 
         //println!(
-        //  "-- receive() base_id: {} end_id: {} channel_ready_flags: {:016X} --",
+        //  "-- receive() base_id: {} end_id: {} channel_ready_flags: {:?} --",
         //  self.base_id,
         //  self.end_id,
         //  self.channel_ready_flags
         //);
 
+        // Set once a keyed packet is delivered, as any of its children later in the window may
+        // then be delivered as well
+        let mut keyed_delivered = false;
+
+        // Packets are collected in the order they were sent, and passed to the sink once all
+        // deliverable packets have been found
+        let mut deliveries = Vec::new();
+
         let mut sequence_id = base_id;
 
         while sequence_id != end_id {
-            if self.channel_ready_flags == 0 {
+            if self.channel_ready_flags.is_empty() && !self.keyed_ready_flag && !keyed_delivered {
                 //println!("channel_ready_flags == 0, breaking");
                 break;
             }
@@ -322,9 +865,37 @@ impl PacketReceiver {
                 //);
 
                 let channel_id = channel_entry.channel_id;
-                let channel_id_bit = 1u64 << channel_id;
 
-                if self.channel_ready_flags & channel_id_bit != 0 {
+                let control = self.control_channels && channel_id == frame::serial::CONTROL_CHANNEL_ID;
+
+                if self.channel_paused_flags.contains(channel_id) {
+                    // Withheld until the channel is resumed
+                } else if channel_entry.keyed {
+                    let channel_parent_lead = channel_entry.channel_parent_lead;
+
+                    if self.keyed_parent_delivered(sequence_id, channel_parent_lead) {
+                        let arrival_index = self.data_entries[window_idx].arrival_index;
+
+                        if let Some(packet_data) = self.data_entries[window_idx].data.take() {
+                            if control {
+                                self.control_packets.push((channel_id, packet_data));
+                            } else {
+                                self.delivered_size += packet_data.len() as u64;
+                                self.delivered_count += 1;
+
+                                if !packet_data.is_empty() || self.deliver_empty_packets {
+                                    deliveries.push(Delivery { channel_id, arrival_index, data: packet_data });
+                                }
+                            }
+
+                            self.channels[channel_id as usize].delivered_count += 1;
+                        }
+
+                        self.data_flags[flags_index] &= !flag_bit;
+
+                        keyed_delivered = true;
+                    }
+                } else if self.channel_ready_flags.contains(channel_id) {
                     let ref mut channel = self.channels[channel_id as usize];
 
                     let channel_base_id = channel.base_id.unwrap_or(base_id);
@@ -333,14 +904,46 @@ impl PacketReceiver {
                     let channel_parent_lead = channel_entry.channel_parent_lead as u32;
                     let channel_delta = packet_id::sub(sequence_id, channel_base_id);
 
-                    if channel_parent_lead == 0 || channel_parent_lead > channel_delta {
-                        sink.send(self.data_entries[window_idx].data.take().unwrap());
+                    let deliverable = channel_parent_lead == 0 || channel_parent_lead > channel_delta;
+
+                    let exceeded = !deliverable &&
+                        channel.limits_exceeded(self.data_entries[window_idx].arrival_ms, now_ms);
+
+                    if exceeded && !channel.is_lossy() && channel.stall_id != Some(sequence_id) {
+                        channel.stall_id = Some(sequence_id);
+                        channel.stall_count += 1;
+                    }
+
+                    if deliverable || (exceeded && channel.is_lossy()) {
+                        if !deliverable {
+                            // Skip this packet's missing predecessors
+                            channel.skip_count += 1;
+                        }
+
+                        // A packet without data was rejected by the assembly window, and is
+                        // consumed without being delivered
+                        let arrival_index = self.data_entries[window_idx].arrival_index;
+
+                        if let Some(packet_data) = self.data_entries[window_idx].data.take() {
+                            if control {
+                                self.control_packets.push((channel_id, packet_data));
+                            } else {
+                                self.delivered_size += packet_data.len() as u64;
+                                self.delivered_count += 1;
+
+                                if !packet_data.is_empty() || self.deliver_empty_packets {
+                                    deliveries.push(Delivery { channel_id, arrival_index, data: packet_data });
+                                }
+                            }
+
+                            channel.delivered_count += 1;
+                        }
 
                         self.data_flags[flags_index] &= !flag_bit;
 
                         channel.packet_count -= 1;
                         if channel.packet_count == 0 {
-                            self.channel_ready_flags &= !channel_id_bit;
+                            self.channel_ready_flags.remove(channel_id);
                         }
 
                         // TODO: Base ID markers only need to be updated once per receive()
@@ -352,7 +955,7 @@ impl PacketReceiver {
                         // Note: If any packets are deliverable past this one (parent_lead = 0),
                         // that is an error on the sender's part
 
-                        self.channel_ready_flags &= !channel_id_bit;
+                        self.channel_ready_flags.remove(channel_id);
                     }
                 }
             } else {
@@ -362,6 +965,11 @@ impl PacketReceiver {
             sequence_id = packet_id::add(sequence_id, 1);
         }
 
+        self.keyed_ready_flag = false;
+        self.channel_ready_flags |= paused_ready_flags;
+
+        Self::dispatch(self.receive_order, deliveries, sink);
+
         if self.window_ready_flag {
             self.window_ready_flag = false;
 
@@ -377,6 +985,11 @@ impl PacketReceiver {
                 let next_id = packet_id::add(sequence_id, 1);
 
                 if self.entry_flags[flags_index] & flag_bit != 0 {
+                    if self.data_flags[flags_index] & flag_bit != 0 {
+                        // This packet has been withheld, cease to consider advancing the window
+                        break;
+                    }
+
                     let ref mut window_entry = self.window_entries[window_idx];
 
                     let window_parent_lead = window_entry.window_parent_lead as u32;
@@ -401,6 +1014,51 @@ impl PacketReceiver {
         }
     }
 
+    // Passes the given packets to the sink in the given order. Packets are given in the order they
+    // were sent, and every order preserves the relative order of packets on the same channel.
+    fn dispatch(order: ReceiveOrder, deliveries: Vec<Delivery>, sink: &mut impl PacketSink) {
+        let mut keyed_deliveries: Vec<(u64, Delivery)> = match order {
+            ReceiveOrder::Sent => {
+                for delivery in deliveries.into_iter() {
+                    sink.send(delivery.data);
+                }
+                return;
+            }
+            ReceiveOrder::Arrival => {
+                // A packet is ready once it and each of its predecessors on the same channel have
+                // arrived
+                let mut ready_indices = HashMap::new();
+
+                deliveries.into_iter().map(|delivery| {
+                    let ready_index = ready_indices.entry(delivery.channel_id).or_insert(0);
+                    *ready_index = delivery.arrival_index.max(*ready_index);
+                    (*ready_index, delivery)
+                }).collect()
+            }
+            ReceiveOrder::ChannelPriority => {
+                deliveries.into_iter().map(|delivery| (delivery.channel_id as u64, delivery)).collect()
+            }
+            ReceiveOrder::RoundRobin => {
+                // Each round takes the next packet from each channel, in channel ID order
+                let mut rounds = HashMap::new();
+
+                deliveries.into_iter().map(|delivery| {
+                    let round = rounds.entry(delivery.channel_id).or_insert(0u64);
+                    let key = (*round << 8) | delivery.channel_id as u64;
+                    *round += 1;
+                    (key, delivery)
+                }).collect()
+            }
+        };
+
+        // The sort is stable, so packets with equal keys remain in the order they were sent
+        keyed_deliveries.sort_by_key(|(key, _)| *key);
+
+        for (_, delivery) in keyed_deliveries.into_iter() {
+            sink.send(delivery.data);
+        }
+    }
+
     // Responds to a resynchronization request sent by the sender. Advances the transfer window to
     // start with the given sequence ID, or to the ID of the first undelivered packet, whichever
     // comes first. Any incomplete or dropped packets are skipped, and as a result, the sender must
@@ -433,12 +1091,48 @@ impl PacketReceiver {
 
         self.advance_window(sequence_id);
     }
+
+    // Discards every packet in the receive window, delivered or otherwise, and restarts the window
+    // at the given sequence ID. Channel settings and counters are retained.
+    pub fn reset(&mut self, base_id: u32) {
+        debug_assert!(packet_id::is_valid(base_id));
+
+        for idx in 0 .. MAX_PACKET_WINDOW_SIZE as usize {
+            self.assembly_window.clear(idx);
+        }
+
+        for entry in self.data_entries.iter_mut() {
+            entry.data = None;
+        }
+
+        self.entry_flags.iter_mut().for_each(|flags| *flags = 0);
+        self.data_flags.iter_mut().for_each(|flags| *flags = 0);
+        self.channel_base_markers.iter_mut().for_each(|markers| *markers = 0);
+
+        for channel in self.channels.iter_mut() {
+            channel.base_id = None;
+            channel.packet_count = 0;
+            channel.newest_id = None;
+            channel.stall_id = None;
+        }
+
+        self.channel_ready_flags = ChannelMask::new();
+        self.window_ready_flag = false;
+        self.keyed_ready_flag = false;
+
+        self.pending_fragment_acks.clear();
+
+        self.base_id = base_id;
+        self.end_id = base_id;
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::CHANNEL_COUNT;
+
     use std::collections::VecDeque;
 
     fn new_packet_data(sequence_id: u32) -> Box<[u8]> {
@@ -451,12 +1145,20 @@ mod tests {
             channel_id,
             window_parent_lead,
             channel_parent_lead,
+            keyed: false,
             fragment_id: 0,
             fragment_id_last: 0,
             data: new_packet_data(sequence_id),
         }
     }
 
+    fn new_keyed_packet_datagram(sequence_id: u32, channel_id: u8, window_parent_lead: u16, channel_parent_lead: u16) -> frame::Datagram {
+        frame::Datagram {
+            keyed: true,
+            .. new_packet_datagram(sequence_id, channel_id, window_parent_lead, channel_parent_lead)
+        }
+    }
+
     struct TestPacketSink {
         packets: VecDeque<Box<[u8]>>,
     }
@@ -492,11 +1194,11 @@ mod tests {
               w  c0        w  c0
         */
 
-        let mut rx = PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 0, 100000);
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
         let mut sink = TestPacketSink::new();
 
-        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0), 0);
+        rx.receive(0, &mut sink);
 
         // A
         assert_eq!(sink.pop(), new_packet_data(0));
@@ -517,11 +1219,11 @@ mod tests {
               w  c0        w  c0
         */
 
-        let mut rx = PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 0, 100000);
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
         let mut sink = TestPacketSink::new();
 
-        rx.handle_datagram(new_packet_datagram(1, 0, 0, 0));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(1, 0, 0, 0), 0);
+        rx.receive(0, &mut sink);
 
         // A
         assert_eq!(sink.pop(), new_packet_data(1));
@@ -542,11 +1244,11 @@ mod tests {
               w  c0        w  c0
         */
 
-        let mut rx = PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 0, 100000);
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
         let mut sink = TestPacketSink::new();
 
-        rx.handle_datagram(new_packet_datagram(1, 0, 1, 1));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(1, 0, 1, 1), 0);
+        rx.receive(0, &mut sink);
 
         // A
         assert!(sink.is_empty());
@@ -569,13 +1271,13 @@ mod tests {
               w  c0 c1        w  c0 c1
         */
 
-        let mut rx = PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 0, 100000);
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
         let mut sink = TestPacketSink::new();
 
-        rx.handle_datagram(new_packet_datagram(2, 1, 2, 2));
-        rx.handle_datagram(new_packet_datagram(3, 0, 3, 0));
-        rx.handle_datagram(new_packet_datagram(4, 0, 4, 0));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(2, 1, 2, 2), 0);
+        rx.handle_datagram(new_packet_datagram(3, 0, 3, 0), 0);
+        rx.handle_datagram(new_packet_datagram(4, 0, 4, 0), 0);
+        rx.receive(0, &mut sink);
 
         // A
         assert_eq!(sink.pop(), new_packet_data(3));
@@ -601,14 +1303,14 @@ mod tests {
               w  c0 c1        w  c0 c1
         */
 
-        let mut rx = PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 1, 100000);
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 1, 100000, CHANNEL_COUNT);
         let mut sink = TestPacketSink::new();
 
-        rx.handle_datagram(new_packet_datagram(2, 1, 2, 2));
-        rx.handle_datagram(new_packet_datagram(3, 0, 3, 0));
-        rx.handle_datagram(new_packet_datagram(4, 0, 4, 0));
+        rx.handle_datagram(new_packet_datagram(2, 1, 2, 2), 0);
+        rx.handle_datagram(new_packet_datagram(3, 0, 3, 0), 0);
+        rx.handle_datagram(new_packet_datagram(4, 0, 4, 0), 0);
 
-        rx.receive(&mut sink);
+        rx.receive(0, &mut sink);
         assert_eq!(sink.pop(), new_packet_data(2));
         assert_eq!(sink.pop(), new_packet_data(3));
         assert_eq!(sink.pop(), new_packet_data(4));
@@ -634,14 +1336,14 @@ mod tests {
               w  c0 c1        w  c0 c1        w  c0 c1        w  c0 c1
         */
 
-        let mut rx = PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 0, 100000);
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
         let mut sink = TestPacketSink::new();
 
-        rx.handle_datagram(new_packet_datagram(1, 1, 1, 1));
-        rx.handle_datagram(new_packet_datagram(2, 1, 2, 2));
-        rx.handle_datagram(new_packet_datagram(3, 1, 3, 3));
-        rx.handle_datagram(new_packet_datagram(4, 0, 4, 0));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(1, 1, 1, 1), 0);
+        rx.handle_datagram(new_packet_datagram(2, 1, 2, 2), 0);
+        rx.handle_datagram(new_packet_datagram(3, 1, 3, 3), 0);
+        rx.handle_datagram(new_packet_datagram(4, 0, 4, 0), 0);
+        rx.receive(0, &mut sink);
 
         // A
         assert_eq!(sink.pop(), new_packet_data(4));
@@ -652,8 +1354,8 @@ mod tests {
         assert_eq!(rx.channels[0].base_id, Some(5));
         assert_eq!(rx.channels[1].base_id, None);
 
-        rx.handle_datagram(new_packet_datagram(0, 1, 0, 0));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(0, 1, 0, 0), 0);
+        rx.receive(0, &mut sink);
 
         // B
         assert_eq!(sink.pop(), new_packet_data(0));
@@ -667,8 +1369,8 @@ mod tests {
         assert_eq!(rx.channels[0].base_id, None);
         assert_eq!(rx.channels[1].base_id, None);
 
-        rx.handle_datagram(new_packet_datagram(5, 0, 1, 1));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(5, 0, 1, 1), 0);
+        rx.receive(0, &mut sink);
 
         // C
         assert_eq!(sink.pop(), new_packet_data(5));
@@ -691,11 +1393,11 @@ mod tests {
               w  c0 c1        w  c0 c1        w  c0 c1        w  c0 c1
         */
 
-        let mut rx = PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 0, 100000);
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
         let mut sink = TestPacketSink::new();
 
-        rx.handle_datagram(new_packet_datagram(2, 0, 2, 0));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(2, 0, 2, 0), 0);
+        rx.receive(0, &mut sink);
 
         // A
         assert_eq!(sink.pop(), new_packet_data(2));
@@ -706,8 +1408,8 @@ mod tests {
         assert_eq!(rx.channels[0].base_id, Some(3));
         assert_eq!(rx.channels[1].base_id, None);
 
-        rx.handle_datagram(new_packet_datagram(1, 0, 1, 0));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(1, 0, 1, 0), 0);
+        rx.receive(0, &mut sink);
 
         // B
         assert!(sink.is_empty());
@@ -717,8 +1419,8 @@ mod tests {
         assert_eq!(rx.channels[0].base_id, Some(3));
         assert_eq!(rx.channels[1].base_id, None);
 
-        rx.handle_datagram(new_packet_datagram(0, 1, 0, 0));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(0, 1, 0, 0), 0);
+        rx.receive(0, &mut sink);
 
         // C
         assert_eq!(sink.pop(), new_packet_data(0));
@@ -742,11 +1444,11 @@ mod tests {
               w  c0 c1        w  c0 c1        w  c0 c1
         */
 
-        let mut rx = PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 0, 100000);
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
         let mut sink = TestPacketSink::new();
 
-        rx.handle_datagram(new_packet_datagram(1, 0, 1, 2));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(1, 0, 1, 2), 0);
+        rx.receive(0, &mut sink);
 
         // A
         assert_eq!(sink.pop(), new_packet_data(1));
@@ -757,9 +1459,9 @@ mod tests {
         assert_eq!(rx.channels[0].base_id, Some(2));
         assert_eq!(rx.channels[1].base_id, None);
 
-        rx.handle_datagram(new_packet_datagram(0, 1, 1, 0));
-        rx.handle_datagram(new_packet_datagram(2, 0, 2, 3));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(0, 1, 1, 0), 0);
+        rx.handle_datagram(new_packet_datagram(2, 0, 2, 3), 0);
+        rx.receive(0, &mut sink);
 
         // B
         assert_eq!(sink.pop(), new_packet_data(0));
@@ -772,15 +1474,55 @@ mod tests {
         assert_eq!(rx.channels[1].base_id, None);
     }
 
+    #[test]
+    fn keyed_packets() {
+        // Reliable packets 0 & 2 share one key, 1 & 3 share another, and 4 is sent without a key
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        rx.handle_datagram(new_keyed_packet_datagram(1, 0, 1, 0), 0);
+        rx.handle_datagram(new_keyed_packet_datagram(3, 0, 1, 2), 0);
+        rx.handle_datagram(new_packet_datagram(4, 0, 1, 0), 0);
+        rx.receive(0, &mut sink);
+
+        // Packets not waiting on packet 0 are delivered
+        assert_eq!(sink.pop(), new_packet_data(1));
+        assert_eq!(sink.pop(), new_packet_data(3));
+        assert_eq!(sink.pop(), new_packet_data(4));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.base_id, 0);
+        assert_eq!(rx.channels[0].base_id, Some(5));
+
+        // Packet 2 precedes the channel's base ID, but waits for packet 0 regardless
+        rx.handle_datagram(new_keyed_packet_datagram(2, 0, 1, 2), 0);
+        rx.receive(0, &mut sink);
+
+        assert!(sink.is_empty());
+        assert_eq!(rx.base_id, 0);
+
+        rx.handle_datagram(new_keyed_packet_datagram(0, 0, 0, 0), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(0));
+        assert_eq!(sink.pop(), new_packet_data(2));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.base_id, 5);
+        assert_eq!(rx.end_id, 5);
+        assert_eq!(rx.channels[0].base_id, None);
+        assert_eq!(rx.channel_counts(0).0, 5);
+    }
+
     #[test]
     fn max_stall() {
-        let mut rx = PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 0, 100000);
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
         let mut sink = TestPacketSink::new();
 
         for sequence_id in 1 .. MAX_PACKET_WINDOW_SIZE {
-            rx.handle_datagram(new_packet_datagram(sequence_id, 0, sequence_id as u16, sequence_id as u16));
+            rx.handle_datagram(new_packet_datagram(sequence_id, 0, sequence_id as u16, sequence_id as u16), 0);
         }
-        rx.receive(&mut sink);
+        rx.receive(0, &mut sink);
 
         assert!(sink.is_empty());
 
@@ -789,8 +1531,8 @@ mod tests {
         assert_eq!(rx.channels[0].base_id, None);
         assert_eq!(rx.channels[1].base_id, None);
 
-        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0));
-        rx.receive(&mut sink);
+        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0), 0);
+        rx.receive(0, &mut sink);
 
         for sequence_id in 0 .. MAX_PACKET_WINDOW_SIZE {
             assert_eq!(sink.pop(), new_packet_data(sequence_id));
@@ -807,7 +1549,7 @@ mod tests {
     fn fill_window_n_times() {
         let n = 4;
 
-        let mut rx = PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 0, 100000);
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
         let mut sink = TestPacketSink::new();
 
         let mut tx_id = 0;
@@ -815,11 +1557,11 @@ mod tests {
 
         for _ in 0 .. n {
             for _ in 0 .. MAX_PACKET_WINDOW_SIZE {
-                rx.handle_datagram(new_packet_datagram(tx_id, 0, 0, 0));
+                rx.handle_datagram(new_packet_datagram(tx_id, 0, 0, 0), 0);
                 tx_id += 1;
             }
 
-            rx.receive(&mut sink);
+            rx.receive(0, &mut sink);
 
             for _ in 0 .. MAX_PACKET_WINDOW_SIZE {
                 assert_eq!(sink.pop(), new_packet_data(rx_id));
@@ -829,6 +1571,439 @@ mod tests {
         }
     }
 
+    #[test]
+    fn channel_counts() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0), 0);
+        rx.handle_datagram(new_packet_datagram(2, 0, 0, 0), 0);
+        rx.handle_datagram(new_packet_datagram(3, 1, 0, 0), 0);
+        rx.handle_datagram(new_packet_datagram(1, 0, 0, 0), 0);
+        rx.receive(0, &mut sink);
+
+        // Packet 1 arrived after packet 2, but is still delivered in order
+        assert_eq!(rx.channel_counts(0), (3, 1));
+        assert_eq!(rx.channel_counts(1), (1, 0));
+    }
+
+    // In lossy mode, a missing predecessor should be skipped once too many packets are waiting,
+    // and the window should still advance once the skipped packet arrives.
+    #[test]
+    fn reorder_limit_lossy_count() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        rx.set_reorder_limits(0, Some(ReorderLimits { max_packets: Some(1), max_hold_ms: None, mode: ReorderMode::Lossy }));
+
+        // Packet 0 (reliable) is missing
+        rx.handle_datagram(new_packet_datagram(1, 0, 1, 1), 0);
+        rx.receive(0, &mut sink);
+        assert!(sink.is_empty());
+
+        rx.handle_datagram(new_packet_datagram(2, 0, 2, 2), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(1));
+        assert_eq!(sink.pop(), new_packet_data(2));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.reorder_counts(0), (0, 1));
+        assert_eq!(rx.base_id, 0);
+
+        // The skipped packet is not delivered, but releases the window
+        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0), 0);
+        rx.receive(0, &mut sink);
+
+        assert!(sink.is_empty());
+        assert_eq!(rx.base_id, 3);
+        assert_eq!(rx.duplicate_datagram_count(), 0);
+    }
+
+    // In lossy mode, a missing predecessor should be skipped once a packet has waited too long.
+    #[test]
+    fn reorder_limit_lossy_hold() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        rx.set_reorder_limits(0, Some(ReorderLimits { max_packets: None, max_hold_ms: Some(50), mode: ReorderMode::Lossy }));
+
+        rx.handle_datagram(new_packet_datagram(1, 0, 1, 1), 100);
+
+        rx.receive(149, &mut sink);
+        assert!(sink.is_empty());
+
+        rx.receive(150, &mut sink);
+        assert_eq!(sink.pop(), new_packet_data(1));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.reorder_counts(0), (0, 1));
+    }
+
+    // In strict mode, packets should continue to wait, and each stalled packet should be counted
+    // once.
+    #[test]
+    fn reorder_limit_strict() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        rx.set_reorder_limits(0, Some(ReorderLimits { max_packets: None, max_hold_ms: Some(50), mode: ReorderMode::Strict }));
+
+        rx.handle_datagram(new_packet_datagram(1, 0, 1, 1), 0);
+
+        rx.receive(100, &mut sink);
+        rx.receive(200, &mut sink);
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.reorder_counts(0), (1, 0));
+
+        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0), 200);
+        rx.receive(200, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(0));
+        assert_eq!(sink.pop(), new_packet_data(1));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.base_id, 2);
+    }
+
+    #[test]
+    fn fragment_acks() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        rx.enable_fragment_acks();
+
+        let fragment_datagram = |sequence_id: u32, fragment_id: u16, fragment_id_last: u16| frame::Datagram {
+            sequence_id,
+            channel_id: 0,
+            window_parent_lead: 0,
+            channel_parent_lead: 0,
+            keyed: false,
+            fragment_id,
+            fragment_id_last,
+            data: vec![0; MAX_FRAGMENT_SIZE].into_boxed_slice(),
+        };
+
+        // Single-fragment packets are not acknowledged individually
+        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0), 0);
+        assert_eq!(rx.take_fragment_acks(), vec![]);
+
+        rx.handle_datagram(fragment_datagram(1, 0, 39), 0);
+        rx.handle_datagram(fragment_datagram(1, 2, 39), 0);
+        rx.handle_datagram(fragment_datagram(1, 35, 39), 0);
+
+        assert_eq!(rx.take_fragment_acks(), vec![
+            frame::FragmentAckGroup { sequence_id: 1, base_id: 0, bitfield: 0b101 },
+            frame::FragmentAckGroup { sequence_id: 1, base_id: 32, bitfield: 0b1000 },
+        ]);
+        assert_eq!(rx.take_fragment_acks(), vec![]);
+
+        // A duplicate fragment causes its group to be acknowledged again
+        rx.handle_datagram(fragment_datagram(1, 2, 39), 0);
+
+        assert_eq!(rx.take_fragment_acks(), vec![
+            frame::FragmentAckGroup { sequence_id: 1, base_id: 0, bitfield: 0b101 },
+        ]);
+
+        // Packets which have been delivered are not acknowledged
+        for i in 0 .. 40 {
+            rx.handle_datagram(fragment_datagram(1, i, 39), 0);
+        }
+
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(0));
+        assert_eq!(sink.pop().len(), MAX_FRAGMENT_SIZE * 40);
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.take_fragment_acks(), vec![]);
+    }
+
+    #[test]
+    fn empty_packets() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        let empty_datagram = |sequence_id: u32| frame::Datagram {
+            sequence_id,
+            channel_id: 0,
+            window_parent_lead: 0,
+            channel_parent_lead: 0,
+            keyed: false,
+            fragment_id: 0,
+            fragment_id_last: 0,
+            data: Box::new([]),
+        };
+
+        rx.handle_datagram(empty_datagram(0), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), Box::new([]) as Box<[u8]>);
+        assert!(sink.is_empty());
+
+        // Discarded empty packets still advance the window
+        rx.discard_empty_packets();
+
+        rx.handle_datagram(empty_datagram(1), 0);
+        rx.handle_datagram(new_packet_datagram(2, 0, 0, 0), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(2));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.base_id, 3);
+        assert_eq!(rx.channel_counts(0), (3, 0));
+    }
+
+    #[test]
+    fn control_channels() {
+        let control_channel_id = frame::serial::CONTROL_CHANNEL_ID;
+
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        // Control channels are ordinary channels unless enabled
+        rx.handle_datagram(new_packet_datagram(0, control_channel_id, 0, 0), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(0));
+        assert!(rx.take_control_packets().is_empty());
+
+        rx.enable_control_channels();
+
+        rx.handle_datagram(new_packet_datagram(1, control_channel_id, 0, 0), 0);
+        rx.handle_datagram(new_packet_datagram(2, 0, 0, 0), 0);
+        rx.handle_datagram(new_keyed_packet_datagram(3, control_channel_id, 0, 0), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(2));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.take_control_packets(), vec![ (control_channel_id, new_packet_data(1)),
+                                                    (control_channel_id, new_packet_data(3)) ]);
+        assert!(rx.take_control_packets().is_empty());
+
+        // Control packets are not counted as delivered
+        assert_eq!(rx.delivered_count(), 2);
+        assert_eq!(rx.base_id, 4);
+    }
+
+    #[test]
+    fn empty_fragment_invalid() {
+        let datagram = frame::Datagram {
+            sequence_id: 0,
+            channel_id: 0,
+            window_parent_lead: 0,
+            channel_parent_lead: 0,
+            keyed: false,
+            fragment_id: 1,
+            fragment_id_last: 1,
+            data: Box::new([]),
+        };
+
+        assert!(!datagram_is_valid(&datagram, CHANNEL_COUNT));
+    }
+
+    #[test]
+    fn fixed_channel_count() {
+        let control_channel_id = frame::serial::CONTROL_CHANNEL_ID;
+
+        let mut rx = PacketReceiver::<0>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, 4);
+        let mut sink = TestPacketSink::new();
+
+        // Channels beyond the channel count are invalid, but the control channel is not
+        assert!(!datagram_is_valid(&new_packet_datagram(0, 4, 0, 0), 4));
+
+        rx.handle_datagram(new_packet_datagram(0, 3, 0, 0), 0);
+        rx.handle_datagram(new_packet_datagram(1, control_channel_id, 0, 0), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(0));
+        assert_eq!(sink.pop(), new_packet_data(1));
+        assert_eq!(rx.channel_counts(3), (1, 0));
+        assert_eq!(rx.channel_counts(control_channel_id), (1, 0));
+    }
+
+    #[test]
+    fn oversized_packets() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        rx.set_max_packet_size(2);
+
+        // The rejected packet is skipped, and the packet which follows is still delivered
+        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0), 0);
+        rx.handle_datagram(frame::Datagram {
+            data: Box::new([1]),
+            .. new_packet_datagram(1, 0, 0, 0)
+        }, 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), Box::new([1u8]) as Box<[u8]>);
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.base_id, 2);
+        assert_eq!(rx.oversized_packet_count(), 1);
+        assert_eq!(rx.channel_counts(0), (1, 0));
+    }
+
+    #[test]
+    fn drop_ranges() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        // Packets 1 and 2 were abandoned by the sender, but must still wait for reliable packet 0
+        rx.handle_drop_range(&frame::DropRange { base_id: 1, count: 2, window_parent_lead: 1 });
+        rx.receive(0, &mut sink);
+
+        assert!(sink.is_empty());
+        assert_eq!(rx.base_id, 0);
+        assert_eq!(rx.end_id, 3);
+
+        // Once packet 0 arrives, the window advances past the dropped packets
+        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(0));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.base_id, 3);
+        assert_eq!(rx.end_id, 3);
+
+        // A late arrival is not delivered
+        rx.handle_datagram(new_packet_datagram(2, 0, 2, 0), 0);
+        rx.receive(0, &mut sink);
+
+        assert!(sink.is_empty());
+        assert_eq!(rx.duplicate_datagram_count(), 1);
+    }
+
+    #[test]
+    fn drop_range_partial_packet() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        let fragment_datagram = |fragment_id: u16| frame::Datagram {
+            sequence_id: 0,
+            channel_id: 0,
+            window_parent_lead: 0,
+            channel_parent_lead: 0,
+            keyed: false,
+            fragment_id,
+            fragment_id_last: 1,
+            data: vec![0; MAX_FRAGMENT_SIZE].into_boxed_slice(),
+        };
+
+        // Already received packets are not dropped
+        rx.handle_datagram(new_packet_datagram(1, 0, 0, 0), 0);
+
+        rx.handle_datagram(fragment_datagram(0), 0);
+        rx.handle_drop_range(&frame::DropRange { base_id: 0, count: 2, window_parent_lead: 0 });
+
+        // The remaining fragment of the dropped packet is ignored
+        rx.handle_datagram(fragment_datagram(1), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(1));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.base_id, 2);
+        assert_eq!(rx.duplicate_datagram_count(), 1);
+    }
+
+    #[test]
+    fn skip_to_latest() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        // Reliable packet 0 is missing, so packets 1-3 are held
+        rx.handle_datagram(new_packet_datagram(1, 0, 1, 1), 0);
+        rx.handle_datagram(new_packet_datagram(2, 0, 2, 2), 0);
+        rx.handle_datagram(new_packet_datagram(3, 0, 3, 3), 0);
+        rx.handle_datagram(new_packet_datagram(4, 1, 4, 0), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(4));
+        assert!(sink.is_empty());
+
+        // Only the newest packet on the channel is delivered
+        assert_eq!(rx.skip_to_latest(0), 2);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(3));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.channels[0].base_id, Some(4));
+        assert_eq!(rx.channel_counts(0), (1, 0));
+
+        // Packets which follow are delivered as usual, but the missing packet is not
+        rx.handle_datagram(new_packet_datagram(5, 0, 5, 5), 0);
+        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0), 0);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(5));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.base_id, 6);
+        assert_eq!(rx.skip_to_latest(0), 0);
+    }
+
+    #[test]
+    fn paused_channel() {
+        let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+        let mut sink = TestPacketSink::new();
+
+        rx.set_paused(0, true);
+
+        rx.handle_datagram(new_packet_datagram(0, 0, 0, 0), 0);
+        rx.handle_datagram(new_packet_datagram(1, 1, 0, 0), 0);
+        rx.handle_datagram(new_packet_datagram(2, 0, 0, 0), 0);
+        rx.receive(0, &mut sink);
+
+        // Other channels are unaffected, but the window is held by the withheld packets
+        assert_eq!(sink.pop(), new_packet_data(1));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.base_id, 0);
+        assert_eq!(rx.end_id, 3);
+
+        rx.set_paused(0, false);
+        rx.receive(0, &mut sink);
+
+        assert_eq!(sink.pop(), new_packet_data(0));
+        assert_eq!(sink.pop(), new_packet_data(2));
+        assert!(sink.is_empty());
+
+        assert_eq!(rx.base_id, 3);
+    }
+
+    #[test]
+    fn receive_order() {
+        // Packets arrive in reverse, but those on the same channel are still delivered in order
+        let receive_in_order = |order| {
+            let mut rx = PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+            let mut sink = TestPacketSink::new();
+
+            rx.set_receive_order(order);
+
+            rx.handle_datagram(new_packet_datagram(3, 0, 0, 0), 0);
+            rx.handle_datagram(new_packet_datagram(2, 1, 0, 0), 0);
+            rx.handle_datagram(new_packet_datagram(1, 0, 0, 0), 0);
+            rx.handle_datagram(new_packet_datagram(0, 1, 0, 0), 0);
+            rx.receive(0, &mut sink);
+
+            sink.packets.into_iter().collect::<Vec<_>>()
+        };
+
+        let packets = |ids: &[u32]| ids.iter().map(|&id| new_packet_data(id)).collect::<Vec<_>>();
+
+        assert_eq!(receive_in_order(ReceiveOrder::Sent), packets(&[ 0, 1, 2, 3 ]));
+        assert_eq!(receive_in_order(ReceiveOrder::Arrival), packets(&[ 1, 3, 0, 2 ]));
+        assert_eq!(receive_in_order(ReceiveOrder::ChannelPriority), packets(&[ 1, 3, 0, 2 ]));
+        assert_eq!(receive_in_order(ReceiveOrder::RoundRobin), packets(&[ 1, 0, 3, 2 ]));
+    }
+
     // TODO: Test invalid datagrams
 }
 
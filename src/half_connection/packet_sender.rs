@@ -1,13 +1,23 @@
 
-use super::pending_packet::{PendingPacket, PendingPacketRc};
+use super::channel_array::ChannelArray;
+use super::channel_mask::ChannelMask;
+use super::pending_packet::{PacketData, PendingPacket, PendingPacketRc};
 
-use crate::CHANNEL_COUNT;
+use crate::frame;
 use crate::MAX_FRAGMENT_SIZE;
 use crate::MAX_PACKET_SIZE;
 use crate::MAX_PACKET_WINDOW_SIZE;
 use crate::packet_id;
+use crate::Backpressure;
+use crate::LatencyClass;
+use crate::QueueAction;
+use crate::QueuedPacket;
 use crate::SendMode;
+use crate::SendOptions;
+use crate::TraceOutcome;
+use crate::Watermarks;
 
+use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -21,6 +31,9 @@ fn alloc_size(packet_size: usize) -> usize {
     }
 }
 
+// Virtual time taken to send one byte on a channel of weight 1
+const VIRTUAL_TIME_SCALE: u64 = 1 << 16;
+
 struct WindowEntry {
     // The packet to be sent in this slot
     packet: PendingPacketRc,
@@ -28,35 +41,143 @@ struct WindowEntry {
     alloc_size: usize,
     // Channel this packet was sent on
     channel_id: u8,
+    // Ordering key this packet was sent with, if any
+    key: Option<u16>,
+    // Trace ID this packet was sent with, if any
+    trace_id: Option<u64>,
+    // Whether this packet's fragments are resent until acknowledged
+    resend: bool,
+    // Set if this packet will not be resent, and a fragment of it was lost
+    abandoned: bool,
+    // Send ID this packet was enqueued with, if it may be canceled
+    send_id: Option<u64>,
+    // Set if this packet was canceled before any of its fragments were sent
+    canceled: bool,
+    // Tag with which this packet's delivery will be confirmed, until confirmed
+    confirm_tag: Option<u64>,
 }
 
 struct Channel {
     parent_id: Option<u32>,
+    // Maximum time an unreliable packet may remain in the send queue under congestion
+    stale_bound_ms: Option<u64>,
+    // Number of packets dropped before being sent
+    drop_count: u64,
+    // Number of packets which were forgotten without having been fully acknowledged
+    loss_count: u64,
+    // Number and combined size of this channel's packets in the send queue
+    queued_count: usize,
+    queued_size: usize,
+    // Share of the send rate given to this channel when sends are weighted, and the virtual time
+    // at which its next packet is due
+    weight: u32,
+    virtual_time: u64,
+    // Backpressure thresholds, and whether the channel is currently paused
+    watermarks: Option<Watermarks>,
+    paused: bool,
 }
 
 impl Channel {
     fn new() -> Self {
         Self {
             parent_id: None,
+            stale_bound_ms: None,
+            drop_count: 0,
+            loss_count: 0,
+            queued_count: 0,
+            queued_size: 0,
+            weight: 1,
+            virtual_time: 0,
+            watermarks: None,
+            paused: false,
+        }
+    }
+}
+
+// Per-channel state of each application channel, followed by that of the control channel, indexed
+// by channel ID
+struct Channels<const N: usize> {
+    application: ChannelArray<Channel, N>,
+    control: Channel,
+}
+
+impl<const N: usize> Channels<N> {
+    fn new(channel_count: usize) -> Self {
+        Self {
+            application: ChannelArray::new(channel_count, Channel::new),
+            control: Channel::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.application.len()
+    }
+
+    // Returns each channel along with its ID
+    fn enumerate(&self) -> impl Iterator<Item = (usize, &Channel)> {
+        self.application.iter().enumerate().chain(std::iter::once((frame::serial::CONTROL_CHANNEL_ID as usize, &self.control)))
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Channel> {
+        self.application.iter().chain(std::iter::once(&self.control))
+    }
+}
+
+impl<const N: usize> std::ops::Index<usize> for Channels<N> {
+    type Output = Channel;
+
+    fn index(&self, channel_idx: usize) -> &Channel {
+        if channel_idx == frame::serial::CONTROL_CHANNEL_ID as usize {
+            &self.control
+        } else {
+            &self.application[channel_idx]
+        }
+    }
+}
+
+impl<const N: usize> std::ops::IndexMut<usize> for Channels<N> {
+    fn index_mut(&mut self, channel_idx: usize) -> &mut Channel {
+        if channel_idx == frame::serial::CONTROL_CHANNEL_ID as usize {
+            &mut self.control
+        } else {
+            &mut self.application[channel_idx]
         }
     }
 }
 
 #[derive(Debug)]
 struct PacketSendEntry {
-    data: Box<[u8]>,
+    data: PacketData,
     channel_id: u8,
     mode: SendMode,
+    options: SendOptions,
+    // Send ID this packet was enqueued with, if it may be canceled
+    send_id: Option<u64>,
     flush_id: u32,
+    enqueue_ms: u64,
 }
 
 impl PacketSendEntry {
-    fn new(data: Box<[u8]>, channel_id: u8, mode: SendMode, flush_id: u32) -> Self {
-        Self {
-            data,
-            channel_id,
-            mode,
-            flush_id,
+    // Returns true if this packet must be dropped rather than sent
+    fn expired(&self, flush_id: u32, now_ms: u64) -> bool {
+        if self.mode == SendMode::TimeSensitive && self.flush_id != flush_id {
+            return true;
+        }
+
+        self.options.ttl.is_some_and(|ttl| now_ms.saturating_sub(self.enqueue_ms) as u128 > ttl.as_millis())
+    }
+
+    fn is_critical(&self) -> bool {
+        self.options.latency_class == LatencyClass::Critical
+    }
+
+    fn describe(&self, now_ms: u64) -> QueuedPacket<'_> {
+        QueuedPacket {
+            data: &self.data,
+            channel_id: self.channel_id as usize,
+            mode: self.mode,
+            latency_class: self.options.latency_class,
+            age_ms: now_ms.saturating_sub(self.enqueue_ms),
         }
     }
 }
@@ -67,8 +188,14 @@ macro_rules! window_index {
     };
 }
 
-pub struct PacketSender {
+pub struct PacketSender<const CHANNELS: usize = 0> {
     packet_send_queue: VecDeque<PacketSendEntry>,
+    // Number of critical packets in the send queue
+    critical_count: usize,
+    // Whether channels are given shares of sends according to their weights, and the virtual time
+    // of the packet most recently sent if so
+    weighted: bool,
+    virtual_time: u64,
 
     base_id: u32,
     next_id: u32,
@@ -77,16 +204,30 @@ pub struct PacketSender {
     window_mask: u32,
 
     window_parent_id: Option<u32>,
-    channels: Box<[Channel]>,
+    channels: Channels<CHANNELS>,
+    // Sequence ID of the most recent reliable packet sent with each channel and ordering key
+    key_parents: HashMap<(u8, u16), u32>,
+    stale_bound_count: usize,
+    // Channels with watermarks, or whose watermarks were removed since last polled
+    watermark_flags: ChannelMask,
 
     max_alloc: usize,
     alloc: usize,
+    // Bytes reported as buffered by a backlogged receiver, which are reserved from the allocation
+    // limit while other packets are in flight
+    remote_backlog: usize,
 
     total_size: usize,
+    // Outcomes of traced packets which have left the send queue or transfer window
+    trace_outcomes: Vec<(u64, TraceOutcome)>,
+    // Number of packets in the transfer window awaiting delivery confirmation, and the channel ID
+    // and tag of each packet confirmed since last polled
+    unconfirmed_count: usize,
+    confirmations: Vec<(u8, u64)>,
 }
 
-impl PacketSender {
-    pub fn new(window_size: u32, base_id: u32, max_alloc: usize) -> Self {
+impl<const CHANNELS: usize> PacketSender<CHANNELS> {
+    pub fn new(window_size: u32, base_id: u32, max_alloc: usize, channel_count: usize) -> Self {
         debug_assert!(window_size > 0);
         debug_assert!(window_size <= MAX_PACKET_WINDOW_SIZE);
         debug_assert!(window_size & (window_size - 1) == 0);
@@ -95,12 +236,13 @@ impl PacketSender {
 
         let window: Vec<Option<WindowEntry>> = (0 .. window_size).map(|_| None).collect();
 
-        let channels: Vec<Channel> = (0 .. CHANNEL_COUNT).map(|_| Channel::new()).collect();
-
         let max_alloc_ceil = ((max_alloc + MAX_FRAGMENT_SIZE - 1) / MAX_FRAGMENT_SIZE) * MAX_FRAGMENT_SIZE;
 
         Self {
             packet_send_queue: VecDeque::new(),
+            critical_count: 0,
+            weighted: false,
+            virtual_time: 0,
 
             base_id: base_id,
             next_id: base_id,
@@ -109,15 +251,28 @@ impl PacketSender {
             window_mask: window_size - 1,
 
             window_parent_id: None,
-            channels: channels.into_boxed_slice(),
+            channels: Channels::new(channel_count),
+            key_parents: HashMap::new(),
+            stale_bound_count: 0,
+            watermark_flags: ChannelMask::new(),
 
             max_alloc: max_alloc_ceil,
             alloc: 0,
+            remote_backlog: 0,
 
             total_size: 0,
+            trace_outcomes: Vec::new(),
+            unconfirmed_count: 0,
+            confirmations: Vec::new(),
         }
     }
 
+    // Reserves the given number of bytes from the allocation limit, as reported by a backlogged
+    // receiver (or zero, once the receiver has recovered)
+    pub fn set_remote_backlog(&mut self, bytes: usize) {
+        self.remote_backlog = bytes;
+    }
+
     pub fn pending_count(&self) -> usize {
         self.packet_send_queue.len()
     }
@@ -134,34 +289,375 @@ impl PacketSender {
         self.base_id
     }
 
+    // Returns the number of packets dropped by the sender, and the number of packets inferred to
+    // have been lost, respectively, on the given channel.
+    pub fn channel_counts(&self, channel_id: u8) -> (u64, u64) {
+        let channel = &self.channels[channel_id as usize];
+        (channel.drop_count, channel.loss_count)
+    }
+
+    // Returns the combined size of the given channel's packets in the send queue
+    pub fn queued_size(&self, channel_id: u8) -> usize {
+        self.channels[channel_id as usize].queued_size
+    }
+
+    // Returns true if the next packet in the send queue consists of multiple fragments
+    pub fn next_packet_fragmented(&self) -> bool {
+        self.next_index().is_some_and(|index| self.packet_send_queue[index].data.len() > MAX_FRAGMENT_SIZE)
+    }
+
+    // Returns true if the send queue contains a critical packet
+    pub fn has_critical_packets(&self) -> bool {
+        self.critical_count > 0
+    }
+
+    // Returns true if the next packet in the send queue is critical
+    pub fn next_packet_critical(&self) -> bool {
+        self.next_index().is_some_and(|index| self.packet_send_queue[index].is_critical())
+    }
+
+    pub fn set_stale_bound(&mut self, channel_id: u8, bound_ms: Option<u64>) {
+        debug_assert!((channel_id as usize) < self.channels.len());
+
+        let channel = &mut self.channels[channel_id as usize];
+
+        if channel.stale_bound_ms.is_some() {
+            self.stale_bound_count -= 1;
+        }
+
+        if bound_ms.is_some() {
+            self.stale_bound_count += 1;
+        }
+
+        channel.stale_bound_ms = bound_ms;
+    }
+
+    pub fn set_watermarks(&mut self, channel_id: u8, watermarks: Option<Watermarks>) {
+        debug_assert!((channel_id as usize) < self.channels.len());
+
+        // Removed watermarks remain flagged until polled, so that a paused channel is resumed
+        self.channels[channel_id as usize].watermarks = watermarks;
+        self.watermark_flags.insert(channel_id);
+    }
+
+    // Returns a backpressure signal for each channel whose queued size has crossed one of its
+    // watermarks since this function was last called.
+    pub fn poll_backpressure(&mut self) -> Vec<(u8, Backpressure)> {
+        let mut signals = Vec::new();
+        let flags = self.watermark_flags;
+
+        for channel_id in flags.iter() {
+            let channel = &mut self.channels[channel_id as usize];
+
+            if let Some(watermarks) = channel.watermarks {
+                if !channel.paused && channel.queued_size >= watermarks.high {
+                    channel.paused = true;
+                    signals.push((channel_id, Backpressure::Pause));
+                } else if channel.paused && channel.queued_size <= watermarks.low {
+                    channel.paused = false;
+                    signals.push((channel_id, Backpressure::Resume));
+                }
+            } else {
+                if channel.paused {
+                    channel.paused = false;
+                    signals.push((channel_id, Backpressure::Resume));
+                }
+
+                self.watermark_flags.remove(channel_id);
+            }
+        }
+
+        signals
+    }
+
     // Places a user packet on the send queue.
-    pub fn enqueue_packet(&mut self, data: Box<[u8]>, channel_id: u8, mode: SendMode, flush_id: u32) {
-        debug_assert!(data.len() <= MAX_PACKET_SIZE);
-        debug_assert!(data.len() <= self.max_alloc);
-        debug_assert!((channel_id as usize) < CHANNEL_COUNT);
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub fn enqueue_packet(&mut self, data: PacketData, channel_id: u8, mode: SendMode, flush_id: u32, now_ms: u64) {
+        self.enqueue_packet_with(data, channel_id, mode, SendOptions::default(), None, flush_id, now_ms);
+    }
+
+    // Places a user packet on the send queue with the given options. If a send ID is given, the
+    // packet may be canceled using that ID until it has been sent.
+    #[allow(clippy::too_many_arguments)]
+    pub fn enqueue_packet_with(&mut self, data: PacketData, channel_id: u8, mode: SendMode, options: SendOptions,
+                               send_id: Option<u64>, flush_id: u32, now_ms: u64) {
+        debug_assert!(options.ttl.is_none() || mode == SendMode::TimeSensitive || mode == SendMode::Unreliable);
+        debug_assert!(options.confirm_tag.is_none() || mode == SendMode::Reliable || mode == SendMode::ReliableUnordered);
 
-        self.total_size += data.len();
-        self.packet_send_queue.push_back(PacketSendEntry::new(data, channel_id, mode, flush_id));
+        self.push_packet(PacketSendEntry { data, channel_id, mode, options, send_id, flush_id, enqueue_ms: now_ms });
     }
 
-    // Pulls a single packet from the send queue, respecting both the maximum allocation limit, and
-    // the maximum transfer window.
-    pub fn emit_packet(&mut self, flush_id: u32) -> Option<(PendingPacketRc, bool)> {
-        while let Some(packet) = self.packet_send_queue.front() {
-            match packet.mode {
-                SendMode::TimeSensitive => {
-                    if packet.flush_id != flush_id {
-                        self.total_size -= packet.data.len();
-                        self.packet_send_queue.pop_front();
-                    } else {
-                        break;
+    // Removes the packet with the given send ID from the send queue. Returns false if no such
+    // packet is queued.
+    pub fn cancel_queued(&mut self, send_id: u64) -> bool {
+        let index = match self.packet_send_queue.iter().position(|packet| packet.send_id == Some(send_id)) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let packet = self.packet_send_queue.remove(index).unwrap();
+
+        let channel = &mut self.channels[packet.channel_id as usize];
+        channel.drop_count += 1;
+        channel.queued_count -= 1;
+        channel.queued_size -= packet.data.len();
+        self.total_size -= packet.data.len();
+        if packet.is_critical() {
+            self.critical_count -= 1;
+        }
+        if let Some(trace_id) = packet.options.trace_id {
+            self.trace_outcomes.push((trace_id, TraceOutcome::Dropped));
+        }
+
+        true
+    }
+
+    // Returns the packet in the transfer window with the given send ID, if it will not be resent
+    // and has not already been canceled.
+    pub fn emitted_packet(&self, send_id: u64) -> Option<PendingPacketRc> {
+        let span = packet_id::sub(self.next_id, self.base_id);
+
+        (0 .. span).filter_map(|i| self.window[window_index!(self, packet_id::add(self.base_id, i))].as_ref())
+            .find(|entry| entry.send_id == Some(send_id) && !entry.resend && !entry.canceled)
+            .map(|entry| Rc::clone(&entry.packet))
+    }
+
+    // Marks the packet in the transfer window with the given send ID as canceled, once its
+    // fragments have been removed from the pending queue. A canceled packet is counted as dropped
+    // rather than lost once forgotten.
+    pub fn cancel_emitted(&mut self, send_id: u64) {
+        let span = packet_id::sub(self.next_id, self.base_id);
+
+        for i in 0 .. span {
+            let window_idx = window_index!(self, packet_id::add(self.base_id, i));
+
+            if let Some(entry) = self.window[window_idx].as_mut() {
+                if entry.send_id == Some(send_id) {
+                    debug_assert!(!entry.resend);
+                    // The receiver is told to skip the packet, as though it had been lost
+                    entry.canceled = true;
+                    entry.abandoned = true;
+                    self.channels[entry.channel_id as usize].drop_count += 1;
+                    return;
+                }
+            }
+        }
+    }
+
+    // Confirms the delivery of each packet in the transfer window whose fragments have all been
+    // acknowledged. (Packets which leave the transfer window are confirmed as they are forgotten.)
+    pub fn confirm_acknowledged(&mut self) {
+        if self.unconfirmed_count == 0 {
+            return;
+        }
+
+        let mut sequence_id = self.base_id;
+
+        while sequence_id != self.next_id {
+            let entry = self.window[window_index!(self, sequence_id)].as_mut().unwrap();
+
+            if entry.confirm_tag.is_some() && entry.packet.borrow().all_fragments_acknowledged() {
+                self.confirmations.push((entry.channel_id, entry.confirm_tag.take().unwrap()));
+                self.unconfirmed_count -= 1;
+            }
+
+            sequence_id = packet_id::add(sequence_id, 1);
+        }
+    }
+
+    // Returns the channel ID and tag of each packet whose delivery has been confirmed since last
+    // polled, in the order they were confirmed
+    pub fn poll_confirmations(&mut self) -> Vec<(u8, u64)> {
+        std::mem::take(&mut self.confirmations)
+    }
+
+    // Returns the outcome of each traced packet which has been dropped, or forgotten after being
+    // sent, since last polled
+    pub fn poll_trace_outcomes(&mut self) -> Vec<(u64, TraceOutcome)> {
+        std::mem::take(&mut self.trace_outcomes)
+    }
+
+    fn push_packet(&mut self, packet: PacketSendEntry) {
+        debug_assert!(packet.data.len() <= MAX_PACKET_SIZE);
+        debug_assert!(packet.data.len() <= self.max_alloc);
+        debug_assert!((packet.channel_id as usize) < self.channels.len() ||
+                      packet.channel_id == frame::serial::CONTROL_CHANNEL_ID);
+
+        if packet.is_critical() {
+            self.critical_count += 1;
+        }
+
+        let channel = &mut self.channels[packet.channel_id as usize];
+
+        // A channel which had nothing to send may not claim the share it left unused
+        if channel.queued_count == 0 {
+            channel.virtual_time = channel.virtual_time.max(self.virtual_time);
+        }
+
+        channel.queued_count += 1;
+        channel.queued_size += packet.data.len();
+
+        self.total_size += packet.data.len();
+        self.packet_send_queue.push_back(packet);
+    }
+
+    // Sets the weight of each channel, indexed by channel ID, where channels beyond the end of the
+    // list have weight 1. Once set, each channel with packets in the send queue is given a share of
+    // sends in proportion to its weight, rather than packets being sent strictly in the order they
+    // were enqueued. Packets on the same channel are still sent in order.
+    pub fn set_channel_weights(&mut self, weights: &[u32]) {
+        debug_assert!(weights.iter().all(|&weight| weight > 0));
+
+        for (channel_id, channel) in self.channels.application.iter_mut().enumerate() {
+            channel.weight = weights.get(channel_id).copied().unwrap_or(1);
+        }
+
+        self.weighted = true;
+    }
+
+    // Returns the index in the send queue of the next packet to be sent. When sends are weighted,
+    // this is the first packet of the channel with the earliest virtual time, as in start-time fair
+    // queueing.
+    fn next_index(&self) -> Option<usize> {
+        if !self.weighted {
+            return if self.packet_send_queue.is_empty() { None } else { Some(0) };
+        }
+
+        let channel_id = self.channels.enumerate()
+            .filter(|(_, channel)| channel.queued_count > 0)
+            .min_by_key(|(_, channel)| channel.virtual_time)
+            .map(|(channel_id, _)| channel_id as u8)?;
+
+        self.packet_send_queue.iter().position(|packet| packet.channel_id == channel_id)
+    }
+
+    // Returns a description of each packet in the send queue, in the order they were enqueued. This
+    // is the order they will be sent, unless channel weights have been set.
+    pub fn queued_packets(&self, now_ms: u64) -> impl Iterator<Item = QueuedPacket<'_>> {
+        self.packet_send_queue.iter().map(move |packet| packet.describe(now_ms))
+    }
+
+    // Reorders or drops packets in the send queue according to the given function. Promoted,
+    // kept, and demoted packets are sent in that order, and packets retain their relative order
+    // within each group. Returns the number of packets dropped.
+    pub fn reshape(&mut self, now_ms: u64, mut f: impl FnMut(&QueuedPacket) -> QueueAction) -> u64 {
+        let mut promoted = VecDeque::new();
+        let mut kept = VecDeque::new();
+        let mut demoted = VecDeque::new();
+        let mut drop_count = 0;
+
+        for packet in std::mem::take(&mut self.packet_send_queue).into_iter() {
+            match f(&packet.describe(now_ms)) {
+                QueueAction::Keep => kept.push_back(packet),
+                QueueAction::Promote => promoted.push_back(packet),
+                QueueAction::Demote => demoted.push_back(packet),
+                QueueAction::Drop => {
+                    let channel = &mut self.channels[packet.channel_id as usize];
+                    channel.drop_count += 1;
+                    channel.queued_count -= 1;
+                    channel.queued_size -= packet.data.len();
+                    self.total_size -= packet.data.len();
+                    if packet.is_critical() {
+                        self.critical_count -= 1;
+                    }
+                    if let Some(trace_id) = packet.options.trace_id {
+                        self.trace_outcomes.push((trace_id, TraceOutcome::Dropped));
                     }
+                    drop_count += 1;
                 }
-                _ => break
             }
         }
 
-        if let Some(packet) = self.packet_send_queue.front() {
+        promoted.append(&mut kept);
+        promoted.append(&mut demoted);
+        self.packet_send_queue = promoted;
+
+        drop_count
+    }
+
+    // If the send queue contains more than max_backlog bytes, drops any unreliable packets which
+    // have remained in the queue longer than their channel's staleness bound. Returns the number of
+    // packets dropped, and their total size in bytes.
+    pub fn drop_stale(&mut self, now_ms: u64, max_backlog: usize) -> (u64, u64) {
+        if self.stale_bound_count == 0 {
+            return (0, 0);
+        }
+
+        let backlog: usize = self.packet_send_queue.iter().map(|packet| packet.data.len()).sum();
+
+        if backlog <= max_backlog {
+            return (0, 0);
+        }
+
+        let channels = &mut self.channels;
+        let critical_count = &mut self.critical_count;
+        let trace_outcomes = &mut self.trace_outcomes;
+        let mut drop_count = 0;
+        let mut drop_size = 0;
+
+        self.packet_send_queue.retain(|packet| {
+            let droppable = match packet.mode {
+                SendMode::TimeSensitive | SendMode::Unreliable => true,
+                SendMode::Persistent | SendMode::Reliable | SendMode::ReliableUnordered => false,
+            };
+
+            if droppable {
+                let channel = &mut channels[packet.channel_id as usize];
+
+                if let Some(bound_ms) = channel.stale_bound_ms {
+                    if now_ms.saturating_sub(packet.enqueue_ms) > bound_ms {
+                        channel.drop_count += 1;
+                        channel.queued_count -= 1;
+                        channel.queued_size -= packet.data.len();
+                        drop_count += 1;
+                        drop_size += packet.data.len();
+                        if packet.is_critical() {
+                            *critical_count -= 1;
+                        }
+                        if let Some(trace_id) = packet.options.trace_id {
+                            trace_outcomes.push((trace_id, TraceOutcome::Dropped));
+                        }
+                        return false;
+                    }
+                }
+            }
+
+            true
+        });
+
+        self.total_size -= drop_size;
+
+        (drop_count, drop_size as u64)
+    }
+
+    // Pulls a single packet from the send queue, respecting both the maximum allocation limit, and
+    // the maximum transfer window. Time-sensitive packets from previous flushes, and packets which
+    // have outlived their TTL, are dropped along the way.
+    pub fn emit_packet(&mut self, flush_id: u32, now_ms: u64) -> Option<(PendingPacketRc, bool)> {
+        while let Some(index) = self.next_index() {
+            let packet = &self.packet_send_queue[index];
+
+            if !packet.expired(flush_id, now_ms) {
+                break;
+            }
+
+            self.total_size -= packet.data.len();
+            let channel = &mut self.channels[packet.channel_id as usize];
+            channel.drop_count += 1;
+            channel.queued_count -= 1;
+            channel.queued_size -= packet.data.len();
+            if packet.is_critical() {
+                self.critical_count -= 1;
+            }
+            if let Some(trace_id) = packet.options.trace_id {
+                self.trace_outcomes.push((trace_id, TraceOutcome::Dropped));
+            }
+            self.packet_send_queue.remove(index);
+        }
+
+        if let Some(index) = self.next_index() {
+            let packet = &self.packet_send_queue[index];
+
             if packet_id::sub(self.next_id, self.base_id) >= self.window_size {
                 return None;
             }
@@ -172,11 +668,29 @@ impl PacketSender {
                 return None;
             }
 
-            let packet = self.packet_send_queue.pop_front().unwrap();
+            // The reservation is ignored if nothing is in flight, so that a stale report can never
+            // stall the sender indefinitely
+            if self.alloc > 0 && self.alloc + self.remote_backlog + packet_alloc_size > self.max_alloc {
+                return None;
+            }
+
+            let packet = self.packet_send_queue.remove(index).unwrap();
+
+            if packet.is_critical() {
+                self.critical_count -= 1;
+            }
 
             let sequence_id = self.next_id;
             let ref mut channel = self.channels[packet.channel_id as usize];
 
+            channel.queued_count -= 1;
+            channel.queued_size -= packet.data.len();
+
+            if self.weighted {
+                self.virtual_time = channel.virtual_time;
+                channel.virtual_time += (packet.data.len() as u64 + 1) * VIRTUAL_TIME_SCALE / channel.weight as u64;
+            }
+
             let window_parent_lead =
                 if let Some(parent_id) = self.window_parent_id {
                     let lead = packet_id::sub(sequence_id, parent_id);
@@ -186,8 +700,19 @@ impl PacketSender {
                     0
                 };
 
+            // A keyed packet's channel parent is the previous reliable packet with the same key. An
+            // unordered packet has no channel parent, and is marked as keyed so that the receiver
+            // orders it independently of the channel.
+            let unordered = packet.mode == SendMode::ReliableUnordered;
+
+            let channel_parent_id = match packet.options.key {
+                _ if unordered => None,
+                Some(key) => self.key_parents.get(&(packet.channel_id, key)).copied(),
+                None => channel.parent_id,
+            };
+
             let channel_parent_lead =
-                if let Some(parent_id) = channel.parent_id {
+                if let Some(parent_id) = channel_parent_id {
                     let lead = packet_id::sub(sequence_id, parent_id);
                     debug_assert!(lead <= u16::MAX as u32);
                     lead as u16
@@ -195,23 +720,49 @@ impl PacketSender {
                     0
                 };
 
-            let pending_packet = Rc::new(RefCell::new(PendingPacket::new(packet.data,
-                                                                         packet.channel_id,
-                                                                         sequence_id,
-                                                                         window_parent_lead,
-                                                                         channel_parent_lead)));
+            let mut pending_packet = PendingPacket::new(packet.data,
+                                                        packet.channel_id,
+                                                        sequence_id,
+                                                        window_parent_lead,
+                                                        channel_parent_lead,
+                                                        packet.options.key.is_some() || unordered);
+
+            if packet.options.trace_id.is_some() {
+                pending_packet.set_traced();
+            }
+
+            let pending_packet = Rc::new(RefCell::new(pending_packet));
 
             let pending_packet_clone = Rc::clone(&pending_packet);
 
+            let resend = match packet.mode {
+                SendMode::TimeSensitive => false,
+                SendMode::Unreliable => false,
+                SendMode::Persistent => true,
+                SendMode::Reliable => true,
+                SendMode::ReliableUnordered => true,
+            };
+
             let window_idx = window_index!(self, sequence_id);
 
             debug_assert!(self.window[window_idx].is_none());
             self.window[window_idx] = Some(WindowEntry {
                 packet: pending_packet,
                 alloc_size: packet_alloc_size,
-                channel_id: packet.channel_id
+                channel_id: packet.channel_id,
+                key: packet.options.key,
+                trace_id: packet.options.trace_id,
+                resend,
+                abandoned: false,
+                send_id: packet.send_id,
+                canceled: false,
+                confirm_tag: packet.options.confirm_tag,
             });
 
+            if packet.options.confirm_tag.is_some() {
+                self.unconfirmed_count += 1;
+            }
+
             self.next_id = packet_id::add(self.next_id, 1);
 
             self.alloc += packet_alloc_size;
@@ -219,18 +770,19 @@ impl PacketSender {
             match packet.mode {
                 SendMode::Reliable => {
                     self.window_parent_id = Some(sequence_id);
-                    channel.parent_id = Some(sequence_id);
+                    match packet.options.key {
+                        Some(key) => { self.key_parents.insert((packet.channel_id, key), sequence_id); }
+                        None => channel.parent_id = Some(sequence_id),
+                    }
+                }
+                // The window may not pass an unordered packet until it has been received, but no
+                // other packet waits for it to be delivered
+                SendMode::ReliableUnordered => {
+                    self.window_parent_id = Some(sequence_id);
                 }
                 _ => ()
             }
 
-            let resend = match packet.mode {
-                SendMode::TimeSensitive => false,
-                SendMode::Unreliable => false,
-                SendMode::Persistent => true,
-                SendMode::Reliable => true,
-            };
-
             return Some((pending_packet_clone, resend));
         }
 
@@ -265,6 +817,38 @@ impl PacketSender {
                 }
             }
 
+            if let Some(key) = entry.key {
+                let key_pair = (entry.channel_id, key);
+                if self.key_parents.get(&key_pair) == Some(&self.base_id) {
+                    self.key_parents.remove(&key_pair);
+                }
+            }
+
+            let delivered = entry.packet.borrow().all_fragments_acknowledged();
+
+            if !delivered && !entry.canceled {
+                channel.loss_count += 1;
+            }
+
+            if let Some(trace_id) = entry.trace_id {
+                let outcome = if delivered {
+                    TraceOutcome::Delivered
+                } else if entry.canceled {
+                    TraceOutcome::Dropped
+                } else {
+                    TraceOutcome::Lost
+                };
+
+                self.trace_outcomes.push((trace_id, outcome));
+            }
+
+            // The receiver cannot move past a reliable packet without receiving it, even if some of
+            // its fragment acknowledgements were lost
+            if let Some(tag) = entry.confirm_tag {
+                self.confirmations.push((entry.channel_id, tag));
+                self.unconfirmed_count -= 1;
+            }
+
             self.alloc -= entry.alloc_size;
             self.total_size -= entry.packet.borrow().size();
 
@@ -273,14 +857,141 @@ impl PacketSender {
             self.base_id = packet_id::add(self.base_id, 1);
         }
     }
+
+    // Drops every packet in the send queue and transfer window, and restarts the transfer window
+    // at the next sequence ID, as though the receiver had acknowledged all packets sent so far.
+    // Channel settings are retained. Returns the number of packets dropped without having been
+    // fully acknowledged.
+    pub fn reset(&mut self) -> u64 {
+        let mut drop_count = 0;
+
+        for packet in self.packet_send_queue.drain(..) {
+            let channel = &mut self.channels[packet.channel_id as usize];
+            channel.drop_count += 1;
+            channel.queued_count -= 1;
+            channel.queued_size -= packet.data.len();
+            self.total_size -= packet.data.len();
+            drop_count += 1;
+            if let Some(trace_id) = packet.options.trace_id {
+                self.trace_outcomes.push((trace_id, TraceOutcome::Dropped));
+            }
+        }
+
+        self.critical_count = 0;
+
+        // Packets forgotten by a reset have not necessarily been delivered
+        let mut sequence_id = self.base_id;
+
+        while sequence_id != self.next_id {
+            self.window[window_index!(self, sequence_id)].as_mut().unwrap().confirm_tag = None;
+            sequence_id = packet_id::add(sequence_id, 1);
+        }
+
+        self.unconfirmed_count = 0;
+
+        let loss_count: u64 = self.channels.iter().map(|channel| channel.loss_count).sum();
+
+        self.acknowledge(self.next_id);
+
+        drop_count += self.channels.iter().map(|channel| channel.loss_count).sum::<u64>() - loss_count;
+
+        drop_count
+    }
+
+    // Marks the given packet as abandoned if it will not be resent. The caller has determined that
+    // one of its fragments was lost, so the receiver will never assemble it.
+    pub fn abandon_packet(&mut self, sequence_id: u32) {
+        if packet_id::sub(sequence_id, self.base_id) >= packet_id::sub(self.next_id, self.base_id) {
+            return;
+        }
+
+        let window_idx = window_index!(self, sequence_id);
+
+        if let Some(entry) = self.window[window_idx].as_mut() {
+            if !entry.resend {
+                entry.abandoned = true;
+            }
+        }
+    }
+
+    // Returns up to `max_count` runs of consecutive abandoned packets in the transfer window, in
+    // order. Because a window parent only changes when a reliable packet is sent, every packet in a
+    // run shares the parent of the first.
+    pub fn drop_ranges(&self, max_count: usize) -> Vec<frame::DropRange> {
+        let mut ranges: Vec<frame::DropRange> = Vec::new();
+
+        let mut sequence_id = self.base_id;
+        let mut run_open = false;
+
+        while sequence_id != self.next_id {
+            let entry = self.window[window_index!(self, sequence_id)].as_ref().unwrap();
+
+            if entry.abandoned && !entry.packet.borrow().all_fragments_acknowledged() {
+                match ranges.last_mut() {
+                    Some(range) if run_open && range.count < u16::MAX => range.count += 1,
+                    _ => {
+                        if ranges.len() == max_count {
+                            break;
+                        }
+
+                        ranges.push(frame::DropRange {
+                            base_id: sequence_id,
+                            count: 1,
+                            window_parent_lead: entry.packet.borrow().window_parent_lead(),
+                        });
+                    }
+                }
+
+                run_open = true;
+            } else {
+                run_open = false;
+            }
+
+            sequence_id = packet_id::add(sequence_id, 1);
+        }
+
+        ranges
+    }
+
+    // Responds to a fragment acknowledgement. The indicated fragments of the given packet are
+    // marked as received, and will not be resent.
+    pub fn acknowledge_fragments(&mut self, sequence_id: u32, base_fragment_id: u16, bitfield: u32) {
+        if !packet_id::is_valid(sequence_id) {
+            return;
+        }
+
+        if packet_id::sub(sequence_id, self.base_id) >= packet_id::sub(self.next_id, self.base_id) {
+            return;
+        }
+
+        let window_idx = window_index!(self, sequence_id);
+
+        if let Some(entry) = self.window[window_idx].as_ref() {
+            let mut packet = entry.packet.borrow_mut();
+            let last_fragment_id = packet.last_fragment_id();
+
+            for bit in 0 .. 32 {
+                if bitfield & (1 << bit) != 0 {
+                    match base_fragment_id.checked_add(bit) {
+                        Some(fragment_id) if fragment_id <= last_fragment_id => packet.acknowledge_fragment(fragment_id),
+                        _ => break,
+                    }
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn new_packet_data(sequence_id: u32) -> Box<[u8]> {
-        sequence_id.to_be_bytes().into()
+    use crate::CHANNEL_COUNT;
+
+    use std::time;
+
+    fn new_packet_data(sequence_id: u32) -> PacketData {
+        PacketData::Owned(sequence_id.to_be_bytes().into())
     }
 
     fn packet_info(emit_result: (PendingPacketRc, bool)) -> (u32, u8, u16, u16, bool) {
@@ -306,19 +1017,19 @@ mod tests {
 
     #[test]
     fn basic() {
-        let mut tx = PacketSender::new(MAX_PACKET_WINDOW_SIZE, 0, 10000);
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
 
-        tx.enqueue_packet(new_packet_data(0), 0, SendMode::TimeSensitive, 0);
-        tx.enqueue_packet(new_packet_data(1), 0, SendMode::Unreliable, 0);
-        tx.enqueue_packet(new_packet_data(2), 0, SendMode::Persistent, 0);
-        tx.enqueue_packet(new_packet_data(3), 0, SendMode::Reliable, 0);
+        tx.enqueue_packet(new_packet_data(0), 0, SendMode::TimeSensitive, 0, 0);
+        tx.enqueue_packet(new_packet_data(1), 0, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(2), 0, SendMode::Persistent, 0, 0);
+        tx.enqueue_packet(new_packet_data(3), 0, SendMode::Reliable, 0, 0);
 
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (0, 0, 0, 0, false));
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (1, 0, 0, 0, false));
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (2, 0, 0, 0, true));
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (3, 0, 0, 0, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (0, 0, 0, 0, false));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (1, 0, 0, 0, false));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (2, 0, 0, 0, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (3, 0, 0, 0, true));
 
-        assert!(tx.emit_packet(0).is_none());
+        assert!(tx.emit_packet(0, 0).is_none());
     }
 
     #[test]
@@ -335,31 +1046,31 @@ mod tests {
               w  c0 c1
         */
 
-        let mut tx = PacketSender::new(MAX_PACKET_WINDOW_SIZE, 0, 10000);
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
 
-        tx.enqueue_packet(new_packet_data(0), 1, SendMode::Unreliable, 0);
-        tx.enqueue_packet(new_packet_data(1), 1, SendMode::Reliable, 0);
-        tx.enqueue_packet(new_packet_data(2), 1, SendMode::Unreliable, 0);
+        tx.enqueue_packet(new_packet_data(0), 1, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(1), 1, SendMode::Reliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(2), 1, SendMode::Unreliable, 0, 0);
 
-        tx.enqueue_packet(new_packet_data(3), 0, SendMode::Reliable, 0);
-        tx.enqueue_packet(new_packet_data(4), 0, SendMode::Unreliable, 0);
-        tx.enqueue_packet(new_packet_data(5), 0, SendMode::Unreliable, 0);
-        tx.enqueue_packet(new_packet_data(6), 0, SendMode::Reliable, 0);
+        tx.enqueue_packet(new_packet_data(3), 0, SendMode::Reliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(4), 0, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(5), 0, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(6), 0, SendMode::Reliable, 0, 0);
 
-        tx.enqueue_packet(new_packet_data(7), 1, SendMode::Reliable, 0);
+        tx.enqueue_packet(new_packet_data(7), 1, SendMode::Reliable, 0, 0);
 
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (0, 1, 0, 0, false));
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (1, 1, 0, 0, true));
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (2, 1, 1, 1, false));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (0, 1, 0, 0, false));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (1, 1, 0, 0, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (2, 1, 1, 1, false));
                                                                                  
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (3, 0, 2, 0, true));
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (4, 0, 1, 1, false));
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (5, 0, 2, 2, false));
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (6, 0, 3, 3, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (3, 0, 2, 0, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (4, 0, 1, 1, false));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (5, 0, 2, 2, false));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (6, 0, 3, 3, true));
                                                                                  
-        assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (7, 1, 1, 6, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (7, 1, 1, 6, true));
 
-        assert!(tx.emit_packet(0).is_none());
+        assert!(tx.emit_packet(0, 0).is_none());
     }
 
     #[test]
@@ -375,7 +1086,7 @@ mod tests {
               w  c0 c1
         */
 
-        let mut tx = PacketSender::new(MAX_PACKET_WINDOW_SIZE, 0, 10000);
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
 
         let mut flush_id = 0;
 
@@ -384,32 +1095,159 @@ mod tests {
 
             tx.acknowledge(ref_id);
 
-            tx.enqueue_packet(new_packet_data(ref_id + 0), 1, SendMode::Unreliable, flush_id);
-            tx.enqueue_packet(new_packet_data(ref_id + 1), 1, SendMode::Reliable, flush_id);
-            tx.enqueue_packet(new_packet_data(ref_id + 2), 1, SendMode::Unreliable, flush_id);
+            tx.enqueue_packet(new_packet_data(ref_id + 0), 1, SendMode::Unreliable, flush_id, 0);
+            tx.enqueue_packet(new_packet_data(ref_id + 1), 1, SendMode::Reliable, flush_id, 0);
+            tx.enqueue_packet(new_packet_data(ref_id + 2), 1, SendMode::Unreliable, flush_id, 0);
 
-            tx.enqueue_packet(new_packet_data(ref_id + 3), 0, SendMode::Reliable, flush_id);
-            tx.enqueue_packet(new_packet_data(ref_id + 4), 0, SendMode::Unreliable, flush_id);
-            tx.enqueue_packet(new_packet_data(ref_id + 5), 0, SendMode::Reliable, flush_id);
+            tx.enqueue_packet(new_packet_data(ref_id + 3), 0, SendMode::Reliable, flush_id, 0);
+            tx.enqueue_packet(new_packet_data(ref_id + 4), 0, SendMode::Unreliable, flush_id, 0);
+            tx.enqueue_packet(new_packet_data(ref_id + 5), 0, SendMode::Reliable, flush_id, 0);
 
-            tx.enqueue_packet(new_packet_data(ref_id + 6), 1, SendMode::Reliable, flush_id);
+            tx.enqueue_packet(new_packet_data(ref_id + 6), 1, SendMode::Reliable, flush_id, 0);
 
-            assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (ref_id + 0, 1, 0, 0, false));
-            assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (ref_id + 1, 1, 0, 0, true));
-            assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (ref_id + 2, 1, 1, 1, false));
+            assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (ref_id + 0, 1, 0, 0, false));
+            assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (ref_id + 1, 1, 0, 0, true));
+            assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (ref_id + 2, 1, 1, 1, false));
                                                                                     
-            assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (ref_id + 3, 0, 2, 0, true));
-            assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (ref_id + 4, 0, 1, 1, false));
-            assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (ref_id + 5, 0, 2, 2, true));
+            assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (ref_id + 3, 0, 2, 0, true));
+            assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (ref_id + 4, 0, 1, 1, false));
+            assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (ref_id + 5, 0, 2, 2, true));
                                                                                     
-            assert_eq!(packet_info(tx.emit_packet(0).unwrap()), (ref_id + 6, 1, 1, 5, true));
+            assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (ref_id + 6, 1, 1, 5, true));
 
-            assert!(tx.emit_packet(0).is_none());
+            assert!(tx.emit_packet(0, 0).is_none());
 
             flush_id = packet_id::add(flush_id, 1);
         }
     }
 
+    #[test]
+    fn keyed_parent_leads() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        let keyed = |key| SendOptions { key: Some(key), .. Default::default() };
+
+        tx.enqueue_packet_with(new_packet_data(0), 0, SendMode::Reliable, keyed(7), None, 0, 0);
+        tx.enqueue_packet(new_packet_data(1), 0, SendMode::Reliable, 0, 0);
+        tx.enqueue_packet_with(new_packet_data(2), 0, SendMode::Reliable, keyed(8), None, 0, 0);
+        tx.enqueue_packet_with(new_packet_data(3), 0, SendMode::Unreliable, keyed(7), None, 0, 0);
+        tx.enqueue_packet(new_packet_data(4), 0, SendMode::Unreliable, 0, 0);
+
+        // Keyed packets are parented by the previous reliable packet with the same key only
+        let emit_result = tx.emit_packet(0, 0).unwrap();
+        assert!(emit_result.0.borrow().datagram(0).keyed);
+        assert_eq!(packet_info(emit_result), (0, 0, 0, 0, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (1, 0, 1, 0, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (2, 0, 1, 0, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (3, 0, 1, 3, false));
+        let emit_result = tx.emit_packet(0, 0).unwrap();
+        assert!(!emit_result.0.borrow().datagram(0).keyed);
+        assert_eq!(packet_info(emit_result), (4, 0, 2, 3, false));
+
+        // Acknowledged key parents are forgotten
+        tx.acknowledge(1);
+        tx.enqueue_packet_with(new_packet_data(5), 0, SendMode::Reliable, keyed(7), None, 0, 0);
+        tx.enqueue_packet_with(new_packet_data(6), 0, SendMode::Reliable, keyed(8), None, 0, 0);
+
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (5, 0, 3, 0, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (6, 0, 1, 4, true));
+
+        assert!(tx.emit_packet(0, 0).is_none());
+    }
+
+    #[test]
+    fn unordered_parent_leads() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        tx.enqueue_packet(new_packet_data(0), 0, SendMode::Reliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(1), 0, SendMode::ReliableUnordered, 0, 0);
+        let keyed = |key| SendOptions { key: Some(key), .. Default::default() };
+
+        tx.enqueue_packet_with(new_packet_data(2), 0, SendMode::ReliableUnordered, keyed(7), None, 0, 0);
+        tx.enqueue_packet(new_packet_data(3), 0, SendMode::Reliable, 0, 0);
+
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (0, 0, 0, 0, true));
+
+        // Unordered packets are parented by the window only, and are marked as keyed
+        let emit_result = tx.emit_packet(0, 0).unwrap();
+        assert!(emit_result.0.borrow().datagram(0).keyed);
+        assert_eq!(packet_info(emit_result), (1, 0, 1, 0, true));
+        let emit_result = tx.emit_packet(0, 0).unwrap();
+        assert!(emit_result.0.borrow().datagram(0).keyed);
+        assert_eq!(packet_info(emit_result), (2, 0, 1, 0, true));
+
+        // Subsequent packets are not ordered after unordered packets
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (3, 0, 1, 3, true));
+
+        assert!(tx.emit_packet(0, 0).is_none());
+    }
+
+    #[test]
+    fn weighted_channels() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 100000, CHANNEL_COUNT);
+
+        tx.set_channel_weights(&[ 3 ]);
+
+        for i in 0 .. 8 {
+            tx.enqueue_packet(PacketData::Owned(vec![i; 100].into_boxed_slice()), 1, SendMode::Reliable, 0, 0);
+        }
+
+        for i in 0 .. 8 {
+            tx.enqueue_packet(PacketData::Owned(vec![i; 100].into_boxed_slice()), 0, SendMode::Reliable, 0, 0);
+        }
+
+        let mut sent = Vec::new();
+        while let Some((packet_rc, _)) = tx.emit_packet(0, 0) {
+            let packet_ref = packet_rc.borrow();
+            sent.push((packet_ref.channel_id(), packet_ref.datagram(0).data[0]));
+        }
+
+        // Channel 0 is given three sends for each of channel 1's, in order within each channel
+        assert_eq!(sent, vec![
+            (0, 0), (1, 0), (0, 1), (0, 2),
+            (0, 3), (1, 1), (0, 4), (0, 5),
+            (0, 6), (1, 2), (0, 7),
+            (1, 3), (1, 4), (1, 5), (1, 6), (1, 7),
+        ]);
+
+        // A channel which was idle shares equally rather than catching up on the sends it missed
+        for i in 0 .. 4 {
+            tx.enqueue_packet(PacketData::Owned(vec![i; 100].into_boxed_slice()), 1, SendMode::Reliable, 0, 0);
+            tx.enqueue_packet(PacketData::Owned(vec![i; 100].into_boxed_slice()), 2, SendMode::Reliable, 0, 0);
+        }
+
+        let channels: Vec<u8> = std::iter::from_fn(|| tx.emit_packet(0, 0)).map(|(packet_rc, _)| packet_rc.borrow().channel_id()).collect();
+        assert_eq!(channels, vec![ 2, 1, 2, 1, 2, 1, 2, 1 ]);
+    }
+
+    #[test]
+    fn remote_backlog() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, MAX_FRAGMENT_SIZE, CHANNEL_COUNT);
+
+        for i in 0 .. 4 {
+            tx.enqueue_packet(new_packet_data(i), 0, SendMode::Reliable, 0, 0);
+        }
+
+        // Each packet allocates 4 bytes, leaving room for two in flight
+        tx.set_remote_backlog(MAX_FRAGMENT_SIZE - 8);
+
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()).0, 0);
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()).0, 1);
+        assert!(tx.emit_packet(0, 0).is_none());
+
+        tx.set_remote_backlog(0);
+
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()).0, 2);
+
+        // The reservation never prevents a packet from being sent if nothing is in flight
+        tx.set_remote_backlog(MAX_FRAGMENT_SIZE);
+        assert!(tx.emit_packet(0, 0).is_none());
+
+        tx.acknowledge(3);
+
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()).0, 3);
+    }
+
     /*
     #[test]
     fn fragment_emission() {
@@ -472,6 +1310,355 @@ mod tests {
     }
     */
 
+    #[test]
+    fn stale_drop() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        tx.set_stale_bound(0, Some(100));
+
+        tx.enqueue_packet(new_packet_data(0), 0, SendMode::TimeSensitive, 0, 0);
+        tx.enqueue_packet(new_packet_data(1), 0, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(2), 0, SendMode::Reliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(3), 1, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(4), 0, SendMode::Unreliable, 0, 50);
+
+        // Not yet stale
+        assert_eq!(tx.drop_stale(100, 0), (0, 0));
+
+        // Stale, but no backlog
+        assert_eq!(tx.drop_stale(101, 20), (0, 0));
+
+        assert_eq!(tx.drop_stale(101, 0), (2, 8));
+        assert_eq!(tx.total_size(), 12);
+
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (0, 0, 0, 0, true));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (1, 1, 1, 0, false));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (2, 0, 2, 2, false));
+        assert!(tx.emit_packet(0, 0).is_none());
+
+        tx.set_stale_bound(0, None);
+        tx.enqueue_packet(new_packet_data(5), 0, SendMode::Unreliable, 0, 0);
+        assert_eq!(tx.drop_stale(1000, 0), (0, 0));
+    }
+
+    #[test]
+    fn ttl_expiry() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        let ttl = |ttl_ms| SendOptions { ttl: Some(time::Duration::from_millis(ttl_ms)), .. Default::default() };
+
+        tx.enqueue_packet_with(new_packet_data(0), 0, SendMode::Unreliable, ttl(50), None, 0, 0);
+        tx.enqueue_packet(new_packet_data(1), 0, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet_with(new_packet_data(2), 1, SendMode::TimeSensitive, ttl(100), None, 0, 0);
+        tx.enqueue_packet_with(new_packet_data(3), 0, SendMode::Unreliable, ttl(50), None, 0, 20);
+
+        // Packet 0 has expired, packets 2 and 3 have not
+        assert_eq!(packet_info(tx.emit_packet(0, 51).unwrap()), (0, 0, 0, 0, false));
+        assert_eq!(packet_info(tx.emit_packet(0, 51).unwrap()), (1, 1, 0, 0, false));
+        assert_eq!(packet_info(tx.emit_packet(0, 70).unwrap()), (2, 0, 0, 0, false));
+        assert!(tx.emit_packet(0, 70).is_none());
+
+        assert_eq!(tx.channel_counts(0), (1, 0));
+        assert_eq!(tx.channel_counts(1), (0, 0));
+        assert_eq!(tx.total_size(), 12);
+    }
+
+    #[test]
+    fn cancel() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        tx.enqueue_packet_with(new_packet_data(0), 0, SendMode::Unreliable, SendOptions::default(), Some(10), 0, 0);
+        tx.enqueue_packet_with(new_packet_data(1), 0, SendMode::Reliable, SendOptions::default(), Some(11), 0, 0);
+        tx.enqueue_packet_with(new_packet_data(2), 0, SendMode::Reliable, SendOptions::default(), Some(12), 0, 0);
+
+        // Queued packets may be canceled regardless of mode
+        assert!(tx.cancel_queued(12));
+        assert!(!tx.cancel_queued(12));
+        assert_eq!(tx.total_size(), 8);
+
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (0, 0, 0, 0, false));
+        assert_eq!(packet_info(tx.emit_packet(0, 0).unwrap()), (1, 0, 0, 0, true));
+        assert!(tx.emit_packet(0, 0).is_none());
+
+        // Emitted packets may only be canceled if they will not be resent
+        assert!(!tx.cancel_queued(10));
+        assert!(tx.emitted_packet(11).is_none());
+        assert_eq!(tx.emitted_packet(10).unwrap().borrow().sequence_id(), 0);
+
+        tx.cancel_emitted(10);
+        assert!(tx.emitted_packet(10).is_none());
+
+        assert_eq!(tx.drop_ranges(16), vec![ frame::DropRange { base_id: 0, count: 1, window_parent_lead: 0 } ]);
+
+        tx.acknowledge_fragments(1, 0, 1);
+        tx.acknowledge(2);
+
+        // Canceled packets are counted as dropped, not lost
+        assert_eq!(tx.channel_counts(0), (2, 0));
+    }
+
+    #[test]
+    fn confirmations() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        let confirmed = |tag| SendOptions { confirm_tag: Some(tag), .. Default::default() };
+
+        tx.enqueue_packet_with(new_packet_data(0), 1, SendMode::Reliable, confirmed(100), None, 0, 0);
+        tx.enqueue_packet_with(new_packet_data(1), 2, SendMode::ReliableUnordered, confirmed(101), None, 0, 0);
+        tx.enqueue_packet(new_packet_data(2), 0, SendMode::Reliable, 0, 0);
+        tx.enqueue_packet_with(new_packet_data(3), 0, SendMode::Reliable, confirmed(103), None, 0, 0);
+
+        for _ in 0 .. 4 {
+            tx.emit_packet(0, 0).unwrap();
+        }
+
+        tx.confirm_acknowledged();
+        assert_eq!(tx.poll_confirmations(), vec![]);
+
+        // Packet 1 is confirmed once acknowledged, ahead of packet 0
+        tx.acknowledge_fragments(1, 0, 1);
+        tx.confirm_acknowledged();
+        assert_eq!(tx.poll_confirmations(), vec![ (2, 101) ]);
+
+        // Packet 0 is confirmed once forgotten, even though its acknowledgement was lost
+        tx.acknowledge(2);
+        assert_eq!(tx.poll_confirmations(), vec![ (1, 100) ]);
+
+        // Packet 3 is not confirmed if forgotten by a reset
+        tx.reset();
+        assert_eq!(tx.poll_confirmations(), vec![]);
+    }
+
+    #[test]
+    fn channel_counts() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        tx.enqueue_packet(new_packet_data(0), 0, SendMode::TimeSensitive, 0, 0);
+        tx.enqueue_packet(new_packet_data(1), 0, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(2), 1, SendMode::Unreliable, 0, 0);
+
+        // Packet 0 is dropped
+        let (p1, _) = tx.emit_packet(1, 0).unwrap();
+        let (p2, _) = tx.emit_packet(1, 0).unwrap();
+
+        // Packet 1 is acknowledged, packet 2 is not
+        p1.borrow_mut().acknowledge_fragment(0);
+        drop(p2);
+
+        tx.acknowledge(2);
+
+        assert_eq!(tx.channel_counts(0), (1, 0));
+        assert_eq!(tx.channel_counts(1), (0, 1));
+    }
+
+    #[test]
+    fn trace_outcomes() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        tx.set_stale_bound(1, Some(100));
+
+        let traced = |trace_id| SendOptions { trace_id: Some(trace_id), .. Default::default() };
+
+        tx.enqueue_packet_with(new_packet_data(0), 0, SendMode::TimeSensitive, traced(10), None, 0, 0);
+        tx.enqueue_packet_with(new_packet_data(1), 0, SendMode::Unreliable, traced(11), None, 0, 0);
+        tx.enqueue_packet_with(new_packet_data(2), 0, SendMode::Reliable, traced(12), None, 0, 0);
+        tx.enqueue_packet(new_packet_data(3), 0, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet_with(new_packet_data(4), 1, SendMode::Unreliable, traced(14), None, 0, 0);
+
+        // Packet 4 becomes stale
+        assert_eq!(tx.drop_stale(101, 0), (1, 4));
+        assert_eq!(tx.poll_trace_outcomes(), vec![ (14, TraceOutcome::Dropped) ]);
+
+        // Packet 0 is dropped in the next flush
+        let (p1, _) = tx.emit_packet(1, 0).unwrap();
+        let (p2, _) = tx.emit_packet(1, 0).unwrap();
+        let (p3, _) = tx.emit_packet(1, 0).unwrap();
+        assert_eq!(tx.poll_trace_outcomes(), vec![ (10, TraceOutcome::Dropped) ]);
+
+        // Packets 2 and 3 are acknowledged, packet 1 is not
+        drop(p1);
+        p2.borrow_mut().acknowledge_fragment(0);
+        p3.borrow_mut().acknowledge_fragment(0);
+
+        // Outcomes are reported once packets have been forgotten
+        assert!(tx.poll_trace_outcomes().is_empty());
+
+        tx.acknowledge(3);
+
+        assert_eq!(tx.poll_trace_outcomes(), vec![ (11, TraceOutcome::Lost), (12, TraceOutcome::Delivered) ]);
+        assert!(tx.poll_trace_outcomes().is_empty());
+    }
+
+    #[test]
+    fn fragment_acknowledgement() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, MAX_FRAGMENT_SIZE * 64, CHANNEL_COUNT);
+
+        tx.enqueue_packet(PacketData::Owned(vec![0; MAX_FRAGMENT_SIZE * 40].into_boxed_slice()), 0, SendMode::Reliable, 0, 0);
+
+        let (p0, _) = tx.emit_packet(0, 0).unwrap();
+
+        tx.acknowledge_fragments(0, 0, 0x80000005);
+        tx.acknowledge_fragments(0, 32, 0xFFFFFFFF);
+
+        // Unknown packets are ignored
+        tx.acknowledge_fragments(1, 0, 0xFFFFFFFF);
+
+        let acknowledged = (0 ..= p0.borrow().last_fragment_id())
+            .filter(|&fragment_id| p0.borrow().fragment_acknowledged(fragment_id))
+            .collect::<Vec<_>>();
+
+        assert_eq!(acknowledged, vec![ 0, 2, 31, 32, 33, 34, 35, 36, 37, 38, 39 ]);
+    }
+
+    #[test]
+    fn drop_ranges() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        tx.enqueue_packet(new_packet_data(0), 0, SendMode::Reliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(1), 0, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(2), 0, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(3), 0, SendMode::Unreliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(4), 0, SendMode::Reliable, 0, 0);
+        tx.enqueue_packet(new_packet_data(5), 0, SendMode::TimeSensitive, 0, 0);
+
+        let packets = (0 .. 6).map(|_| tx.emit_packet(0, 0).unwrap().0).collect::<Vec<_>>();
+
+        // Reliable packets are never abandoned
+        for sequence_id in 0 .. 6 {
+            tx.abandon_packet(sequence_id);
+        }
+
+        assert_eq!(tx.drop_ranges(16), vec![
+            frame::DropRange { base_id: 1, count: 3, window_parent_lead: 1 },
+            frame::DropRange { base_id: 5, count: 1, window_parent_lead: 1 },
+        ]);
+
+        // Acknowledged packets need not be skipped
+        packets[2].borrow_mut().acknowledge_fragment(0);
+
+        assert_eq!(tx.drop_ranges(16), vec![
+            frame::DropRange { base_id: 1, count: 1, window_parent_lead: 1 },
+            frame::DropRange { base_id: 3, count: 1, window_parent_lead: 3 },
+            frame::DropRange { base_id: 5, count: 1, window_parent_lead: 1 },
+        ]);
+
+        assert_eq!(tx.drop_ranges(2).len(), 2);
+
+        // Ranges which pass beyond the receiver's window are forgotten
+        tx.acknowledge(4);
+
+        assert_eq!(tx.drop_ranges(16), vec![
+            frame::DropRange { base_id: 5, count: 1, window_parent_lead: 1 },
+        ]);
+    }
+
+    #[test]
+    fn critical_packets() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        assert!(!tx.has_critical_packets());
+
+        tx.enqueue_packet(PacketData::Owned(vec![0; 10].into_boxed_slice()), 0, SendMode::Reliable, 0, 0);
+        let critical = SendOptions { latency_class: LatencyClass::Critical, .. Default::default() };
+
+        tx.enqueue_packet_with(PacketData::Owned(vec![1; 10].into_boxed_slice()), 0, SendMode::TimeSensitive, critical, None, 0, 0);
+        tx.enqueue_packet_with(PacketData::Owned(vec![2; 10].into_boxed_slice()), 0, SendMode::Reliable, critical, None, 1, 0);
+
+        assert!(tx.has_critical_packets());
+        assert!(!tx.next_packet_critical());
+
+        tx.emit_packet(1, 0).unwrap();
+
+        // The time-sensitive critical packet is dropped
+        assert!(tx.next_packet_critical());
+        let (p1, _) = tx.emit_packet(1, 0).unwrap();
+        assert_eq!(p1.borrow().datagram(0).data, &[2; 10]);
+
+        assert!(!tx.has_critical_packets());
+        assert!(!tx.next_packet_critical());
+    }
+
+    #[test]
+    fn reshape() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        for i in 0 .. 6 {
+            let latency_class = if i == 4 { LatencyClass::Critical } else { LatencyClass::Normal };
+            tx.enqueue_packet_with(PacketData::Owned(vec![i; 10].into_boxed_slice()), i % 2, SendMode::Reliable,
+                                   SendOptions { latency_class, .. Default::default() }, None, 0, i as u64 * 10);
+        }
+
+        let ages: Vec<u64> = tx.queued_packets(100).map(|packet| packet.age_ms).collect();
+        assert_eq!(ages, vec![100, 90, 80, 70, 60, 50]);
+
+        let drop_count = tx.reshape(100, |packet| {
+            match packet.data[0] {
+                1 | 3 => QueueAction::Promote,
+                0 => QueueAction::Demote,
+                4 => QueueAction::Drop,
+                _ => QueueAction::Keep,
+            }
+        });
+
+        assert_eq!(drop_count, 1);
+        assert_eq!(tx.pending_count(), 5);
+        assert_eq!(tx.total_size(), 50);
+        assert_eq!(tx.channel_counts(0), (1, 0));
+        assert!(!tx.has_critical_packets());
+
+        let order: Vec<u8> = tx.queued_packets(100).map(|packet| packet.data[0]).collect();
+        assert_eq!(order, vec![1, 3, 2, 5, 0]);
+
+        // Sequence IDs are assigned in the new order
+        for (sequence_id, &first_byte) in order.iter().enumerate() {
+            let (packet, _) = tx.emit_packet(0, 0).unwrap();
+            assert_eq!(packet.borrow().sequence_id(), sequence_id as u32);
+            assert_eq!(packet.borrow().datagram(0).data[0], first_byte);
+        }
+    }
+
+    #[test]
+    fn watermarks() {
+        let mut tx = PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, 10000, CHANNEL_COUNT);
+
+        tx.set_watermarks(1, Some(Watermarks { low: 10, high: 30 }));
+
+        for _ in 0 .. 2 {
+            tx.enqueue_packet(PacketData::Owned(vec![0; 10].into_boxed_slice()), 1, SendMode::Reliable, 0, 0);
+        }
+        tx.enqueue_packet(PacketData::Owned(vec![0; 50].into_boxed_slice()), 0, SendMode::Reliable, 0, 0);
+
+        assert_eq!(tx.queued_size(0), 50);
+        assert_eq!(tx.queued_size(1), 20);
+        assert_eq!(tx.poll_backpressure(), vec![]);
+
+        tx.enqueue_packet(PacketData::Owned(vec![0; 10].into_boxed_slice()), 1, SendMode::Reliable, 0, 0);
+
+        assert_eq!(tx.poll_backpressure(), vec![(1, Backpressure::Pause)]);
+        assert_eq!(tx.poll_backpressure(), vec![]);
+
+        // Signals only once the low watermark has been reached
+        tx.emit_packet(0, 0).unwrap();
+        assert_eq!(tx.queued_size(1), 20);
+        assert_eq!(tx.poll_backpressure(), vec![]);
+
+        tx.emit_packet(0, 0).unwrap();
+        tx.emit_packet(0, 0).unwrap();
+        assert_eq!(tx.queued_size(0), 0);
+        assert_eq!(tx.queued_size(1), 10);
+        assert_eq!(tx.poll_backpressure(), vec![(1, Backpressure::Resume)]);
+
+        // Removing the watermarks resumes a paused channel
+        for _ in 0 .. 3 {
+            tx.enqueue_packet(PacketData::Owned(vec![0; 10].into_boxed_slice()), 1, SendMode::Reliable, 0, 0);
+        }
+        assert_eq!(tx.poll_backpressure(), vec![(1, Backpressure::Pause)]);
+
+        tx.set_watermarks(1, None);
+        assert_eq!(tx.poll_backpressure(), vec![(1, Backpressure::Resume)]);
+        assert_eq!(tx.poll_backpressure(), vec![]);
+    }
+
     // TODO: Test transfer window limit
     // TODO: Test allocation limit
     // TODO: Test allocation size tracking
@@ -2,6 +2,7 @@
 use super::packet_sender;
 use super::packet_receiver;
 
+use crate::CHANNEL_COUNT;
 use crate::MAX_PACKET_WINDOW_SIZE;
 use crate::SendMode;
 use crate::frame;
@@ -20,8 +21,8 @@ impl TestDatagramSink {
         }
     }
 
-    pub fn pull(&mut self, sender: &mut packet_sender::PacketSender, flush_id: u32) {
-        while let Some((pending_packet_rc, _)) = sender.emit_packet(flush_id) {
+    pub fn pull(&mut self, sender: &mut packet_sender::PacketSender<CHANNEL_COUNT>, flush_id: u32) {
+        while let Some((pending_packet_rc, _)) = sender.emit_packet(flush_id, 0) {
             let pending_packet_ref = std::cell::RefCell::borrow(&pending_packet_rc);
             let last_fragment_id = pending_packet_ref.last_fragment_id();
 
@@ -63,8 +64,6 @@ fn random_packet_data_with_id(id: u32, size: usize) -> Box<[u8]> {
 /// Ensures that packets of various sizes can be fragmented and reassembled correctly.
 #[test]
 fn random_transfer() {
-    use crate::CHANNEL_COUNT;
-
     const NUM_PACKETS: usize = 1024;
     const MAX_PACKET_SIZE: usize = 5000;
     const MAX_ALLOC_SIZE: usize = MAX_PACKET_SIZE*NUM_PACKETS;
@@ -72,8 +71,8 @@ fn random_transfer() {
 
     let base_id = packet_id::sub(0u32, NUM_PACKETS as u32/2);
 
-    let mut sender = packet_sender::PacketSender::new(WINDOW_SIZE, base_id, MAX_ALLOC_SIZE);
-    let mut receiver = packet_receiver::PacketReceiver::new(WINDOW_SIZE, base_id, MAX_ALLOC_SIZE);
+    let mut sender = packet_sender::PacketSender::<CHANNEL_COUNT>::new(WINDOW_SIZE, base_id, MAX_ALLOC_SIZE, CHANNEL_COUNT);
+    let mut receiver = packet_receiver::PacketReceiver::<CHANNEL_COUNT>::new(WINDOW_SIZE, base_id, MAX_ALLOC_SIZE, CHANNEL_COUNT);
 
     let mut sent_packet_ids = [0u32; CHANNEL_COUNT];
     let mut sent_packets = VecDeque::new();
@@ -97,7 +96,7 @@ fn random_transfer() {
             _ => panic!()
         };
 
-        sender.enqueue_packet(packet, channel_id, send_mode, 0);
+        sender.enqueue_packet(packet.into(), channel_id, send_mode, 0, 0);
     }
 
     let mut datagram_sink = TestDatagramSink::new();
@@ -111,32 +110,32 @@ fn random_transfer() {
         }
 
         for datagram in datagrams.into_iter() {
-            receiver.handle_datagram(datagram);
+            receiver.handle_datagram(datagram, 0);
         }
     }
 
     let mut packet_sink = TestPacketSink::new();
-    receiver.receive(&mut packet_sink);
+    receiver.receive(0, &mut packet_sink);
 
     assert_eq!(packet_sink.packets, sent_packets.into_iter().map(|pair| pair.0).collect::<Vec<_>>());
 }
 
 fn test_single_transfer(packet_size: usize, max_alloc: usize) {
-    let mut sender = packet_sender::PacketSender::new(MAX_PACKET_WINDOW_SIZE, 0, max_alloc);
-    let mut receiver = packet_receiver::PacketReceiver::new(MAX_PACKET_WINDOW_SIZE, 0, max_alloc);
+    let mut sender = packet_sender::PacketSender::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, max_alloc, CHANNEL_COUNT);
+    let mut receiver = packet_receiver::PacketReceiver::<CHANNEL_COUNT>::new(MAX_PACKET_WINDOW_SIZE, 0, max_alloc, CHANNEL_COUNT);
 
     let packet_data = random_packet_data(packet_size);
-    sender.enqueue_packet(packet_data.clone(), 0, SendMode::Unreliable, 0);
+    sender.enqueue_packet(packet_data.clone().into(), 0, SendMode::Unreliable, 0, 0);
 
     let mut datagram_sink = TestDatagramSink::new();
     datagram_sink.pull(&mut sender, 0);
 
     for datagram in datagram_sink.datagrams.into_iter() {
-        receiver.handle_datagram(datagram);
+        receiver.handle_datagram(datagram, 0);
     }
 
     let mut packet_sink = TestPacketSink::new();
-    receiver.receive(&mut packet_sink);
+    receiver.receive(0, &mut packet_sink);
 
     assert_eq!(packet_sink.packets.len(), 1);
     assert_eq!(packet_sink.packets[0], packet_data);
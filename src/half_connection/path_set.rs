@@ -0,0 +1,257 @@
+// Tracks the paths over which a connection sends, and selects a path for each frame. Each data
+// frame is attributed to the path over which it was sent, so that acknowledgements yield an RTT
+// estimate for each path (smoothed as the SRTT of RFC 6298), and losses yield a moving average of
+// each path's loss rate. Data frames are spread over live paths by smooth weighted round robin,
+// each path being weighted by its estimated goodput, and all other frames are sent over the live
+// path with the least RTT.
+//
+// A path is declared dead if the frames sent over it go unacknowledged for several RTTs, at which
+// point its share of data moves to the remaining paths, and any of its frames in flight are
+// recovered as losses in the usual way. Dead paths and paths which have yet to be used are probed
+// periodically, and are declared alive as soon as any frame is received over them.
+
+use crate::PathStats;
+
+// Weight of each loss sample in the moving average
+const LOSS_SMOOTHING: f64 = 1.0 / 16.0;
+
+// RTT assumed for a live path which has not yet been measured
+const DEFAULT_RTT_MS: f64 = 100.0;
+
+// A path is declared dead if frames sent over it go unacknowledged for this many RTTs, and no
+// less than MIN_PATH_TIMEOUT_MS
+const PATH_TIMEOUT_RTTS: f64 = 4.0;
+const MIN_PATH_TIMEOUT_MS: u64 = 500;
+
+const PROBE_INTERVAL_MS: u64 = 500;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum PathFeedback {
+    // A frame sent over the path was acknowledged. The send time is omitted if the frame
+    // contained retransmitted data, and cannot serve as an RTT sample.
+    Ack { path: u8, send_time_ms: Option<u64> },
+    // A frame sent over the path was declared lost
+    Loss { path: u8 },
+}
+
+struct Path {
+    srtt_ms: Option<f64>,
+    loss_rate: f64,
+    alive: bool,
+    // Send time of the earliest data frame sent over the path since an acknowledgement was last
+    // received for it
+    unacked_since_ms: Option<u64>,
+    next_probe_ms: u64,
+    // Share of data frames accrued, as in smooth weighted round robin
+    credit: f64,
+    sent_frames: u64,
+}
+
+impl Path {
+    fn new(alive: bool) -> Self {
+        Self {
+            srtt_ms: None,
+            loss_rate: 0.0,
+            alive,
+            unacked_since_ms: None,
+            next_probe_ms: 0,
+            credit: 0.0,
+            sent_frames: 0,
+        }
+    }
+
+    fn rtt_ms(&self) -> f64 {
+        self.srtt_ms.unwrap_or(DEFAULT_RTT_MS).max(1.0)
+    }
+
+    fn weight(&self) -> f64 {
+        (1.0 - self.loss_rate) / self.rtt_ms()
+    }
+
+    fn timeout_ms(&self) -> u64 {
+        ((self.rtt_ms() * PATH_TIMEOUT_RTTS) as u64).max(MIN_PATH_TIMEOUT_MS)
+    }
+}
+
+pub struct PathSet {
+    paths: Vec<Path>,
+}
+
+impl PathSet {
+    // Begins with a single path, which is presumed alive
+    pub fn new() -> Self {
+        Self {
+            paths: vec![ Path::new(true) ],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    // Adds a path which is not used until a frame has been received over it. Returns its index.
+    pub fn add(&mut self) -> usize {
+        self.paths.push(Path::new(false));
+        self.paths.len() - 1
+    }
+
+    pub fn stats(&self, path: usize) -> PathStats {
+        let path = &self.paths[path];
+
+        PathStats {
+            rtt_s: path.srtt_ms.map(|srtt_ms| srtt_ms / 1000.0),
+            loss_rate: path.loss_rate,
+            alive: path.alive,
+            sent_frames: path.sent_frames,
+        }
+    }
+
+    pub fn handle_feedback(&mut self, feedback: PathFeedback, now_ms: u64) {
+        match feedback {
+            PathFeedback::Ack { path, send_time_ms } => {
+                if let Some(path) = self.paths.get_mut(path as usize) {
+                    if let Some(send_time_ms) = send_time_ms {
+                        let sample_ms = now_ms.saturating_sub(send_time_ms) as f64;
+
+                        path.srtt_ms = Some(match path.srtt_ms {
+                            Some(srtt_ms) => srtt_ms + (sample_ms - srtt_ms) / 8.0,
+                            None => sample_ms,
+                        });
+                    }
+
+                    path.loss_rate *= 1.0 - LOSS_SMOOTHING;
+                    path.unacked_since_ms = None;
+                    path.alive = true;
+                }
+            }
+            PathFeedback::Loss { path } => {
+                if let Some(path) = self.paths.get_mut(path as usize) {
+                    path.loss_rate += (1.0 - path.loss_rate) * LOSS_SMOOTHING;
+                }
+            }
+        }
+    }
+
+    pub fn notify_received(&mut self, path: usize) {
+        if let Some(path) = self.paths.get_mut(path) {
+            path.alive = true;
+        }
+    }
+
+    pub fn notify_sent(&mut self, path: usize, now_ms: u64) {
+        let path = &mut self.paths[path];
+
+        path.sent_frames += 1;
+        path.unacked_since_ms.get_or_insert(now_ms);
+    }
+
+    // Declares dead any live path whose frames have gone unacknowledged for too long
+    pub fn step(&mut self, now_ms: u64) {
+        for path in self.paths.iter_mut() {
+            if let Some(unacked_since_ms) = path.unacked_since_ms {
+                if path.alive && now_ms - unacked_since_ms >= path.timeout_ms() {
+                    path.alive = false;
+                    path.unacked_since_ms = None;
+                    path.next_probe_ms = now_ms;
+                    path.credit = 0.0;
+                }
+            }
+        }
+    }
+
+    // Selects the path for the next data frame
+    pub fn schedule(&mut self) -> usize {
+        let total_weight = self.paths.iter().filter(|path| path.alive).map(Path::weight).sum::<f64>();
+
+        if total_weight <= 0.0 {
+            return self.preferred();
+        }
+
+        let mut selected = None;
+
+        for (index, path) in self.paths.iter_mut().enumerate().filter(|(_, path)| path.alive) {
+            path.credit += path.weight();
+
+            if selected.is_none_or(|(_, credit)| path.credit > credit) {
+                selected = Some((index, path.credit));
+            }
+        }
+
+        let (index, _) = selected.unwrap();
+        self.paths[index].credit -= total_weight;
+
+        index
+    }
+
+    // Returns the live path with the least RTT, or the first path if none are alive
+    pub fn preferred(&self) -> usize {
+        self.paths.iter()
+            .enumerate()
+            .filter(|(_, path)| path.alive)
+            .min_by(|(_, a), (_, b)| a.rtt_ms().total_cmp(&b.rtt_ms()))
+            .map_or(0, |(index, _)| index)
+    }
+
+    // Returns a path which is due to be probed, if any, and schedules its next probe
+    pub fn take_probe(&mut self, now_ms: u64) -> Option<usize> {
+        let (index, path) = self.paths.iter_mut().enumerate().find(|(_, path)| !path.alive && now_ms >= path.next_probe_ms)?;
+
+        path.next_probe_ms = now_ms + PROBE_INTERVAL_MS;
+
+        Some(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_schedule() {
+        let mut ps = PathSet::new();
+        ps.add();
+        ps.notify_received(1);
+
+        ps.handle_feedback(PathFeedback::Ack { path: 0, send_time_ms: Some(0) }, 20);
+        ps.handle_feedback(PathFeedback::Ack { path: 1, send_time_ms: Some(0) }, 60);
+
+        let mut counts = [ 0; 2 ];
+        for _ in 0 .. 400 {
+            counts[ps.schedule()] += 1;
+        }
+
+        // Shares are inversely proportional to RTT
+        assert_eq!(counts, [ 300, 100 ]);
+        assert_eq!(ps.preferred(), 0);
+    }
+
+    #[test]
+    fn failover() {
+        let mut ps = PathSet::new();
+        ps.add();
+        ps.notify_received(1);
+
+        ps.notify_sent(0, 0);
+        ps.notify_sent(1, 0);
+        ps.handle_feedback(PathFeedback::Ack { path: 1, send_time_ms: Some(0) }, 50);
+
+        ps.step(MIN_PATH_TIMEOUT_MS - 1);
+        assert!(ps.stats(0).alive);
+
+        ps.step(MIN_PATH_TIMEOUT_MS);
+        assert!(!ps.stats(0).alive);
+        assert!(ps.stats(1).alive);
+
+        assert!((0 .. 10).all(|_| ps.schedule() == 1));
+        assert_eq!(ps.preferred(), 1);
+
+        // The dead path is probed periodically until a frame is received over it
+        assert_eq!(ps.take_probe(MIN_PATH_TIMEOUT_MS), Some(0));
+        assert_eq!(ps.take_probe(MIN_PATH_TIMEOUT_MS), None);
+        assert_eq!(ps.take_probe(MIN_PATH_TIMEOUT_MS + PROBE_INTERVAL_MS), Some(0));
+
+        ps.notify_received(0);
+        assert!(ps.stats(0).alive);
+        assert_eq!(ps.take_probe(MIN_PATH_TIMEOUT_MS + 2*PROBE_INTERVAL_MS), None);
+    }
+}
@@ -5,23 +5,58 @@ use crate::MAX_FRAGMENT_SIZE;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::rc::Weak;
+use std::sync::Arc;
+
+// Packet data is either owned by a single connection, or shared between many connections (e.g. a
+// server broadcast), in which case it is never copied.
+#[derive(Debug)]
+pub enum PacketData {
+    Owned(Box<[u8]>),
+    Shared(Arc<[u8]>),
+}
+
+impl std::ops::Deref for PacketData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            PacketData::Owned(data) => data,
+            PacketData::Shared(data) => data,
+        }
+    }
+}
+
+impl From<Box<[u8]>> for PacketData {
+    fn from(data: Box<[u8]>) -> Self {
+        PacketData::Owned(data)
+    }
+}
+
+impl From<Arc<[u8]>> for PacketData {
+    fn from(data: Arc<[u8]>) -> Self {
+        PacketData::Shared(data)
+    }
+}
 
 #[derive(Debug)]
 pub struct PendingPacket {
-    data: Box<[u8]>,
+    data: PacketData,
     channel_id: u8,
 
     sequence_id: u32,
     window_parent_lead: u16,
     channel_parent_lead: u16,
+    keyed: bool,
     last_fragment_id: u16,
 
     ack_flags: Box<[u64]>,
+    // Whether acknowledgements must be tracked for every fragment, even if not resent
+    traced: bool,
 }
 
 impl PendingPacket {
-    pub fn new(data: Box<[u8]>, channel_id: u8,
-               sequence_id: u32, window_parent_lead: u16, channel_parent_lead: u16) -> Self {
+    pub fn new(data: PacketData, channel_id: u8,
+               sequence_id: u32, window_parent_lead: u16, channel_parent_lead: u16, keyed: bool) -> Self {
         let num_fragments = (data.len() + MAX_FRAGMENT_SIZE - 1) / MAX_FRAGMENT_SIZE + (data.len() == 0) as usize;
         debug_assert!(num_fragments != 0);
 
@@ -35,13 +70,22 @@ impl PendingPacket {
             sequence_id,
             window_parent_lead,
             channel_parent_lead,
+            keyed,
             last_fragment_id,
 
             ack_flags: vec![0u64; (num_fragments + 63)/64].into_boxed_slice(),
+            traced: false,
         }
     }
 
-    #[cfg(test)]
+    pub fn set_traced(&mut self) {
+        self.traced = true;
+    }
+
+    pub fn traced(&self) -> bool {
+        self.traced
+    }
+
     pub fn sequence_id(&self) -> u32 {
         self.sequence_id
     }
@@ -51,7 +95,6 @@ impl PendingPacket {
         self.channel_id
     }
 
-    #[cfg(test)]
     pub fn window_parent_lead(&self) -> u16 {
         self.window_parent_lead
     }
@@ -71,6 +114,10 @@ impl PendingPacket {
         self.ack_flags[flags_index] & flag_bit != 0
     }
 
+    pub fn all_fragments_acknowledged(&self) -> bool {
+        (0 ..= self.last_fragment_id).all(|fragment_id| self.fragment_acknowledged(fragment_id))
+    }
+
     pub fn acknowledge_fragment(&mut self, fragment_id: u16) {
         let flag_bit = 1 << (fragment_id % 64) as u64;
         let flags_index = (fragment_id / 64) as usize;
@@ -96,6 +143,7 @@ impl PendingPacket {
             channel_id: self.channel_id,
             window_parent_lead: self.window_parent_lead,
             channel_parent_lead: self.channel_parent_lead,
+            keyed: self.keyed,
             fragment_id,
             fragment_id_last: self.last_fragment_id,
             data,
@@ -128,5 +176,10 @@ impl FragmentRef {
             fragment_id,
         }
     }
+
+    // Returns true if this fragment belongs to the given packet
+    pub fn belongs_to(&self, packet_rc: &PendingPacketRc) -> bool {
+        std::ptr::eq(self.packet.as_ptr(), Rc::as_ptr(packet_rc))
+    }
 }
 
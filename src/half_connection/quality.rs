@@ -0,0 +1,191 @@
+// Combines a connection's loss rate, RTT, arrival jitter and stalls into a single score from 0
+// (unusable) to 100 (ideal). Each metric deducts a bounded penalty from the ideal score, which is
+// sampled at a fixed interval and smoothed as an exponential moving average. Stalls (reorder stalls
+// and skips, and window-limited pauses) each deduct a fixed penalty which decays over subsequent
+// samples. A new score is only reported once it differs from the score last reported by at least
+// CHANGE_THRESHOLD points, or reaches either end of the scale, so that small fluctuations do not
+// cause a displayed score to flicker.
+
+const SAMPLE_INTERVAL_MS: u64 = 100;
+
+// Weight of each sample in the moving average
+const SMOOTHING: f64 = 1.0 / 8.0;
+
+// Points deducted per unit of loss rate, i.e. 10% loss deducts 40 points
+const LOSS_PENALTY: f64 = 400.0;
+const MAX_LOSS_PENALTY: f64 = 60.0;
+
+// Points deducted per millisecond of RTT beyond RTT_ALLOWANCE_MS
+const RTT_ALLOWANCE_MS: f64 = 50.0;
+const RTT_PENALTY: f64 = 0.2;
+const MAX_RTT_PENALTY: f64 = 40.0;
+
+// Points deducted per millisecond of arrival jitter
+const JITTER_PENALTY: f64 = 0.5;
+const MAX_JITTER_PENALTY: f64 = 20.0;
+
+// Points deducted per stall, and the factor by which the stall penalty decays each sample
+const STALL_PENALTY: f64 = 10.0;
+const STALL_DECAY: f64 = 0.9;
+const MAX_STALL_PENALTY: f64 = 40.0;
+
+const CHANGE_THRESHOLD: u8 = 5;
+
+pub struct QualityEstimator {
+    next_sample_ms: u64,
+    stall_count: u64,
+    stall_penalty: f64,
+    score: Option<f64>,
+
+    reported: Option<u8>,
+}
+
+impl QualityEstimator {
+    pub fn new() -> Self {
+        Self {
+            next_sample_ms: 0,
+            stall_count: 0,
+            stall_penalty: 0.0,
+            score: None,
+
+            reported: None,
+        }
+    }
+
+    // Samples the given metrics if the sample interval has elapsed. No score is computed until an
+    // RTT has been measured. The stall count is the total number of stalls over the lifetime of the
+    // connection.
+    pub fn sample(&mut self, now_ms: u64, loss_rate: f64, rtt_ms: Option<u64>, jitter_ms: Option<f64>, stall_count: u64) {
+        if now_ms < self.next_sample_ms {
+            return;
+        }
+
+        self.next_sample_ms = now_ms + SAMPLE_INTERVAL_MS;
+
+        let new_stalls = stall_count.saturating_sub(self.stall_count);
+        self.stall_count = stall_count;
+        self.stall_penalty = (self.stall_penalty * STALL_DECAY + new_stalls as f64 * STALL_PENALTY).min(MAX_STALL_PENALTY);
+
+        let rtt_ms = match rtt_ms {
+            Some(rtt_ms) => rtt_ms as f64,
+            None => return,
+        };
+
+        let loss_penalty = (loss_rate * LOSS_PENALTY).clamp(0.0, MAX_LOSS_PENALTY);
+        let rtt_penalty = ((rtt_ms - RTT_ALLOWANCE_MS) * RTT_PENALTY).clamp(0.0, MAX_RTT_PENALTY);
+        let jitter_penalty = (jitter_ms.unwrap_or(0.0) * JITTER_PENALTY).clamp(0.0, MAX_JITTER_PENALTY);
+
+        let sample = (100.0 - loss_penalty - rtt_penalty - jitter_penalty - self.stall_penalty).max(0.0);
+
+        self.score = Some(match self.score {
+            Some(score) => score + (sample - score) * SMOOTHING,
+            None => sample,
+        });
+    }
+
+    pub fn score(&self) -> Option<u8> {
+        self.score.map(|score| score.round() as u8)
+    }
+
+    // Returns the current score if it differs significantly from the last score returned
+    pub fn poll_change(&mut self) -> Option<u8> {
+        let score = self.score()?;
+
+        if let Some(reported) = self.reported {
+            let at_limit = score == 0 || score == 100;
+
+            if score == reported || (score.abs_diff(reported) < CHANGE_THRESHOLD && !at_limit) {
+                return None;
+            }
+        }
+
+        self.reported = Some(score);
+
+        Some(score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ideal_link() {
+        let mut qe = QualityEstimator::new();
+
+        qe.sample(0, 0.0, None, None, 0);
+        assert_eq!(qe.score(), None);
+        assert_eq!(qe.poll_change(), None);
+
+        qe.sample(SAMPLE_INTERVAL_MS, 0.0, Some(20), Some(1.0), 0);
+        assert_eq!(qe.score(), Some(100));
+        assert_eq!(qe.poll_change(), Some(100));
+        assert_eq!(qe.poll_change(), None);
+    }
+
+    #[test]
+    fn penalties() {
+        let mut qe = QualityEstimator::new();
+
+        // 5% loss, 150ms RTT, and 40ms jitter deduct 20 points each
+        qe.sample(0, 0.05, Some(150), Some(40.0), 0);
+        assert_eq!(qe.score(), Some(40));
+
+        // Penalties are bounded
+        let mut qe = QualityEstimator::new();
+        qe.sample(0, 1.0, Some(10000), Some(1000.0), 0);
+        assert_eq!(qe.score(), Some(0));
+    }
+
+    #[test]
+    fn stalls_decay() {
+        let mut qe = QualityEstimator::new();
+
+        qe.sample(0, 0.0, Some(20), None, 0);
+        assert_eq!(qe.poll_change(), Some(100));
+
+        // Samples are not taken more often than the sample interval
+        qe.sample(1, 0.0, Some(20), None, 3);
+        assert_eq!(qe.score(), Some(100));
+
+        // Repeated stalls accumulate
+        let mut now_ms = 0;
+        for stall_count in 3 .. 23 {
+            now_ms += SAMPLE_INTERVAL_MS;
+            qe.sample(now_ms, 0.0, Some(20), None, stall_count);
+        }
+
+        let stalled = qe.score().unwrap();
+        assert!(stalled < 80);
+        assert_eq!(qe.poll_change(), Some(stalled));
+
+        // Recovers fully once stalls cease
+        for _ in 0 .. 200 {
+            now_ms += SAMPLE_INTERVAL_MS;
+            qe.sample(now_ms, 0.0, Some(20), None, 22);
+        }
+
+        assert_eq!(qe.score(), Some(100));
+        assert_eq!(qe.poll_change(), Some(100));
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut qe = QualityEstimator::new();
+
+        qe.sample(0, 0.0, Some(150), None, 0);
+        assert_eq!(qe.poll_change(), Some(80));
+
+        // Small changes are not reported
+        let mut now_ms = 0;
+        while qe.score() != Some(80 - CHANGE_THRESHOLD + 1) {
+            now_ms += SAMPLE_INTERVAL_MS;
+            qe.sample(now_ms, 0.0, Some(200), None, 0);
+            assert_eq!(qe.poll_change(), None);
+        }
+
+        now_ms += SAMPLE_INTERVAL_MS;
+        qe.sample(now_ms, 0.0, Some(200), None, 0);
+        assert_eq!(qe.poll_change(), Some(80 - CHANGE_THRESHOLD));
+    }
+}
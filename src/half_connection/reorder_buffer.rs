@@ -1,21 +1,36 @@
+// The tolerance may span the entire window if reordering is never to be considered loss, so space
+// for held frames is allocated as needed beyond this
+const MAX_INITIAL_CAPACITY: usize = 16;
 
+// Holds acknowledged frames which follow a gap in the acknowledged sequence. Frames in the gap are
+// considered dropped once `tolerance` frames beyond it have been acknowledged (NDUPACK), or once
+// the buffer is advanced past them.
 pub struct ReorderBuffer {
-    frames: [u32; 2],
-    frame_count: u32,
+    // Sorted in sequence order relative to base_id
+    frames: Vec<u32>,
     base_id: u32,
     max_span: u32,
+    tolerance: u32,
 }
 
 impl ReorderBuffer {
-    pub fn new(base_id: u32, max_span: u32) -> Self {
+    pub fn new(base_id: u32, max_span: u32, tolerance: u32) -> Self {
+        debug_assert!(tolerance >= 1);
+
         Self {
-            frames: [0, 0],
-            frame_count: 0,
+            frames: Vec::with_capacity((tolerance as usize).min(MAX_INITIAL_CAPACITY)),
             base_id,
             max_span,
+            tolerance,
         }
     }
 
+    // Empties the buffer, and restarts it at the given frame ID
+    pub fn reset(&mut self, base_id: u32) {
+        self.frames.clear();
+        self.base_id = base_id;
+    }
+
     #[cfg(test)]
     pub fn base_id(&self) -> u32 {
         self.base_id
@@ -27,91 +42,40 @@ impl ReorderBuffer {
 
     pub fn put<F>(&mut self, new_frame_id: u32, mut callback: F) where F: FnMut(u32, bool) {
         debug_assert!(self.can_put(new_frame_id));
+        debug_assert!(self.frames.len() < self.tolerance as usize);
 
-        if self.frame_count > 0 {
-            debug_assert!(self.frames[0] != self.base_id);
+        if let Some(&first_frame_id) = self.frames.first() {
+            debug_assert!(first_frame_id != self.base_id);
         }
 
-        debug_assert!(self.frame_count <= 2);
-
-        match self.frame_count {
-            0 => {
-                if new_frame_id == self.base_id {
-                    callback(new_frame_id, true);
-                    self.base_id = self.base_id.wrapping_add(1);
-                } else {
-                    self.frames[0] = new_frame_id;
-                    self.frame_count = 1;
-                }
-            }
-            1 => {
-                if new_frame_id == self.base_id {
-                    callback(new_frame_id, true);
-                    self.base_id = self.base_id.wrapping_add(1);
-
-                    if self.frames[0] == self.base_id {
-                        callback(self.frames[0], true);
-                        self.base_id = self.base_id.wrapping_add(1);
-                        self.frame_count = 0;
-                    }
-                } else {
-                    let delta_new = new_frame_id.wrapping_sub(self.base_id);
-                    let delta_0 = self.frames[0].wrapping_sub(self.base_id);
-
-                    debug_assert!(delta_new != delta_0);
-                    if delta_new < delta_0 {
-                        self.frames[1] = self.frames[0];
-                        self.frames[0] = new_frame_id;
-                    } else {
-                        self.frames[1] = new_frame_id;
-                    }
-
-                    self.frame_count = 2;
-                }
-            }
-            2 => {
-                let mut min_frame_id = new_frame_id;
-                let mut delta_min = new_frame_id.wrapping_sub(self.base_id);
-
-                let delta_1 = self.frames[1].wrapping_sub(self.base_id);
-
-                debug_assert!(delta_1 != delta_min);
-                if delta_1 < delta_min {
-                    std::mem::swap(&mut self.frames[1], &mut min_frame_id);
-                    delta_min = delta_1;
-                }
+        if new_frame_id == self.base_id {
+            callback(new_frame_id, true);
+            self.base_id = self.base_id.wrapping_add(1);
+        } else {
+            let base_id = self.base_id;
+            let delta_new = new_frame_id.wrapping_sub(base_id);
 
-                let delta_0 = self.frames[0].wrapping_sub(self.base_id);
+            let idx = self.frames.partition_point(|frame_id| frame_id.wrapping_sub(base_id) < delta_new);
+            debug_assert!(self.frames.get(idx) != Some(&new_frame_id));
+            self.frames.insert(idx, new_frame_id);
 
-                debug_assert!(delta_0 != delta_min);
-                if delta_0 < delta_min {
-                    std::mem::swap(&mut self.frames[0], &mut min_frame_id);
-                }
+            if self.frames.len() < self.tolerance as usize {
+                return;
+            }
 
-                while self.base_id != min_frame_id {
-                    callback(self.base_id, false);
-                    self.base_id = self.base_id.wrapping_add(1);
-                }
+            // Enough frames have been acknowledged beyond the gap to consider it dropped
+            let min_frame_id = self.frames.remove(0);
 
-                callback(min_frame_id, true);
+            while self.base_id != min_frame_id {
+                callback(self.base_id, false);
                 self.base_id = self.base_id.wrapping_add(1);
-
-                if self.frames[0] == self.base_id {
-                    callback(self.frames[0], true);
-                    self.base_id = self.base_id.wrapping_add(1);
-                    self.frame_count -= 1;
-
-                    if self.frames[1] == self.base_id {
-                        callback(self.frames[1], true);
-                        self.base_id = self.base_id.wrapping_add(1);
-                        self.frame_count -= 1;
-                    } else {
-                        self.frames[0] = self.frames[1];
-                    }
-                }
             }
-            _ => ()
+
+            callback(min_frame_id, true);
+            self.base_id = self.base_id.wrapping_add(1);
         }
+
+        self.drain_contiguous(&mut callback);
     }
 
     pub fn can_advance(&self, new_base_id: u32) -> bool {
@@ -122,26 +86,22 @@ impl ReorderBuffer {
     pub fn advance<F>(&mut self, new_base_id: u32, mut callback: F) where F: FnMut(u32, bool) {
         debug_assert!(self.can_advance(new_base_id));
 
-        if self.frame_count > 0 {
-            debug_assert!(self.frames[0] != self.base_id);
+        if let Some(&first_frame_id) = self.frames.first() {
+            debug_assert!(first_frame_id != self.base_id);
         }
 
-        debug_assert!(self.frame_count <= 2);
+        let delta_new_base = new_base_id.wrapping_sub(self.base_id);
+        let base_id = self.base_id;
+        let passed_count = self.frames.partition_point(|frame_id| frame_id.wrapping_sub(base_id) < delta_new_base);
 
-        while self.frame_count > 0 && self.frames[0].wrapping_sub(self.base_id) < new_base_id.wrapping_sub(self.base_id) {
-            while self.base_id != self.frames[0] {
+        for frame_id in self.frames.drain(.. passed_count) {
+            while self.base_id != frame_id {
                 callback(self.base_id, false);
                 self.base_id = self.base_id.wrapping_add(1);
             }
 
-            callback(self.frames[0], true);
+            callback(frame_id, true);
             self.base_id = self.base_id.wrapping_add(1);
-
-            if self.frame_count == 2 {
-                self.frames[0] = self.frames[1];
-            }
-
-            self.frame_count -= 1;
         }
 
         while self.base_id != new_base_id {
@@ -149,31 +109,16 @@ impl ReorderBuffer {
             self.base_id = self.base_id.wrapping_add(1);
         }
 
-        match self.frame_count {
-            0 => (),
-            1 => {
-                if self.frames[0] == self.base_id {
-                    callback(self.frames[0], true);
-                    self.base_id = self.base_id.wrapping_add(1);
-                    self.frame_count = 0;
-                }
-            }
-            2 => {
-                if self.frames[0] == self.base_id {
-                    callback(self.frames[0], true);
-                    self.base_id = self.base_id.wrapping_add(1);
-                    self.frame_count -= 1;
-
-                    if self.frames[1] == self.base_id {
-                        callback(self.frames[1], true);
-                        self.base_id = self.base_id.wrapping_add(1);
-                        self.frame_count -= 1;
-                    } else {
-                        self.frames[0] = self.frames[1];
-                    }
-                }
-            }
-            _ => (),
+        self.drain_contiguous(&mut callback);
+    }
+
+    // Acknowledges held frames which directly follow the base ID
+    fn drain_contiguous<F>(&mut self, callback: &mut F) where F: FnMut(u32, bool) {
+        let contiguous_count = self.frames.iter().zip(0u32 ..).take_while(|(&frame_id, i)| frame_id == self.base_id.wrapping_add(*i)).count();
+
+        for frame_id in self.frames.drain(.. contiguous_count) {
+            callback(frame_id, true);
+            self.base_id = self.base_id.wrapping_add(1);
         }
     }
 }
@@ -203,294 +148,294 @@ mod tests {
     #[test]
     fn ack_1_ack() {
         // Ack 0
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 0, vec![(0, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 0, vec![(0, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 0, vec![(0, true)]);
-        assert_eq!(rb.frame_count, 2);
+        assert_eq!(rb.frames.len(), 2);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 0, vec![(0, true)]);
-        assert_eq!(rb.frame_count, 2);
+        assert_eq!(rb.frames.len(), 2);
 
         // Nack 0, 1, Ack 2
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 4, vec![]);
         test_put_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, true)]);
-        assert_eq!(rb.frame_count, 2);
+        assert_eq!(rb.frames.len(), 2);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_put_callbacks(&mut rb, 4, vec![(0, false), (1, false), (2, true)]);
-        assert_eq!(rb.frame_count, 2);
+        assert_eq!(rb.frames.len(), 2);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 4, vec![]);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, true)]);
-        assert_eq!(rb.frame_count, 2);
+        assert_eq!(rb.frames.len(), 2);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 4, vec![]);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_put_callbacks(&mut rb, 2, vec![(0, false), (1, false), (2, true)]);
-        assert_eq!(rb.frame_count, 2);
+        assert_eq!(rb.frames.len(), 2);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 4, vec![(0, false), (1, false), (2, true)]);
-        assert_eq!(rb.frame_count, 2);
+        assert_eq!(rb.frames.len(), 2);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_put_callbacks(&mut rb, 4, vec![]);
         test_put_callbacks(&mut rb, 2, vec![(0, false), (1, false), (2, true)]);
-        assert_eq!(rb.frame_count, 2);
+        assert_eq!(rb.frames.len(), 2);
     }
 
     #[test]
     fn ack_2_acks() {
         // Ack 0, 1
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 0, vec![(0, true), (1, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 0, vec![(0, true), (1, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 0, vec![(0, true), (1, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
         // Nack 0, 1, Ack 2, 3
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, true), (3, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_put_callbacks(&mut rb, 3, vec![(0, false), (1, false), (2, true), (3, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, true), (3, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_put_callbacks(&mut rb, 2, vec![(0, false), (1, false), (2, true), (3, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 3, vec![(0, false), (1, false), (2, true), (3, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 2, vec![(0, false), (1, false), (2, true), (3, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
     }
 
     #[test]
     fn ack_3_acks() {
         // Ack 0, 1, 2
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 0, vec![(0, true), (1, true), (2, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 0, vec![(0, true), (1, true), (2, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // Nack 0, 1, ack 2, 3, 4
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 4, vec![(0, false), (1, false), (2, true), (3, true), (4, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 4, vec![]);
         test_put_callbacks(&mut rb, 3, vec![(0, false), (1, false), (2, true), (3, true), (4, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 4, vec![(0, false), (1, false), (2, true), (3, true), (4, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 4, vec![]);
         test_put_callbacks(&mut rb, 2, vec![(0, false), (1, false), (2, true), (3, true), (4, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 4, vec![]);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_put_callbacks(&mut rb, 3, vec![(0, false), (1, false), (2, true), (3, true), (4, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 4, vec![]);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_put_callbacks(&mut rb, 2, vec![(0, false), (1, false), (2, true), (3, true), (4, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
     }
 
     #[test]
     fn advance_0_acks() {
         // 0x beyond
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, false), (3, false), (4, false)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // 1x beyond
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 6, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, false), (3, false), (4, false)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
         // 2x beyond
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 6, vec![]);
         test_put_callbacks(&mut rb, 7, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, false), (3, false), (4, false)]);
-        assert_eq!(rb.frame_count, 2);
+        assert_eq!(rb.frames.len(), 2);
     }
 
     #[test]
     fn advance_1_ack() {
         // ~space, ~beyond
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_advance_callbacks(&mut rb, 2, vec![(0, false), (1, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // ~space, beyond
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 6, vec![]);
         test_advance_callbacks(&mut rb, 2, vec![(0, false), (1, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
         // space, ~beyond
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, true), (2, false), (3, false), (4, false)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // space, beyond
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 6, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, true), (2, false), (3, false), (4, false)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
 
         // past-end, ~beyond
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, false), (3, false), (4, false), (5, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // past-end, beyond
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_put_callbacks(&mut rb, 7, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, false), (3, false), (4, false), (5, true)]);
-        assert_eq!(rb.frame_count, 1);
+        assert_eq!(rb.frames.len(), 1);
     }
 
     #[test]
     fn advance_2_acks() {
         // ~space, ~space
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_advance_callbacks(&mut rb, 3, vec![(0, false), (1, true), (2, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // ~space, space
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 2, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, true), (2, true), (3, false), (4, false)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // space, ~space
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 4, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, true), (2, false), (3, false), (4, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // space, space
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 3, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, true), (2, false), (3, true), (4, false)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // ~space, past-end
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 4, vec![]);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, false), (3, false), (4, true), (5, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // space, past-end
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 1, vec![]);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, true), (2, false), (3, false), (4, false), (5, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
 
         // 2x past-end
-        let mut rb = ReorderBuffer::new(0, 100);
+        let mut rb = ReorderBuffer::new(0, 100, 3);
         test_put_callbacks(&mut rb, 5, vec![]);
         test_put_callbacks(&mut rb, 6, vec![]);
         test_advance_callbacks(&mut rb, 5, vec![(0, false), (1, false), (2, false), (3, false), (4, false), (5, true), (6, true)]);
-        assert_eq!(rb.frame_count, 0);
+        assert_eq!(rb.frames.len(), 0);
     }
 
     #[test]
     fn max_span() {
-        let rb = ReorderBuffer::new(1, 100);
+        let rb = ReorderBuffer::new(1, 100, 3);
         assert_eq!(rb.can_put(0), false);
         assert_eq!(rb.can_put(1), true);
 
@@ -505,5 +450,48 @@ mod tests {
         assert_eq!(rb.can_advance(101), true);
         assert_eq!(rb.can_advance(102), false);
     }
-}
 
+    #[test]
+    fn tolerance() {
+        // Loss is declared on the first out-of-order ack
+        let mut rb = ReorderBuffer::new(0, 100, 1);
+        test_put_callbacks(&mut rb, 2, vec![(0, false), (1, false), (2, true)]);
+        test_put_callbacks(&mut rb, 3, vec![(3, true)]);
+        assert_eq!(rb.frames.len(), 0);
+
+        // Loss is declared once five frames beyond the gap have been acknowledged
+        let mut rb = ReorderBuffer::new(0, 100, 5);
+        test_put_callbacks(&mut rb, 5, vec![]);
+        test_put_callbacks(&mut rb, 2, vec![]);
+        test_put_callbacks(&mut rb, 4, vec![]);
+        test_put_callbacks(&mut rb, 3, vec![]);
+        test_put_callbacks(&mut rb, 7, vec![(0, false), (1, false), (2, true), (3, true), (4, true), (5, true)]);
+        assert_eq!(rb.frames, vec![ 7 ]);
+
+        // Late frames within the tolerance are acknowledged
+        let mut rb = ReorderBuffer::new(0, 100, 5);
+        test_put_callbacks(&mut rb, 1, vec![]);
+        test_put_callbacks(&mut rb, 3, vec![]);
+        test_put_callbacks(&mut rb, 4, vec![]);
+        test_put_callbacks(&mut rb, 0, vec![(0, true), (1, true)]);
+        test_put_callbacks(&mut rb, 2, vec![(2, true), (3, true), (4, true)]);
+        assert_eq!(rb.frames.len(), 0);
+
+        // Advancing past held frames
+        let mut rb = ReorderBuffer::new(0, 100, 5);
+        test_put_callbacks(&mut rb, 2, vec![]);
+        test_put_callbacks(&mut rb, 4, vec![]);
+        test_put_callbacks(&mut rb, 5, vec![]);
+        test_put_callbacks(&mut rb, 8, vec![]);
+        test_advance_callbacks(&mut rb, 4, vec![(0, false), (1, false), (2, true), (3, false), (4, true), (5, true)]);
+        assert_eq!(rb.frames, vec![ 8 ]);
+
+        // Sequence wraparound
+        let mut rb = ReorderBuffer::new(u32::MAX - 1, 100, 4);
+        test_put_callbacks(&mut rb, 1, vec![]);
+        test_put_callbacks(&mut rb, u32::MAX, vec![]);
+        test_put_callbacks(&mut rb, 0, vec![]);
+        test_put_callbacks(&mut rb, 2, vec![(u32::MAX - 1, false), (u32::MAX, true), (0, true), (1, true), (2, true)]);
+        assert_eq!(rb.frames.len(), 0);
+    }
+}
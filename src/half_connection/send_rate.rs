@@ -60,7 +60,8 @@ fn eval_tcp_throughput_inv(rtt: f64, target_rate_bps: u32) -> f64 {
 
 #[derive(Debug,PartialEq)]
 pub struct FeedbackData {
-    pub rtt_ms: u64,
+    // None if only frames containing retransmitted data were acknowledged
+    pub rtt_ms: Option<u64>,
     pub receive_rate: u32,
     pub loss_rate: f64,
     pub rate_limited: bool,
@@ -140,6 +141,10 @@ impl SendRateComp {
         self.send_rate as f64
     }
 
+    pub fn loss_rate(&self) -> f64 {
+        self.prev_loss_rate
+    }
+
     pub fn rtt_s(&self) -> Option<f64> {
         self.rtt_s
     }
@@ -185,12 +190,17 @@ impl SendRateComp {
     }
 
     fn handle_feedback<F>(&mut self, now_ms: u64, feedback: FeedbackData, reset_loss_rate: F) where F: FnOnce(f64) {
-        let rtt_sample_s = ms_to_s(feedback.rtt_ms);
         let recv_rate = feedback.receive_rate;
         let loss_rate = feedback.loss_rate;
         let rate_limited = feedback.rate_limited;
 
-        let (rtt_s, rtt_ms) = self.update_rtt(rtt_sample_s);
+        let (rtt_s, rtt_ms) = match (feedback.rtt_ms, self.rtt_s, self.rtt_ms) {
+            (Some(rtt_sample_ms), _, _) => self.update_rtt(ms_to_s(rtt_sample_ms)),
+            // Feedback without an RTT sample retains the current estimate
+            (None, Some(rtt_s), Some(rtt_ms)) => (rtt_s, rtt_ms),
+            // The send rate cannot be computed without an RTT estimate
+            _ => return,
+        };
         let rto_s = self.update_rto(rtt_s, self.send_rate);
 
         // TODO: When ThroughputEqn is entered, this may produce a false positive depending on
@@ -0,0 +1,179 @@
+use crate::frame;
+use crate::SessionKeys;
+
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+
+use std::fmt;
+
+// Distinguishes keys derived by this protocol from those of any other use of the same secrets
+const KEY_EXCHANGE_SALT: &[u8] = b"uflow key exchange v1";
+
+/// A long-term X25519 key pair which identifies a server during a key exchange (see
+/// [`server::Config::static_key`](crate::server::Config::static_key)).
+///
+/// The public key may be distributed to clients out of band, so that they can verify that they
+/// have reached the intended server (see
+/// [`client::Config::server_public_key`](crate::client::Config::server_public_key)).
+#[derive(Clone)]
+pub struct StaticKey {
+    secret: StaticSecret,
+}
+
+impl StaticKey {
+    /// Generates a new random key pair.
+    pub fn generate() -> Self {
+        Self::from_bytes(rand::random::<[u8; 32]>())
+    }
+
+    /// Recreates a key pair from a secret key previously returned by
+    /// [`to_bytes()`](Self::to_bytes).
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self {
+            secret: StaticSecret::from(bytes),
+        }
+    }
+
+    /// Returns the secret key, so that the key pair may be stored and reused.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.secret.to_bytes()
+    }
+
+    /// Returns the public key.
+    pub fn public_key(&self) -> [u8; 32] {
+        PublicKey::from(&self.secret).to_bytes()
+    }
+}
+
+impl fmt::Debug for StaticKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticKey")
+            .field("public_key", &self.public_key())
+            .finish_non_exhaustive()
+    }
+}
+
+// The client's half of a key exchange, kept until the server replies
+pub struct ClientHandshake {
+    ephemeral_key: StaticSecret,
+}
+
+impl ClientHandshake {
+    pub fn new() -> Self {
+        Self {
+            ephemeral_key: StaticSecret::from(rand::random::<[u8; 32]>()),
+        }
+    }
+
+    pub fn key_share(&self) -> [u8; 32] {
+        PublicKey::from(&self.ephemeral_key).to_bytes()
+    }
+
+    // Derives session keys from the server's key share, or returns None if either of the server's
+    // public keys would contribute nothing to the result
+    pub fn complete(&self, server_share: &frame::ServerKeyShare) -> Option<SessionKeys> {
+        let ee = self.ephemeral_key.diffie_hellman(&PublicKey::from(server_share.ephemeral_key));
+        let es = self.ephemeral_key.diffie_hellman(&PublicKey::from(server_share.static_key));
+
+        derive_session_keys(&ee, &es, &self.key_share(), server_share)
+    }
+}
+
+// Answers a client's key share using the server's static key, returning the server's key share and
+// the derived session keys, or None if the client's public key would contribute nothing to the
+// result
+#[cfg_attr(not(feature = "server"), allow(dead_code))]
+pub fn respond(static_key: &StaticKey, client_share: &[u8; 32]) -> Option<(frame::ServerKeyShare, SessionKeys)> {
+    let ephemeral_key = StaticSecret::from(rand::random::<[u8; 32]>());
+
+    let server_share = frame::ServerKeyShare {
+        ephemeral_key: PublicKey::from(&ephemeral_key).to_bytes(),
+        static_key: static_key.public_key(),
+    };
+
+    let ee = ephemeral_key.diffie_hellman(&PublicKey::from(*client_share));
+    let es = static_key.secret.diffie_hellman(&PublicKey::from(*client_share));
+
+    derive_session_keys(&ee, &es, client_share, &server_share).map(|session_keys| (server_share, session_keys))
+}
+
+// Both shared secrets are mixed with every public key exchanged, so that keys are only agreed upon
+// if both endpoints observed the same exchange, and only by a server holding the static secret
+fn derive_session_keys(ee: &SharedSecret, es: &SharedSecret, client_share: &[u8; 32],
+                       server_share: &frame::ServerKeyShare) -> Option<SessionKeys> {
+    if !ee.was_contributory() || !es.was_contributory() {
+        return None;
+    }
+
+    let mut input_key = [0; 64];
+    input_key[.. 32].copy_from_slice(ee.as_bytes());
+    input_key[32 ..].copy_from_slice(es.as_bytes());
+
+    let mut info = [0; 96];
+    info[.. 32].copy_from_slice(client_share);
+    info[32 .. 64].copy_from_slice(&server_share.ephemeral_key);
+    info[64 ..].copy_from_slice(&server_share.static_key);
+
+    let mut output_key = [0; 64];
+    Hkdf::<Sha256>::new(Some(KEY_EXCHANGE_SALT), &input_key)
+        .expand(&info, &mut output_key)
+        .expect("invalid key length");
+
+    let mut session_keys = SessionKeys {
+        client_to_server: [0; 32],
+        server_to_client: [0; 32],
+    };
+
+    session_keys.client_to_server.copy_from_slice(&output_key[.. 32]);
+    session_keys.server_to_client.copy_from_slice(&output_key[32 ..]);
+
+    Some(session_keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn agreement() {
+        let static_key = StaticKey::generate();
+        let client = ClientHandshake::new();
+
+        let (server_share, server_keys) = respond(&static_key, &client.key_share()).unwrap();
+        assert_eq!(server_share.static_key, static_key.public_key());
+
+        let client_keys = client.complete(&server_share).unwrap();
+        assert_eq!(client_keys.client_to_server, server_keys.client_to_server);
+        assert_eq!(client_keys.server_to_client, server_keys.server_to_client);
+        assert_ne!(client_keys.client_to_server, client_keys.server_to_client);
+
+        // A server claiming another's static key derives different keys
+        let impostor_key = StaticKey::generate();
+        let (mut impostor_share, impostor_keys) = respond(&impostor_key, &client.key_share()).unwrap();
+        impostor_share.static_key = static_key.public_key();
+
+        let client_keys = client.complete(&impostor_share).unwrap();
+        assert_ne!(client_keys.client_to_server, impostor_keys.client_to_server);
+        assert_ne!(client_keys.server_to_client, impostor_keys.server_to_client);
+    }
+
+    #[test]
+    fn agreement_low_order() {
+        let static_key = StaticKey::generate();
+        let client = ClientHandshake::new();
+
+        assert!(respond(&static_key, &[0; 32]).is_none());
+
+        let (mut server_share, _) = respond(&static_key, &client.key_share()).unwrap();
+        server_share.ephemeral_key = [0; 32];
+        assert!(client.complete(&server_share).is_none());
+    }
+
+    #[test]
+    fn static_key_bytes() {
+        let static_key = StaticKey::generate();
+        let restored = StaticKey::from_bytes(static_key.to_bytes());
+        assert_eq!(restored.public_key(), static_key.public_key());
+    }
+}
@@ -32,9 +32,10 @@
 //!
 //! Once a client handshake has been completed, a [`RemoteClient`](server::RemoteClient) object
 //! will be created to represent the new connection. These objects may be obtained by calling
-//! [`Server::client()`](server::Server::client) with the appropriate address. Because
-//! `RemoteClient` does not store user data, it is expected that the application will store
-//! any necessary per-client data in a separate data structure.
+//! [`Server::client()`](server::Server::client) with the ID given by each event concerning the
+//! client (see [`server::ClientId`]). Because `RemoteClient` does not store user data, it is
+//! expected that the application will store any necessary per-client data in a separate data
+//! structure.
 //!
 //! A `RemoteClient` functions as a handle for a given connection, and allows the server
 //! application to send packets and query various connection details. However, no packets will be
@@ -52,20 +53,62 @@
 //!     // Process inbound UDP frames and handle events
 //!     for event in server.step() {
 //!         match event {
-//!             uflow::server::Event::Connect(client_address) => {
+//!             uflow::server::Event::Connect(client_id) => {
 //!                 // TODO: Handle client connection
 //!             }
-//!             uflow::server::Event::Disconnect(client_address) => {
+//!             uflow::server::Event::Disconnect(client_id) => {
 //!                 // TODO: Handle client disconnection
 //!             }
-//!             uflow::server::Event::Error(client_address, error) => {
+//!             uflow::server::Event::Migrate(client_id, old_address, new_address) => {
+//!                 // TODO: Update client address (if migration is enabled)
+//!             }
+//!             uflow::server::Event::Error(client_id, error) => {
 //!                 // TODO: Handle connection error
 //!             }
-//!             uflow::server::Event::Receive(client_address, packet_data) => {
+//!             uflow::server::Event::HandshakeError(client_address, error) => {
+//!                 // TODO: Monitor failed connection requests (if enabled)
+//!             }
+//!             uflow::server::Event::ConnectionRefused(client_address, reason) => {
+//!                 // TODO: Monitor refused connection requests (if desired)
+//!             }
+//!             uflow::server::Event::Receive(client_id, packet_data) => {
 //!                 // Echo the packet on channel 0
-//!                 let mut client = server.client(&client_address).unwrap().borrow_mut();
+//!                 let mut client = server.client(client_id).unwrap().borrow_mut();
 //!                 client.send(packet_data, 0, uflow::SendMode::Unreliable);
 //!             }
+//!             uflow::server::Event::CadenceChange(client_id, cadence) => {
+//!                 // TODO: Resize interpolation buffer (if enabled)
+//!             }
+//!             uflow::server::Event::WindowLimited(client_id, usage) => {
+//!                 // TODO: Send fewer, larger packets
+//!             }
+//!             uflow::server::Event::StateChange(client_id, state) => {
+//!                 // TODO: Track connection state (if desired)
+//!             }
+//!             uflow::server::Event::QualityChange(client_id, score) => {
+//!                 // TODO: Display connection quality (if enabled)
+//!             }
+//!             uflow::server::Event::CustomFrame(client_id, type_id, frame_data) => {
+//!                 // TODO: Handle custom frames (if registered)
+//!             }
+//!             uflow::server::Event::Backpressure(client_id, channel_id, signal) => {
+//!                 // TODO: Pause or resume producing data (if watermarks are set)
+//!             }
+//!             uflow::server::Event::Heartbeat(client_id, data) => {
+//!                 // TODO: Handle heartbeat payloads (if enabled)
+//!             }
+//!             uflow::server::Event::Summary(client_id, summary) => {
+//!                 // TODO: Record session statistics (if enabled)
+//!             }
+//!             uflow::server::Event::Trace(client_id, trace_id, outcome) => {
+//!                 // TODO: Follow traced packets (if any)
+//!             }
+//!             uflow::server::Event::Delivered(client_id, channel_id, tag) => {
+//!                 // TODO: Record confirmed packets (if any)
+//!             }
+//!             uflow::server::Event::Reset(client_id, drop_count) => {
+//!                 // TODO: Resynchronize application state (if either side reset the connection)
+//!             }
 //!         }
 //!     }
 //!
@@ -123,6 +166,42 @@
 //!             uflow::client::Event::Receive(packet_data) => {
 //!                 // TODO: Handle received packets
 //!             }
+//!             uflow::client::Event::CadenceChange(cadence) => {
+//!                 // TODO: Resize interpolation buffer (if enabled)
+//!             }
+//!             uflow::client::Event::SendFailed(packet_data) => {
+//!                 // TODO: Handle packets which were never sent
+//!             }
+//!             uflow::client::Event::WindowLimited(usage) => {
+//!                 // TODO: Send fewer, larger packets
+//!             }
+//!             uflow::client::Event::StateChange(state) => {
+//!                 // TODO: Track connection state (if desired)
+//!             }
+//!             uflow::client::Event::QualityChange(score) => {
+//!                 // TODO: Display connection quality (if enabled)
+//!             }
+//!             uflow::client::Event::CustomFrame(type_id, frame_data) => {
+//!                 // TODO: Handle custom frames (if registered)
+//!             }
+//!             uflow::client::Event::Backpressure(channel_id, signal) => {
+//!                 // TODO: Pause or resume producing data (if watermarks are set)
+//!             }
+//!             uflow::client::Event::Heartbeat(data) => {
+//!                 // TODO: Handle heartbeat payloads (if enabled)
+//!             }
+//!             uflow::client::Event::Summary(summary) => {
+//!                 // TODO: Record session statistics (if enabled)
+//!             }
+//!             uflow::client::Event::Trace(trace_id, outcome) => {
+//!                 // TODO: Follow traced packets (if any)
+//!             }
+//!             uflow::client::Event::Delivered(channel_id, tag) => {
+//!                 // TODO: Record confirmed packets (if any)
+//!             }
+//!             uflow::client::Event::Reset(drop_count) => {
+//!                 // TODO: Resynchronize application state (if either side reset the connection)
+//!             }
 //!         }
 //!     }
 //!
@@ -167,15 +246,25 @@
 //! Small packets are aggregated into larger UDP frames, and large packets are divided into
 //! fragments such that no frame exceeds the internet MTU (1500 bytes). Each fragment is
 //! transferred with the same send mode as its containing packet—that is, fragments will be resent
-//! if and only if the containing packet is marked with [`SendMode::Persistent`] or
-//! [`SendMode::Reliable`]. A packet is considered received once all of its constituent fragments
+//! if and only if the containing packet is marked with [`SendMode::Persistent`],
+//! [`SendMode::Reliable`] or [`SendMode::ReliableUnordered`]. A packet is considered received once all of its constituent fragments
 //! have been received.
 //!
+//! ##### Zero-Length Packets
+//!
+//! Packets may be empty, in which case they serve as markers on their channel: an empty packet
+//! occupies a single 6-byte datagram, counts against neither the sender's nor the receiver's
+//! memory limits, and is ordered and resent according to its send mode like any other packet.
+//! Empty packets are delivered as empty `Receive` events, unless
+//! [`deliver_empty_packets`](EndpointConfig#structfield.deliver_empty_packets) is disabled at the
+//! receiver, in which case they are discarded once their turn for delivery arrives.
+//!
 //! ##### Channels
 //!
-//! Each connection contains 64 virtual channels that are used to ensure relative packet ordering:
-//! packets that are received on a given channel will be delivered to the receiving application in
-//! the order they were sent. Packets which have not yet been received may be skipped, depending on
+//! Each connection contains a number of virtual channels (63 by default, and up to
+//! [`MAX_CHANNEL_COUNT`] as configured by [`EndpointConfig::channel_count`]) that are used to ensure
+//! relative packet ordering: packets that are received on a given channel will be delivered to the
+//! receiving application in the order they were sent. Packets which have not yet been received may be skipped, depending on
 //! the send mode of the particular packet, and whether or not any subsequent packets have been
 //! received.
 //!
@@ -184,12 +273,21 @@
 //! given received packet until all previous reliable packets on the same channel have also been
 //! received. This means that if a reliable packet is dropped, that channel will effectively stall
 //! for its arrival, but packets received on other channels may still be delivered in the meantime.
+//! Packets sent using [`SendMode::ReliableUnordered`] are resent in the same way, but are exempt
+//! from channel ordering altogether: each is delivered as soon as it has been received in full,
+//! and never stalls its channel.
 //!
 //! Thus, by carefully choosing the send mode and channel of outgoing packets, the latency effects
 //! of intermittent network losses can be mitigated. Because `uflow` does not store packets by
 //! channel, and otherwise never iterates over the space of channel IDs, there is no penalty to
 //! using a large number of channels.
 //!
+//! Where more independent orderings are needed than there are channels, packets may instead be
+//! sent with an ordering key (see [`client::Client::send_keyed()`] and
+//! [`server::RemoteClient::send_keyed()`]). A keyed packet is ordered only with respect to previous
+//! reliable packets sent on the same channel with the same key, so that, for example, updates to
+//! many independent entities may share a single channel without stalling one another.
+//!
 //! ##### Packet Buffering
 //!
 //! All packets are sent subject to adaptive rate control, a maximum transfer window, and a memory
@@ -207,6 +305,38 @@
 //! event that the total available bandwidth is limited, this prevents outdated packets from using
 //! any unnecessary bandwidth, and prioritizes sending newer packets in the send queue.
 //!
+//! ##### Groups
+//!
+//! A client may subscribe to any number of named groups on the server (up to
+//! [`MAX_GROUP_SUBSCRIPTIONS`]) by calling [`Client::join_group()`](client::Client::join_group).
+//! The server may then enqueue a packet for every subscribed client at once using
+//! [`Server::send_group()`](server::Server::send_group), which, like
+//! [`Server::broadcast()`](server::Server::broadcast), shares the packet data among all recipients.
+//! Subscriptions are sent to the server separately from packet data, and are resent until the
+//! server acknowledges them.
+//!
+//! ##### Custom Frames
+//!
+//! An application may exchange its own frame types alongside `uflow`'s by calling
+//! [`Client::send_custom_frame()`](client::Client::send_custom_frame) or
+//! [`RemoteClient::send_custom_frame()`](server::RemoteClient::send_custom_frame). A custom frame
+//! carries a type ID (less than [`CUSTOM_FRAME_TYPE_COUNT`]) and up to [`MAX_CUSTOM_FRAME_SIZE`]
+//! bytes of data, the encoding of which is left to the application. Custom frames are sent
+//! unreliably, ahead of any packet data, and are only delivered if the receiving endpoint has
+//! registered the corresponding type via
+//! [`custom_frame_types`](EndpointConfig#structfield.custom_frame_types). Custom frames are
+//! discarded by the sender if the remote endpoint has not registered any custom frame types.
+//!
+//! ##### Heartbeats
+//!
+//! Keepalive frames may carry a small application payload (up to [`MAX_HEARTBEAT_SIZE`] bytes),
+//! so that lightweight liveness data, such as a server's tick number, continues to flow while no
+//! channels have any traffic. Payloads are supplied by a [`HeartbeatProvider`] set via
+//! [`client::Config::heartbeat_provider`] or [`server::Config::heartbeat_provider`], and are
+//! delivered as `Heartbeat` events to a remote endpoint which has enabled
+//! [`heartbeat_events`](EndpointConfig#structfield.heartbeat_events). Heartbeats are sent
+//! unreliably, at the rate keepalive frames are sent.
+//!
 //! # Receiving Packets (and Other Events)
 //!
 //! Each time `step()` is called on a `Client` or `Server` object, connection events are returned
@@ -239,6 +369,29 @@
 //! to which packets have been delivered (and how much buffer space is available) will be relayed
 //! back to the sender as soon as possible.
 //!
+//! ##### Event Loop Integration
+//!
+//! Rather than calling `step()` at a fixed interval, an application may wait until there is work
+//! to do. `Client`, `Server`, and [`Multiplexer`](client::Multiplexer) objects expose their socket
+//! via `AsRawFd` (or `AsRawSocket` on Windows), so that it may be registered with an event loop
+//! such as `mio` (e.g. using `mio::unix::SourceFd`), and provide a `next_timeout()` method which
+//! returns the time until `step()` and `flush()` must next be called. An application then calls
+//! `step()` and `flush()` whenever the socket becomes readable or the timeout elapses, and
+//! additionally waits for the socket to become writable whenever `is_send_blocked()` returns
+//! `true`.
+//!
+//! ##### Custom Transports
+//!
+//! By default, `Client` and `Server` objects communicate over a non-blocking UDP socket. Either
+//! may instead be created over any datagram transport which implements [`Transport`], e.g. an
+//! in-memory channel for testing, or a relay, via
+//! [`Client::with_transport()`](client::Client::with_transport) or
+//! [`Server::with_transport()`](server::Server::with_transport). `AsRawFd` and `AsRawSocket` are
+//! only implemented for the default UDP transport.
+//!
+//! For tests and examples, [`transport::MemoryNetwork`] connects any number of endpoints through
+//! in-process queues with an optional delay, so that no real sockets or ports are required.
+//!
 //! # Disconnecting
 //!
 //! A connection is explicitly closed by calling `disconnect()` or `disconnect_now()` on the
@@ -260,24 +413,103 @@
 //! Servers may also call [`Server::drop()`](server::Server::drop), which sends no further packets
 //! and forgets the connection immediately. This will generate a timeout error on the client.
 
+mod clock;
 mod half_connection;
+mod extended_id;
 mod frame;
+#[cfg(feature = "encryption")]
+mod key_exchange;
+mod logger;
 mod packet_id;
-mod udp_frame_sink;
+mod seal;
+#[cfg(feature = "stats")]
+mod stats_history;
+
+pub use clock::Clock;
+pub use clock::SystemClock;
+pub use clock::VirtualClock;
+#[cfg(feature = "encryption")]
+pub use key_exchange::StaticKey;
+pub use logger::LogLevel;
+pub use logger::Logger;
+pub use logger::StderrLogger;
+#[cfg(feature = "stats")]
+pub use stats_history::StatsHistory;
+#[cfg(feature = "stats")]
+pub use stats_history::StatsSample;
+pub use transport::Transport;
 
 /// Server-related connection objects and parameters.
+#[cfg(feature = "server")]
 pub mod server;
 
 /// Client-related connection objects and parameters.
 pub mod client;
 
+/// Helpers for opening direct connections between peers behind NATs.
+pub mod rendezvous;
+
+/// Helpers for replicating application state via snapshots and deltas.
+pub mod replication;
+
+/// Helpers for streaming large transfers to many clients.
+#[cfg(feature = "server")]
+pub mod streaming;
+
+/// An HTTP endpoint for inspecting a server's connections.
+#[cfg(feature = "server")]
+pub mod admin;
+
+/// Datagram transports over which connections may run.
+pub mod transport;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
 /// The current protocol version ID.
-pub const PROTOCOL_VERSION: u8 = 3;
+pub const PROTOCOL_VERSION: u8 = 4;
+
+/// The number of channels available to a [`Client`](client::Client) or
+/// [`Server`](server::Server) by default (see [`EndpointConfig::channel_count`]), and the most
+/// channels which may be used with an endpoint unaware of configurable channel counts.
+pub const CHANNEL_COUNT: usize = frame::serial::NARROW_CHANNELS;
 
 /// The maximum number of channels which may be used on a given connection.
-pub const CHANNEL_COUNT: usize = frame::serial::MAX_CHANNELS;
+///
+/// *Note*: The protocol reserves one further channel ID for control messages exchanged by the
+/// endpoints themselves, so that built-in features never collide with application channels.
+pub const MAX_CHANNEL_COUNT: usize = frame::serial::MAX_APPLICATION_CHANNELS;
+
+/// The maximum size of a group name, in bytes (see
+/// [`Client::join_group()`](client::Client::join_group)).
+pub const MAX_GROUP_NAME_SIZE: usize = frame::serial::MAX_GROUP_NAME_SIZE;
+
+/// The maximum number of groups to which a client may subscribe.
+pub const MAX_GROUP_SUBSCRIPTIONS: usize = frame::serial::MAX_GROUPS;
+
+/// The number of custom frame types which may be registered by an endpoint.
+pub const CUSTOM_FRAME_TYPE_COUNT: usize = frame::serial::MAX_CUSTOM_FRAME_TYPES;
+
+/// The maximum size of a custom frame's data, in bytes.
+pub const MAX_CUSTOM_FRAME_SIZE: usize = frame::serial::MAX_CUSTOM_FRAME_PAYLOAD_SIZE;
+
+/// The maximum size of a heartbeat payload, in bytes (see [`HeartbeatProvider`]).
+pub const MAX_HEARTBEAT_SIZE: usize = frame::serial::MAX_HEARTBEAT_SIZE;
+
+/// The maximum size of a connect token, in bytes (see [`client::Config::connect_token`]).
+pub const MAX_CONNECT_TOKEN_SIZE: usize = frame::serial::MAX_CONNECT_TOKEN_SIZE;
+
+/// The maximum number of paths over which a client may send, including the transport it was
+/// created with (see [`client::Client::add_path()`]).
+pub const MAX_PATHS: usize = 4;
 
 /// The maximum size of the frame transfer window, in sequence IDs.
+///
+/// This is a protocol constant: a sender may never have more than this many frames awaiting
+/// acknowledgement. A connection which sends many small frames over a path with a large RTT may
+/// exhaust the window, in which case sending pauses until acknowledgements arrive, and a
+/// `WindowLimited` event is generated (see [`WindowUsage`]). Sending fewer, larger packets, or
+/// flushing less frequently, reduces the number of frames sent per round trip.
 pub const MAX_FRAME_WINDOW_SIZE: u32 = 4096;
 
 /// The maximum size of the packet transfer window, in sequence IDs.
@@ -293,8 +525,20 @@ pub const UDP_HEADER_SIZE: usize = 28;
 /// size.
 pub const MAX_FRAME_SIZE: usize = INTERNET_MTU - UDP_HEADER_SIZE;
 
+/// The number of bytes added to each frame when it is sealed (see [`SessionKeys`]).
+#[cfg(feature = "encryption")]
+pub const SEAL_OVERHEAD: usize = seal::SEAL_OVERHEAD;
+
+/// The maximum number of bytes which a [`DatagramTransform`] may add to each datagram. A data
+/// frame carrying a single transformed datagram may exceed [`MAX_FRAME_SIZE`] by this amount.
+pub const MAX_TRANSFORM_OVERHEAD: usize = 32;
+
 /// The maximum size of a packet fragment in bytes, according to frame serialization overhead.
-pub const MAX_FRAGMENT_SIZE: usize = MAX_FRAME_SIZE - frame::serial::DATA_FRAME_OVERHEAD - frame::serial::MAX_DATAGRAM_OVERHEAD;
+/// Space is reserved for the overhead of sealing frames and of tagging them with connection IDs,
+/// whether or not either applies, so that a frame carrying a single fragment never exceeds the
+/// internet MTU.
+pub const MAX_FRAGMENT_SIZE: usize = frame::serial::MAX_UNTAGGED_FRAME_SIZE - frame::serial::DATA_FRAME_OVERHEAD -
+                                     frame::serial::MAX_DATAGRAM_OVERHEAD;
 
 /// The absolute maximum size of a packet, in bytes.
 pub const MAX_PACKET_SIZE: usize = MAX_FRAGMENT_SIZE * frame::serial::MAX_FRAGMENTS;
@@ -320,6 +564,260 @@ pub enum SendMode {
     /// This packet will be sent until acknowledged by the receiver. The receiver will not deliver
     /// subsequent packets on the same channel until this packet has been delivered.
     Reliable,
+    /// This packet will be sent until acknowledged by the receiver, but is not ordered with
+    /// respect to any other packet: the receiver will deliver it as soon as it has been received
+    /// in full, and will neither withhold it for earlier packets on the same channel, nor
+    /// withhold subsequent packets for it. Any key given to this packet is ignored.
+    ReliableUnordered,
+}
+
+/// The outcome of a packet sent with a trace ID, as signaled by a `Trace` event. See
+/// [`Client::send_traced()`](client::Client::send_traced).
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum TraceOutcome {
+    /// Every fragment of the packet was acknowledged by the receiver.
+    Delivered,
+    /// The packet was discarded without being sent, e.g. because it became stale, was dropped
+    /// from the send queue, or was time-sensitive and could not be sent in the flush it was
+    /// enqueued for.
+    Dropped,
+    /// The packet was sent, but the receiver moved past it before it was acknowledged in full.
+    /// Because acknowledgements may themselves be lost, the packet may nonetheless have been
+    /// received.
+    Lost,
+}
+
+/// Describes how urgently a packet should be sent, relative to other packets sent in the same
+/// flush. See [`Client::send_with_class()`](client::Client::send_with_class).
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub enum LatencyClass {
+    /// The datagrams of this packet are packed into the earliest frames of a flush, ahead of
+    /// resent data and other new packets, so that they leave the socket first when frames are
+    /// paced. Packets enqueued before a critical packet are still sent first, as required by
+    /// packet ordering, but will not share its priority.
+    Critical,
+    /// This packet is sent in the order it was enqueued.
+    #[default]
+    Normal,
+}
+
+/// Describes a packet which has been enqueued for sending, but which has not yet been sent. See
+/// `queued_packets()` on [`Client`](client::Client::queued_packets) and
+/// [`RemoteClient`](server::RemoteClient::queued_packets).
+#[derive(Debug)]
+pub struct QueuedPacket<'a> {
+    /// The packet's data.
+    pub data: &'a [u8],
+    /// The channel on which the packet will be sent.
+    pub channel_id: usize,
+    /// The mode with which the packet will be sent.
+    pub mode: SendMode,
+    /// The latency class with which the packet will be sent.
+    pub latency_class: LatencyClass,
+    /// The time since the packet was enqueued, in milliseconds.
+    pub age_ms: u64,
+}
+
+/// Determines how a queued packet is treated when reshaping the send queue. See
+/// `reshape_send_queue()` on [`Client`](client::Client::reshape_send_queue) and
+/// [`RemoteClient`](server::RemoteClient::reshape_send_queue).
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum QueueAction {
+    /// The packet keeps its place in the queue.
+    Keep,
+    /// The packet is moved ahead of all kept and demoted packets.
+    Promote,
+    /// The packet is moved behind all promoted and kept packets.
+    Demote,
+    /// The packet is removed from the queue, and will not be sent. Each dropped packet is counted
+    /// by [`ChannelStats::sender_dropped`].
+    Drop,
+}
+
+/// Determines whether a frame observed by a [`FrameHook`] is processed normally.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum FrameAction {
+    /// The frame will be processed normally.
+    Process,
+    /// The frame will be discarded without further processing, as if it were never received.
+    Consume,
+}
+
+/// Observes raw frames received from the remote end of a connection before they are processed,
+/// e.g. to mirror traffic to an analytics or replay service. The hook is called with the sender's
+/// address and the frame's bytes (including its header and checksum) for every frame received from
+/// a connected or connecting host which has a valid checksum, including frames of types unknown
+/// to `uflow`. Such frames may be used to implement custom frame types, provided the hook consumes
+/// them.
+///
+/// See [`client::Config::frame_hook`] and [`server::Config::frame_hook`].
+pub type FrameHook = Box<dyn FnMut(std::net::SocketAddr, &[u8]) -> FrameAction + Send>;
+
+/// Supplies a small application payload to be carried by outgoing keepalive (sync) frames, e.g. a
+/// server tick number. The provider is called with the remote endpoint's address each time such a
+/// frame is about to be sent to an endpoint which accepts heartbeats, and may return `None` to send
+/// the frame without a payload. Payloads larger than [`MAX_HEARTBEAT_SIZE`] are discarded.
+///
+/// See [`client::Config::heartbeat_provider`] and [`server::Config::heartbeat_provider`].
+pub type HeartbeatProvider = Box<dyn FnMut(std::net::SocketAddr) -> Option<Box<[u8]>> + Send>;
+
+/// Selects the [`Variant`] used by an incoming connection, e.g. to assign a fraction of clients to
+/// an experimental configuration. The selector is called with the client's address when its
+/// connection request is accepted, and may return `None` to use the server's endpoint
+/// configuration unchanged.
+///
+/// See [`server::Config::variant_selector`].
+pub type VariantSelector = Box<dyn FnMut(std::net::SocketAddr) -> Option<Variant>>;
+
+/// Decides whether to accept an incoming connection, e.g. by validating a matchmaking token. The
+/// validator is called with the client's address and the connect token it sent (see
+/// [`client::Config::connect_token`]), which is empty if the client sent none, once the client's
+/// connection request has otherwise been found acceptable. If it returns `false`, the connection
+/// is refused, and the client is notified.
+///
+/// See [`server::Config::connect_validator`].
+pub type ConnectValidator = Box<dyn FnMut(std::net::SocketAddr, &[u8]) -> bool>;
+
+/// Limits the data sent by each flush of a connection on behalf of an application-owned pacer, so
+/// that `uflow` traffic may be coordinated with other flows sharing the same uplink.
+///
+/// See [`client::Config::pacer`] and [`server::Config::pacer`].
+pub trait Pacer: Send {
+    /// Returns the number of bytes which may be sent to the given address by the flush about to
+    /// take place. `budget` is the number of bytes which the connection's own congestion control
+    /// would allow, and `bytes_in_flight` is the combined size of all frames sent to the address
+    /// which are awaiting acknowledgement (see [`LinkStats::bytes_in_flight`]).
+    ///
+    /// Returning less than `budget` defers any excess data to a later flush; returning more has
+    /// no effect. As with the connection's own budget, the final frame of a flush may exceed the
+    /// grant, but nothing is sent if the grant is zero.
+    fn grant(&mut self, address: std::net::SocketAddr, budget: usize, bytes_in_flight: usize) -> usize;
+
+    /// Called after each flush with the number of bytes actually sent to the given address.
+    fn sent(&mut self, address: std::net::SocketAddr, bytes: usize) {
+        let _ = (address, bytes);
+    }
+}
+
+/// Transforms the payload of every datagram sent over a connection as it is placed into a data
+/// frame, and reverses the transformation as datagrams are received, e.g. to encrypt or sign
+/// individual messages with application-held keys. Payloads are transformed in place within each
+/// frame, so packet data is not copied any more than usual, and the transformed size of each
+/// datagram is accounted for when frames are filled. Every fragment of a large packet is
+/// transformed separately, and is transformed again each time it is resent.
+///
+/// Both endpoints of a connection must be configured with matching transforms.
+///
+/// See [`client::Config::transform`] and [`server::Config::transform_selector`].
+pub trait DatagramTransform: Send {
+    /// Returns the number of bytes by which `encode()` lengthens each payload. Must not change
+    /// over the lifetime of the transform, and must not exceed [`MAX_TRANSFORM_OVERHEAD`].
+    fn overhead(&self) -> usize;
+
+    /// Transforms the payload `data` of a datagram about to be sent, writing the result to `out`,
+    /// which is exactly `overhead()` bytes longer than `data`.
+    fn encode(&mut self, data: &[u8], out: &mut [u8]);
+
+    /// Reverses `encode()` for the payload of a received datagram. If `None` is returned, the
+    /// entire frame containing the datagram is discarded, as if it had been lost, so that any
+    /// reliable data it carried will be resent.
+    fn decode(&mut self, data: &[u8]) -> Option<Box<[u8]>>;
+}
+
+/// Supplies the [`DatagramTransform`] used by an incoming connection, e.g. one holding keys
+/// belonging to that client. The selector is called with the client's address once its connection
+/// has been established, and may return `None` to leave the connection's datagrams untransformed.
+///
+/// See [`server::Config::transform_selector`].
+pub type TransformSelector = Box<dyn FnMut(std::net::SocketAddr) -> Option<Box<dyn DatagramTransform + Send>>>;
+
+/// Presets of loss recovery settings suited to different kinds of link (see
+/// [`EndpointConfig::with_link_profile()`]).
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum LinkProfile {
+    /// The default settings, which recover quickly from loss on links with modest, stable
+    /// round-trip times.
+    Terrestrial,
+    /// Pessimistic settings for links with extreme round-trip times, such as geostationary
+    /// satellite links. Early retransmission is disabled, resends wait at least 1.5s, and
+    /// connections and frames are given longer to time out.
+    Satellite,
+}
+
+/// A set of algorithm settings which may differ between the connections of a single server, so
+/// that transport settings can be compared across a population of clients in a live experiment.
+///
+/// Each variant carries an application-defined tag, which is reported by the statistics of every
+/// connection using it (see [`LinkStats::variant`]). Settings which are negotiated with the client
+/// (`fragment_acks` and `receiver_health`) are only enabled if the client supports them.
+#[derive(Clone,Debug,PartialEq)]
+pub struct Variant {
+    /// Identifies this variant in connection statistics.
+    pub tag: u32,
+    /// See [`EndpointConfig::reorder_tolerance_frames`].
+    pub reorder_tolerance_frames: u32,
+    /// See [`EndpointConfig::reorder_tolerance_ms`].
+    pub reorder_tolerance_ms: u64,
+    /// See [`EndpointConfig::early_retransmit`].
+    pub early_retransmit: bool,
+    /// See [`EndpointConfig::min_rto_ms`].
+    pub min_rto_ms: u64,
+    /// See [`EndpointConfig::max_frame_age_ms`].
+    pub max_frame_age_ms: u64,
+    /// See [`EndpointConfig::fragment_acks`].
+    pub fragment_acks: bool,
+    /// See [`EndpointConfig::adaptive_frame_size`].
+    pub adaptive_frame_size: bool,
+    /// See [`EndpointConfig::receiver_health`].
+    pub receiver_health: bool,
+}
+
+impl Variant {
+    /// Returns a variant with the given tag whose settings match those of the given endpoint
+    /// configuration.
+    pub fn new(tag: u32, endpoint_config: &EndpointConfig) -> Self {
+        Self {
+            tag,
+            reorder_tolerance_frames: endpoint_config.reorder_tolerance_frames,
+            reorder_tolerance_ms: endpoint_config.reorder_tolerance_ms,
+            early_retransmit: endpoint_config.early_retransmit,
+            min_rto_ms: endpoint_config.min_rto_ms,
+            max_frame_age_ms: endpoint_config.max_frame_age_ms,
+            fragment_acks: endpoint_config.fragment_acks,
+            adaptive_frame_size: endpoint_config.adaptive_frame_size,
+            receiver_health: endpoint_config.receiver_health,
+        }
+    }
+
+    /// Returns `true` if each parameter has a valid value.
+    pub fn is_valid(&self) -> bool {
+        self.reorder_tolerance_frames > 0 &&
+        self.max_frame_age_ms > 0
+    }
+}
+
+/// A pair of 256-bit ChaCha20-Poly1305 keys, one for each direction of a connection (see
+/// [`EndpointConfig::session_keys`]).
+///
+/// Each sealed frame is prefixed by a random value chosen by the sending endpoint, and by a
+/// sequence ID which the sending endpoint increments with every frame. Together these form the
+/// frame's nonce, and add [`SEAL_OVERHEAD`] bytes to every frame, including the authentication
+/// tag. Frames are made smaller by as much when sealed, so that they do not exceed the internet
+/// MTU.
+#[cfg(feature = "encryption")]
+#[derive(Clone,PartialEq,Eq)]
+pub struct SessionKeys {
+    /// The key used to seal frames sent from the client to the server.
+    pub client_to_server: [u8; 32],
+    /// The key used to seal frames sent from the server to the client.
+    pub server_to_client: [u8; 32],
+}
+
+#[cfg(feature = "encryption")]
+impl std::fmt::Debug for SessionKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SessionKeys { .. }")
+    }
 }
 
 /// Parameters used to configure either endpoint of a `uflow` connection.
@@ -355,6 +853,26 @@ pub struct EndpointConfig {
     /// exceeds this value.
     pub max_receive_alloc: usize,
 
+    /// The maximum number of frames and packets the endpoint will send ahead of the opposing
+    /// endpoint's acknowledgements. The effective send window is the smaller of this value and the
+    /// opposing endpoint's `max_receive_window`.
+    ///
+    /// Must be a power of two, greater than 0, and less than or equal to
+    /// [`MAX_FRAME_WINDOW_SIZE`] and [`MAX_PACKET_WINDOW_SIZE`].
+    pub max_send_window: u32,
+
+    /// The maximum number of frames and packets the endpoint will accept ahead of those it has
+    /// received in order. This value is sent to the opposing endpoint during the handshake, which
+    /// limits its send window accordingly. Servers may use a small receive window to bound the
+    /// state kept for each client's upstream traffic.
+    ///
+    /// Must be a power of two, greater than 0, and less than or equal to
+    /// [`MAX_FRAME_WINDOW_SIZE`] and [`MAX_PACKET_WINDOW_SIZE`].
+    ///
+    /// *Note*: Peers running older protocol versions do not exchange window sizes, and always use
+    /// the maximum window in both directions.
+    pub max_receive_window: u32,
+
     // TODO (0.8.0): Combine keepalive and keepalive_interval_ms into Option<u64>
 
     /// Whether the endpoint should automatically send keepalive frames if no data has been sent
@@ -371,6 +889,170 @@ pub struct EndpointConfig {
     /// Time in milliseconds after which an active connection will terminate if no frames have been
     /// received from the remote endpoint.
     pub active_timeout_ms: u64,
+
+    /// Whether the endpoint should generate `CadenceChange` events when the estimated arrival
+    /// cadence of the remote host changes significantly (see [`ArrivalCadence`]). The current
+    /// estimate may always be queried via `link_stats()`.
+    pub cadence_events: bool,
+
+    /// Whether the endpoint should generate `StateChange` events when the state of a connection
+    /// changes (see [`ConnectionState`]). The current state may always be queried via
+    /// `connection_state()`.
+    pub state_events: bool,
+
+    /// Whether the endpoint should generate `QualityChange` events when the quality score of a
+    /// connection changes significantly (see [`LinkStats::quality`]). Small changes are not
+    /// signaled, so that a displayed score does not flicker. The current score may always be
+    /// queried via `link_stats()`.
+    pub quality_events: bool,
+
+    /// The interval in milliseconds at which connection metrics are sampled into a
+    /// [`StatsHistory`], which may be queried via `stats_history()`. If set to `None`, no samples
+    /// are taken.
+    ///
+    /// Must not be zero.
+    #[cfg(feature = "stats")]
+    pub stats_sample_interval_ms: Option<u64>,
+
+    /// The maximum number of samples retained by a connection's [`StatsHistory`].
+    ///
+    /// Must be greater than 0.
+    #[cfg(feature = "stats")]
+    pub stats_history_len: usize,
+
+    /// The number of frames which must be acknowledged after an unacknowledged frame before that
+    /// frame is considered lost for the purposes of congestion control. Larger values tolerate
+    /// more reordering in the network, e.g. across bonded links, at the expense of slower loss
+    /// detection.
+    ///
+    /// Must be greater than 0.
+    pub reorder_tolerance_frames: u32,
+
+    /// Additional time in milliseconds, beyond one round-trip time, which an unacknowledged
+    /// reliable fragment is given before it is resent. Larger values avoid spurious
+    /// retransmissions on paths which delay some frames significantly, at the expense of slower
+    /// recovery from genuine loss.
+    pub reorder_tolerance_ms: u64,
+
+    /// Whether an unacknowledged frame may be declared lost as soon as `reorder_tolerance_frames`
+    /// later frames have been acknowledged. If set to false, recovery is purely timer-driven:
+    /// frames are only declared lost once they have gone unacknowledged for several round-trip
+    /// times (or `min_rto_ms`, if longer), so that reordering on extreme-RTT paths neither abandons
+    /// unreliable packets nor registers as congestion.
+    pub early_retransmit: bool,
+
+    /// The minimum time in milliseconds which an unacknowledged reliable fragment is given before
+    /// it is resent, and which an unacknowledged frame is given before it is declared lost,
+    /// however short the measured round-trip time. A long minimum avoids spurious retransmissions
+    /// on paths whose delay varies widely, such as satellite links.
+    pub min_rto_ms: u64,
+
+    /// Time in milliseconds after which an unacknowledged frame is declared lost, and the transfer
+    /// window is advanced past it, even if the remote endpoint has not done so. This bounds the
+    /// memory retained for in-flight frames if acknowledgements cease to arrive. Persistent and
+    /// reliable packets in such frames are resent immediately.
+    ///
+    /// Must be greater than 0.
+    pub max_frame_age_ms: u64,
+
+    /// Whether the endpoint should acknowledge the individual fragments of partially received
+    /// packets, and make use of such acknowledgements from the remote endpoint, so that only the
+    /// missing fragments of a large packet are resent after an acknowledgement is lost. This
+    /// feature is only enabled for a connection if both endpoints support it.
+    pub fragment_acks: bool,
+
+    /// Whether acknowledgements sent by the endpoint should report the state of its receive buffer
+    /// (see [`ReceiverHealth`]), and whether such reports from the remote endpoint should be used
+    /// to slow sending while the remote endpoint is backlogged. This feature is only enabled for a
+    /// connection if both endpoints support it.
+    pub receiver_health: bool,
+
+    /// Whether acknowledgements sent by the endpoint should report an application latency, e.g.
+    /// the time between receiving a packet and processing it, as set via
+    /// [`Client::set_app_latency_ms()`](client::Client::set_app_latency_ms) or
+    /// [`RemoteClient::set_app_latency_ms()`](server::RemoteClient::set_app_latency_ms). Reports from the remote endpoint are exposed by
+    /// [`LinkStats::remote_app_latency_ms`], so that the combined network and processing latency
+    /// of a connection may be observed from either end. This feature is only enabled for a
+    /// connection if both endpoints support it.
+    pub app_latency: bool,
+
+    /// Whether the endpoint should fill data frames to a smaller size while the measured loss rate
+    /// is high, so that each lost frame damages fewer packets. Frames grow back to the maximum
+    /// size as the loss rate falls.
+    pub adaptive_frame_size: bool,
+
+    /// The relative share of sends given to each channel while several channels have packets
+    /// waiting to be sent, indexed by channel ID. Channels beyond the end of the list have weight 1.
+    /// For example, weights of `[8, 1]` send up to eight times as many bytes on channel 0 as on
+    /// channel 1 while both are backlogged, so that bulk data on one channel cannot starve
+    /// latency-sensitive data on another. Packets on the same channel are always sent in order, and
+    /// critical packets (see [`LatencyClass::Critical`]) are still sent ahead of others once
+    /// dequeued. If empty, packets are sent in the order they were enqueued, regardless of channel.
+    ///
+    /// Weights must be greater than 0.
+    pub channel_weights: Vec<u32>,
+
+    /// The number of channels available to the endpoint, from 1 to [`MAX_CHANNEL_COUNT`]. A client
+    /// proposes its channel count to the server while connecting, and fails to connect with a
+    /// [`Config`](client::ErrorType::Config) error if the count exceeds that of the server, so a
+    /// server should be configured with at least as many channels as any of its clients. Each
+    /// connection to a server then uses the channel count of its client (see
+    /// [`RemoteClient::channel_count()`](server::RemoteClient::channel_count)). Per-channel state
+    /// is allocated for every channel, so an endpoint using only a few channels may use less memory
+    /// with a smaller channel count.
+    ///
+    /// Channel counts greater than [`CHANNEL_COUNT`] may only be used if both endpoints support
+    /// them.
+    pub channel_count: usize,
+
+    /// The rate at which the endpoint should send padded probe packets while it has no other data
+    /// to send, in bytes per second. Probes keep the RTT and send rate estimates fresh while the
+    /// application is idle (e.g. so that an accurate bandwidth estimate is available once a match
+    /// begins), and keep NAT bindings warm. Probes are sent on the control channel, are never
+    /// delivered to the remote application, and are paced by the connection's send rate like any
+    /// other data. The total size of probes sent is given by [`LinkStats::probe_bytes`]. This
+    /// feature is only used for a connection if both endpoints support control channels. If 0, no
+    /// probes are sent.
+    pub probe_rate: u32,
+
+    /// Whether zero-length packets received by the endpoint should be delivered to the
+    /// application. If set to false, zero-length packets are still ordered with respect to other
+    /// packets on their channel, but are discarded rather than delivered.
+    pub deliver_empty_packets: bool,
+
+    /// The order in which packets on different channels are delivered to the application when
+    /// several become deliverable during the same call to `step()` (see [`ReceiveOrder`]).
+    pub receive_order: ReceiveOrder,
+
+    /// A bitmask of the custom frame types which will be delivered to the application, where bit
+    /// `n` corresponds to type ID `n`. Custom frames of unregistered types are silently discarded.
+    /// If no types are registered, the remote endpoint will not send any custom frames.
+    pub custom_frame_types: u16,
+
+    /// Whether heartbeat payloads carried by keepalive frames from the remote endpoint should be
+    /// delivered to the application as `Heartbeat` events. If set to false, the remote endpoint
+    /// will not attach any heartbeat payloads.
+    pub heartbeat_events: bool,
+
+    /// Whether a `Summary` event describing the entire lifetime of a connection should be
+    /// generated when an established connection ends (see [`SessionSummary`]).
+    pub session_summaries: bool,
+
+    /// The maximum time in milliseconds which buffered packet data may take to drain at the
+    /// current send rate before `try_send()` refuses new packets (see
+    /// [`Client::try_send()`](client::Client::try_send)). Has no effect on other send functions.
+    pub max_send_delay_ms: u64,
+
+    /// Keys with which every frame exchanged with the remote endpoint is sealed using
+    /// ChaCha20-Poly1305 (see [`SessionKeys`]). Frames received from the remote endpoint which
+    /// fail authentication are dropped. If set to `None`, frames are sent in the clear.
+    ///
+    /// A server seals frames for all of its clients with the keys of its own endpoint
+    /// configuration, so both endpoints of a connection must be configured with the same keys.
+    /// Alternatively, keys may be agreed upon separately for each connection during the handshake
+    /// (see [`StaticKey`]), in which case these keys only seal the handshake itself.
+    #[cfg(feature = "encryption")]
+    pub session_keys: Option<SessionKeys>,
 }
 
 impl Default for EndpointConfig {
@@ -379,9 +1061,32 @@ impl Default for EndpointConfig {
     ///   * Maximum incoming bandwidth: 2MB/s
     ///   * Maximum packet size: 1MB
     ///   * Maximum packet receive allocation: 1MB
+    ///   * Maximum send window: 4096
+    ///   * Maximum receive window: 4096
     ///   * Keepalive: true
     ///   * Keepalive interval: 5s
     ///   * Active timeout: 20s
+    ///   * Cadence events: false
+    ///   * Quality events: false
+    ///   * Stats sample interval: None
+    ///   * Stats history length: 256
+    ///   * Reorder tolerance: 3 frames, 0ms
+    ///   * Early retransmit: true
+    ///   * Minimum RTO: 0ms
+    ///   * Maximum frame age: 10s
+    ///   * Fragment acknowledgements: true
+    ///   * Receiver health: false
+    ///   * Application latency: false
+    ///   * Adaptive frame size: false
+    ///   * Channel weights: None
+    ///   * Channel count: 63
+    ///   * Probe rate: 0
+    ///   * Deliver empty packets: true
+    ///   * Custom frame types: None
+    ///   * Heartbeat events: false
+    ///   * Session summaries: false
+    ///   * Maximum send delay: 200ms
+    ///   * Session keys: None
     fn default() -> Self {
         Self {
             max_send_rate: 2_000_000,
@@ -390,21 +1095,521 @@ impl Default for EndpointConfig {
             max_packet_size: 1_000_000,
             max_receive_alloc: 1_000_000,
 
+            max_send_window: MAX_FRAME_WINDOW_SIZE,
+            max_receive_window: MAX_FRAME_WINDOW_SIZE,
+
             keepalive: true,
             keepalive_interval_ms: 5000,
 
-            active_timeout_ms: 20000
+            active_timeout_ms: 20000,
+
+            cadence_events: false,
+            state_events: false,
+            quality_events: false,
+
+            #[cfg(feature = "stats")]
+            stats_sample_interval_ms: None,
+            #[cfg(feature = "stats")]
+            stats_history_len: 256,
+
+            reorder_tolerance_frames: 3,
+            reorder_tolerance_ms: 0,
+
+            early_retransmit: true,
+            min_rto_ms: 0,
+
+            max_frame_age_ms: 10000,
+
+            fragment_acks: true,
+
+            receiver_health: false,
+
+            app_latency: false,
+
+            adaptive_frame_size: false,
+
+            channel_weights: Vec::new(),
+            channel_count: CHANNEL_COUNT,
+            probe_rate: 0,
+
+            deliver_empty_packets: true,
+
+            receive_order: ReceiveOrder::Sent,
+
+            custom_frame_types: 0,
+
+            heartbeat_events: false,
+
+            session_summaries: false,
+
+            max_send_delay_ms: 200,
+
+            #[cfg(feature = "encryption")]
+            session_keys: None,
         }
     }
 }
 
 impl EndpointConfig {
+    /// Returns the default endpoint configuration, adjusted to suit the given kind of link (see
+    /// [`LinkProfile`]).
+    pub fn with_link_profile(profile: LinkProfile) -> Self {
+        match profile {
+            LinkProfile::Terrestrial => Default::default(),
+            LinkProfile::Satellite => Self {
+                active_timeout_ms: 30000,
+                reorder_tolerance_ms: 100,
+                early_retransmit: false,
+                min_rto_ms: 1500,
+                max_frame_age_ms: 20000,
+                .. Default::default()
+            },
+        }
+    }
+
     /// Returns `true` if each parameter has a valid value.
     pub fn is_valid(&self) -> bool {
         self.max_send_rate > 0 &&
         self.max_receive_rate > 0 &&
         self.max_packet_size > 0 &&
         self.max_packet_size <= MAX_PACKET_SIZE &&
-        self.max_receive_alloc > 0
+        self.max_receive_alloc > 0 &&
+        window_valid(self.max_send_window) &&
+        window_valid(self.max_receive_window) &&
+        self.stats_valid() &&
+        self.reorder_tolerance_frames > 0 &&
+        self.max_frame_age_ms > 0 &&
+        self.channel_weights.iter().all(|&weight| weight > 0) &&
+        self.channel_count > 0 &&
+        self.channel_count <= MAX_CHANNEL_COUNT
     }
+
+    #[cfg(feature = "stats")]
+    fn stats_valid(&self) -> bool {
+        self.stats_sample_interval_ms != Some(0) &&
+        self.stats_history_len > 0
+    }
+
+    #[cfg(not(feature = "stats"))]
+    fn stats_valid(&self) -> bool {
+        true
+    }
+
+    // Returns the send and receive window sizes of a connection, given the receive window
+    // advertised by the opposing endpoint (zero if none was advertised)
+    pub(crate) fn transfer_windows(&self, remote_max_receive_window: u32) -> (u32, u32) {
+        let max_window = MAX_FRAME_WINDOW_SIZE.min(MAX_PACKET_WINDOW_SIZE);
+
+        if remote_max_receive_window == 0 {
+            // The opposing endpoint is unaware of our receive window, and will send using the
+            // maximum window
+            (self.max_send_window.min(max_window), max_window)
+        } else {
+            (self.max_send_window.min(remote_max_receive_window), self.max_receive_window)
+        }
+    }
+}
+
+fn window_valid(window: u32) -> bool {
+    window.is_power_of_two() && window <= MAX_FRAME_WINDOW_SIZE.min(MAX_PACKET_WINDOW_SIZE)
+}
+
+/// An estimate of the rate and regularity with which data arrives from a remote host.
+///
+/// Because a sender will typically send data once per update, these values may be used to size
+/// an interpolation (jitter) buffer at the receiver. Estimates are computed using the arrival
+/// times of data frames, and are therefore limited by the rate at which `step()` is called on the
+/// receiver.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct ArrivalCadence {
+    /// Smoothed interval between data arrivals, in milliseconds.
+    pub interval_ms: f64,
+    /// Smoothed mean deviation of the interval between data arrivals, in milliseconds.
+    pub jitter_ms: f64,
+}
+
+/// A snapshot of a connection's frame transfer window, taken when sending was paused because the
+/// window was full.
+///
+/// Unlike a limited send rate, which indicates congestion, a full window indicates that more
+/// frames were sent than could be acknowledged within one round trip. See
+/// [`MAX_FRAME_WINDOW_SIZE`].
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct WindowUsage {
+    /// The number of frames sent which are awaiting acknowledgement.
+    pub frames_in_flight: u32,
+    /// The size of the frame transfer window.
+    pub frame_window_size: u32,
+    /// The combined size of all outstanding packets, in bytes.
+    pub send_buffer_size: usize,
+}
+
+/// Determines the order in which packets which become deliverable on different channels during the
+/// same call to `step()` are delivered to the application. Packets on the same channel are always
+/// delivered in channel order, and packets received from different connections are not reordered
+/// relative to one another. See
+/// [`EndpointConfig::receive_order`](EndpointConfig#structfield.receive_order).
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum ReceiveOrder {
+    /// Packets are delivered in the order they were sent.
+    #[default]
+    Sent,
+    /// Packets are delivered in the order they became deliverable, i.e. the order in which each
+    /// packet and all of its predecessors on the same channel had arrived.
+    Arrival,
+    /// Packets on lower-numbered channels are delivered before those on higher-numbered channels,
+    /// so that latency-critical data may be placed on channel 0 and handled first.
+    ChannelPriority,
+    /// One packet is delivered from each channel in turn, in channel ID order, so that a burst of
+    /// packets on one channel does not delay those on the others.
+    RoundRobin,
+}
+
+/// Determines how a channel responds once a packet awaiting a missing predecessor exceeds the
+/// channel's [`ReorderLimits`].
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum ReorderMode {
+    /// Packets continue to wait for their predecessors, so that delivery order is preserved. Each
+    /// occurrence is counted by [`ChannelStats::reorder_stalls`].
+    #[default]
+    Strict,
+    /// Missing predecessors are skipped, and the waiting packet is delivered immediately. Skipped
+    /// packets which arrive later are discarded. Each occurrence is counted by
+    /// [`ChannelStats::reorder_skips`].
+    Lossy,
+}
+
+/// Limits the number of received packets which may wait on a channel for a missing predecessor
+/// (e.g. a dropped [`Reliable`](SendMode::Reliable) packet which is being resent), and the time
+/// for which they may wait. By default, a channel imposes no limits, and packets wait as long as
+/// necessary.
+///
+/// See `set_reorder_limits()` on [`Client`](client::Client::set_reorder_limits) and
+/// [`RemoteClient`](server::RemoteClient::set_reorder_limits).
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct ReorderLimits {
+    /// The maximum number of packets which may be buffered on the channel while a predecessor is
+    /// missing.
+    pub max_packets: Option<u32>,
+    /// The maximum time, in milliseconds, which a packet may wait for a missing predecessor.
+    pub max_hold_ms: Option<u64>,
+    /// Determines the behavior of the channel once either limit is exceeded.
+    pub mode: ReorderMode,
+}
+
+/// Thresholds on the number of bytes waiting in a channel's send queue, which are used to signal
+/// backpressure to the application (see [`Backpressure`]). Only packets which have not yet been
+/// sent are counted.
+///
+/// See `set_watermarks()` on [`Client`](client::Client::set_watermarks) and
+/// [`RemoteClient`](server::RemoteClient::set_watermarks).
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct Watermarks {
+    /// Once a paused channel's queued bytes fall to or below this value, a
+    /// [`Resume`](Backpressure::Resume) signal is generated.
+    pub low: usize,
+    /// Once the channel's queued bytes reach or exceed this value, a
+    /// [`Pause`](Backpressure::Pause) signal is generated.
+    pub high: usize,
+}
+
+/// Signals that the send queue of a channel has crossed one of its [`Watermarks`]. Signals for a
+/// given channel alternate, beginning with `Pause`.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Backpressure {
+    /// The channel's queued bytes have reached the high watermark. Producers of data for the
+    /// channel should pause.
+    Pause,
+    /// The channel's queued bytes have fallen to the low watermark, or the channel's watermarks
+    /// were removed. Producers of data for the channel may resume.
+    Resume,
+}
+
+/// Returned by `try_send()` when a packet is refused because the connection's buffered packet data
+/// could not be drained within [`EndpointConfig::max_send_delay_ms`] at the current send rate. See
+/// [`Client::try_send()`](client::Client::try_send).
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct SendCongested {
+    /// The estimated time needed to send and acknowledge all buffered packet data, in
+    /// milliseconds.
+    pub estimated_delay_ms: u64,
+}
+
+/// Identifies a packet passed to `send()` or one of its variants, so that it may be canceled before
+/// it is sent. See [`Client::cancel()`](client::Client::cancel).
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub struct SendHandle(pub (crate) u64);
+
+/// Additional parameters for a packet passed to `send_with()`. Options may be combined, subject to
+/// the send mode restrictions noted below. See [`Client::send_with()`](client::Client::send_with).
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct SendOptions {
+    /// The latency class with which the packet is sent (see [`LatencyClass`]).
+    pub latency_class: LatencyClass,
+    /// If set, the packet is ordered only with respect to other packets sent on the same channel
+    /// with the same key. See [`Client::send_keyed()`](client::Client::send_keyed).
+    pub key: Option<u16>,
+    /// If set, the packet's outcome is reported by a trace event carrying this ID. See
+    /// [`Client::send_traced()`](client::Client::send_traced).
+    pub trace_id: Option<u64>,
+    /// If set, the packet is dropped, rather than sent, if it remains in the send queue for longer
+    /// than this duration (rounded down to the nearest millisecond). Requires a send mode of
+    /// [`TimeSensitive`](SendMode::TimeSensitive) or [`Unreliable`](SendMode::Unreliable). See
+    /// [`Client::send_with_ttl()`](client::Client::send_with_ttl).
+    pub ttl: Option<std::time::Duration>,
+    /// If set, the packet's receipt is confirmed by a delivery event carrying its channel ID and
+    /// this tag. Requires a send mode of [`Reliable`](SendMode::Reliable) or
+    /// [`ReliableUnordered`](SendMode::ReliableUnordered). See
+    /// [`Client::send_confirmed()`](client::Client::send_confirmed).
+    pub confirm_tag: Option<u64>,
+}
+
+/// The reason a connection was closed. See [`ConnectionState`].
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum CloseReason {
+    /// The connection was terminated by either party.
+    Disconnect,
+    /// The remote host stopped responding, either during the connection handshake or afterward.
+    Timeout,
+    /// The connection request was refused or rejected by the remote host.
+    Rejected,
+    /// The connection was forgotten locally without notifying the remote host.
+    Dropped,
+}
+
+/// The lifecycle state of a connection.
+///
+/// Each endpoint reports its current state via `connection_state()`, and, if
+/// [`EndpointConfig::state_events`] is set, signals transitions via `StateChange` events in the
+/// order in which they occur. A connection progresses through the
+/// states in the order listed, though any state may be skipped.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum ConnectionState {
+    /// A connection handshake is in progress.
+    Connecting,
+    /// The connection is active, and packets may be sent and received.
+    Connected,
+    /// A disconnection has been requested locally, and any pending outbound packets are being
+    /// sent. The connection is still active.
+    Draining,
+    /// A disconnection request has been sent, and is awaiting acknowledgement. Packets are no
+    /// longer sent or received.
+    Closing,
+    /// The connection has been closed for the given reason.
+    Closed {
+        /// Why the connection was closed.
+        reason: CloseReason,
+    },
+}
+
+/// An implementation of the frame checksum, selected at runtime according to the features
+/// supported by the CPU.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum ChecksumBackend {
+    /// A portable, table-driven implementation.
+    #[default]
+    Table,
+    /// An implementation using the x86-64 carry-less multiplication instruction (PCLMULQDQ).
+    Pclmul,
+}
+
+/// Returns the checksum implementation used by this process.
+pub fn checksum_backend() -> ChecksumBackend {
+    frame::serial::checksum_backend()
+}
+
+/// A snapshot of statistics describing the state of a connection.
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct LinkStats {
+    /// The current estimate of the round-trip time (RTT), in seconds, if computed.
+    pub rtt_s: Option<f64>,
+    /// The combined size of all outstanding packets, in bytes.
+    pub send_buffer_size: usize,
+    /// The current estimate of the remote host's data arrival cadence, if computed.
+    pub arrival_cadence: Option<ArrivalCadence>,
+    /// A smoothed score from 0 (unusable) to 100 (ideal) summarizing the connection's loss rate,
+    /// RTT, arrival jitter, and recent stalls, e.g. for display as "connection bars". This is
+    /// `None` until the RTT has been measured. See [`EndpointConfig::quality_events`].
+    pub quality: Option<u8>,
+    /// The total number of unreliable packets which were dropped by the sender due to exceeding
+    /// their channel's staleness bound.
+    pub stale_drop_count: u64,
+    /// The total size of all unreliable packets which were dropped by the sender due to exceeding
+    /// their channel's staleness bound, in bytes.
+    pub stale_drop_bytes: u64,
+    /// The total size of the probe packets sent while the connection was idle, in bytes. See
+    /// [`EndpointConfig::probe_rate`].
+    pub probe_bytes: u64,
+    /// The total number of data frames received which had already been received, e.g. resends
+    /// which crossed paths with an acknowledgement. Duplicate frames are discarded without being
+    /// acknowledged a second time.
+    pub duplicate_frames: u64,
+    /// The total number of packet fragments received which had already been received or
+    /// delivered. Duplicate fragments are discarded, and never result in a duplicate delivery.
+    pub duplicate_datagrams: u64,
+    /// The total number of incoming packets which were rejected because their fragments implied a
+    /// size greater than the remote host's [maximum packet
+    /// size](EndpointConfig#structfield.max_packet_size). Such packets are skipped without being
+    /// delivered, and no memory is allocated for them.
+    pub oversized_packets: u64,
+    /// The total number of frame acknowledgements received which acknowledged no new frames,
+    /// e.g. those retransmitted by the remote host. Duplicate acknowledgements are ignored for the
+    /// purposes of RTT estimation and congestion control.
+    pub duplicate_ack_groups: u64,
+    /// The total number of sent frames which were abandoned after exceeding the maximum frame age
+    /// (see [`EndpointConfig::max_frame_age_ms`]).
+    pub expired_frames: u64,
+    /// The checksum implementation used by this process (see [`checksum_backend()`]).
+    pub checksum_backend: ChecksumBackend,
+    /// The total number of flushes which were interrupted because the socket's send buffer was
+    /// full. Frames which could not be sent are sent first on the next flush.
+    pub send_blocked_count: u64,
+    /// The number of frames sent which are awaiting acknowledgement.
+    pub frames_in_flight: u32,
+    /// The combined size of all frames sent which are awaiting acknowledgement, in bytes. Frames
+    /// which have been declared lost are not counted.
+    pub bytes_in_flight: usize,
+    /// The total number of times sending was paused because the frame transfer window was full
+    /// (see [`WindowUsage`]).
+    pub window_limited_count: u64,
+    /// The time between the receipt of a data frame and the sending of its acknowledgement, as of
+    /// the most recent acknowledgement sent, in milliseconds. This includes any time the frame
+    /// waited for a flush.
+    pub ack_delay_ms: Option<u64>,
+    /// The greatest value of `ack_delay_ms` observed over the lifetime of the connection, in
+    /// milliseconds.
+    pub max_ack_delay_ms: u64,
+    /// The total time spent processing this connection's inbound frames, outbound frames, and
+    /// periodic updates. This is `None` unless the `cpu-accounting` feature is enabled.
+    pub processing_time: Option<std::time::Duration>,
+    /// The most recent receive buffer report from the remote host, if
+    /// [`receiver_health`](EndpointConfig#structfield.receiver_health) is enabled for this
+    /// connection and a report has been received.
+    pub remote_health: Option<ReceiverHealth>,
+    /// The most recent application latency reported by the remote host, in milliseconds, if
+    /// [`app_latency`](EndpointConfig#structfield.app_latency) is enabled for this connection and
+    /// a report has been received.
+    pub remote_app_latency_ms: Option<u64>,
+    /// The tag of the [`Variant`] selected for this connection, if any. Only set for connections
+    /// accepted by a server with a [variant
+    /// selector](server::Config#structfield.variant_selector).
+    pub variant: Option<u32>,
+    /// The maximum number of frames and packets sent ahead of the remote host's acknowledgements
+    /// (see [`EndpointConfig::max_send_window`]).
+    pub send_window: u32,
+    /// The maximum number of frames and packets received ahead of those received in order (see
+    /// [`EndpointConfig::max_receive_window`]).
+    pub receive_window: u32,
+}
+
+/// The state of a host's receive buffer, as reported to the sender by acknowledgements (see
+/// [`EndpointConfig::receiver_health`]).
+///
+/// A growing buffer indicates that the receiving application is falling behind, or that packets
+/// are waiting for missing predecessors, and may be noticed well before any packets are lost.
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct ReceiverHealth {
+    /// The number of bytes held by the receiver for packets which have not yet been delivered.
+    pub buffered_bytes: usize,
+    /// Whether more than half of the receiver's [packet receive
+    /// allocation](EndpointConfig#structfield.max_receive_alloc) is in use. While a receiver is
+    /// backlogged, the sender reserves its buffered bytes from the allocation available for new
+    /// packets.
+    pub backlogged: bool,
+}
+
+/// The upper bounds of the buckets of an [`RttHistogram`], in milliseconds.
+pub const RTT_HISTOGRAM_BOUNDS_MS: [u64; 7] = [ 10, 25, 50, 100, 200, 500, 1000 ];
+
+/// A histogram of the round-trip time (RTT) samples taken over the lifetime of a connection.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub struct RttHistogram {
+    /// The number of samples in each bucket. The sample counted by bucket `i` was at most
+    /// `RTT_HISTOGRAM_BOUNDS_MS[i]` milliseconds, and greater than the previous bound, if any. The
+    /// final bucket counts all samples greater than the last bound.
+    pub counts: [u64; RTT_HISTOGRAM_BOUNDS_MS.len() + 1],
+}
+
+impl RttHistogram {
+    pub(crate) fn record(&mut self, rtt_ms: u64) {
+        let index = RTT_HISTOGRAM_BOUNDS_MS.iter().position(|&bound| rtt_ms <= bound).unwrap_or(RTT_HISTOGRAM_BOUNDS_MS.len());
+        self.counts[index] += 1;
+    }
+
+    /// Returns the total number of samples.
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
+/// Describes the entire lifetime of a connection. Generated when an established connection ends,
+/// if [`EndpointConfig::session_summaries`] is set, so that transport quality may be recorded
+/// without polling during the session.
+#[derive(Clone,Debug,PartialEq)]
+pub struct SessionSummary {
+    /// The reason the connection ended.
+    pub reason: CloseReason,
+    /// The time for which the connection was active, i.e. from the completion of the connection
+    /// handshake until the connection began closing or was closed.
+    pub duration: std::time::Duration,
+    /// The total number of packets enqueued for sending.
+    pub packets_sent: u64,
+    /// The combined size of all packets enqueued for sending, in bytes.
+    pub bytes_sent: u64,
+    /// The total number of packets delivered to the application.
+    pub packets_received: u64,
+    /// The combined size of all packets delivered to the application, in bytes.
+    pub bytes_received: u64,
+    /// The distribution of round-trip time samples.
+    pub rtt_histogram: RttHistogram,
+    /// The connection's final link statistics.
+    pub link_stats: LinkStats,
+}
+
+/// Measurements of a single path of a client which sends over several (see
+/// [`client::Client::add_path()`]).
+///
+/// Each data frame is sent over one path, and its acknowledgement is attributed to that path,
+/// regardless of the path over which the acknowledgement arrives.
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct PathStats {
+    /// The current estimate of the path's round-trip time (RTT), in seconds, if computed.
+    pub rtt_s: Option<f64>,
+    /// A moving average of the fraction of data frames sent over the path which were lost.
+    pub loss_rate: f64,
+    /// Whether the path is currently in use. A path is not used until a frame has been received
+    /// over it, and stops being used if frames sent over it go unacknowledged for too long, after
+    /// which it is periodically probed until it recovers.
+    pub alive: bool,
+    /// The total number of data frames sent over the path.
+    pub sent_frames: u64,
+}
+
+/// Packet counters for a single channel of a connection.
+///
+/// Outgoing counters describe packets sent by this endpoint, and incoming counters describe
+/// packets received by this endpoint. Packets dropped by the sender are never placed on the
+/// network, and are therefore not counted as lost.
+#[derive(Clone,Debug,Default,PartialEq)]
+pub struct ChannelStats {
+    /// The number of incoming packets which have been delivered to the application.
+    pub delivered: u64,
+    /// The number of incoming packets which arrived after a newer packet on the same channel.
+    pub out_of_order: u64,
+    /// The number of times an incoming packet awaiting a missing predecessor exceeded the
+    /// channel's [`ReorderLimits`] in [`Strict`](ReorderMode::Strict) mode.
+    pub reorder_stalls: u64,
+    /// The number of times missing incoming packets were skipped because a waiting packet
+    /// exceeded the channel's [`ReorderLimits`] in [`Lossy`](ReorderMode::Lossy) mode.
+    pub reorder_skips: u64,
+    /// The number of outgoing packets which were dropped by this endpoint prior to being sent, e.g.
+    /// [`TimeSensitive`](SendMode::TimeSensitive) packets which could not be sent immediately.
+    pub sender_dropped: u64,
+    /// The number of outgoing packets which are inferred to have been lost in the network, i.e.
+    /// those which were forgotten by this endpoint without having been acknowledged in full.
+    ///
+    /// *Note*: Because acknowledgements may also be lost, this is an upper bound.
+    pub lost: u64,
+    /// The combined size of the outgoing packets which are waiting to be sent, in bytes.
+    pub queued_bytes: usize,
 }
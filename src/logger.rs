@@ -0,0 +1,61 @@
+
+use std::fmt;
+use std::net;
+
+// The number of consecutive frames from a known remote host which must fail to decode before a
+// warning is logged
+pub(crate) const DECODE_FAILURE_WARN_COUNT: u32 = 8;
+
+/// The severity of a message passed to a [`Logger`].
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord)]
+pub enum LogLevel {
+    /// A condition which prevents a connection from functioning.
+    Error,
+    /// An anomaly which may degrade a connection, e.g. a stalled frame window, or frames which
+    /// violate the protocol.
+    Warn,
+    /// A notable change in connection state.
+    Info,
+    /// Detailed diagnostic information.
+    Debug,
+}
+
+/// Receives diagnostic messages concerning individual connections, e.g. to forward them to an
+/// application's logging facility. See [`client::Config`](crate::client::Config) and
+/// [`server::Config`](crate::server::Config).
+pub trait Logger: Send {
+    /// Called with a message concerning the connection to the remote host with the given address.
+    fn log(&mut self, level: LogLevel, address: net::SocketAddr, message: fmt::Arguments);
+}
+
+/// A [`Logger`] which writes messages to standard error, prefixed by the address of the
+/// corresponding remote host.
+#[derive(Clone,Copy,Debug)]
+pub struct StderrLogger {
+    /// Messages less severe than this level are discarded.
+    pub max_level: LogLevel,
+}
+
+impl Default for StderrLogger {
+    /// Creates a logger which writes warnings and errors.
+    fn default() -> Self {
+        Self {
+            max_level: LogLevel::Warn,
+        }
+    }
+}
+
+impl Logger for StderrLogger {
+    fn log(&mut self, level: LogLevel, address: net::SocketAddr, message: fmt::Arguments) {
+        if level <= self.max_level {
+            eprintln!("[{}] {:?}: {}", address, level, message);
+        }
+    }
+}
+
+// Forwards a message to the given logger, if there is one
+pub(crate) fn log(logger: &mut Option<Box<dyn Logger>>, level: LogLevel, address: net::SocketAddr, message: fmt::Arguments) {
+    if let Some(logger) = logger {
+        logger.log(level, address, message);
+    }
+}
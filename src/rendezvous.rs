@@ -0,0 +1,295 @@
+use crate::client::Client;
+#[cfg(feature = "server")]
+use crate::server::Server;
+use crate::Clock;
+use crate::SystemClock;
+use crate::Transport;
+
+use std::io;
+use std::net;
+use std::sync::Arc;
+
+// Identifies punch datagrams, which are never valid frames
+const PUNCH_MAGIC: &[u8; 11] = b"\xFFuflowpunch";
+const PUNCH_DATAGRAM_SIZE: usize = PUNCH_MAGIC.len() + 1;
+
+const PUNCH_PROBE: u8 = 0;
+const PUNCH_REPLY: u8 = 1;
+
+fn punch_datagram(kind: u8) -> [u8; PUNCH_DATAGRAM_SIZE] {
+    let mut data = [0; PUNCH_DATAGRAM_SIZE];
+    data[.. PUNCH_MAGIC.len()].copy_from_slice(PUNCH_MAGIC);
+    data[PUNCH_MAGIC.len()] = kind;
+    data
+}
+
+fn read_punch_datagram(data: &[u8]) -> Option<u8> {
+    if data.len() == PUNCH_DATAGRAM_SIZE && data.starts_with(PUNCH_MAGIC) {
+        Some(data[PUNCH_MAGIC.len()])
+    } else {
+        None
+    }
+}
+
+/// Stores configuration parameters for a [`HolePunch`] object.
+pub struct Config {
+    /// The time to wait between probes, in milliseconds.
+    pub probe_interval_ms: u64,
+    /// The number of probes sent before the attempt is abandoned.
+    pub probe_count: u32,
+    /// The source of time for probe timers (see [`Clock`]).
+    pub clock: Arc<dyn Clock>,
+}
+
+impl Config {
+    /// Returns true if the given configuration is valid.
+    pub fn is_valid(&self) -> bool {
+        self.probe_interval_ms > 0 && self.probe_count > 0
+    }
+}
+
+impl Default for Config {
+    /// Creates a hole punching configuration with the following parameters:
+    ///   * Probe interval: 200ms
+    ///   * Probe count: 50
+    ///   * Clock: [`SystemClock`]
+    ///
+    /// With these values, an attempt which receives no reply is abandoned after 10s.
+    fn default() -> Self {
+        Self {
+            probe_interval_ms: 200,
+            probe_count: 50,
+            clock: Arc::new(SystemClock::new()),
+        }
+    }
+}
+
+/// The status of a [`HolePunch`].
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Status {
+    /// Probes are still being exchanged.
+    Punching,
+    /// Probes have been exchanged in both directions, so that a connection may be opened.
+    Open,
+    /// Every probe was sent without a reply from the peer.
+    Failed,
+}
+
+/// Opens a path through NATs or firewalls between two peers which know each other's public
+/// address, so that one may then connect to the other directly.
+///
+/// Public addresses are exchanged by the application over some signaling channel (e.g. a
+/// matchmaking server), after which both peers create a `HolePunch` at roughly the same time, using
+/// the transport bound to the address they advertised. Each peer periodically sends a small probe
+/// to the other, and replies to every probe it receives. Once a reply arrives, both directions of
+/// the path are known to be open, and the status becomes [`Status::Open`].
+///
+/// The transport is then handed off to a normal connection: one peer (chosen by the application,
+/// e.g. via signaling) calls [`into_server()`](Self::into_server), and the other calls
+/// [`into_client()`](Self::into_client). The connection's transport continues to answer probes
+/// from the peer, so a peer whose replies were lost still sees the path open.
+///
+/// ```
+/// let (a, b) = uflow::transport::Memory::pair();
+/// let (a_address, b_address) = (a.address(), b.address());
+///
+/// let mut a = uflow::rendezvous::HolePunch::new(a, b_address, Default::default());
+/// let mut b = uflow::rendezvous::HolePunch::new(b, a_address, Default::default());
+///
+/// loop {
+///     let (a_status, b_status) = (a.step(), b.step());
+///
+///     if a_status == uflow::rendezvous::Status::Open && b_status == uflow::rendezvous::Status::Open {
+///         break;
+///     }
+///
+///     // ...
+/// }
+///
+/// let mut server = a.into_server(Default::default());
+/// let mut client = b.into_client(Default::default()).unwrap();
+/// ```
+pub struct HolePunch<T: Transport> {
+    transport: T,
+    peer_address: net::SocketAddr,
+    config: Config,
+
+    status: Status,
+    probes_sent: u32,
+    next_probe_time_ms: u64,
+}
+
+impl<T: Transport> HolePunch<T> {
+    /// Begins punching a path to the peer at the given address over the provided non-blocking
+    /// transport. The first probe is sent immediately.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if the provided configuration is not valid.
+    pub fn new(transport: T, peer_address: net::SocketAddr, config: Config) -> Self {
+        assert!(config.is_valid(), "invalid hole punch config");
+
+        let next_probe_time_ms = config.clock.now().as_millis() as u64;
+
+        let mut punch = Self {
+            transport,
+            peer_address,
+            config,
+
+            status: Status::Punching,
+            probes_sent: 0,
+            next_probe_time_ms,
+        };
+
+        punch.step();
+
+        punch
+    }
+
+    /// Returns the address of the peer.
+    pub fn peer_address(&self) -> net::SocketAddr {
+        self.peer_address
+    }
+
+    /// Returns the current status of the attempt.
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    /// Processes received probes and replies, and sends a new probe if one is due. Returns the
+    /// resulting status of the attempt.
+    ///
+    /// Once the status is [`Status::Open`], probes from the peer should continue to be answered
+    /// (by calling `step()`) until the transport is handed off.
+    pub fn step(&mut self) -> Status {
+        let mut recv_buf = [0; PUNCH_DATAGRAM_SIZE + 1];
+
+        while let Ok((size, address)) = self.transport.recv_from(&mut recv_buf) {
+            if address != self.peer_address {
+                continue;
+            }
+
+            match read_punch_datagram(&recv_buf[.. size]) {
+                Some(PUNCH_PROBE) => {
+                    let _ = self.transport.send_to(&punch_datagram(PUNCH_REPLY), self.peer_address);
+                }
+                Some(PUNCH_REPLY) if self.status == Status::Punching => {
+                    self.status = Status::Open;
+                }
+                _ => (),
+            }
+        }
+
+        if self.status == Status::Punching {
+            let now_ms = self.config.clock.now().as_millis() as u64;
+
+            if now_ms >= self.next_probe_time_ms {
+                if self.probes_sent < self.config.probe_count {
+                    let _ = self.transport.send_to(&punch_datagram(PUNCH_PROBE), self.peer_address);
+
+                    self.probes_sent += 1;
+                    self.next_probe_time_ms = now_ms + self.config.probe_interval_ms;
+                } else {
+                    self.status = Status::Failed;
+                }
+            }
+        }
+
+        self.status
+    }
+
+    /// Returns a transport which continues to answer probes from the peer, and which passes every
+    /// other datagram through unchanged.
+    pub fn into_transport(self) -> Punched<T> {
+        Punched {
+            inner: self.transport,
+            peer_address: self.peer_address,
+        }
+    }
+
+    /// Connects to the peer as a client over the punched path (see
+    /// [`Client::with_transport()`]).
+    ///
+    /// # Error Handling
+    ///
+    /// Any errors resulting from querying the transport's local address are forwarded to the
+    /// caller. This function will panic if the provided client configuration is not valid.
+    pub fn into_client(self, config: crate::client::Config) -> Result<Client<Punched<T>>, io::Error> {
+        let peer_address = self.peer_address;
+        Client::with_transport(self.into_transport(), peer_address, config)
+    }
+
+    /// Accepts a connection from the peer as a server over the punched path (see
+    /// [`Server::with_transport()`]).
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if the provided server configuration is not valid.
+    #[cfg(feature = "server")]
+    pub fn into_server(self, config: crate::server::Config) -> Server<Punched<T>> {
+        Server::with_transport(self.into_transport(), config)
+    }
+}
+
+/// A transport over which a [`HolePunch`] has been completed (see
+/// [`HolePunch::into_transport()`]).
+pub struct Punched<T: Transport> {
+    inner: T,
+    peer_address: net::SocketAddr,
+}
+
+impl<T: Transport> Punched<T> {
+    /// Returns the address of the peer.
+    pub fn peer_address(&self) -> net::SocketAddr {
+        self.peer_address
+    }
+
+    /// Returns the underlying transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: Transport> Transport for Punched<T> {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        self.inner.send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        loop {
+            let (size, address) = self.inner.recv_from(buf)?;
+
+            if address == self.peer_address {
+                match read_punch_datagram(&buf[.. size]) {
+                    Some(PUNCH_PROBE) => {
+                        let _ = self.inner.send_to(&punch_datagram(PUNCH_REPLY), self.peer_address);
+                        continue;
+                    }
+                    Some(_) => continue,
+                    None => (),
+                }
+            }
+
+            return Ok((size, address));
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn punch_datagram_roundtrip() {
+        assert_eq!(read_punch_datagram(&punch_datagram(PUNCH_PROBE)), Some(PUNCH_PROBE));
+        assert_eq!(read_punch_datagram(&punch_datagram(PUNCH_REPLY)), Some(PUNCH_REPLY));
+
+        let data = punch_datagram(PUNCH_PROBE);
+        assert_eq!(read_punch_datagram(&data[.. PUNCH_DATAGRAM_SIZE - 1]), None);
+        assert_eq!(read_punch_datagram(&[ 0; PUNCH_DATAGRAM_SIZE ]), None);
+    }
+}
@@ -0,0 +1,253 @@
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::hash::Hash;
+
+struct PeerEntry {
+    // Snapshot IDs which have been sent to this peer, but not yet acknowledged, oldest first
+    sent_ids: VecDeque<u32>,
+    // Most recent snapshot ID acknowledged by this peer
+    acked_id: Option<u32>,
+}
+
+impl PeerEntry {
+    fn new() -> Self {
+        Self {
+            sent_ids: VecDeque::new(),
+            acked_id: None,
+        }
+    }
+}
+
+/// Tracks which full-state snapshot (the *baseline*) each peer has acknowledged, and therefore
+/// which snapshot a new delta should be computed against.
+///
+/// Snapshots are identified by sequential `u32` IDs which may wrap. For each peer, the application
+/// calls [`mark_sent()`](Self::mark_sent) once a snapshot has been sent, and
+/// [`acknowledge()`](Self::acknowledge) once that peer has reported receipt of a snapshot. The
+/// tracker then reports the newest acknowledged snapshot via [`baseline()`](Self::baseline),
+/// provided that the application still retains that snapshot. If no usable baseline exists, the
+/// application should send a full snapshot instead.
+///
+/// ```
+/// let mut tracker = uflow::replication::BaselineTracker::new(32);
+/// let peer = "127.0.0.1:8888".parse::<std::net::SocketAddr>().unwrap();
+///
+/// tracker.mark_sent(&peer, 0);
+/// tracker.mark_sent(&peer, 1);
+/// assert_eq!(tracker.baseline(&peer), None);
+///
+/// // Peer has reported receipt of snapshot 0, future deltas may be based on it
+/// tracker.acknowledge(&peer, 0);
+/// assert_eq!(tracker.baseline(&peer), Some(0));
+/// ```
+pub struct BaselineTracker<P> {
+    peers: HashMap<P, PeerEntry>,
+    history_len: u32,
+    latest_id: Option<u32>,
+}
+
+impl<P> BaselineTracker<P> where P: Eq + Hash + Clone {
+    /// Creates a new tracker for an application which retains the `history_len` most recent
+    /// snapshots. A peer's acknowledged snapshot is not reported as a baseline once it falls out of
+    /// this history.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `history_len` is zero.
+    pub fn new(history_len: u32) -> Self {
+        assert!(history_len > 0, "invalid snapshot history length");
+
+        Self {
+            peers: HashMap::new(),
+            history_len,
+            latest_id: None,
+        }
+    }
+
+    /// Records that the snapshot with the given ID has been sent to the given peer. Snapshot IDs
+    /// are expected to increase (modulo 2^32) with each call for a given peer.
+    pub fn mark_sent(&mut self, peer: &P, snapshot_id: u32) {
+        let entry = self.peers.entry(peer.clone()).or_insert_with(PeerEntry::new);
+
+        if let Some(&last_id) = entry.sent_ids.back() {
+            if snapshot_id.wrapping_sub(last_id).wrapping_sub(1) >= i32::MAX as u32 {
+                // Not newer than the last sent snapshot
+                return;
+            }
+        }
+
+        entry.sent_ids.push_back(snapshot_id);
+
+        while entry.sent_ids.len() > self.history_len as usize {
+            entry.sent_ids.pop_front();
+        }
+
+        match self.latest_id {
+            Some(latest_id) if snapshot_id.wrapping_sub(latest_id) >= i32::MAX as u32 => (),
+            _ => self.latest_id = Some(snapshot_id),
+        }
+    }
+
+    /// Records that the given peer has received the snapshot with the given ID. Returns `true` if
+    /// the peer's baseline has advanced as a result.
+    ///
+    /// Acknowledgements for snapshots which were never sent, or which are older than the current
+    /// baseline, are ignored.
+    pub fn acknowledge(&mut self, peer: &P, snapshot_id: u32) -> bool {
+        if let Some(entry) = self.peers.get_mut(peer) {
+            if let Some(idx) = entry.sent_ids.iter().position(|&id| id == snapshot_id) {
+                // Older snapshots can no longer become the baseline
+                entry.sent_ids.drain(..= idx);
+                entry.acked_id = Some(snapshot_id);
+
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns the ID of the snapshot which a delta for the given peer should be computed against.
+    /// Returns `None` if the peer has not acknowledged any snapshot, or if its most recently
+    /// acknowledged snapshot is no longer retained by the application.
+    pub fn baseline(&self, peer: &P) -> Option<u32> {
+        let acked_id = self.peers.get(peer)?.acked_id?;
+
+        if self.is_retained(acked_id) {
+            Some(acked_id)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the ID of the oldest snapshot which is a baseline for any peer. Snapshots older than
+    /// this one are not needed to compute deltas, and may be discarded by the application.
+    pub fn oldest_baseline(&self) -> Option<u32> {
+        let latest_id = self.latest_id?;
+
+        self.peers.values()
+            .filter_map(|entry| entry.acked_id)
+            .filter(|&acked_id| self.is_retained(acked_id))
+            .max_by_key(|&acked_id| latest_id.wrapping_sub(acked_id))
+    }
+
+    /// Forgets the given peer, e.g. once it has disconnected. A subsequent snapshot sent to this
+    /// peer must be a full snapshot.
+    pub fn remove(&mut self, peer: &P) {
+        self.peers.remove(peer);
+    }
+
+    fn is_retained(&self, snapshot_id: u32) -> bool {
+        match self.latest_id {
+            Some(latest_id) => latest_id.wrapping_sub(snapshot_id) < self.history_len,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_baseline() {
+        let mut tracker = BaselineTracker::new(8);
+
+        assert_eq!(tracker.baseline(&0), None);
+
+        tracker.mark_sent(&0, 10);
+        tracker.mark_sent(&0, 11);
+        tracker.mark_sent(&0, 12);
+
+        assert_eq!(tracker.baseline(&0), None);
+
+        assert_eq!(tracker.acknowledge(&0, 11), true);
+        assert_eq!(tracker.baseline(&0), Some(11));
+
+        // Older acknowledgements do not regress the baseline
+        assert_eq!(tracker.acknowledge(&0, 10), false);
+        assert_eq!(tracker.baseline(&0), Some(11));
+
+        // Acknowledgements for unsent snapshots are ignored
+        assert_eq!(tracker.acknowledge(&0, 13), false);
+        assert_eq!(tracker.baseline(&0), Some(11));
+
+        assert_eq!(tracker.acknowledge(&0, 12), true);
+        assert_eq!(tracker.baseline(&0), Some(12));
+
+        // Peers are independent
+        assert_eq!(tracker.baseline(&1), None);
+        assert_eq!(tracker.acknowledge(&1, 12), false);
+    }
+
+    #[test]
+    fn baseline_expiry() {
+        let mut tracker = BaselineTracker::new(4);
+
+        tracker.mark_sent(&0, 0);
+        tracker.acknowledge(&0, 0);
+
+        for id in 1 .. 4 {
+            tracker.mark_sent(&0, id);
+            assert_eq!(tracker.baseline(&0), Some(0));
+        }
+
+        // Snapshot 0 is no longer retained
+        tracker.mark_sent(&0, 4);
+        assert_eq!(tracker.baseline(&0), None);
+
+        assert_eq!(tracker.acknowledge(&0, 1), true);
+        assert_eq!(tracker.baseline(&0), Some(1));
+
+        tracker.mark_sent(&0, 5);
+        assert_eq!(tracker.baseline(&0), None);
+
+        // Snapshot 2 has been evicted from the sent history
+        tracker.mark_sent(&0, 6);
+        tracker.mark_sent(&0, 7);
+        tracker.mark_sent(&0, 8);
+        tracker.mark_sent(&0, 9);
+        assert_eq!(tracker.acknowledge(&0, 2), false);
+        assert_eq!(tracker.acknowledge(&0, 6), true);
+        assert_eq!(tracker.baseline(&0), Some(6));
+    }
+
+    #[test]
+    fn oldest_baseline() {
+        let mut tracker = BaselineTracker::new(8);
+
+        for id in 0 .. 4 {
+            tracker.mark_sent(&0, id);
+            tracker.mark_sent(&1, id);
+        }
+
+        assert_eq!(tracker.oldest_baseline(), None);
+
+        tracker.acknowledge(&0, 3);
+        assert_eq!(tracker.oldest_baseline(), Some(3));
+
+        tracker.acknowledge(&1, 1);
+        assert_eq!(tracker.oldest_baseline(), Some(1));
+
+        tracker.remove(&1);
+        assert_eq!(tracker.oldest_baseline(), Some(3));
+    }
+
+    #[test]
+    fn id_wraparound() {
+        let mut tracker = BaselineTracker::new(8);
+
+        tracker.mark_sent(&0, u32::MAX - 1);
+        tracker.mark_sent(&0, u32::MAX);
+        tracker.mark_sent(&0, 0);
+        tracker.mark_sent(&0, 1);
+
+        assert_eq!(tracker.acknowledge(&0, u32::MAX), true);
+        assert_eq!(tracker.baseline(&0), Some(u32::MAX));
+        assert_eq!(tracker.oldest_baseline(), Some(u32::MAX));
+
+        assert_eq!(tracker.acknowledge(&0, 1), true);
+        assert_eq!(tracker.baseline(&0), Some(1));
+    }
+}
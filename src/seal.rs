@@ -0,0 +1,808 @@
+use crate::EndpointConfig;
+use crate::MAX_FRAME_SIZE;
+use crate::MAX_TRANSFORM_OVERHEAD;
+use crate::Transport;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::net;
+
+use crate::frame;
+#[cfg(feature = "encryption")]
+use crate::SessionKeys;
+
+#[cfg(feature = "encryption")]
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, Key, KeyInit, Nonce, Tag};
+#[cfg(feature = "encryption")]
+use std::cell::Cell;
+
+// Random sender prefix (u32) and frame sequence ID (u64), which together form the nonce
+const SEAL_HEADER_SIZE: usize = 12;
+const SEAL_TAG_SIZE: usize = 16;
+
+// The overhead of a sealed frame, regardless of whether this build can seal frames, so that
+// fragments are sized alike by every build
+pub const MAX_SEAL_OVERHEAD: usize = SEAL_HEADER_SIZE + SEAL_TAG_SIZE;
+
+#[cfg(feature = "encryption")]
+pub const SEAL_OVERHEAD: usize = MAX_SEAL_OVERHEAD;
+
+// The number of sequence IDs preceding the greatest yet received which are checked for replays
+#[cfg(feature = "encryption")]
+const REPLAY_WINDOW_SIZE: u64 = 1024;
+
+/// The maximum size of a datagram carrying a single frame, sealed or otherwise, and tagged with a
+/// connection ID or otherwise, including any datagram transform overhead.
+pub const MAX_DATAGRAM_SIZE: usize = MAX_FRAME_SIZE + MAX_TRANSFORM_OVERHEAD;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Role {
+    Client,
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    Server,
+}
+
+#[cfg(feature = "encryption")]
+struct Cipher {
+    seal_key: ChaCha20Poly1305,
+    open_key: ChaCha20Poly1305,
+    prefix: u32,
+    next_sequence_id: Cell<u64>,
+}
+
+#[cfg(feature = "encryption")]
+impl Cipher {
+    fn new(keys: &SessionKeys, role: Role) -> Self {
+        let (seal_key, open_key) = match role {
+            Role::Client => (&keys.client_to_server, &keys.server_to_client),
+            Role::Server => (&keys.server_to_client, &keys.client_to_server),
+        };
+
+        Self {
+            seal_key: ChaCha20Poly1305::new(Key::from_slice(seal_key)),
+            open_key: ChaCha20Poly1305::new(Key::from_slice(open_key)),
+            prefix: rand::random::<u32>(),
+            next_sequence_id: Cell::new(0),
+        }
+    }
+
+    fn seal(&self, frame_data: &[u8]) -> Box<[u8]> {
+        self.reserve().seal(frame_data)
+    }
+
+    // Reserves the next sequence ID, so that a frame may be sealed with it later, possibly on
+    // another thread
+    fn reserve(&self) -> Sealer<'_> {
+        let sequence_id = self.next_sequence_id.get();
+        self.next_sequence_id.set(sequence_id.wrapping_add(1));
+
+        let mut header = [0; SEAL_HEADER_SIZE];
+        header[.. 4].copy_from_slice(&self.prefix.to_be_bytes());
+        header[4 ..].copy_from_slice(&sequence_id.to_be_bytes());
+
+        Sealer {
+            seal_key: &self.seal_key,
+            header,
+        }
+    }
+
+    // Opens a sealed datagram in place, returning false if it failed authentication, in which case
+    // the datagram is left unmodified
+    fn open_in_place(&self, datagram: &mut [u8]) -> bool {
+        if datagram.len() < SEAL_OVERHEAD {
+            return false;
+        }
+
+        let (header, rest) = datagram.split_at_mut(SEAL_HEADER_SIZE);
+        let (payload, tag) = rest.split_at_mut(rest.len() - SEAL_TAG_SIZE);
+
+        self.open_key.decrypt_in_place_detached(Nonce::from_slice(header), b"", payload, Tag::from_slice(tag)).is_ok()
+    }
+
+    #[cfg(test)]
+    fn open<'a>(&self, datagram: &'a mut [u8]) -> Option<&'a [u8]> {
+        if self.open_in_place(datagram) {
+            Some(opened_payload(datagram))
+        } else {
+            None
+        }
+    }
+}
+
+// Seals a single frame using a nonce reserved from a cipher
+#[cfg(feature = "encryption")]
+struct Sealer<'a> {
+    seal_key: &'a ChaCha20Poly1305,
+    header: [u8; SEAL_HEADER_SIZE],
+}
+
+#[cfg(feature = "encryption")]
+impl<'a> Sealer<'a> {
+    fn seal(&self, frame_data: &[u8]) -> Box<[u8]> {
+        let mut datagram = Vec::with_capacity(frame_data.len() + SEAL_OVERHEAD);
+        datagram.extend_from_slice(&self.header);
+        datagram.extend_from_slice(frame_data);
+
+        let (header, payload) = datagram.split_at_mut(SEAL_HEADER_SIZE);
+        let tag = self.seal_key.encrypt_in_place_detached(Nonce::from_slice(header), b"", payload)
+            .expect("frame too large to seal");
+
+        datagram.extend_from_slice(&tag);
+
+        datagram.into_boxed_slice()
+    }
+}
+
+#[cfg(feature = "encryption")]
+fn opened_payload(datagram: &[u8]) -> &[u8] {
+    &datagram[SEAL_HEADER_SIZE .. datagram.len() - SEAL_TAG_SIZE]
+}
+
+#[cfg(feature = "encryption")]
+fn sender_prefix(datagram: &[u8]) -> Option<u32> {
+    if datagram.len() < SEAL_OVERHEAD {
+        return None;
+    }
+
+    Some(u32::from_be_bytes([ datagram[0], datagram[1], datagram[2], datagram[3] ]))
+}
+
+// Tracks the sequence IDs of frames opened from a single sender, so that replayed frames may be
+// discarded. Sequence IDs which precede the window are presumed to be replays.
+#[cfg(feature = "encryption")]
+#[derive(Clone)]
+struct ReplayWindow {
+    // The greatest sequence ID yet accepted
+    max_id: u64,
+    // Bit (id % REPLAY_WINDOW_SIZE) is set if the sequence ID has been accepted
+    seen: [u64; (REPLAY_WINDOW_SIZE / 64) as usize],
+}
+
+#[cfg(feature = "encryption")]
+impl ReplayWindow {
+    fn new(sequence_id: u64) -> Self {
+        let mut window = Self {
+            max_id: sequence_id,
+            seen: [0; (REPLAY_WINDOW_SIZE / 64) as usize],
+        };
+
+        window.mark(sequence_id);
+
+        window
+    }
+
+    fn mark(&mut self, sequence_id: u64) {
+        let bit = sequence_id % REPLAY_WINDOW_SIZE;
+        self.seen[(bit / 64) as usize] |= 1 << (bit % 64);
+    }
+
+    fn is_marked(&self, sequence_id: u64) -> bool {
+        let bit = sequence_id % REPLAY_WINDOW_SIZE;
+        self.seen[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+    }
+
+    // Returns true and marks the sequence ID if it has not been seen before
+    fn accept(&mut self, sequence_id: u64) -> bool {
+        if sequence_id > self.max_id {
+            let advance = sequence_id - self.max_id;
+
+            if advance >= REPLAY_WINDOW_SIZE {
+                self.seen = [0; (REPLAY_WINDOW_SIZE / 64) as usize];
+            } else {
+                for id in self.max_id + 1 ..= sequence_id {
+                    let bit = id % REPLAY_WINDOW_SIZE;
+                    self.seen[(bit / 64) as usize] &= !(1 << (bit % 64));
+                }
+            }
+
+            self.max_id = sequence_id;
+            self.mark(sequence_id);
+
+            true
+        } else if self.max_id - sequence_id >= REPLAY_WINDOW_SIZE || self.is_marked(sequence_id) {
+            false
+        } else {
+            self.mark(sequence_id);
+
+            true
+        }
+    }
+}
+
+// The state kept for each peer, by its address
+#[derive(Default)]
+struct Peer {
+    // The connection ID assigned by the peer, with which frames sent to it are tagged
+    connection_id: Option<u32>,
+    // Keys agreed upon with the peer
+    #[cfg(feature = "encryption")]
+    cipher: Option<Cipher>,
+    // Indexed by the random prefix of each sender at the peer's address, which is authenticated as
+    // part of the nonce
+    #[cfg(feature = "encryption")]
+    replay_windows: HashMap<u32, ReplayWindow>,
+}
+
+impl Peer {
+    // Returns a peer which has only received the frames this peer has
+    fn copy_replay_windows(&self) -> Peer {
+        Peer {
+            #[cfg(feature = "encryption")]
+            replay_windows: self.replay_windows.clone(),
+            .. Default::default()
+        }
+    }
+
+    // Adopts the replay windows of senders known only to the other peer
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    fn merge_replay_windows(&mut self, other: Peer) {
+        #[cfg(feature = "encryption")]
+        for (prefix, window) in other.replay_windows.into_iter() {
+            self.replay_windows.entry(prefix).or_insert(window);
+        }
+    }
+}
+
+#[cfg(feature = "encryption")]
+enum Opened {
+    // The datagram was authenticated, and its payload decrypted in place. If it was authenticated
+    // using the keys agreed upon with a peer at another address, that address is given.
+    Sealed(Option<net::SocketAddr>),
+    // The datagram was not sealed, and may be used as-is
+    Clear,
+    // The datagram failed authentication, or was not permitted in the clear
+    Rejected,
+}
+
+// Seals frames sent over, and opens frames received from, an underlying transport according to
+// the session keys of the endpoint configuration. Frames are passed through unmodified if no keys
+// are configured.
+//
+// If keys are exchanged during the handshake, frames exchanged with each peer are instead sealed
+// using the keys agreed upon with that peer, and only the handshake frames which precede the
+// exchange are sealed with the configured keys, if any.
+//
+// Any sealed frame other than a handshake frame is discarded if a frame bearing the same nonce has
+// already been received from the same peer, so that captured frames cannot be replayed.
+//
+// If a connection ID has been assigned by a peer, every frame sent to that peer other than a
+// connection request is tagged with it before being sealed.
+//
+// A peer may be reached at additional path addresses, over which frames are handled exactly as
+// though they were exchanged with the peer's own address. A server also recognizes the frames of a
+// client with which keys have been exchanged when they arrive from an unknown address, by the
+// random sender prefix of the client's keys.
+pub struct Sealed<T> {
+    inner: T,
+    peers: RefCell<HashMap<net::SocketAddr, Peer>>,
+    // The address of the peer to which each additional path belongs
+    paths: RefCell<HashMap<net::SocketAddr, net::SocketAddr>>,
+    #[cfg(feature = "encryption")]
+    role: Role,
+    #[cfg(feature = "encryption")]
+    cipher: Option<Cipher>,
+    #[cfg(feature = "encryption")]
+    key_exchange: bool,
+    // The address of the peer with exchanged keys to which each sender prefix belongs
+    #[cfg(feature = "encryption")]
+    senders: RefCell<HashMap<u32, net::SocketAddr>>,
+}
+
+impl<T> Sealed<T> {
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    pub fn new(inner: T, config: &EndpointConfig, role: Role, key_exchange: bool) -> Self {
+        Self {
+            inner,
+            peers: RefCell::new(HashMap::new()),
+            paths: RefCell::new(HashMap::new()),
+            #[cfg(feature = "encryption")]
+            role,
+            #[cfg(feature = "encryption")]
+            cipher: config.session_keys.as_ref().map(|keys| Cipher::new(keys, role)),
+            #[cfg(feature = "encryption")]
+            key_exchange,
+            #[cfg(feature = "encryption")]
+            senders: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Seals all further frames exchanged with the given peer using keys agreed upon with that
+    /// peer.
+    #[cfg(feature = "encryption")]
+    pub fn set_peer_keys(&self, address: net::SocketAddr, keys: &SessionKeys) {
+        self.peers.borrow_mut().entry(address).or_default().cipher = Some(Cipher::new(keys, self.role));
+    }
+
+    /// Tags all further frames sent to the given peer with a connection ID assigned by that peer.
+    pub fn set_peer_connection_id(&self, address: net::SocketAddr, connection_id: u32) {
+        self.peers.borrow_mut().entry(address).or_default().connection_id = Some(connection_id);
+    }
+
+    /// Handles all further frames exchanged over the given path address as though they were
+    /// exchanged with the given peer, until the path is forgotten via
+    /// [`forget_peer()`](Self::forget_peer). Any frames previously received from the path address
+    /// are forgotten.
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub fn add_peer_path(&self, address: net::SocketAddr, path_address: net::SocketAddr) {
+        if let Some(peer) = self.peers.borrow_mut().remove(&path_address) {
+            self.update_senders(&peer, path_address, None);
+        }
+
+        self.paths.borrow_mut().insert(path_address, address);
+    }
+
+    /// Moves all state concerning the peer at the first address to the second, e.g. when the peer
+    /// has migrated. Frames received from either address are not accepted again.
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub fn move_peer(&self, address: net::SocketAddr, new_address: net::SocketAddr) {
+        let mut peers = self.peers.borrow_mut();
+
+        self.paths.borrow_mut().remove(&new_address);
+
+        if let Some(mut peer) = peers.remove(&address) {
+            if let Some(new_peer) = peers.remove(&new_address) {
+                peer.merge_replay_windows(new_peer);
+            }
+
+            self.update_senders(&peer, address, Some(new_address));
+
+            peers.insert(new_address, peer);
+        }
+    }
+
+    /// Copies the frames received from the peer at the first address to the peer at the second,
+    /// e.g. when one of several peers at an address has migrated, so that those frames are not
+    /// accepted again from the second address.
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub fn copy_peer(&self, address: net::SocketAddr, new_address: net::SocketAddr) {
+        let mut peers = self.peers.borrow_mut();
+
+        self.paths.borrow_mut().remove(&new_address);
+
+        if let Some(peer) = peers.get(&address) {
+            let copy = peer.copy_replay_windows();
+            peers.entry(new_address).or_default().merge_replay_windows(copy);
+        }
+    }
+
+    // Points those sender prefixes which belong to the given peer at its new address, or forgets
+    // them if the peer has no new address
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    fn update_senders(&self, peer: &Peer, address: net::SocketAddr, new_address: Option<net::SocketAddr>) {
+        #[cfg(feature = "encryption")]
+        {
+            let mut senders = self.senders.borrow_mut();
+
+            for prefix in peer.replay_windows.keys() {
+                if senders.get(prefix) == Some(&address) {
+                    match new_address {
+                        Some(new_address) => senders.insert(*prefix, new_address),
+                        None => senders.remove(prefix),
+                    };
+                }
+            }
+        }
+    }
+
+    // Returns the address of the peer to which frames exchanged with the given address belong
+    fn peer_address(&self, address: net::SocketAddr) -> net::SocketAddr {
+        self.paths.borrow().get(&address).copied().unwrap_or(address)
+    }
+
+    /// Returns the number of bytes added to each frame sent to the given peer once its connection
+    /// has been established.
+    pub fn frame_overhead(&self, address: net::SocketAddr) -> usize {
+        let peers = self.peers.borrow();
+        let peer = peers.get(&self.peer_address(address));
+
+        let tag_overhead = if peer.is_some_and(|peer| peer.connection_id.is_some()) {
+            frame::serial::CONNECTION_ID_SIZE
+        } else {
+            0
+        };
+
+        #[cfg(feature = "encryption")]
+        if self.cipher.is_some() || (self.key_exchange && peer.is_some_and(|peer| peer.cipher.is_some())) {
+            return tag_overhead + SEAL_OVERHEAD;
+        }
+
+        tag_overhead
+    }
+
+    /// Forgets any keys agreed upon with, any connection ID assigned by, and any frames received
+    /// from, the given peer. If the address is an additional path of a peer, only the path is
+    /// forgotten.
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
+    pub fn forget_peer(&self, address: &net::SocketAddr) {
+        if self.paths.borrow_mut().remove(address).is_none() {
+            if let Some(peer) = self.peers.borrow_mut().remove(address) {
+                self.update_senders(&peer, *address, None);
+            }
+        }
+    }
+
+    /// Opens a sealed datagram in place, returning the frame data it contains, or `None` if the
+    /// datagram failed authentication, or has been received before.
+    pub fn open<'a>(&self, datagram: &'a mut [u8], address: net::SocketAddr) -> Option<&'a [u8]> {
+        self.open_roaming(datagram, address).map(|(frame_data, _)| frame_data)
+    }
+
+    /// Identical to [`open()`](Self::open), but also returns the address of the peer whose
+    /// exchanged keys authenticated the datagram, if that peer is known by another address.
+    #[cfg_attr(not(feature = "encryption"), allow(unused_variables))]
+    pub fn open_roaming<'a>(&self, datagram: &'a mut [u8], address: net::SocketAddr)
+        -> Option<(&'a [u8], Option<net::SocketAddr>)> {
+        #[cfg(feature = "encryption")]
+        match self.open_in_place(datagram, address) {
+            Opened::Sealed(peer_address) => {
+                let frame_data = opened_payload(datagram);
+
+                // A replayed connection request or reply is no different from a resent one, and
+                // is handled by the handshake itself
+                if !frame::serial::precedes_key_exchange(frame_data) &&
+                    !self.accept_sequence_id(datagram, peer_address.unwrap_or(address)) {
+                    return None;
+                }
+
+                return Some((opened_payload(datagram), peer_address));
+            }
+            Opened::Clear => (),
+            Opened::Rejected => return None,
+        }
+
+        Some((datagram, None))
+    }
+
+    #[cfg(feature = "encryption")]
+    fn open_in_place(&self, datagram: &mut [u8], address: net::SocketAddr) -> Opened {
+        if self.key_exchange {
+            let peers = self.peers.borrow();
+
+            match peers.get(&self.peer_address(address)).and_then(|peer| peer.cipher.as_ref()) {
+                Some(cipher) => {
+                    if cipher.open_in_place(datagram) {
+                        return Opened::Sealed(None);
+                    }
+                }
+                None => {
+                    // The datagram may have been sent by a known peer from a new address
+                    let sender = sender_prefix(datagram).and_then(|prefix| self.senders.borrow().get(&prefix).copied());
+
+                    if let Some(peer_address) = sender {
+                        let cipher = peers.get(&peer_address).and_then(|peer| peer.cipher.as_ref());
+
+                        if cipher.is_some_and(|cipher| cipher.open_in_place(datagram)) {
+                            return Opened::Sealed(Some(peer_address));
+                        }
+                    }
+                }
+            }
+
+            // Without exchanged keys, a peer may only send those frames which precede the exchange
+            let opened = match self.cipher {
+                Some(ref cipher) if cipher.open_in_place(datagram) => Opened::Sealed(None),
+                Some(_) => return Opened::Rejected,
+                None => Opened::Clear,
+            };
+
+            let frame_data = match opened {
+                Opened::Sealed(_) => opened_payload(datagram),
+                _ => datagram,
+            };
+
+            return if frame::serial::precedes_key_exchange(frame_data) {
+                opened
+            } else {
+                Opened::Rejected
+            };
+        }
+
+        match self.cipher {
+            Some(ref cipher) if cipher.open_in_place(datagram) => Opened::Sealed(None),
+            Some(_) => Opened::Rejected,
+            None => Opened::Clear,
+        }
+    }
+
+    // Returns the cipher with which the given frame would be sealed when sent to the given peer,
+    // if any
+    #[cfg(feature = "encryption")]
+    fn cipher_for<'a>(&'a self, peer: Option<&'a Peer>, frame_data: &[u8]) -> Option<&'a Cipher> {
+        if self.key_exchange && !frame::serial::precedes_key_exchange(frame_data) {
+            if let Some(cipher) = peer.and_then(|peer| peer.cipher.as_ref()) {
+                return Some(cipher);
+            }
+        }
+
+        self.cipher.as_ref()
+    }
+
+    // Returns false if a frame with the same sender prefix and sequence ID as the given
+    // authenticated datagram has already been accepted from the given peer
+    #[cfg(feature = "encryption")]
+    fn accept_sequence_id(&self, datagram: &[u8], address: net::SocketAddr) -> bool {
+        let prefix = u32::from_be_bytes([ datagram[0], datagram[1], datagram[2], datagram[3] ]);
+        let sequence_id = u64::from_be_bytes([ datagram[4], datagram[5], datagram[6], datagram[7],
+                                               datagram[8], datagram[9], datagram[10], datagram[11] ]);
+
+        let peer_address = self.peer_address(address);
+
+        let mut peers = self.peers.borrow_mut();
+        let peer = peers.entry(peer_address).or_default();
+
+        match peer.replay_windows.get_mut(&prefix) {
+            Some(window) => window.accept(sequence_id),
+            None => {
+                peer.replay_windows.insert(prefix, ReplayWindow::new(sequence_id));
+
+                // A client with exchanged keys may later be recognized by its prefix alone
+                if self.role == Role::Server && peer.cipher.is_some() {
+                    self.senders.borrow_mut().entry(prefix).or_insert(peer_address);
+                }
+
+                true
+            }
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<T: Transport> Sealed<T> {
+    /// Seals each batch of frames on rayon's global thread pool, then sends every batch in turn,
+    /// with the frames of each batch in their original order. Returns true if sending was
+    /// interrupted because the transport would block, in which case any remaining frames are
+    /// discarded.
+    pub fn send_batches(&self, batches: Vec<(net::SocketAddr, Vec<Box<[u8]>>)>) -> bool {
+        use rayon::prelude::*;
+
+        let peers = self.peers.borrow();
+
+        // Nonces are reserved up front, so that the threads share nothing but immutable keys
+        let jobs = batches.into_iter().map(|(address, frames)| {
+            let peer = peers.get(&self.peer_address(address));
+
+            let frames = frames.into_iter().map(|frame_data| {
+                let sealer = self.cipher_for(peer, &frame_data).map(Cipher::reserve);
+                (sealer, frame_data)
+            }).collect::<Vec<_>>();
+
+            (address, frames)
+        }).collect::<Vec<_>>();
+
+        let batches = jobs.into_par_iter().map(|(address, frames)| {
+            let datagrams = frames.into_iter().map(|(sealer, frame_data)| match sealer {
+                Some(sealer) => sealer.seal(&frame_data),
+                None => frame_data,
+            }).collect::<Vec<_>>();
+
+            (address, datagrams)
+        }).collect::<Vec<_>>();
+
+        for (address, datagrams) in batches {
+            for datagram in datagrams {
+                match self.inner.send_to(&datagram, address) {
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => return true,
+                    _ => (),
+                }
+            }
+        }
+
+        false
+    }
+}
+
+impl<T: Transport> Sealed<T> {
+    /// Sends a frame to the given address as though it were sent to the peer at another address,
+    /// e.g. to a peer which has yet to be moved to the address from which it was recognized.
+    pub fn send_to_peer(&self, buf: &[u8], addr: net::SocketAddr, peer_address: net::SocketAddr) -> io::Result<usize> {
+        let peers = self.peers.borrow();
+        let peer = peers.get(&peer_address);
+
+        let tagged_buf;
+
+        let frame_data = match peer.and_then(|peer| peer.connection_id) {
+            Some(connection_id) if !frame::serial::is_handshake_syn(buf) => {
+                tagged_buf = frame::serial::tag_connection_id(buf, connection_id);
+                &tagged_buf[..]
+            }
+            _ => buf,
+        };
+
+        #[cfg(feature = "encryption")]
+        if let Some(cipher) = self.cipher_for(peer, frame_data) {
+            return self.inner.send_to(&cipher.seal(frame_data), addr).map(|_| buf.len());
+        }
+
+        self.inner.send_to(frame_data, addr).map(|_| buf.len())
+    }
+
+    /// Identical to [`recv_from()`](Transport::recv_from), but also returns the address of the
+    /// peer whose exchanged keys authenticated the frame, if that peer is known by another address
+    /// (see [`open_roaming()`](Self::open_roaming)).
+    pub fn recv_from_roaming(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr, Option<net::SocketAddr>)> {
+        #[cfg(feature = "encryption")]
+        if self.cipher.is_some() || self.key_exchange {
+            let mut datagram = [0; MAX_DATAGRAM_SIZE];
+
+            loop {
+                let (size, address) = self.inner.recv_from(&mut datagram)?;
+
+                // Frames which fail authentication are dropped
+                if let Some((frame_data, peer_address)) = self.open_roaming(&mut datagram[.. size], address) {
+                    let size = frame_data.len().min(buf.len());
+                    buf[.. size].copy_from_slice(&frame_data[.. size]);
+                    return Ok((size, address, peer_address));
+                }
+            }
+        }
+
+        self.inner.recv_from(buf).map(|(size, address)| (size, address, None))
+    }
+}
+
+impl<T: Transport> Transport for Sealed<T> {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        self.send_to_peer(buf, addr, self.peer_address(addr))
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        self.recv_from_roaming(buf).map(|(size, address, _)| (size, address))
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod tests {
+    use super::*;
+
+    use crate::SessionKeys;
+
+    fn keys() -> SessionKeys {
+        SessionKeys {
+            client_to_server: [1; 32],
+            server_to_client: [2; 32],
+        }
+    }
+
+    #[test]
+    fn seal_open() {
+        let client = Cipher::new(&keys(), Role::Client);
+        let server = Cipher::new(&keys(), Role::Server);
+
+        let frame_data = [0, 1, 2, 3, 4, 5, 6, 7];
+
+        let mut datagram = client.seal(&frame_data).into_vec();
+        assert_eq!(datagram.len(), frame_data.len() + SEAL_OVERHEAD);
+        assert_eq!(server.open(&mut datagram), Some(&frame_data[..]));
+
+        // Each frame is sealed with a distinct nonce
+        let first = client.seal(&frame_data);
+        let second = client.seal(&frame_data);
+        assert_ne!(first, second);
+
+        // Frames are only opened by the opposing role
+        let mut datagram = client.seal(&frame_data).into_vec();
+        assert_eq!(client.open(&mut datagram), None);
+    }
+
+    #[test]
+    fn open_tampered() {
+        let client = Cipher::new(&keys(), Role::Client);
+        let server = Cipher::new(&keys(), Role::Server);
+
+        let sealed = client.seal(&[0, 1, 2, 3]);
+
+        for i in 0 .. sealed.len() {
+            let mut datagram = sealed.clone().into_vec();
+            datagram[i] ^= 0x01;
+            assert_eq!(server.open(&mut datagram), None);
+        }
+
+        let mut datagram = sealed.into_vec();
+        assert_eq!(server.open(&mut datagram[.. SEAL_OVERHEAD - 1]), None);
+    }
+
+    #[test]
+    fn replay_window() {
+        let mut window = ReplayWindow::new(10);
+
+        // Duplicates are rejected, in or out of order
+        assert!(!window.accept(10));
+        assert!(window.accept(12));
+        assert!(window.accept(11));
+        assert!(!window.accept(11));
+        assert!(!window.accept(12));
+
+        // Sequence IDs within the window may arrive late
+        assert!(window.accept(10 + REPLAY_WINDOW_SIZE));
+        assert!(window.accept(13));
+        assert!(!window.accept(13));
+
+        // Those before the window are presumed to be replays
+        assert!(!window.accept(9));
+        assert!(!window.accept(10));
+
+        // Advancing past the window forgets everything in it
+        assert!(window.accept(10 + 4 * REPLAY_WINDOW_SIZE));
+        assert!(window.accept(10 + 3 * REPLAY_WINDOW_SIZE + 1));
+        assert!(!window.accept(10 + 3 * REPLAY_WINDOW_SIZE));
+    }
+
+    #[test]
+    fn open_replayed() {
+        let config = EndpointConfig {
+            session_keys: Some(keys()),
+            .. Default::default()
+        };
+
+        let client = Cipher::new(&keys(), Role::Client);
+        let server = Sealed::new((), &config, Role::Server, false);
+
+        let address = "127.0.0.1:8888".parse().unwrap();
+
+        let data_frame = [10, 1, 2, 3];
+        let sealed = client.seal(&data_frame);
+
+        assert_eq!(server.open(&mut sealed.clone().into_vec(), address), Some(&data_frame[..]));
+        assert_eq!(server.open(&mut sealed.clone().into_vec(), address), None);
+
+        // Handshake frames are handled by the handshake itself
+        let syn_frame = [0, 1, 2, 3];
+        let sealed_syn = client.seal(&syn_frame);
+
+        assert_eq!(server.open(&mut sealed_syn.clone().into_vec(), address), Some(&syn_frame[..]));
+        assert_eq!(server.open(&mut sealed_syn.clone().into_vec(), address), Some(&syn_frame[..]));
+
+        // The window is discarded along with the peer
+        server.forget_peer(&address);
+        assert_eq!(server.open(&mut sealed.into_vec(), address), Some(&data_frame[..]));
+    }
+
+    #[test]
+    fn open_replayed_peers() {
+        let config = EndpointConfig {
+            session_keys: Some(keys()),
+            .. Default::default()
+        };
+
+        let client = Cipher::new(&keys(), Role::Client);
+        let server = Sealed::new((), &config, Role::Server, false);
+
+        let address = "127.0.0.1:8888".parse().unwrap();
+        let other_address = "127.0.0.2:8888".parse().unwrap();
+        let path_address = "127.0.0.3:8888".parse().unwrap();
+        let new_address = "127.0.0.4:8888".parse().unwrap();
+
+        let first = client.seal(&[10, 1]);
+        let second = client.seal(&[10, 2]);
+
+        assert_eq!(server.open(&mut first.clone().into_vec(), address), Some(&[10, 1][..]));
+
+        // Each peer has its own windows
+        assert_eq!(server.open(&mut first.clone().into_vec(), other_address), Some(&[10, 1][..]));
+
+        // Additional paths share the windows of their peer
+        server.add_peer_path(address, path_address);
+        assert_eq!(server.open(&mut first.clone().into_vec(), path_address), None);
+
+        // Windows follow a peer which has moved, and are not disturbed by forgetting its paths
+        server.move_peer(address, new_address);
+        server.forget_peer(&path_address);
+        assert_eq!(server.open(&mut first.clone().into_vec(), new_address), None);
+        assert_eq!(server.open(&mut second.clone().into_vec(), new_address), Some(&[10, 2][..]));
+        assert_eq!(server.open(&mut second.clone().into_vec(), new_address), None);
+        assert_eq!(server.open(&mut second.clone().into_vec(), address), Some(&[10, 2][..]));
+    }
+}
@@ -1,30 +1,75 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::hash::Hasher;
 use std::net;
 use std::rc::Rc;
+use std::sync::Arc;
 use std::time;
 
+use crate::ArrivalCadence;
+use crate::Backpressure;
+use crate::CHANNEL_COUNT;
+use crate::MAX_CHANNEL_COUNT;
+use crate::Clock;
+use crate::SystemClock;
+use crate::CloseReason;
+use crate::ConnectionState;
+use crate::ConnectValidator;
 use crate::EndpointConfig;
+use crate::FrameAction;
+use crate::FrameHook;
+use crate::HeartbeatProvider;
+use crate::TransformSelector;
+use crate::Variant;
+use crate::VariantSelector;
 use crate::frame::serial::Serialize;
 use crate::frame;
 use crate::half_connection;
+use crate::logger;
+use crate::LogLevel;
+use crate::Logger;
 use crate::MAX_FRAME_SIZE;
-use crate::MAX_FRAME_WINDOW_SIZE;
-use crate::MAX_PACKET_WINDOW_SIZE;
+use crate::MAX_TRANSFORM_OVERHEAD;
+use crate::MAX_PATHS;
+use crate::Pacer;
 use crate::PROTOCOL_VERSION;
-use crate::udp_frame_sink::UdpFrameSink;
+use crate::SendMode;
+use crate::SessionSummary;
+use crate::TraceOutcome;
+#[cfg(feature = "encryption")]
+use crate::key_exchange;
+use crate::seal;
+use crate::seal::Sealed;
+#[cfg(feature = "encryption")]
+use crate::StaticKey;
+use crate::transport;
+use crate::transport::TransportFrameSink;
+#[cfg(feature = "parallel")]
+use crate::transport::CollectFrameSink;
+use crate::Transport;
+use crate::WindowUsage;
 
 mod event_queue;
 mod remote_client;
+mod timer_wheel;
 
-static HANDSHAKE_RESEND_INTERVAL_MS: u64 = 2000;
-static HANDSHAKE_RESEND_COUNT: u8 = 10;
+static HANDSHAKE_RESEND_INTERVAL_MS: u64 = 1000;
+static HANDSHAKE_MAX_RESEND_INTERVAL_MS: u64 = 4000;
+// Minimum interval between SYN+ACKs resent in response to duplicate SYNs
+static HANDSHAKE_COALESCE_INTERVAL_MS: u64 = 100;
+// Time for which a handshake cookie may be echoed by a client
+static HANDSHAKE_COOKIE_LIFETIME_MS: u64 = 10000;
 
 static DISCONNECT_RESEND_INTERVAL_MS: u64 = 2000;
 static DISCONNECT_RESEND_COUNT: u8 = 10;
 
 static CLOSED_TIMEOUT_MS: u64 = 20000;
 
+// Minimum interval between path challenges sent to the same unknown address
+static PATH_CHALLENGE_RESEND_INTERVAL_MS: u64 = 250;
+
 pub use remote_client::RemoteClient;
 
 /// Stores configuration parameters for a [`Server`](Server) object.
@@ -34,10 +79,122 @@ pub struct Config {
     pub max_total_connections: usize,
     /// The maximum number of active connections.
     pub max_active_connections: usize,
-    /// Whether to emit events in response to client handshake errors.
+    /// The maximum number of incomplete (half-open) connections. If a connection request is
+    /// received while this many connections are incomplete, the oldest incomplete connection is
+    /// forgotten to make room for the new one (see [`Server::half_open_eviction_count()`]).
+    pub max_half_open_connections: usize,
+    /// Whether to emit [`Event::HandshakeError`] events in response to client handshake errors.
     pub enable_handshake_errors: bool,
+    /// Limits the rate at which connection requests are accepted from any single IP address, if set
+    /// (see [`HandshakeThrottle`]). Requests in excess of this rate are refused without reply. IPv6
+    /// addresses are throttled by their /64 prefix, since a single host may use any address within
+    /// its prefix.
+    ///
+    /// No more than `max_half_open_connections` addresses are tracked at once. If a request is
+    /// received from a new address while this many addresses are being throttled, it is refused.
+    pub handshake_throttle: Option<HandshakeThrottle>,
+    /// Whether clients must prove that they can receive frames at their address before the server
+    /// allocates any state for their connection.
+    ///
+    /// If enabled, a connection request is first answered with a handshake cookie, which is
+    /// authenticated using a key private to the server. Only requests which echo a valid cookie
+    /// from the same address within a few seconds are considered further, so that requests from
+    /// spoofed addresses consume neither connection slots nor throttle entries. This requires one
+    /// additional round trip per connection, and clients which predate handshake cookies will be
+    /// unable to connect.
+    pub enable_handshake_cookies: bool,
+    /// The time after which an incomplete (half-open) connection is forgotten, in milliseconds.
+    /// Until then, handshake replies are resent with exponential backoff.
+    pub handshake_timeout_ms: u64,
+    /// Whether clients may continue their connections from a new address, e.g. after switching
+    /// networks or having their NAT mapping change (see [`Event::Migrate`]).
+    ///
+    /// If enabled, a frame from an unknown address which would belong to an established connection
+    /// is answered with a path challenge, in place of any reply prescribed by
+    /// `unknown_address_policy`. A client which answers the challenge from that address, and which
+    /// proves that it owns an active connection, is moved to the new address. Path challenges are
+    /// outstanding for at most `handshake_timeout_ms`, and no more than
+    /// `max_half_open_connections` may be outstanding at once.
+    ///
+    /// If `static_key` is set, a client proves that it owns a connection by sealing its answer with
+    /// the keys agreed upon during its handshake, which are moved along with the client. Otherwise,
+    /// it does so by echoing the nonces exchanged during its handshake.
+    pub enable_migration: bool,
+    /// Whether to assign each client a connection ID during the handshake, if the client supports
+    /// them. Every frame a client sends after its connection request is then tagged with its ID,
+    /// by which the server resolves the frame to its client, and a tagged frame is only accepted
+    /// if it was received over one of that client's paths.
+    ///
+    /// A client which has been assigned an ID is identified by it (see [`ClientId`]), so several
+    /// such clients may connect from a single address, e.g. from behind the same NAT mapping.
+    /// Otherwise, only one connection may be established from each address, and none may be
+    /// established from an address while another's handshake is in progress there. If
+    /// `static_key` is set, only one client may connect from each address regardless.
+    ///
+    /// If `enable_migration` is also set, frames from an unknown address are only answered with a
+    /// path challenge if they carry the ID of an existing client. Tags add four bytes to each
+    /// frame sent by the client.
+    pub enable_connection_ids: bool,
+    /// Whether clients may send over several paths at once (see
+    /// [`client::Client::add_path()`](crate::client::Client::add_path)).
+    ///
+    /// If enabled, a frame from an unknown address which carries the connection ID of an existing
+    /// client is answered with a path challenge, as for `enable_migration`. A client which answers
+    /// the challenge keeps its address, and the new address becomes an additional path of that
+    /// client, up to [`MAX_PATHS`](crate::MAX_PATHS) in total (see [`RemoteClient::paths()`]).
+    /// Frames from any of a client's paths are handled as though they came from its address, and
+    /// frames to the client are sent via the path over which the server most recently received a
+    /// frame. A client which supports both is never migrated.
+    ///
+    /// Multiple paths require `enable_connection_ids`. As with `enable_migration`, a client proves
+    /// that it owns a new path using the keys agreed upon during its handshake, if `static_key` is
+    /// set.
+    pub enable_multipath: bool,
     /// Endpoint configuration to use for inbound client connections.
     pub endpoint_config: EndpointConfig,
+    /// Determines how frames from unknown addresses are handled (see [`UnknownAddressPolicy`]).
+    pub unknown_address_policy: UnknownAddressPolicy,
+    /// Receives warnings and diagnostic messages concerning individual clients, if set (see
+    /// [`Logger`]).
+    pub logger: Option<Box<dyn Logger>>,
+    /// Observes raw frames received from clients before they are processed, if set (see
+    /// [`FrameHook`]). Frames from unknown addresses are instead handled according to
+    /// `unknown_address_policy`.
+    pub frame_hook: Option<FrameHook>,
+    /// Supplies payloads for keepalive frames sent to the clients, if set (see
+    /// [`HeartbeatProvider`]).
+    pub heartbeat_provider: Option<HeartbeatProvider>,
+    /// Limits the data sent to each client by each flush, if set (see [`Pacer`]).
+    pub pacer: Option<Box<dyn Pacer>>,
+    /// Selects algorithm settings for each incoming connection, if set (see [`VariantSelector`]).
+    pub variant_selector: Option<VariantSelector>,
+    /// Supplies a transform for the payload of each datagram exchanged with each client, if set
+    /// (see [`TransformSelector`]). Clients must be configured with matching transforms via
+    /// [`client::Config::transform`](crate::client::Config::transform).
+    pub transform_selector: Option<TransformSelector>,
+    /// Decides whether to accept each incoming connection, if set (see [`ConnectValidator`]).
+    /// Connections which are refused are signaled by [`Event::ConnectionRefused`].
+    pub connect_validator: Option<ConnectValidator>,
+    /// The source of time for all connection timers (see [`Clock`]).
+    pub clock: Arc<dyn Clock>,
+    /// A long-term key pair used to agree upon session keys with each client during the
+    /// handshake, if set (see [`StaticKey`]). Clients must request a key exchange (see
+    /// [`client::Config::key_exchange`](crate::client::Config::key_exchange)), and those which do
+    /// not are refused with a configuration error. Once a client has acknowledged the server's
+    /// reply, all frames exchanged with it are sealed using keys known only to that client and
+    /// the server.
+    #[cfg(feature = "encryption")]
+    pub static_key: Option<StaticKey>,
+    /// Whether [`Server::flush()`] should seal the frames of all active clients in parallel, on
+    /// rayon's global thread pool. This reduces the time taken to flush a large broadcast to many
+    /// clients when frames are sealed (see
+    /// [`EndpointConfig::session_keys`](crate::EndpointConfig::session_keys) and `static_key`).
+    ///
+    /// Frames are first collected from every client, then sealed, and then sent to each client in
+    /// the order they were emitted. If the socket's send buffer fills, the remaining frames are
+    /// dropped as though lost in transit, rather than retained for the next flush.
+    #[cfg(feature = "parallel")]
+    pub parallel_flush: bool,
 }
 
 impl Config {
@@ -45,6 +202,10 @@ impl Config {
     pub fn is_valid(&self) -> bool {
         return self.max_total_connections > 0
             && self.max_active_connections > 0
+            && self.max_half_open_connections > 0
+            && self.handshake_throttle.as_ref().map_or(true, |throttle| throttle.is_valid())
+            && self.handshake_timeout_ms > 0
+            && (!self.enable_multipath || self.enable_connection_ids)
             && self.endpoint_config.is_valid();
     }
 }
@@ -54,12 +215,116 @@ impl Default for Config {
         Self {
             max_total_connections: 4096,
             max_active_connections: 32,
+            max_half_open_connections: 1024,
             enable_handshake_errors: false,
+            handshake_throttle: None,
+            enable_handshake_cookies: false,
+            handshake_timeout_ms: 20000,
+            enable_migration: false,
+            enable_connection_ids: false,
+            enable_multipath: false,
             endpoint_config: Default::default(),
+            unknown_address_policy: UnknownAddressPolicy::Count,
+            logger: None,
+            frame_hook: None,
+            heartbeat_provider: None,
+            pacer: None,
+            variant_selector: None,
+            transform_selector: None,
+            connect_validator: None,
+            clock: Arc::new(SystemClock::new()),
+            #[cfg(feature = "encryption")]
+            static_key: None,
+            #[cfg(feature = "parallel")]
+            parallel_flush: false,
         }
     }
 }
 
+/// Limits the rate of connection requests from a single IP address with a token bucket. Each
+/// address may make `burst` requests in quick succession, and regains the ability to make one more
+/// every `interval_ms` milliseconds. See [`Config::handshake_throttle`].
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct HandshakeThrottle {
+    /// The number of connection requests an address may make before it is throttled.
+    pub burst: u32,
+    /// The interval at which a throttled address may make one more connection request, in
+    /// milliseconds.
+    pub interval_ms: u64,
+}
+
+impl HandshakeThrottle {
+    /// Returns true if the given throttle is valid.
+    pub fn is_valid(&self) -> bool {
+        self.burst > 0 && self.interval_ms > 0
+    }
+}
+
+/// Determines how a server handles frames received from addresses which do not correspond to a
+/// known client, e.g. noise from port scanners, or a legitimate client whose NAT mapping has
+/// changed. Connection requests from new clients are always handled normally.
+pub enum UnknownAddressPolicy {
+    /// Frames are ignored, and are not counted.
+    Ignore,
+    /// Frames are ignored, but are counted (see [`Server::unknown_address_stats()`]).
+    Count,
+    /// Frames are counted, and if a frame would belong to an established connection, a disconnect
+    /// frame is sent in reply. This causes a client whose address has changed to disconnect
+    /// promptly rather than time out, unless [`Config::enable_migration`] is set, in which case a
+    /// path challenge is sent instead.
+    ///
+    /// *Note*: A reply is never larger than the frame which caused it.
+    Reset,
+    /// Frames are counted, and the given function is called with the source address and contents
+    /// of each frame.
+    Callback(UnknownAddressCallback),
+}
+
+/// A function called with the source address and contents of a frame received from an unknown
+/// address. See [`UnknownAddressPolicy::Callback`].
+pub type UnknownAddressCallback = Box<dyn FnMut(net::SocketAddr, &[u8])>;
+
+/// Counters describing frames received from unknown addresses. See [`UnknownAddressPolicy`].
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct UnknownAddressStats {
+    /// The number of frames received from unknown addresses.
+    pub frames: u64,
+    /// The number of frames received from unknown addresses which could not be parsed.
+    pub malformed_frames: u64,
+    /// The number of disconnect frames sent in reply to frames from unknown addresses.
+    pub resets: u64,
+}
+
+/// Counters describing duplicate connection requests. See [`Server::handshake_stats()`].
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct HandshakeStats {
+    /// The number of connection requests received from clients whose handshake was already in
+    /// progress.
+    pub duplicate_requests: u64,
+    /// The number of handshake replies resent in response to duplicate connection requests.
+    pub coalesced_replies: u64,
+    /// The number of connection requests refused by the handshake throttle (see
+    /// [`Config::handshake_throttle`]).
+    pub throttled_requests: u64,
+    /// The number of connection requests answered with a handshake cookie, rather than
+    /// considered, because they did not echo a valid cookie (see
+    /// [`Config::enable_handshake_cookies`]).
+    pub cookie_replies: u64,
+}
+
+/// The reason a connection request was refused. See [`Event::ConnectionRefused`].
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum RefusalReason {
+    /// The maximum number of clients are already connected to the server.
+    ServerFull,
+    /// The client's IP address has made too many connection requests recently (see
+    /// [`Config::handshake_throttle`]).
+    Throttled,
+    /// The connection was declined by the server's connect validator (see
+    /// [`Config::connect_validator`]).
+    Rejected,
+}
+
 /// Represents a connection error.
 #[derive(Debug,PartialEq)]
 pub enum ErrorType {
@@ -76,29 +341,160 @@ pub enum ErrorType {
     ServerFull,
 }
 
+/// Identifies a client of a [`Server`] for as long as the server knows it, regardless of the
+/// client's address. Each [`Event`] concerning a client carries its ID, by which the client may be
+/// retrieved via [`Server::client()`].
+///
+/// If the client was assigned a connection ID (see [`Config::enable_connection_ids`]), its client
+/// ID is the same value. IDs are never zero, and an ID may be reused once its client has been
+/// forgotten.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub struct ClientId(pub (crate) u32);
+
 /// Used to signal connection events and deliver received packets.
 #[derive(Debug)]
 pub enum Event {
     /// Indicates a successful connection from a client.
-    Connect(net::SocketAddr),
+    Connect(ClientId),
+    /// Indicates that a client has moved from the first address to the second, and that its
+    /// connection continues uninterrupted. Only generated if [`Config::enable_migration`] is set.
+    Migrate(ClientId, net::SocketAddr, net::SocketAddr),
     /// Indicates that a client has disconnected. A disconnection event is only produced if either
     /// party explicitly terminates an active connection.
-    Disconnect(net::SocketAddr),
+    Disconnect(ClientId),
     /// Signals a packet received from a client.
-    Receive(net::SocketAddr, Box<[u8]>),
+    Receive(ClientId, Box<[u8]>),
     /// Indicates that a connection has been terminated due to an unrecoverable error.
-    Error(net::SocketAddr, ErrorType),
+    Error(ClientId, ErrorType),
+    /// Indicates that a connection request from the given address could not be completed due to
+    /// an error. Only generated if [`Config::enable_handshake_errors`] is set.
+    HandshakeError(net::SocketAddr, ErrorType),
+    /// Indicates that a connection request was refused before a handshake began. A throttled
+    /// address is signaled once each time it becomes throttled, rather than for every request which
+    /// is refused (see [`HandshakeStats::throttled_requests`]).
+    ConnectionRefused(net::SocketAddr, RefusalReason),
+    /// Indicates that the estimated arrival cadence of data sent by a client has changed
+    /// significantly. See [`RemoteClient::link_stats()`].
+    CadenceChange(ClientId, ArrivalCadence),
+    /// Indicates that sending to a client has paused because the frame transfer window is full,
+    /// i.e. more frames were sent than the client could acknowledge within one round trip. This is
+    /// distinct from a reduced send rate due to congestion. See [`WindowUsage`].
+    WindowLimited(ClientId, WindowUsage),
+    /// Indicates that the state of a client's connection has changed. Only generated if
+    /// [`EndpointConfig::state_events`] is set. Changes are only signaled once a connection has
+    /// been established, beginning with [`Connected`](ConnectionState::Connected). See
+    /// [`RemoteClient::connection_state()`].
+    StateChange(ClientId, ConnectionState),
+    /// Indicates that the quality score of a client's connection has changed significantly. Only
+    /// generated if [`EndpointConfig::quality_events`] is set. See
+    /// [`LinkStats::quality`](crate::LinkStats::quality).
+    QualityChange(ClientId, u8),
+    /// Indicates that the send queue of the given channel has crossed one of the channel's
+    /// watermarks. Only generated for channels with watermarks (see
+    /// [`RemoteClient::set_watermarks()`]).
+    Backpressure(ClientId, usize, Backpressure),
+    /// Signals a custom frame received from a client, along with its type ID. Only generated for
+    /// types registered via [`EndpointConfig::custom_frame_types`].
+    CustomFrame(ClientId, usize, Box<[u8]>),
+    /// Signals a heartbeat payload carried by a keepalive frame from a client. Only generated if
+    /// [`EndpointConfig::heartbeat_events`] is set.
+    Heartbeat(ClientId, Box<[u8]>),
+    /// Describes the entire lifetime of an established connection which has ended. Generated
+    /// immediately before the corresponding [`Disconnect`](Event::Disconnect) or
+    /// [`Error`](Event::Error) event, and only if [`EndpointConfig::session_summaries`] is set.
+    Summary(ClientId, Box<SessionSummary>),
+    /// Reports the outcome of a packet sent via
+    /// [`RemoteClient::send_traced()`](remote_client::RemoteClient::send_traced), along with its
+    /// trace ID.
+    Trace(ClientId, u64, TraceOutcome),
+    /// Confirms that a packet sent via
+    /// [`RemoteClient::send_confirmed()`](remote_client::RemoteClient::send_confirmed) has been
+    /// received by the client, along with its channel ID and tag.
+    Delivered(ClientId, usize, u64),
+    /// Indicates that a soft reset of a client's connection has completed, whether requested via
+    /// [`RemoteClient::reset()`](remote_client::RemoteClient::reset) or by the client, along with
+    /// the number of packets which the server dropped from its send queue and transfer window as a
+    /// result.
+    Reset(ClientId, u64),
+}
+
+/// Determines whether (and how) a broadcast packet is sent to a particular client. See
+/// [`Server::broadcast_filtered()`].
+#[derive(Debug)]
+pub enum BroadcastFilter {
+    /// The broadcast packet will be sent to this client.
+    Include,
+    /// The broadcast packet will not be sent to this client.
+    Exclude,
+    /// The given packet will be sent to this client in place of the broadcast packet.
+    Transform(Box<[u8]>),
+}
+
+// The handshake throttle's token bucket for a single IP address
+struct ThrottleEntry {
+    tokens: u32,
+    refill_time_ms: u64,
+    // Whether a refusal has been signaled since the address was last given a token
+    refused: bool,
+}
+
+impl ThrottleEntry {
+    fn refill(&mut self, throttle: &HandshakeThrottle, now_ms: u64) {
+        if self.tokens >= throttle.burst {
+            self.refill_time_ms = now_ms;
+            return;
+        }
+
+        let gained = (now_ms.saturating_sub(self.refill_time_ms) / throttle.interval_ms).min(throttle.burst as u64) as u32;
+
+        if gained > 0 {
+            self.tokens = (self.tokens + gained).min(throttle.burst);
+            self.refill_time_ms += gained as u64 * throttle.interval_ms;
+            self.refused = false;
+        }
+    }
+}
+
+// Returns the address by which a client's connection requests are throttled, which for IPv6 is
+// the /64 prefix of the client's address
+fn throttle_address(ip: net::IpAddr) -> net::IpAddr {
+    match ip {
+        net::IpAddr::V6(v6) if v6.to_ipv4_mapped().is_none() => {
+            let segments = v6.segments();
+            net::IpAddr::V6(net::Ipv6Addr::new(segments[0], segments[1], segments[2], segments[3], 0, 0, 0, 0))
+        }
+        _ => ip,
+    }
+}
+
+// The clients which send from an address
+#[derive(Default)]
+struct AddressEntry {
+    // The client to which frames from the address are resolved if they are not tagged with a
+    // connection ID: one which was not assigned an ID, one whose handshake is in progress, or any
+    // client if addresses are not shared
+    owner: Option<ClientId>,
+    // The number of clients which send from the address, whether as their own address or as an
+    // additional path
+    client_count: usize,
+}
+
+// A path challenge sent to an unknown address, awaiting a response
+struct PathChallenge {
+    challenge: u32,
+    send_time_ms: u64,
+    timeout_time_ms: u64,
 }
 
 struct EventPacketSink<'a> {
-    address: net::SocketAddr,
+    client_id: ClientId,
     event_queue: &'a mut Vec<Event>,
 }
 
 impl<'a> EventPacketSink<'a> {
-    fn new(address: net::SocketAddr, event_queue: &'a mut Vec<Event>) -> Self {
+    fn new(client_id: ClientId, event_queue: &'a mut Vec<Event>) -> Self {
         Self {
-            address,
+            client_id,
             event_queue,
         }
     }
@@ -106,21 +502,55 @@ impl<'a> EventPacketSink<'a> {
 
 impl<'a> half_connection::PacketSink for EventPacketSink<'a> {
     fn send(&mut self, packet_data: Box<[u8]>) {
-        self.event_queue.push(Event::Receive(self.address, packet_data));
+        self.event_queue.push(Event::Receive(self.client_id, packet_data));
     }
 }
 
 /// Acts as a host for inbound `uflow` connections.
-pub struct Server {
-    socket: net::UdpSocket,
+///
+/// A server communicates over a non-blocking UDP socket by default, but may be created with any
+/// other [`Transport`] via [`Server::with_transport()`].
+///
+/// A server may use up to [`EndpointConfig::channel_count`](crate::EndpointConfig::channel_count)
+/// channels, and accepts clients which use no more channels than that. Each client may then use the
+/// number of channels it proposed while connecting (see
+/// [`RemoteClient::channel_count()`](RemoteClient::channel_count)).
+///
+/// As with a [`Client`](crate::client::Client), a server with a fixed channel count of `CHANNELS`
+/// may be created via [`Server::with_fixed_channels()`], in which case the per-channel state of
+/// each client is held in arrays of exactly that size. A `CHANNELS` of 0, the default, leaves the
+/// channel count to be configured at runtime.
+pub struct Server<T: Transport = net::UdpSocket, const CHANNELS: usize = 0> {
+    socket: Sealed<T>,
     config: Config,
 
-    clients: HashMap<net::SocketAddr, Rc<RefCell<remote_client::RemoteClient>>>,
-    active_clients: Vec<Rc<RefCell<remote_client::RemoteClient>>>,
+    // Indexed by client ID, which is also the connection ID by which a tagged frame is resolved
+    clients: HashMap<ClientId, Rc<RefCell<remote_client::RemoteClient<CHANNELS>>>>,
+    addresses: HashMap<net::SocketAddr, AddressEntry>,
+    active_clients: Vec<Rc<RefCell<remote_client::RemoteClient<CHANNELS>>>>,
+    flush_index: usize,
+    send_blocked: bool,
 
-    client_events: event_queue::EventQueue,
+    // Clients in order of connection request, some of which may no longer be pending
+    half_open_clients: VecDeque<Rc<RefCell<remote_client::RemoteClient<CHANNELS>>>>,
+    half_open_count: usize,
+    half_open_eviction_count: u64,
 
-    time_base: time::Instant,
+    path_challenges: HashMap<net::SocketAddr, PathChallenge>,
+
+    throttle_entries: HashMap<net::IpAddr, ThrottleEntry>,
+
+    // Keys the SipHash-2-4 MAC of each handshake cookie
+    cookie_key: [u8; 16],
+
+    client_events: event_queue::EventQueue<CHANNELS>,
+    // Clients which have been given something to do before their next scheduled step
+    wake_queue: Rc<RefCell<Vec<ClientId>>>,
+
+    time_base: time::Duration,
+
+    unknown_address_stats: UnknownAddressStats,
+    handshake_stats: HandshakeStats,
 
     events_out: Vec<Event>,
 }
@@ -139,19 +569,7 @@ impl Server {
         let socket = net::UdpSocket::bind(addr)?;
         socket.set_nonblocking(true)?;
 
-        Ok(Self {
-            socket,
-            config,
-
-            clients: HashMap::new(),
-            active_clients: Vec::new(),
-
-            client_events: event_queue::EventQueue::new(),
-
-            time_base: time::Instant::now(),
-
-            events_out: Vec::new(),
-        })
+        Ok(Self::with_transport(socket, config))
     }
 
     /// Equivalent to calling [`bind()`](Self::bind) with address
@@ -165,12 +583,117 @@ impl Server {
     pub fn bind_any_ipv6(config: Config) -> Result<Self, std::io::Error> {
         Self::bind((net::Ipv6Addr::UNSPECIFIED, 0), config)
     }
+}
+
+impl Server<transport::DualStack> {
+    /// Opens a non-blocking UDP socket bound to the given port of every local IPv4 and IPv6
+    /// address, and returns a corresponding [`Server`](Self) object which accepts connections from
+    /// both IPv4 and IPv6 clients (see [`DualStack`](transport::DualStack)). IPv4 clients are
+    /// identified by their plain IPv4 addresses.
+    ///
+    /// # Error Handling
+    ///
+    /// Any errors resulting from socket initialization are forwarded to the caller. This function
+    /// will panic if the provided server configuration is not valid.
+    pub fn bind_dual_stack(port: u16, config: Config) -> Result<Self, std::io::Error> {
+        assert!(config.is_valid(), "invalid server config");
+
+        Ok(Self::with_transport(transport::DualStack::bind(port)?, config))
+    }
+}
+
+impl<T: Transport> Server<T> {
+    /// Returns a [`Server`](Self) object which accepts connections over the provided transport,
+    /// which must be non-blocking (see [`Transport`]).
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if the provided server configuration is not valid.
+    pub fn with_transport(transport: T, config: Config) -> Self {
+        assert!(config.is_valid(), "invalid server config");
+
+        Self::new(transport, config)
+    }
+}
+
+impl<T: Transport, const CHANNELS: usize> Server<T, CHANNELS> {
+    /// Returns a [`Server`](Self) object which accepts connections over the provided transport, and
+    /// which uses exactly `CHANNELS` channels, in place of the [channel
+    /// count](crate::EndpointConfig::channel_count) of the provided configuration. Clients which
+    /// propose more channels are refused, and clients which propose fewer are accepted as usual.
+    /// Otherwise identical to [`with_transport()`](Server::with_transport).
+    ///
+    /// ```
+    /// # let transport = uflow::transport::MemoryNetwork::new().bind("10.0.0.1:1000".parse().unwrap()).unwrap();
+    /// let server = uflow::server::Server::<_, 4>::with_fixed_channels(transport, Default::default());
+    /// ```
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `CHANNELS` is zero or exceeds [`MAX_CHANNEL_COUNT`], or if the
+    /// provided server configuration is not valid.
+    pub fn with_fixed_channels(transport: T, mut config: Config) -> Self {
+        assert!(CHANNELS > 0 && CHANNELS <= MAX_CHANNEL_COUNT, "invalid channel count {}", CHANNELS);
+
+        config.endpoint_config.channel_count = CHANNELS;
+
+        assert!(config.is_valid(), "invalid server config");
+
+        Self::new(transport, config)
+    }
+
+    fn new(transport: T, config: Config) -> Self {
+        #[cfg(feature = "encryption")]
+        let socket = Sealed::new(transport, &config.endpoint_config, seal::Role::Server, config.static_key.is_some());
+        #[cfg(not(feature = "encryption"))]
+        let socket = Sealed::new(transport, &config.endpoint_config, seal::Role::Server, false);
+
+        let time_base = config.clock.now();
+
+        Self {
+            socket,
+            config,
+
+            clients: HashMap::new(),
+            addresses: HashMap::new(),
+            active_clients: Vec::new(),
+            flush_index: 0,
+            send_blocked: false,
+
+            half_open_clients: VecDeque::new(),
+            half_open_count: 0,
+            half_open_eviction_count: 0,
+
+            path_challenges: HashMap::new(),
+
+            throttle_entries: HashMap::new(),
+
+            cookie_key: rand::random(),
+
+            client_events: event_queue::EventQueue::new(),
+            wake_queue: Rc::new(RefCell::new(Vec::new())),
+
+            time_base,
+
+            unknown_address_stats: Default::default(),
+            handshake_stats: Default::default(),
+
+            events_out: Vec::new(),
+        }
+    }
 
-    /// Returns the local address of the internal UDP socket.
+    /// Returns the local address of the internal socket.
     pub fn address(&self) -> net::SocketAddr {
         self.socket.local_addr().unwrap()
     }
 
+    /// Returns the public key presented to clients during a key exchange, if a static key has been
+    /// configured (see [`Config::static_key`]).
+    #[cfg(feature = "encryption")]
+    pub fn public_key(&self) -> Option<[u8; 32]> {
+        self.config.static_key.as_ref().map(|static_key| static_key.public_key())
+    }
+
     /// Flushes outbound frames, then processes as many inbound frames as possible from the
     /// internal socket. Returns an iterator of [`Event`] objects to signal connection events and
     /// deliver received packets for each client.
@@ -198,182 +721,857 @@ impl Server {
         std::mem::take(&mut self.events_out).into_iter()
     }
 
-    /// Sends as many outbound frames as possible for each client.
+    /// Sends as many outbound frames as possible for each client. Pending acknowledgements are sent
+    /// to every client before any other frames, so that each client continues to receive timely
+    /// acknowledgements while the socket's send buffer is full (see
+    /// [`LinkStats::ack_delay_ms`](crate::LinkStats::ack_delay_ms)).
     pub fn flush(&mut self) {
         self.flush_active_clients();
     }
 
-    /// Returns a reference to the [`RemoteClient`] with the given address. Returns `None` if no
-    /// such client exists.
-    pub fn client(&self, client_addr: &net::SocketAddr) -> Option<&Rc<RefCell<remote_client::RemoteClient>>> {
-        self.clients.get(client_addr)
-    }
-
-    /// Immediately terminates the connection with the given address. No further data will be sent
-    /// or received, and a timeout error will be generated on the client.
-    pub fn drop(&mut self, client_addr: &net::SocketAddr) {
-        if let Some(client_rc) = self.clients.get(client_addr) {
-            // Forget client immediately
-            client_rc.borrow_mut().state = remote_client::State::Fin;
-            self.clients.remove(client_addr);
-        }
-    }
-
-    fn now_ms(&self) -> u64 {
-        let now = time::Instant::now();
-        (now - self.time_base).as_millis() as u64
+    /// Returns `true` if the most recent call to [`flush()`](Self::flush) or [`step()`](Self::step)
+    /// was interrupted because the socket's send buffer was full. Frames which could not be sent
+    /// are retained, and the next flush resumes with the client which was interrupted.
+    pub fn is_send_blocked(&self) -> bool {
+        self.send_blocked
     }
 
-    fn handle_handshake_syn(
-        &mut self,
-        client_addr: net::SocketAddr,
-        handshake: frame::HandshakeSynFrame,
-        now_ms: u64,
-    ) {
-        if let Some(_) = self.clients.get(&client_addr) {
-            // Ignore subsequent SYN (either spam or a duplicate - SYN+ACK will be resent if no ACK
-            // is received)
-            return;
-        }
+    /// Sends as many outbound frames as possible for the client with the given ID, if it is active.
+    pub fn flush_client(&mut self, client_id: ClientId) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
+            let mut client = client_rc.borrow_mut();
 
-        if handshake.version != PROTOCOL_VERSION {
-            // Bad version
-            let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
-                nonce_ack: handshake.nonce,
-                error: frame::HandshakeErrorType::Version,
-            });
-            let _ = self.socket.send_to(&reply.write(), client_addr);
+            let client_addr = client.address;
+            let reply_address = client.reply_address;
 
-            if self.config.enable_handshake_errors {
-                self.events_out.push(Event::Error(client_addr, ErrorType::Version));
+            if let remote_client::State::Active(ref mut state) = client.state {
+                let data_sink = &mut TransportFrameSink::new(&self.socket, reply_address);
+                state.flush(data_sink, &mut self.config.heartbeat_provider, &mut self.config.pacer, client_addr);
             }
-
-            return;
         }
+    }
 
-        if self.clients.len() >= self.config.max_total_connections
-            && self.active_clients.len() >= self.config.max_active_connections
-        {
-            // No room in the inn
-            let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
-                nonce_ack: handshake.nonce,
-                error: frame::HandshakeErrorType::ServerFull,
-            });
-            let _ = self.socket.send_to(&reply.write(), client_addr);
+    /// Returns the number of milliseconds elapsed since this server was created. This is the time
+    /// base used by [`Server::due_connections()`].
+    pub fn time_ms(&self) -> u64 {
+        self.now_ms()
+    }
 
-            if self.config.enable_handshake_errors {
-                self.events_out.push(Event::Error(client_addr, ErrorType::ServerFull));
-            }
+    /// Returns an iterator over the IDs of active clients which require service at the given time
+    /// (see [`Server::time_ms()`]). A client requires service if it has outbound packets or
+    /// acknowledgements to send, if a sync or keepalive frame is due, if its connection has timed
+    /// out, or if it is disconnecting.
+    ///
+    /// Flushing a client which is not returned would not send any frames, so servers with many
+    /// mostly-idle clients may call [`Server::flush_client()`] for each due client in place of
+    /// [`Server::flush()`].
+    pub fn due_connections(&self, now_ms: u64) -> impl Iterator<Item = ClientId> + '_ {
+        // Only clients which have been woken, or whose scheduled step or timeout has expired, are
+        // visited. A client may have several expired events, some of which may be stale.
+        let woken_ids = self.wake_queue.borrow().clone();
 
-            return;
-        }
+        let expired_ids = self.client_events.peek_expired(now_ms).filter_map(move |event| {
+            let client = event.client.borrow();
 
-        if (handshake.max_receive_alloc as usize) < self.config.endpoint_config.max_packet_size {
-            // This connection may stall
-            let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
-                nonce_ack: handshake.nonce,
-                error: frame::HandshakeErrorType::Config,
-            });
-            let _ = self.socket.send_to(&reply.write(), client_addr);
+            match client.state {
+                remote_client::State::Active(ref state) => {
+                    let due = match event.kind {
+                        event_queue::EventType::Step => {
+                            state.step_deadline_ms.is_some_and(|deadline_ms| deadline_ms <= now_ms)
+                        }
+                        event_queue::EventType::ActiveTimeout => now_ms >= state.timeout_time_ms,
+                        _ => false,
+                    };
 
-            if self.config.enable_handshake_errors {
-                self.events_out.push(Event::Error(client_addr, ErrorType::Config));
+                    if due { Some(client.id) } else { None }
+                }
+                _ => None,
             }
+        });
 
-            return;
-        }
+        let woken_ids = woken_ids.into_iter().filter(move |client_id| {
+            self.clients.get(client_id).is_some_and(|client_rc| client_rc.borrow().is_active())
+        });
 
-        if (handshake.max_packet_size as usize) > self.config.endpoint_config.max_receive_alloc {
-            // This connection may stall
-            let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
-                nonce_ack: handshake.nonce,
-                error: frame::HandshakeErrorType::Config,
-            });
-            let _ = self.socket.send_to(&reply.write(), client_addr);
+        let mut visited = HashSet::new();
 
-            if self.config.enable_handshake_errors {
-                self.events_out.push(Event::Error(client_addr, ErrorType::Config));
-            }
+        woken_ids.chain(expired_ids).filter(move |&client_id| visited.insert(client_id))
+    }
 
-            return;
-        }
+    /// Returns the amount of time after which [`step()`](Self::step) and [`flush()`](Self::flush)
+    /// should next be called, assuming no frames are received in the meantime. Returns `None` if
+    /// no client requires service until a frame is received.
+    ///
+    /// This allows the server to be driven by an event loop which waits for its socket to become
+    /// readable (see [`AsRawFd`](std::os::unix::io::AsRawFd)) or for this timeout to elapse,
+    /// whichever comes first, rather than calling [`step()`](Self::step) at a fixed interval. If
+    /// [`is_send_blocked()`](Self::is_send_blocked) returns `true`, the event loop should also
+    /// wait for the socket to become writable.
+    pub fn next_timeout(&self) -> Option<time::Duration> {
+        let now_ms = self.now_ms();
 
-        // Handshake appears valid, send reply
+        // Each active client's next step is scheduled alongside other client events, and clients
+        // which have been given something to do are due immediately
+        let deadline_ms = if self.wake_queue.borrow().is_empty() {
+            self.client_events.next_expiry_ms()
+        } else {
+            Some(now_ms)
+        };
 
-        let local_nonce = rand::random::<u32>();
+        deadline_ms.map(|deadline_ms| time::Duration::from_millis(deadline_ms.saturating_sub(now_ms)))
+    }
 
-        let reply = frame::Frame::HandshakeSynAckFrame(frame::HandshakeSynAckFrame {
-            nonce_ack: handshake.nonce,
-            nonce: local_nonce,
-            max_receive_rate: self
-                .config
-                .endpoint_config
-                .max_receive_rate
-                .min(u32::MAX as usize) as u32,
-            max_packet_size: self
-                .config
-                .endpoint_config
-                .max_packet_size
-                .min(u32::MAX as usize) as u32,
-            max_receive_alloc: self
-                .config
-                .endpoint_config
-                .max_receive_alloc
-                .min(u32::MAX as usize) as u32,
-        });
+    /// Returns a reference to the [`RemoteClient`] with the given ID. Returns `None` if no such
+    /// client exists.
+    pub fn client(&self, client_id: ClientId) -> Option<&Rc<RefCell<remote_client::RemoteClient<CHANNELS>>>> {
+        self.clients.get(&client_id)
+    }
 
-        let reply_bytes = reply.write();
-        let _ = self.socket.send_to(&reply_bytes, client_addr);
+    /// Returns an iterator over all known clients, including those which are in the process of
+    /// connecting or disconnecting.
+    pub fn clients(&self) -> impl Iterator<Item = &Rc<RefCell<remote_client::RemoteClient<CHANNELS>>>> {
+        self.clients.values()
+    }
 
-        // Create a tentative client object
+    /// Returns counters describing frames received from unknown addresses. Frames are only counted
+    /// if the configured [`UnknownAddressPolicy`] is not `Ignore`.
+    pub fn unknown_address_stats(&self) -> UnknownAddressStats {
+        self.unknown_address_stats
+    }
 
-        let client_rc = Rc::new(RefCell::new(remote_client::RemoteClient {
-            address: client_addr,
-            state: remote_client::State::Pending(remote_client::PendingState {
-                local_nonce,
-                remote_nonce: handshake.nonce,
-                remote_max_receive_rate: handshake.max_receive_rate,
-                remote_max_receive_alloc: handshake.max_receive_alloc,
-                reply_bytes,
-            }),
-            max_packet_size: self.config.endpoint_config.max_packet_size,
-        }));
+    /// Returns counters describing duplicate connection requests. A connection request received
+    /// while a handshake with the same client is in progress does not restart the handshake;
+    /// instead, the server's reply is resent (at most once per 100ms).
+    pub fn handshake_stats(&self) -> HandshakeStats {
+        self.handshake_stats
+    }
 
-        self.client_events.push(event_queue::Event::new(
-            Rc::clone(&client_rc),
-            event_queue::EventType::ResendHandshakeSynAck,
-            now_ms + HANDSHAKE_RESEND_INTERVAL_MS,
-            HANDSHAKE_RESEND_COUNT,
-        ));
+    /// Returns the number of incomplete connections which have been forgotten to make room for new
+    /// connection requests (see
+    /// [`Config::max_half_open_connections`](Config#structfield.max_half_open_connections)).
+    pub fn half_open_eviction_count(&self) -> u64 {
+        self.half_open_eviction_count
+    }
 
-        self.clients.insert(client_addr, client_rc);
+    /// Enqueues a packet for delivery to every active client. The packet will be sent on the given
+    /// channel according to the specified mode.
+    ///
+    /// The packet data is shared by all recipients, and is not copied for each client. Clients which
+    /// use too few channels to receive packets on the given channel (see
+    /// [`RemoteClient::channel_count()`](RemoteClient::channel_count)) are skipped.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (i.e. if
+    /// `channel_id` is not less than the server's [channel
+    /// count](crate::EndpointConfig#structfield.channel_count)), or if `data.len()` exceeds the
+    /// [maximum packet size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn broadcast(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode) {
+        self.broadcast_filtered(data, channel_id, mode, |_| BroadcastFilter::Include);
     }
 
-    fn handle_handshake_ack(
-        &mut self,
-        client_addr: net::SocketAddr,
-        handshake: frame::HandshakeAckFrame,
-        now_ms: u64,
-    ) {
-        if let Some(client_rc) = self.clients.get(&client_addr) {
-            let mut client = client_rc.borrow_mut();
+    /// Enqueues a packet for delivery to a subset of active clients. The filter is called with the
+    /// ID of each active client at the time of this call, and determines whether that client
+    /// receives the packet, a client-specific replacement, or nothing at all. This allows
+    /// interest-management schemes (e.g. area-of-interest culling) to share a single broadcast
+    /// packet among all clients that receive it unmodified. Clients which use too few channels to
+    /// receive packets on the given channel (see
+    /// [`RemoteClient::channel_count()`](RemoteClient::channel_count)) are skipped.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (i.e. if
+    /// `channel_id` is not less than the server's [channel
+    /// count](crate::EndpointConfig#structfield.channel_count)), or if the size of `data` or of any
+    /// transformed packet exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size). The filter is evaluated for every
+    /// client before any packet is enqueued, so a panic leaves no client with a partial broadcast.
+    pub fn broadcast_filtered<F>(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, mut filter: F)
+        where F: FnMut(ClientId) -> BroadcastFilter
+    {
+        let max_packet_size = self.config.endpoint_config.max_packet_size;
+
+        assert!(data.len() <= max_packet_size,
+                "broadcast failed: packet of size {} exceeds configured maximum of {}",
+                data.len(),
+                max_packet_size);
+
+        assert!(channel_id < self.config.endpoint_config.channel_count,
+                "broadcast failed: channel ID {} is invalid",
+                channel_id);
+
+        // Evaluate the filter for every client before enqueuing anything, so that an oversized
+        // transformed packet doesn't leave the broadcast partially sent
+        let mut recipients = Vec::new();
 
-            match client.state {
-                remote_client::State::Pending(ref state) => {
-                    if handshake.nonce_ack == state.local_nonce {
-                        use crate::packet_id;
+        for client_rc in self.active_clients.iter() {
+            let client = client_rc.borrow();
 
-                        let config = half_connection::Config {
-                            tx_frame_window_size: MAX_FRAME_WINDOW_SIZE,
-                            rx_frame_window_size: MAX_FRAME_WINDOW_SIZE,
+            if channel_id >= client.channel_count {
+                continue;
+            }
 
-                            tx_frame_base_id: state.local_nonce,
-                            rx_frame_base_id: state.remote_nonce,
+            if let remote_client::State::Active(_) = client.state {
+                let decision = filter(client.id);
 
-                            tx_packet_window_size: MAX_PACKET_WINDOW_SIZE,
-                            rx_packet_window_size: MAX_PACKET_WINDOW_SIZE,
+                if let BroadcastFilter::Transform(ref data) = decision {
+                    assert!(data.len() <= max_packet_size,
+                            "broadcast failed: packet of size {} exceeds configured maximum of {}",
+                            data.len(),
+                            max_packet_size);
+                }
 
-                            tx_packet_base_id: state.local_nonce & packet_id::MASK,
+                recipients.push((Rc::clone(client_rc), decision));
+            }
+        }
+
+        let shared_data: Arc<[u8]> = data.into();
+
+        for (client_rc, decision) in recipients.into_iter() {
+            let mut client = client_rc.borrow_mut();
+
+            if let remote_client::State::Active(ref mut state) = client.state {
+                match decision {
+                    BroadcastFilter::Include => {
+                        state.half_connection.send_shared(Arc::clone(&shared_data), channel_id as u8, mode);
+                    }
+                    BroadcastFilter::Exclude => continue,
+                    BroadcastFilter::Transform(data) => {
+                        state.half_connection.send(data, channel_id as u8, mode);
+                    }
+                }
+
+                client.wake();
+            }
+        }
+    }
+
+    /// Enqueues a packet for delivery to every active client subscribed to the given group (see
+    /// [`Client::join_group()`](crate::client::Client::join_group)). The packet will be sent on
+    /// the given channel according to the specified mode.
+    ///
+    /// As with [`broadcast()`](Self::broadcast), the packet data is shared by all recipients, and
+    /// clients which use too few channels are skipped. If no active client is subscribed to the
+    /// group, the packet is discarded.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (i.e. if
+    /// `channel_id` is not less than the server's [channel
+    /// count](crate::EndpointConfig#structfield.channel_count)), or if `data.len()` exceeds the
+    /// [maximum packet size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_group(&mut self, group: &str, data: Box<[u8]>, channel_id: usize, mode: SendMode) {
+        let max_packet_size = self.config.endpoint_config.max_packet_size;
+
+        assert!(data.len() <= max_packet_size,
+                "send_group failed: packet of size {} exceeds configured maximum of {}",
+                data.len(),
+                max_packet_size);
+
+        assert!(channel_id < self.config.endpoint_config.channel_count,
+                "send_group failed: channel ID {} is invalid",
+                channel_id);
+
+        let shared_data: Arc<[u8]> = data.into();
+
+        for client_rc in self.active_clients.iter() {
+            let mut client = client_rc.borrow_mut();
+            let client = &mut *client;
+
+            if let remote_client::State::Active(ref mut state) = client.state {
+                if client.groups.iter().any(|name| name == group) && channel_id < client.channel_count {
+                    state.half_connection.send_shared(Arc::clone(&shared_data), channel_id as u8, mode);
+                    client.wake();
+                }
+            }
+        }
+    }
+
+    /// Returns the IDs of all active clients currently subscribed to the given group.
+    pub fn group_members<'a>(&'a self, group: &'a str) -> impl Iterator<Item = ClientId> + 'a {
+        self.active_clients.iter().filter_map(move |client_rc| {
+            let client = client_rc.borrow();
+
+            if client.is_active() && client.groups.iter().any(|name| name == group) {
+                Some(client.id)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Immediately terminates the connection with the given client. No further data will be sent
+    /// or received, and a timeout error will be generated on the client.
+    pub fn drop(&mut self, client_id: ClientId) {
+        if let Some(client_rc) = self.clients.get(&client_id).cloned() {
+            let mut client = client_rc.borrow_mut();
+
+            if let remote_client::State::Pending(_) = client.state {
+                self.half_open_count -= 1;
+            }
+
+            // Forget client immediately
+            client.close_reason = CloseReason::Dropped;
+            client.state = remote_client::State::Fin;
+            self.forget_client(&client);
+        }
+    }
+
+    // Forgets the given client, along with any state kept for those addresses from which no other
+    // client sends
+    fn forget_client(&mut self, client: &remote_client::RemoteClient<CHANNELS>) {
+        self.clients.remove(&client.id);
+
+        for &address in std::iter::once(&client.address).chain(client.paths.iter()) {
+            if self.leave_address(client.id, address) {
+                self.socket.forget_peer(&address);
+            }
+        }
+    }
+
+    // Notes that the given client sends from the given address. If the client owns the address,
+    // untagged frames from the address are resolved to it.
+    fn join_address(&mut self, client_id: ClientId, address: net::SocketAddr, owner: bool) {
+        let entry = self.addresses.entry(address).or_default();
+
+        entry.client_count += 1;
+
+        if owner {
+            entry.owner = Some(client_id);
+        }
+    }
+
+    // Notes that the given client no longer sends from the given address. Returns true if no
+    // client does, in which case the address is forgotten.
+    fn leave_address(&mut self, client_id: ClientId, address: net::SocketAddr) -> bool {
+        if let Some(entry) = self.addresses.get_mut(&address) {
+            if entry.owner == Some(client_id) {
+                entry.owner = None;
+            }
+
+            entry.client_count -= 1;
+
+            if entry.client_count == 0 {
+                self.addresses.remove(&address);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // Returns true if a client may join the clients which already send from the given address. A
+    // client which does not tag its frames may only do so if no other client owns the address.
+    fn address_available(&self, address: net::SocketAddr, tagged: bool) -> bool {
+        match self.addresses.get(&address) {
+            Some(entry) => self.addresses_shared() && (tagged || entry.owner.is_none()),
+            None => true,
+        }
+    }
+
+    // Keys are agreed upon with each address, so clients which exchange keys cannot share one, and
+    // are always resolved by address
+    #[cfg(feature = "encryption")]
+    fn addresses_shared(&self) -> bool {
+        self.config.static_key.is_none()
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    fn addresses_shared(&self) -> bool {
+        true
+    }
+
+    fn now_ms(&self) -> u64 {
+        let now = self.config.clock.now();
+        (now - self.time_base).as_millis() as u64
+    }
+
+    // Takes a token from the handshake throttle's bucket for the given address, if a throttle is
+    // configured. Returns false if the connection request should be refused.
+    fn admit_handshake(&mut self, client_addr: net::SocketAddr, now_ms: u64) -> bool {
+        let throttle = match self.config.handshake_throttle {
+            Some(throttle) => throttle,
+            None => return true,
+        };
+
+        let ip = throttle_address(client_addr.ip());
+
+        if !self.throttle_entries.contains_key(&ip) && self.throttle_entries.len() >= self.config.max_half_open_connections {
+            // Forget addresses whose buckets have refilled
+            self.throttle_entries.retain(|_, entry| {
+                entry.refill(&throttle, now_ms);
+                entry.tokens < throttle.burst
+            });
+
+            if self.throttle_entries.len() >= self.config.max_half_open_connections {
+                self.handshake_stats.throttled_requests += 1;
+                return false;
+            }
+        }
+
+        let entry = self.throttle_entries.entry(ip).or_insert(ThrottleEntry {
+            tokens: throttle.burst,
+            refill_time_ms: now_ms,
+            refused: false,
+        });
+
+        entry.refill(&throttle, now_ms);
+
+        if entry.tokens == 0 {
+            self.handshake_stats.throttled_requests += 1;
+
+            if !entry.refused {
+                entry.refused = true;
+
+                logger::log(&mut self.config.logger, LogLevel::Warn, client_addr,
+                            format_args!("connection requests throttled"));
+
+                self.events_out.push(Event::ConnectionRefused(client_addr, RefusalReason::Throttled));
+            }
+
+            return false;
+        }
+
+        entry.tokens -= 1;
+
+        true
+    }
+
+    fn handshake_cookie_mac(&self, client_addr: net::SocketAddr, nonce: u32, timestamp: u64) -> u64 {
+        let mut hasher = siphasher::sip::SipHasher24::new_with_key(&self.cookie_key);
+        match client_addr.ip() {
+            net::IpAddr::V4(ip) => hasher.write(&ip.octets()),
+            net::IpAddr::V6(ip) => hasher.write(&ip.octets()),
+        }
+        hasher.write(&client_addr.port().to_be_bytes());
+        hasher.write(&nonce.to_be_bytes());
+        hasher.write(&timestamp.to_be_bytes());
+        hasher.finish()
+    }
+
+    // Returns true if the request echoes a cookie recently issued to this address, otherwise
+    // replies with a fresh cookie
+    fn check_handshake_cookie(&mut self, client_addr: net::SocketAddr, handshake: &frame::HandshakeSynFrame, now_ms: u64) -> bool {
+        if let Some(cookie) = handshake.cookie {
+            if cookie.timestamp <= now_ms && now_ms - cookie.timestamp < HANDSHAKE_COOKIE_LIFETIME_MS &&
+                cookie.mac == self.handshake_cookie_mac(client_addr, handshake.nonce, cookie.timestamp) {
+                return true;
+            }
+        }
+
+        let cookie = frame::HandshakeCookie {
+            timestamp: now_ms,
+            mac: self.handshake_cookie_mac(client_addr, handshake.nonce, now_ms),
+        };
+
+        let reply = frame::Frame::HandshakeCookieFrame(frame::HandshakeCookieFrame {
+            nonce_ack: handshake.nonce,
+            cookie,
+        });
+        let _ = self.socket.send_to(&reply.write(), client_addr);
+
+        self.handshake_stats.cookie_replies += 1;
+
+        false
+    }
+
+    fn handle_handshake_syn(
+        &mut self,
+        client_addr: net::SocketAddr,
+        handshake: frame::HandshakeSynFrame,
+        now_ms: u64,
+    ) {
+        let owner = self.addresses.get(&client_addr).and_then(|entry| entry.owner);
+
+        if let Some(client_rc) = owner.and_then(|client_id| self.clients.get(&client_id)).cloned() {
+            // Coalesce a duplicate SYN into the handshake in progress by resending the SYN+ACK
+            // early, but no more often than the coalesce interval. Any other SYN is spam, or
+            // belongs to a different connection attempt, which will have to wait.
+            let mut client = client_rc.borrow_mut();
+
+            if let remote_client::State::Pending(ref mut state) = client.state {
+                self.handshake_stats.duplicate_requests += 1;
+
+                if handshake.nonce == state.remote_nonce &&
+                    now_ms >= state.reply_time_ms + HANDSHAKE_COALESCE_INTERVAL_MS {
+                    let _ = self.socket.send_to(&state.reply_bytes, client_addr);
+                    state.reply_time_ms = now_ms;
+
+                    self.handshake_stats.coalesced_replies += 1;
+                }
+            }
+
+            return;
+        }
+
+        if !self.address_available(client_addr, false) {
+            return;
+        }
+
+        if self.config.enable_handshake_cookies && !self.check_handshake_cookie(client_addr, &handshake, now_ms) {
+            return;
+        }
+
+        if !self.admit_handshake(client_addr, now_ms) {
+            return;
+        }
+
+        if handshake.version != PROTOCOL_VERSION {
+            // Bad version
+            let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
+                nonce_ack: handshake.nonce,
+                error: frame::HandshakeErrorType::Version,
+            });
+            let _ = self.socket.send_to(&reply.write(), client_addr);
+
+            if self.config.enable_handshake_errors {
+                self.events_out.push(Event::HandshakeError(client_addr, ErrorType::Version));
+            }
+
+            return;
+        }
+
+        if self.clients.len() >= self.config.max_total_connections
+            || self.active_clients.len() >= self.config.max_active_connections
+        {
+            // No room in the inn
+            let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
+                nonce_ack: handshake.nonce,
+                error: frame::HandshakeErrorType::ServerFull,
+            });
+            let _ = self.socket.send_to(&reply.write(), client_addr);
+
+            if self.config.enable_handshake_errors {
+                self.events_out.push(Event::HandshakeError(client_addr, ErrorType::ServerFull));
+            }
+
+            self.events_out.push(Event::ConnectionRefused(client_addr, RefusalReason::ServerFull));
+
+            return;
+        }
+
+        if (handshake.max_receive_alloc as usize) < self.config.endpoint_config.max_packet_size {
+            // This connection may stall
+            let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
+                nonce_ack: handshake.nonce,
+                error: frame::HandshakeErrorType::Config,
+            });
+            let _ = self.socket.send_to(&reply.write(), client_addr);
+
+            if self.config.enable_handshake_errors {
+                self.events_out.push(Event::HandshakeError(client_addr, ErrorType::Config));
+            }
+
+            return;
+        }
+
+        if (handshake.max_packet_size as usize) > self.config.endpoint_config.max_receive_alloc {
+            // This connection may stall
+            let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
+                nonce_ack: handshake.nonce,
+                error: frame::HandshakeErrorType::Config,
+            });
+            let _ = self.socket.send_to(&reply.write(), client_addr);
+
+            if self.config.enable_handshake_errors {
+                self.events_out.push(Event::HandshakeError(client_addr, ErrorType::Config));
+            }
+
+            return;
+        }
+
+        // Clients which do not propose a channel count use the default count
+        let channel_count = if handshake.ext_features & frame::serial::EXT_FEATURE_CHANNEL_COUNT != 0 {
+            handshake.channel_count as usize
+        } else {
+            CHANNEL_COUNT
+        };
+
+        if channel_count > self.config.endpoint_config.channel_count {
+            // The client may send on channels which we would not receive
+            let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
+                nonce_ack: handshake.nonce,
+                error: frame::HandshakeErrorType::Config,
+            });
+            let _ = self.socket.send_to(&reply.write(), client_addr);
+
+            if self.config.enable_handshake_errors {
+                self.events_out.push(Event::HandshakeError(client_addr, ErrorType::Config));
+            }
+
+            return;
+        }
+
+        if let Some(ref mut validator) = self.config.connect_validator {
+            if !validator(client_addr, &handshake.connect_token) {
+                let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
+                    nonce_ack: handshake.nonce,
+                    error: frame::HandshakeErrorType::Rejected,
+                });
+                let _ = self.socket.send_to(&reply.write(), client_addr);
+
+                self.events_out.push(Event::ConnectionRefused(client_addr, RefusalReason::Rejected));
+
+                return;
+            }
+        }
+
+        // Agree upon session keys, if the client has offered a key share we can answer
+        #[cfg(feature = "encryption")]
+        let (key_share, session_keys) = match self.config.static_key {
+            Some(ref static_key) => {
+                match handshake.key_share.as_ref().and_then(|key_share| key_exchange::respond(static_key, key_share)) {
+                    Some((key_share, session_keys)) => (Some(key_share), Some(session_keys)),
+                    None => {
+                        // Unencrypted connections are not permitted
+                        let reply = frame::Frame::HandshakeErrorFrame(frame::HandshakeErrorFrame {
+                            nonce_ack: handshake.nonce,
+                            error: frame::HandshakeErrorType::Config,
+                        });
+                        let _ = self.socket.send_to(&reply.write(), client_addr);
+
+                        if self.config.enable_handshake_errors {
+                            self.events_out.push(Event::HandshakeError(client_addr, ErrorType::Config));
+                        }
+
+                        return;
+                    }
+                }
+            }
+            None => (None, None),
+        };
+        #[cfg(not(feature = "encryption"))]
+        let key_share = None;
+
+        // Handshake appears valid, send reply
+
+        let local_nonce = rand::random::<u32>();
+
+        // Select the algorithm variant used by this connection
+        let variant = match self.config.variant_selector.as_mut().and_then(|select| select(client_addr)) {
+            Some(variant) if !variant.is_valid() => {
+                logger::log(&mut self.config.logger, LogLevel::Warn, client_addr,
+                            format_args!("invalid variant {} selected, using default settings", variant.tag));
+                None
+            }
+            variant => variant,
+        };
+
+        let settings = variant.clone().unwrap_or_else(|| Variant::new(0, &self.config.endpoint_config));
+        let variant_tag = variant.as_ref().map(|variant| variant.tag);
+
+        // Accept those optional features which are supported by both endpoints
+        let local_features = frame::serial::FEATURE_GROUPS | frame::serial::FEATURE_CONTROL_CHANNELS | frame::serial::FEATURE_DROP_RANGES | if self.config.enable_migration {
+            frame::serial::FEATURE_MIGRATION
+        } else {
+            0
+        } | if settings.fragment_acks {
+            frame::serial::FEATURE_FRAGMENT_ACKS
+        } else {
+            0
+        } | if settings.receiver_health {
+            frame::serial::FEATURE_RECEIVER_HEALTH
+        } else {
+            0
+        };
+        let features = handshake.features & local_features;
+
+        // Custom frame and heartbeat support is advertised in each direction, rather than negotiated
+        let features = features | if self.config.endpoint_config.custom_frame_types != 0 {
+            frame::serial::FEATURE_CUSTOM_FRAMES
+        } else {
+            0
+        } | if self.config.endpoint_config.heartbeat_events {
+            frame::serial::FEATURE_HEARTBEATS
+        } else {
+            0
+        };
+
+        // Assign a connection ID, if the client supports them
+        let client_id = self.allocate_client_id();
+
+        let connection_id = if self.config.enable_connection_ids &&
+            handshake.ext_features & frame::serial::EXT_FEATURE_CONNECTION_IDS != 0 {
+            Some(client_id.0)
+        } else {
+            None
+        };
+
+        let ext_features = if connection_id.is_some() {
+            frame::serial::EXT_FEATURE_CONNECTION_IDS
+        } else {
+            0
+        } | if self.config.endpoint_config.app_latency {
+            handshake.ext_features & frame::serial::EXT_FEATURE_APP_LATENCY
+        } else {
+            0
+        } | if self.config.enable_multipath && connection_id.is_some() {
+            handshake.ext_features & frame::serial::EXT_FEATURE_MULTIPATH
+        } else {
+            0
+        } | handshake.ext_features & frame::serial::EXT_FEATURE_CHANNEL_COUNT;
+
+        let reply = frame::Frame::HandshakeSynAckFrame(frame::HandshakeSynAckFrame {
+            nonce_ack: handshake.nonce,
+            nonce: local_nonce,
+            max_receive_rate: self
+                .config
+                .endpoint_config
+                .max_receive_rate
+                .min(u32::MAX as usize) as u32,
+            max_packet_size: self
+                .config
+                .endpoint_config
+                .max_packet_size
+                .min(u32::MAX as usize) as u32,
+            max_receive_alloc: self
+                .config
+                .endpoint_config
+                .max_receive_alloc
+                .min(u32::MAX as usize) as u32,
+            features,
+            // Only clients which advertise their own window expect ours in return
+            max_receive_window: if handshake.max_receive_window != 0 {
+                self.config.endpoint_config.max_receive_window
+            } else {
+                0
+            },
+            key_share,
+            ext_features,
+            connection_id: connection_id.unwrap_or(0),
+        });
+
+        let reply_bytes = reply.write();
+        let _ = self.socket.send_to(&reply_bytes, client_addr);
+
+        // The client's ACK, and all frames which follow, are sealed using the new keys
+        #[cfg(feature = "encryption")]
+        if let Some(session_keys) = session_keys {
+            self.socket.set_peer_keys(client_addr, &session_keys);
+        }
+
+        // Make room for the tentative client object, if necessary
+        if self.half_open_count >= self.config.max_half_open_connections {
+            self.evict_half_open_client();
+        }
+
+        // Create a tentative client object
+
+        let client_rc = Rc::new(RefCell::new(remote_client::RemoteClient {
+            id: client_id,
+            address: client_addr,
+            paths: Vec::new(),
+            reply_address: client_addr,
+            connection_id,
+            state: remote_client::State::Pending(remote_client::PendingState {
+                local_nonce,
+                remote_nonce: handshake.nonce,
+                remote_max_receive_rate: handshake.max_receive_rate,
+                remote_max_receive_alloc: handshake.max_receive_alloc,
+                remote_max_packet_size: handshake.max_packet_size,
+                remote_max_receive_window: handshake.max_receive_window,
+                features,
+                ext_features,
+                remote_custom_frames: handshake.features & frame::serial::FEATURE_CUSTOM_FRAMES != 0,
+                remote_heartbeats: handshake.features & frame::serial::FEATURE_HEARTBEATS != 0,
+                variant,
+                reply_bytes,
+                resend_interval_ms: HANDSHAKE_RESEND_INTERVAL_MS,
+                reply_time_ms: now_ms,
+                timeout_time_ms: now_ms + self.config.handshake_timeout_ms,
+            }),
+            channel_count,
+            max_packet_size: self.config.endpoint_config.max_packet_size,
+            max_send_delay_ms: self.config.endpoint_config.max_send_delay_ms,
+            decode_failure_count: 0,
+            close_reason: CloseReason::Disconnect,
+            signaled_state: ConnectionState::Connecting,
+            groups: Vec::new(),
+            group_version: None,
+            variant_tag,
+            session_summary: None,
+            next_send_id: 0,
+            woken: false,
+            wake_queue: Rc::clone(&self.wake_queue),
+        }));
+
+        self.client_events.push(
+            (now_ms + HANDSHAKE_RESEND_INTERVAL_MS).min(now_ms + self.config.handshake_timeout_ms),
+            event_queue::Event::new(
+                Rc::clone(&client_rc),
+                event_queue::EventType::ResendHandshakeSynAck,
+                0,
+            ),
+        );
+
+        self.half_open_clients.push_back(Rc::clone(&client_rc));
+        self.half_open_count += 1;
+
+        // Until its handshake is complete, the client owns its address
+        self.join_address(client_id, client_addr, true);
+
+        self.clients.insert(client_id, client_rc);
+    }
+
+    // Returns a random, nonzero ID which has not been assigned to any other client
+    fn allocate_client_id(&self) -> ClientId {
+        loop {
+            let client_id = ClientId(rand::random::<u32>());
+
+            if client_id.0 != 0 && !self.clients.contains_key(&client_id) {
+                return client_id;
+            }
+        }
+    }
+
+    // Forgets the oldest pending client
+    fn evict_half_open_client(&mut self) {
+        while let Some(client_rc) = self.half_open_clients.pop_front() {
+            let mut client = client_rc.borrow_mut();
+
+            if let remote_client::State::Pending(_) = client.state {
+                client.close_reason = CloseReason::Dropped;
+                client.state = remote_client::State::Fin;
+                self.forget_client(&client);
+
+                self.half_open_count -= 1;
+                self.half_open_eviction_count += 1;
+
+                return;
+            }
+        }
+    }
+
+    fn handle_handshake_ack(
+        &mut self,
+        client_id: ClientId,
+        handshake: frame::HandshakeAckFrame,
+        now_ms: u64,
+    ) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
+            let mut client = client_rc.borrow_mut();
+            let client_addr = client.address;
+
+            match client.state {
+                remote_client::State::Pending(ref state) => {
+                    if handshake.nonce_ack == state.local_nonce {
+                        use crate::packet_id;
+
+                        let settings = state.variant.clone().unwrap_or_else(|| Variant::new(0, &self.config.endpoint_config));
+
+                        let (tx_window_size, rx_window_size) = self.config.endpoint_config.transfer_windows(state.remote_max_receive_window);
+
+                        let config = half_connection::Config {
+                            tx_frame_window_size: tx_window_size,
+                            rx_frame_window_size: rx_window_size,
+
+                            tx_frame_base_id: state.local_nonce,
+                            rx_frame_base_id: state.remote_nonce,
+
+                            tx_packet_window_size: tx_window_size,
+                            rx_packet_window_size: rx_window_size,
+
+                            tx_packet_base_id: state.local_nonce & packet_id::MASK,
                             rx_packet_base_id: state.remote_nonce & packet_id::MASK,
 
                             tx_bandwidth_limit: (self.config.endpoint_config.max_send_rate as u32).min(state.remote_max_receive_rate),
@@ -381,25 +1579,101 @@ impl Server {
                             tx_alloc_limit: state.remote_max_receive_alloc as usize,
                             rx_alloc_limit: self.config.endpoint_config.max_receive_alloc as usize,
 
+                            rx_max_packet_size: state.remote_max_packet_size as usize,
+
                             keepalive_interval_ms: if self.config.endpoint_config.keepalive {
                                 Some(self.config.endpoint_config.keepalive_interval_ms)
                             } else {
                                 None
                             },
+
+                            #[cfg(feature = "stats")]
+                            stats_sample_interval_ms: self.config.endpoint_config.stats_sample_interval_ms,
+                            #[cfg(feature = "stats")]
+                            stats_history_len: self.config.endpoint_config.stats_history_len,
+
+                            reorder_tolerance_frames: settings.reorder_tolerance_frames,
+                            reorder_tolerance_ms: settings.reorder_tolerance_ms,
+
+                            early_retransmit: settings.early_retransmit,
+                            min_rto_ms: settings.min_rto_ms,
+
+                            max_frame_age_ms: settings.max_frame_age_ms,
+
+                            fragment_acks: state.features & frame::serial::FEATURE_FRAGMENT_ACKS != 0,
+
+                            receiver_health: state.features & frame::serial::FEATURE_RECEIVER_HEALTH != 0,
+
+                            app_latency: state.ext_features & frame::serial::EXT_FEATURE_APP_LATENCY != 0,
+
+                            control_channels: state.features & frame::serial::FEATURE_CONTROL_CHANNELS != 0,
+
+                            drop_ranges: state.features & frame::serial::FEATURE_DROP_RANGES != 0,
+
+                            adaptive_frame_size: settings.adaptive_frame_size,
+
+                            deliver_empty_packets: self.config.endpoint_config.deliver_empty_packets,
+                            receive_order: self.config.endpoint_config.receive_order,
+
+                            channel_count: client.channel_count,
+                            channel_weights: self.config.endpoint_config.channel_weights.clone(),
+                            probe_rate: self.config.endpoint_config.probe_rate,
+
+                            frame_overhead: self.socket.frame_overhead(client_addr),
+
+                            clock: Arc::clone(&self.config.clock),
                         };
 
-                        let half_connection = half_connection::HalfConnection::new(config);
+                        let mut half_connection = half_connection::HalfConnection::new(config);
+
+                        match self.config.transform_selector.as_mut().and_then(|select| select(client_addr)) {
+                            Some(transform) if transform.overhead() > MAX_TRANSFORM_OVERHEAD => {
+                                logger::log(&mut self.config.logger, LogLevel::Warn, client_addr,
+                                            format_args!("transform overhead {} exceeds maximum, using no transform", transform.overhead()));
+                            }
+                            Some(transform) => half_connection.set_transform(transform),
+                            None => (),
+                        }
+
+                        let timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+
+                        self.half_open_count -= 1;
 
                         client.state = remote_client::State::Active(remote_client::ActiveState {
                             half_connection,
-                            timeout_time_ms: now_ms + self.config.endpoint_config.active_timeout_ms,
+                            local_nonce: state.local_nonce,
+                            remote_nonce: state.remote_nonce,
+                            migration_enabled: state.features & frame::serial::FEATURE_MIGRATION != 0,
+                            multipath_enabled: state.ext_features & frame::serial::EXT_FEATURE_MULTIPATH != 0,
+                            time_base_ms: now_ms,
+                            timeout_time_ms,
+                            step_deadline_ms: None,
                             disconnect_signal: None,
+                            custom_frames_enabled: state.remote_custom_frames,
+                            heartbeats_enabled: state.remote_heartbeats,
                         });
 
+                        self.client_events.push(
+                            timeout_time_ms,
+                            event_queue::Event::new(
+                                Rc::clone(client_rc),
+                                event_queue::EventType::ActiveTimeout,
+                                0,
+                            ),
+                        );
+
                         self.active_clients.push(Rc::clone(&client_rc));
 
+                        // A client which tags its frames no longer needs its address to itself
+                        if client.connection_id.is_some() && self.addresses_shared() {
+                            if let Some(entry) = self.addresses.get_mut(&client_addr) {
+                                entry.owner = None;
+                            }
+                        }
+
                         // Signal connect
-                        self.events_out.push(Event::Connect(client_addr));
+                        self.events_out.push(Event::Connect(client_id));
+                        Self::signal_state_change(&mut client, self.config.endpoint_config.state_events, &mut self.events_out);
                     }
                 }
                 _ => (),
@@ -409,11 +1683,12 @@ impl Server {
 
     fn handle_disconnect(
         &mut self,
-        client_addr: net::SocketAddr,
+        client_id: ClientId,
         now_ms: u64,
     ) {
-        if let Some(client_rc) = self.clients.get(&client_addr) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
             let mut client = client_rc.borrow_mut();
+            let client_addr = client.address;
 
             match client.state {
                 remote_client::State::Pending(_) => {
@@ -425,20 +1700,26 @@ impl Server {
                     let _ = self.socket.send_to(&reply.write(), client_addr);
 
                     // Signal remaining received packets prior to connection destruction
-                    state.half_connection.receive(&mut EventPacketSink::new(client_addr, &mut self.events_out));
+                    state.half_connection.receive(&mut EventPacketSink::new(client_id, &mut self.events_out));
 
                     // Signal disconnect
-                    self.events_out.push(Event::Disconnect(client_addr));
+                    Self::record_summary(&mut client, CloseReason::Disconnect, self.config.endpoint_config.session_summaries);
+                    Self::signal_summary(&mut client, &mut self.events_out);
+                    self.events_out.push(Event::Disconnect(client_id));
 
                     // Close now, but forget after a timeout
+                    client.close_reason = CloseReason::Disconnect;
                     client.state = remote_client::State::Closed;
+                    Self::signal_state_change(&mut client, self.config.endpoint_config.state_events, &mut self.events_out);
 
-                    self.client_events.push(event_queue::Event::new(
-                        Rc::clone(&client_rc),
-                        event_queue::EventType::ClosedTimeout,
+                    self.client_events.push(
                         now_ms + CLOSED_TIMEOUT_MS,
-                        0,
-                    ));
+                        event_queue::Event::new(
+                            Rc::clone(&client_rc),
+                            event_queue::EventType::ClosedTimeout,
+                            0,
+                        ),
+                    );
                 },
                 remote_client::State::Closing => {
                     // This may as well be an acknowledgement
@@ -446,17 +1727,22 @@ impl Server {
                     let _ = self.socket.send_to(&reply.write(), client_addr);
 
                     // Signal disconnect
-                    self.events_out.push(Event::Disconnect(client_addr));
+                    Self::signal_summary(&mut client, &mut self.events_out);
+                    self.events_out.push(Event::Disconnect(client_id));
 
                     // Close now, but forget after a timeout
+                    client.close_reason = CloseReason::Disconnect;
                     client.state = remote_client::State::Closed;
+                    Self::signal_state_change(&mut client, self.config.endpoint_config.state_events, &mut self.events_out);
 
-                    self.client_events.push(event_queue::Event::new(
-                        Rc::clone(&client_rc),
-                        event_queue::EventType::ClosedTimeout,
+                    self.client_events.push(
                         now_ms + CLOSED_TIMEOUT_MS,
-                        0,
-                    ));
+                        event_queue::Event::new(
+                            Rc::clone(&client_rc),
+                            event_queue::EventType::ClosedTimeout,
+                            0,
+                        ),
+                    );
                 },
                 remote_client::State::Closed => {
                     // Acknowledge subsequent disconnection requests
@@ -470,80 +1756,195 @@ impl Server {
 
     fn handle_disconnect_ack(
         &mut self,
-        client_addr: net::SocketAddr
+        client_id: ClientId
     ) {
-        if let Some(client_rc) = self.clients.get(&client_addr) {
+        if let Some(client_rc) = self.clients.get(&client_id).cloned() {
             let mut client = client_rc.borrow_mut();
 
             match client.state {
                 remote_client::State::Closing => {
                     // Forget client and signal disconnect
-                    self.events_out.push(Event::Disconnect(client_addr));
+                    Self::signal_summary(&mut client, &mut self.events_out);
+                    self.events_out.push(Event::Disconnect(client_id));
 
+                    client.close_reason = CloseReason::Disconnect;
                     client.state = remote_client::State::Fin;
-                    std::mem::drop(client);
-                    self.clients.remove(&client_addr);
+                    Self::signal_state_change(&mut client, self.config.endpoint_config.state_events, &mut self.events_out);
+                    self.forget_client(&client);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn handle_data(
+        &mut self,
+        client_id: ClientId,
+        frame: frame::DataFrame,
+        now_ms: u64
+    ) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
+            let mut client = client_rc.borrow_mut();
+            let reply_address = client.reply_address;
+
+            match client.state {
+                remote_client::State::Active(ref mut state) => {
+                    state
+                        .half_connection
+                        .handle_data_frame(frame);
+
+                    state
+                        .half_connection
+                        .flush_urgent_acks(&mut TransportFrameSink::new(&self.socket, reply_address));
+
+                    state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
                 }
                 _ => (),
             }
         }
     }
 
-    fn handle_data(
+    fn handle_acks(
+        &mut self,
+        client_id: ClientId,
+        frames: Vec<frame::AckFrame>,
+        now_ms: u64
+    ) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
+            let mut client = client_rc.borrow_mut();
+
+            match client.state {
+                remote_client::State::Active(ref mut state) => {
+                    state
+                        .half_connection
+                        .handle_ack_frames(frames);
+
+                    state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn handle_fragment_ack(
+        &mut self,
+        client_id: ClientId,
+        frame: frame::FragmentAckFrame,
+        now_ms: u64
+    ) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
+            let mut client = client_rc.borrow_mut();
+
+            if let remote_client::State::Active(ref mut state) = client.state {
+                state
+                    .half_connection
+                    .handle_fragment_ack_frame(frame);
+
+                state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+            }
+        }
+    }
+
+    fn handle_group(
+        &mut self,
+        client_id: ClientId,
+        frame: frame::GroupFrame,
+        now_ms: u64
+    ) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
+            let mut client = client_rc.borrow_mut();
+            let client = &mut *client;
+
+            if let remote_client::State::Active(ref mut state) = client.state {
+                // Subscriptions are replaced in full, unless they predate those already applied
+                let newer = match client.group_version {
+                    Some(version) => (frame.version.wrapping_sub(version) as i32) > 0,
+                    None => true,
+                };
+
+                if newer {
+                    client.groups = frame.groups;
+                    client.group_version = Some(frame.version);
+                }
+
+                let reply = frame::Frame::GroupAckFrame(frame::GroupAckFrame {
+                    version: frame.version,
+                });
+                let _ = self.socket.send_to(&reply.write(), client.address);
+
+                state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+            }
+        }
+    }
+
+    fn handle_custom(
+        &mut self,
+        client_id: ClientId,
+        frame: frame::CustomFrame,
+        now_ms: u64
+    ) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
+            let mut client = client_rc.borrow_mut();
+
+            if let remote_client::State::Active(ref mut state) = client.state {
+                if self.config.endpoint_config.custom_frame_types & (1 << frame.type_id) != 0 {
+                    self.events_out.push(Event::CustomFrame(client_id, frame.type_id as usize, frame.data));
+                }
+
+                state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+            }
+        }
+    }
+
+    fn handle_reset(
         &mut self,
-        client_addr: net::SocketAddr,
-        frame: frame::DataFrame,
+        client_id: ClientId,
+        frame: frame::ResetFrame,
         now_ms: u64
     ) {
-        if let Some(client_rc) = self.clients.get(&client_addr) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
             let mut client = client_rc.borrow_mut();
 
-            match client.state {
-                remote_client::State::Active(ref mut state) => {
-                    state
-                        .half_connection
-                        .handle_data_frame(frame);
-
-                    state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
-                }
-                _ => (),
+            if let remote_client::State::Active(ref mut state) = client.state {
+                state.half_connection.handle_reset_frame(frame);
+                state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
             }
         }
     }
 
-    fn handle_ack(
+    fn handle_reset_ack(
         &mut self,
-        client_addr: net::SocketAddr,
-        frame: frame::AckFrame,
+        client_id: ClientId,
+        frame: frame::ResetAckFrame,
         now_ms: u64
     ) {
-        if let Some(client_rc) = self.clients.get(&client_addr) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
             let mut client = client_rc.borrow_mut();
 
-            match client.state {
-                remote_client::State::Active(ref mut state) => {
-                    state
-                        .half_connection
-                        .handle_ack_frame(frame);
-
-                    state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
-                }
-                _ => (),
+            if let remote_client::State::Active(ref mut state) = client.state {
+                state.half_connection.handle_reset_ack_frame(frame);
+                state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
             }
         }
     }
 
     fn handle_sync(
         &mut self,
-        client_addr: net::SocketAddr,
-        frame: frame::SyncFrame,
+        client_id: ClientId,
+        mut frame: frame::SyncFrame,
         now_ms: u64
     ) {
-        if let Some(client_rc) = self.clients.get(&client_addr) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
             let mut client = client_rc.borrow_mut();
 
             match client.state {
                 remote_client::State::Active(ref mut state) => {
+                    if let Some(data) = frame.heartbeat.take() {
+                        if self.config.endpoint_config.heartbeat_events {
+                            self.events_out.push(Event::Heartbeat(client_id, data));
+                        }
+                    }
+
                     state
                         .half_connection
                         .handle_sync_frame(frame);
@@ -558,33 +1959,204 @@ impl Server {
     fn handle_frame(
         &mut self,
         address: net::SocketAddr,
+        client_id: Option<ClientId>,
         frame: frame::Frame,
         now_ms: u64
     ) {
+        // Connection requests are resolved by address, and all other frames by client
+        if let frame::Frame::HandshakeSynFrame(frame) = frame {
+            self.handle_handshake_syn(address, frame, now_ms);
+            return;
+        }
+
+        let client_id = match client_id {
+            Some(client_id) => client_id,
+            None => return,
+        };
+
         match frame {
-            frame::Frame::HandshakeSynFrame(frame) => {
-                self.handle_handshake_syn(address, frame, now_ms);
-            }
+            frame::Frame::HandshakeSynFrame(_) => (),
             frame::Frame::HandshakeAckFrame(frame) => {
-                self.handle_handshake_ack(address, frame, now_ms);
+                self.handle_handshake_ack(client_id, frame, now_ms);
+            }
+            frame::Frame::HandshakeSynAckFrame(_) |
+            frame::Frame::HandshakeErrorFrame(_) |
+            frame::Frame::HandshakeCookieFrame(_) => {
+                logger::log(&mut self.config.logger, LogLevel::Warn, address,
+                            format_args!("received handshake reply from client"));
             }
-            frame::Frame::HandshakeSynAckFrame(_) => (),
-            frame::Frame::HandshakeErrorFrame(_) => (),
             frame::Frame::DisconnectFrame(_frame) => {
-                self.handle_disconnect(address, now_ms);
+                self.handle_disconnect(client_id, now_ms);
             }
             frame::Frame::DisconnectAckFrame(_frame) => {
-                self.handle_disconnect_ack(address);
+                self.handle_disconnect_ack(client_id);
             }
             frame::Frame::DataFrame(frame) => {
-                self.handle_data(address, frame, now_ms);
+                self.handle_data(client_id, frame, now_ms);
             }
             frame::Frame::SyncFrame(frame) => {
-                self.handle_sync(address, frame, now_ms);
+                self.handle_sync(client_id, frame, now_ms);
             }
             frame::Frame::AckFrame(frame) => {
-                self.handle_ack(address, frame, now_ms);
+                self.handle_acks(client_id, vec![ frame ], now_ms);
+            }
+            frame::Frame::FragmentAckFrame(frame) => {
+                self.handle_fragment_ack(client_id, frame, now_ms);
+            }
+            frame::Frame::GroupFrame(frame) => {
+                self.handle_group(client_id, frame, now_ms);
+            }
+            frame::Frame::GroupAckFrame(_) => {
+                logger::log(&mut self.config.logger, LogLevel::Warn, address,
+                            format_args!("received group acknowledgement from client"));
+            }
+            frame::Frame::CustomFrame(frame) => {
+                self.handle_custom(client_id, frame, now_ms);
+            }
+            frame::Frame::PathChallengeFrame(_) => {
+                logger::log(&mut self.config.logger, LogLevel::Warn, address,
+                            format_args!("received path challenge from client"));
+            }
+            frame::Frame::PathResponseFrame(_) => {
+                // A client which has not moved has nothing to prove
+            }
+            frame::Frame::ResetFrame(frame) => {
+                self.handle_reset(client_id, frame, now_ms);
+            }
+            frame::Frame::ResetAckFrame(frame) => {
+                self.handle_reset_ack(client_id, frame, now_ms);
+            }
+        }
+    }
+
+    // Sends a path challenge to an unknown address from which an established client appears to
+    // be sending, unless one was sent very recently. If the client has been recognized by its
+    // exchanged keys, the challenge is sealed with them.
+    fn challenge_path(
+        &mut self,
+        address: net::SocketAddr,
+        roamed: Option<net::SocketAddr>,
+        frame_data: &[u8],
+        now_ms: u64
+    ) {
+        if !self.path_challenges.contains_key(&address) {
+            if self.path_challenges.len() >= self.config.max_half_open_connections {
+                self.path_challenges.retain(|_, entry| now_ms < entry.timeout_time_ms);
+
+                if self.path_challenges.len() >= self.config.max_half_open_connections {
+                    return;
+                }
+            }
+
+            self.path_challenges.insert(address, PathChallenge {
+                challenge: rand::random::<u32>(),
+                send_time_ms: now_ms,
+                timeout_time_ms: now_ms + self.config.handshake_timeout_ms,
+            });
+        } else {
+            let entry = self.path_challenges.get_mut(&address).unwrap();
+
+            if now_ms < entry.send_time_ms + PATH_CHALLENGE_RESEND_INTERVAL_MS {
+                return;
+            }
+
+            entry.send_time_ms = now_ms;
+        }
+
+        let challenge = self.path_challenges[&address].challenge;
+        let request_bytes = frame::Frame::PathChallengeFrame(frame::PathChallengeFrame { challenge }).write();
+
+        // As with resets, a challenge is never larger than the frame which caused it
+        if request_bytes.len() <= frame_data.len() {
+            let _ = self.socket.send_to_peer(&request_bytes, address, roamed.unwrap_or(address));
+        }
+    }
+
+    // Moves an active client to the address from which it has answered a path challenge, or adds
+    // the address as an additional path if the client sends over several
+    fn handle_path_response(
+        &mut self,
+        address: net::SocketAddr,
+        roamed: Option<net::SocketAddr>,
+        frame: frame::PathResponseFrame,
+        now_ms: u64
+    ) {
+        match self.path_challenges.get(&address) {
+            Some(entry) if entry.challenge == frame.challenge_ack && now_ms < entry.timeout_time_ms => (),
+            _ => return,
+        }
+
+        let owns_connection = |client_rc: &&Rc<RefCell<remote_client::RemoteClient<CHANNELS>>>| {
+            match client_rc.borrow().state {
+                remote_client::State::Active(ref state) => {
+                    (state.migration_enabled || state.multipath_enabled) &&
+                        state.remote_nonce == frame.client_nonce &&
+                        state.local_nonce == frame.server_nonce
+                }
+                _ => false,
+            }
+        };
+
+        // A client whose keys have been exchanged is only recognized by them, since the response
+        // could not otherwise have been opened
+        let client_rc = match roamed {
+            Some(client_addr) => self.addresses.get(&client_addr)
+                .and_then(|entry| entry.owner)
+                .and_then(|client_id| self.clients.get(&client_id))
+                .filter(owns_connection),
+            None => self.active_clients.iter().find(owns_connection),
+        };
+
+        if let Some(client_rc) = client_rc.cloned() {
+            self.path_challenges.remove(&address);
+
+            let mut client = client_rc.borrow_mut();
+            let client_id = client.id;
+            let old_address = client.address;
+
+            let multipath_enabled = match client.state {
+                remote_client::State::Active(ref mut state) => {
+                    state.timeout_time_ms = now_ms + self.config.endpoint_config.active_timeout_ms;
+                    state.multipath_enabled
+                }
+                _ => false,
+            };
+
+            if multipath_enabled {
+                // A path is never shared with another client
+                if client.paths.len() + 1 < MAX_PATHS && !self.addresses.contains_key(&address) {
+                    logger::log(&mut self.config.logger, LogLevel::Info, old_address,
+                                format_args!("client added path {}", address));
+
+                    client.paths.push(address);
+                    self.join_address(client_id, address, false);
+                    self.socket.add_peer_path(old_address, address);
+                }
+
+                return;
+            }
+
+            // The address may be claimed by another client in the meantime
+            if !self.address_available(address, client.connection_id.is_some()) {
+                return;
+            }
+
+            logger::log(&mut self.config.logger, LogLevel::Info, address,
+                        format_args!("client migrated from {}", old_address));
+
+            client.address = address;
+            client.reply_address = address;
+
+            // Other clients at the old address continue to be heard from there
+            if self.leave_address(client_id, old_address) {
+                self.socket.move_peer(old_address, address);
+            } else {
+                self.socket.copy_peer(old_address, address);
             }
+
+            self.join_address(client_id, address, client.connection_id.is_none() || !self.addresses_shared());
+
+            self.events_out.push(Event::Migrate(client_id, old_address, address));
         }
     }
 
@@ -592,41 +2164,241 @@ impl Server {
         &mut self,
         now_ms: u64
     ) {
-        let mut frame_data_buf = [0; MAX_FRAME_SIZE];
+        let mut frame_data_buf = [0; MAX_FRAME_SIZE + MAX_TRANSFORM_OVERHEAD];
+
+        // Ack frames from each client are deferred until another kind of frame is received from
+        // that client, or until no more frames are available, so that they may be processed as a
+        // batch
+        let mut pending_acks: HashMap<ClientId, Vec<frame::AckFrame>> = HashMap::new();
+
+        while let Ok((frame_size, path_address, roamed)) = self.socket.recv_from_roaming(&mut frame_data_buf) {
+            let frame_data = &frame_data_buf[..frame_size];
+            let frame = frame::Frame::read(frame_data);
+
+            // A tagged frame is resolved to the client to which its ID was assigned, and is only
+            // known if it was received over one of that client's paths. An untagged frame is
+            // resolved to the client which owns the address it was received from. Frames received
+            // over an additional path of a client are handled as though they came from its address.
+            let client_id = match frame::serial::connection_id(frame_data) {
+                Some(connection_id) => self.clients.get(&ClientId(connection_id)).filter(|client_rc| {
+                    let client = client_rc.borrow();
+
+                    client.connection_id == Some(connection_id) &&
+                        (client.address == path_address || client.paths.contains(&path_address))
+                }).map(|_| ClientId(connection_id)),
+                None => self.addresses.get(&path_address).and_then(|entry| entry.owner),
+            };
+
+            let address = match client_id.and_then(|client_id| self.clients.get(&client_id)) {
+                Some(client_rc) => client_rc.borrow().address,
+                None => path_address,
+            };
+
+            let known = client_id.is_some() || matches!(frame, Some(frame::Frame::HandshakeSynFrame(_)));
+
+            if !known && (self.config.enable_migration || self.config.enable_multipath) {
+                if let Some(frame::Frame::PathResponseFrame(frame)) = frame {
+                    // The migrating client's acks must be handled before it moves
+                    for (client_id, frames) in pending_acks.drain() {
+                        self.handle_acks(client_id, frames, now_ms);
+                    }
+
+                    self.handle_path_response(address, roamed, frame, now_ms);
+                    continue;
+                }
+            }
+
+            if known {
+                if let Some(client_rc) = client_id.and_then(|client_id| self.clients.get(&client_id)) {
+                    let mut client = client_rc.borrow_mut();
+
+                    // Replies follow the client to the path over which it was most recently heard
+                    if self.config.enable_multipath {
+                        client.reply_address = path_address;
+                    }
+
+                    client.wake();
+                }
+
+                if let Some(ref mut hook) = self.config.frame_hook {
+                    if frame::serial::checksum_valid(frame_data) && hook(address, frame_data) == FrameAction::Consume {
+                        continue;
+                    }
+                }
+
+                if let Some(client_id) = client_id {
+                    self.note_decode_result(client_id, frame.is_some());
+                }
+
+                match (client_id, frame) {
+                    (Some(client_id), Some(frame::Frame::AckFrame(frame))) => {
+                        pending_acks.entry(client_id).or_default().push(frame);
+                    }
+                    (client_id, Some(frame)) => {
+                        if let Some(client_id) = client_id {
+                            if let Some(frames) = pending_acks.remove(&client_id) {
+                                self.handle_acks(client_id, frames, now_ms);
+                            }
+                        }
+
+                        self.handle_frame(address, client_id, frame, now_ms);
+                    }
+                    (_, None) => (),
+                }
+            } else {
+                self.handle_unknown_frame(address, roamed, frame_data, frame, now_ms);
+            }
+        }
+
+        for (client_id, frames) in pending_acks.into_iter() {
+            self.handle_acks(client_id, frames, now_ms);
+        }
+    }
+
+    // Tracks consecutive decode failures from a known client, and warns if they persist
+    fn note_decode_result(&mut self, client_id: ClientId, decoded: bool) {
+        if let Some(client_rc) = self.clients.get(&client_id) {
+            let mut client = client_rc.borrow_mut();
+            let address = client.address;
+
+            if decoded {
+                client.decode_failure_count = 0;
+            } else {
+                client.decode_failure_count += 1;
+
+                if client.decode_failure_count == logger::DECODE_FAILURE_WARN_COUNT {
+                    logger::log(&mut self.config.logger, LogLevel::Warn, address,
+                                format_args!("{} consecutive frames could not be decoded", client.decode_failure_count));
+                }
+            }
+        }
+    }
+
+    fn handle_unknown_frame(
+        &mut self,
+        address: net::SocketAddr,
+        roamed: Option<net::SocketAddr>,
+        frame_data: &[u8],
+        frame: Option<frame::Frame>,
+        now_ms: u64,
+    ) {
+        // Only reply to frames which would be sent by an established client; a client may not
+        // reply to a disconnect frame with anything but an acknowledgement.
+        let established = matches!(frame,
+            Some(frame::Frame::HandshakeAckFrame(_)) |
+            Some(frame::Frame::DataFrame(_)) |
+            Some(frame::Frame::SyncFrame(_)) |
+            Some(frame::Frame::AckFrame(_)) |
+            Some(frame::Frame::FragmentAckFrame(_)) |
+            Some(frame::Frame::GroupFrame(_)) |
+            Some(frame::Frame::CustomFrame(_)) |
+            Some(frame::Frame::ResetFrame(_)) |
+            Some(frame::Frame::ResetAckFrame(_)));
+
+        // A migrating client which was assigned a connection ID presents it, as does a client
+        // which sends over an additional path
+        let challenge = (self.config.enable_migration || self.config.enable_multipath) && established &&
+            !matches!(frame, Some(frame::Frame::HandshakeAckFrame(_))) &&
+            frame::serial::connection_id(frame_data).is_none_or(|connection_id| {
+                self.clients.get(&ClientId(connection_id))
+                    .is_some_and(|client_rc| client_rc.borrow().connection_id == Some(connection_id))
+            });
+
+        if challenge {
+            self.challenge_path(address, roamed, frame_data, now_ms);
+        }
 
-        while let Ok((frame_size, address)) = self.socket.recv_from(&mut frame_data_buf) {
-            if let Some(frame) = frame::Frame::read(&frame_data_buf[..frame_size]) {
-                self.handle_frame(address, frame, now_ms);
+        if let UnknownAddressPolicy::Ignore = self.config.unknown_address_policy {
+            return;
+        }
+
+        self.unknown_address_stats.frames += 1;
+
+        if frame.is_none() {
+            self.unknown_address_stats.malformed_frames += 1;
+        }
+
+        match self.config.unknown_address_policy {
+            UnknownAddressPolicy::Reset => {
+                if established && !challenge {
+                    let reply_bytes = frame::Frame::DisconnectFrame(frame::DisconnectFrame {}).write();
+
+                    if reply_bytes.len() <= frame_data.len() {
+                        let _ = self.socket.send_to(&reply_bytes, address);
+                        self.unknown_address_stats.resets += 1;
+                    }
+                }
+            }
+            UnknownAddressPolicy::Callback(ref mut callback) => {
+                callback(address, frame_data);
             }
+            _ => (),
         }
     }
 
     fn handle_event(
         &mut self,
-        mut event: event_queue::Event,
+        mut event: event_queue::Event<CHANNELS>,
         now_ms: u64
     ) {
         let mut client = event.client.borrow_mut();
+        let client_id = client.id;
+        let client_addr = client.address;
 
         match client.state {
-            remote_client::State::Pending(ref state) => {
+            remote_client::State::Pending(ref mut state) => {
                 if event.kind == event_queue::EventType::ResendHandshakeSynAck {
-                    if event.count > 0 {
-                        let _ = self.socket.send_to(&state.reply_bytes, client.address);
+                    if now_ms < state.timeout_time_ms {
+                        // Resend SYN+ACK, and back off
+                        let _ = self.socket.send_to(&state.reply_bytes, client_addr);
+                        state.reply_time_ms = now_ms;
 
-                        event.count -= 1;
-                        event.time = now_ms + HANDSHAKE_RESEND_INTERVAL_MS;
+                        state.resend_interval_ms = (state.resend_interval_ms * 2).min(HANDSHAKE_MAX_RESEND_INTERVAL_MS);
+                        let resend_time_ms = (now_ms + state.resend_interval_ms).min(state.timeout_time_ms);
 
                         std::mem::drop(client);
-                        self.client_events.push(event);
+                        self.client_events.push(resend_time_ms, event);
                     } else {
                         if self.config.enable_handshake_errors {
                             // Forget client and signal handshake timeout
-                            self.events_out.push(Event::Error(client.address, ErrorType::Timeout));
+                            self.events_out.push(Event::HandshakeError(client_addr, ErrorType::Timeout));
                         }
 
+                        self.half_open_count -= 1;
+
+                        client.close_reason = CloseReason::Timeout;
+                        client.state = remote_client::State::Fin;
+                        self.forget_client(&client);
+                    }
+                }
+            }
+            remote_client::State::Active(ref mut state) => {
+                if event.kind == event_queue::EventType::ActiveTimeout {
+                    if now_ms >= state.timeout_time_ms {
+                        // Signal remaining received packets
+                        state.half_connection.receive(&mut EventPacketSink::new(client_id, &mut self.events_out));
+
+                        // Forget client and signal timeout
+                        Self::record_summary(&mut client, CloseReason::Timeout, self.config.endpoint_config.session_summaries);
+                        Self::signal_summary(&mut client, &mut self.events_out);
+                        self.events_out.push(Event::Error(client_id, ErrorType::Timeout));
+
+                        client.close_reason = CloseReason::Timeout;
                         client.state = remote_client::State::Fin;
-                        self.clients.remove(&client.address);
+                        Self::signal_state_change(&mut client, self.config.endpoint_config.state_events, &mut self.events_out);
+                        self.forget_client(&client);
+                    } else {
+                        // Frames have been received since this timeout was scheduled
+                        let timeout_time_ms = state.timeout_time_ms;
+
+                        std::mem::drop(client);
+                        self.client_events.push(timeout_time_ms, event);
+                    }
+                } else if event.kind == event_queue::EventType::Step {
+                    // The event is stale if the client has since been rescheduled
+                    if state.step_deadline_ms.is_some_and(|deadline_ms| deadline_ms <= now_ms) {
+                        state.step_deadline_ms = None;
+                        client.wake();
                     }
                 }
             }
@@ -634,19 +2406,24 @@ impl Server {
                 if event.kind == event_queue::EventType::ResendDisconnect {
                     if event.count > 0 {
                         let request = frame::Frame::DisconnectFrame(frame::DisconnectFrame {});
-                        let _ = self.socket.send_to(&request.write(), client.address);
+                        let _ = self.socket.send_to(&request.write(), client_addr);
 
                         event.count -= 1;
-                        event.time = now_ms + DISCONNECT_RESEND_INTERVAL_MS;
 
                         std::mem::drop(client);
-                        self.client_events.push(event);
+                        self.client_events.push(now_ms + DISCONNECT_RESEND_INTERVAL_MS, event);
                     } else {
                         // Forget client and signal timeout
-                        self.events_out.push(Event::Error(client.address, ErrorType::Timeout));
+                        if let Some(ref mut summary) = client.session_summary {
+                            summary.reason = CloseReason::Timeout;
+                        }
+                        Self::signal_summary(&mut client, &mut self.events_out);
+                        self.events_out.push(Event::Error(client_id, ErrorType::Timeout));
 
+                        client.close_reason = CloseReason::Timeout;
                         client.state = remote_client::State::Fin;
-                        self.clients.remove(&client.address);
+                        Self::signal_state_change(&mut client, self.config.endpoint_config.state_events, &mut self.events_out);
+                        self.forget_client(&client);
                     }
                 }
             }
@@ -654,7 +2431,7 @@ impl Server {
                 if event.kind == event_queue::EventType::ClosedTimeout {
                     // Forget client at last (disconnect has already been signaled)
                     client.state = remote_client::State::Fin;
-                    self.clients.remove(&client.address);
+                    self.forget_client(&client);
                 }
             }
             _ => (),
@@ -665,42 +2442,41 @@ impl Server {
         &mut self,
         now_ms: u64
     ) {
-        while let Some(event) = self.client_events.peek() {
-            if event.time > now_ms {
+        while let Some(event) = self.client_events.pop_expired(now_ms) {
+            self.handle_event(event, now_ms);
+        }
+
+        // Release clients which are no longer pending
+        while let Some(client_rc) = self.half_open_clients.front() {
+            if let remote_client::State::Pending(_) = client_rc.borrow().state {
                 break;
             }
 
-            let event = self.client_events.pop().unwrap();
-
-            self.handle_event(event, now_ms);
+            self.half_open_clients.pop_front();
         }
+    }
+
+    // Steps each active client which has received frames or been given something to do since it
+    // was last stepped, or whose scheduled step is due. Other clients are not visited.
+    fn step_active_clients(&mut self, now_ms: u64) {
+        let woken_ids = std::mem::take(&mut *self.wake_queue.borrow_mut());
+
+        for client_id in woken_ids.into_iter() {
+            let client_rc = match self.clients.get(&client_id) {
+                Some(client_rc) => Rc::clone(client_rc),
+                None => continue,
+            };
 
-        for client_rc in self.active_clients.iter() {
             let mut client = client_rc.borrow_mut();
             let client_addr = client.address;
 
-            match client.state {
-                remote_client::State::Active(ref mut state) => {
-                    if now_ms >= state.timeout_time_ms {
-                        // Signal remaining received packets
-                        state.half_connection.receive(&mut EventPacketSink::new(client_addr, &mut self.events_out));
-
-                        // Forget client and signal timeout
-                        self.events_out.push(Event::Error(client_addr, ErrorType::Timeout));
+            let woken = std::mem::replace(&mut client.woken, false);
 
-                        client.state = remote_client::State::Fin;
-                        self.clients.remove(&client.address);
-                    }
-                }
-                _ => (),
+            if !woken || !client.is_active() {
+                continue;
             }
-        }
-    }
 
-    fn step_active_clients(&mut self, now_ms: u64) {
-        for client_rc in self.active_clients.iter() {
-            let mut client = client_rc.borrow_mut();
-            let client_addr = client.address;
+            Self::signal_state_change(&mut client, self.config.endpoint_config.state_events, &mut self.events_out);
 
             match client.state {
                 remote_client::State::Active(ref mut state) => {
@@ -712,24 +2488,92 @@ impl Server {
 
                     if disconnect_now {
                         // Signal remaining received packets
-                        state.half_connection.receive(&mut EventPacketSink::new(client_addr, &mut self.events_out));
+                        state.half_connection.receive(&mut EventPacketSink::new(client_id, &mut self.events_out));
 
                         // Attempt to close the connection
                         let request = frame::Frame::DisconnectFrame(frame::DisconnectFrame {});
                         let _ = self.socket.send_to(&request.write(), client.address);
 
+                        Self::record_summary(&mut client, CloseReason::Disconnect, self.config.endpoint_config.session_summaries);
+
                         client.state = remote_client::State::Closing;
+                        Self::signal_state_change(&mut client, self.config.endpoint_config.state_events, &mut self.events_out);
 
-                        self.client_events.push(event_queue::Event::new(
-                            Rc::clone(&client_rc),
-                            event_queue::EventType::ResendDisconnect,
+                        self.client_events.push(
                             now_ms + DISCONNECT_RESEND_INTERVAL_MS,
-                            DISCONNECT_RESEND_COUNT,
-                        ));
+                            event_queue::Event::new(
+                                Rc::clone(&client_rc),
+                                event_queue::EventType::ResendDisconnect,
+                                DISCONNECT_RESEND_COUNT,
+                            ),
+                        );
                     } else {
                         // Process and signal received packets
                         state.half_connection.step();
-                        state.half_connection.receive(&mut EventPacketSink::new(client_addr, &mut self.events_out));
+                        state.half_connection.receive(&mut EventPacketSink::new(client_id, &mut self.events_out));
+
+                        if self.config.endpoint_config.cadence_events {
+                            if let Some(cadence) = state.half_connection.poll_cadence_change() {
+                                self.events_out.push(Event::CadenceChange(client_id, cadence));
+                            }
+                        }
+
+                        if self.config.endpoint_config.quality_events {
+                            if let Some(score) = state.half_connection.poll_quality_change() {
+                                self.events_out.push(Event::QualityChange(client_id, score));
+                            }
+                        }
+
+                        if let Some(usage) = state.half_connection.poll_window_limited() {
+                            logger::log(&mut self.config.logger, LogLevel::Warn, client_addr,
+                                        format_args!("frame window stalled ({} of {} frames in flight)",
+                                                     usage.frames_in_flight, usage.frame_window_size));
+                            self.events_out.push(Event::WindowLimited(client_id, usage));
+                        }
+
+                        for (channel_id, signal) in state.half_connection.poll_backpressure() {
+                            self.events_out.push(Event::Backpressure(client_id, channel_id as usize, signal));
+                        }
+
+                        for (trace_id, outcome) in state.half_connection.poll_trace_outcomes() {
+                            logger::log(&mut self.config.logger, LogLevel::Debug, client_addr,
+                                        format_args!("traced packet {:#018x}: {:?}", trace_id, outcome));
+                            self.events_out.push(Event::Trace(client_id, trace_id, outcome));
+                        }
+
+                        for (channel_id, tag) in state.half_connection.poll_confirmations() {
+                            self.events_out.push(Event::Delivered(client_id, channel_id as usize, tag));
+                        }
+
+                        if let Some(drop_count) = state.half_connection.poll_reset() {
+                            logger::log(&mut self.config.logger, LogLevel::Info, client_addr,
+                                        format_args!("connection reset ({} packets dropped)", drop_count));
+                            self.events_out.push(Event::Reset(client_id, drop_count));
+                        }
+
+                        // A disconnecting client is stepped until its packets have been sent.
+                        // Otherwise, a step is scheduled unless one is already due sooner, in which
+                        // case the next deadline is recomputed then.
+                        let deadline_ms = if state.disconnect_signal.is_some() {
+                            Some(now_ms)
+                        } else {
+                            state.half_connection.next_timeout_ms().map(|timeout_ms| state.time_base_ms + timeout_ms)
+                        };
+
+                        if let Some(deadline_ms) = deadline_ms {
+                            if state.step_deadline_ms.is_none_or(|scheduled_ms| deadline_ms < scheduled_ms) {
+                                state.step_deadline_ms = Some(deadline_ms);
+
+                                self.client_events.push(
+                                    deadline_ms,
+                                    event_queue::Event::new(
+                                        Rc::clone(&client_rc),
+                                        event_queue::EventType::Step,
+                                        0,
+                                    ),
+                                );
+                            }
+                        }
                     }
                 }
                 _ => (),
@@ -737,18 +2581,143 @@ impl Server {
         }
     }
 
+    // Signals the client's connection state if it differs from the last state signaled
+    // Records the final statistics of an active client which is about to end
+    fn record_summary(client: &mut remote_client::RemoteClient<CHANNELS>, reason: CloseReason, enabled: bool) {
+        if let remote_client::State::Active(ref state) = client.state {
+            if enabled {
+                let mut summary = state.half_connection.session_summary(reason);
+                summary.link_stats.variant = client.variant_tag;
+                client.session_summary = Some(summary);
+            }
+        }
+    }
+
+    // Signals the recorded statistics of a client which has ended
+    fn signal_summary(client: &mut remote_client::RemoteClient<CHANNELS>, events_out: &mut Vec<Event>) {
+        if let Some(summary) = client.session_summary.take() {
+            events_out.push(Event::Summary(client.id, Box::new(summary)));
+        }
+    }
+
+    fn signal_state_change(client: &mut remote_client::RemoteClient<CHANNELS>, enabled: bool, events_out: &mut Vec<Event>) {
+        if let Some(state) = client.poll_state_change() {
+            if enabled {
+                events_out.push(Event::StateChange(client.id, state));
+            }
+        }
+    }
+
     fn flush_active_clients(&mut self) {
-        for client_rc in self.active_clients.iter() {
-            let mut client = client_rc.borrow_mut();
+        #[cfg(feature = "parallel")]
+        if self.config.parallel_flush {
+            self.flush_active_clients_parallel();
+            return;
+        }
+
+        let client_count = self.active_clients.len();
+
+        self.send_blocked = false;
+
+        // Acknowledgements are sent to every client before data is sent to any, so that a client
+        // which is late in the order still receives timely acknowledgements when the send buffer
+        // fills
+        for i in 0 .. client_count {
+            let index = (self.flush_index + i) % client_count;
+
+            let mut client = self.active_clients[index].borrow_mut();
+            let reply_address = client.reply_address;
+
+            if let remote_client::State::Active(ref mut state) = client.state {
+                state.half_connection.flush_acks(&mut TransportFrameSink::new(&self.socket, reply_address));
+
+                if state.half_connection.is_send_blocked() {
+                    self.flush_index = index;
+                    self.send_blocked = true;
+                    return;
+                }
+            }
+        }
+
+        // Begin with the client which was last interrupted, so that no client is starved by a
+        // persistently full send buffer
+        for i in 0 .. client_count {
+            let index = (self.flush_index + i) % client_count;
+
+            let mut client = self.active_clients[index].borrow_mut();
             let client_addr = client.address;
+            let reply_address = client.reply_address;
 
             match client.state {
                 remote_client::State::Active(ref mut state) => {
-                    let ref mut data_sink = UdpFrameSink::new(&self.socket, client_addr);
-                    state.half_connection.flush(data_sink);
+                    let ref mut data_sink = TransportFrameSink::new(&self.socket, reply_address);
+                    state.flush(data_sink, &mut self.config.heartbeat_provider, &mut self.config.pacer, client_addr);
+
+                    if state.half_connection.is_send_blocked() {
+                        // The socket is shared, so any remaining clients would block as well
+                        self.flush_index = index;
+                        self.send_blocked = true;
+                        return;
+                    }
                 }
                 _ => (),
             }
         }
     }
+
+    #[cfg(feature = "parallel")]
+    fn flush_active_clients_parallel(&mut self) {
+        let mut batches = Vec::with_capacity(2 * self.active_clients.len());
+
+        // As above, acknowledgements precede all data
+        for client_rc in self.active_clients.iter() {
+            let mut client = client_rc.borrow_mut();
+            let reply_address = client.reply_address;
+
+            if let remote_client::State::Active(ref mut state) = client.state {
+                let ref mut ack_sink = CollectFrameSink::new();
+                state.half_connection.flush_acks(ack_sink);
+
+                if !ack_sink.frames.is_empty() {
+                    batches.push((reply_address, std::mem::take(&mut ack_sink.frames)));
+                }
+            }
+        }
+
+        for client_rc in self.active_clients.iter() {
+            let mut client = client_rc.borrow_mut();
+            let client_addr = client.address;
+            let reply_address = client.reply_address;
+
+            if let remote_client::State::Active(ref mut state) = client.state {
+                let ref mut data_sink = CollectFrameSink::new();
+                state.flush(data_sink, &mut self.config.heartbeat_provider, &mut self.config.pacer, client_addr);
+
+                if !data_sink.frames.is_empty() {
+                    batches.push((reply_address, std::mem::take(&mut data_sink.frames)));
+                }
+            }
+        }
+
+        self.send_blocked = self.socket.send_batches(batches);
+    }
+}
+
+#[cfg(unix)]
+impl<const CHANNELS: usize> std::os::unix::io::AsRawFd for Server<net::UdpSocket, CHANNELS> {
+    /// Returns the file descriptor of the server's UDP socket, which may be registered with an
+    /// external event loop (e.g. via `mio::unix::SourceFd`). The socket must not be read from or
+    /// written to directly.
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.socket.inner().as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl<const CHANNELS: usize> std::os::windows::io::AsRawSocket for Server<net::UdpSocket, CHANNELS> {
+    /// Returns the handle of the server's UDP socket, which may be registered with an external
+    /// event loop. The socket must not be read from or written to directly.
+    fn as_raw_socket(&self) -> std::os::windows::io::RawSocket {
+        self.socket.inner().as_raw_socket()
+    }
 }
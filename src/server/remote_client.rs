@@ -1,8 +1,33 @@
+use std::cell::RefCell;
 use std::net;
+use std::rc::Rc;
+use std::time;
 
+use crate::half_connection::FrameSink;
 use crate::half_connection::HalfConnection;
+use crate::LatencyClass;
+use crate::LinkStats;
+use crate::QueueAction;
+use crate::QueuedPacket;
+use crate::ReorderLimits;
+use crate::SendCongested;
+use crate::SendHandle;
 use crate::SendMode;
-use crate::CHANNEL_COUNT;
+use crate::SendOptions;
+use crate::SessionSummary;
+use crate::Watermarks;
+#[cfg(feature = "stats")]
+use crate::StatsHistory;
+use crate::CUSTOM_FRAME_TYPE_COUNT;
+use crate::MAX_CUSTOM_FRAME_SIZE;
+use crate::ChannelStats;
+use crate::CloseReason;
+use crate::HeartbeatProvider;
+use crate::Pacer;
+use crate::Variant;
+use crate::ConnectionState;
+
+use super::ClientId;
 
 pub (super) enum DisconnectMode {
     Now,
@@ -14,31 +39,107 @@ pub (super) struct PendingState {
     pub remote_nonce: u32,
     pub remote_max_receive_rate: u32,
     pub remote_max_receive_alloc: u32,
+    pub remote_max_packet_size: u32,
+    pub remote_max_receive_window: u32,
+    pub features: u8,
+    pub ext_features: u8,
+    pub remote_custom_frames: bool,
+    pub remote_heartbeats: bool,
+    // Algorithm settings selected for this connection, if any
+    pub variant: Option<Variant>,
     pub reply_bytes: Box<[u8]>,
+    pub resend_interval_ms: u64,
+    // Time at which the reply was last sent, whether on schedule or in response to a duplicate SYN
+    pub reply_time_ms: u64,
+    pub timeout_time_ms: u64,
 }
 
-pub (super) struct ActiveState {
-    pub half_connection: HalfConnection,
+pub (super) struct ActiveState<const CHANNELS: usize> {
+    pub half_connection: HalfConnection<CHANNELS>,
+    // Handshake nonces, which a migrating client presents in response to a path challenge
+    pub local_nonce: u32,
+    pub remote_nonce: u32,
+    // Set if the client may move to a new address
+    pub migration_enabled: bool,
+    // Set if the client may send over additional paths
+    pub multipath_enabled: bool,
+    pub time_base_ms: u64,
     pub timeout_time_ms: u64,
+    // Time at which the client is next due to be stepped, if a step event has been scheduled
+    pub step_deadline_ms: Option<u64>,
     pub disconnect_signal: Option<DisconnectMode>,
+    // Set if the client accepts custom frames
+    pub custom_frames_enabled: bool,
+    // Set if the client accepts heartbeat payloads
+    pub heartbeats_enabled: bool,
 }
 
-pub (super) enum State {
+impl<const CHANNELS: usize> ActiveState<CHANNELS> {
+    // Sends as many frames as possible, attaching heartbeat payloads to any sync frame if the client
+    // accepts them
+    pub fn flush(&mut self, sink: &mut impl FrameSink, heartbeat_provider: &mut Option<HeartbeatProvider>,
+                 pacer: &mut Option<Box<dyn Pacer>>, address: net::SocketAddr) {
+        let heartbeats_enabled = self.heartbeats_enabled;
+        let heartbeat = || if heartbeats_enabled { heartbeat_provider.as_mut().and_then(|p| p(address)) } else { None };
+
+        if let Some(pacer) = pacer {
+            self.half_connection.flush_paced(sink, heartbeat, pacer.as_mut(), address);
+        } else {
+            self.half_connection.flush_with_heartbeat(sink, heartbeat);
+        }
+    }
+}
+
+pub (super) enum State<const CHANNELS: usize> {
     Pending(PendingState),
-    Active(ActiveState),
+    Active(ActiveState<CHANNELS>),
     Closing,
     Closed,
     Fin,
 }
 
-/// Used by a [`Server`](super::Server) object to represent a connected client.
-pub struct RemoteClient {
+/// Used by a [`Server`](super::Server) object to represent a connected client. The client may use
+/// the number of channels it proposed while connecting (see
+/// [`channel_count()`](Self::channel_count)).
+pub struct RemoteClient<const CHANNELS: usize = 0> {
+    pub (super) id: ClientId,
     pub (super) address: net::SocketAddr,
-    pub (super) state: State,
+    // Additional addresses from which the client sends, and the address to which frames are sent,
+    // which is that of the path over which the client was most recently heard
+    pub (super) paths: Vec<net::SocketAddr>,
+    pub (super) reply_address: net::SocketAddr,
+    // Assigned during the handshake, if the client supports connection IDs
+    pub (super) connection_id: Option<u32>,
+    pub (super) state: State<CHANNELS>,
+    // Number of channels proposed by the client, or the default count if it proposed none
+    pub (super) channel_count: usize,
     pub (super) max_packet_size: usize,
+    pub (super) max_send_delay_ms: u64,
+    // Number of consecutive frames received which could not be decoded
+    pub (super) decode_failure_count: u32,
+    // Reason reported once the connection has been closed
+    pub (super) close_reason: CloseReason,
+    // Most recent state signaled via Event::StateChange
+    pub (super) signaled_state: ConnectionState,
+    // Groups to which the client has subscribed, and the version of the subscription request which
+    // set them
+    pub (super) groups: Vec<String>,
+    pub (super) group_version: Option<u32>,
+    // Tag of the variant selected for this connection, if any
+    pub (super) variant_tag: Option<u32>,
+    // Final statistics of the connection, recorded once it is no longer active, and signaled once
+    // it has ended
+    pub (super) session_summary: Option<SessionSummary>,
+    // Identifies the next packet passed to send()
+    pub (super) next_send_id: u64,
+    // Set once the client has been queued to be stepped, so that it is queued at most once
+    pub (super) woken: bool,
+    // IDs of the clients to be stepped by the next call to Server::step(), shared by all clients
+    // of a server
+    pub (super) wake_queue: Rc<RefCell<Vec<ClientId>>>,
 }
 
-impl RemoteClient {
+impl<const CHANNELS: usize> RemoteClient<CHANNELS> {
     /// Returns `true` if the connection is active, that is, a connection handshake has been
     /// completed and the remote host has not yet timed out or disconnected. Returns `false`
     /// otherwise.
@@ -49,31 +150,499 @@ impl RemoteClient {
         }
     }
 
+    /// Returns the current state of the connection. If
+    /// [`EndpointConfig::state_events`](crate::EndpointConfig::state_events) is set, each change in
+    /// state following a successful handshake is also signaled by a
+    /// [`StateChange`](super::Event::StateChange) event.
+    pub fn connection_state(&self) -> ConnectionState {
+        match self.state {
+            State::Pending(_) => ConnectionState::Connecting,
+            State::Active(ref state) => {
+                if state.disconnect_signal.is_some() {
+                    ConnectionState::Draining
+                } else {
+                    ConnectionState::Connected
+                }
+            }
+            State::Closing => ConnectionState::Closing,
+            State::Closed | State::Fin => ConnectionState::Closed { reason: self.close_reason },
+        }
+    }
+
+    /// Returns the number of channels which may be used with this client, as proposed by the
+    /// client while connecting (see
+    /// [`EndpointConfig::channel_count`](crate::EndpointConfig::channel_count)). Clients which do
+    /// not propose a channel count use [`CHANNEL_COUNT`](crate::CHANNEL_COUNT) channels.
+    pub fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    /// Returns the maximum size of a packet which may be sent to this client (see
+    /// [`EndpointConfig::max_packet_size`](crate::EndpointConfig#structfield.max_packet_size)).
+    pub fn max_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
+
+    // Returns the current connection state if it differs from the last state returned
+    pub (super) fn poll_state_change(&mut self) -> Option<ConnectionState> {
+        let state = self.connection_state();
+
+        if state != self.signaled_state {
+            self.signaled_state = state;
+            Some(state)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the names of the groups to which this client is subscribed (see
+    /// [`Server::send_group()`](super::Server::send_group)).
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.groups.iter().map(|name| name.as_str())
+    }
+
+    /// Returns the ID by which this client is identified by its server.
+    pub fn id(&self) -> ClientId {
+        self.id
+    }
+
+    /// Returns the address of this client.
+    pub fn address(&self) -> net::SocketAddr {
+        self.address
+    }
+
+    /// Returns the additional addresses from which this client has been validated to send (see
+    /// [`Config::enable_multipath`](super::Config::enable_multipath)).
+    pub fn paths(&self) -> &[net::SocketAddr] {
+        &self.paths
+    }
+
+    /// Returns the connection ID assigned to this client, if any (see
+    /// [`Config::enable_connection_ids`](super::Config::enable_connection_ids)).
+    pub fn connection_id(&self) -> Option<u32> {
+        self.connection_id
+    }
+
+    /// Sets the application latency reported to the client by subsequent acknowledgements, in
+    /// milliseconds, e.g. the time taken to process the most recently received packet (see
+    /// [`EndpointConfig::app_latency`](crate::EndpointConfig::app_latency)). Values are reported
+    /// with millisecond precision, up to 65535ms.
+    ///
+    /// If the connection is not active, this function has no effect.
+    pub fn set_app_latency_ms(&mut self, latency_ms: u64) {
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.set_app_latency_ms(latency_ms);
+        }
+    }
+
+    // Returns a short name describing the state of the connection, for diagnostic purposes
+    pub (crate) fn state_name(&self) -> &'static str {
+        match self.state {
+            State::Pending(_) => "pending",
+            State::Active(_) => "active",
+            State::Closing => "closing",
+            State::Closed => "closed",
+            State::Fin => "fin",
+        }
+    }
+
+    // Queues this client to be stepped by the next call to Server::step(), having been given
+    // something to do before its next scheduled step
+    pub (super) fn wake(&mut self) {
+        if !self.woken {
+            self.woken = true;
+            self.wake_queue.borrow_mut().push(self.id);
+        }
+    }
+
     /// Enqueues a packet for delivery to this client. The packet will be sent on the given channel
     /// according to the specified mode.
     ///
     /// If the connection is not active, the packet will be silently discarded.
     ///
+    /// The returned handle may be passed to [`cancel()`](Self::cancel) to withdraw the packet
+    /// before it is sent.
+    ///
     /// # Error Handling
     ///
     /// This function will panic if `channel_id` does not refer to a valid channel (i.e. if
-    /// `channel_id >= CHANNEL_COUNT`), or if `data.len()` exceeds the [maximum packet
+    /// `channel_id >= self.channel_count()`), or if `data.len()` exceeds the [maximum packet
     /// size](crate::EndpointConfig#structfield.max_packet_size).
-    pub fn send(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode) {
+    pub fn send(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions::default())
+    }
+
+    /// Enqueues a packet for delivery to this client with the given options (see [`SendOptions`]).
+    /// Otherwise identical to [`send()`](Self::send).
+    ///
+    /// Options may be combined, e.g. to send a critical packet which is both keyed and traced. The
+    /// remaining `send_*()` functions are shorthand for this function with a single option set.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `options.ttl` is set and `mode` is not
+    /// [`TimeSensitive`](SendMode::TimeSensitive) or [`Unreliable`](SendMode::Unreliable), if
+    /// `options.confirm_tag` is set and `mode` is not [`Reliable`](SendMode::Reliable) or
+    /// [`ReliableUnordered`](SendMode::ReliableUnordered), if `channel_id` does not refer to a valid
+    /// channel (i.e. if `channel_id >= self.channel_count()`), or if `data.len()` exceeds the
+    /// [maximum packet size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_with(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, options: SendOptions) -> SendHandle {
+        self.check_send(&data, channel_id, mode, &options);
+
+        let send_id = self.next_send_id;
+        self.next_send_id += 1;
+
+        self.wake();
+
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.send_with(data, channel_id as u8, mode, options, send_id);
+        }
+
+        SendHandle(send_id)
+    }
+
+    // Panics if a packet with the given parameters may not be sent
+    fn check_send(&self, data: &[u8], channel_id: usize, mode: SendMode, options: &SendOptions) {
+        assert!(options.ttl.is_none() || mode == SendMode::TimeSensitive || mode == SendMode::Unreliable,
+                "send failed: send mode {:?} does not support a TTL",
+                mode);
+
+        assert!(options.confirm_tag.is_none() || mode == SendMode::Reliable || mode == SendMode::ReliableUnordered,
+                "send failed: send mode {:?} does not support delivery confirmation",
+                mode);
+
         assert!(data.len() <= self.max_packet_size,
                 "send failed: packet of size {} exceeds configured maximum of {}",
                 data.len(),
                 self.max_packet_size);
 
-        assert!(channel_id < CHANNEL_COUNT,
+        assert!(channel_id < self.channel_count,
                 "send failed: channel ID {} is invalid",
                 channel_id);
+    }
 
+    /// Cancels a packet previously enqueued by [`send()`](Self::send) or one of its variants.
+    /// Returns `true` if the packet was canceled, or `false` if it has already been sent (in part or
+    /// in full) or discarded.
+    ///
+    /// See [`Client::cancel()`](crate::client::Client::cancel) for details.
+    pub fn cancel(&mut self, handle: SendHandle) -> bool {
         match self.state {
-            State::Active(ref mut state) => {
-                state.half_connection.send(data, channel_id as u8, mode);
+            State::Active(ref mut state) => state.half_connection.cancel(handle.0),
+            _ => false,
+        }
+    }
+
+    /// Enqueues a packet for delivery to this client, unless the connection is congested.
+    /// Otherwise identical to [`send()`](Self::send).
+    ///
+    /// See [`Client::try_send()`](crate::client::Client::try_send) for details.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (i.e. if
+    /// `channel_id >= self.channel_count()`), or if `data.len()` exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn try_send(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode) -> Result<SendHandle, SendCongested> {
+        self.check_send(&data, channel_id, mode, &SendOptions::default());
+
+        if let State::Active(ref state) = self.state {
+            let estimated_delay_ms = state.half_connection.send_delay_ms();
+
+            if estimated_delay_ms > self.max_send_delay_ms {
+                return Err(SendCongested { estimated_delay_ms });
             }
-            _ => (),
+        }
+
+        Ok(self.send(data, channel_id, mode))
+    }
+
+    /// Enqueues a packet for delivery to this client with the given latency class (see
+    /// [`LatencyClass`]). Otherwise identical to [`send()`](Self::send).
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (i.e. if
+    /// `channel_id >= self.channel_count()`), or if `data.len()` exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_with_class(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, latency_class: LatencyClass) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions { latency_class, .. Default::default() })
+    }
+
+    /// Enqueues a packet for delivery to this client which is ordered only with respect to other
+    /// packets sent on the same channel with the same key. Otherwise identical to
+    /// [`send()`](Self::send).
+    ///
+    /// See [`Client::send_keyed()`](crate::client::Client::send_keyed) for details.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (i.e. if
+    /// `channel_id >= self.channel_count()`), or if `data.len()` exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_keyed(&mut self, data: Box<[u8]>, channel_id: usize, key: u16, mode: SendMode) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions { key: Some(key), .. Default::default() })
+    }
+
+    /// Enqueues a packet for delivery to this client whose outcome will be reported by a
+    /// [`Trace`](super::Event::Trace) event carrying the given trace ID. Otherwise identical to
+    /// [`send()`](Self::send).
+    ///
+    /// See [`Client::send_traced()`](crate::client::Client::send_traced) for details.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (i.e. if
+    /// `channel_id >= self.channel_count()`), or if `data.len()` exceeds the [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_traced(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, trace_id: u64) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions { trace_id: Some(trace_id), .. Default::default() })
+    }
+
+    /// Enqueues a reliable packet for delivery to this client whose receipt will be confirmed by a
+    /// [`Delivered`](super::Event::Delivered) event carrying its channel ID and the given tag.
+    /// Otherwise identical to [`send()`](Self::send).
+    ///
+    /// See [`Client::send_confirmed()`](crate::client::Client::send_confirmed) for details.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `mode` is not [`Reliable`](SendMode::Reliable) or
+    /// [`ReliableUnordered`](SendMode::ReliableUnordered), if `channel_id` does not refer to a
+    /// valid channel (i.e. if `channel_id >= self.channel_count()`), or if `data.len()` exceeds the
+    /// [maximum packet size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_confirmed(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, tag: u64) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions { confirm_tag: Some(tag), .. Default::default() })
+    }
+
+    /// Enqueues an unreliable packet for delivery to this client which is dropped, rather than
+    /// sent, if it remains in the send queue for longer than `ttl`. Otherwise identical to
+    /// [`send()`](Self::send).
+    ///
+    /// See [`Client::send_with_ttl()`](crate::client::Client::send_with_ttl) for details.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `mode` is not [`TimeSensitive`](SendMode::TimeSensitive) or
+    /// [`Unreliable`](SendMode::Unreliable), if `channel_id` does not refer to a valid channel
+    /// (i.e. if `channel_id >= self.channel_count()`), or if `data.len()` exceeds the [maximum
+    /// packet size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn send_with_ttl(&mut self, data: Box<[u8]>, channel_id: usize, mode: SendMode, ttl: time::Duration) -> SendHandle {
+        self.send_with(data, channel_id, mode, SendOptions { ttl: Some(ttl), .. Default::default() })
+    }
+
+    /// Enqueues a custom frame of the given type, to be sent to this client during the next call to
+    /// [`Server::step()`](super::Server::step) or [`Server::flush()`](super::Server::flush).
+    /// Custom frames are sent unreliably, and are silently discarded if the connection is not
+    /// active, or if the client has not registered any custom frame types.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `type_id` does not refer to a valid custom frame type (`type_id
+    /// >= CUSTOM_FRAME_TYPE_COUNT`), or if the size of `data` exceeds
+    /// [`MAX_CUSTOM_FRAME_SIZE`](crate::MAX_CUSTOM_FRAME_SIZE).
+    pub fn send_custom_frame(&mut self, type_id: usize, data: Box<[u8]>) {
+        assert!(type_id < CUSTOM_FRAME_TYPE_COUNT,
+                "send_custom_frame failed: type ID {} is invalid",
+                type_id);
+
+        assert!(data.len() <= MAX_CUSTOM_FRAME_SIZE,
+                "send_custom_frame failed: frame of size {} exceeds maximum of {}",
+                data.len(),
+                MAX_CUSTOM_FRAME_SIZE);
+
+        self.wake();
+
+        if let State::Active(ref mut state) = self.state {
+            if state.custom_frames_enabled {
+                state.half_connection.send_custom_frame(type_id as u8, data);
+            }
+        }
+    }
+
+    /// Returns a description of each packet which has been enqueued for sending to the client, but
+    /// which has not yet been sent, in the order the packets will be sent (or, if
+    /// [`channel_weights`](crate::EndpointConfig#structfield.channel_weights) are set, the order
+    /// the packets were enqueued). Packets enqueued before the connection was established are not
+    /// included.
+    pub fn queued_packets(&self) -> impl Iterator<Item = QueuedPacket<'_>> {
+        match self.state {
+            State::Active(ref state) => Some(state.half_connection.queued_packets()),
+            _ => None,
+        }.into_iter().flatten()
+    }
+
+    /// Reorders or drops packets which have been enqueued for sending to the client, but which
+    /// have not yet been sent, according to the given function (see [`QueueAction`]). The function
+    /// is called once for each queued packet, in the order the packets would otherwise be sent.
+    ///
+    /// Packets are assigned sequence numbers when they are sent, so a promoted packet will be
+    /// delivered ahead of any packets which were enqueued before it on the same channel, and a
+    /// demoted packet will be delivered behind any packets which were enqueued after it. Packets
+    /// retain their relative order otherwise.
+    pub fn reshape_send_queue(&mut self, f: impl FnMut(&QueuedPacket) -> QueueAction) {
+        self.wake();
+
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.reshape_send_queue(f);
+        }
+    }
+
+    /// Sets the staleness bound of the given channel, in milliseconds.
+    ///
+    /// If the connection is congested such that the send queue cannot be drained within one RTT,
+    /// any [`Unreliable`](SendMode::Unreliable) or [`TimeSensitive`](SendMode::TimeSensitive)
+    /// packets on this channel which have waited in the send queue for longer than the staleness
+    /// bound will be dropped by the server. Dropped packets are counted by `link_stats()`. A bound
+    /// of `None` (the default) disables this behavior.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn set_stale_bound(&mut self, channel_id: usize, bound_ms: Option<u64>) {
+        assert!(channel_id < self.channel_count,
+                "set_stale_bound failed: channel ID {} is invalid",
+                channel_id);
+
+        self.wake();
+
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.set_stale_bound(channel_id as u8, bound_ms);
+        }
+    }
+
+    /// Sets the watermarks of the given channel, which bound the number of bytes which may wait in
+    /// the channel's send queue before a [`Backpressure`](super::Event::Backpressure) event is
+    /// generated (see [`Watermarks`]). Watermarks are evaluated during each call to
+    /// [`Server::step()`](super::Server::step), so an application may pause producing data for a
+    /// channel once signaled, rather than polling the size of its send queue. Watermarks of `None`
+    /// (the default) disable this behavior, and resume the channel if it was paused.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`), or if `watermarks.low` is not less than `watermarks.high`.
+    pub fn set_watermarks(&mut self, channel_id: usize, watermarks: Option<Watermarks>) {
+        assert!(channel_id < self.channel_count,
+                "set_watermarks failed: channel ID {} is invalid",
+                channel_id);
+
+        if let Some(watermarks) = watermarks {
+            assert!(watermarks.low < watermarks.high,
+                    "set_watermarks failed: low watermark {} is not less than high watermark {}",
+                    watermarks.low,
+                    watermarks.high);
+        }
+
+        self.wake();
+
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.set_watermarks(channel_id as u8, watermarks);
+        }
+    }
+
+    /// Sets the reorder limits of the given channel, which bound the number of packets received
+    /// from this client that may wait for a missing predecessor, and the time for which they may
+    /// wait (see [`ReorderLimits`]). Limits of `None` (the default) allow packets to wait
+    /// indefinitely.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn set_reorder_limits(&mut self, channel_id: usize, limits: Option<ReorderLimits>) {
+        assert!(channel_id < self.channel_count,
+                "set_reorder_limits failed: channel ID {} is invalid",
+                channel_id);
+
+        self.wake();
+
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.set_reorder_limits(channel_id as u8, limits);
+        }
+    }
+
+    /// Withholds packets received from this client on the given channel until
+    /// [`resume_receive()`](Self::resume_receive) is called. Packets on other channels continue to
+    /// be delivered.
+    ///
+    /// Withheld packets occupy the receive window, so the client's send rate on all channels is
+    /// naturally limited once the window fills, and the connection stalls if the channel remains
+    /// paused for too long.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn pause_receive(&mut self, channel_id: usize) {
+        assert!(channel_id < self.channel_count,
+                "pause_receive failed: channel ID {} is invalid",
+                channel_id);
+
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.set_receive_paused(channel_id as u8, true);
+        }
+    }
+
+    /// Resumes delivery of packets received on the given channel. Withheld packets are delivered
+    /// in order by the next call to [`Server::step()`](super::Server::step).
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn resume_receive(&mut self, channel_id: usize) {
+        assert!(channel_id < self.channel_count,
+                "resume_receive failed: channel ID {} is invalid",
+                channel_id);
+
+        self.wake();
+
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.set_receive_paused(channel_id as u8, false);
+        }
+    }
+
+    /// Discards all packets received from this client on the given channel which are waiting for a
+    /// missing predecessor, except for the newest, which will be delivered by the next call to
+    /// [`Server::step()`](super::Server::step). Missing predecessors are no longer awaited, and are
+    /// dropped if they arrive later. Returns the number of packets discarded.
+    ///
+    /// Packets sent with an ordering key are unaffected.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn skip_to_latest(&mut self, channel_id: usize) -> usize {
+        assert!(channel_id < self.channel_count,
+                "skip_to_latest failed: channel ID {} is invalid",
+                channel_id);
+
+        self.wake();
+
+        match self.state {
+            State::Active(ref mut state) => state.half_connection.skip_to_latest(channel_id as u8),
+            _ => 0,
+        }
+    }
+
+    /// Requests a soft reset of this connection, which returns both endpoints to a clean slate
+    /// without a reconnect round trip. All packets awaiting transmission or acknowledgement are
+    /// dropped (traced packets are reported via [`Event::Trace`](super::Event::Trace) as usual),
+    /// and the client is asked to drop its own, after which both transfer windows restart from the
+    /// next sequence IDs. Packets received but not yet delivered are discarded by both endpoints.
+    ///
+    /// Packets received from the client are ignored, and no packets are sent, until the client
+    /// acknowledges the reset, at which point an [`Event::Reset`](super::Event::Reset) is
+    /// generated. Packets enqueued in the meantime are sent afterward. Has no effect if a reset is
+    /// already pending, or if the connection is not active.
+    pub fn reset(&mut self) {
+        self.wake();
+
+        if let State::Active(ref mut state) = self.state {
+            state.half_connection.reset();
         }
     }
 
@@ -84,6 +653,8 @@ impl RemoteClient {
     /// disconnect in the meantime. The connection will remain active until the next call to
     /// [`Server::step()`](super::Server::step) with no pending outbound packets.
     pub fn disconnect(&mut self) {
+        self.wake();
+
         match self.state {
             State::Active(ref mut state) => {
                 state.disconnect_signal = Some(DisconnectMode::Flush);
@@ -98,6 +669,8 @@ impl RemoteClient {
     /// packets are guaranteed to be received by the client. The connection will remain active
     /// until the next call to [`Server::step()`](super::Server::step).
     pub fn disconnect_now(&mut self) {
+        self.wake();
+
         match self.state {
             State::Active(ref mut state) => {
                 state.disconnect_signal = Some(DisconnectMode::Now);
@@ -129,4 +702,64 @@ impl RemoteClient {
             _ => 0,
         }
     }
+
+    /// Returns `true` if the most recent flush was interrupted because the socket's send buffer
+    /// was full. Frames which could not be sent are retained, and are sent first on the next flush.
+    ///
+    /// If the connection is not active, `false` is returned instead.
+    pub fn is_send_blocked(&self) -> bool {
+        match self.state {
+            State::Active(ref state) => state.half_connection.is_send_blocked(),
+            _ => false,
+        }
+    }
+
+    /// Returns a snapshot of statistics describing the current connection.
+    ///
+    /// If the connection is not active, default values are returned instead.
+    pub fn link_stats(&self) -> LinkStats {
+        let stats = match self.state {
+            State::Active(ref state) => state.half_connection.link_stats(),
+            _ => LinkStats::default(),
+        };
+
+        LinkStats { variant: self.variant_tag, .. stats }
+    }
+
+    /// Returns the tag of the [`Variant`] selected for this connection, if any (see
+    /// [`Config::variant_selector`](super::Config::variant_selector)).
+    pub fn variant(&self) -> Option<u32> {
+        self.variant_tag
+    }
+
+    /// Returns packet counters for the given channel.
+    ///
+    /// If the connection is not active, default values are returned instead.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (`channel_id >=
+    /// self.channel_count()`).
+    pub fn channel_stats(&self, channel_id: usize) -> ChannelStats {
+        assert!(channel_id < self.channel_count,
+                "channel_stats failed: channel ID {} is invalid",
+                channel_id);
+
+        match self.state {
+            State::Active(ref state) => state.half_connection.channel_stats(channel_id as u8),
+            _ => ChannelStats::default(),
+        }
+    }
+
+    /// Returns the history of sampled connection metrics, if enabled (see
+    /// [`EndpointConfig::stats_sample_interval_ms`](crate::EndpointConfig#structfield.stats_sample_interval_ms)).
+    ///
+    /// If sampling is disabled, or if the connection is not active, `None` is returned instead.
+    #[cfg(feature = "stats")]
+    pub fn stats_history(&self) -> Option<&StatsHistory> {
+        match self.state {
+            State::Active(ref state) => state.half_connection.stats_history(),
+            _ => None,
+        }
+    }
 }
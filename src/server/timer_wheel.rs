@@ -0,0 +1,308 @@
+
+// A hierarchical timer wheel with millisecond resolution. Level 0 contains SLOT_COUNT slots of
+// 1ms each, and each subsequent level contains SLOT_COUNT slots which span an entire rotation of
+// the level below. A timer is placed in the lowest level whose current rotation contains its
+// deadline, and is moved (cascaded) to a lower level once the wheel reaches its slot. Occupied
+// slots are tracked using a bitmask per level, so that empty spans of time are skipped in constant
+// time, regardless of how many timers are pending or how much time has elapsed.
+
+use std::collections::VecDeque;
+
+const SLOT_BITS: u32 = 6;
+const SLOT_COUNT: usize = 1 << SLOT_BITS;
+const LEVEL_COUNT: usize = 6;
+
+// Deadlines beyond this span are placed in the furthest slot of the last level, and are cascaded
+// until they expire
+const MAX_SPAN_MS: u64 = 1 << (SLOT_BITS as usize * LEVEL_COUNT);
+const MAX_DELAY_MS: u64 = MAX_SPAN_MS - (MAX_SPAN_MS >> SLOT_BITS);
+
+struct Level<T> {
+    slots: Vec<Vec<(u64, T)>>,
+    occupied: u64,
+}
+
+impl<T> Level<T> {
+    fn new() -> Self {
+        Self {
+            slots: (0 .. SLOT_COUNT).map(|_| Vec::new()).collect(),
+            occupied: 0,
+        }
+    }
+}
+
+pub struct TimerWheel<T> {
+    levels: Vec<Level<T>>,
+    expired: VecDeque<T>,
+    current_ms: u64,
+}
+
+impl<T> TimerWheel<T> {
+    pub fn new() -> Self {
+        Self {
+            levels: (0 .. LEVEL_COUNT).map(|_| Level::new()).collect(),
+            expired: VecDeque::new(),
+            current_ms: 0,
+        }
+    }
+
+    // Schedules an item to be returned by pop_expired() once time_ms has been reached
+    pub fn push(&mut self, time_ms: u64, item: T) {
+        self.place(time_ms, item);
+    }
+
+    // Returns the next item whose deadline is no later than now_ms, if any
+    pub fn pop_expired(&mut self, now_ms: u64) -> Option<T> {
+        loop {
+            if let Some(item) = self.expired.pop_front() {
+                return Some(item);
+            }
+
+            match self.next_deadline() {
+                Some((level_idx, slot_idx, deadline_ms)) if deadline_ms <= now_ms => {
+                    self.current_ms = self.current_ms.max(deadline_ms);
+
+                    let level = &mut self.levels[level_idx];
+                    let entries = std::mem::take(&mut level.slots[slot_idx]);
+                    level.occupied &= !(1 << slot_idx);
+
+                    for (time_ms, item) in entries.into_iter() {
+                        self.place(time_ms, item);
+                    }
+                }
+                _ => {
+                    self.current_ms = self.current_ms.max(now_ms);
+                    return None;
+                }
+            }
+        }
+    }
+
+    // Returns an iterator over the items whose deadline is no later than now_ms, without removing
+    // them. Only those slots which begin no later than now_ms are visited.
+    pub fn peek_expired(&self, now_ms: u64) -> impl Iterator<Item = &T> + '_ {
+        let current_ms = self.current_ms;
+
+        let pending = self.levels.iter().enumerate().flat_map(move |(level_idx, level)| {
+            let slot_shift = level_idx as u32 * SLOT_BITS;
+            let level_span_ms = 1u64 << (slot_shift + SLOT_BITS);
+
+            let current_slot_idx = ((current_ms >> slot_shift) as usize) & (SLOT_COUNT - 1);
+            let level_base_ms = current_ms & !(level_span_ms - 1);
+
+            (0 .. SLOT_COUNT)
+                .map(move |offset| (current_slot_idx + offset) % SLOT_COUNT)
+                .take_while(move |&slot_idx| {
+                    let mut start_ms = level_base_ms + ((slot_idx as u64) << slot_shift);
+
+                    if slot_idx < current_slot_idx {
+                        start_ms += level_span_ms;
+                    }
+
+                    start_ms <= now_ms
+                })
+                .filter(move |&slot_idx| level.occupied & (1 << slot_idx) != 0)
+                .flat_map(move |slot_idx| level.slots[slot_idx].iter())
+        });
+
+        self.expired.iter().chain(
+            pending.filter(move |&&(time_ms, _)| time_ms <= now_ms).map(|(_, item)| item)
+        )
+    }
+
+    // Returns a time no later than the earliest pending deadline, or None if no items are pending.
+    // Deadlines in higher levels are reported as the start of their slot, so the time returned may
+    // precede the deadline itself.
+    pub fn next_expiry_ms(&self) -> Option<u64> {
+        if !self.expired.is_empty() {
+            return Some(self.current_ms);
+        }
+
+        self.next_deadline().map(|(_, _, deadline_ms)| deadline_ms)
+    }
+
+    fn place(&mut self, time_ms: u64, item: T) {
+        if time_ms <= self.current_ms {
+            self.expired.push_back(item);
+            return;
+        }
+
+        let clamped_ms = time_ms.min(self.current_ms + MAX_DELAY_MS);
+
+        // The level is determined by the most significant bit which differs from the current time
+        let masked = (self.current_ms ^ clamped_ms) | (SLOT_COUNT as u64 - 1);
+        let significant_bit = 63 - masked.leading_zeros();
+        let level_idx = ((significant_bit / SLOT_BITS) as usize).min(LEVEL_COUNT - 1);
+        let slot_idx = ((clamped_ms >> (level_idx as u32 * SLOT_BITS)) as usize) & (SLOT_COUNT - 1);
+
+        let level = &mut self.levels[level_idx];
+        level.slots[slot_idx].push((time_ms, item));
+        level.occupied |= 1 << slot_idx;
+    }
+
+    // Returns the level, slot, and start time of the earliest occupied slot
+    fn next_deadline(&self) -> Option<(usize, usize, u64)> {
+        for (level_idx, level) in self.levels.iter().enumerate() {
+            if level.occupied == 0 {
+                continue;
+            }
+
+            let slot_shift = level_idx as u32 * SLOT_BITS;
+            let level_span_ms = 1u64 << (slot_shift + SLOT_BITS);
+
+            let current_slot_idx = ((self.current_ms >> slot_shift) as usize) & (SLOT_COUNT - 1);
+            let offset = level.occupied.rotate_right(current_slot_idx as u32).trailing_zeros() as usize;
+            let slot_idx = (current_slot_idx + offset) % SLOT_COUNT;
+
+            // Only the last level may contain timers which belong to its next rotation
+            let level_base_ms = self.current_ms & !(level_span_ms - 1);
+            let mut deadline_ms = level_base_ms + ((slot_idx as u64) << slot_shift);
+
+            if slot_idx < current_slot_idx {
+                deadline_ms += level_span_ms;
+            }
+
+            return Some((level_idx, slot_idx, deadline_ms.max(self.current_ms)));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pop_all(wheel: &mut TimerWheel<u64>, now_ms: u64) -> Vec<u64> {
+        let mut items = Vec::new();
+        while let Some(item) = wheel.pop_expired(now_ms) {
+            items.push(item);
+        }
+        items
+    }
+
+    #[test]
+    fn expiration_order() {
+        let mut wheel = TimerWheel::new();
+
+        let times = [ 0, 1, 5, 63, 64, 65, 100, 4095, 4096, 5000, 300000, 2000000 ];
+
+        for &time_ms in times.iter().rev() {
+            wheel.push(time_ms, time_ms);
+        }
+
+        let mut now_ms = 0;
+        let mut popped = Vec::new();
+
+        while now_ms <= 2000000 {
+            for item in pop_all(&mut wheel, now_ms) {
+                // Each item is returned exactly on time
+                assert_eq!(item, now_ms);
+                popped.push(item);
+            }
+            now_ms += 1;
+        }
+
+        assert_eq!(popped, times.to_vec());
+    }
+
+    #[test]
+    fn coarse_polling() {
+        let mut wheel = TimerWheel::new();
+
+        wheel.push(10, 10);
+        wheel.push(2000, 2000);
+        wheel.push(70000, 70000);
+
+        assert_eq!(pop_all(&mut wheel, 9), vec![]);
+        assert_eq!(pop_all(&mut wheel, 5000), vec![ 10, 2000 ]);
+
+        // Timers scheduled in the past expire immediately
+        wheel.push(100, 100);
+        assert_eq!(pop_all(&mut wheel, 5000), vec![ 100 ]);
+
+        // Timers scheduled relative to the new time are cascaded correctly
+        wheel.push(5001, 5001);
+        assert_eq!(pop_all(&mut wheel, 5000), vec![]);
+        assert_eq!(pop_all(&mut wheel, 1000000), vec![ 5001, 70000 ]);
+    }
+
+    #[test]
+    fn next_expiry() {
+        let mut wheel = TimerWheel::new();
+
+        assert_eq!(wheel.next_expiry_ms(), None);
+
+        wheel.push(10, 10);
+        wheel.push(2000, 2000);
+        wheel.push(70000, 70000);
+
+        assert_eq!(wheel.next_expiry_ms(), Some(10));
+
+        // Waiting until each reported time never passes a deadline
+        let mut popped = Vec::new();
+
+        while let Some(now_ms) = wheel.next_expiry_ms() {
+            for item in pop_all(&mut wheel, now_ms) {
+                assert_eq!(item, now_ms);
+                popped.push(item);
+            }
+        }
+
+        assert_eq!(popped, vec![ 10, 2000, 70000 ]);
+    }
+
+    #[test]
+    fn peek_expired() {
+        let mut wheel = TimerWheel::new();
+
+        let times = [ 0, 1, 5, 63, 64, 65, 100, 4095, 4096, 5000, 300000, 2000000 ];
+
+        for &time_ms in times.iter() {
+            wheel.push(time_ms, time_ms);
+        }
+
+        // Peeking returns exactly the items which would be popped, and leaves them in place
+        for &now_ms in [ 0, 64, 99, 4096, 70000, 2000000 ].iter() {
+            let mut peeked = wheel.peek_expired(now_ms).copied().collect::<Vec<_>>();
+            peeked.sort();
+
+            let expected = times.iter().copied().filter(|&time_ms| time_ms <= now_ms).collect::<Vec<_>>();
+            assert_eq!(peeked, expected);
+        }
+
+        assert_eq!(pop_all(&mut wheel, 4096), vec![ 0, 1, 5, 63, 64, 65, 100, 4095, 4096 ]);
+        assert_eq!(wheel.peek_expired(4096).count(), 0);
+
+        // Items which have been cascaded, or pushed in the past, are peeked as well
+        wheel.push(10, 10);
+        assert_eq!(wheel.peek_expired(4096).copied().collect::<Vec<_>>(), vec![ 10 ]);
+        assert_eq!(wheel.peek_expired(300000).copied().collect::<Vec<_>>(), vec![ 10, 5000, 300000 ]);
+    }
+
+    #[test]
+    fn beyond_max_span() {
+        let mut wheel = TimerWheel::new();
+
+        let time_ms = MAX_SPAN_MS * 3 + 7;
+        wheel.push(time_ms, time_ms);
+
+        assert_eq!(pop_all(&mut wheel, MAX_SPAN_MS), vec![]);
+        assert_eq!(pop_all(&mut wheel, MAX_SPAN_MS * 2), vec![]);
+        assert_eq!(pop_all(&mut wheel, time_ms - 1), vec![]);
+        assert_eq!(pop_all(&mut wheel, time_ms), vec![ time_ms ]);
+
+        // Scheduling across the end of a rotation
+        assert_eq!(pop_all(&mut wheel, MAX_SPAN_MS * 4 - 3), vec![]);
+
+        let time_ms = MAX_SPAN_MS * 6 + 11;
+        wheel.push(time_ms, time_ms);
+        wheel.push(MAX_SPAN_MS * 4 + 1, 0);
+
+        assert_eq!(pop_all(&mut wheel, MAX_SPAN_MS * 4), vec![]);
+        assert_eq!(pop_all(&mut wheel, MAX_SPAN_MS * 4 + 1), vec![ 0 ]);
+        assert_eq!(pop_all(&mut wheel, MAX_SPAN_MS * 5 + 12345), vec![]);
+        assert_eq!(pop_all(&mut wheel, time_ms - 1), vec![]);
+        assert_eq!(pop_all(&mut wheel, time_ms), vec![ time_ms ]);
+    }
+}
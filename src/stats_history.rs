@@ -0,0 +1,95 @@
+
+use std::collections::VecDeque;
+
+/// A sample of connection metrics, taken periodically when a stats history is enabled (see
+/// [`EndpointConfig::stats_sample_interval_ms`](crate::EndpointConfig#structfield.stats_sample_interval_ms)).
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct StatsSample {
+    /// The time at which this sample was taken, in milliseconds since the connection was
+    /// established.
+    pub time_ms: u64,
+    /// The estimated round-trip time (RTT) in seconds, if computed.
+    pub rtt_s: Option<f64>,
+    /// The loss event rate most recently reported by the remote host.
+    pub loss_rate: f64,
+    /// The send rate allowed by congestion control, in bytes per second.
+    pub send_rate: f64,
+    /// The combined size of all outstanding packets, in bytes.
+    pub send_buffer_size: usize,
+    /// The rate at which received packet data was delivered to the application since the previous
+    /// sample, in bytes per second.
+    pub goodput: f64,
+}
+
+/// A fixed-size history of [`StatsSample`] objects. Once full, each new sample replaces the oldest
+/// sample.
+#[derive(Clone,Debug)]
+pub struct StatsHistory {
+    samples: VecDeque<StatsSample>,
+    capacity: usize,
+}
+
+impl StatsHistory {
+    pub(crate) fn new(capacity: usize) -> Self {
+        debug_assert!(capacity > 0);
+
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub(crate) fn push(&mut self, sample: StatsSample) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(sample);
+    }
+
+    /// Returns the maximum number of samples retained.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of samples currently retained.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns `true` if no samples have been taken.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns the most recent sample, if any.
+    pub fn latest(&self) -> Option<&StatsSample> {
+        self.samples.back()
+    }
+
+    /// Returns an iterator over all retained samples, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &StatsSample> {
+        self.samples.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_replacement() {
+        let mut history = StatsHistory::new(3);
+
+        assert_eq!(history.is_empty(), true);
+        assert_eq!(history.latest(), None);
+
+        for time_ms in 0 .. 5 {
+            history.push(StatsSample { time_ms, .. Default::default() });
+        }
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.latest().unwrap().time_ms, 4);
+        assert_eq!(history.iter().map(|sample| sample.time_ms).collect::<Vec<_>>(), vec![ 2, 3, 4 ]);
+    }
+}
@@ -0,0 +1,396 @@
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::server::ClientId;
+use crate::server::Server;
+use crate::Transport;
+use crate::SendMode;
+use crate::MAX_CHANNEL_COUNT;
+
+// The longest interval between steps for which send budget is accumulated, so that a stalled
+// application does not produce a burst of chunks once it resumes
+const MAX_STEP_INTERVAL_MS: u64 = 100;
+
+/// Identifies a transfer started by a [`TransferScheduler`].
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub struct TransferId(u64);
+
+/// Describes the sending state of a transfer.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum TransferState {
+    /// The transfer is waiting for earlier transfers to the same client to complete.
+    Queued,
+    /// The transfer is being sent.
+    Active,
+    /// The transfer has been paused because the client's send buffer is full, i.e. the
+    /// connection cannot currently accept more data.
+    Paused,
+}
+
+/// Reports the progress of a transfer. See [`TransferScheduler::progress()`].
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct TransferProgress {
+    /// The current state of the transfer.
+    pub state: TransferState,
+    /// The number of bytes which have been enqueued for sending so far.
+    pub sent_bytes: usize,
+    /// The total size of the transfer, in bytes.
+    pub total_bytes: usize,
+}
+
+/// Signals the end of a transfer. See [`TransferScheduler::step()`].
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum TransferEvent {
+    /// All data of the transfer has been enqueued for sending. Because chunks are sent reliably,
+    /// they will be delivered unless the connection fails.
+    Complete(ClientId, TransferId),
+    /// The transfer was abandoned because the client is no longer connected.
+    Aborted(ClientId, TransferId),
+}
+
+struct Transfer {
+    id: TransferId,
+    data: Arc<[u8]>,
+    channel_id: usize,
+    sent_bytes: usize,
+    paused: bool,
+}
+
+impl Transfer {
+    fn remaining_bytes(&self) -> usize {
+        self.data.len() - self.sent_bytes
+    }
+}
+
+struct PeerEntry {
+    // Transfers to this peer, in the order they will be sent
+    transfers: VecDeque<Transfer>,
+    // Bytes allocated to this peer which have not yet been spent on a chunk
+    credit: usize,
+}
+
+/// Schedules large reliable transfers (e.g. asset downloads) to many clients of a [`Server`].
+///
+/// Each transfer is split into chunks of at most `chunk_size` bytes, which are sent in order as
+/// [`Reliable`](SendMode::Reliable) packets on the transfer's channel. The receiving application
+/// may therefore reassemble a transfer by concatenating the packets received on that channel.
+/// Transfers to the same client are sent one after another, in the order they were started.
+///
+/// The scheduler limits the combined rate of all transfers to a given maximum, and divides that
+/// rate among clients with max-min fairness: clients which cannot use their full share (because
+/// their connection is congested, or because little data remains) yield the remainder to the
+/// others. A client's transfer is paused while its [send
+/// buffer](crate::server::RemoteClient::send_buffer_size) exceeds `max_buffer_size`, and resumes
+/// once enough data has been acknowledged.
+///
+/// ```
+/// let mut server = uflow::server::Server::bind("127.0.0.1:0", Default::default()).unwrap();
+/// let mut scheduler = uflow::streaming::TransferScheduler::new(1_000_000, 1024, 65536);
+///
+/// let asset: std::sync::Arc<[u8]> = vec![0; 100_000].into();
+///
+/// for event in server.step() {
+///     if let uflow::server::Event::Connect(client_id) = event {
+///         scheduler.start(client_id, std::sync::Arc::clone(&asset), 0);
+///     }
+/// }
+///
+/// // Call once per server step, prior to flushing
+/// for event in scheduler.step(&mut server) {
+///     // ...
+/// }
+/// ```
+pub struct TransferScheduler {
+    peers: HashMap<ClientId, PeerEntry>,
+    max_send_rate: usize,
+    chunk_size: usize,
+    max_buffer_size: usize,
+    next_id: u64,
+    last_step_ms: Option<u64>,
+}
+
+impl TransferScheduler {
+    /// Creates a new scheduler which sends at most `max_send_rate` bytes per second in total,
+    /// using chunks of at most `chunk_size` bytes, and which pauses transfers to any client whose
+    /// send buffer exceeds `max_buffer_size` bytes.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `max_send_rate`, `chunk_size`, or `max_buffer_size` is zero.
+    /// Sending a chunk will panic if `chunk_size` exceeds the server's [maximum packet
+    /// size](crate::EndpointConfig#structfield.max_packet_size).
+    pub fn new(max_send_rate: usize, chunk_size: usize, max_buffer_size: usize) -> Self {
+        assert!(max_send_rate > 0, "invalid maximum send rate");
+        assert!(chunk_size > 0, "invalid chunk size");
+        assert!(max_buffer_size > 0, "invalid maximum buffer size");
+
+        Self {
+            peers: HashMap::new(),
+            max_send_rate,
+            chunk_size,
+            max_buffer_size,
+            next_id: 0,
+            last_step_ms: None,
+        }
+    }
+
+    /// Starts a transfer of the given data to the given client on the given channel, and returns
+    /// an ID which identifies the transfer. The data is shared, so the same asset may be sent to
+    /// many clients without copying it for each.
+    ///
+    /// # Error Handling
+    ///
+    /// This function will panic if `channel_id` does not refer to a valid channel (i.e. if
+    /// `channel_id >= MAX_CHANNEL_COUNT`). Sending a chunk will panic if the channel is not
+    /// available to the client (see
+    /// [`RemoteClient::channel_count()`](crate::server::RemoteClient::channel_count)).
+    pub fn start(&mut self, client_id: ClientId, data: Arc<[u8]>, channel_id: usize) -> TransferId {
+        assert!(channel_id < MAX_CHANNEL_COUNT,
+                "start failed: channel ID {} is invalid",
+                channel_id);
+
+        let id = TransferId(self.next_id);
+        self.next_id += 1;
+
+        let entry = self.peers.entry(client_id).or_insert_with(|| PeerEntry {
+            transfers: VecDeque::new(),
+            credit: 0,
+        });
+
+        entry.transfers.push_back(Transfer {
+            id,
+            data,
+            channel_id,
+            sent_bytes: 0,
+            paused: false,
+        });
+
+        id
+    }
+
+    /// Cancels the given transfer. Chunks which have already been sent will still be delivered.
+    /// Returns `true` if the transfer was found.
+    pub fn cancel(&mut self, id: TransferId) -> bool {
+        for entry in self.peers.values_mut() {
+            if let Some(idx) = entry.transfers.iter().position(|transfer| transfer.id == id) {
+                entry.transfers.remove(idx);
+
+                if idx == 0 {
+                    entry.credit = 0;
+                }
+
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Returns the progress of the given transfer. Returns `None` if no such transfer exists, or if
+    /// the transfer has ended.
+    pub fn progress(&self, id: TransferId) -> Option<TransferProgress> {
+        for entry in self.peers.values() {
+            if let Some(idx) = entry.transfers.iter().position(|transfer| transfer.id == id) {
+                let transfer = &entry.transfers[idx];
+
+                let state = if idx > 0 {
+                    TransferState::Queued
+                } else if transfer.paused {
+                    TransferState::Paused
+                } else {
+                    TransferState::Active
+                };
+
+                return Some(TransferProgress {
+                    state,
+                    sent_bytes: transfer.sent_bytes,
+                    total_bytes: transfer.data.len(),
+                });
+            }
+        }
+
+        None
+    }
+
+    /// Returns the number of transfers which have not yet ended.
+    pub fn transfer_count(&self) -> usize {
+        self.peers.values().map(|entry| entry.transfers.len()).sum()
+    }
+
+    /// Enqueues as many chunks as the current send budget allows to the clients of the given
+    /// server, and returns events for any transfers which have ended. The send budget is
+    /// accumulated according to the server's time (see [`Server::time_ms()`]), so this function
+    /// should be called regularly, before calling [`Server::flush()`].
+    pub fn step<T: Transport>(&mut self, server: &mut Server<T>) -> Vec<TransferEvent> {
+        let mut events = Vec::new();
+
+        let now_ms = server.time_ms();
+        let elapsed_ms = match self.last_step_ms {
+            Some(last_step_ms) => now_ms.saturating_sub(last_step_ms).min(MAX_STEP_INTERVAL_MS),
+            None => 0,
+        };
+        self.last_step_ms = Some(now_ms);
+
+        let budget = (self.max_send_rate as u64 * elapsed_ms / 1000) as usize;
+
+        // Abort transfers to clients which have gone, and determine how much each remaining client
+        // could accept
+        let mut demands = Vec::new();
+        let max_buffer_size = self.max_buffer_size;
+
+        self.peers.retain(|&client_id, entry| {
+            let headroom = match server.client(client_id) {
+                Some(client_rc) if client_rc.borrow().is_active() => {
+                    let client = client_rc.borrow();
+
+                    if client.is_send_blocked() {
+                        0
+                    } else {
+                        max_buffer_size.saturating_sub(client.send_buffer_size())
+                    }
+                }
+                _ => {
+                    for transfer in entry.transfers.drain(..) {
+                        events.push(TransferEvent::Aborted(client_id, transfer.id));
+                    }
+                    return false;
+                }
+            };
+
+            if let Some(transfer) = entry.transfers.front() {
+                let demand = transfer.remaining_bytes().min(headroom).saturating_sub(entry.credit);
+
+                if demand > 0 {
+                    demands.push((client_id, demand));
+                }
+
+                true
+            } else {
+                false
+            }
+        });
+
+        for (client_id, allocation) in max_min_allocate(budget, demands) {
+            if let Some(entry) = self.peers.get_mut(&client_id) {
+                entry.credit += allocation;
+            }
+        }
+
+        // Spend each client's credit on whole chunks
+        for (&client_id, entry) in self.peers.iter_mut() {
+            let client_rc = server.client(client_id).expect("client removed during step");
+            let mut client = client_rc.borrow_mut();
+
+            let mut headroom = self.max_buffer_size.saturating_sub(client.send_buffer_size());
+
+            while let Some(transfer) = entry.transfers.front_mut() {
+                let chunk_len = transfer.remaining_bytes().min(self.chunk_size);
+
+                transfer.paused = headroom < chunk_len || client.is_send_blocked();
+
+                if transfer.paused || entry.credit < chunk_len {
+                    break;
+                }
+
+                let chunk = &transfer.data[transfer.sent_bytes .. transfer.sent_bytes + chunk_len];
+                client.send(chunk.into(), transfer.channel_id, SendMode::Reliable);
+
+                transfer.sent_bytes += chunk_len;
+                entry.credit -= chunk_len;
+                headroom = headroom.saturating_sub(chunk_len);
+
+                if transfer.remaining_bytes() == 0 {
+                    events.push(TransferEvent::Complete(client_id, transfer.id));
+                    entry.transfers.pop_front();
+                    entry.credit = 0;
+                }
+            }
+        }
+
+        self.peers.retain(|_, entry| !entry.transfers.is_empty());
+
+        events
+    }
+}
+
+// Divides the budget among the given demands such that no demand receives less than an equal share
+// of the budget unless it is fully satisfied (max-min fairness)
+fn max_min_allocate<K>(mut budget: usize, mut demands: Vec<(K, usize)>) -> Vec<(K, usize)> {
+    demands.sort_by_key(|&(_, demand)| demand);
+
+    let mut remaining_num = demands.len();
+
+    demands.into_iter().map(|(key, demand)| {
+        let share = budget / remaining_num;
+        let allocation = demand.min(share);
+
+        budget -= allocation;
+        remaining_num -= 1;
+
+        (key, allocation)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_min_fairness() {
+        // Equal shares when all demands exceed them
+        assert_eq!(max_min_allocate(300, vec![ (0, 1000), (1, 500), (2, 200) ]),
+                   vec![ (2, 100), (1, 100), (0, 100) ]);
+
+        // Unused shares are redistributed
+        assert_eq!(max_min_allocate(300, vec![ (0, 1000), (1, 20), (2, 200) ]),
+                   vec![ (1, 20), (2, 140), (0, 140) ]);
+
+        assert_eq!(max_min_allocate(300, vec![ (0, 1000), (1, 20), (2, 50) ]),
+                   vec![ (1, 20), (2, 50), (0, 230) ]);
+
+        // All demands satisfied
+        assert_eq!(max_min_allocate(300, vec![ (0, 10), (1, 20) ]),
+                   vec![ (0, 10), (1, 20) ]);
+
+        assert_eq!(max_min_allocate(300, Vec::<(u32, usize)>::new()), vec![]);
+    }
+
+    #[test]
+    fn transfer_bookkeeping() {
+        let client_a = ClientId(1);
+        let client_b = ClientId(2);
+
+        let mut scheduler = TransferScheduler::new(1000, 100, 1000);
+
+        let data: Arc<[u8]> = vec![0; 250].into();
+
+        let id_0 = scheduler.start(client_a, Arc::clone(&data), 0);
+        let id_1 = scheduler.start(client_a, Arc::clone(&data), 0);
+        let id_2 = scheduler.start(client_b, Arc::clone(&data), 1);
+
+        assert_eq!(scheduler.transfer_count(), 3);
+
+        assert_eq!(scheduler.progress(id_0), Some(TransferProgress {
+            state: TransferState::Active, sent_bytes: 0, total_bytes: 250 }));
+        assert_eq!(scheduler.progress(id_1), Some(TransferProgress {
+            state: TransferState::Queued, sent_bytes: 0, total_bytes: 250 }));
+
+        assert_eq!(scheduler.cancel(id_0), true);
+        assert_eq!(scheduler.cancel(id_0), false);
+        assert_eq!(scheduler.progress(id_0), None);
+        assert_eq!(scheduler.progress(id_1).unwrap().state, TransferState::Active);
+
+        // Transfers to clients which are not connected are aborted
+        let mut server = Server::bind("127.0.0.1:0", Default::default()).unwrap();
+
+        let mut events = scheduler.step(&mut server);
+        events.sort_by_key(|event| match event {
+            TransferEvent::Complete(_, id) | TransferEvent::Aborted(_, id) => id.0,
+        });
+
+        assert_eq!(events, vec![ TransferEvent::Aborted(client_a, id_1), TransferEvent::Aborted(client_b, id_2) ]);
+        assert_eq!(scheduler.transfer_count(), 0);
+    }
+}
@@ -0,0 +1,422 @@
+#[cfg(feature = "server")]
+use crate::half_connection;
+use crate::Clock;
+use crate::SystemClock;
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io;
+use std::net;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time;
+
+// First port assigned to endpoints bound with port 0
+const MEMORY_EPHEMERAL_PORT_BASE: u16 = 49152;
+
+/// A datagram transport over which `uflow` frames are exchanged.
+///
+/// [`Server`](crate::server::Server) and [`Client`](crate::client::Client) communicate over a
+/// non-blocking UDP socket by default, but may be created with any other transport via
+/// `with_transport()`. A transport must preserve datagram boundaries, and must not block: if no
+/// datagram is available, `recv_from()` should return an error of kind
+/// [`WouldBlock`](io::ErrorKind::WouldBlock), and if a datagram cannot be sent immediately,
+/// `send_to()` should return an error of the same kind.
+///
+/// Peers are identified by socket addresses. Transports whose peers are not naturally identified
+/// this way (e.g. Unix domain sockets) should map each peer to a unique address.
+pub trait Transport {
+    /// Sends a single datagram to the given address, returning the number of bytes sent.
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize>;
+
+    /// Receives a single datagram, returning its size and the address of its sender.
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)>;
+
+    /// Returns the local address of this transport.
+    fn local_addr(&self) -> io::Result<net::SocketAddr>;
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        (**self).send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        (**self).recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        (**self).local_addr()
+    }
+}
+
+impl Transport for net::UdpSocket {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        net::UdpSocket::send_to(self, buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        net::UdpSocket::recv_from(self, buf)
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        net::UdpSocket::local_addr(self)
+    }
+}
+
+/// A UDP socket bound to an IPv6 address which also exchanges datagrams with IPv4 peers, so that a
+/// single server may accept both.
+///
+/// The operating system reports IPv4 peers of such a socket by their IPv4-mapped IPv6 addresses
+/// (e.g. `[::ffff:192.0.2.1]:1000`). A `DualStack` transport reports them by their plain IPv4
+/// addresses instead, and maps IPv4 destinations back when sending, so that every peer is
+/// identified by the same address it would have over an IPv4 socket.
+///
+/// *Note*: The socket must not be restricted to IPv6 traffic (`IPV6_V6ONLY`). This is the default on
+/// most Unix systems, but not on Windows, where a socket configured beforehand should be passed to
+/// [`from_socket()`](Self::from_socket) instead.
+pub struct DualStack {
+    socket: net::UdpSocket,
+}
+
+impl DualStack {
+    /// Opens a non-blocking UDP socket bound to the unspecified IPv6 address (`[::]`) and the
+    /// given port. If the port is 0, an ephemeral port is assigned.
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let socket = net::UdpSocket::bind((net::Ipv6Addr::UNSPECIFIED, port))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self { socket })
+    }
+
+    /// Wraps an existing non-blocking UDP socket.
+    ///
+    /// # Error Handling
+    ///
+    /// Returns an error of kind [`InvalidInput`](io::ErrorKind::InvalidInput) if the socket is not
+    /// bound to an IPv6 address, and forwards any error resulting from querying its local address.
+    pub fn from_socket(socket: net::UdpSocket) -> io::Result<Self> {
+        if !socket.local_addr()?.is_ipv6() {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        Ok(Self { socket })
+    }
+
+    /// Returns the underlying socket.
+    pub fn socket(&self) -> &net::UdpSocket {
+        &self.socket
+    }
+}
+
+impl Transport for DualStack {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        self.socket.send_to(buf, ipv4_mapped_address(addr))
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        let (size, addr) = self.socket.recv_from(buf)?;
+        Ok((size, canonical_address(addr)))
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+
+// Replaces an IPv4-mapped IPv6 address with the IPv4 address it represents
+fn canonical_address(addr: net::SocketAddr) -> net::SocketAddr {
+    match addr {
+        net::SocketAddr::V6(v6) => match v6.ip().to_ipv4_mapped() {
+            Some(ip) => net::SocketAddr::new(net::IpAddr::V4(ip), v6.port()),
+            None => addr,
+        },
+        net::SocketAddr::V4(_) => addr,
+    }
+}
+
+// Replaces an IPv4 address with its IPv4-mapped IPv6 equivalent
+fn ipv4_mapped_address(addr: net::SocketAddr) -> net::SocketAddr {
+    match addr {
+        net::SocketAddr::V4(v4) => net::SocketAddr::new(net::IpAddr::V6(v4.ip().to_ipv6_mapped()), v4.port()),
+        net::SocketAddr::V6(_) => addr,
+    }
+}
+
+struct MemoryDatagram {
+    delivery_time: time::Duration,
+    data: Box<[u8]>,
+    source: net::SocketAddr,
+}
+
+struct MemoryNetworkState {
+    clock: Arc<dyn Clock>,
+    delay: time::Duration,
+    queues: HashMap<net::SocketAddr, VecDeque<MemoryDatagram>>,
+}
+
+/// A set of in-process endpoints which exchange datagrams through queues, so that clients and
+/// servers may communicate without real sockets or ports, e.g. in tests and examples.
+///
+/// Endpoints are created by calling [`bind()`](Self::bind). Datagrams are delivered in order and
+/// without loss, after an optional delay, and datagrams sent to an address which is not bound are
+/// silently discarded. Clones of a network share the same endpoints.
+///
+/// ```
+/// let network = uflow::transport::MemoryNetwork::new();
+///
+/// let server_transport = network.bind("10.0.0.1:1000".parse().unwrap()).unwrap();
+/// let client_transport = network.bind("10.0.0.2:1000".parse().unwrap()).unwrap();
+///
+/// let server_address = server_transport.address();
+///
+/// let mut server = uflow::server::Server::with_transport(server_transport, Default::default());
+/// let mut client = uflow::client::Client::with_transport(client_transport, server_address, Default::default()).unwrap();
+/// ```
+#[derive(Clone)]
+pub struct MemoryNetwork {
+    state: Arc<Mutex<MemoryNetworkState>>,
+}
+
+impl MemoryNetwork {
+    /// Creates an empty network whose delays are measured by the system clock.
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock::new()))
+    }
+
+    /// Creates an empty network whose delays are measured by the given clock. Endpoints driven by a
+    /// [`VirtualClock`](crate::VirtualClock) should share their clock with the network.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MemoryNetworkState {
+                clock,
+                delay: time::Duration::ZERO,
+                queues: HashMap::new(),
+            })),
+        }
+    }
+
+    /// Sets the delay between sending a datagram and its becoming available to the receiver. The
+    /// delay applies to datagrams sent after this call.
+    pub fn set_delay(&self, delay: time::Duration) {
+        self.state.lock().unwrap().delay = delay;
+    }
+
+    /// Returns a new endpoint with the given address. If the address has port 0, an unused port is
+    /// assigned instead.
+    ///
+    /// # Error Handling
+    ///
+    /// Returns an error of kind [`AddrInUse`](io::ErrorKind::AddrInUse) if an endpoint with the
+    /// given address already exists.
+    pub fn bind(&self, address: net::SocketAddr) -> io::Result<Memory> {
+        let mut state = self.state.lock().unwrap();
+
+        let address = if address.port() == 0 {
+            (MEMORY_EPHEMERAL_PORT_BASE ..= u16::MAX)
+                .map(|port| net::SocketAddr::new(address.ip(), port))
+                .find(|address| !state.queues.contains_key(address))
+                .ok_or_else(|| io::Error::from(io::ErrorKind::AddrInUse))?
+        } else {
+            address
+        };
+
+        if state.queues.contains_key(&address) {
+            return Err(io::ErrorKind::AddrInUse.into());
+        }
+
+        state.queues.insert(address, VecDeque::new());
+
+        Ok(Memory {
+            network: self.clone(),
+            address,
+        })
+    }
+}
+
+impl Default for MemoryNetwork {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An endpoint of a [`MemoryNetwork`]. The endpoint's address is released when it is dropped.
+pub struct Memory {
+    network: MemoryNetwork,
+    address: net::SocketAddr,
+}
+
+impl Memory {
+    /// Returns two endpoints of a new network, with addresses `127.0.0.1:1` and `127.0.0.1:2`, and
+    /// no delay.
+    pub fn pair() -> (Memory, Memory) {
+        let network = MemoryNetwork::new();
+
+        let a = network.bind(net::SocketAddr::from(([ 127, 0, 0, 1 ], 1))).unwrap();
+        let b = network.bind(net::SocketAddr::from(([ 127, 0, 0, 1 ], 2))).unwrap();
+
+        (a, b)
+    }
+
+    /// Returns the address of this endpoint.
+    pub fn address(&self) -> net::SocketAddr {
+        self.address
+    }
+}
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.network.state.lock() {
+            state.queues.remove(&self.address);
+        }
+    }
+}
+
+impl Transport for Memory {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        let mut state = self.network.state.lock().unwrap();
+
+        let delivery_time = state.clock.now() + state.delay;
+
+        if let Some(queue) = state.queues.get_mut(&addr) {
+            queue.push_back(MemoryDatagram {
+                delivery_time,
+                data: buf.into(),
+                source: self.address,
+            });
+        }
+
+        Ok(buf.len())
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        let mut state = self.network.state.lock().unwrap();
+
+        let now = state.clock.now();
+        let queue = state.queues.get_mut(&self.address).expect("memory endpoint not bound");
+
+        match queue.front() {
+            Some(datagram) if datagram.delivery_time <= now => {
+                let datagram = queue.pop_front().unwrap();
+
+                // Like UDP, excess bytes are discarded if the buffer is too small
+                let size = datagram.data.len().min(buf.len());
+                buf[.. size].copy_from_slice(&datagram.data[.. size]);
+
+                Ok((size, datagram.source))
+            }
+            _ => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        Ok(self.address)
+    }
+}
+
+#[cfg(feature = "server")]
+pub(crate) struct TransportFrameSink<'a, T: Transport> {
+    transport: &'a T,
+    address: net::SocketAddr,
+}
+
+#[cfg(feature = "server")]
+impl<'a, T: Transport> TransportFrameSink<'a, T> {
+    pub fn new(transport: &'a T, address: net::SocketAddr) -> Self {
+        Self {
+            transport,
+            address,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl<'a, T: Transport> half_connection::FrameSink for TransportFrameSink<'a, T> {
+    fn send(&mut self, frame_data: &[u8]) -> io::Result<()> {
+        self.transport.send_to(frame_data, self.address).map(|_| ())
+    }
+}
+
+// Collects frames to be sent later, e.g. as a batch
+#[cfg(feature = "parallel")]
+pub(crate) struct CollectFrameSink {
+    pub frames: Vec<Box<[u8]>>,
+}
+
+#[cfg(feature = "parallel")]
+impl CollectFrameSink {
+    pub fn new() -> Self {
+        Self {
+            frames: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl half_connection::FrameSink for CollectFrameSink {
+    fn send(&mut self, frame_data: &[u8]) -> io::Result<()> {
+        self.frames.push(frame_data.into());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::VirtualClock;
+
+    fn recv(endpoint: &Memory) -> Option<(Box<[u8]>, net::SocketAddr)> {
+        let mut buf = [0; 16];
+        endpoint.recv_from(&mut buf).ok().map(|(size, source)| (buf[.. size].into(), source))
+    }
+
+    #[test]
+    fn dual_stack_addresses() {
+        let v4: net::SocketAddr = "192.0.2.1:1000".parse().unwrap();
+        let mapped: net::SocketAddr = "[::ffff:192.0.2.1]:1000".parse().unwrap();
+        let v6: net::SocketAddr = "[2001:db8::1]:1000".parse().unwrap();
+
+        assert_eq!(ipv4_mapped_address(v4), mapped);
+        assert_eq!(ipv4_mapped_address(v6), v6);
+
+        assert_eq!(canonical_address(mapped), v4);
+        assert_eq!(canonical_address(v4), v4);
+        assert_eq!(canonical_address(v6), v6);
+    }
+
+    #[test]
+    fn memory_delay() {
+        let clock = VirtualClock::new();
+        let network = MemoryNetwork::with_clock(Arc::new(clock.clone()));
+
+        let a = network.bind("10.0.0.1:1".parse().unwrap()).unwrap();
+        let b = network.bind("10.0.0.2:0".parse().unwrap()).unwrap();
+
+        assert_eq!(b.address(), "10.0.0.2:49152".parse().unwrap());
+        assert_eq!(network.bind(a.address()).err().map(|err| err.kind()), Some(io::ErrorKind::AddrInUse));
+
+        network.set_delay(time::Duration::from_millis(50));
+
+        a.send_to(&[ 0 ], b.address()).unwrap();
+        clock.advance(time::Duration::from_millis(10));
+        a.send_to(&[ 1 ], b.address()).unwrap();
+
+        clock.advance(time::Duration::from_millis(39));
+        assert_eq!(recv(&b), None);
+
+        clock.advance(time::Duration::from_millis(1));
+        assert_eq!(recv(&b), Some(([ 0 ].into(), a.address())));
+        assert_eq!(recv(&b), None);
+
+        clock.advance(time::Duration::from_millis(10));
+        assert_eq!(recv(&b), Some(([ 1 ].into(), a.address())));
+
+        // Dropped endpoints release their address, and no longer receive datagrams
+        let b_address = b.address();
+        drop(b);
+
+        a.send_to(&[ 2 ], b_address).unwrap();
+        assert!(network.bind(b_address).is_ok());
+    }
+}
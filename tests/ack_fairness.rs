@@ -0,0 +1,87 @@
+mod common;
+
+use std::cell::Cell;
+use std::io;
+use std::net;
+use std::rc::Rc;
+
+use uflow::Transport;
+
+use common::{ Sim, STEP_INTERVAL };
+
+// A transport which accepts a limited number of datagrams between refills, as though its send
+// buffer were persistently full
+struct Throttled {
+    inner: uflow::transport::Memory,
+    budget: Rc<Cell<usize>>,
+}
+
+impl Transport for Throttled {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        match self.budget.get() {
+            0 => Err(io::ErrorKind::WouldBlock.into()),
+            budget => {
+                self.budget.set(budget - 1);
+                self.inner.send_to(buf, addr)
+            }
+        }
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        self.inner.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+// While a server's uplink is saturated by a broadcast, every client should continue to receive
+// timely acknowledgements of the data it sends.
+#[test]
+fn ack_fairness() {
+    let sim = Sim::new();
+
+    let budget = Rc::new(Cell::new(usize::MAX));
+    let server_transport = Throttled { inner: sim.bind(common::SERVER_ADDRESS), budget: Rc::clone(&budget) };
+
+    let mut server = uflow::server::Server::with_transport(server_transport, sim.server_config());
+
+    let client_addresses: Vec<String> = (0 .. 8).map(|i| format!("10.0.0.2:{}", 2000 + i)).collect();
+
+    let mut clients = client_addresses.iter().map(|address| {
+        sim.client(address, sim.client_config())
+    }).collect::<Vec<_>>();
+
+    for _ in 0 .. 10 {
+        sim.step_all(&mut server, &mut clients, common::ignore, |_, _, _| ());
+    }
+
+    assert!(clients.iter().all(|client| client.is_active()));
+
+    let mut blocked_count = 0;
+
+    for _ in 0 .. 100 {
+        server.broadcast(vec![ 0; 1000 ].into(), 0, uflow::SendMode::Reliable);
+
+        for client in clients.iter_mut() {
+            client.send(vec![ 0; 100 ].into(), 0, uflow::SendMode::Reliable);
+        }
+
+        budget.set(10);
+
+        sim.step_all(&mut server, &mut clients, common::ignore, |_, _, _| ());
+
+        if server.is_send_blocked() {
+            blocked_count += 1;
+        }
+    }
+
+    assert!(blocked_count > 50);
+
+    for address in client_addresses.iter() {
+        let stats = server.client(common::client_id(&server, common::address(address))).unwrap().borrow().link_stats();
+        assert!(stats.ack_delay_ms.is_some());
+        assert!(stats.max_ack_delay_ms <= STEP_INTERVAL.as_millis() as u64);
+    }
+}
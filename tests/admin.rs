@@ -0,0 +1,94 @@
+use std::io::Read;
+use std::io::Write;
+use std::net;
+use std::thread;
+use std::time;
+
+// An admin request should be answered with a snapshot which includes each connected client.
+#[test]
+fn admin_snapshot() {
+    let mut server = uflow::server::Server::bind("127.0.0.1:5611", Default::default()).unwrap();
+    let mut client = uflow::client::Client::connect("127.0.0.1:5611", Default::default()).unwrap();
+
+    let mut admin = uflow::admin::AdminEndpoint::bind("127.0.0.1:0").unwrap();
+    let admin_addr = admin.local_address();
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(4000);
+
+    let is_connected = |server: &uflow::server::Server| server.clients().any(|client| client.borrow().is_active());
+
+    while !is_connected(&server) && time::Instant::now() < end_time {
+        for _ in server.step() {}
+        for _ in client.step() {}
+        thread::sleep(time::Duration::from_millis(50));
+    }
+
+    assert!(is_connected(&server));
+
+    let request_thread = thread::spawn(move || {
+        let mut stream = net::TcpStream::connect(admin_addr).unwrap();
+        stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    });
+
+    while !request_thread.is_finished() {
+        admin.serve(&server);
+        thread::sleep(time::Duration::from_millis(10));
+    }
+
+    let response = request_thread.join().unwrap();
+
+    assert!(response.starts_with("HTTP/1.0 200 OK\r\n"));
+    assert!(response.contains("\"connections\":[{"));
+
+    let client_port = client.local_address().port();
+    assert!(response.contains(&format!("\"address\":\"127.0.0.1:{}\",\"state\":\"active\"", client_port)));
+}
+
+// Idle and slow admin clients should not stall the caller of serve(), and a request which arrives
+// in pieces should still be answered.
+#[test]
+fn admin_nonblocking() {
+    let server = uflow::server::Server::bind("127.0.0.1:5612", Default::default()).unwrap();
+
+    let mut admin = uflow::admin::AdminEndpoint::bind("127.0.0.1:0").unwrap();
+    let admin_addr = admin.local_address();
+
+    let idle_streams = (0 .. 8).map(|_| net::TcpStream::connect(admin_addr).unwrap()).collect::<Vec<_>>();
+
+    let mut slow_stream = net::TcpStream::connect(admin_addr).unwrap();
+    slow_stream.write_all(b"GET / HT").unwrap();
+
+    let begin = time::Instant::now();
+
+    for _ in 0 .. 10 {
+        admin.serve(&server);
+    }
+
+    assert!(begin.elapsed() < time::Duration::from_millis(50));
+
+    slow_stream.write_all(b"TP/1.0\r\n\r\n").unwrap();
+    slow_stream.set_read_timeout(Some(time::Duration::from_millis(10))).unwrap();
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(500);
+    let mut response = Vec::new();
+
+    while time::Instant::now() < end_time {
+        admin.serve(&server);
+
+        let mut buf = [0; 4096];
+
+        match slow_stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => response.extend_from_slice(&buf[.. n]),
+            Err(_) => (),
+        }
+    }
+
+    assert!(String::from_utf8(response).unwrap().starts_with("HTTP/1.0 200 OK\r\n"));
+
+    drop(idle_streams);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::Sim;
+
+fn endpoint_config(app_latency: bool) -> uflow::EndpointConfig {
+    uflow::EndpointConfig {
+        app_latency,
+        .. Default::default()
+    }
+}
+
+// Exchanges data in both directions for a while, with each endpoint enabling application latency
+// reports as given, and returns the latencies reported by the server and the client,
+// respectively
+fn exchange(server_app_latency: bool, client_app_latency: bool) -> (Option<u64>, Option<u64>) {
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        endpoint_config: endpoint_config(server_app_latency),
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client("10.0.0.2:2000", uflow::client::Config {
+        endpoint_config: endpoint_config(client_app_latency),
+        .. sim.client_config()
+    });
+
+    // Set before the connection is established
+    client.set_app_latency_ms(12);
+
+    for step in 0 .. 50 {
+        // Each endpoint sends in turn, so that neither has to fit acknowledgements into its flush
+        // allocation before it has an RTT estimate
+        if step < 20 {
+            server.broadcast(vec![ 0 ].into(), 0, uflow::SendMode::Reliable);
+        } else {
+            client.send(vec![ 0 ].into(), 0, uflow::SendMode::Reliable);
+        }
+
+        sim.step(&mut server, &mut client, |server, event| {
+            if let uflow::server::Event::Connect(address) = event {
+                server.client(address).unwrap().borrow_mut().set_app_latency_ms(70000);
+            }
+        }, common::ignore_client);
+    }
+
+    assert!(client.is_active());
+
+    let server_reported = client.link_stats().remote_app_latency_ms;
+    let client_id = common::client_id(&server, client.local_address());
+    let client_reported = server.client(client_id).unwrap().borrow().link_stats().remote_app_latency_ms;
+
+    (server_reported, client_reported)
+}
+
+// Each endpoint should observe the application latency reported by the other, saturated to the
+// largest representable value.
+#[test]
+fn app_latency_reported() {
+    assert_eq!(exchange(true, true), (Some(u16::MAX as u64), Some(12)));
+}
+
+// Application latency should only be reported if both endpoints support it.
+#[test]
+fn app_latency_disabled() {
+    assert_eq!(exchange(false, true), (None, None));
+    assert_eq!(exchange(true, false), (None, None));
+}
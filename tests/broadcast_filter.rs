@@ -0,0 +1,72 @@
+mod common;
+
+use std::panic;
+
+use common::Sim;
+
+// Each client should receive the broadcast packet, a replacement, or nothing, as determined by the
+// filter, and an oversized replacement should panic without any client having been sent anything.
+#[test]
+fn broadcast_filtered() {
+    let sim = Sim::new();
+
+    let max_packet_size = uflow::EndpointConfig::default().max_packet_size;
+
+    let mut server = sim.server(sim.server_config());
+
+    let mut clients = (0 .. 3).map(|i| {
+        sim.client(&format!("10.0.0.2:{}", 2000 + i), sim.client_config())
+    }).collect::<Vec<_>>();
+
+    let addresses = clients.iter().map(|client| client.local_address()).collect::<Vec<_>>();
+
+    let mut received = vec![ Vec::new(); clients.len() ];
+
+    let mut step = |server: &mut common::Server, clients: &mut [common::Client]| {
+        sim.step_all(server, clients, common::expect_connect, |index, _, event| match event {
+            uflow::client::Event::Connect => (),
+            uflow::client::Event::Receive(data) => received[index].push(data),
+            other => panic!("unexpected event: {:?}", other),
+        });
+    };
+
+    for _ in 0 .. 10 {
+        step(&mut server, &mut clients);
+    }
+
+    let client_ids = addresses.iter().map(|&address| common::client_id(&server, address)).collect::<Vec<_>>();
+
+    // The last client's replacement is too large to send
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        server.broadcast_filtered([ 0 ].into(), 0, uflow::SendMode::Reliable, |client_id| {
+            if client_id == client_ids[2] {
+                uflow::server::BroadcastFilter::Transform(vec![ 0; max_packet_size + 1 ].into())
+            } else {
+                uflow::server::BroadcastFilter::Include
+            }
+        });
+    }));
+
+    assert!(result.is_err());
+
+    server.broadcast_filtered([ 1 ].into(), 0, uflow::SendMode::Reliable, |client_id| {
+        if client_id == client_ids[0] {
+            uflow::server::BroadcastFilter::Include
+        } else if client_id == client_ids[1] {
+            uflow::server::BroadcastFilter::Exclude
+        } else {
+            uflow::server::BroadcastFilter::Transform([ 2 ].into())
+        }
+    });
+
+    for _ in 0 .. 10 {
+        step(&mut server, &mut clients);
+    }
+
+    let expected: Vec<Vec<Box<[u8]>>> = vec![
+        vec![ [ 1 ].into() ],
+        vec![],
+        vec![ [ 2 ].into() ],
+    ];
+    assert_eq!(received, expected);
+}
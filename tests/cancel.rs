@@ -0,0 +1,51 @@
+mod common;
+
+use std::time;
+
+use common::Sim;
+
+// Packets canceled before being sent should never be received, whether they were sent before or
+// after the connection was established, and packets which have been sent cannot be canceled.
+#[test]
+fn cancel_queued() {
+    let sim = Sim::with_delay(time::Duration::from_millis(20));
+
+    let mut server = sim.server(sim.server_config());
+    let mut client = sim.client("10.0.0.2:1000", sim.client_config());
+
+    let first_handle = client.send([ 0 ].into(), 0, uflow::SendMode::Reliable);
+    let canceled_handle = client.send([ 1 ].into(), 0, uflow::SendMode::Reliable);
+    client.send([ 2 ].into(), 0, uflow::SendMode::Reliable);
+
+    assert!(client.cancel(canceled_handle));
+    assert!(!client.cancel(canceled_handle));
+
+    let mut server_received = Vec::new();
+    let mut client_received = Vec::new();
+
+    sim.run(100, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(address) => {
+            let mut remote_client = server.client(address).unwrap().borrow_mut();
+            let handle = remote_client.send([ 10 ].into(), 0, uflow::SendMode::Unreliable);
+            remote_client.send([ 11 ].into(), 0, uflow::SendMode::Unreliable);
+            assert!(remote_client.cancel(handle));
+        }
+        uflow::server::Event::Receive(_, data) => server_received.push(data[0]),
+        _ => (),
+    }, |client, event| match event {
+        uflow::client::Event::Connect => {
+            client.send([ 3 ].into(), 0, uflow::SendMode::Reliable);
+            let handle = client.send([ 4 ].into(), 0, uflow::SendMode::Reliable);
+            client.send([ 5 ].into(), 0, uflow::SendMode::Reliable);
+            assert!(client.cancel(handle));
+        }
+        uflow::client::Event::Receive(data) => client_received.push(data[0]),
+        _ => (),
+    });
+
+    assert_eq!(server_received, vec![ 0, 2, 3, 5 ]);
+    assert_eq!(client_received, vec![ 11 ]);
+
+    assert!(!client.cancel(first_handle));
+    assert_eq!(client.channel_stats(0).sender_dropped, 1);
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use common::Sim;
+
+type Packets = Vec<Box<[u8]>>;
+
+fn endpoint_config(channel_count: usize) -> uflow::EndpointConfig {
+    uflow::EndpointConfig {
+        channel_count,
+        .. Default::default()
+    }
+}
+
+// Sends a packet from the client on each of its channels, which the server echoes back on the
+// same channel. Returns the packets received by the server and by the client, and any client
+// errors.
+fn echo(server_channels: usize, client_channels: usize) -> (Packets, Packets, Vec<uflow::client::ErrorType>) {
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        endpoint_config: endpoint_config(server_channels),
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client("10.0.0.2:2000", uflow::client::Config {
+        endpoint_config: endpoint_config(client_channels),
+        .. sim.client_config()
+    });
+
+    for channel_id in 0 .. client_channels {
+        client.send(vec![ channel_id as u8 ].into(), channel_id, uflow::SendMode::Reliable);
+    }
+
+    let mut server_received = Vec::new();
+    let mut client_received = Vec::new();
+    let mut client_errors = Vec::new();
+
+    sim.run(50, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(address) => {
+            assert_eq!(server.client(address).unwrap().borrow().channel_count(), client_channels);
+        }
+        uflow::server::Event::Receive(address, data) => {
+            let channel_id = data[0] as usize;
+            server.client(address).unwrap().borrow_mut().send(data.clone(), channel_id, uflow::SendMode::Reliable);
+            server_received.push(data);
+        }
+        other => panic!("unexpected event: {:?}", other),
+    }, |_, event| match event {
+        uflow::client::Event::Connect => (),
+        uflow::client::Event::Receive(data) => client_received.push(data),
+        uflow::client::Event::Error(error) => client_errors.push(error),
+        uflow::client::Event::SendFailed(_) => (),
+        other => panic!("unexpected event: {:?}", other),
+    });
+
+    if client_errors.is_empty() {
+        assert_eq!(client.channel_stats(client_channels - 1).delivered, 1);
+    }
+
+    (server_received, client_received, client_errors)
+}
+
+fn expected_packets(channel_count: usize) -> Packets {
+    (0 .. channel_count).map(|channel_id| vec![ channel_id as u8 ].into()).collect()
+}
+
+// A client and server with the same small channel count should exchange packets on every channel.
+#[test]
+fn channel_count_small() {
+    let (server_received, client_received, client_errors) = echo(4, 4);
+
+    assert_eq!(server_received, expected_packets(4));
+    assert_eq!(client_received, expected_packets(4));
+    assert!(client_errors.is_empty());
+}
+
+// Channels beyond the default channel count should be usable once negotiated, up to the maximum
+// channel count.
+#[test]
+fn channel_count_wide() {
+    for &channel_count in [ 200, uflow::MAX_CHANNEL_COUNT ].iter() {
+        let (server_received, client_received, client_errors) = echo(uflow::MAX_CHANNEL_COUNT, channel_count);
+
+        assert_eq!(server_received, expected_packets(channel_count));
+        assert_eq!(client_received, expected_packets(channel_count));
+        assert!(client_errors.is_empty());
+    }
+}
+
+// A server should refuse a client which proposes more channels than the server has.
+#[test]
+fn channel_count_exceeds_server() {
+    let (server_received, client_received, client_errors) = echo(4, 8);
+
+    assert!(server_received.is_empty());
+    assert!(client_received.is_empty());
+    assert_eq!(client_errors, vec![ uflow::client::ErrorType::Config ]);
+}
+
+// Sending on a channel beyond the configured channel count is a usage error.
+#[test]
+#[should_panic]
+fn channel_count_invalid() {
+    let (transport, server_transport) = uflow::transport::Memory::pair();
+
+    let client_cfg = uflow::client::Config {
+        endpoint_config: endpoint_config(4),
+        .. Default::default()
+    };
+
+    let mut client = uflow::client::Client::with_transport(transport, server_transport.address(), client_cfg).unwrap();
+
+    client.send(vec![ 0 ].into(), 4, uflow::SendMode::Reliable);
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use std::time;
+
+use common::Sim;
+
+// Enqueues a large backlog of bulk data on channel 0 once connected, followed by a small input
+// packet on channel 1 every step. Returns the greatest number of steps any input packet took to
+// arrive at the server, and whether all data arrived.
+fn max_input_delay(channel_weights: Vec<u32>) -> (u32, bool) {
+    let sim = Sim::with_delay(time::Duration::from_millis(20));
+
+    let client_cfg = uflow::client::Config {
+        endpoint_config: uflow::EndpointConfig {
+            channel_weights,
+            .. Default::default()
+        },
+        .. sim.client_config()
+    };
+
+    assert!(client_cfg.is_valid());
+
+    let mut server = sim.server(sim.server_config());
+    let mut client = sim.client("10.0.0.2:2000", client_cfg);
+
+    const BULK_COUNT: usize = 200;
+    const INPUT_COUNT: u32 = 50;
+
+    let mut connected = false;
+    let mut input_sent = 0;
+    let mut bulk_received = 0;
+    let mut input_received = 0;
+    let mut max_delay = 0;
+
+    for step in 0 .. 1500u32 {
+        if connected && input_sent < INPUT_COUNT {
+            if input_sent == 0 {
+                for _ in 0 .. BULK_COUNT {
+                    client.send(vec![ 0; 1000 ].into(), 0, uflow::SendMode::Reliable);
+                }
+            }
+
+            client.send(step.to_be_bytes().into(), 1, uflow::SendMode::Reliable);
+            input_sent += 1;
+        }
+
+        sim.step(&mut server, &mut client, |_, event| match event {
+            uflow::server::Event::Connect(_) => (),
+            uflow::server::Event::Receive(_, data) => {
+                if data.len() == 4 {
+                    let sent_step = u32::from_be_bytes([ data[0], data[1], data[2], data[3] ]);
+                    max_delay = max_delay.max(step - sent_step);
+                    input_received += 1;
+                } else {
+                    bulk_received += 1;
+                }
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }, |_, event| match event {
+            uflow::client::Event::Connect => connected = true,
+            other => panic!("unexpected event: {:?}", other),
+        });
+    }
+
+    (max_delay, bulk_received == BULK_COUNT && input_received == INPUT_COUNT)
+}
+
+// Input packets on a heavily weighted channel should not wait behind a backlog of bulk data on
+// another channel, as they do when packets are sent in the order they were enqueued.
+#[test]
+fn channel_weights() {
+    let (fifo_delay, fifo_complete) = max_input_delay(Vec::new());
+    let (weighted_delay, weighted_complete) = max_input_delay(vec![ 1, 16 ]);
+
+    assert!(fifo_complete);
+    assert!(weighted_complete);
+
+    assert!(fifo_delay > 30, "{}", fifo_delay);
+    assert!(weighted_delay < 10, "{}", weighted_delay);
+}
+
+// Zero weights are invalid.
+#[test]
+fn channel_weights_invalid() {
+    let cfg = uflow::EndpointConfig {
+        channel_weights: vec![ 1, 0 ],
+        .. Default::default()
+    };
+
+    assert!(!cfg.is_valid());
+}
@@ -0,0 +1,162 @@
+// Not every test uses every helper
+#![allow(dead_code)]
+
+use std::net;
+use std::sync::Arc;
+use std::time;
+
+use uflow::Transport;
+
+pub static STEP_INTERVAL: time::Duration = time::Duration::from_millis(10);
+
+pub const SERVER_ADDRESS: &str = "10.0.0.1:1000";
+
+pub type Server = uflow::server::Server<uflow::transport::Memory>;
+pub type Client = uflow::client::Client<uflow::transport::Memory>;
+
+pub fn address(address: &str) -> net::SocketAddr {
+    address.parse().unwrap()
+}
+
+// An in-memory network whose endpoints share a virtual clock
+pub struct Sim {
+    pub clock: uflow::VirtualClock,
+    pub network: uflow::transport::MemoryNetwork,
+}
+
+impl Sim {
+    pub fn new() -> Self {
+        let clock = uflow::VirtualClock::new();
+        let network = uflow::transport::MemoryNetwork::with_clock(Arc::new(clock.clone()));
+
+        Self { clock, network }
+    }
+
+    pub fn with_delay(delay: time::Duration) -> Self {
+        let sim = Self::new();
+        sim.network.set_delay(delay);
+        sim
+    }
+
+    pub fn bind(&self, address: &str) -> uflow::transport::Memory {
+        self.network.bind(self::address(address)).unwrap()
+    }
+
+    pub fn server_config(&self) -> uflow::server::Config {
+        uflow::server::Config {
+            clock: Arc::new(self.clock.clone()),
+            .. Default::default()
+        }
+    }
+
+    pub fn client_config(&self) -> uflow::client::Config {
+        uflow::client::Config {
+            clock: Arc::new(self.clock.clone()),
+            .. Default::default()
+        }
+    }
+
+    // Creates a server bound to SERVER_ADDRESS
+    pub fn server(&self, config: uflow::server::Config) -> Server {
+        uflow::server::Server::with_transport(self.bind(SERVER_ADDRESS), config)
+    }
+
+    // Creates a client bound to the given address which connects to SERVER_ADDRESS
+    pub fn client(&self, address: &str, config: uflow::client::Config) -> Client {
+        self.client_with_transport(self.bind(address), config)
+    }
+
+    pub fn client_with_transport<T: Transport>(&self, transport: T, config: uflow::client::Config) -> uflow::client::Client<T> {
+        uflow::client::Client::with_transport(transport, self::address(SERVER_ADDRESS), config).unwrap()
+    }
+
+    pub fn advance(&self) {
+        self.clock.advance(STEP_INTERVAL);
+    }
+
+    // Steps the server and then each client, passing each event to the corresponding handler,
+    // flushes every endpoint, and advances the clock by one step interval
+    pub fn step_all<S, C, FS, FC>(&self,
+                                  server: &mut uflow::server::Server<S>,
+                                  clients: &mut [uflow::client::Client<C>],
+                                  mut on_server: FS,
+                                  mut on_client: FC)
+        where S: Transport,
+              C: Transport,
+              FS: FnMut(&mut uflow::server::Server<S>, uflow::server::Event),
+              FC: FnMut(usize, &mut uflow::client::Client<C>, uflow::client::Event)
+    {
+        for event in server.step().collect::<Vec<_>>() {
+            on_server(server, event);
+        }
+
+        for (index, client) in clients.iter_mut().enumerate() {
+            for event in client.step().collect::<Vec<_>>() {
+                on_client(index, client, event);
+            }
+        }
+
+        server.flush();
+
+        for client in clients.iter_mut() {
+            client.flush();
+        }
+
+        self.advance();
+    }
+
+    pub fn step<S, C, FS, FC>(&self,
+                              server: &mut uflow::server::Server<S>,
+                              client: &mut uflow::client::Client<C>,
+                              on_server: FS,
+                              mut on_client: FC)
+        where S: Transport,
+              C: Transport,
+              FS: FnMut(&mut uflow::server::Server<S>, uflow::server::Event),
+              FC: FnMut(&mut uflow::client::Client<C>, uflow::client::Event)
+    {
+        self.step_all(server, std::slice::from_mut(client), on_server, |_, client, event| on_client(client, event));
+    }
+
+    // Calls step() the given number of times
+    pub fn run<S, C, FS, FC>(&self,
+                             steps: usize,
+                             server: &mut uflow::server::Server<S>,
+                             client: &mut uflow::client::Client<C>,
+                             mut on_server: FS,
+                             mut on_client: FC)
+        where S: Transport,
+              C: Transport,
+              FS: FnMut(&mut uflow::server::Server<S>, uflow::server::Event),
+              FC: FnMut(&mut uflow::client::Client<C>, uflow::client::Event)
+    {
+        for _ in 0 .. steps {
+            self.step(server, client, &mut on_server, &mut on_client);
+        }
+    }
+}
+
+// Event handlers which panic on any event but a connection
+pub fn expect_connect<S: Transport>(_: &mut uflow::server::Server<S>, event: uflow::server::Event) {
+    match event {
+        uflow::server::Event::Connect(_) => (),
+        other => panic!("unexpected event: {:?}", other),
+    }
+}
+
+pub fn expect_client_connect<C: Transport>(_: &mut uflow::client::Client<C>, event: uflow::client::Event) {
+    match event {
+        uflow::client::Event::Connect => (),
+        other => panic!("unexpected event: {:?}", other),
+    }
+}
+
+// Event handlers which ignore every event
+pub fn ignore<S: Transport>(_: &mut uflow::server::Server<S>, _: uflow::server::Event) {}
+
+pub fn ignore_client<C: Transport>(_: &mut uflow::client::Client<C>, _: uflow::client::Event) {}
+
+// Returns the ID of the server's client at the given address
+pub fn client_id<S: Transport>(server: &uflow::server::Server<S>, address: net::SocketAddr) -> uflow::server::ClientId {
+    server.clients().map(|client| client.borrow()).find(|client| client.address() == address).unwrap().id()
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use common::Sim;
+
+// A server's connect validator should receive each client's connect token, and a client whose
+// token is declined should be notified that its connection was rejected.
+#[test]
+fn connect_token() {
+    let sim = Sim::new();
+
+    let tokens_seen = Rc::new(RefCell::new(Vec::new()));
+    let tokens_seen_clone = Rc::clone(&tokens_seen);
+
+    let mut server = sim.server(uflow::server::Config {
+        connect_validator: Some(Box::new(move |address, token| {
+            tokens_seen_clone.borrow_mut().push((address, token.to_vec()));
+            token == b"valid"
+        })),
+        .. sim.server_config()
+    });
+
+    let address_a = common::address("10.0.0.2:2000");
+    let address_b = common::address("10.0.0.3:2000");
+
+    let mut clients = [ (address_a, &b"valid"[..]), (address_b, &b"invalid"[..]) ].iter().map(|&(address, token)| {
+        sim.client(&address.to_string(), uflow::client::Config {
+            connect_token: Some(token.into()),
+            .. sim.client_config()
+        })
+    }).collect::<Vec<_>>();
+
+    let mut connected = Vec::new();
+    let mut refused = Vec::new();
+    let mut client_b_error = None;
+
+    for _ in 0 .. 20 {
+        sim.step_all(&mut server, &mut clients, |_, event| match event {
+            uflow::server::Event::Connect(address) => connected.push(address),
+            uflow::server::Event::ConnectionRefused(address, reason) => refused.push((address, reason)),
+            other => panic!("unexpected event: {:?}", other),
+        }, |index, _, event| {
+            if let (1, uflow::client::Event::Error(error)) = (index, event) {
+                client_b_error = Some(error);
+            }
+        });
+    }
+
+    assert!(clients[0].is_active());
+    assert_eq!(connected, vec![ common::client_id(&server, address_a) ]);
+    assert_eq!(refused, vec![ (address_b, uflow::server::RefusalReason::Rejected) ]);
+    assert_eq!(client_b_error, Some(uflow::client::ErrorType::Rejected));
+    assert_eq!(server.clients().count(), 1);
+
+    let mut tokens_seen = tokens_seen.borrow().clone();
+    tokens_seen.sort();
+    assert_eq!(tokens_seen, vec![ (address_a, b"valid".to_vec()), (address_b, b"invalid".to_vec()) ]);
+}
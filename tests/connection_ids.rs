@@ -0,0 +1,273 @@
+mod common;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io;
+use std::net;
+use std::rc::Rc;
+
+use uflow::Transport;
+
+use common::Sim;
+
+// A transport which records every datagram sent over it, and which may be made to send a given
+// datagram in place of the next
+struct Spoofing {
+    inner: uflow::transport::Memory,
+    sent: Rc<RefCell<Vec<Box<[u8]>>>>,
+    spoofed: Rc<RefCell<Option<Box<[u8]>>>>,
+}
+
+impl Transport for Spoofing {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        if let Some(spoofed) = self.spoofed.borrow_mut().take() {
+            self.inner.send_to(&spoofed, addr)?;
+            return Ok(buf.len());
+        }
+
+        self.sent.borrow_mut().push(buf.into());
+        self.inner.send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        self.inner.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+fn spoofing(sim: &Sim, address: &str) -> Spoofing {
+    Spoofing {
+        inner: sim.bind(address),
+        sent: Rc::new(RefCell::new(Vec::new())),
+        spoofed: Rc::new(RefCell::new(None)),
+    }
+}
+
+// A transport which shares a single socket with others, as though each were a separate host behind
+// the same NAT mapping. Because nothing identifies the host for which a datagram is meant, every
+// datagram received is delivered to every host.
+struct SharedAddress {
+    socket: Rc<uflow::transport::Memory>,
+    inboxes: Rc<RefCell<Vec<VecDeque<(Box<[u8]>, net::SocketAddr)>>>>,
+    index: usize,
+}
+
+impl Transport for SharedAddress {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        self.socket.send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        let mut inboxes = self.inboxes.borrow_mut();
+        let mut datagram = [0; 1500];
+
+        while let Ok((size, addr)) = self.socket.recv_from(&mut datagram) {
+            for inbox in inboxes.iter_mut() {
+                inbox.push_back((datagram[.. size].into(), addr));
+            }
+        }
+
+        match inboxes[self.index].pop_front() {
+            Some((datagram, addr)) => {
+                buf[.. datagram.len()].copy_from_slice(&datagram);
+                Ok((datagram.len(), addr))
+            }
+            None => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+
+fn shared_address(sim: &Sim, address: &str, count: usize) -> Vec<SharedAddress> {
+    let socket = Rc::new(sim.bind(address));
+    let inboxes = Rc::new(RefCell::new(vec![ VecDeque::new(); count ]));
+
+    (0 .. count).map(|index| SharedAddress {
+        socket: Rc::clone(&socket),
+        inboxes: Rc::clone(&inboxes),
+        index,
+    }).collect()
+}
+
+fn step_all(sim: &Sim, server: &mut common::Server, clients: &mut [uflow::client::Client<Spoofing>],
+            server_received: &mut Vec<(net::SocketAddr, Box<[u8]>)>) {
+    sim.step_all(server, clients, |server, event| match event {
+        uflow::server::Event::Connect(_) => (),
+        uflow::server::Event::Receive(client_id, data) => {
+            server_received.push((server.client(client_id).unwrap().borrow().address(), data));
+        }
+        other => panic!("unexpected event: {:?}", other),
+    }, |_, _, event| match event {
+        uflow::client::Event::Connect => (),
+        other => panic!("unexpected event: {:?}", other),
+    });
+}
+
+// Clients of a server which assigns connection IDs should each be assigned a distinct ID, and
+// should exchange data as usual.
+#[test]
+fn connection_ids_transfer() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        enable_connection_ids: true,
+        .. sim.server_config()
+    });
+
+    let mut clients = [ "10.0.0.2:2000", "10.0.0.2:2001" ].iter().map(|address| {
+        sim.client_with_transport(spoofing(&sim, address), sim.client_config())
+    }).collect::<Vec<_>>();
+
+    for (i, client) in clients.iter_mut().enumerate() {
+        client.send(vec![ i as u8 ].into(), 0, uflow::SendMode::Reliable);
+    }
+
+    let mut server_received = Vec::new();
+
+    for _ in 0 .. 50 {
+        step_all(&sim, &mut server, &mut clients, &mut server_received);
+    }
+
+    let connection_ids = clients.iter().map(|client| client.connection_id().unwrap()).collect::<Vec<_>>();
+    assert_ne!(connection_ids[0], connection_ids[1]);
+
+    for (i, client) in clients.iter().enumerate() {
+        let client_rc = server.client(common::client_id(&server, client.local_address())).unwrap();
+        assert_eq!(client_rc.borrow().connection_id(), Some(connection_ids[i]));
+        assert!(server_received.contains(&(client.local_address(), vec![ i as u8 ].into())));
+    }
+}
+
+// A frame tagged with the connection ID of one client should be ignored if it is received from the
+// address of another.
+#[test]
+fn connection_ids_foreign() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        enable_connection_ids: true,
+        .. sim.server_config()
+    });
+
+    let transports = vec![ spoofing(&sim, "10.0.0.2:2000"), spoofing(&sim, "10.0.0.3:3000") ];
+    let victim_sent = Rc::clone(&transports[0].sent);
+    let attacker_spoofed = Rc::clone(&transports[1].spoofed);
+
+    let mut clients = transports.into_iter().map(|transport| {
+        sim.client_with_transport(transport, sim.client_config())
+    }).collect::<Vec<_>>();
+
+    let mut server_received = Vec::new();
+
+    for _ in 0 .. 20 {
+        step_all(&sim, &mut server, &mut clients, &mut server_received);
+    }
+
+    assert!(clients.iter().all(|client| client.is_active()));
+
+    // Have the attacker send a data frame of the victim's from its own address
+    clients[0].send(vec![ 0xAA ].into(), 0, uflow::SendMode::Reliable);
+    clients[0].flush();
+
+    let victim_datagram = victim_sent.borrow().last().cloned().unwrap();
+    *attacker_spoofed.borrow_mut() = Some(victim_datagram);
+
+    clients[1].send(vec![ 0xBB ].into(), 0, uflow::SendMode::Reliable);
+
+    let unknown_frames = server.unknown_address_stats().frames;
+
+    for _ in 0 .. 20 {
+        step_all(&sim, &mut server, &mut clients, &mut server_received);
+    }
+
+    assert_eq!(server.unknown_address_stats().frames, unknown_frames + 1);
+    assert_eq!(server_received, vec![
+        (clients[0].local_address(), vec![ 0xAA ].into()),
+        (clients[1].local_address(), vec![ 0xBB ].into()),
+    ]);
+}
+
+// A server which does not assign connection IDs should accept clients which support them as usual.
+#[test]
+fn connection_ids_disabled() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(sim.server_config());
+    let mut clients = [ sim.client_with_transport(spoofing(&sim, "10.0.0.2:2000"), sim.client_config()) ];
+
+    clients[0].send(vec![ 0 ].into(), 0, uflow::SendMode::Reliable);
+
+    let mut server_received = Vec::new();
+
+    for _ in 0 .. 50 {
+        step_all(&sim, &mut server, &mut clients, &mut server_received);
+    }
+
+    assert!(clients[0].is_active());
+    assert_eq!(clients[0].connection_id(), None);
+    assert_eq!(server.client(common::client_id(&server, clients[0].local_address())).unwrap().borrow().connection_id(), None);
+    assert_eq!(server_received, vec![ (clients[0].local_address(), vec![ 0 ].into()) ]);
+}
+
+// Clients which share an address should each be given a connection of their own, identified by its
+// client ID, and should exchange data as usual.
+#[test]
+fn connection_ids_shared_address() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        enable_connection_ids: true,
+        .. sim.server_config()
+    });
+
+    let mut clients = shared_address(&sim, "10.0.0.2:2000", 2).into_iter().map(|transport| {
+        sim.client_with_transport(transport, sim.client_config())
+    }).collect::<Vec<_>>();
+
+    for (i, client) in clients.iter_mut().enumerate() {
+        client.send(vec![ i as u8 ].into(), 0, uflow::SendMode::Reliable);
+    }
+
+    let mut connected = Vec::new();
+    let mut server_received = Vec::new();
+    let mut client_received = vec![ Vec::new(); clients.len() ];
+
+    // Whichever client's request arrives second must wait for the first's handshake to complete,
+    // and resend its own
+    for _ in 0 .. 300 {
+        sim.step_all(&mut server, &mut clients, |server, event| match event {
+            uflow::server::Event::Connect(client_id) => connected.push(client_id),
+            uflow::server::Event::Receive(client_id, data) => {
+                // Echo each packet to its sender only
+                server.client(client_id).unwrap().borrow_mut().send(data.clone(), 0, uflow::SendMode::Reliable);
+                server_received.push((client_id, data));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }, |index, _, event| match event {
+            uflow::client::Event::Connect => (),
+            uflow::client::Event::Receive(data) => client_received[index].push(data),
+            other => panic!("unexpected event: {:?}", other),
+        });
+    }
+
+    assert!(clients.iter().all(|client| client.is_active()));
+
+    assert_eq!(connected.len(), 2);
+    assert_ne!(connected[0], connected[1]);
+
+    for (i, client) in clients.iter().enumerate() {
+        let client_id = connected.iter().copied().find(|&client_id| {
+            server.client(client_id).unwrap().borrow().connection_id() == client.connection_id()
+        }).unwrap();
+
+        assert_eq!(server.client(client_id).unwrap().borrow().address(), client.local_address());
+        assert!(server_received.contains(&(client_id, vec![ i as u8 ].into())));
+        assert_eq!(client_received[i], vec![ vec![ i as u8 ].into_boxed_slice() ]);
+    }
+}
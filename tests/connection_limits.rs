@@ -0,0 +1,144 @@
+mod common;
+
+use std::net;
+
+use common::Sim;
+
+// Connection requests from a single IP address beyond the throttle's burst should be refused, and
+// the refusal signaled once, until the address regains a token.
+#[test]
+fn handshake_throttle() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        handshake_throttle: Some(uflow::server::HandshakeThrottle { burst: 2, interval_ms: 1000 }),
+        .. sim.server_config()
+    });
+
+    // The last address is throttled independently
+    let client_addresses: Vec<String> = (0 .. 4).map(|i| format!("10.0.0.2:{}", 2000 + i))
+                                                .chain(Some("10.0.0.3:2000".to_string()))
+                                                .collect();
+
+    let mut clients = client_addresses.iter().map(|address| {
+        sim.client(address, sim.client_config())
+    }).collect::<Vec<_>>();
+
+    let other_address = clients[4].local_address();
+
+    let mut refused = Vec::new();
+    let mut connected = Vec::new();
+
+    for _ in 0 .. 50 {
+        sim.step_all(&mut server, &mut clients, |server, event| match event {
+            uflow::server::Event::Connect(client_id) => connected.push(server.client(client_id).unwrap().borrow().address()),
+            uflow::server::Event::ConnectionRefused(address, reason) => {
+                assert_eq!(reason, uflow::server::RefusalReason::Throttled);
+                refused.push(address);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }, |_, _, _| ());
+    }
+
+    assert_eq!(connected.len(), 3);
+    assert!(connected.contains(&other_address));
+    assert_eq!(refused.len(), 1);
+    assert!(server.handshake_stats().throttled_requests >= 2);
+
+    // After the interval, clients which resend their requests are admitted one at a time
+    for _ in 0 .. 150 {
+        sim.step_all(&mut server, &mut clients[.. 4], |server, event| {
+            if let uflow::server::Event::Connect(client_id) = event {
+                connected.push(server.client(client_id).unwrap().borrow().address());
+            }
+        }, |_, _, _| ());
+    }
+
+    assert_eq!(connected.len(), 4);
+}
+
+// A connection request received while the maximum number of clients are active should be refused.
+#[test]
+fn server_full() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        max_active_connections: 1,
+        .. sim.server_config()
+    });
+
+    let address_b = common::address("10.0.0.3:2000");
+
+    let mut clients = vec![ sim.client("10.0.0.2:2000", sim.client_config()) ];
+
+    for _ in 0 .. 10 {
+        sim.step_all(&mut server, &mut clients, common::ignore, |_, _, _| ());
+    }
+
+    assert!(clients[0].is_active());
+
+    clients.push(sim.client(&address_b.to_string(), sim.client_config()));
+
+    let mut refused = false;
+    let mut client_error = None;
+
+    for _ in 0 .. 10 {
+        sim.step_all(&mut server, &mut clients, |_, event| match event {
+            uflow::server::Event::ConnectionRefused(address, reason) => {
+                assert_eq!(address, address_b);
+                assert_eq!(reason, uflow::server::RefusalReason::ServerFull);
+                refused = true;
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }, |index, _, event| {
+            if let (1, uflow::client::Event::Error(error)) = (index, event) {
+                client_error = Some(error);
+            }
+        });
+    }
+
+    assert!(refused);
+    assert_eq!(client_error, Some(uflow::client::ErrorType::ServerFull));
+    assert_eq!(server.clients().count(), 1);
+}
+
+// Connection requests from IPv6 addresses within the same /64 prefix should share a throttle.
+#[test]
+fn handshake_throttle_ipv6() {
+    let sim = Sim::new();
+
+    let server_transport = sim.bind("[2001:db8::1]:1000");
+    let server_address = server_transport.address();
+
+    let mut server = uflow::server::Server::with_transport(server_transport, uflow::server::Config {
+        handshake_throttle: Some(uflow::server::HandshakeThrottle { burst: 2, interval_ms: 1000 }),
+        .. sim.server_config()
+    });
+
+    // The last address lies within another prefix
+    let client_addresses: Vec<net::SocketAddr> = vec![
+        common::address("[2001:db8::2]:2000"),
+        common::address("[2001:db8::3]:2000"),
+        common::address("[2001:db8::ffff:4]:2000"),
+        common::address("[2001:db8:0:1::2]:2000"),
+    ];
+
+    let mut clients = client_addresses.iter().map(|&address| {
+        uflow::client::Client::with_transport(sim.network.bind(address).unwrap(), server_address, sim.client_config()).unwrap()
+    }).collect::<Vec<_>>();
+
+    let mut refused = Vec::new();
+    let mut connected = Vec::new();
+
+    for _ in 0 .. 50 {
+        sim.step_all(&mut server, &mut clients, |server, event| match event {
+            uflow::server::Event::Connect(client_id) => connected.push(server.client(client_id).unwrap().borrow().address()),
+            uflow::server::Event::ConnectionRefused(address, _) => refused.push(address),
+            other => panic!("unexpected event: {:?}", other),
+        }, |_, _, _| ());
+    }
+
+    assert_eq!(connected.len(), 3);
+    assert!(connected.contains(&client_addresses[3]));
+    assert_eq!(refused.len(), 1);
+}
@@ -0,0 +1,101 @@
+use std::thread;
+use std::time;
+
+fn endpoint_config() -> uflow::EndpointConfig {
+    uflow::EndpointConfig {
+        state_events: true,
+        .. Default::default()
+    }
+}
+
+// Both endpoints should signal each state transition of a gracefully closed connection.
+#[test]
+fn connection_state_changes() {
+    let server_cfg = uflow::server::Config {
+        endpoint_config: endpoint_config(),
+        .. Default::default()
+    };
+
+    let client_cfg = uflow::client::Config {
+        endpoint_config: endpoint_config(),
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind("127.0.0.1:5621", server_cfg).unwrap();
+    let mut client = uflow::client::Client::connect("127.0.0.1:5621", client_cfg).unwrap();
+
+    assert_eq!(client.connection_state(), uflow::ConnectionState::Connecting);
+
+    let mut server_states = Vec::new();
+    let mut client_states = Vec::new();
+
+    let closed = uflow::ConnectionState::Closed { reason: uflow::CloseReason::Disconnect };
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(4000);
+
+    while client_states.last() != Some(&closed) && time::Instant::now() < end_time {
+        for event in server.step() {
+            if let uflow::server::Event::StateChange(_, state) = event {
+                server_states.push(state);
+            }
+        }
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => client.disconnect(),
+                uflow::client::Event::StateChange(state) => client_states.push(state),
+                _ => (),
+            }
+        }
+
+        thread::sleep(time::Duration::from_millis(50));
+    }
+
+    assert_eq!(client_states, vec![
+        uflow::ConnectionState::Connected,
+        uflow::ConnectionState::Draining,
+        uflow::ConnectionState::Closing,
+        closed,
+    ]);
+
+    assert_eq!(server_states, vec![
+        uflow::ConnectionState::Connected,
+        closed,
+    ]);
+
+    assert_eq!(client.connection_state(), closed);
+}
+
+// A failed connection attempt should be reported as a closed state with the corresponding reason.
+#[test]
+fn connection_state_timeout() {
+    let client_cfg = uflow::client::Config {
+        endpoint_config: endpoint_config(),
+        handshake_resend_interval_ms: 50,
+        handshake_resend_count: 0,
+        .. Default::default()
+    };
+
+    // Bind a socket which never replies
+    let _server_socket = std::net::UdpSocket::bind("127.0.0.1:5622").unwrap();
+
+    let mut client = uflow::client::Client::connect("127.0.0.1:5622", client_cfg).unwrap();
+
+    let timeout = uflow::ConnectionState::Closed { reason: uflow::CloseReason::Timeout };
+    let mut client_states = Vec::new();
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(2000);
+
+    while client_states.is_empty() && time::Instant::now() < end_time {
+        for event in client.step() {
+            if let uflow::client::Event::StateChange(state) = event {
+                client_states.push(state);
+            }
+        }
+
+        thread::sleep(time::Duration::from_millis(50));
+    }
+
+    assert_eq!(client_states, vec![ timeout ]);
+    assert_eq!(client.connection_state(), timeout);
+}
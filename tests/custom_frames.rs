@@ -0,0 +1,96 @@
+use std::thread;
+use std::time;
+
+fn endpoint_config(custom_frame_types: u16) -> uflow::EndpointConfig {
+    uflow::EndpointConfig {
+        custom_frame_types,
+        .. Default::default()
+    }
+}
+
+// Connects a client and server with the given custom frame registrations, has each send custom
+// frames of types 3 and 5 once connected, and returns the custom frames received by the server and
+// client, respectively.
+fn exchange(port: u16, server_types: u16, client_types: u16) -> (Vec<(usize, Box<[u8]>)>, Vec<(usize, Box<[u8]>)>) {
+    let address = format!("127.0.0.1:{}", port);
+
+    let server_cfg = uflow::server::Config {
+        endpoint_config: endpoint_config(server_types),
+        .. Default::default()
+    };
+
+    let client_cfg = uflow::client::Config {
+        endpoint_config: endpoint_config(client_types),
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind(address.as_str(), server_cfg).unwrap();
+    let mut client = uflow::client::Client::connect(address.as_str(), client_cfg).unwrap();
+
+    let mut server_received = Vec::new();
+    let mut client_received = Vec::new();
+
+    let mut client_id = None;
+    let mut client_connected = false;
+    let mut sent = false;
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(1000);
+
+    while time::Instant::now() < end_time {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(id) => client_id = Some(id),
+                uflow::server::Event::CustomFrame(_, type_id, data) => server_received.push((type_id, data)),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => client_connected = true,
+                uflow::client::Event::CustomFrame(type_id, data) => client_received.push((type_id, data)),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        if let (Some(client_id), true, false) = (client_id, client_connected, sent) {
+            let mut remote_client = server.client(client_id).unwrap().borrow_mut();
+
+            remote_client.send_custom_frame(3, [ 0, 1, 2 ].into());
+            remote_client.send_custom_frame(5, [ 3, 4, 5 ].into());
+
+            client.send_custom_frame(3, [ 6, 7, 8 ].into());
+            client.send_custom_frame(5, [ 9, 10, 11 ].into());
+
+            sent = true;
+        }
+
+        server.flush();
+        client.flush();
+
+        thread::sleep(time::Duration::from_millis(20));
+    }
+
+    assert!(sent);
+
+    (server_received, client_received)
+}
+
+// Only registered custom frame types should be delivered.
+#[test]
+fn custom_frames_registered() {
+    let (server_received, client_received) = exchange(5681, 1 << 3, 1 << 3);
+
+    assert_eq!(server_received, vec![ (3, [ 6, 7, 8 ].into()) ]);
+    assert_eq!(client_received, vec![ (3, [ 0, 1, 2 ].into()) ]);
+}
+
+// Custom frames should be delivered to an endpoint which has registered their type, even if the
+// sending endpoint has not registered any types.
+#[test]
+fn custom_frames_one_sided() {
+    let (server_received, client_received) = exchange(5682, 1 << 5, 0);
+
+    assert_eq!(server_received, vec![ (5, [ 9, 10, 11 ].into()) ]);
+    assert!(client_received.is_empty());
+}
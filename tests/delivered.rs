@@ -0,0 +1,45 @@
+mod common;
+
+use std::time;
+
+use common::Sim;
+
+// Confirmed packets should each produce a delivery event carrying their channel and tag, whether
+// sent before or after the connection was established, and whether fragmented or not.
+#[test]
+fn delivered_events() {
+    let sim = Sim::with_delay(time::Duration::from_millis(20));
+
+    let mut server = sim.server(sim.server_config());
+    let mut client = sim.client("10.0.0.2:1000", sim.client_config());
+
+    client.send_confirmed([ 0 ].into(), 0, uflow::SendMode::Reliable, 100);
+    client.send([ 1 ].into(), 0, uflow::SendMode::Reliable);
+
+    let mut server_delivered = Vec::new();
+    let mut client_delivered = Vec::new();
+    let mut server_received = 0;
+    let mut client_received = 0;
+
+    sim.run(100, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(address) => {
+            let mut remote_client = server.client(address).unwrap().borrow_mut();
+            remote_client.send_confirmed([ 2 ].into(), 3, uflow::SendMode::ReliableUnordered, 200);
+        }
+        uflow::server::Event::Delivered(_, channel_id, tag) => server_delivered.push((channel_id, tag)),
+        uflow::server::Event::Receive(..) => server_received += 1,
+        _ => (),
+    }, |client, event| match event {
+        uflow::client::Event::Connect => {
+            let data = vec![ 3; 3 * uflow::MAX_FRAGMENT_SIZE ].into();
+            client.send_confirmed(data, 1, uflow::SendMode::Reliable, 101);
+        }
+        uflow::client::Event::Delivered(channel_id, tag) => client_delivered.push((channel_id, tag)),
+        uflow::client::Event::Receive(..) => client_received += 1,
+        _ => (),
+    });
+
+    assert_eq!((server_received, client_received), (3, 1));
+    assert_eq!(client_delivered, vec![ (0, 100), (1, 101) ]);
+    assert_eq!(server_delivered, vec![ (3, 200) ]);
+}
@@ -104,12 +104,12 @@ fn server_disconnect_now() {
         while time::Instant::now() < end_time {
             for event in server.step() {
                 match event {
-                    uflow::server::Event::Connect(peer_address) => {
+                    uflow::server::Event::Connect(client_id) => {
                         assert_eq!(connect_seen, false);
                         assert_eq!(disconnect_seen, false);
                         connect_seen = true;
 
-                        server.client(&peer_address).unwrap().borrow_mut().disconnect_now();
+                        server.client(client_id).unwrap().borrow_mut().disconnect_now();
                     }
                     uflow::server::Event::Disconnect(_) => {
                         assert_eq!(connect_seen, true);
@@ -292,12 +292,12 @@ fn server_disconnect_flush() {
         while time::Instant::now() < end_time {
             for event in server.step() {
                 match event {
-                    uflow::server::Event::Connect(peer_address) => {
+                    uflow::server::Event::Connect(client_id) => {
                         assert_eq!(connect_seen, false);
                         assert_eq!(disconnect_seen, false);
                         connect_seen = true;
 
-                        let mut client = server.client(&peer_address).unwrap().borrow_mut();
+                        let mut client = server.client(client_id).unwrap().borrow_mut();
                         client.disconnect();
                         client.send([0, 1, 2, 3].into(), 0, uflow::SendMode::Reliable);
                     }
@@ -0,0 +1,47 @@
+use std::thread;
+use std::time;
+
+// A dual-stack server should accept both IPv4 and IPv6 clients, and should identify IPv4 clients by
+// their plain IPv4 addresses.
+#[test]
+fn dual_stack_clients() {
+    let mut server = uflow::server::Server::bind_dual_stack(0, Default::default()).unwrap();
+    let server_port = server.address().port();
+
+    let mut clients = [
+        uflow::client::Client::connect(("127.0.0.1", server_port), Default::default()).unwrap(),
+        uflow::client::Client::connect(("::1", server_port), Default::default()).unwrap(),
+    ];
+
+    for (i, client) in clients.iter_mut().enumerate() {
+        client.send(vec![ i as u8 ].into(), 0, uflow::SendMode::Reliable);
+    }
+
+    let mut server_received = Vec::new();
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(2000);
+
+    while server_received.len() < clients.len() && time::Instant::now() < end_time {
+        for event in server.step() {
+            if let uflow::server::Event::Receive(client_id, data) = event {
+                server_received.push((server.client(client_id).unwrap().borrow().address(), data));
+            }
+        }
+
+        for client in clients.iter_mut() {
+            for _ in client.step() {}
+            client.flush();
+        }
+
+        server.flush();
+
+        thread::sleep(time::Duration::from_millis(10));
+    }
+
+    assert!(clients[0].local_address().is_ipv4());
+    assert!(clients[1].local_address().is_ipv6());
+
+    for (i, client) in clients.iter().enumerate() {
+        assert!(server_received.contains(&(client.local_address(), vec![ i as u8 ].into())));
+    }
+}
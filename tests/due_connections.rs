@@ -0,0 +1,65 @@
+
+use std::thread;
+use std::time;
+
+#[test]
+fn due_connections() {
+    let mut server = uflow::server::Server::bind("127.0.0.1:5571", Default::default()).unwrap();
+    let mut client = uflow::client::Client::connect("127.0.0.1:5571", Default::default()).unwrap();
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(4000);
+    let mut client_id = None;
+
+    while client_id.is_none() && time::Instant::now() < end_time {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(id) => client_id = Some(id),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for _ in client.step() {}
+
+        thread::sleep(time::Duration::from_millis(50));
+    }
+
+    let client_id = client_id.unwrap();
+
+    for _ in server.step() {}
+
+    // A newly connected, idle client requires no service until a keepalive is due
+    let now_ms = server.time_ms();
+    assert_eq!(server.due_connections(now_ms).count(), 0);
+
+    let keepalive_ms = now_ms + uflow::EndpointConfig::default().keepalive_interval_ms + 1000;
+    assert_eq!(server.due_connections(keepalive_ms).collect::<Vec<_>>(), vec![ client_id ]);
+
+    // A client with pending outbound data requires service immediately
+    server.client(client_id).unwrap().borrow_mut().send([ 0; 64 ].into(), 0, uflow::SendMode::Reliable);
+    assert_eq!(server.due_connections(server.time_ms()).collect::<Vec<_>>(), vec![ client_id ]);
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(4000);
+    let mut received = false;
+
+    while !received && time::Instant::now() < end_time {
+        for _ in server.step() {}
+
+        for client_id in server.due_connections(server.time_ms()).collect::<Vec<_>>() {
+            server.flush_client(client_id);
+        }
+
+        thread::sleep(time::Duration::from_millis(50));
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Receive(data) => {
+                    assert_eq!(data, [ 0; 64 ].into());
+                    received = true;
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+    }
+
+    assert!(received);
+}
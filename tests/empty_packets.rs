@@ -0,0 +1,91 @@
+use std::thread;
+use std::time;
+
+// Sends a mix of empty and non-empty packets on one channel, and returns the packets received by
+// the server in the order they were delivered.
+fn transfer(port: u16, server_cfg: uflow::server::Config) -> Vec<Box<[u8]>> {
+    let address = format!("127.0.0.1:{}", port);
+
+    let mut server = uflow::server::Server::bind(address.as_str(), server_cfg).unwrap();
+    let mut client = uflow::client::Client::connect(address.as_str(), Default::default()).unwrap();
+
+    let packets: Vec<Box<[u8]>> = vec![
+        [ 1 ].into(),
+        [].into(),
+        [ 2, 2 ].into(),
+        [].into(),
+        [].into(),
+        [ 3 ].into(),
+    ];
+
+    for packet in packets.into_iter() {
+        client.send(packet, 0, uflow::SendMode::Reliable);
+    }
+
+    let mut received = Vec::new();
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(2000);
+
+    while time::Instant::now() < end_time {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(_) => (),
+                uflow::server::Event::Receive(_, data) => received.push(data),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => (),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        server.flush();
+        client.flush();
+
+        thread::sleep(time::Duration::from_millis(50));
+    }
+
+    received
+}
+
+// Empty packets should be delivered in order as empty slices.
+#[test]
+fn empty_packets_delivered() {
+    let received = transfer(5641, Default::default());
+
+    let expected: Vec<Box<[u8]>> = vec![
+        [ 1 ].into(),
+        [].into(),
+        [ 2, 2 ].into(),
+        [].into(),
+        [].into(),
+        [ 3 ].into(),
+    ];
+
+    assert_eq!(received, expected);
+}
+
+// If so configured, empty packets should be discarded without disrupting other packets.
+#[test]
+fn empty_packets_discarded() {
+    let server_cfg = uflow::server::Config {
+        endpoint_config: uflow::EndpointConfig {
+            deliver_empty_packets: false,
+            .. Default::default()
+        },
+        .. Default::default()
+    };
+
+    let received = transfer(5642, server_cfg);
+
+    let expected: Vec<Box<[u8]>> = vec![
+        [ 1 ].into(),
+        [ 2, 2 ].into(),
+        [ 3 ].into(),
+    ];
+
+    assert_eq!(received, expected);
+}
@@ -0,0 +1,270 @@
+#![cfg(feature = "encryption")]
+
+mod common;
+
+use std::cell::RefCell;
+use std::io;
+use std::net;
+use std::rc::Rc;
+
+use uflow::Transport;
+
+use common::Sim;
+
+// A transport which records every datagram sent over it
+struct Recording {
+    inner: uflow::transport::Memory,
+    sent: Rc<RefCell<Vec<Box<[u8]>>>>,
+}
+
+impl Transport for Recording {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        self.sent.borrow_mut().push(buf.into());
+        self.inner.send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        self.inner.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+fn endpoint_config(client_to_server: u8) -> uflow::EndpointConfig {
+    uflow::EndpointConfig {
+        session_keys: Some(uflow::SessionKeys {
+            client_to_server: [client_to_server; 32],
+            server_to_client: [0xA5; 32],
+        }),
+        .. Default::default()
+    }
+}
+
+// Endpoints sharing session keys should exchange data as usual, without any of it appearing in the
+// clear.
+#[test]
+fn encryption_transfer() {
+    let sim = Sim::new();
+
+    let sent = Rc::new(RefCell::new(Vec::new()));
+    let client_transport = Recording { inner: sim.bind("10.0.0.2:2000"), sent: Rc::clone(&sent) };
+
+    let mut server = sim.server(uflow::server::Config {
+        endpoint_config: endpoint_config(0x5A),
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client_with_transport(client_transport, uflow::client::Config {
+        endpoint_config: endpoint_config(0x5A),
+        .. sim.client_config()
+    });
+
+    let secret = b"the quick brown fox jumps over the lazy dog";
+
+    client.send(secret[..].into(), 0, uflow::SendMode::Reliable);
+
+    let mut server_received = Vec::new();
+    let mut client_received = Vec::new();
+
+    sim.run(50, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(address) => {
+            server.client(address).unwrap().borrow_mut().send(secret[..].into(), 0, uflow::SendMode::Reliable);
+        }
+        uflow::server::Event::Receive(_, data) => server_received.push(data),
+        other => panic!("unexpected event: {:?}", other),
+    }, |_, event| match event {
+        uflow::client::Event::Connect => (),
+        uflow::client::Event::Receive(data) => client_received.push(data),
+        other => panic!("unexpected event: {:?}", other),
+    });
+
+    assert_eq!(server_received, vec![ secret[..].into() ]);
+    assert_eq!(client_received, vec![ secret[..].into() ]);
+
+    let sent = sent.borrow();
+    assert!(!sent.is_empty());
+
+    for datagram in sent.iter() {
+        assert!(!datagram.windows(secret.len()).any(|window| window == &secret[..]));
+    }
+}
+
+// A client whose session keys differ from the server's should be unable to connect, as the server
+// drops its frames.
+#[test]
+fn encryption_key_mismatch() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        endpoint_config: endpoint_config(0x5A),
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client("10.0.0.2:2000", uflow::client::Config {
+        endpoint_config: endpoint_config(0x3C),
+        .. sim.client_config()
+    });
+
+    sim.run(50, &mut server, &mut client, |_, event| {
+        panic!("unexpected event: {:?}", event);
+    }, |_, event| {
+        panic!("unexpected event: {:?}", event);
+    });
+
+    assert!(!client.is_active());
+    assert_eq!(server.clients().count(), 0);
+}
+
+// Sealed frames should fit within the internet MTU, even when tagged with connection IDs and
+// filled with full-size fragments.
+#[test]
+fn encryption_mtu() {
+    let sim = Sim::new();
+
+    let sent = Rc::new(RefCell::new(Vec::new()));
+    let client_transport = Recording { inner: sim.bind("10.0.0.2:2000"), sent: Rc::clone(&sent) };
+
+    let mut server = sim.server(uflow::server::Config {
+        endpoint_config: endpoint_config(0x5A),
+        enable_connection_ids: true,
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client_with_transport(client_transport, uflow::client::Config {
+        endpoint_config: endpoint_config(0x5A),
+        .. sim.client_config()
+    });
+
+    for _ in 0 .. 20 {
+        client.send(vec![ 0; 4 * uflow::MAX_FRAGMENT_SIZE ].into(), 0, uflow::SendMode::Reliable);
+    }
+
+    let mut server_received = 0;
+
+    sim.run(100, &mut server, &mut client, |_, event| match event {
+        uflow::server::Event::Connect(_) => (),
+        uflow::server::Event::Receive(..) => server_received += 1,
+        other => panic!("unexpected event: {:?}", other),
+    }, common::expect_client_connect);
+
+    assert!(client.connection_id().is_some());
+    assert_eq!(server_received, 20);
+
+    let max_size = sent.borrow().iter().map(|datagram| datagram.len()).max().unwrap();
+    assert_eq!(max_size, uflow::MAX_FRAME_SIZE);
+}
+
+fn key_exchange_endpoints<T: Transport>(sim: &Sim, client_transport: T, static_key: uflow::StaticKey, client_cfg: uflow::client::Config)
+    -> (common::Server, uflow::client::Client<T>) {
+    let server = sim.server(uflow::server::Config {
+        static_key: Some(static_key),
+        .. sim.server_config()
+    });
+
+    let client = sim.client_with_transport(client_transport, uflow::client::Config {
+        clock: sim.client_config().clock,
+        .. client_cfg
+    });
+
+    (server, client)
+}
+
+// Endpoints which agree upon session keys during the handshake should exchange data as usual,
+// without any of it appearing in the clear, and the client should learn the server's public key.
+#[test]
+fn key_exchange_transfer() {
+    let sim = Sim::new();
+
+    let sent = Rc::new(RefCell::new(Vec::new()));
+    let client_transport = Recording { inner: sim.bind("10.0.0.2:2000"), sent: Rc::clone(&sent) };
+
+    let static_key = uflow::StaticKey::generate();
+    let public_key = static_key.public_key();
+
+    let client_cfg = uflow::client::Config {
+        key_exchange: true,
+        server_public_key: Some(public_key),
+        .. Default::default()
+    };
+
+    let (mut server, mut client) = key_exchange_endpoints(&sim, client_transport, static_key, client_cfg);
+
+    assert_eq!(server.public_key(), Some(public_key));
+
+    let secret = b"the quick brown fox jumps over the lazy dog";
+
+    client.send(secret[..].into(), 0, uflow::SendMode::Reliable);
+
+    let mut server_received = Vec::new();
+    let mut client_received = Vec::new();
+
+    sim.run(50, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(address) => {
+            server.client(address).unwrap().borrow_mut().send(secret[..].into(), 0, uflow::SendMode::Reliable);
+        }
+        uflow::server::Event::Receive(_, data) => server_received.push(data),
+        other => panic!("unexpected event: {:?}", other),
+    }, |_, event| match event {
+        uflow::client::Event::Connect => (),
+        uflow::client::Event::Receive(data) => client_received.push(data),
+        other => panic!("unexpected event: {:?}", other),
+    });
+
+    assert_eq!(server_received, vec![ secret[..].into() ]);
+    assert_eq!(client_received, vec![ secret[..].into() ]);
+    assert_eq!(client.server_public_key(), Some(public_key));
+
+    let sent = sent.borrow();
+    assert!(!sent.is_empty());
+
+    for datagram in sent.iter() {
+        assert!(!datagram.windows(secret.len()).any(|window| window == &secret[..]));
+    }
+}
+
+// A client which expects a different server key should abandon the connection.
+#[test]
+fn key_exchange_pin_mismatch() {
+    let sim = Sim::new();
+
+    let client_cfg = uflow::client::Config {
+        key_exchange: true,
+        server_public_key: Some(uflow::StaticKey::generate().public_key()),
+        .. Default::default()
+    };
+
+    let (mut server, mut client) = key_exchange_endpoints(&sim, sim.bind("10.0.0.2:2000"), uflow::StaticKey::generate(), client_cfg);
+
+    let mut client_errors = Vec::new();
+
+    sim.run(50, &mut server, &mut client, |_, event| {
+        panic!("unexpected event: {:?}", event);
+    }, |_, event| match event {
+        uflow::client::Event::Error(error) => client_errors.push(error),
+        other => panic!("unexpected event: {:?}", other),
+    });
+
+    assert_eq!(client_errors, vec![ uflow::client::ErrorType::KeyExchange ]);
+    assert!(server.clients().all(|client| !client.borrow().is_active()));
+}
+
+// A server with a static key should refuse clients which do not request a key exchange.
+#[test]
+fn key_exchange_required() {
+    let sim = Sim::new();
+
+    let (mut server, mut client) = key_exchange_endpoints(&sim, sim.bind("10.0.0.2:2000"), uflow::StaticKey::generate(), Default::default());
+
+    let mut client_errors = Vec::new();
+
+    sim.run(50, &mut server, &mut client, |_, event| {
+        panic!("unexpected event: {:?}", event);
+    }, |_, event| match event {
+        uflow::client::Event::Error(error) => client_errors.push(error),
+        other => panic!("unexpected event: {:?}", other),
+    });
+
+    assert_eq!(client_errors, vec![ uflow::client::ErrorType::Config ]);
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use uflow::Transport;
+
+use common::Sim;
+
+const CHANNELS: usize = 4;
+
+type Packets = Vec<Box<[u8]>>;
+
+// Sends a packet from the client on each of its channels, which the server echoes back on the
+// same channel. Returns the packets received by the server and by the client, and any client
+// errors.
+fn echo<S, C, const M: usize, const N: usize>(sim: &Sim,
+                                             server: &mut uflow::server::Server<S, M>,
+                                             client: &mut uflow::client::Client<C, N>)
+    -> (Packets, Packets, Vec<uflow::client::ErrorType>)
+    where S: Transport,
+          C: Transport
+{
+    let client_channels = client.channel_count();
+
+    for channel_id in 0 .. client_channels {
+        client.send(vec![ channel_id as u8 ].into(), channel_id, uflow::SendMode::Reliable);
+    }
+
+    let mut server_received = Vec::new();
+    let mut client_received = Vec::new();
+    let mut client_errors = Vec::new();
+
+    for _ in 0 .. 50 {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(address) => {
+                    assert_eq!(server.client(address).unwrap().borrow().channel_count(), client_channels);
+                }
+                uflow::server::Event::Receive(address, data) => {
+                    let channel_id = data[0] as usize;
+                    server.client(address).unwrap().borrow_mut().send(data.clone(), channel_id, uflow::SendMode::Reliable);
+                    server_received.push(data);
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => (),
+                uflow::client::Event::Receive(data) => client_received.push(data),
+                uflow::client::Event::Error(error) => client_errors.push(error),
+                uflow::client::Event::SendFailed(_) => (),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        server.flush();
+        client.flush();
+
+        sim.advance();
+    }
+
+    (server_received, client_received, client_errors)
+}
+
+fn expected_packets(channel_count: usize) -> Packets {
+    (0 .. channel_count).map(|channel_id| vec![ channel_id as u8 ].into()).collect()
+}
+
+fn fixed_server(sim: &Sim) -> uflow::server::Server<uflow::transport::Memory, CHANNELS> {
+    uflow::server::Server::with_fixed_channels(sim.bind(common::SERVER_ADDRESS), sim.server_config())
+}
+
+fn fixed_client(sim: &Sim) -> uflow::client::Client<uflow::transport::Memory, CHANNELS> {
+    uflow::client::Client::with_fixed_channels(sim.bind("10.0.0.2:2000"), common::address(common::SERVER_ADDRESS),
+                                               sim.client_config()).unwrap()
+}
+
+fn runtime_config(channel_count: usize) -> uflow::EndpointConfig {
+    uflow::EndpointConfig {
+        channel_count,
+        .. Default::default()
+    }
+}
+
+// A client and server with the same fixed channel count should exchange packets on every channel.
+#[test]
+fn fixed_channels_transfer() {
+    let sim = Sim::new();
+
+    let mut server = fixed_server(&sim);
+    let mut client = fixed_client(&sim);
+
+    assert_eq!(client.channel_count(), CHANNELS);
+
+    let (server_received, client_received, client_errors) = echo(&sim, &mut server, &mut client);
+
+    assert_eq!(server_received, expected_packets(CHANNELS));
+    assert_eq!(client_received, expected_packets(CHANNELS));
+    assert!(client_errors.is_empty());
+    assert_eq!(client.channel_stats(CHANNELS - 1).delivered, 1);
+}
+
+// Endpoints with fixed channel counts should interoperate with endpoints whose channel counts are
+// configured at runtime, subject to the usual negotiation.
+#[test]
+fn fixed_channels_runtime_peer() {
+    let sim = Sim::new();
+
+    let mut server = fixed_server(&sim);
+    let mut client = sim.client("10.0.0.3:3000", uflow::client::Config {
+        endpoint_config: runtime_config(2),
+        .. sim.client_config()
+    });
+
+    let (server_received, client_received, client_errors) = echo(&sim, &mut server, &mut client);
+
+    assert_eq!(server_received, expected_packets(2));
+    assert_eq!(client_received, expected_packets(2));
+    assert!(client_errors.is_empty());
+
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        endpoint_config: runtime_config(8),
+        .. sim.server_config()
+    });
+    let mut client = fixed_client(&sim);
+
+    let (server_received, client_received, client_errors) = echo(&sim, &mut server, &mut client);
+
+    assert_eq!(server_received, expected_packets(CHANNELS));
+    assert_eq!(client_received, expected_packets(CHANNELS));
+    assert!(client_errors.is_empty());
+}
+
+// A server with a fixed channel count should refuse a client which proposes more channels.
+#[test]
+fn fixed_channels_exceeds_server() {
+    let sim = Sim::new();
+
+    let mut server = fixed_server(&sim);
+    let mut client = sim.client("10.0.0.3:3000", uflow::client::Config {
+        endpoint_config: runtime_config(CHANNELS + 1),
+        .. sim.client_config()
+    });
+
+    let (server_received, client_received, client_errors) = echo(&sim, &mut server, &mut client);
+
+    assert!(server_received.is_empty());
+    assert!(client_received.is_empty());
+    assert_eq!(client_errors, vec![ uflow::client::ErrorType::Config ]);
+}
+
+// Sending on a channel beyond a fixed channel count is a usage error.
+#[test]
+#[should_panic]
+fn fixed_channels_invalid() {
+    let (transport, server_transport) = uflow::transport::Memory::pair();
+
+    let mut client = uflow::client::Client::<_, CHANNELS>::with_fixed_channels(transport, server_transport.address(),
+                                                                             Default::default()).unwrap();
+
+    client.send(vec![ 0 ].into(), CHANNELS, uflow::SendMode::Reliable);
+}
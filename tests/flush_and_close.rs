@@ -0,0 +1,75 @@
+mod common;
+
+use std::thread;
+use std::time;
+
+use common::Sim;
+
+static STEP_INTERVAL: time::Duration = time::Duration::from_millis(1);
+
+// A client should block until every packet it has sent is acknowledged, and then disconnect,
+// without the application driving the connection itself.
+#[test]
+fn flush_and_close_delivered() {
+    let network = uflow::transport::MemoryNetwork::new();
+    network.set_delay(time::Duration::from_millis(20));
+
+    let server_transport = network.bind("10.0.0.1:1000".parse().unwrap()).unwrap();
+    let server_address = server_transport.address();
+
+    let server_thread = thread::spawn(move || {
+        let mut server = uflow::server::Server::with_transport(server_transport, Default::default());
+
+        let mut received = Vec::new();
+        let end_time = time::Instant::now() + time::Duration::from_secs(5);
+
+        while time::Instant::now() < end_time {
+            for event in server.step() {
+                match event {
+                    uflow::server::Event::Connect(_) => (),
+                    uflow::server::Event::Receive(_, data) => received.push(data),
+                    uflow::server::Event::Disconnect(_) => return received,
+                    other => panic!("unexpected event: {:?}", other),
+                }
+            }
+
+            server.flush();
+
+            thread::sleep(STEP_INTERVAL);
+        }
+
+        panic!("[server] no disconnect event received");
+    });
+
+    let mut client = uflow::client::Client::with_transport(network.bind("10.0.0.2:2000".parse().unwrap()).unwrap(),
+                                                           server_address, Default::default()).unwrap();
+
+    let packets = (0 .. 20u8).map(|i| vec![ i; 3 * uflow::MAX_FRAGMENT_SIZE ].into()).collect::<Vec<Box<[u8]>>>();
+
+    for packet in packets.iter() {
+        client.send(packet.clone(), 0, uflow::SendMode::Reliable);
+    }
+
+    assert!(client.flush_and_close(time::Duration::from_secs(5)));
+    assert_eq!(client.send_buffer_size(), 0);
+
+    assert_eq!(server_thread.join().unwrap(), packets);
+}
+
+// A client should give up once the timeout elapses, measuring time with its own clock.
+#[test]
+fn flush_and_close_timeout() {
+    let sim = Sim::new();
+
+    // The server never responds
+    let _server_transport = sim.bind(common::SERVER_ADDRESS);
+
+    let mut client = sim.client("10.0.0.2:2000", sim.client_config());
+
+    client.send(vec![ 0 ].into(), 0, uflow::SendMode::Reliable);
+
+    assert!(!client.flush_and_close(time::Duration::from_secs(1)));
+    assert!(uflow::Clock::now(&sim.clock) >= time::Duration::from_secs(1));
+    assert!(uflow::Clock::now(&sim.clock) < time::Duration::from_secs(2));
+    assert!(!client.is_active());
+}
@@ -0,0 +1,102 @@
+use std::sync::atomic;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time;
+
+// Runs a client which sends a single packet to a server with the given frame hook, and returns the
+// packets received by the server.
+fn transfer(port: u16, frame_hook: uflow::FrameHook, connected: Arc<atomic::AtomicBool>) -> Vec<Box<[u8]>> {
+    let address = format!("127.0.0.1:{}", port);
+
+    let server_cfg = uflow::server::Config {
+        frame_hook: Some(frame_hook),
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind(address.as_str(), server_cfg).unwrap();
+    let mut client = uflow::client::Client::connect(address.as_str(), Default::default()).unwrap();
+
+    let mut received = Vec::new();
+    let mut sent = false;
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(1000);
+
+    while time::Instant::now() < end_time {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(_) => connected.store(true, atomic::Ordering::Relaxed),
+                uflow::server::Event::Receive(_, data) => received.push(data),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => (),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        // Send once the server has signaled the connection
+        if connected.load(atomic::Ordering::Relaxed) && !sent {
+            client.send([ 0, 1, 2 ].into(), 0, uflow::SendMode::Reliable);
+            sent = true;
+        }
+
+        server.flush();
+        client.flush();
+
+        thread::sleep(time::Duration::from_millis(20));
+    }
+
+    received
+}
+
+// Observed frames should be processed normally.
+#[test]
+fn frame_hook_observe() {
+    let frame_sizes = Arc::new(Mutex::new(Vec::new()));
+    let frame_sizes_hook = Arc::clone(&frame_sizes);
+
+    let frame_hook: uflow::FrameHook = Box::new(move |address, frame_data| {
+        assert_eq!(address.ip(), std::net::Ipv4Addr::LOCALHOST);
+        frame_sizes_hook.lock().unwrap().push(frame_data.len());
+        uflow::FrameAction::Process
+    });
+
+    let received = transfer(5671, frame_hook, Arc::new(atomic::AtomicBool::new(false)));
+
+    assert_eq!(received, vec![ [ 0, 1, 2 ].into() ]);
+
+    // The first frame is the connection request, which is padded to the maximum frame size, less
+    // the 28 bytes reserved for sealing
+    let frame_sizes = frame_sizes.lock().unwrap();
+    assert!(frame_sizes.len() > 1);
+    assert_eq!(frame_sizes[0], uflow::MAX_FRAME_SIZE - 28);
+}
+
+// Consumed frames should not be processed.
+#[test]
+fn frame_hook_consume() {
+    let connected = Arc::new(atomic::AtomicBool::new(false));
+    let connected_hook = Arc::clone(&connected);
+
+    let consumed_count = Arc::new(atomic::AtomicU32::new(0));
+    let consumed_count_hook = Arc::clone(&consumed_count);
+
+    // Allow the handshake to complete, then consume everything
+    let frame_hook: uflow::FrameHook = Box::new(move |_, _| {
+        if connected_hook.load(atomic::Ordering::Relaxed) {
+            consumed_count_hook.fetch_add(1, atomic::Ordering::Relaxed);
+            uflow::FrameAction::Consume
+        } else {
+            uflow::FrameAction::Process
+        }
+    });
+
+    let received = transfer(5672, frame_hook, connected);
+
+    assert!(received.is_empty());
+    assert!(consumed_count.load(atomic::Ordering::Relaxed) > 0);
+}
@@ -0,0 +1,99 @@
+use std::thread;
+use std::time;
+
+fn step_all(server: &mut uflow::server::Server, clients: &mut [uflow::client::Client], received: &mut [Vec<Box<[u8]>>]) {
+    for event in server.step() {
+        match event {
+            uflow::server::Event::Connect(_) => (),
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    for (client, received) in clients.iter_mut().zip(received.iter_mut()) {
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => (),
+                uflow::client::Event::Receive(data) => received.push(data),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+    }
+
+    server.flush();
+
+    for client in clients.iter_mut() {
+        client.flush();
+    }
+}
+
+fn step_for(ms: u64, server: &mut uflow::server::Server, clients: &mut [uflow::client::Client], received: &mut [Vec<Box<[u8]>>]) {
+    let end_time = time::Instant::now() + time::Duration::from_millis(ms);
+
+    while time::Instant::now() < end_time {
+        step_all(server, clients, received);
+        thread::sleep(time::Duration::from_millis(20));
+    }
+}
+
+// Group packets should only be delivered to subscribed clients, and subscription changes should
+// take effect on the server.
+#[test]
+fn send_group() {
+    let address = "127.0.0.1:5651";
+
+    let mut server = uflow::server::Server::bind(address, Default::default()).unwrap();
+
+    let mut clients: Vec<uflow::client::Client> = (0 .. 3).map(|_| {
+        uflow::client::Client::connect(address, Default::default()).unwrap()
+    }).collect();
+
+    let mut received = vec![Vec::new(); 3];
+
+    // Subscriptions made before the connection is established are sent once it is
+    clients[0].join_group("red");
+    clients[1].join_group("red");
+    clients[1].join_group("blue");
+    clients[1].join_group("blue");
+
+    assert_eq!(clients[1].groups().collect::<Vec<_>>(), vec![ "red", "blue" ]);
+
+    step_for(500, &mut server, &mut clients, &mut received);
+
+    let mut red_members: Vec<_> = server.group_members("red").map(|client_id| {
+        server.client(client_id).unwrap().borrow().address().port()
+    }).collect();
+    red_members.sort();
+    let mut expected: Vec<_> = vec![ clients[0].local_address().port(), clients[1].local_address().port() ];
+    expected.sort();
+    assert_eq!(red_members, expected);
+
+    server.send_group("red", [ 1 ].into(), 0, uflow::SendMode::Reliable);
+    server.send_group("blue", [ 2 ].into(), 0, uflow::SendMode::Reliable);
+    server.send_group("green", [ 3 ].into(), 0, uflow::SendMode::Reliable);
+
+    step_for(500, &mut server, &mut clients, &mut received);
+
+    let expected: Vec<Vec<Box<[u8]>>> = vec![
+        vec![ [ 1 ].into() ],
+        vec![ [ 1 ].into(), [ 2 ].into() ],
+        vec![],
+    ];
+    assert_eq!(received, expected);
+
+    // Leave one group, and join another
+    clients[1].leave_group("red");
+    clients[2].join_group("red");
+
+    step_for(500, &mut server, &mut clients, &mut received);
+
+    server.send_group("red", [ 4 ].into(), 0, uflow::SendMode::Reliable);
+
+    step_for(500, &mut server, &mut clients, &mut received);
+
+    let expected: Vec<Vec<Box<[u8]>>> = vec![
+        vec![ [ 1 ].into(), [ 4 ].into() ],
+        vec![ [ 1 ].into(), [ 2 ].into() ],
+        vec![ [ 4 ].into() ],
+    ];
+    assert_eq!(received, expected);
+}
@@ -0,0 +1,89 @@
+
+use std::net;
+use std::thread;
+use std::time;
+
+fn loopback_address(client: &uflow::client::Client) -> net::SocketAddr {
+    (net::Ipv4Addr::LOCALHOST, client.local_address().port()).into()
+}
+
+fn has_client(server: &uflow::server::Server, address: net::SocketAddr) -> bool {
+    server.clients().any(|client| client.borrow().address() == address)
+}
+
+#[test]
+fn half_open_eviction() {
+    let cfg = uflow::server::Config {
+        max_half_open_connections: 2,
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind("127.0.0.1:5591", cfg).unwrap();
+
+    let client_a = uflow::client::Client::connect("127.0.0.1:5591", Default::default()).unwrap();
+    thread::sleep(time::Duration::from_millis(50));
+    for _ in server.step() {}
+
+    let client_b = uflow::client::Client::connect("127.0.0.1:5591", Default::default()).unwrap();
+    thread::sleep(time::Duration::from_millis(50));
+    for _ in server.step() {}
+
+    assert_eq!(server.half_open_eviction_count(), 0);
+
+    let client_c = uflow::client::Client::connect("127.0.0.1:5591", Default::default()).unwrap();
+    thread::sleep(time::Duration::from_millis(50));
+    for _ in server.step() {}
+
+    // The oldest incomplete connection should have been forgotten
+    assert_eq!(server.half_open_eviction_count(), 1);
+    assert!(!has_client(&server, loopback_address(&client_a)));
+    assert!(has_client(&server, loopback_address(&client_b)));
+    assert!(has_client(&server, loopback_address(&client_c)));
+}
+
+// A repeated connection request should be answered immediately, rather than waiting for the
+// server's scheduled resend.
+#[test]
+fn duplicate_request_coalescing() {
+    let mut server = uflow::server::Server::bind("127.0.0.1:5592", Default::default()).unwrap();
+
+    // Discard the first SYN+ACK, so that the client must resend its request
+    let mut discarded = false;
+    let client_cfg = uflow::client::Config {
+        handshake_resend_interval_ms: 200,
+        frame_hook: Some(Box::new(move |_, _| {
+            if discarded {
+                uflow::FrameAction::Process
+            } else {
+                discarded = true;
+                uflow::FrameAction::Consume
+            }
+        })),
+        .. Default::default()
+    };
+
+    let mut client = uflow::client::Client::connect("127.0.0.1:5592", client_cfg).unwrap();
+
+    let mut connected = false;
+    let end_time = time::Instant::now() + time::Duration::from_millis(600);
+
+    while time::Instant::now() < end_time && !connected {
+        for _ in server.step() {}
+
+        for event in client.step() {
+            if let uflow::client::Event::Connect = event {
+                connected = true;
+            }
+        }
+
+        thread::sleep(time::Duration::from_millis(10));
+    }
+
+    // The server would not otherwise resend its reply for 1000ms
+    assert!(connected);
+
+    let stats = server.handshake_stats();
+    assert_eq!(stats.duplicate_requests, 1);
+    assert_eq!(stats.coalesced_replies, 1);
+    assert!(has_client(&server, loopback_address(&client)));
+}
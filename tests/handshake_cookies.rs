@@ -0,0 +1,35 @@
+mod common;
+
+use common::Sim;
+
+// A client should connect to a server requiring handshake cookies after one additional round trip,
+// while a client which never reads the server's cookie should not be allocated a connection.
+#[test]
+fn handshake_cookies() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        enable_handshake_cookies: true,
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client("10.0.0.2:2000", sim.client_config());
+
+    // This client sends its initial request, but is never stepped to receive a reply
+    let spoofed_client = sim.client("10.0.0.3:2000", sim.client_config());
+
+    let client_address = client.local_address();
+    let spoofed_address = spoofed_client.local_address();
+
+    let mut connected = Vec::new();
+
+    sim.run(20, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(client_id) => connected.push(server.client(client_id).unwrap().borrow().address()),
+        other => panic!("unexpected event: {:?}", other),
+    }, common::ignore_client);
+
+    assert!(client.is_active());
+    assert_eq!(connected, vec![ client_address ]);
+    assert!(server.clients().all(|client| client.borrow().address() != spoofed_address));
+    assert_eq!(server.handshake_stats().cookie_replies, 2);
+}
@@ -0,0 +1,85 @@
+use std::sync::atomic;
+use std::sync::Arc;
+use std::thread;
+use std::time;
+
+// Heartbeat payloads should be delivered to an endpoint which has enabled heartbeat events, and
+// should not be requested from a provider whose remote endpoint has not.
+#[test]
+fn heartbeat_one_sided() {
+    let address = "127.0.0.1:5711";
+
+    let server_ticks = Arc::new(atomic::AtomicU32::new(0));
+    let client_requests = Arc::new(atomic::AtomicU32::new(0));
+
+    let server_ticks_clone = server_ticks.clone();
+    let client_requests_clone = client_requests.clone();
+
+    let server_cfg = uflow::server::Config {
+        endpoint_config: uflow::EndpointConfig {
+            keepalive_interval_ms: 100,
+            .. Default::default()
+        },
+        heartbeat_provider: Some(Box::new(move |_| {
+            let tick = server_ticks_clone.fetch_add(1, atomic::Ordering::Relaxed) + 1;
+            Some(tick.to_be_bytes().into())
+        })),
+        .. Default::default()
+    };
+
+    let client_cfg = uflow::client::Config {
+        endpoint_config: uflow::EndpointConfig {
+            keepalive_interval_ms: 100,
+            heartbeat_events: true,
+            .. Default::default()
+        },
+        heartbeat_provider: Some(Box::new(move |_| {
+            client_requests_clone.fetch_add(1, atomic::Ordering::Relaxed);
+            Some([ 0xFF ].into())
+        })),
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind(address, server_cfg).unwrap();
+    let mut client = uflow::client::Client::connect(address, client_cfg).unwrap();
+
+    let mut heartbeats = Vec::new();
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(4500);
+
+    while time::Instant::now() < end_time {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(_) => (),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => (),
+                uflow::client::Event::Heartbeat(data) => heartbeats.push(data),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        server.flush();
+        client.flush();
+
+        thread::sleep(time::Duration::from_millis(20));
+    }
+
+    assert!(!heartbeats.is_empty());
+    assert!(heartbeats.len() as u32 <= server_ticks.load(atomic::Ordering::Relaxed));
+
+    for (i, data) in heartbeats.iter().enumerate() {
+        assert_eq!(data.len(), 4);
+
+        // Payloads arrive in the order they were provided, barring loss
+        if i > 0 {
+            assert!(data[..] > heartbeats[i - 1][..]);
+        }
+    }
+
+    assert_eq!(client_requests.load(atomic::Ordering::Relaxed), 0);
+}
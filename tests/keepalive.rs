@@ -0,0 +1,51 @@
+mod common;
+
+use std::time;
+
+use common::{ Sim, STEP_INTERVAL };
+
+// Runs an idle connection for the given duration, and returns whether the client and server
+// remain connected.
+fn idle_connection(keepalive: bool, duration: time::Duration) -> (bool, bool) {
+    let sim = Sim::with_delay(time::Duration::from_millis(20));
+
+    let endpoint_config = uflow::EndpointConfig {
+        keepalive,
+        keepalive_interval_ms: 1000,
+        active_timeout_ms: 5000,
+        .. Default::default()
+    };
+
+    let mut server = sim.server(uflow::server::Config {
+        endpoint_config: endpoint_config.clone(),
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client("10.0.0.2:1000", uflow::client::Config {
+        endpoint_config,
+        .. sim.client_config()
+    });
+
+    let client_address = client.local_address();
+
+    let steps = duration.as_millis() / STEP_INTERVAL.as_millis();
+
+    sim.run(steps as usize, &mut server, &mut client, common::ignore, common::ignore_client);
+
+    let server_active = server.clients().map(|client| client.borrow())
+                                        .any(|client| client.address() == client_address && client.is_active());
+
+    (client.is_active(), server_active)
+}
+
+// Keepalive frames should prevent an idle connection from timing out.
+#[test]
+fn keepalive_idle() {
+    assert_eq!(idle_connection(true, time::Duration::from_secs(30)), (true, true));
+}
+
+// Without keepalive frames, an idle connection should time out at both ends.
+#[test]
+fn keepalive_disabled_idle() {
+    assert_eq!(idle_connection(false, time::Duration::from_secs(30)), (false, false));
+}
@@ -0,0 +1,50 @@
+use std::thread;
+use std::time;
+
+// Keyed packets sent over a lossless connection should all be delivered, in the order sent.
+#[test]
+fn keyed_delivery() {
+    let address = "127.0.0.1:5691";
+
+    let mut server = uflow::server::Server::bind(address, Default::default()).unwrap();
+    let mut client = uflow::client::Client::connect(address, Default::default()).unwrap();
+
+    for i in 0 .. 30u8 {
+        match i % 3 {
+            0 => {
+                client.send(vec![ i ].into(), 0, uflow::SendMode::Reliable);
+            }
+            _ => {
+                client.send_keyed(vec![ i ].into(), 0, (i % 3) as u16, uflow::SendMode::Reliable);
+            }
+        }
+    }
+
+    let mut received = Vec::new();
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(1000);
+
+    while time::Instant::now() < end_time {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(_) => (),
+                uflow::server::Event::Receive(_, data) => received.push(data[0]),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => (),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        server.flush();
+        client.flush();
+
+        thread::sleep(time::Duration::from_millis(20));
+    }
+
+    assert_eq!(received, (0 .. 30).collect::<Vec<u8>>());
+}
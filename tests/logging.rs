@@ -0,0 +1,54 @@
+use std::fmt;
+use std::net;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time;
+
+struct TestLogger {
+    messages: Arc<Mutex<Vec<(uflow::LogLevel, net::SocketAddr, String)>>>,
+}
+
+impl uflow::Logger for TestLogger {
+    fn log(&mut self, level: uflow::LogLevel, address: net::SocketAddr, message: fmt::Arguments) {
+        self.messages.lock().unwrap().push((level, address, message.to_string()));
+    }
+}
+
+// Repeated decode failures should produce a single warning, attributed to the remote host.
+#[test]
+fn decode_failure_warning() {
+    let messages = Arc::new(Mutex::new(Vec::new()));
+
+    let cfg = uflow::client::Config {
+        logger: Some(Box::new(TestLogger { messages: Arc::clone(&messages) })),
+        .. Default::default()
+    };
+
+    let server_socket = net::UdpSocket::bind("127.0.0.1:5601").unwrap();
+    server_socket.set_read_timeout(Some(time::Duration::from_millis(1000))).unwrap();
+
+    let mut client = uflow::client::Client::connect("127.0.0.1:5601", cfg).unwrap();
+
+    // Learn the client's address from its connection request
+    let mut buf = [0; 1500];
+    let (_, client_addr) = server_socket.recv_from(&mut buf).unwrap();
+
+    for _ in 0 .. 9 {
+        server_socket.send_to(&[ 0xFF; 8 ], client_addr).unwrap();
+    }
+
+    thread::sleep(time::Duration::from_millis(50));
+
+    for event in client.step() {
+        panic!("unexpected event: {:?}", event);
+    }
+
+    let messages = messages.lock().unwrap();
+    assert_eq!(messages.len(), 1);
+
+    let (level, address, ref message) = messages[0];
+    assert_eq!(level, uflow::LogLevel::Warn);
+    assert_eq!(address, "127.0.0.1:5601".parse().unwrap());
+    assert!(message.contains("could not be decoded"));
+}
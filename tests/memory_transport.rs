@@ -0,0 +1,50 @@
+mod common;
+
+use std::time;
+
+use common::Sim;
+
+// A client and server should be able to connect and exchange packets over an in-memory network,
+// and should observe its configured delay, without real sockets or waiting in real time.
+#[test]
+fn memory_transport_delay() {
+    let sim = Sim::with_delay(time::Duration::from_millis(50));
+
+    let mut server = sim.server(sim.server_config());
+    let mut client = sim.client("10.0.0.2:0", sim.client_config());
+
+    let client_address = client.local_address();
+
+    let mut server_received = Vec::new();
+    let mut client_received = Vec::new();
+    let mut connect_time = None;
+
+    for step in 0 .. 200 {
+        sim.step(&mut server, &mut client, |server, event| match event {
+            uflow::server::Event::Connect(client_id) => {
+                let mut remote_client = server.client(client_id).unwrap().borrow_mut();
+                assert_eq!(remote_client.address(), client_address);
+
+                remote_client.send([ 0, 1, 2 ].into(), 0, uflow::SendMode::Reliable);
+            }
+            uflow::server::Event::Receive(_, data) => server_received.push(data),
+            other => panic!("unexpected event: {:?}", other),
+        }, |client, event| match event {
+            uflow::client::Event::Connect => {
+                connect_time = Some(step);
+                client.send([ 3, 4, 5 ].into(), 0, uflow::SendMode::Reliable);
+            }
+            uflow::client::Event::Receive(data) => client_received.push(data),
+            other => panic!("unexpected event: {:?}", other),
+        });
+    }
+
+    assert_eq!(server_received, vec![ [ 3, 4, 5 ].into() ]);
+    assert_eq!(client_received, vec![ [ 0, 1, 2 ].into() ]);
+
+    // The handshake requires at least two trips across the network
+    assert!(connect_time.unwrap() >= 10);
+
+    let rtt_s = client.link_stats().rtt_s.unwrap();
+    assert!((0.1 .. 0.15).contains(&rtt_s), "rtt_s: {}", rtt_s);
+}
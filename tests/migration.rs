@@ -0,0 +1,233 @@
+mod common;
+
+use std::cell::RefCell;
+use std::io;
+use std::net;
+use std::rc::Rc;
+
+use uflow::Transport;
+
+use common::Sim;
+
+// A client transport whose underlying endpoint may be replaced, as though the client had switched
+// networks
+#[derive(Clone)]
+struct Rebindable {
+    inner: Rc<RefCell<uflow::transport::Memory>>,
+}
+
+impl Rebindable {
+    fn new(sim: &Sim, address: net::SocketAddr) -> Self {
+        Self { inner: Rc::new(RefCell::new(sim.network.bind(address).unwrap())) }
+    }
+
+    fn rebind(&self, sim: &Sim, address: net::SocketAddr) {
+        *self.inner.borrow_mut() = sim.network.bind(address).unwrap();
+    }
+}
+
+impl Transport for Rebindable {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        self.inner.borrow().send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        self.inner.borrow().recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.inner.borrow().local_addr()
+    }
+}
+
+// A client whose address changes mid-connection should continue its connection from the new
+// address once it has answered the server's path challenge, without losing reliable data.
+#[test]
+fn migration_rebind() {
+    let sim = Sim::new();
+
+    let old_address = common::address("10.0.0.2:1000");
+    let new_address = common::address("10.0.0.3:2000");
+
+    let client_transport = Rebindable::new(&sim, old_address);
+
+    let mut server = sim.server(uflow::server::Config {
+        enable_migration: true,
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client_with_transport(client_transport.clone(), sim.client_config());
+
+    let mut client_id = None;
+    let mut migrate_seen = false;
+    let mut server_received = Vec::new();
+
+    for step in 0 .. 300 {
+        if step == 100 {
+            assert!(client_id.is_some());
+            client_transport.rebind(&sim, new_address);
+        }
+
+        if (50 .. 150).contains(&step) {
+            client.send(vec![ step as u8 ].into(), 0, uflow::SendMode::Reliable);
+        }
+
+        sim.step(&mut server, &mut client, |server, event| match event {
+            uflow::server::Event::Connect(id) => {
+                assert_eq!(server.client(id).unwrap().borrow().address(), old_address);
+                client_id = Some(id);
+            }
+            uflow::server::Event::Migrate(id, from, to) => {
+                assert_eq!(Some(id), client_id);
+                assert_eq!(from, old_address);
+                assert_eq!(to, new_address);
+                migrate_seen = true;
+            }
+            uflow::server::Event::Receive(_, data) => server_received.push(data[0]),
+            other => panic!("unexpected event: {:?}", other),
+        }, common::expect_client_connect);
+    }
+
+    assert!(migrate_seen);
+
+    let remote_client = server.client(client_id.unwrap()).unwrap().borrow();
+    assert_eq!(remote_client.address(), new_address);
+    assert!(remote_client.is_active());
+    assert_eq!(server_received, (50 .. 150).map(|step| step as u8).collect::<Vec<_>>());
+}
+
+// Unless migration is enabled, a client whose address changes should be treated as unknown.
+#[test]
+fn migration_disabled() {
+    let sim = Sim::new();
+
+    let old_address = common::address("10.0.0.2:1000");
+    let new_address = common::address("10.0.0.3:2000");
+
+    let client_transport = Rebindable::new(&sim, old_address);
+
+    let mut server = sim.server(sim.server_config());
+    let mut client = sim.client_with_transport(client_transport.clone(), sim.client_config());
+
+    for step in 0 .. 200 {
+        if step == 100 {
+            client_transport.rebind(&sim, new_address);
+        }
+
+        if step >= 100 {
+            client.send(vec![ step as u8 ].into(), 0, uflow::SendMode::Reliable);
+        }
+
+        sim.step(&mut server, &mut client, common::expect_connect, common::ignore_client);
+    }
+
+    assert_eq!(server.clients().map(|client| client.borrow().address()).collect::<Vec<_>>(), vec![ old_address ]);
+    assert!(server.unknown_address_stats().frames > 0);
+}
+
+// A client which was assigned a connection ID should keep it across a migration, and the server
+// should continue to accept the frames it tags from its new address.
+#[test]
+fn migration_connection_ids() {
+    let sim = Sim::new();
+
+    let old_address = common::address("10.0.0.2:1000");
+    let new_address = common::address("10.0.0.3:2000");
+
+    let client_transport = Rebindable::new(&sim, old_address);
+
+    let mut server = sim.server(uflow::server::Config {
+        enable_migration: true,
+        enable_connection_ids: true,
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client_with_transport(client_transport.clone(), sim.client_config());
+
+    let mut migrate_seen = false;
+    let mut server_received = Vec::new();
+
+    for step in 0 .. 300 {
+        if step == 100 {
+            client_transport.rebind(&sim, new_address);
+        }
+
+        if (50 .. 150).contains(&step) {
+            client.send(vec![ step as u8 ].into(), 0, uflow::SendMode::Reliable);
+        }
+
+        sim.step(&mut server, &mut client, |_, event| match event {
+            uflow::server::Event::Connect(_) => (),
+            uflow::server::Event::Migrate(..) => migrate_seen = true,
+            uflow::server::Event::Receive(_, data) => server_received.push(data[0]),
+            other => panic!("unexpected event: {:?}", other),
+        }, common::ignore_client);
+    }
+
+    assert!(migrate_seen);
+    assert!(client.connection_id().is_some());
+    assert_eq!(server.client(common::client_id(&server, new_address)).unwrap().borrow().connection_id(), client.connection_id());
+    assert_eq!(server_received, (50 .. 150).map(|step| step as u8).collect::<Vec<_>>());
+}
+
+// A client which has agreed upon session keys with the server should be recognized by them at its
+// new address, and its connection should continue there, still sealed with the same keys.
+#[cfg(feature = "encryption")]
+#[test]
+fn migration_key_exchange() {
+    let sim = Sim::new();
+
+    let old_address = common::address("10.0.0.2:1000");
+    let new_address = common::address("10.0.0.3:2000");
+
+    let client_transport = Rebindable::new(&sim, old_address);
+
+    let mut server = sim.server(uflow::server::Config {
+        enable_migration: true,
+        static_key: Some(uflow::StaticKey::generate()),
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client_with_transport(client_transport.clone(), uflow::client::Config {
+        key_exchange: true,
+        .. sim.client_config()
+    });
+
+    let mut migrate_seen = false;
+    let mut server_received = Vec::new();
+    let mut client_received = Vec::new();
+
+    for step in 0 .. 300 {
+        if step == 100 {
+            client_transport.rebind(&sim, new_address);
+        }
+
+        if (50 .. 150).contains(&step) {
+            client.send(vec![ step as u8 ].into(), 0, uflow::SendMode::Reliable);
+        }
+
+        sim.step(&mut server, &mut client, |server, event| match event {
+            uflow::server::Event::Connect(_) => (),
+            uflow::server::Event::Migrate(_, from, to) => {
+                assert_eq!(from, old_address);
+                assert_eq!(to, new_address);
+                migrate_seen = true;
+            }
+            uflow::server::Event::Receive(client_id, data) => {
+                server.client(client_id).unwrap().borrow_mut().send(data.clone(), 0, uflow::SendMode::Reliable);
+
+                server_received.push(data[0]);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }, |_, event| match event {
+            uflow::client::Event::Connect => (),
+            uflow::client::Event::Receive(data) => client_received.push(data[0]),
+            other => panic!("unexpected event: {:?}", other),
+        });
+    }
+
+    assert!(migrate_seen);
+    assert!(server.client(common::client_id(&server, new_address)).unwrap().borrow().is_active());
+    assert_eq!(server_received, (50 .. 150).map(|step| step as u8).collect::<Vec<_>>());
+    assert_eq!(client_received, server_received);
+}
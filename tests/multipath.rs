@@ -0,0 +1,182 @@
+mod common;
+
+use std::cell::Cell;
+use std::io;
+use std::net;
+use std::rc::Rc;
+
+use uflow::Transport;
+
+use common::Sim;
+
+// A transport which silently discards every datagram in either direction while it is down
+struct Link {
+    inner: uflow::transport::Memory,
+    down: Rc<Cell<bool>>,
+}
+
+impl Transport for Link {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        if self.down.get() {
+            return Ok(buf.len());
+        }
+
+        self.inner.send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        let result = self.inner.recv_from(buf);
+
+        if self.down.get() && result.is_ok() {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        result
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+fn link(sim: &Sim, address: &str) -> Link {
+    Link {
+        inner: sim.bind(address),
+        down: Rc::new(Cell::new(false)),
+    }
+}
+
+struct Harness {
+    sim: Sim,
+    server: common::Server,
+    client: uflow::client::Client<Link>,
+    downs: Vec<Rc<Cell<bool>>>,
+    addresses: Vec<net::SocketAddr>,
+    received: Vec<Box<[u8]>>,
+}
+
+impl Harness {
+    // Creates a client which sends over two paths to a server which permits it
+    fn new() -> Self {
+        let sim = Sim::new();
+
+        let server = sim.server(uflow::server::Config {
+            enable_connection_ids: true,
+            enable_multipath: true,
+            .. sim.server_config()
+        });
+
+        let links = [ link(&sim, "10.0.0.2:2000"), link(&sim, "10.0.0.3:3000") ];
+        let downs = links.iter().map(|link| Rc::clone(&link.down)).collect();
+        let addresses = links.iter().map(|link| link.local_addr().unwrap()).collect();
+
+        let [ primary, secondary ] = links;
+        let mut client = sim.client_with_transport(primary, sim.client_config());
+        assert_eq!(client.add_path(secondary).unwrap(), 1);
+
+        Self { sim, server, client, downs, addresses, received: Vec::new() }
+    }
+
+    // Sends the given number of packets from the client to the server, one per step, then steps
+    // until the last has had time to arrive
+    fn run(&mut self, count: u32, first_id: u32) {
+        for i in 0 .. count + 20 {
+            if i < count {
+                let data = (first_id + i).to_be_bytes();
+                self.client.send(data.into(), 0, uflow::SendMode::Reliable);
+            }
+
+            let received = &mut self.received;
+
+            self.sim.step(&mut self.server, &mut self.client, |_, event| match event {
+                uflow::server::Event::Connect(_) => (),
+                uflow::server::Event::Receive(_, data) => received.push(data),
+                other => panic!("unexpected event: {:?}", other),
+            }, common::expect_client_connect);
+        }
+    }
+
+    fn expected(count: u32) -> Vec<Box<[u8]>> {
+        (0 .. count).map(|i| i.to_be_bytes().into()).collect()
+    }
+}
+
+// A client with two working paths should have the server validate the second, and should spread
+// its data over both.
+#[test]
+fn multipath_transfer() {
+    let mut h = Harness::new();
+
+    h.run(200, 0);
+
+    assert_eq!(h.received, Harness::expected(200));
+
+    let client_rc = h.server.client(common::client_id(&h.server, h.addresses[0])).unwrap();
+    assert_eq!(client_rc.borrow().paths(), &[ h.addresses[1] ]);
+
+    for path in 0 .. 2 {
+        let stats = h.client.path_stats(path);
+        assert!(stats.alive);
+        assert!(stats.rtt_s.is_some());
+        assert!(stats.sent_frames > 10, "path {}: {:?}", path, stats);
+    }
+}
+
+// Once one path stops delivering, data should move to the remaining path without loss.
+#[test]
+fn multipath_failover() {
+    let mut h = Harness::new();
+
+    h.run(100, 0);
+    assert!(h.client.path_stats(1).alive);
+
+    h.downs[0].set(true);
+
+    h.run(200, 100);
+
+    assert_eq!(h.received, Harness::expected(300));
+
+    assert!(!h.client.path_stats(0).alive);
+    assert!(h.client.path_stats(1).alive);
+
+    // The failed path recovers once frames are again received over it
+    let sent_frames = h.client.path_stats(0).sent_frames;
+
+    h.downs[0].set(false);
+
+    h.run(200, 300);
+
+    assert_eq!(h.received, Harness::expected(500));
+
+    assert!(h.client.path_stats(0).alive);
+    assert!(h.client.path_stats(0).sent_frames > sent_frames);
+}
+
+// A server which does not permit multiple paths should leave additional paths unused.
+#[test]
+fn multipath_disabled() {
+    let (transport, server_transport) = uflow::transport::Memory::pair();
+    let network = uflow::transport::MemoryNetwork::new();
+
+    let mut client = uflow::client::Client::with_transport(transport, server_transport.address(), Default::default()).unwrap();
+
+    for i in 1 .. uflow::MAX_PATHS {
+        assert_eq!(client.add_path(network.bind(format!("10.0.0.2:{}", i).parse().unwrap()).unwrap()).unwrap(), i);
+    }
+
+    let err = client.add_path(network.bind("10.0.0.3:3000".parse().unwrap()).unwrap()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    let mut server = uflow::server::Server::with_transport(server_transport, Default::default());
+
+    for _ in 0 .. 10 {
+        for _ in server.step() {}
+        for _ in client.step() {}
+        client.flush();
+        server.flush();
+    }
+
+    assert!(client.is_active());
+    assert_eq!(client.path_count(), uflow::MAX_PATHS);
+    assert_eq!(client.path_stats(1), uflow::PathStats::default());
+}
@@ -0,0 +1,98 @@
+
+use std::thread;
+use std::time;
+
+// Two connections which share a socket should each reach their own server, and packets received
+// from either server should be attributed to the correct connection.
+#[test]
+fn multiplexed_connections() {
+    let mut server_a = uflow::server::Server::bind("127.0.0.1:5561", Default::default()).unwrap();
+    let mut server_b = uflow::server::Server::bind("127.0.0.1:5562", Default::default()).unwrap();
+
+    let mut mux = uflow::client::Multiplexer::bind("127.0.0.1:0").unwrap();
+
+    let addr_a = mux.connect("127.0.0.1:5561", Default::default()).unwrap();
+    let addr_b = mux.connect("127.0.0.1:5562", Default::default()).unwrap();
+
+    assert_eq!(mux.connect("127.0.0.1:5561", Default::default()).unwrap_err().kind(),
+               std::io::ErrorKind::AlreadyExists);
+    assert_eq!(mux.client_count(), 2);
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(4000);
+
+    let mut connect_count = 0;
+    let mut received_a = Vec::new();
+    let mut received_b = Vec::new();
+
+    while (received_a.is_empty() || received_b.is_empty()) && time::Instant::now() < end_time {
+        for event in server_a.step() {
+            match event {
+                uflow::server::Event::Connect(client_id) => {
+                    let mut remote_client = server_a.client(client_id).unwrap().borrow_mut();
+                    assert_eq!(remote_client.address(), mux.local_address());
+                    remote_client.send([ 0xAA ].into(), 0, uflow::SendMode::Reliable);
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in server_b.step() {
+            match event {
+                uflow::server::Event::Connect(client_id) => {
+                    let mut remote_client = server_b.client(client_id).unwrap().borrow_mut();
+                    assert_eq!(remote_client.address(), mux.local_address());
+                    remote_client.send([ 0xBB ].into(), 0, uflow::SendMode::Reliable);
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        server_a.flush();
+        server_b.flush();
+
+        thread::sleep(time::Duration::from_millis(50));
+
+        for (remote_addr, event) in mux.step() {
+            match event {
+                uflow::client::Event::Connect => connect_count += 1,
+                uflow::client::Event::Receive(data) if remote_addr == addr_a => received_a.push(data),
+                uflow::client::Event::Receive(data) if remote_addr == addr_b => received_b.push(data),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+    }
+
+    assert_eq!(connect_count, 2);
+    assert_eq!(received_a, vec![ [ 0xAA ].into() ]);
+    assert_eq!(received_b, vec![ [ 0xBB ].into() ]);
+
+    assert!(mux.client(&addr_a).unwrap().is_active());
+    assert!(mux.client(&addr_b).unwrap().is_active());
+
+    // Connections are closed via their handles, and forgotten once the servers have replied
+    mux.client_mut(&addr_a).unwrap().disconnect();
+    mux.client_mut(&addr_b).unwrap().disconnect_now();
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(4000);
+    let mut disconnect_count = 0;
+
+    while mux.client_count() > 0 && time::Instant::now() < end_time {
+        for _ in server_a.step() {}
+        for _ in server_b.step() {}
+
+        server_a.flush();
+        server_b.flush();
+
+        thread::sleep(time::Duration::from_millis(50));
+
+        for (_, event) in mux.step() {
+            match event {
+                uflow::client::Event::Disconnect => disconnect_count += 1,
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+    }
+
+    assert_eq!(disconnect_count, 2);
+    assert_eq!(mux.client_count(), 0);
+}
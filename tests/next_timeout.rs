@@ -0,0 +1,74 @@
+use std::thread;
+use std::time;
+
+// An idle connection should not require service until a sync or timeout is due, whereas pending
+// outbound packets should require immediate service.
+#[test]
+fn next_timeout() {
+    let address = "127.0.0.1:5701";
+
+    let mut server = uflow::server::Server::bind(address, Default::default()).unwrap();
+    let mut client = uflow::client::Client::connect(address, Default::default()).unwrap();
+
+    assert!(client.next_timeout().is_some());
+
+    let mut client_id = None;
+    let mut received = 0;
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(500);
+
+    while time::Instant::now() < end_time {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(id) => {
+                    client_id = Some(id);
+
+                    client.send([ 0, 1, 2 ].into(), 0, uflow::SendMode::Unreliable);
+                    assert_eq!(client.next_timeout(), Some(time::Duration::ZERO));
+                }
+                uflow::server::Event::Receive(..) => received += 1,
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => (),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        server.flush();
+        client.flush();
+
+        thread::sleep(time::Duration::from_millis(20));
+    }
+
+    assert!(client_id.is_some());
+    assert_eq!(received, 1);
+
+    // An idle server wakes only a handful of times
+    let mut wakeup_count = 0;
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(1500);
+
+    while time::Instant::now() < end_time {
+        let timeout = server.next_timeout().unwrap().min(end_time - time::Instant::now());
+
+        thread::sleep(timeout);
+
+        assert_eq!(server.step().count(), 0);
+        server.flush();
+
+        wakeup_count += 1;
+    }
+
+    assert!(wakeup_count < 10);
+
+    assert!(client.next_timeout().unwrap() > time::Duration::from_millis(500));
+
+    // Enqueued packets are due immediately
+    server.client(client_id.unwrap()).unwrap().borrow_mut().send([ 0, 1, 2 ].into(), 0, uflow::SendMode::Unreliable);
+
+    assert_eq!(server.next_timeout(), Some(time::Duration::ZERO));
+}
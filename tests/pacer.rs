@@ -0,0 +1,69 @@
+mod common;
+
+use std::net;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use common::Sim;
+
+#[derive(Default)]
+struct PacerState {
+    open: bool,
+    max_bytes_in_flight: usize,
+    bytes_sent: usize,
+}
+
+struct SharedPacer(Arc<Mutex<PacerState>>);
+
+impl uflow::Pacer for SharedPacer {
+    fn grant(&mut self, _address: net::SocketAddr, budget: usize, bytes_in_flight: usize) -> usize {
+        let mut state = self.0.lock().unwrap();
+        state.max_bytes_in_flight = state.max_bytes_in_flight.max(bytes_in_flight);
+        if state.open { budget } else { 0 }
+    }
+
+    fn sent(&mut self, _address: net::SocketAddr, bytes: usize) {
+        self.0.lock().unwrap().bytes_sent += bytes;
+    }
+}
+
+// A client should send no data while its pacer grants no budget, and should send all of its data
+// once the pacer opens, reporting the bytes it has in flight and the bytes it has sent.
+#[test]
+fn pacer_budget() {
+    let sim = Sim::new();
+
+    let pacer_state = Arc::new(Mutex::new(PacerState::default()));
+
+    let mut server = sim.server(sim.server_config());
+
+    let mut client = sim.client("10.0.0.2:2000", uflow::client::Config {
+        pacer: Some(Box::new(SharedPacer(Arc::clone(&pacer_state)))),
+        .. sim.client_config()
+    });
+
+    for i in 0 .. 10 {
+        client.send(vec![ i; 500 ].into(), 0, uflow::SendMode::Reliable);
+    }
+
+    let mut received = Vec::new();
+
+    for step in 0 .. 100 {
+        if step == 50 {
+            assert!(received.is_empty());
+            assert_eq!(pacer_state.lock().unwrap().max_bytes_in_flight, 0);
+            pacer_state.lock().unwrap().open = true;
+        }
+
+        sim.step(&mut server, &mut client, |_, event| {
+            if let uflow::server::Event::Receive(_, data) = event {
+                received.push(data[0]);
+            }
+        }, common::ignore_client);
+    }
+
+    assert_eq!(received, (0 .. 10).collect::<Vec<_>>());
+    assert!(pacer_state.lock().unwrap().max_bytes_in_flight > 0);
+    assert!(pacer_state.lock().unwrap().bytes_sent >= 5000);
+    assert_eq!(client.link_stats().bytes_in_flight, 0);
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "parallel")]
+
+mod common;
+
+use common::Sim;
+
+fn endpoint_config() -> uflow::EndpointConfig {
+    uflow::EndpointConfig {
+        session_keys: Some(uflow::SessionKeys {
+            client_to_server: [0x5A; 32],
+            server_to_client: [0xA5; 32],
+        }),
+        .. Default::default()
+    }
+}
+
+// A broadcast flushed in parallel should reach every client, with each client's packets arriving
+// in the order they were sent.
+#[test]
+fn parallel_broadcast() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(uflow::server::Config {
+        endpoint_config: endpoint_config(),
+        parallel_flush: true,
+        .. sim.server_config()
+    });
+
+    let mut clients = (0 .. 16).map(|i| {
+        sim.client(&format!("10.0.0.2:{}", 2000 + i), uflow::client::Config {
+            endpoint_config: endpoint_config(),
+            .. sim.client_config()
+        })
+    }).collect::<Vec<_>>();
+
+    let mut received = vec![ Vec::new(); clients.len() ];
+
+    for step in 0 .. 100 {
+        if (10 .. 30).contains(&step) {
+            server.broadcast(vec![ step as u8 ].into(), 0, uflow::SendMode::Reliable);
+        }
+
+        sim.step_all(&mut server, &mut clients, common::ignore, |index, _, event| match event {
+            uflow::client::Event::Connect => (),
+            uflow::client::Event::Receive(data) => received[index].push(data[0]),
+            other => panic!("unexpected event: {:?}", other),
+        });
+    }
+
+    assert!(!server.is_send_blocked());
+
+    for received in received {
+        assert_eq!(received, (10 .. 30).collect::<Vec<u8>>());
+    }
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::Sim;
+
+// Packets on a paused channel should be withheld, while packets on other channels are delivered,
+// and all withheld packets should be delivered in order once the channel is resumed.
+#[test]
+fn pause_receive() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(sim.server_config());
+    let mut client = sim.client("10.0.0.2:1000", sim.client_config());
+
+    let client_address = client.local_address();
+
+    client.pause_receive(0);
+
+    let mut received = Vec::new();
+
+    for step in 0 .. 200 {
+        if step == 150 {
+            client.resume_receive(0);
+        }
+
+        if step == 100 {
+            // Only packets on the unpaused channel have been delivered
+            assert_eq!(received, (20 .. 40).filter(|step| step % 2 == 1).collect::<Vec<_>>());
+        }
+
+        if (20 .. 40).contains(&step) {
+            let mut remote_client = server.client(common::client_id(&server, client_address)).unwrap().borrow_mut();
+            remote_client.send(vec![ step as u8 ].into(), (step % 2) as usize, uflow::SendMode::Reliable);
+        }
+
+        sim.step(&mut server, &mut client, common::ignore, |_, event| {
+            if let uflow::client::Event::Receive(data) = event {
+                received.push(data[0]);
+            }
+        });
+    }
+
+    let withheld = received.split_off(10);
+
+    assert_eq!(withheld, (20 .. 40).filter(|step| step % 2 == 0).collect::<Vec<_>>());
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use std::time;
+
+use common::Sim;
+
+// Connects a client with the given probe rate to a server over a network with a 50ms delay, and
+// steps both for three seconds without sending any packets. Returns the client's final link
+// statistics.
+fn idle(probe_rate: u32) -> uflow::LinkStats {
+    let sim = Sim::with_delay(time::Duration::from_millis(50));
+
+    let mut server = sim.server(sim.server_config());
+
+    let mut client = sim.client("10.0.0.2:2000", uflow::client::Config {
+        endpoint_config: uflow::EndpointConfig {
+            probe_rate,
+            .. Default::default()
+        },
+        .. sim.client_config()
+    });
+
+    // Probes are never delivered to the application
+    sim.run(300, &mut server, &mut client, common::expect_connect, common::expect_client_connect);
+
+    client.link_stats()
+}
+
+// An idle connection without probes should have no RTT estimate.
+#[test]
+fn probe_disabled() {
+    let stats = idle(0);
+
+    assert_eq!(stats.rtt_s, None);
+    assert_eq!(stats.probe_bytes, 0);
+}
+
+// An idle connection with probes should measure its RTT, while sending no more than its probe
+// budget.
+#[test]
+fn probe_idle() {
+    let stats = idle(10_000);
+
+    assert_eq!(stats.rtt_s, Some(0.1));
+    assert!(stats.probe_bytes > 20_000 && stats.probe_bytes <= 30_000, "{}", stats.probe_bytes);
+}
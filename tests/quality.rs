@@ -0,0 +1,64 @@
+mod common;
+
+use std::time;
+
+use common::Sim;
+
+// Connects a client to a server over a network with the given delay, and sends a packet from the
+// client every few steps. Returns the quality scores signaled to the client, and its final score.
+fn measure(delay: time::Duration) -> (Vec<u8>, Option<u8>) {
+    let sim = Sim::with_delay(delay);
+
+    let mut server = sim.server(sim.server_config());
+
+    let mut client = sim.client("10.0.0.2:2000", uflow::client::Config {
+        endpoint_config: uflow::EndpointConfig {
+            quality_events: true,
+            .. Default::default()
+        },
+        .. sim.client_config()
+    });
+
+    let mut scores = Vec::new();
+
+    for i in 0 .. 500 {
+        if i % 5 == 0 {
+            client.send(vec![ 0; 100 ].into(), 0, uflow::SendMode::Reliable);
+        }
+
+        sim.step(&mut server, &mut client, |_, event| match event {
+            uflow::server::Event::Connect(_) => (),
+            uflow::server::Event::Receive(..) => (),
+            other => panic!("unexpected event: {:?}", other),
+        }, |_, event| match event {
+            uflow::client::Event::Connect => (),
+            uflow::client::Event::QualityChange(score) => scores.push(score),
+            other => panic!("unexpected event: {:?}", other),
+        });
+    }
+
+    (scores, client.link_stats().quality)
+}
+
+// A client sending over an ideal link should report a perfect quality score once.
+#[test]
+fn quality_ideal() {
+    let (scores, quality) = measure(time::Duration::ZERO);
+
+    assert_eq!(scores, [ 100 ]);
+    assert_eq!(quality, Some(100));
+}
+
+// A client sending over a link with significant latency should report a reduced quality score,
+// without signaling insignificant changes.
+#[test]
+fn quality_delayed() {
+    let (scores, quality) = measure(time::Duration::from_millis(100));
+
+    assert!(!scores.is_empty());
+    assert!(scores.windows(2).all(|pair| pair[0].abs_diff(pair[1]) >= 5), "{:?}", scores);
+
+    let last_score = *scores.last().unwrap();
+    assert!(last_score < 80, "{:?}", scores);
+    assert!(quality.unwrap().abs_diff(last_score) < 5);
+}
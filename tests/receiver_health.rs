@@ -0,0 +1,75 @@
+use std::thread;
+use std::time;
+
+// Sends a few packets from the client to the server, and returns the client's report of the
+// server's receive buffer.
+fn remote_health(port: u16, server_health: bool, client_health: bool) -> Option<uflow::ReceiverHealth> {
+    let address = format!("127.0.0.1:{}", port);
+
+    let server_cfg = uflow::server::Config {
+        endpoint_config: uflow::EndpointConfig {
+            receiver_health: server_health,
+            .. Default::default()
+        },
+        .. Default::default()
+    };
+
+    let client_cfg = uflow::client::Config {
+        endpoint_config: uflow::EndpointConfig {
+            receiver_health: client_health,
+            .. Default::default()
+        },
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind(address.as_str(), server_cfg).unwrap();
+    let mut client = uflow::client::Client::connect(address.as_str(), client_cfg).unwrap();
+
+    let mut received = 0;
+    let mut sent = false;
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(1000);
+
+    while time::Instant::now() < end_time {
+        for event in server.step() {
+            if let uflow::server::Event::Receive(..) = event {
+                received += 1;
+            }
+        }
+
+        for event in client.step() {
+            if let uflow::client::Event::Connect = event {
+                for i in 0 .. 4u8 {
+                    client.send([ i ].into(), 0, uflow::SendMode::Reliable);
+                }
+                sent = true;
+            }
+        }
+
+        server.flush();
+        client.flush();
+
+        thread::sleep(time::Duration::from_millis(20));
+    }
+
+    assert!(sent);
+    assert_eq!(received, 4);
+
+    client.link_stats().remote_health
+}
+
+// Receive buffer reports should be exchanged if both endpoints enable them, and the receiver
+// should have no backlog once all packets have been delivered.
+#[test]
+fn receiver_health_enabled() {
+    let health = remote_health(5721, true, true);
+
+    assert_eq!(health, Some(uflow::ReceiverHealth { buffered_bytes: 0, backlogged: false }));
+}
+
+// The feature is negotiated, so no reports should be exchanged unless both endpoints enable it.
+#[test]
+fn receiver_health_negotiated() {
+    assert_eq!(remote_health(5722, true, false), None);
+    assert_eq!(remote_health(5723, false, true), None);
+}
@@ -8,7 +8,7 @@ extern crate md5;
 
 const STEP_DURATION: time::Duration = time::Duration::from_millis(15);
 
-const NUM_CHANNELS: usize = 64;
+const NUM_CHANNELS: usize = uflow::CHANNEL_COUNT;
 
 struct BandwidthLimiter {
     bandwidth: f64,
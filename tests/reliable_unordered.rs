@@ -0,0 +1,87 @@
+mod common;
+
+use std::cell::Cell;
+use std::io;
+use std::net;
+use std::rc::Rc;
+
+use uflow::Transport;
+
+use common::Sim;
+
+// A transport which discards the next datagram it sends once armed
+struct DropNext {
+    inner: uflow::transport::Memory,
+    armed: Rc<Cell<bool>>,
+}
+
+impl Transport for DropNext {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        if self.armed.get() {
+            self.armed.set(false);
+            return Ok(buf.len());
+        }
+
+        self.inner.send_to(buf, addr)
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        self.inner.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+// Unordered packets should be delivered as soon as they arrive, even while an earlier reliable
+// packet on the same channel is awaiting retransmission, and the lost packet should still arrive.
+#[test]
+fn reliable_unordered_delivery() {
+    let sim = Sim::new();
+
+    let client_transport = DropNext {
+        inner: sim.bind("10.0.0.2:2000"),
+        armed: Rc::new(Cell::new(false)),
+    };
+    let armed = Rc::clone(&client_transport.armed);
+
+    let mut server = sim.server(sim.server_config());
+    let mut client = sim.client_with_transport(client_transport, sim.client_config());
+
+    let lost: Box<[u8]> = vec![ 0xFF; 8 ].into();
+
+    let mut received = Vec::new();
+    let mut connect_step = None;
+
+    for i in 0 .. 200u8 {
+        // Once connected, send a reliable packet whose first transmission is lost, followed by an
+        // unordered packet on each of the next few steps
+        if let Some(connect_step) = connect_step {
+            match i - connect_step {
+                0 => {
+                    armed.set(true);
+                    client.send(lost.clone(), 0, uflow::SendMode::Reliable);
+                }
+                n @ 1 ..= 5 => {
+                    client.send(vec![ n ].into(), 0, uflow::SendMode::ReliableUnordered);
+                }
+                _ => (),
+            }
+        }
+
+        sim.step(&mut server, &mut client, |_, event| match event {
+            uflow::server::Event::Connect(_) => (),
+            uflow::server::Event::Receive(_, data) => received.push(data),
+            other => panic!("unexpected event: {:?}", other),
+        }, |_, event| match event {
+            uflow::client::Event::Connect => connect_step = Some(i + 1),
+            other => panic!("unexpected event: {:?}", other),
+        });
+    }
+
+    let mut expected: Vec<Box<[u8]>> = (1 ..= 5u8).map(|i| vec![ i ].into()).collect();
+    expected.push(lost);
+
+    assert_eq!(received, expected);
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::sync::Arc;
+use std::time;
+
+use uflow::rendezvous::Status;
+
+use common::Sim;
+
+fn punch_config(sim: &Sim) -> uflow::rendezvous::Config {
+    uflow::rendezvous::Config {
+        clock: Arc::new(sim.clock.clone()),
+        .. Default::default()
+    }
+}
+
+// Two peers which begin punching at different times should both see the path open, and should then
+// be able to connect and exchange data over it.
+#[test]
+fn rendezvous_connect() {
+    let sim = Sim::with_delay(time::Duration::from_millis(50));
+
+    let a_address = common::address("10.0.0.1:1000");
+    let b_address = common::address("10.0.0.2:2000");
+
+    // Probes sent before the other peer exists are lost, as though dropped by its NAT
+    let mut a = uflow::rendezvous::HolePunch::new(sim.network.bind(a_address).unwrap(), b_address, punch_config(&sim));
+
+    for _ in 0 .. 50 {
+        assert_eq!(a.step(), Status::Punching);
+        sim.advance();
+    }
+
+    let mut b = uflow::rendezvous::HolePunch::new(sim.network.bind(b_address).unwrap(), a_address, punch_config(&sim));
+
+    for _ in 0 .. 100 {
+        a.step();
+        b.step();
+        sim.advance();
+    }
+
+    assert_eq!(a.status(), Status::Open);
+    assert_eq!(b.status(), Status::Open);
+
+    let mut server = a.into_server(sim.server_config());
+    let mut client = b.into_client(sim.client_config()).unwrap();
+
+    client.send(vec![ 0xAA ].into(), 0, uflow::SendMode::Reliable);
+
+    let mut server_received = Vec::new();
+
+    sim.run(100, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(_) => (),
+        uflow::server::Event::Receive(client_id, data) => {
+            server_received.push((server.client(client_id).unwrap().borrow().address(), data));
+        }
+        other => panic!("unexpected event: {:?}", other),
+    }, common::ignore_client);
+
+    assert!(client.is_active());
+    assert_eq!(server_received, vec![ (b_address, vec![ 0xAA ].into()) ]);
+}
+
+// A peer which never receives a reply should abandon the attempt after sending every probe.
+#[test]
+fn rendezvous_failed() {
+    let sim = Sim::new();
+
+    let punch_cfg = uflow::rendezvous::Config {
+        probe_interval_ms: 100,
+        probe_count: 5,
+        clock: Arc::new(sim.clock.clone()),
+    };
+
+    let mut a = uflow::rendezvous::HolePunch::new(sim.bind("10.0.0.1:1000"), common::address("10.0.0.2:2000"), punch_cfg);
+
+    for _ in 0 .. 49 {
+        sim.advance();
+        assert_eq!(a.step(), Status::Punching);
+    }
+
+    sim.advance();
+    assert_eq!(a.step(), Status::Failed);
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::Sim;
+
+// A reset should drop all pending data on both sides and signal both endpoints, after which the
+// connection continues to deliver data as usual.
+#[test]
+fn reset_resynchronize() {
+    let sim = Sim::new();
+
+    let mut server = sim.server(sim.server_config());
+    let mut client = sim.client("10.0.0.2:2000", sim.client_config());
+
+    let mut client_address = None;
+
+    sim.run(10, &mut server, &mut client, |_, event| {
+        if let uflow::server::Event::Connect(address) = event {
+            client_address = Some(address);
+        }
+    }, common::ignore_client);
+
+    assert!(client.is_active());
+    let client_address = client_address.unwrap();
+
+    // Enqueued, but never flushed, before the reset
+    for i in 0 .. 4 {
+        client.send(vec![ i ].into(), 0, uflow::SendMode::Reliable);
+    }
+
+    client.reset();
+
+    let mut client_resets = Vec::new();
+    let mut server_resets = Vec::new();
+    let mut server_received = Vec::new();
+
+    for step in 0 .. 50 {
+        if step == 10 {
+            client.send(vec![ 100 ].into(), 0, uflow::SendMode::Reliable);
+        }
+
+        sim.step(&mut server, &mut client, |_, event| match event {
+            uflow::server::Event::Reset(address, drop_count) => {
+                assert_eq!(address, client_address);
+                server_resets.push(drop_count);
+            }
+            uflow::server::Event::Receive(_, data) => server_received.push(data[0]),
+            other => panic!("unexpected event: {:?}", other),
+        }, |_, event| match event {
+            uflow::client::Event::Reset(drop_count) => client_resets.push(drop_count),
+            other => panic!("unexpected event: {:?}", other),
+        });
+    }
+
+    assert_eq!(client_resets, vec![ 4 ]);
+    assert_eq!(server_resets, vec![ 0 ]);
+    assert_eq!(server_received, vec![ 100 ]);
+    assert!(client.is_active());
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::time;
+
+use common::Sim;
+
+// Options passed to send_with() should combine, whether the packet was sent before or after the
+// connection was established, and packets sent with any send variant should be cancelable, with
+// canceled packets traced as dropped.
+#[test]
+fn combined_options() {
+    let sim = Sim::with_delay(time::Duration::from_millis(20));
+
+    let mut server = sim.server(sim.server_config());
+    let mut client = sim.client("10.0.0.2:1000", sim.client_config());
+
+    let options = uflow::SendOptions {
+        latency_class: uflow::LatencyClass::Critical,
+        key: Some(7),
+        trace_id: Some(10),
+        confirm_tag: Some(100),
+        .. Default::default()
+    };
+
+    client.send_with([ 0 ].into(), 0, uflow::SendMode::Reliable, options);
+
+    let canceled_handle = client.send_keyed([ 1 ].into(), 0, 7, uflow::SendMode::Reliable);
+    assert!(client.cancel(canceled_handle));
+
+    let canceled_handle = client.send_traced([ 2 ].into(), 0, uflow::SendMode::Reliable, 11);
+    assert!(client.cancel(canceled_handle));
+
+    let mut server_events = Vec::new();
+    let mut client_events = Vec::new();
+
+    sim.run(100, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(address) => {
+            let mut remote_client = server.client(address).unwrap().borrow_mut();
+
+            remote_client.send_with([ 3 ].into(), 1, uflow::SendMode::ReliableUnordered, uflow::SendOptions {
+                trace_id: Some(20),
+                confirm_tag: Some(200),
+                .. Default::default()
+            });
+
+            let handle = remote_client.send_traced([ 4 ].into(), 1, uflow::SendMode::Unreliable, 30);
+            assert!(remote_client.cancel(handle));
+        }
+        uflow::server::Event::Receive(_, data) => server_events.push(format!("receive {}", data[0])),
+        uflow::server::Event::Trace(_, trace_id, outcome) => server_events.push(format!("trace {} {:?}", trace_id, outcome)),
+        uflow::server::Event::Delivered(_, channel_id, tag) => server_events.push(format!("delivered {} {}", channel_id, tag)),
+        _ => (),
+    }, |_, event| match event {
+        uflow::client::Event::Receive(data) => client_events.push(format!("receive {}", data[0])),
+        uflow::client::Event::Trace(trace_id, outcome) => client_events.push(format!("trace {} {:?}", trace_id, outcome)),
+        uflow::client::Event::Delivered(channel_id, tag) => client_events.push(format!("delivered {} {}", channel_id, tag)),
+        _ => (),
+    });
+
+    server_events.sort();
+    client_events.sort();
+
+    assert_eq!(server_events, vec![ "delivered 1 200", "receive 0", "trace 20 Delivered", "trace 30 Dropped" ]);
+    assert_eq!(client_events, vec![ "delivered 0 100", "receive 3", "trace 10 Delivered", "trace 11 Dropped" ]);
+}
+
+// Options which are incompatible with the send mode are a usage error.
+#[test]
+#[should_panic]
+fn ttl_requires_unreliable_mode() {
+    let (transport, server_transport) = uflow::transport::Memory::pair();
+
+    let mut client = uflow::client::Client::with_transport(transport, server_transport.address(), Default::default()).unwrap();
+
+    client.send_with([ 0 ].into(), 0, uflow::SendMode::Reliable, uflow::SendOptions {
+        ttl: Some(time::Duration::from_millis(100)),
+        .. Default::default()
+    });
+}
@@ -0,0 +1,9 @@
+fn assert_send<T: Send>() {}
+
+// Clients, and the configurations used to create them, may be moved to other threads.
+#[test]
+fn client_is_send() {
+    assert_send::<uflow::client::Client>();
+    assert_send::<uflow::client::Client<uflow::transport::Memory>>();
+    assert_send::<uflow::client::Config>();
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use std::time;
+
+use common::{ Sim, STEP_INTERVAL };
+
+struct Session {
+    sim: Sim,
+    server: common::Server,
+    client: common::Client,
+}
+
+impl Session {
+    fn new() -> Self {
+        let sim = Sim::with_delay(time::Duration::from_millis(20));
+
+        let endpoint_config = uflow::EndpointConfig {
+            session_summaries: true,
+            .. Default::default()
+        };
+
+        let server = sim.server(uflow::server::Config {
+            endpoint_config: endpoint_config.clone(),
+            .. sim.server_config()
+        });
+
+        let client = sim.client("10.0.0.2:1000", uflow::client::Config {
+            endpoint_config,
+            .. sim.client_config()
+        });
+
+        Self { sim, server, client }
+    }
+
+    // Steps both endpoints for the given number of steps, returning the events each generated
+    fn run(&mut self, steps: usize, step_server: bool) -> (Vec<uflow::server::Event>, Vec<uflow::client::Event>) {
+        let mut server_events = Vec::new();
+        let mut client_events = Vec::new();
+
+        for _ in 0 .. steps {
+            if step_server {
+                self.sim.step(&mut self.server, &mut self.client, |_, event| server_events.push(event),
+                              |_, event| client_events.push(event));
+            } else {
+                client_events.extend(self.client.step());
+                self.client.flush();
+
+                self.sim.advance();
+            }
+        }
+
+        (server_events, client_events)
+    }
+}
+
+// Both endpoints should signal a summary of the session immediately before signaling a
+// disconnection.
+#[test]
+fn summary_on_disconnect() {
+    let mut session = Session::new();
+
+    session.run(20, true);
+    assert!(session.client.is_active());
+
+    session.client.send([ 0; 100 ].into(), 0, uflow::SendMode::Reliable);
+    session.client.send([ 1; 50 ].into(), 0, uflow::SendMode::Reliable);
+
+    let (server_events, _) = session.run(20, true);
+    assert_eq!(server_events.len(), 2);
+
+    session.client.disconnect();
+
+    let (server_events, client_events) = session.run(20, true);
+
+    match &client_events[..] {
+        [ uflow::client::Event::Summary(summary), uflow::client::Event::Disconnect ] => {
+            assert_eq!(summary.reason, uflow::CloseReason::Disconnect);
+            assert_eq!(summary.packets_sent, 2);
+            assert_eq!(summary.bytes_sent, 150);
+            assert_eq!(summary.packets_received, 0);
+            assert!(summary.duration >= time::Duration::from_millis(200));
+
+            // Each acknowledgement contributes a sample of approximately 40ms
+            assert!(summary.rtt_histogram.total() > 0);
+            assert_eq!(summary.rtt_histogram.counts[2], summary.rtt_histogram.total());
+        }
+        other => panic!("unexpected events: {:?}", other),
+    }
+
+    match &server_events[..] {
+        [ uflow::server::Event::Summary(address, summary), uflow::server::Event::Disconnect(disconnect_address) ] => {
+            assert_eq!(address, disconnect_address);
+            assert_eq!(summary.reason, uflow::CloseReason::Disconnect);
+            assert_eq!(summary.packets_received, 2);
+            assert_eq!(summary.bytes_received, 150);
+        }
+        other => panic!("unexpected events: {:?}", other),
+    }
+}
+
+// A summary should also be signaled if the connection times out.
+#[test]
+fn summary_on_timeout() {
+    let mut session = Session::new();
+
+    session.run(20, true);
+    assert!(session.client.is_active());
+
+    // Stop servicing the server until the client times out
+    let active_timeout_ms = uflow::EndpointConfig::default().active_timeout_ms;
+    let steps = (active_timeout_ms / STEP_INTERVAL.as_millis() as u64) as usize + 10;
+
+    let (_, client_events) = session.run(steps, false);
+
+    match &client_events[..] {
+        [ uflow::client::Event::Summary(summary), uflow::client::Event::Error(uflow::client::ErrorType::Timeout) ] => {
+            assert_eq!(summary.reason, uflow::CloseReason::Timeout);
+        }
+        other => panic!("unexpected events: {:?}", other),
+    }
+}
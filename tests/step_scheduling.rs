@@ -0,0 +1,79 @@
+mod common;
+
+use std::rc::Rc;
+use std::sync::atomic;
+use std::sync::Arc;
+use std::time;
+
+use common::Sim;
+
+const CLIENT_COUNT: usize = 32;
+
+// A clock which counts the number of times it has been read
+struct CountingClock {
+    inner: uflow::VirtualClock,
+    reads: Arc<atomic::AtomicUsize>,
+}
+
+impl uflow::Clock for CountingClock {
+    fn now(&self) -> time::Duration {
+        self.reads.fetch_add(1, atomic::Ordering::Relaxed);
+        self.inner.now()
+    }
+}
+
+// Once idle, clients should not be stepped by the server until they receive frames or are given
+// something to send, and packets enqueued via a retained client reference should be sent as usual.
+#[test]
+fn step_idle_clients() {
+    let sim = Sim::new();
+
+    let reads = Arc::new(atomic::AtomicUsize::new(0));
+
+    let mut server = sim.server(uflow::server::Config {
+        clock: Arc::new(CountingClock { inner: sim.clock.clone(), reads: Arc::clone(&reads) }),
+        .. sim.server_config()
+    });
+
+    let mut clients = (0 .. CLIENT_COUNT).map(|i| {
+        sim.client(&format!("10.0.0.2:{}", 2000 + i), sim.client_config())
+    }).collect::<Vec<_>>();
+
+    let mut received = vec![ Vec::new(); CLIENT_COUNT ];
+
+    let mut step = |server: &mut common::Server, clients: &mut [common::Client]| {
+        sim.step_all(server, clients, common::expect_connect, |index, _, event| match event {
+            uflow::client::Event::Connect => (),
+            uflow::client::Event::Receive(data) => received[index].push(data),
+            other => panic!("unexpected event: {:?}", other),
+        });
+    };
+
+    for _ in 0 .. 20 {
+        step(&mut server, &mut clients);
+    }
+
+    assert!(clients.iter().all(|client| client.is_active()));
+
+    let client_rc = Rc::clone(server.client(common::client_id(&server, clients[0].local_address())).unwrap());
+
+    // Step only the server, well before any keepalive is due
+    let reads_before = reads.load(atomic::Ordering::Relaxed);
+
+    for _ in 0 .. 10 {
+        assert_eq!(server.step().count(), 0);
+        server.flush();
+        sim.advance();
+    }
+
+    assert!(reads.load(atomic::Ordering::Relaxed) - reads_before < CLIENT_COUNT);
+
+    client_rc.borrow_mut().send([ 0 ].into(), 0, uflow::SendMode::Reliable);
+
+    for _ in 0 .. 10 {
+        step(&mut server, &mut clients);
+    }
+
+    assert_eq!(received[0], vec![ [ 0 ].into() ]);
+    assert!(received[1 ..].iter().all(|received| received.is_empty()));
+}
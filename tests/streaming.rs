@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use std::thread;
+use std::time;
+
+// Transfers to several clients should complete, and be reassembled in order.
+#[test]
+fn transfer_to_clients() {
+    let address = "127.0.0.1:5661";
+
+    let mut server = uflow::server::Server::bind(address, Default::default()).unwrap();
+
+    let mut clients: Vec<uflow::client::Client> = (0 .. 3).map(|_| {
+        uflow::client::Client::connect(address, Default::default()).unwrap()
+    }).collect();
+
+    let mut received: Vec<Vec<u8>> = vec![Vec::new(); clients.len()];
+
+    let mut scheduler = uflow::streaming::TransferScheduler::new(500_000, 1000, 16000);
+
+    let assets: Vec<Arc<[u8]>> = (0 .. 2).map(|i| {
+        (0 .. 25000).map(|j| (i * 7 + j * 13) as u8).collect::<Vec<u8>>().into()
+    }).collect();
+
+    let mut transfers = Vec::new();
+    let mut completed = Vec::new();
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(5000);
+
+    while time::Instant::now() < end_time {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(client_addr) => {
+                    // Every client receives both assets, one after the other
+                    for asset in assets.iter() {
+                        transfers.push(scheduler.start(client_addr, Arc::clone(asset), 0));
+                    }
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in scheduler.step(&mut server) {
+            match event {
+                uflow::streaming::TransferEvent::Complete(_, id) => completed.push(id),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for (client, received) in clients.iter_mut().zip(received.iter_mut()) {
+            for event in client.step() {
+                match event {
+                    uflow::client::Event::Connect => (),
+                    uflow::client::Event::Receive(data) => received.extend_from_slice(&data),
+                    other => panic!("unexpected event: {:?}", other),
+                }
+            }
+        }
+
+        server.flush();
+
+        for client in clients.iter_mut() {
+            client.flush();
+        }
+
+        if completed.len() == clients.len() * assets.len() &&
+           received.iter().all(|data| data.len() == 50000) {
+            break;
+        }
+
+        thread::sleep(time::Duration::from_millis(10));
+    }
+
+    completed.sort_by_key(|id| transfers.iter().position(|other| other == id));
+    assert_eq!(completed, transfers);
+    assert_eq!(scheduler.transfer_count(), 0);
+
+    let expected: Vec<u8> = assets.iter().flat_map(|asset| asset.iter().copied()).collect();
+
+    for data in received.iter() {
+        assert!(*data == expected);
+    }
+}
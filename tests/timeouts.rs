@@ -6,10 +6,13 @@ static TEST_TIMEOUT_MS: u64 = 4000;
 
 #[test]
 fn client_handshake_timeout() {
+    // A socket which never replies
+    let _socket = std::net::UdpSocket::bind("127.0.0.1:8888").unwrap();
+
     let cfg = Default::default();
     let mut client = uflow::client::Client::connect("127.0.0.1:8888", cfg).unwrap();
 
-    // We expect to see exactly one Error(ErrorType::Timeout) within 25 seconds
+    // We expect to see exactly one Error(ErrorType::ConnectTimeout) within 25 seconds
     let end_time = time::Instant::now() + time::Duration::from_secs(25);
     let mut timeout_seen = false;
 
@@ -18,7 +21,7 @@ fn client_handshake_timeout() {
             match event {
                 uflow::client::Event::Error(error) => {
                     assert_eq!(timeout_seen, false);
-                    assert_eq!(error, uflow::client::ErrorType::Timeout);
+                    assert_eq!(error, uflow::client::ErrorType::ConnectTimeout);
                     timeout_seen = true;
                 }
                 other => panic!("unexpected event: {:?}", other),
@@ -33,6 +36,42 @@ fn client_handshake_timeout() {
     }
 }
 
+#[test]
+fn client_connect_refused() {
+    let cfg = uflow::client::Config {
+        handshake_resend_interval_ms: 100,
+        handshake_max_resend_interval_ms: 200,
+        handshake_resend_count: 3,
+        .. Default::default()
+    };
+
+    // Nothing is bound to this address
+    let mut client = uflow::client::Client::connect("127.0.0.1:5581", cfg).unwrap();
+
+    // We expect to see exactly one Error(ErrorType::ConnectRefused) within 2 seconds
+    let end_time = time::Instant::now() + time::Duration::from_secs(2);
+    let mut refused_seen = false;
+
+    while time::Instant::now() < end_time {
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Error(error) => {
+                    assert_eq!(refused_seen, false);
+                    assert_eq!(error, uflow::client::ErrorType::ConnectRefused);
+                    refused_seen = true;
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        thread::sleep(time::Duration::from_millis(20));
+    }
+
+    if !refused_seen {
+        panic!("no refusal event received");
+    }
+}
+
 #[test]
 fn client_active_timeout() {
     thread::spawn(|| {
@@ -42,8 +81,8 @@ fn client_active_timeout() {
         loop {
             for event in server.step() {
                 match event {
-                    uflow::server::Event::Connect(client_address) => {
-                        server.drop(&client_address);
+                    uflow::server::Event::Connect(client_id) => {
+                        server.drop(client_id);
                     }
                     _ => (),
                 }
@@ -58,7 +97,8 @@ fn client_active_timeout() {
             endpoint_config: uflow::EndpointConfig {
                 active_timeout_ms: TIMEOUT_MS,
                 ..Default::default()
-            }
+            },
+            ..Default::default()
         };
 
         let mut client = uflow::client::Client::connect("127.0.0.1:9999", cfg).unwrap();
@@ -110,14 +150,14 @@ fn server_handshake_timeout() {
 
         let mut server = uflow::server::Server::bind("127.0.0.1:7777", cfg).unwrap();
 
-        // We expect to see exactly one Error(_, ErrorType::Timeout) within 25 seconds
+        // We expect to see exactly one HandshakeError(_, ErrorType::Timeout) within 25 seconds
         let end_time = time::Instant::now() + time::Duration::from_secs(25);
         let mut timeout_seen = false;
 
         while time::Instant::now() < end_time {
             for event in server.step() {
                 match event {
-                    uflow::server::Event::Error(_, error) => {
+                    uflow::server::Event::HandshakeError(_, error) => {
                         assert_eq!(timeout_seen, false);
                         assert_eq!(error, uflow::server::ErrorType::Timeout);
                         timeout_seen = true;
@@ -209,3 +249,81 @@ fn server_active_timeout() {
 
     server.join().unwrap();
 }
+
+#[test]
+fn server_handshake_timeout_config() {
+    let cfg = uflow::server::Config {
+        enable_handshake_errors: true,
+        handshake_timeout_ms: 500,
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind("127.0.0.1:5582", cfg).unwrap();
+
+    // Client::connect sends the first SYN, and this is all the server will receive
+    uflow::client::Client::connect("127.0.0.1:5582", Default::default()).unwrap();
+
+    // We expect to see exactly one HandshakeError(_, ErrorType::Timeout) within 2 seconds
+    let start_time = time::Instant::now();
+    let end_time = start_time + time::Duration::from_secs(2);
+    let mut timeout_seen = false;
+
+    while time::Instant::now() < end_time {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::HandshakeError(_, error) => {
+                    assert_eq!(timeout_seen, false);
+                    assert_eq!(error, uflow::server::ErrorType::Timeout);
+                    assert!(start_time.elapsed() >= time::Duration::from_millis(500));
+                    timeout_seen = true;
+                }
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        thread::sleep(time::Duration::from_millis(20));
+    }
+
+    if !timeout_seen {
+        panic!("no timeout event received");
+    }
+}
+
+#[test]
+fn client_handshake_timeout_send_failed() {
+    // A socket which never replies
+    let _socket = std::net::UdpSocket::bind("127.0.0.1:5583").unwrap();
+
+    let cfg = uflow::client::Config {
+        handshake_resend_interval_ms: 100,
+        handshake_max_resend_interval_ms: 200,
+        handshake_resend_count: 3,
+        .. Default::default()
+    };
+
+    let mut client = uflow::client::Client::connect("127.0.0.1:5583", cfg).unwrap();
+
+    client.send([ 0 ].into(), 0, uflow::SendMode::Reliable);
+    client.send([ 1 ].into(), 1, uflow::SendMode::Unreliable);
+
+    // We expect to see a timeout, followed by each packet in order
+    let end_time = time::Instant::now() + time::Duration::from_secs(2);
+    let mut events = Vec::new();
+
+    while time::Instant::now() < end_time {
+        events.extend(client.step());
+        thread::sleep(time::Duration::from_millis(20));
+    }
+
+    assert_eq!(events.len(), 3);
+
+    match (&events[0], &events[1], &events[2]) {
+        (uflow::client::Event::Error(uflow::client::ErrorType::ConnectTimeout),
+         uflow::client::Event::SendFailed(data_0),
+         uflow::client::Event::SendFailed(data_1)) => {
+            assert_eq!(**data_0, [ 0 ]);
+            assert_eq!(**data_1, [ 1 ]);
+        }
+        other => panic!("unexpected events: {:?}", other),
+    }
+}
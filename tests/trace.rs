@@ -0,0 +1,74 @@
+mod common;
+
+use std::time;
+
+use common::Sim;
+
+// Traced packets should be reported as delivered once acknowledged, whether sent before or after
+// the connection was established.
+#[test]
+fn trace_delivered() {
+    let sim = Sim::with_delay(time::Duration::from_millis(20));
+
+    let mut server = sim.server(sim.server_config());
+    let mut client = sim.client("10.0.0.2:1000", sim.client_config());
+
+    client.send_traced([ 0 ].into(), 0, uflow::SendMode::Reliable, 100);
+
+    let mut server_traces = Vec::new();
+    let mut client_traces = Vec::new();
+    let mut server_received = 0;
+    let mut client_received = 0;
+
+    sim.run(100, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(address) => {
+            let mut remote_client = server.client(address).unwrap().borrow_mut();
+            remote_client.send_traced([ 1 ].into(), 0, uflow::SendMode::Unreliable, 200);
+        }
+        uflow::server::Event::Trace(_, trace_id, outcome) => server_traces.push((trace_id, outcome)),
+        uflow::server::Event::Receive(..) => server_received += 1,
+        _ => (),
+    }, |client, event| match event {
+        uflow::client::Event::Connect => {
+            client.send_traced([ 2 ].into(), 1, uflow::SendMode::Reliable, 101);
+            client.send([ 3 ].into(), 1, uflow::SendMode::Reliable);
+        }
+        uflow::client::Event::Trace(trace_id, outcome) => client_traces.push((trace_id, outcome)),
+        uflow::client::Event::Receive(..) => client_received += 1,
+        _ => (),
+    });
+
+    assert_eq!((server_received, client_received), (3, 1));
+    assert_eq!(client_traces, vec![ (100, uflow::TraceOutcome::Delivered), (101, uflow::TraceOutcome::Delivered) ]);
+    assert_eq!(server_traces, vec![ (200, uflow::TraceOutcome::Delivered) ]);
+}
+
+// Traced packets enqueued before a failed connection attempt should be reported as dropped,
+// immediately following their SendFailed events.
+#[test]
+fn trace_send_failed() {
+    let sim = Sim::new();
+
+    // No server is bound
+    let mut client = sim.client("10.0.0.2:1000", sim.client_config());
+
+    client.send_traced([ 0 ].into(), 0, uflow::SendMode::Reliable, 100);
+    client.send([ 1 ].into(), 0, uflow::SendMode::Reliable);
+
+    let mut events = Vec::new();
+
+    for _ in 0 .. 2000 {
+        events.extend(client.step());
+        client.flush();
+
+        sim.advance();
+    }
+
+    let events = events.into_iter().filter_map(|event| match event {
+        uflow::client::Event::SendFailed(data) => Some(format!("failed {}", data[0])),
+        uflow::client::Event::Trace(trace_id, outcome) => Some(format!("trace {} {:?}", trace_id, outcome)),
+        _ => None,
+    }).collect::<Vec<_>>();
+
+    assert_eq!(events, vec![ "failed 0", "trace 100 Dropped", "failed 1" ]);
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use std::time;
+
+use common::Sim;
+
+// Each direction of a connection should use the smaller of the sender's send window and the
+// receiver's advertised receive window, and transfers should complete even when a window is
+// much smaller than the number of packets sent.
+#[test]
+fn asymmetric_windows() {
+    let sim = Sim::with_delay(time::Duration::from_millis(20));
+
+    // A large downstream, and a small upstream
+    let mut server = sim.server(uflow::server::Config {
+        endpoint_config: uflow::EndpointConfig {
+            max_send_window: 1024,
+            max_receive_window: 16,
+            .. Default::default()
+        },
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client("10.0.0.2:1000", uflow::client::Config {
+        endpoint_config: uflow::EndpointConfig {
+            max_send_window: 256,
+            .. Default::default()
+        },
+        .. sim.client_config()
+    });
+
+    let client_address = client.local_address();
+
+    let mut server_received = Vec::new();
+    let mut client_received = 0;
+
+    sim.run(500, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(address) => {
+            let mut remote_client = server.client(address).unwrap().borrow_mut();
+            for i in 0 .. 100u8 {
+                remote_client.send([ i ].into(), 0, uflow::SendMode::Reliable);
+            }
+        }
+        uflow::server::Event::Receive(_, data) => server_received.push(data[0]),
+        _ => (),
+    }, |client, event| match event {
+        uflow::client::Event::Connect => {
+            for i in 0 .. 100u8 {
+                client.send([ i ].into(), 0, uflow::SendMode::Reliable);
+            }
+        }
+        uflow::client::Event::Receive(_) => client_received += 1,
+        _ => (),
+    });
+
+    let client_stats = client.link_stats();
+    assert_eq!((client_stats.send_window, client_stats.receive_window), (16, 4096));
+
+    let server_stats = server.client(common::client_id(&server, client_address)).unwrap().borrow().link_stats();
+    assert_eq!((server_stats.send_window, server_stats.receive_window), (1024, 16));
+
+    assert_eq!(server_received, (0 .. 100).collect::<Vec<_>>());
+    assert_eq!(client_received, 100);
+}
+
+// Window sizes must be nonzero powers of two which do not exceed the maximum window size.
+#[test]
+fn window_validation() {
+    for &window in [ 1, 64, uflow::MAX_FRAME_WINDOW_SIZE ].iter() {
+        assert!(uflow::EndpointConfig { max_send_window: window, .. Default::default() }.is_valid());
+        assert!(uflow::EndpointConfig { max_receive_window: window, .. Default::default() }.is_valid());
+    }
+
+    for &window in [ 0, 3, uflow::MAX_FRAME_WINDOW_SIZE * 2 ].iter() {
+        assert!(!uflow::EndpointConfig { max_send_window: window, .. Default::default() }.is_valid());
+        assert!(!uflow::EndpointConfig { max_receive_window: window, .. Default::default() }.is_valid());
+    }
+}
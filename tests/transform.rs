@@ -0,0 +1,147 @@
+mod common;
+
+use std::sync::atomic;
+use std::sync::Arc;
+
+use common::Sim;
+
+type Packets = Vec<Box<[u8]>>;
+
+// Masks each payload with a key, and appends a checksum of the original payload
+struct Mask {
+    key: u8,
+    // Number of payloads which will yet be rejected as though they had been tampered with
+    reject_count: Arc<atomic::AtomicU32>,
+}
+
+impl Mask {
+    fn new(key: u8) -> Self {
+        Self { key, reject_count: Arc::new(atomic::AtomicU32::new(0)) }
+    }
+
+    fn checksum(data: &[u8]) -> [u8; 4] {
+        data.iter().fold(0u32, |sum, &byte| sum.wrapping_mul(31).wrapping_add(byte as u32)).to_be_bytes()
+    }
+}
+
+impl uflow::DatagramTransform for Mask {
+    fn overhead(&self) -> usize {
+        4
+    }
+
+    fn encode(&mut self, data: &[u8], out: &mut [u8]) {
+        let (masked, checksum) = out.split_at_mut(data.len());
+
+        for (out_byte, &byte) in masked.iter_mut().zip(data.iter()) {
+            *out_byte = byte ^ self.key;
+        }
+
+        checksum.copy_from_slice(&Self::checksum(data));
+    }
+
+    fn decode(&mut self, data: &[u8]) -> Option<Box<[u8]>> {
+        let rejected = self.reject_count.fetch_update(atomic::Ordering::Relaxed, atomic::Ordering::Relaxed,
+                                                      |count| count.checked_sub(1));
+
+        if rejected.is_ok() {
+            return None;
+        }
+
+        let (masked, checksum) = data.split_at(data.len().checked_sub(4)?);
+        let unmasked = masked.iter().map(|&byte| byte ^ self.key).collect::<Box<[u8]>>();
+
+        if Self::checksum(&unmasked) != checksum {
+            return None;
+        }
+
+        Some(unmasked)
+    }
+}
+
+// Sends the given packets from the client to the server, and then echoes them back to the client,
+// returning the packets received by each
+fn echo(server_mask: Mask, client_mask: Mask, packets: &[Box<[u8]>]) -> (Packets, Packets) {
+    let sim = Sim::new();
+
+    let mut server_mask = Some(server_mask);
+
+    let mut server = sim.server(uflow::server::Config {
+        transform_selector: Some(Box::new(move |_| {
+            server_mask.take().map(|mask| Box::new(mask) as Box<dyn uflow::DatagramTransform + Send>)
+        })),
+        .. sim.server_config()
+    });
+
+    let mut client = sim.client("10.0.0.2:2000", uflow::client::Config {
+        transform: Some(Box::new(client_mask)),
+        .. sim.client_config()
+    });
+
+    for packet in packets.iter() {
+        client.send(packet.clone(), 0, uflow::SendMode::Reliable);
+    }
+
+    let mut server_received = Vec::new();
+    let mut client_received = Vec::new();
+
+    sim.run(1000, &mut server, &mut client, |server, event| match event {
+        uflow::server::Event::Connect(_) => (),
+        uflow::server::Event::Receive(address, data) => {
+            server_received.push(data);
+
+            // Reply once all packets have arrived, so that the endpoints send in turn
+            if server_received.len() == packets.len() {
+                let mut client = server.client(address).unwrap().borrow_mut();
+
+                for packet in server_received.iter() {
+                    client.send(packet.clone(), 0, uflow::SendMode::Reliable);
+                }
+            }
+        }
+        other => panic!("unexpected event: {:?}", other),
+    }, |_, event| match event {
+        uflow::client::Event::Connect => (),
+        uflow::client::Event::Receive(data) => client_received.push(data),
+        other => panic!("unexpected event: {:?}", other),
+    });
+
+    (server_received, client_received)
+}
+
+fn test_packets() -> Packets {
+    vec![
+        vec![ 1, 2, 3 ].into(),
+        (0 .. 3 * uflow::MAX_FRAGMENT_SIZE + 10).map(|i| i as u8).collect(),
+        Box::new([]),
+        vec![ 0xFF; uflow::MAX_FRAGMENT_SIZE ].into(),
+    ]
+}
+
+// Packets should be delivered intact in both directions when both endpoints apply matching
+// transforms, including packets whose fragments fill entire frames.
+#[test]
+fn transform_transfer() {
+    let packets = test_packets();
+
+    let (server_received, client_received) = echo(Mask::new(0x5A), Mask::new(0x5A), &packets);
+
+    assert_eq!(server_received, packets);
+    assert_eq!(client_received, packets);
+}
+
+// Frames containing payloads which cannot be decoded should be treated as lost, so that reliable
+// packets are resent.
+#[test]
+fn transform_rejected() {
+    let packets = test_packets();
+
+    let server_mask = Mask::new(0x5A);
+    server_mask.reject_count.store(3, atomic::Ordering::Relaxed);
+    let reject_count = Arc::clone(&server_mask.reject_count);
+
+    let (server_received, client_received) = echo(server_mask, Mask::new(0x5A), &packets);
+
+    assert_eq!(reject_count.load(atomic::Ordering::Relaxed), 0);
+    assert_eq!(server_received, packets);
+    assert_eq!(client_received, packets);
+}
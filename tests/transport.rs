@@ -0,0 +1,109 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io;
+use std::net;
+use std::rc::Rc;
+use std::thread;
+use std::time;
+
+type Network = Rc<RefCell<HashMap<net::SocketAddr, VecDeque<(Box<[u8]>, net::SocketAddr)>>>>;
+
+// A lossless, in-memory datagram transport
+struct MemTransport {
+    network: Network,
+    address: net::SocketAddr,
+}
+
+impl MemTransport {
+    fn new(network: &Network, address: &str) -> Self {
+        let address = address.parse().unwrap();
+        network.borrow_mut().insert(address, VecDeque::new());
+
+        Self {
+            network: Rc::clone(network),
+            address,
+        }
+    }
+}
+
+impl uflow::Transport for MemTransport {
+    fn send_to(&self, buf: &[u8], addr: net::SocketAddr) -> io::Result<usize> {
+        // Datagrams to unknown addresses are silently dropped, as with UDP
+        if let Some(queue) = self.network.borrow_mut().get_mut(&addr) {
+            queue.push_back((buf.into(), self.address));
+        }
+        Ok(buf.len())
+    }
+
+    fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, net::SocketAddr)> {
+        match self.network.borrow_mut().get_mut(&self.address).unwrap().pop_front() {
+            Some((data, address)) => {
+                buf[.. data.len()].copy_from_slice(&data);
+                Ok((data.len(), address))
+            }
+            None => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<net::SocketAddr> {
+        Ok(self.address)
+    }
+}
+
+// A client and server should be able to connect and exchange packets over a user-supplied
+// transport.
+#[test]
+fn custom_transport() {
+    let network = Network::default();
+
+    let server_transport = MemTransport::new(&network, "10.0.0.1:1000");
+    let client_transport = MemTransport::new(&network, "10.0.0.2:2000");
+    let stray_transport = MemTransport::new(&network, "10.0.0.3:3000");
+
+    let mut server = uflow::server::Server::with_transport(server_transport, Default::default());
+    let mut client = uflow::client::Client::with_transport(client_transport, server.address(), Default::default()).unwrap();
+
+    assert_eq!(client.local_address(), "10.0.0.2:2000".parse().unwrap());
+
+    let mut server_received = Vec::new();
+    let mut client_received = Vec::new();
+    let mut client_addr = None;
+
+    for _ in 0 .. 100 {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(client_id) => {
+                    let mut remote_client = server.client(client_id).unwrap().borrow_mut();
+                    client_addr = Some(remote_client.address());
+
+                    remote_client.send([ 0, 1, 2 ].into(), 0, uflow::SendMode::Reliable);
+                }
+                uflow::server::Event::Receive(_, data) => server_received.push(data),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => {
+                    client.send([ 3, 4, 5 ].into(), 0, uflow::SendMode::Reliable);
+                }
+                uflow::client::Event::Receive(data) => client_received.push(data),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        // Frames from addresses other than the server's are ignored by the client
+        uflow::Transport::send_to(&stray_transport, &[ 0xFF; 16 ], client.local_address()).unwrap();
+
+        server.flush();
+        client.flush();
+
+        thread::sleep(time::Duration::from_millis(10));
+    }
+
+    assert_eq!(client_addr, Some("10.0.0.2:2000".parse().unwrap()));
+    assert_eq!(server_received, vec![ [ 3, 4, 5 ].into() ]);
+    assert_eq!(client_received, vec![ [ 0, 1, 2 ].into() ]);
+}
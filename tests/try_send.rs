@@ -0,0 +1,56 @@
+mod common;
+
+use std::time;
+
+use common::Sim;
+
+// Packets should be refused by try_send() while buffered data would take longer than the
+// configured bound to drain, and accepted again once it has drained.
+#[test]
+fn try_send_congested() {
+    let sim = Sim::with_delay(time::Duration::from_millis(20));
+
+    let mut server = sim.server(sim.server_config());
+
+    let mut client = sim.client("10.0.0.2:1000", uflow::client::Config {
+        endpoint_config: uflow::EndpointConfig {
+            max_send_delay_ms: 100,
+            .. Default::default()
+        },
+        .. sim.client_config()
+    });
+
+    let step = |server: &mut common::Server, client: &mut common::Client, steps: usize| {
+        let mut received = 0;
+
+        sim.run(steps, server, client, |_, event| {
+            if let uflow::server::Event::Receive(..) = event {
+                received += 1;
+            }
+        }, common::ignore_client);
+
+        received
+    };
+
+    step(&mut server, &mut client, 10);
+    assert!(client.is_active());
+
+    // An idle connection accepts packets
+    assert!(client.try_send([ 0; 1000 ].into(), 0, uflow::SendMode::Reliable).is_ok());
+
+    for _ in 0 .. 99 {
+        client.send([ 0; 1000 ].into(), 0, uflow::SendMode::Reliable);
+    }
+
+    let estimated_delay_ms = match client.try_send([ 0; 1000 ].into(), 0, uflow::SendMode::Reliable) {
+        Err(uflow::SendCongested { estimated_delay_ms }) => estimated_delay_ms,
+        Ok(_) => panic!("packet accepted by a congested connection"),
+    };
+
+    assert!(estimated_delay_ms > 100);
+
+    // Refused packets are not sent
+    assert_eq!(step(&mut server, &mut client, 300), 100);
+
+    assert!(client.try_send([ 0; 1000 ].into(), 0, uflow::SendMode::Reliable).is_ok());
+}
@@ -0,0 +1,107 @@
+
+use std::cell::Cell;
+use std::net;
+use std::rc::Rc;
+use std::thread;
+use std::time;
+
+#[test]
+fn unknown_address_count() {
+    let mut server = uflow::server::Server::bind("127.0.0.1:5551", Default::default()).unwrap();
+
+    let socket = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.send_to(&[ 0xFF; 32 ], "127.0.0.1:5551").unwrap();
+
+    thread::sleep(time::Duration::from_millis(50));
+
+    for event in server.step() {
+        panic!("unexpected event: {:?}", event);
+    }
+
+    let stats = server.unknown_address_stats();
+    assert_eq!(stats.frames, 1);
+    assert_eq!(stats.malformed_frames, 1);
+    assert_eq!(stats.resets, 0);
+}
+
+#[test]
+fn unknown_address_callback() {
+    let callback_count = Rc::new(Cell::new(0));
+    let callback_count_clone = Rc::clone(&callback_count);
+
+    let cfg = uflow::server::Config {
+        unknown_address_policy: uflow::server::UnknownAddressPolicy::Callback(Box::new(move |_, data| {
+            assert_eq!(data, &[ 0xFF; 32 ]);
+            callback_count_clone.set(callback_count_clone.get() + 1);
+        })),
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind("127.0.0.1:5552", cfg).unwrap();
+
+    let socket = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.send_to(&[ 0xFF; 32 ], "127.0.0.1:5552").unwrap();
+    socket.send_to(&[ 0xFF; 32 ], "127.0.0.1:5552").unwrap();
+
+    thread::sleep(time::Duration::from_millis(50));
+
+    for event in server.step() {
+        panic!("unexpected event: {:?}", event);
+    }
+
+    assert_eq!(callback_count.get(), 2);
+    assert_eq!(server.unknown_address_stats().frames, 2);
+}
+
+// A client which continues to send data to a server that has forgotten it should be disconnected
+// if the server replies with a reset.
+#[test]
+fn unknown_address_reset() {
+    let mut client = uflow::client::Client::connect("127.0.0.1:5553", Default::default()).unwrap();
+
+    {
+        let mut server = uflow::server::Server::bind("127.0.0.1:5553", Default::default()).unwrap();
+
+        let end_time = time::Instant::now() + time::Duration::from_millis(4000);
+
+        while !client.is_active() && time::Instant::now() < end_time {
+            for _ in server.step() {}
+            for _ in client.step() {}
+            thread::sleep(time::Duration::from_millis(50));
+        }
+
+        assert!(client.is_active());
+    }
+
+    let cfg = uflow::server::Config {
+        unknown_address_policy: uflow::server::UnknownAddressPolicy::Reset,
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind("127.0.0.1:5553", cfg).unwrap();
+
+    client.send([ 0; 64 ].into(), 0, uflow::SendMode::Reliable);
+
+    let end_time = time::Instant::now() + time::Duration::from_millis(4000);
+    let mut disconnect_seen = false;
+
+    while !disconnect_seen && time::Instant::now() < end_time {
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Disconnect => disconnect_seen = true,
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        client.flush();
+
+        thread::sleep(time::Duration::from_millis(50));
+
+        for event in server.step() {
+            panic!("unexpected event: {:?}", event);
+        }
+    }
+
+    assert!(disconnect_seen);
+    assert!(server.unknown_address_stats().resets >= 1);
+}
@@ -0,0 +1,86 @@
+use std::thread;
+use std::time;
+
+// Connections should use the variant selected for them, and report its tag in their statistics.
+#[test]
+fn variant_selection() {
+    let address = "127.0.0.1:5731";
+
+    let endpoint_config = uflow::EndpointConfig {
+        receiver_health: false,
+        .. Default::default()
+    };
+
+    let mut selection_count = 0;
+    let variant_config = endpoint_config.clone();
+
+    let server_cfg = uflow::server::Config {
+        endpoint_config,
+        variant_selector: Some(Box::new(move |_| {
+            selection_count += 1;
+            match selection_count {
+                // Enable a negotiated feature which is disabled by the server's configuration
+                1 => Some(uflow::Variant { receiver_health: true, .. uflow::Variant::new(1, &variant_config) }),
+                2 => Some(uflow::Variant::new(2, &variant_config)),
+                _ => None,
+            }
+        })),
+        .. Default::default()
+    };
+
+    let client_cfg = || uflow::client::Config {
+        endpoint_config: uflow::EndpointConfig {
+            receiver_health: true,
+            .. Default::default()
+        },
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind(address, server_cfg).unwrap();
+
+    // Connect clients one at a time so that their selection order is known
+    let mut clients = Vec::new();
+
+    for _ in 0 .. 3 {
+        clients.push(uflow::client::Client::connect(address, client_cfg()).unwrap());
+
+        let end_time = time::Instant::now() + time::Duration::from_millis(300);
+
+        while time::Instant::now() < end_time {
+            server.step().for_each(drop);
+
+            for client in clients.iter_mut() {
+                for event in client.step() {
+                    if let uflow::client::Event::Connect = event {
+                        client.send([ 0 ].into(), 0, uflow::SendMode::Reliable);
+                    }
+                }
+            }
+
+            server.flush();
+
+            for client in clients.iter_mut() {
+                client.flush();
+            }
+
+            thread::sleep(time::Duration::from_millis(20));
+        }
+    }
+
+    let tags = clients.iter().map(|client| {
+        let remote_client = server.clients().map(|client_rc| client_rc.borrow())
+                                  .find(|remote_client| remote_client.address() == client.local_address()).unwrap();
+        assert_eq!(remote_client.link_stats().variant, remote_client.variant());
+        remote_client.variant()
+    }).collect::<Vec<_>>();
+
+    assert_eq!(tags, vec![ Some(1), Some(2), None ]);
+
+    // Only the first variant enables receiver health reports
+    let health = clients.iter().map(|client| client.link_stats().remote_health.is_some()).collect::<Vec<_>>();
+
+    assert_eq!(health, vec![ true, false, false ]);
+
+    // Clients are unaware of the server's variants
+    assert!(clients.iter().all(|client| client.link_stats().variant.is_none()));
+}
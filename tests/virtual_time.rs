@@ -0,0 +1,79 @@
+use std::sync::Arc;
+use std::time;
+
+static STEP_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+// Keepalives should sustain an idle connection indefinitely, and a silent server should time out,
+// without waiting in real time.
+#[test]
+fn virtual_keepalive_timeout() {
+    let clock = uflow::VirtualClock::new();
+
+    let server_cfg = uflow::server::Config {
+        clock: Arc::new(clock.clone()),
+        .. Default::default()
+    };
+
+    let client_cfg = uflow::client::Config {
+        clock: Arc::new(clock.clone()),
+        .. Default::default()
+    };
+
+    let mut server = uflow::server::Server::bind("127.0.0.1:5631", server_cfg).unwrap();
+    let mut client = uflow::client::Client::connect("127.0.0.1:5631", client_cfg).unwrap();
+
+    let begin = time::Instant::now();
+
+    // Remain connected for 1000 simulated seconds
+    for _ in 0 .. 10000 {
+        for event in server.step() {
+            match event {
+                uflow::server::Event::Connect(_) => (),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        for event in client.step() {
+            match event {
+                uflow::client::Event::Connect => (),
+                other => panic!("unexpected event: {:?}", other),
+            }
+        }
+
+        server.flush();
+        client.flush();
+
+        clock.advance(STEP_INTERVAL);
+    }
+
+    assert!(client.is_active());
+    assert_eq!(server.clients().filter(|client| client.borrow().is_active()).count(), 1);
+
+    // Stop servicing the server; the client should time out once the active timeout has elapsed
+    // since the last keepalive was received
+    let active_timeout_ms = uflow::EndpointConfig::default().active_timeout_ms;
+    let keepalive_interval_ms = uflow::EndpointConfig::default().keepalive_interval_ms;
+    let mut elapsed_ms = 0;
+
+    loop {
+        clock.advance(STEP_INTERVAL);
+        elapsed_ms += STEP_INTERVAL.as_millis() as u64;
+
+        let events = client.step().collect::<Vec<_>>();
+        client.flush();
+
+        if !events.is_empty() {
+            match events.as_slice() {
+                [ uflow::client::Event::Error(uflow::client::ErrorType::Timeout) ] => break,
+                other => panic!("unexpected events: {:?}", other),
+            }
+        }
+
+        assert!(elapsed_ms <= active_timeout_ms, "client did not time out");
+    }
+
+    assert!(elapsed_ms >= active_timeout_ms - keepalive_interval_ms);
+
+    // The above should have taken far less time than was simulated
+    assert!(begin.elapsed() < time::Duration::from_secs(60));
+}